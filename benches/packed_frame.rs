@@ -0,0 +1,116 @@
+//! The previous-frame cache, run-length encoded against kept whole: how
+//! long working out what changed takes, and how much memory each takes.
+//! The frames are the screens in `tests/golden`.
+//!
+//! ```text
+//! cargo bench --bench packed_frame
+//! ```
+
+// Only the packing is needed, not the rest of the daemon, and its tests
+// are left out of benches.
+#![allow(dead_code)]
+#[allow(unused_imports)]
+#[path = "../src/rle.rs"]
+mod rle;
+
+use std::path::Path;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use rle::Packed;
+
+const WIDTH: usize = 212;
+const STRIDE: usize = WIDTH.div_ceil(8);
+
+/// The black and red planes of a golden, as `screens::ascii` writes them,
+/// and the shade plane, which goldens don't show.
+fn golden(name: &str) -> Vec<Vec<u8>> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("tests/golden/{name}.txt"));
+    let text = std::fs::read_to_string(&path).unwrap();
+    let rows: Vec<&str> = text.lines().collect();
+    let mut planes = vec![vec![0u8; STRIDE * rows.len()]; 3];
+    for (y, row) in rows.iter().enumerate() {
+        for (x, pixel) in row.chars().enumerate() {
+            let plane = match pixel {
+                '#' => 0,
+                'r' => 1,
+                _ => continue,
+            };
+            planes[plane][y * STRIDE + x / 8] |= 0x80 >> (x % 8);
+        }
+    }
+    planes
+}
+
+fn refs(planes: &[Vec<u8>]) -> Vec<&[u8]> {
+    planes.iter().map(Vec::as_slice).collect()
+}
+
+/// What the display did before the cache was packed: compare a whole copy
+/// a byte at a time.
+fn whole(old: &[Vec<u8>], new: &[Vec<u8>]) -> Option<(usize, usize, usize, usize)> {
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for (a, b) in old.iter().zip(new) {
+        for (i, _) in a.iter().zip(b).enumerate().filter(|(_, (a, b))| a != b) {
+            let (col, row) = (i % STRIDE, i / STRIDE);
+            bounds = Some(match bounds {
+                None => (col, row, col, row),
+                Some((c0, r0, c1, r1)) => (c0.min(col), r0.min(row), c1.max(col), r1.max(row)),
+            });
+        }
+    }
+    bounds
+}
+
+const SCREENS: [&str; 8] = [
+    "heatmap",
+    "heatmap-large",
+    "layout-bars",
+    "layout-care",
+    "layout-every-kind",
+    "layout-host",
+    "mirror-x",
+    "rotation-180",
+];
+
+fn benches(c: &mut Criterion) {
+    let (mut packed, mut kept) = (0, 0);
+    for name in SCREENS {
+        let planes = golden(name);
+        packed += Packed::new(&refs(&planes), STRIDE).footprint();
+        kept += planes.iter().map(Vec::len).sum::<usize>();
+    }
+    let saved = 1.0 - packed as f64 / kept as f64;
+    println!(
+        "cached frames: {packed} bytes packed, {kept} whole, {:.0}% less",
+        saved * 100.0
+    );
+    assert!(saved > 0.7);
+
+    // A day filled in on the heatmap, and a frame that hasn't changed.
+    let (old, new) = (golden("heatmap-empty"), golden("heatmap"));
+    let cache = Packed::new(&refs(&old), STRIDE);
+    assert_eq!(cache.dirty(&refs(&new)), whole(&old, &new));
+    let mut group = c.benchmark_group("changed");
+    group.bench_function("packed", |b| {
+        b.iter(|| black_box(&cache).dirty(black_box(&refs(&new))))
+    });
+    group.bench_function("whole", |b| {
+        b.iter(|| whole(black_box(&old), black_box(&new)))
+    });
+    group.finish();
+    let mut group = c.benchmark_group("unchanged");
+    group.bench_function("packed", |b| {
+        b.iter(|| black_box(&cache).dirty(black_box(&refs(&old))))
+    });
+    group.bench_function("whole", |b| {
+        b.iter(|| whole(black_box(&old), black_box(&old)))
+    });
+    group.finish();
+    c.bench_function("pack", |b| {
+        b.iter(|| Packed::new(black_box(&refs(&new)), STRIDE))
+    });
+}
+
+criterion_group!(packed_frame, benches);
+criterion_main!(packed_frame);
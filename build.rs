@@ -0,0 +1,81 @@
+//! Embeds build metadata for the about screen and `--version --verbose`.
+//!
+//! Everything here degrades to "unknown" rather than failing, since the
+//! crate may be built from a tarball without git, or by an unusual rustc.
+
+use std::env;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let commit = run("git", &["rev-parse", "--short=10", "HEAD"]);
+    let dirty = match run("git", &["status", "--porcelain", "--untracked-files=no"]) {
+        Some(status) => {
+            if status.is_empty() {
+                "false"
+            } else {
+                "true"
+            }
+        }
+        None => "unknown",
+    };
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let rustc_version = run(&rustc, &["--version"]);
+
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_owned))
+        .map(|name| name.to_lowercase().replace('_', "-"))
+        .collect();
+    features.sort();
+
+    set("INKY_GIT_COMMIT", commit.as_deref().unwrap_or("unknown"));
+    set("INKY_GIT_DIRTY", dirty);
+    set("INKY_BUILD_DATE", &build_date());
+    set(
+        "INKY_RUSTC_VERSION",
+        rustc_version.as_deref().unwrap_or("unknown"),
+    );
+    set("INKY_FEATURES", &features.join(","));
+}
+
+fn set(key: &str, value: &str) {
+    println!("cargo:rustc-env={key}={value}");
+}
+
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_owned())
+}
+
+/// UTC build date as YYYY-MM-DD, honouring SOURCE_DATE_EPOCH for
+/// reproducible builds.
+fn build_date() -> String {
+    let secs = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .or_else(|| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+            Some(now.as_secs() as i64)
+        });
+    let Some(secs) = secs else {
+        return "unknown".into();
+    };
+    // Days since the epoch to a civil date (Howard Hinnant's algorithm).
+    let z = secs.div_euclid(86_400) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
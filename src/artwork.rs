@@ -0,0 +1,560 @@
+//! Artwork packs: the pet drawn from images on disk instead of the
+//! built-in sprites, so it can be reskinned without a rebuild.
+//!
+//! A pack is a directory with a `pack.toml` naming the pack, giving the
+//! pet's size and mapping sprite ids to PNG or 1-bit BMP files in it. Dark
+//! pixels are drawn, light and transparent ones aren't. A sprite the pack
+//! leaves out is the built-in one.
+//!
+//! With `greyscale = true`, PNGs are also read in four levels: paper,
+//! light grey, dark grey and ink. The panel still gets them in black and
+//! white, cut at the same place as without, and previews can show the
+//! greys, see [`crate::display::Frame`]. Everything wrong with a pack is
+//! reported together, so an artist can fix it all in one go.
+//!
+//! The pack in use lives in a static, like [`crate::logbuf`]'s lines, so
+//! the screens, the card and the particles can all get at it.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{bail, ensure, Context as _, Result};
+use embedded_graphics::prelude::*;
+use serde::Deserialize;
+
+use crate::sprite::{
+    Sprite, EGG, EGG_FLAMES, EGG_SPOTS, PET_HAPPY, PET_IDLE, PET_LOST, PET_MISCHIEF, PET_NOBLE,
+    PET_WILD, PET_WORRIED,
+};
+
+pub const MANIFEST: &str = "pack.toml";
+/// The biggest pet that still leaves it room to walk about, and a speech
+/// bubble room above it.
+const PET_LIMIT: Size = Size::new(48, 48);
+/// The biggest egg that fits the picker twice over, large text included.
+const EGG_LIMIT: Size = Size::new(32, 24);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpriteId {
+    Idle,
+    Happy,
+    Worried,
+    Lost,
+    Mischief,
+    Noble,
+    Wild,
+    Egg,
+    /// Drawn over the egg for the speckled one.
+    EggSpots,
+    /// Drawn over the egg for the ember one.
+    EggFlames,
+}
+
+impl SpriteId {
+    pub const ALL: [SpriteId; 10] = [
+        SpriteId::Idle,
+        SpriteId::Happy,
+        SpriteId::Worried,
+        SpriteId::Lost,
+        SpriteId::Mischief,
+        SpriteId::Noble,
+        SpriteId::Wild,
+        SpriteId::Egg,
+        SpriteId::EggSpots,
+        SpriteId::EggFlames,
+    ];
+
+    /// The id's name in `pack.toml`.
+    pub fn key(self) -> &'static str {
+        match self {
+            SpriteId::Idle => "idle",
+            SpriteId::Happy => "happy",
+            SpriteId::Worried => "worried",
+            SpriteId::Lost => "lost",
+            SpriteId::Mischief => "mischief",
+            SpriteId::Noble => "noble",
+            SpriteId::Wild => "wild",
+            SpriteId::Egg => "egg",
+            SpriteId::EggSpots => "egg_spots",
+            SpriteId::EggFlames => "egg_flames",
+        }
+    }
+
+    fn parse(key: &str) -> Option<SpriteId> {
+        SpriteId::ALL.into_iter().find(|id| id.key() == key)
+    }
+
+    fn builtin(self) -> &'static Sprite {
+        match self {
+            SpriteId::Idle => &PET_IDLE,
+            SpriteId::Happy => &PET_HAPPY,
+            SpriteId::Worried => &PET_WORRIED,
+            SpriteId::Lost => &PET_LOST,
+            SpriteId::Mischief => &PET_MISCHIEF,
+            SpriteId::Noble => &PET_NOBLE,
+            SpriteId::Wild => &PET_WILD,
+            SpriteId::Egg => &EGG,
+            SpriteId::EggSpots => &EGG_SPOTS,
+            SpriteId::EggFlames => &EGG_FLAMES,
+        }
+    }
+
+    fn is_egg(self) -> bool {
+        matches!(
+            self,
+            SpriteId::Egg | SpriteId::EggSpots | SpriteId::EggFlames
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    name: String,
+    /// The pet's size. Every pet sprite is drawn on a canvas this big,
+    /// standing at the bottom in the middle, so the pet doesn't shift
+    /// about as it changes face.
+    width: u32,
+    height: u32,
+    /// Whether PNGs are drawn in grey, for previews.
+    #[serde(default)]
+    greyscale: bool,
+    /// Image file for each sprite id, relative to the pack.
+    #[serde(default)]
+    sprites: BTreeMap<String, PathBuf>,
+}
+
+pub struct Pack {
+    pub name: String,
+    /// How many sprites came from the pack rather than built in.
+    pub drawn: usize,
+    /// How many of those are in grey.
+    pub grey: usize,
+    /// Indexed by [`SpriteId`].
+    sprites: Vec<Sprite>,
+}
+
+impl Pack {
+    /// Reads the pack in `dir`, failing with every problem it has.
+    pub fn load(dir: &Path) -> Result<Pack> {
+        let path = dir.join(MANIFEST);
+        let text =
+            fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        let manifest: Manifest =
+            toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+
+        let mut problems = Vec::new();
+        let pet = Size::new(manifest.width, manifest.height);
+        if pet.width == 0 || pet.height == 0 || !fits(pet, PET_LIMIT) {
+            problems.push(format!(
+                "the pet is {}, it has to be from 1x1 to {}",
+                show(pet),
+                show(PET_LIMIT)
+            ));
+        }
+        let mut images: BTreeMap<usize, Sprite> = BTreeMap::new();
+        for (key, file) in &manifest.sprites {
+            let Some(id) = SpriteId::parse(key) else {
+                let known: Vec<_> = SpriteId::ALL.iter().map(|id| id.key()).collect();
+                problems.push(format!(
+                    "{key}: no such sprite, expected one of {}",
+                    known.join(", ")
+                ));
+                continue;
+            };
+            let image = match read_image(&dir.join(file), manifest.greyscale) {
+                Ok(image) => image,
+                Err(err) => {
+                    problems.push(format!("{key}: {}: {err:#}", file.display()));
+                    continue;
+                }
+            };
+            let (limit, what) = match id.is_egg() {
+                true => (EGG_LIMIT, "eggs"),
+                false => (pet, "the pet"),
+            };
+            if !fits(image.size(), limit) {
+                problems.push(format!(
+                    "{key}: {} is {}, bigger than {} for {what}",
+                    file.display(),
+                    show(image.size()),
+                    show(limit)
+                ));
+            }
+            images.insert(id as usize, image);
+        }
+        if !problems.is_empty() {
+            bail!(
+                "{} has {} problem(s):\n  {}",
+                dir.display(),
+                problems.len(),
+                problems.join("\n  ")
+            );
+        }
+
+        // The egg and what's drawn over it have to line up, so they share
+        // a canvas as big as the biggest of them.
+        let egg = SpriteId::ALL
+            .iter()
+            .filter(|id| id.is_egg())
+            .map(|&id| images.get(&(id as usize)).unwrap_or(id.builtin()).size())
+            .fold(Size::zero(), |all, size| all.component_max(size));
+        let sprites = SpriteId::ALL
+            .iter()
+            .map(|&id| {
+                let canvas = if id.is_egg() { egg } else { pet };
+                images
+                    .get(&(id as usize))
+                    .unwrap_or(id.builtin())
+                    .fitted(canvas)
+            })
+            .collect();
+        Ok(Pack {
+            name: manifest.name,
+            drawn: images.len(),
+            grey: images.values().filter(|image| image.is_grey()).count(),
+            sprites,
+        })
+    }
+}
+
+fn fits(size: Size, limit: Size) -> bool {
+    size.width <= limit.width && size.height <= limit.height
+}
+
+fn show(size: Size) -> String {
+    format!("{}x{}", size.width, size.height)
+}
+
+static PACK: OnceLock<Pack> = OnceLock::new();
+
+/// Draws the pet from `pack` from now on. Only the first pack counts.
+pub fn install(pack: Pack) {
+    PACK.set(pack).ok();
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The pack in use for one test's thread, see [`install_for_test`].
+    static TEST_PACK: std::cell::Cell<Option<&'static Pack>> = const { std::cell::Cell::new(None) };
+}
+
+/// Draws the pet from `pack` on this thread only, until the guard is
+/// dropped, so a test can render with it while the others keep the
+/// built-in sprites.
+#[cfg(test)]
+pub fn install_for_test(pack: Pack) -> TestPack {
+    TEST_PACK.set(Some(Box::leak(Box::new(pack))));
+    TestPack
+}
+
+#[cfg(test)]
+pub struct TestPack;
+
+#[cfg(test)]
+impl Drop for TestPack {
+    fn drop(&mut self) {
+        TEST_PACK.set(None);
+    }
+}
+
+/// The sprite for `id`: the installed pack's, else the built-in one.
+pub fn get(id: SpriteId) -> &'static Sprite {
+    #[cfg(test)]
+    if let Some(pack) = TEST_PACK.get() {
+        return &pack.sprites[id as usize];
+    }
+    match PACK.get() {
+        Some(pack) => &pack.sprites[id as usize],
+        None => id.builtin(),
+    }
+}
+
+/// The size all pet sprites share.
+pub fn footprint() -> Size {
+    get(SpriteId::Idle).size()
+}
+
+fn read_image(path: &Path, greyscale: bool) -> Result<Sprite> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("png") => read_png(path, greyscale),
+        Some("bmp") => read_bmp(&fs::read(path)?),
+        _ => bail!("not a .png or .bmp file"),
+    }
+}
+
+/// Whether a pixel counts as drawn: dark enough and opaque enough.
+fn is_ink(red: u8, green: u8, blue: u8, alpha: u8) -> bool {
+    level(red, green, blue, alpha) >= 2
+}
+
+/// How dark a pixel is, from 0 for paper or transparent to 3 for ink.
+/// Drawn is 2 and up, i.e. darker than middle grey.
+fn level(red: u8, green: u8, blue: u8, alpha: u8) -> u8 {
+    let luma = (299 * red as u32 + 587 * green as u32 + 114 * blue as u32) / 1000;
+    match alpha >= 128 {
+        true => ((255 - luma) / 64) as u8,
+        false => 0,
+    }
+}
+
+fn read_png(path: &Path, greyscale: bool) -> Result<Sprite> {
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(fs::File::open(path)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut data = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let info = reader.next_frame(&mut data)?;
+    let channels = info.color_type.samples();
+    let at = |x: u32, y: u32| {
+        let start = y as usize * info.line_size + x as usize * channels;
+        let pixel = &data[start..start + channels];
+        match *pixel {
+            [gray] => level(gray, gray, gray, 255),
+            [gray, alpha] => level(gray, gray, gray, alpha),
+            [red, green, blue] => level(red, green, blue, 255),
+            [red, green, blue, alpha] => level(red, green, blue, alpha),
+            _ => 0,
+        }
+    };
+    let size = Size::new(info.width, info.height);
+    Ok(match greyscale {
+        true => Sprite::from_levels(size, at),
+        false => Sprite::from_fn(size, |x, y| at(x, y) >= 2),
+    })
+}
+
+/// Reads an uncompressed 1-bit BMP, the kind most editors write for
+/// "monochrome bitmap".
+fn read_bmp(bytes: &[u8]) -> Result<Sprite> {
+    let u16_at = |at: usize| {
+        bytes
+            .get(at..at + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+    };
+    let u32_at = |at: usize| {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    ensure!(bytes.starts_with(b"BM"), "not a BMP file");
+    let (Some(offset), Some(header), Some(width), Some(height), Some(depth), Some(compression)) = (
+        u32_at(10),
+        u32_at(14),
+        u32_at(18),
+        u32_at(22),
+        u16_at(28),
+        u32_at(30),
+    ) else {
+        bail!("BMP header cut short");
+    };
+    ensure!(
+        depth == 1 && compression == 0,
+        "not an uncompressed 1-bit BMP"
+    );
+    let (width, height) = (width as i32, height as i32);
+    // Rows go bottom up, unless the height is negative.
+    let top_down = height < 0;
+    let height = height.unsigned_abs();
+    ensure!(width > 0 && height > 0, "BMP has no pixels");
+    let width = width as u32;
+    let palette = 14 + header as usize;
+    let ink = [0, 1].map(|index| {
+        let at = palette + index * 4;
+        bytes
+            .get(at..at + 3)
+            .is_some_and(|bgr| is_ink(bgr[2], bgr[1], bgr[0], 255))
+    });
+    let stride = width.div_ceil(32) as usize * 4;
+    ensure!(
+        bytes.len() >= offset as usize + stride * height as usize,
+        "BMP pixels cut short"
+    );
+    Ok(Sprite::from_fn(Size::new(width, height), |x, y| {
+        let row = if top_down { y } else { height - 1 - y };
+        let byte = bytes[offset as usize + row as usize * stride + x as usize / 8];
+        ink[(byte >> (7 - x % 8) & 1) as usize]
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::{Color, Frame};
+    use crate::sprite::Flip;
+
+    /// A 16x16 grey-and-alpha PNG running through every grey across and
+    /// from see-through to opaque down.
+    fn gradient() -> Vec<u8> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 16, 16);
+        encoder.set_color(png::ColorType::GrayscaleAlpha);
+        let data: Vec<u8> = (0..16u8)
+            .flat_map(|y| (0..16u8).flat_map(move |x| [x * 17, y * 17]))
+            .collect();
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(&data)
+            .unwrap();
+        png
+    }
+
+    /// A pack in `dir` with the gradient as the idle pet.
+    fn pack(dir: &Path, greyscale: bool) -> Pack {
+        fs::write(dir.join("idle.png"), gradient()).unwrap();
+        let manifest = format!(
+            "name = \"test\"\nwidth = 16\nheight = 16\ngreyscale = {greyscale}\n\
+             [sprites]\nidle = \"idle.png\"\n"
+        );
+        fs::write(dir.join(MANIFEST), manifest).unwrap();
+        Pack::load(dir).unwrap()
+    }
+
+    fn idle(pack: &Pack) -> &Sprite {
+        &pack.sprites[SpriteId::Idle as usize]
+    }
+
+    #[test]
+    fn grey_levels_cut_where_ink_always_did() {
+        for luma in 0..=255 {
+            for alpha in [0, 127, 128, 255] {
+                let before = alpha >= 128 && luma < 128;
+                assert_eq!(is_ink(luma, luma, luma, alpha), before, "{luma} {alpha}");
+                let level = level(luma, luma, luma, alpha);
+                assert!(level <= 3);
+                assert_eq!(level >= 2, before, "{luma} {alpha}");
+            }
+        }
+        assert_eq!(level(0, 0, 0, 255), 3);
+        assert_eq!(level(255, 255, 255, 255), 0);
+        assert_eq!(level(0, 0, 0, 0), 0);
+    }
+
+    #[test]
+    fn grey_packs_draw_the_same_on_the_panel() {
+        let (flat, grey) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (flat, grey) = (pack(flat.path(), false), pack(grey.path(), true));
+        assert_eq!((flat.drawn, flat.grey), (1, 0));
+        assert_eq!((grey.drawn, grey.grey), (1, 1));
+        assert!(!idle(&flat).is_grey());
+        assert!(idle(&grey).is_grey());
+        // Built in either way, so flat.
+        assert!(!grey.sprites[SpriteId::Egg as usize].is_grey());
+
+        for flip in [Flip::None, Flip::Horizontal] {
+            let mut frames = [Frame::new(40, 30), Frame::new(40, 30)];
+            for (frame, pack) in frames.iter_mut().zip([&flat, &grey]) {
+                idle(pack).draw(frame, Point::new(3, 5), Color::Black, flip);
+            }
+            assert!(frames[0] == frames[1]);
+            // Scaled the same, too, as the egg is.
+            let mut frames = [Frame::new(40, 40), Frame::new(40, 40)];
+            for (frame, pack) in frames.iter_mut().zip([&flat, &grey]) {
+                idle(pack).draw_scaled(frame, Point::new(2, 2), Color::Red, 2);
+            }
+            assert!(frames[0] == frames[1]);
+        }
+    }
+
+    #[test]
+    fn shading_only_touches_the_previews() {
+        let dir = tempfile::tempdir().unwrap();
+        let grey = pack(dir.path(), true);
+        let sprite = idle(&grey);
+        let origin = Point::new(1, 1);
+        let mut drawn = Frame::new(20, 20);
+        sprite.draw(&mut drawn, origin, Color::Black, Flip::None);
+        let mut shaded = drawn.clone();
+        sprite.shade(&mut shaded, origin, Flip::None, 1);
+
+        let mut greys = 0;
+        for y in 0..20 {
+            for x in 0..20 {
+                assert_eq!(shaded.pixel(x, y), drawn.pixel(x, y));
+                greys += shaded.is_shaded(x, y) as usize;
+            }
+        }
+        // Over the opaque rows, the columns of light and dark grey.
+        assert!(greys > 0);
+        assert!(shaded.is_shaded(1 + 6, 1 + 15));
+        assert!(shaded.is_shaded(1 + 9, 1 + 15));
+        assert!(!shaded.is_shaded(1 + 15, 1 + 15));
+        assert!(!shaded.is_shaded(1, 1 + 15));
+
+        // A flat sprite has nothing to shade.
+        let dir = tempfile::tempdir().unwrap();
+        let flat = pack(dir.path(), false);
+        let mut frame = drawn.clone();
+        idle(&flat).shade(&mut frame, origin, Flip::None, 1);
+        assert!(frame == drawn);
+    }
+
+    #[test]
+    fn the_fixture_pack_draws_from_png_and_bmp() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/pack");
+        let pack = Pack::load(&dir).unwrap();
+        assert_eq!((pack.name.as_str(), pack.drawn, pack.grey), ("blob", 3, 0));
+        let sprite = |id: SpriteId| &pack.sprites[id as usize];
+        // Every face on the pet's canvas, built in or not.
+        for id in SpriteId::ALL.into_iter().filter(|id| !id.is_egg()) {
+            assert_eq!(sprite(id).size(), Size::new(20, 18), "{}", id.key());
+        }
+        // The egg's canvas takes in the built-in spots and flames.
+        let egg = sprite(SpriteId::Egg).size();
+        assert!(egg.width >= 16 && egg.height >= 21, "{egg:?}");
+        assert_eq!(sprite(SpriteId::EggSpots).size(), egg);
+
+        // Eyes open and the middle empty, from the PNG and the BMP alike.
+        for id in [SpriteId::Idle, SpriteId::Happy] {
+            let mut frame = Frame::new(20, 18);
+            sprite(id).draw(&mut frame, Point::zero(), Color::Black, Flip::None);
+            assert_eq!(frame.pixel(6, 6), Color::Black, "{}", id.key());
+            assert_eq!(frame.pixel(9, 9), Color::White, "{}", id.key());
+        }
+    }
+
+    #[test]
+    fn every_problem_with_a_pack_is_reported_together() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("idle.png"), gradient()).unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+        let mut big = Vec::new();
+        let mut encoder = png::Encoder::new(&mut big, 40, 30);
+        encoder.set_color(png::ColorType::Grayscale);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0; 40 * 30]).unwrap();
+        writer.finish().unwrap();
+        fs::write(dir.path().join("big.png"), big).unwrap();
+        fs::write(
+            dir.path().join(MANIFEST),
+            "name = \"broken\"\nwidth = 60\nheight = 20\n[sprites]\n\
+             idle = \"idle.png\"\nhappy = \"happy.png\"\ngrin = \"idle.png\"\n\
+             worried = \"notes.txt\"\negg = \"big.png\"\n",
+        )
+        .unwrap();
+
+        let err = Pack::load(dir.path()).err().unwrap().to_string();
+        let mut lines = err.lines();
+        assert_eq!(
+            lines.next(),
+            Some(format!("{} has 5 problem(s):", dir.path().display()).as_str())
+        );
+        let problems: Vec<_> = lines.map(str::trim).collect();
+        assert_eq!(problems.len(), 5, "{err}");
+        for (problem, start) in problems.iter().zip([
+            "the pet is 60x20, it has to be from 1x1 to 48x48",
+            "egg: big.png is 40x30, bigger than 32x24 for eggs",
+            "grin: no such sprite, expected one of idle, happy,",
+            "happy: happy.png: ",
+            "worried: notes.txt: not a .png or .bmp file",
+        ]) {
+            assert!(problem.starts_with(start), "{problem}");
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Sun and moon positions, computed locally so the footer works offline.
+//!
+//! Sunrise and sunset follow the NOAA solar calculator (itself based on
+//! Meeus, "Astronomical Algorithms"), which is good to about a minute for
+//! latitudes within the polar circles.
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+
+/// Zenith angle of the sun's centre at the moment of sunrise or sunset,
+/// accounting for atmospheric refraction and the solar disc's radius.
+pub const SUNRISE_ZENITH: f64 = 90.833;
+/// The same at civil dawn and dusk, with the sun six degrees down: about
+/// when it's too dark to read outside.
+pub const CIVIL_ZENITH: f64 = 96.0;
+
+/// Mean length of a lunar cycle in days.
+const SYNODIC_MONTH: f64 = 29.530_588_853;
+/// Julian day of a known new moon (2000-01-06 18:14 UTC).
+const REFERENCE_NEW_MOON: f64 = 2_451_550.259_72;
+
+/// Whether the sun crosses a given zenith angle on a particular day.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Daylight {
+    Normal {
+        rise: DateTime<Utc>,
+        set: DateTime<Utc>,
+    },
+    /// The sun stays above the horizon all day.
+    PolarDay,
+    /// The sun stays below the horizon all day.
+    PolarNight,
+}
+
+impl Daylight {
+    pub fn rise(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Daylight::Normal { rise, .. } => Some(*rise),
+            _ => None,
+        }
+    }
+
+    pub fn set(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Daylight::Normal { set, .. } => Some(*set),
+            _ => None,
+        }
+    }
+}
+
+/// Sunrise and sunset for `date` at the given position.
+///
+/// Latitude is positive north, longitude positive east, both in degrees.
+pub fn sun_times(date: NaiveDate, latitude: f64, longitude: f64) -> Daylight {
+    daylight(date, latitude, longitude, SUNRISE_ZENITH)
+}
+
+/// Times at which the sun's centre crosses `zenith` degrees on `date`.
+pub fn daylight(date: NaiveDate, latitude: f64, longitude: f64, zenith: f64) -> Daylight {
+    // Start from an estimate of local solar noon and refine each event once
+    // at its own time, since declination and the equation of time drift
+    // slightly over the day.
+    let midnight = Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN));
+    let noon = midnight + Duration::minutes((720.0 - 4.0 * longitude) as i64);
+    let event = |at| solar_event(midnight, at, latitude, longitude, zenith);
+    let first = match event(noon) {
+        Event::Crosses { rise, set } => (rise, set),
+        Event::AlwaysAbove => return Daylight::PolarDay,
+        Event::AlwaysBelow => return Daylight::PolarNight,
+    };
+    let rise = match event(first.0) {
+        Event::Crosses { rise, .. } => rise,
+        _ => first.0,
+    };
+    let set = match event(first.1) {
+        Event::Crosses { set, .. } => set,
+        _ => first.1,
+    };
+    Daylight::Normal { rise, set }
+}
+
+enum Event {
+    Crosses {
+        rise: DateTime<Utc>,
+        set: DateTime<Utc>,
+    },
+    AlwaysAbove,
+    AlwaysBelow,
+}
+
+/// Evaluates the solar position at `at` and derives the rise/set times of
+/// the day starting at `midnight` (UTC midnight of the requested date).
+fn solar_event(
+    midnight: DateTime<Utc>,
+    at: DateTime<Utc>,
+    latitude: f64,
+    longitude: f64,
+    zenith: f64,
+) -> Event {
+    let t = (julian_day(at) - 2_451_545.0) / 36_525.0;
+
+    let mean_long = (280.466_46 + t * (36_000.769_83 + t * 0.000_303_2)).rem_euclid(360.0);
+    let mean_anomaly = 357.529_11 + t * (35_999.050_29 - 0.000_153_7 * t);
+    let eccentricity = 0.016_708_634 - t * (0.000_042_037 + 0.000_000_126_7 * t);
+
+    let m = mean_anomaly.to_radians();
+    let center = m.sin() * (1.914_602 - t * (0.004_817 + 0.000_014 * t))
+        + (2.0 * m).sin() * (0.019_993 - 0.000_101 * t)
+        + (3.0 * m).sin() * 0.000_289;
+    let true_long = mean_long + center;
+    let omega = (125.04 - 1_934.136 * t).to_radians();
+    let apparent_long = true_long - 0.005_69 - 0.004_78 * omega.sin();
+
+    let mean_obliquity =
+        23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.000_59 - t * 0.001_813))) / 60.0) / 60.0;
+    let obliquity = (mean_obliquity + 0.002_56 * omega.cos()).to_radians();
+    let declination = (obliquity.sin() * apparent_long.to_radians().sin()).asin();
+
+    let y = (obliquity / 2.0).tan().powi(2);
+    let l0 = mean_long.to_radians();
+    let eq_time = 4.0
+        * (y * (2.0 * l0).sin() - 2.0 * eccentricity * m.sin()
+            + 4.0 * eccentricity * y * m.sin() * (2.0 * l0).cos()
+            - 0.5 * y * y * (4.0 * l0).sin()
+            - 1.25 * eccentricity * eccentricity * (2.0 * m).sin())
+        .to_degrees();
+
+    let lat = latitude.to_radians();
+    let cos_hour_angle =
+        zenith.to_radians().cos() / (lat.cos() * declination.cos()) - lat.tan() * declination.tan();
+    if cos_hour_angle > 1.0 {
+        return Event::AlwaysBelow;
+    }
+    if cos_hour_angle < -1.0 {
+        return Event::AlwaysAbove;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+
+    let noon_minutes = 720.0 - 4.0 * longitude - eq_time;
+    let at_minutes = |minutes: f64| midnight + Duration::seconds((minutes * 60.0).round() as i64);
+    Event::Crosses {
+        rise: at_minutes(noon_minutes - 4.0 * hour_angle),
+        set: at_minutes(noon_minutes + 4.0 * hour_angle),
+    }
+}
+
+fn julian_day(at: DateTime<Utc>) -> f64 {
+    at.timestamp() as f64 / 86_400.0 + 2_440_587.5
+}
+
+/// Position in the lunar cycle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MoonPhase {
+    /// Fraction of the synodic month elapsed since new moon, in `[0, 1)`.
+    /// 0.25 is first quarter, 0.5 full moon, 0.75 last quarter.
+    pub age: f64,
+}
+
+impl MoonPhase {
+    pub fn at(at: DateTime<Utc>) -> Self {
+        let age = ((julian_day(at) - REFERENCE_NEW_MOON) / SYNODIC_MONTH).rem_euclid(1.0);
+        MoonPhase { age }
+    }
+
+    /// True while the lit part is growing, i.e. lit on the right as seen
+    /// from the northern hemisphere.
+    pub fn waxing(&self) -> bool {
+        self.age < 0.5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().to_utc()
+    }
+
+    /// Within two minutes of `expected`, the published tables' rounding
+    /// plus the algorithm's own error.
+    fn assert_near(actual: Option<DateTime<Utc>>, expected: &str) {
+        let actual = actual.expect("the sun rises and sets");
+        let off = (actual - utc(expected)).num_seconds().abs();
+        assert!(off <= 120, "{actual} is {off}s from {expected}");
+    }
+
+    #[test]
+    fn known_sunrises_and_sunsets() {
+        // Times from the NOAA solar calculator.
+        let cases = [
+            // Bucharest, both solstices.
+            (
+                (2024, 6, 21),
+                44.43,
+                26.10,
+                "2024-06-21T02:32:00Z",
+                "2024-06-21T18:02:00Z",
+            ),
+            (
+                (2024, 12, 21),
+                44.43,
+                26.10,
+                "2024-12-21T05:49:00Z",
+                "2024-12-21T14:39:00Z",
+            ),
+            // The equator at the March equinox.
+            (
+                (2024, 3, 20),
+                0.0,
+                0.0,
+                "2024-03-20T06:04:00Z",
+                "2024-03-20T18:11:00Z",
+            ),
+            // Sydney in winter, where sunrise is the evening before in UTC.
+            (
+                (2024, 6, 21),
+                -33.87,
+                151.21,
+                "2024-06-20T21:00:00Z",
+                "2024-06-21T06:54:00Z",
+            ),
+        ];
+        for ((y, m, d), latitude, longitude, rise, set) in cases {
+            let times = sun_times(date(y, m, d), latitude, longitude);
+            assert_near(times.rise(), rise);
+            assert_near(times.set(), set);
+        }
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        // Tromsø, inside the Arctic circle.
+        assert_eq!(
+            sun_times(date(2024, 6, 21), 69.65, 18.96),
+            Daylight::PolarDay
+        );
+        assert_eq!(
+            sun_times(date(2024, 12, 21), 69.65, 18.96),
+            Daylight::PolarNight
+        );
+        // McMurdo, the other way round.
+        assert_eq!(
+            sun_times(date(2024, 12, 21), -77.85, 166.67),
+            Daylight::PolarDay
+        );
+        assert_eq!(
+            sun_times(date(2024, 6, 21), -77.85, 166.67),
+            Daylight::PolarNight
+        );
+        let polar = sun_times(date(2024, 12, 21), 69.65, 18.96);
+        assert_eq!((polar.rise(), polar.set()), (None, None));
+    }
+
+    #[test]
+    fn civil_twilight_outlasts_the_sun() {
+        let sun = sun_times(date(2024, 3, 20), 44.43, 26.10);
+        let civil = daylight(date(2024, 3, 20), 44.43, 26.10, CIVIL_ZENITH);
+        assert!(civil.rise().unwrap() < sun.rise().unwrap());
+        assert!(civil.set().unwrap() > sun.set().unwrap());
+        // Tromsø in midwinter has no sunrise, but still gets civil dawn.
+        let dusk = daylight(date(2024, 12, 21), 69.65, 18.96, CIVIL_ZENITH);
+        assert!(matches!(dusk, Daylight::Normal { .. }));
+    }
+
+    #[test]
+    fn known_moon_phases() {
+        let age = |at| MoonPhase::at(utc(at)).age;
+        // New moon on 2024-01-11 11:57 UTC, full on 2024-01-25 17:54.
+        let new = age("2024-01-11T11:57:00Z");
+        assert!(!(0.02..=0.98).contains(&new), "new moon at {new}");
+        assert!((age("2024-01-25T17:54:00Z") - 0.5).abs() < 0.02);
+        assert!(MoonPhase::at(utc("2024-01-18T00:00:00Z")).waxing());
+        assert!(!MoonPhase::at(utc("2024-02-01T00:00:00Z")).waxing());
+    }
+}
@@ -0,0 +1,46 @@
+//! What this binary is and how it was built, as embedded by `build.rs`.
+
+use serde::Serialize;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub commit: &'static str,
+    /// "true", "false", or "unknown" outside a git checkout.
+    pub dirty: &'static str,
+    pub build_date: &'static str,
+    pub rustc: &'static str,
+    pub features: Vec<&'static str>,
+}
+
+impl BuildInfo {
+    pub fn get() -> Self {
+        BuildInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            commit: env!("INKY_GIT_COMMIT"),
+            dirty: env!("INKY_GIT_DIRTY"),
+            build_date: env!("INKY_BUILD_DATE"),
+            rustc: env!("INKY_RUSTC_VERSION"),
+            features: env!("INKY_FEATURES")
+                .split(',')
+                .filter(|f| !f.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Commit hash with a `-dirty` suffix when built from modified sources.
+    pub fn commit_label(&self) -> String {
+        match self.dirty {
+            "true" => format!("{}-dirty", self.commit),
+            _ => self.commit.to_string(),
+        }
+    }
+
+    pub fn features_label(&self) -> String {
+        if self.features.is_empty() {
+            "none".into()
+        } else {
+            self.features.join(", ")
+        }
+    }
+}
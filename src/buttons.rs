@@ -0,0 +1,241 @@
+//! Button input: turns raw presses into gestures and gestures into actions.
+//!
+//! Presses arrive on a channel so any source can feed them: `press a` and
+//! `hold a` on the control socket, or a source from [`crate::input`]. Three
+//! quick presses of the same button make a [`ButtonEvent::Tripletap`].
+//! Until a pattern is complete the presses are held back, then replayed as
+//! ordinary short presses once the window runs out, so nothing is lost.
+
+#[cfg(not(feature = "async"))]
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+#[cfg(not(feature = "async"))]
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(not(feature = "async"))]
+use anyhow::Result;
+
+use crate::channel::Outbox;
+use crate::events::{Action, Command};
+
+/// Presses that make up a tap pattern.
+const TAP_COUNT: usize = 3;
+/// Time from the first press in which the rest have to follow.
+const TAP_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Button {
+    A,
+    B,
+    C,
+}
+
+impl Button {
+    pub fn parse(name: &str) -> Option<Button> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "a" => Some(Button::A),
+            "b" => Some(Button::B),
+            "c" => Some(Button::C),
+            _ => None,
+        }
+    }
+}
+
+/// A raw press, before it's been matched against any pattern.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Press {
+    pub button: Button,
+    /// Held down rather than tapped.
+    pub long: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ButtonEvent {
+    Short(Button),
+    Long(Button),
+    /// Three short presses of one button within [`TAP_WINDOW`].
+    Tripletap(Button),
+}
+
+impl ButtonEvent {
+    /// What each gesture does to the pet, if anything. Long presses are
+    /// left for the settings menu, and three taps of B or C for the
+    /// carousel.
+    pub fn action(self) -> Option<Action> {
+        match self {
+            ButtonEvent::Short(Button::A) => Some(Action::Feed),
+            ButtonEvent::Short(Button::B) => Some(Action::Play),
+            ButtonEvent::Short(Button::C) => Some(Action::Clean),
+            ButtonEvent::Long(_) => None,
+            ButtonEvent::Tripletap(Button::A) => Some(Action::Pet),
+            ButtonEvent::Tripletap(_) => None,
+        }
+    }
+}
+
+/// Recognises tap patterns in a stream of presses.
+#[derive(Default)]
+pub struct TapDetector {
+    /// Presses that might still become a pattern, oldest first.
+    pending: Vec<(Button, Instant)>,
+}
+
+impl TapDetector {
+    /// Feeds in a press, returning whatever it settles.
+    pub fn press(&mut self, press: Press, at: Instant) -> Vec<ButtonEvent> {
+        let button = press.button;
+        if press.long {
+            // Ends any pattern so far, and isn't part of one itself.
+            let mut events = self.flush();
+            events.push(ButtonEvent::Long(button));
+            return events;
+        }
+        // A different button, or a press after the window, can't extend the
+        // pattern so far; let those presses through first.
+        let mut events = match self.pending.first() {
+            Some(&(first, since)) if first != button || at - since > TAP_WINDOW => self.flush(),
+            _ => Vec::new(),
+        };
+        self.pending.push((button, at));
+        if self.pending.len() == TAP_COUNT {
+            self.pending.clear();
+            events.push(ButtonEvent::Tripletap(button));
+        }
+        events
+    }
+
+    /// When the pending presses go stale, if there are any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.first().map(|&(_, since)| since + TAP_WINDOW)
+    }
+
+    /// Releases the pending presses as short presses once the window is
+    /// over.
+    pub fn expire(&mut self, now: Instant) -> Vec<ButtonEvent> {
+        match self.deadline() {
+            Some(deadline) if deadline <= now => self.flush(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn flush(&mut self) -> Vec<ButtonEvent> {
+        self.pending
+            .drain(..)
+            .map(|(button, _)| ButtonEvent::Short(button))
+            .collect()
+    }
+}
+
+/// Passes gestures on, returning false once nobody's listening.
+pub fn forward(events: Vec<ButtonEvent>, tx: &impl Outbox) -> bool {
+    events.into_iter().all(|event| {
+        log::debug!("button {event:?}");
+        tx.send(Command::Button(event))
+    })
+}
+
+/// Starts the thread turning `presses` into commands on `tx`.
+#[cfg(not(feature = "async"))]
+pub fn spawn(presses: Receiver<Press>, tx: Sender<Command>) -> Result<()> {
+    thread::Builder::new()
+        .name("buttons".into())
+        .spawn(move || {
+            let mut detector = TapDetector::default();
+            loop {
+                let events = match detector.deadline() {
+                    Some(deadline) => {
+                        let wait = deadline.saturating_duration_since(Instant::now());
+                        match presses.recv_timeout(wait) {
+                            Ok(press) => detector.press(press, Instant::now()),
+                            Err(RecvTimeoutError::Timeout) => detector.expire(Instant::now()),
+                            Err(RecvTimeoutError::Disconnected) => return,
+                        }
+                    }
+                    None => match presses.recv() {
+                        Ok(press) => detector.press(press, Instant::now()),
+                        Err(_) => return,
+                    },
+                };
+                if !forward(events, &tx) {
+                    return;
+                }
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAP: Duration = Duration::from_millis(300);
+
+    fn short(button: Button) -> Press {
+        Press {
+            button,
+            long: false,
+        }
+    }
+
+    #[test]
+    fn three_quick_taps_make_a_tripletap() {
+        let mut taps = TapDetector::default();
+        let start = Instant::now();
+        assert!(taps.press(short(Button::A), start).is_empty());
+        assert!(taps.press(short(Button::A), start + TAP).is_empty());
+        let events = taps.press(short(Button::A), start + 2 * TAP);
+        assert_eq!(events, [ButtonEvent::Tripletap(Button::A)]);
+        assert_eq!(taps.deadline(), None);
+        assert_eq!(
+            ButtonEvent::Tripletap(Button::A).action(),
+            Some(Action::Pet)
+        );
+    }
+
+    #[test]
+    fn unfinished_patterns_come_out_as_short_presses() {
+        let mut taps = TapDetector::default();
+        let start = Instant::now();
+        taps.press(short(Button::A), start);
+        taps.press(short(Button::A), start + TAP);
+        assert_eq!(taps.deadline(), Some(start + TAP_WINDOW));
+        assert!(taps.expire(start + TAP_WINDOW - TAP).is_empty());
+        let events = taps.expire(start + TAP_WINDOW);
+        assert_eq!(events, [ButtonEvent::Short(Button::A); 2]);
+        assert!(taps.expire(start + 2 * TAP_WINDOW).is_empty());
+    }
+
+    #[test]
+    fn another_button_lets_the_pattern_through() {
+        let mut taps = TapDetector::default();
+        let start = Instant::now();
+        taps.press(short(Button::A), start);
+        let events = taps.press(short(Button::B), start + TAP);
+        assert_eq!(events, [ButtonEvent::Short(Button::A)]);
+        let events = taps.press(
+            Press {
+                button: Button::B,
+                long: true,
+            },
+            start + 2 * TAP,
+        );
+        assert_eq!(
+            events,
+            [ButtonEvent::Short(Button::B), ButtonEvent::Long(Button::B)]
+        );
+    }
+
+    #[test]
+    fn a_late_tap_starts_a_new_pattern() {
+        let mut taps = TapDetector::default();
+        let start = Instant::now();
+        taps.press(short(Button::A), start);
+        taps.press(short(Button::A), start + TAP);
+        let late = start + TAP_WINDOW + TAP;
+        let events = taps.press(short(Button::A), late);
+        assert_eq!(events, [ButtonEvent::Short(Button::A); 2]);
+        taps.press(short(Button::A), late + TAP);
+        let events = taps.press(short(Button::A), late + 2 * TAP);
+        assert_eq!(events, [ButtonEvent::Tripletap(Button::A)]);
+    }
+}
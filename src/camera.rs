@@ -0,0 +1,521 @@
+//! Selfies with a Pi camera. A still is taken with `libcamera-still`,
+//! turned grey, fitted to the panel and dithered down to black and white
+//! with Floyd–Steinberg error diffusion, which keeps faces recognisable
+//! where a plain threshold leaves blobs.
+//!
+//! With `tricolor` on, strongly red parts of the photo go to the red
+//! plane instead of coming out grey. Each pixel is either red or grey by
+//! its hue and saturation, and any pixel most of its neighbours disagree
+//! with is then switched over, so red areas don't come out speckled where
+//! they meet the rest. Each area is then dithered on its own: red areas
+//! between red and white, the rest between black and white, and error is
+//! only passed on within an area.
+//!
+//! The camera is run as a subprocess rather than through bindings, so
+//! there's nothing to build and `command` can point at `rpicam-still` or
+//! a script. What it prints on stderr is logged when it fails.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+
+use crate::config::SelfieConfig;
+use crate::display::{self, Color, Frame, HEIGHT, WIDTH};
+use crate::persist;
+use crate::subprocess;
+
+/// The still is taken at twice the panel's size and averaged down, which
+/// takes the sensor noise out before dithering.
+const OVERSAMPLE: u32 = 2;
+/// How saturated a pixel has to be to count as red, from 0 to 1.
+const RED_SATURATION: f32 = 0.35;
+
+/// Why there's no photo, in terms the screen can explain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trouble {
+    /// No camera is attached, or no program to drive one.
+    NoCamera,
+    /// Something else has the camera open.
+    Busy,
+    /// It ran out of time, or failed some other way.
+    Failed,
+}
+
+/// A photo on the panel, or why there isn't one.
+pub struct Selfie {
+    pub shot: Result<Frame, Trouble>,
+    /// When it comes down.
+    pub until: DateTime<Utc>,
+}
+
+impl Selfie {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.until
+    }
+}
+
+/// Takes a still and dithers it to fit the panel, reading it in bands
+/// when `banded`, see [`decode`]. Details of a failure are logged here;
+/// the caller only learns what kind of failure it was.
+pub fn capture(config: &SelfieConfig, banded: bool) -> Result<Frame, Trouble> {
+    let size = format!("{}x{}", WIDTH * OVERSAMPLE, HEIGHT * OVERSAMPLE);
+    let width = (WIDTH * OVERSAMPLE).to_string();
+    let height = (HEIGHT * OVERSAMPLE).to_string();
+    let args = [
+        "--nopreview",
+        "--timeout",
+        "1000",
+        "--width",
+        &width,
+        "--height",
+        &height,
+        "--encoding",
+        "png",
+        "--output",
+        "-",
+    ];
+    let mut command = Command::new(&config.command);
+    command.args(args);
+    let finished = match subprocess::run(&mut command, Duration::from_secs(config.timeout)) {
+        Ok(finished) => finished,
+        Err(err) => {
+            log::warn!("couldn't start {}: {err}", config.command);
+            return Err(match err.kind() {
+                std::io::ErrorKind::NotFound => Trouble::NoCamera,
+                _ => Trouble::Failed,
+            });
+        }
+    };
+    let Some(status) = finished.status else {
+        log::warn!(
+            "{} took longer than {}s, stopped it: {}",
+            config.command,
+            config.timeout,
+            finished.stderr
+        );
+        return Err(Trouble::Failed);
+    };
+    if !status.success() {
+        log::warn!("{} {status}: {}", config.command, finished.stderr);
+        return Err(classify(&finished.stderr));
+    }
+    let red_hue = config.tricolor.then_some(config.red_hue);
+    match decode(&finished.stdout, banded, red_hue) {
+        Ok(image) => {
+            log::info!("took a {size} selfie");
+            Ok(match red_hue {
+                Some(_) => dither_tricolor(&image),
+                None => dither(&image.luma),
+            })
+        }
+        Err(err) => {
+            log::warn!("couldn't read the selfie: {err:#}");
+            Err(Trouble::Failed)
+        }
+    }
+}
+
+/// Tells the usual failures apart by what libcamera says about them.
+fn classify(stderr: &str) -> Trouble {
+    let stderr = stderr.to_ascii_lowercase();
+    if stderr.contains("no cameras available") || stderr.contains("no camera") {
+        Trouble::NoCamera
+    } else if stderr.contains("busy") || stderr.contains("failed to acquire camera") {
+        Trouble::Busy
+    } else {
+        Trouble::Failed
+    }
+}
+
+/// A still fitted to the panel.
+struct Image {
+    /// Brightness from 0 to 1.
+    luma: Vec<f32>,
+    /// How red, from 0 to 1; empty unless asked for.
+    red: Vec<f32>,
+}
+
+/// Reads the still and fits it to the panel, see [`Fit`]. When `banded`,
+/// the still is read a row at a time instead of all at once, so only one
+/// row of it is ever in memory. With `red_hue`, how red each pixel is
+/// comes along too, see [`redness`].
+fn decode(png: &[u8], banded: bool, red_hue: Option<f32>) -> Result<Image> {
+    let mut decoder = png::Decoder::new(std::io::Cursor::new(png));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let (width, height) = (reader.info().width, reader.info().height);
+    let channels = reader.output_color_type().0.samples();
+    let mut fit = Fit::new(width, height);
+    let mut red = red_hue.map(|_| Fit::new(width, height));
+    // Interlaced rows come in passes, out of order.
+    if banded && !reader.info().interlaced {
+        let mut y = 0;
+        while let Some(row) = reader.next_row()? {
+            fit.add(y, &luma(row.data(), channels));
+            if let (Some(red), Some(hue)) = (&mut red, red_hue) {
+                red.add(y, &redness(row.data(), channels, hue));
+            }
+            y += 1;
+        }
+    } else {
+        let mut data = vec![0; reader.output_buffer_size().unwrap_or_default()];
+        let info = reader.next_frame(&mut data)?;
+        for y in 0..info.height {
+            let row = &data[y as usize * info.line_size..][..info.width as usize * channels];
+            fit.add(y, &luma(row, channels));
+            if let (Some(red), Some(hue)) = (&mut red, red_hue) {
+                red.add(y, &redness(row, channels, hue));
+            }
+        }
+    }
+    Ok(Image {
+        luma: fit.finish(true),
+        red: red.map(|red| red.finish(false)).unwrap_or_default(),
+    })
+}
+
+/// A row's brightness from 0 to 1.
+fn luma(row: &[u8], channels: usize) -> Vec<f32> {
+    row.chunks(channels)
+        .map(|pixel| {
+            let luma = match *pixel {
+                [gray] | [gray, _] => gray as f32,
+                [red, green, blue] | [red, green, blue, _] => {
+                    0.299 * red as f32 + 0.587 * green as f32 + 0.114 * blue as f32
+                }
+                _ => 0.0,
+            };
+            luma / 255.0
+        })
+        .collect()
+}
+
+/// How red each pixel in a row is: its saturation if its hue is within
+/// `hue` degrees of red and it's saturated enough to count, else 0.
+fn redness(row: &[u8], channels: usize, hue: f32) -> Vec<f32> {
+    row.chunks(channels)
+        .map(|pixel| {
+            let [red, green, blue] = match *pixel {
+                [red, green, blue] | [red, green, blue, _] => [red, green, blue],
+                _ => return 0.0,
+            }
+            .map(|c| c as f32 / 255.0);
+            let max = red.max(green).max(blue);
+            let spread = max - red.min(green).min(blue);
+            if max == 0.0 || spread == 0.0 {
+                return 0.0;
+            }
+            let saturation = spread / max;
+            // Degrees round the colour wheel, red at 0.
+            let angle = 60.0
+                * match max {
+                    m if m == red => ((green - blue) / spread).rem_euclid(6.0),
+                    m if m == green => (blue - red) / spread + 2.0,
+                    _ => (red - green) / spread + 4.0,
+                };
+            let off_red = angle.min(360.0 - angle);
+            match off_red <= hue && saturation >= RED_SATURATION {
+                true => saturation,
+                false => 0.0,
+            }
+        })
+        .collect()
+}
+
+/// Scales a grey image to cover the panel, cropping whatever sticks out
+/// around the middle, and stretches its levels out to the full range. Each
+/// panel pixel is the average of the ones it covers. The image comes in a
+/// row at a time. Works just as well on any other measure of a pixel,
+/// like [`redness`], left unstretched.
+struct Fit {
+    /// The image rows under each panel row, and columns under each column.
+    rows: Vec<std::ops::Range<u32>>,
+    columns: Vec<std::ops::Range<u32>>,
+    sums: Vec<f32>,
+}
+
+impl Fit {
+    fn new(width: u32, height: u32) -> Fit {
+        let scale = (width as f32 / WIDTH as f32).min(height as f32 / HEIGHT as f32);
+        let left = (width as f32 - WIDTH as f32 * scale) / 2.0;
+        let top = (height as f32 - HEIGHT as f32 * scale) / 2.0;
+        Fit {
+            rows: (0..HEIGHT)
+                .map(|y| span(top + y as f32 * scale, scale, height))
+                .collect(),
+            columns: (0..WIDTH)
+                .map(|x| span(left + x as f32 * scale, scale, width))
+                .collect(),
+            sums: vec![0.0; (WIDTH * HEIGHT) as usize],
+        }
+    }
+
+    /// Adds in row `y` of the image.
+    fn add(&mut self, y: u32, row: &[f32]) {
+        for (rows, line) in self.rows.iter().zip(self.sums.chunks_mut(WIDTH as usize)) {
+            if !rows.contains(&y) {
+                continue;
+            }
+            for (sum, columns) in line.iter_mut().zip(&self.columns) {
+                *sum += columns.clone().map(|c| row[c as usize]).sum::<f32>();
+            }
+        }
+    }
+
+    fn finish(self, stretch: bool) -> Vec<f32> {
+        let mut out = self.sums;
+        for (rows, line) in self.rows.iter().zip(out.chunks_mut(WIDTH as usize)) {
+            for (value, columns) in line.iter_mut().zip(&self.columns) {
+                *value /= (rows.len() * columns.len()).max(1) as f32;
+            }
+        }
+        let (low, high) = out.iter().fold((1.0f32, 0.0f32), |(low, high), &v| {
+            (low.min(v), high.max(v))
+        });
+        if stretch && high - low > 0.05 {
+            for value in &mut out {
+                *value = (*value - low) / (high - low);
+            }
+        }
+        out
+    }
+}
+
+/// Source pixels from `start` for `length`, at least one and all inside
+/// `limit`.
+fn span(start: f32, length: f32, limit: u32) -> std::ops::Range<u32> {
+    let first = (start as u32).min(limit - 1);
+    let end = ((start + length) as u32).clamp(first + 1, limit);
+    first..end
+}
+
+/// Floyd–Steinberg: each pixel goes to whichever of black and white is
+/// nearer, and what that got wrong is passed on to the neighbours still
+/// to come, 7/16 to the right and 3/16, 5/16 and 1/16 along the row below.
+fn dither(gray: &[f32]) -> Frame {
+    let (width, height) = (WIDTH as usize, HEIGHT as usize);
+    let mut values = gray.to_vec();
+    let mut frame = Frame::new(WIDTH, HEIGHT);
+    for y in 0..height {
+        for x in 0..width {
+            let old = values[y * width + x];
+            let ink = old < 0.5;
+            if ink {
+                frame.set_pixel(x as i32, y as i32, Color::Black);
+            }
+            let error = old - if ink { 0.0 } else { 1.0 };
+            let mut spread = |dx: isize, dy: usize, share: f32| {
+                let nx = x as isize + dx;
+                if nx >= 0 && (nx as usize) < width && y + dy < height {
+                    values[(y + dy) * width + nx as usize] += error * share;
+                }
+            };
+            spread(1, 0, 7.0 / 16.0);
+            spread(-1, 1, 3.0 / 16.0);
+            spread(0, 1, 5.0 / 16.0);
+            spread(1, 1, 1.0 / 16.0);
+        }
+    }
+    frame
+}
+
+/// Which panel pixels are red: those at least [`RED_SATURATION`] red on
+/// average, then any that at least three quarters of their neighbours
+/// disagree with switched over. That takes out lone pixels, small holes
+/// and speckled edges and leaves corners alone.
+fn red_areas(red: &[f32]) -> Vec<bool> {
+    let (width, height) = (WIDTH as i32, HEIGHT as i32);
+    let raw: Vec<bool> = red.iter().map(|&red| red >= RED_SATURATION).collect();
+    let mut areas = raw.clone();
+    for y in 0..height {
+        for x in 0..width {
+            let own = raw[(y * width + x) as usize];
+            let (mut against, mut all) = (0, 0);
+            for (dx, dy) in (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy))) {
+                let (nx, ny) = (x + dx, y + dy);
+                if (dx, dy) != (0, 0) && (0..width).contains(&nx) && (0..height).contains(&ny) {
+                    all += 1;
+                    against += (raw[(ny * width + nx) as usize] != own) as u32;
+                }
+            }
+            if against * 4 >= all * 3 {
+                areas[(y * width + x) as usize] = !own;
+            }
+        }
+    }
+    areas
+}
+
+/// Like [`dither`], in two areas at once: red ones between red and white
+/// by how red they are, the rest between black and white by brightness.
+/// Error is only passed to neighbours in the same area, so neither bleeds
+/// into the other.
+fn dither_tricolor(image: &Image) -> Frame {
+    let (width, height) = (WIDTH as usize, HEIGHT as usize);
+    let areas = red_areas(&image.red);
+    // Worked in brightness for both: a red pixel is as light as it's
+    // unsaturated.
+    let mut values: Vec<f32> = (0..width * height)
+        .map(|i| match areas[i] {
+            true => 1.0 - image.red[i],
+            false => image.luma[i],
+        })
+        .collect();
+    let mut frame = Frame::new(WIDTH, HEIGHT);
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let old = values[i];
+            let ink = old < 0.5;
+            if ink {
+                let color = if areas[i] { Color::Red } else { Color::Black };
+                frame.set_pixel(x as i32, y as i32, color);
+            }
+            let error = old - if ink { 0.0 } else { 1.0 };
+            let mut spread = |dx: isize, dy: usize, share: f32| {
+                let nx = x as isize + dx;
+                if nx >= 0 && (nx as usize) < width && y + dy < height {
+                    let n = (y + dy) * width + nx as usize;
+                    if areas[n] == areas[i] {
+                        values[n] += error * share;
+                    }
+                }
+            };
+            spread(1, 0, 7.0 / 16.0);
+            spread(-1, 1, 3.0 / 16.0);
+            spread(0, 1, 5.0 / 16.0);
+            spread(1, 1, 1.0 / 16.0);
+        }
+    }
+    frame
+}
+
+/// Saves `frame` in `dir` as `selfie-<date>-<time>.png`, named by local
+/// time.
+pub fn save(frame: &Frame, dir: &Path, now: DateTime<FixedOffset>) -> Result<PathBuf> {
+    fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    let path = dir.join(format!("selfie-{}.png", now.format("%Y%m%d-%H%M%S")));
+    let mut png = Vec::new();
+    display::encode_png(frame, &mut png)?;
+    persist::write_atomic(&path, &png)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A still as the camera would take it: `pixel(x, y)` gives the colour
+    /// of each, at twice the panel's size.
+    fn still(pixel: impl Fn(u32, u32) -> [u8; 3]) -> Vec<u8> {
+        let (width, height) = (WIDTH * OVERSAMPLE, HEIGHT * OVERSAMPLE);
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        let data: Vec<u8> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| pixel(x, y))
+            .collect();
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(&data)
+            .unwrap();
+        png
+    }
+
+    /// A grey gradient, dark on the left, with a pure red square from
+    /// panel pixel 80 to 120 across and 30 to 70 down.
+    fn red_square(x: u32, y: u32) -> [u8; 3] {
+        let (x, y) = (x / OVERSAMPLE, y / OVERSAMPLE);
+        if (80..120).contains(&x) && (30..70).contains(&y) {
+            return [255, 0, 0];
+        }
+        [(x * 255 / WIDTH) as u8; 3]
+    }
+
+    fn count(frame: &Frame, color: Color, xs: std::ops::Range<i32>) -> usize {
+        xs.flat_map(|x| (0..HEIGHT as i32).map(move |y| (x, y)))
+            .filter(|&(x, y)| frame.pixel(x, y) == color)
+            .count()
+    }
+
+    #[test]
+    fn the_red_square_goes_on_the_red_plane() {
+        let image = decode(&still(red_square), false, Some(20.0)).unwrap();
+        let frame = dither_tricolor(&image);
+        for y in 0..HEIGHT as i32 {
+            for x in 0..WIDTH as i32 {
+                let inside = (80..120).contains(&x) && (30..70).contains(&y);
+                assert_eq!(
+                    frame.pixel(x, y) == Color::Red,
+                    inside,
+                    "at {x},{y}: {:?}",
+                    frame.pixel(x, y)
+                );
+            }
+        }
+        // The gradient dithers as it would without any red about: mostly
+        // black on the dark side, mostly white on the light one.
+        let column = HEIGHT as usize * 40;
+        assert!(count(&frame, Color::Black, 0..40) > column * 3 / 4);
+        assert!(count(&frame, Color::Black, 172..212) < column / 4);
+    }
+
+    #[test]
+    fn without_tricolor_red_is_just_dark() {
+        let image = decode(&still(red_square), false, None).unwrap();
+        assert!(image.red.is_empty());
+        let frame = dither(&image.luma);
+        assert_eq!(count(&frame, Color::Red, 0..WIDTH as i32), 0);
+        // Pure red is about a third as bright as white.
+        assert!(count(&frame, Color::Black, 80..120) > 40 * 40 / 2);
+    }
+
+    #[test]
+    fn banded_reading_is_the_same() {
+        let png = still(red_square);
+        let whole = decode(&png, false, Some(20.0)).unwrap();
+        let banded = decode(&png, true, Some(20.0)).unwrap();
+        assert!(dither_tricolor(&whole) == dither_tricolor(&banded));
+    }
+
+    #[test]
+    fn only_hues_near_red_count() {
+        let red = |pixel: [u8; 3], hue| redness(&pixel, 3, hue)[0];
+        assert_eq!(red([255, 0, 0], 20.0), 1.0);
+        // Orange is 30 degrees off.
+        assert_eq!(red([255, 128, 0], 20.0), 0.0);
+        assert!(red([255, 128, 0], 35.0) > 0.9);
+        // Pink from the other side of the wheel.
+        assert!(red([255, 0, 60], 20.0) > 0.9);
+        // Grey, and red too washed out to count.
+        assert_eq!(red([128, 128, 128], 20.0), 0.0);
+        assert_eq!(red([255, 200, 200], 20.0), 0.0);
+    }
+
+    #[test]
+    fn lone_red_pixels_and_holes_are_tidied() {
+        let mut red = vec![0.0; (WIDTH * HEIGHT) as usize];
+        let mut set = |x: u32, y: u32| red[(y * WIDTH + x) as usize] = 1.0;
+        // A stray pixel, and a square with a hole in it.
+        set(10, 10);
+        for y in 40..50 {
+            for x in 40..50 {
+                if (x, y) != (45, 45) {
+                    set(x, y);
+                }
+            }
+        }
+        let areas = red_areas(&red);
+        let at = |x: u32, y: u32| areas[(y * WIDTH + x) as usize];
+        assert!(!at(10, 10));
+        assert!(at(45, 45));
+        assert!(at(40, 40), "the corner is kept");
+        assert!(!at(39, 45));
+    }
+}
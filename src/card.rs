@@ -0,0 +1,153 @@
+//! A picture of the pet for sharing, from `card` or `GET /card.png`: the
+//! pet, its name, stage, age and stats on a canvas much bigger than the
+//! panel. The 1-bit art is blown up by whole pixels, so it stays crisp,
+//! and can be shaded in grey, see [`crate::artwork`].
+
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use embedded_graphics::mono_font::iso_8859_15::{FONT_10X20, FONT_6X10};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle, RoundedRectangle};
+
+use crate::artwork::{self, SpriteId};
+use crate::display::{self, Color, Frame};
+use crate::pet::{Form, Pet, STAT_MAX};
+use crate::screens::pet::{body_color, form_sprite};
+use crate::sprite::Flip;
+use crate::text::{self, Align, BigText};
+
+pub const WIDTH: u32 = 600;
+pub const HEIGHT: u32 = 300;
+/// How many card pixels each pixel of the pet becomes, at most; a pet
+/// from a bigger artwork pack is scaled down to fit.
+const SCALE: u32 = 10;
+const MARGIN: i32 = 24;
+/// Where the text and bars start, right of the pet.
+const COLUMN: i32 = 300;
+const BAR_WIDTH: u32 = 220;
+const BAR_HEIGHT: u32 = 20;
+const ROW_HEIGHT: i32 = 34;
+
+/// Draws the card for `pet` as of `now`.
+pub fn render(pet: &Pet, now: DateTime<Utc>, supersample: bool, greyscale: bool) -> Frame {
+    let mut frame = Frame::new(WIDTH, HEIGHT);
+    RoundedRectangle::with_equal_corners(
+        Rectangle::new(Point::new(4, 4), Size::new(WIDTH - 8, HEIGHT - 8)),
+        Size::new(16, 16),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(Color::Black, 4))
+    .draw(&mut frame)
+    .ok();
+
+    let (sprite, color) = if pet.is_dead() {
+        (artwork::get(SpriteId::Lost), Color::Black)
+    } else {
+        (form_sprite(pet.form), body_color(pet))
+    };
+    let room = (COLUMN - 2 * MARGIN) as u32;
+    let scale = SCALE.min(room / sprite.width).min(room / sprite.height);
+    let size = Size::new(sprite.width * scale, sprite.height * scale);
+    let origin = Point::new(
+        (COLUMN - size.width as i32) / 2,
+        (HEIGHT - size.height) as i32 / 2,
+    );
+    sprite.draw_scaled(&mut frame, origin, color, scale);
+    if greyscale {
+        sprite.shade(&mut frame, origin, Flip::None, scale);
+    }
+
+    let width = WIDTH - COLUMN as u32 - MARGIN as u32;
+    let name = Rectangle::new(Point::new(COLUMN, MARGIN), Size::new(width, 40));
+    BigText::new(&FONT_10X20, Color::Black, supersample).draw(
+        &mut frame,
+        &pet.name,
+        name,
+        Align::LEFT,
+    );
+    let stage = if pet.is_dead() {
+        "Gone"
+    } else {
+        match pet.form {
+            Form::Baby => "Baby",
+            Form::Noble => "Noble",
+            Form::Wild => "Wild",
+        }
+    };
+    let age = (now - pet.born).num_days() + 1;
+    let line = Rectangle::new(Point::new(COLUMN, MARGIN + 48), Size::new(width, 20));
+    let about = format!("{stage}, day {age}");
+    text::draw(
+        &mut frame,
+        &about,
+        &FONT_10X20,
+        Color::Black,
+        line,
+        Align::LEFT,
+    );
+
+    let stats = [
+        ("Food", pet.hunger),
+        ("Joy", pet.happiness),
+        ("Rest", pet.energy),
+    ];
+    for (row, (label, value)) in stats.into_iter().enumerate() {
+        let top = MARGIN + 96 + row as i32 * ROW_HEIGHT;
+        let cell = Rectangle::new(Point::new(COLUMN, top), Size::new(50, BAR_HEIGHT));
+        text::draw(
+            &mut frame,
+            label,
+            &FONT_10X20,
+            Color::Black,
+            cell,
+            Align::LEFT,
+        );
+        draw_bar(&mut frame, Point::new(COLUMN + 54, top), value);
+    }
+
+    let footer = Rectangle::new(
+        Point::new(COLUMN, HEIGHT as i32 - MARGIN - 10),
+        Size::new(width, 10),
+    );
+    text::draw(
+        &mut frame,
+        "inky-gotchi",
+        &FONT_6X10,
+        Color::Black,
+        footer,
+        Align::RIGHT,
+    );
+    frame
+}
+
+/// Like the pet screen's bars, only thicker: red once the stat is low.
+fn draw_bar(frame: &mut Frame, origin: Point, value: f32) {
+    Rectangle::new(origin, Size::new(BAR_WIDTH, BAR_HEIGHT))
+        .into_styled(PrimitiveStyle::with_stroke(Color::Black, 2))
+        .draw(frame)
+        .ok();
+    let filled = ((BAR_WIDTH - 8) as f32 * value / STAT_MAX).round() as u32;
+    let color = if value < 25.0 {
+        Color::Red
+    } else {
+        Color::Black
+    };
+    Rectangle::new(origin + Point::new(4, 4), Size::new(filled, BAR_HEIGHT - 8))
+        .into_styled(PrimitiveStyle::with_fill(color))
+        .draw(frame)
+        .ok();
+}
+
+/// The card for the pet saved at `save_file`, as a PNG.
+pub fn png(
+    save_file: &Path,
+    now: DateTime<Utc>,
+    supersample: bool,
+    greyscale: bool,
+) -> Result<Vec<u8>> {
+    let pet = Pet::load(save_file)?;
+    let mut png = Vec::new();
+    display::encode_png(&render(&pet, now, supersample, greyscale), &mut png)?;
+    Ok(png)
+}
@@ -0,0 +1,80 @@
+//! The command channel into the render loop, kept behind two small traits
+//! so the loop doesn't care whether threads or tokio tasks are on the other
+//! end.
+
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::Instant;
+
+use anyhow::{bail, Result};
+
+use crate::events::Command;
+
+/// The render loop's end: waits for commands, up to a deadline.
+pub trait Inbox {
+    /// The next command, or `None` once `deadline` passes without one.
+    /// Fails when every sender is gone.
+    fn recv_until(&mut self, deadline: Instant) -> Result<Option<Command>>;
+}
+
+/// Anything feeding the render loop. Sending never blocks, so it's fine
+/// from threads and tasks alike.
+pub trait Outbox: Clone + Send + 'static {
+    /// Returns false once the render loop has gone away.
+    fn send(&self, command: Command) -> bool;
+}
+
+impl Inbox for Receiver<Command> {
+    fn recv_until(&mut self, deadline: Instant) -> Result<Option<Command>> {
+        match self.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(command) => Ok(Some(command)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => bail!("control thread exited"),
+        }
+    }
+}
+
+impl Outbox for Sender<Command> {
+    fn send(&self, command: Command) -> bool {
+        Sender::send(self, command).is_ok()
+    }
+}
+
+#[cfg(feature = "async")]
+pub use self::tokio_impl::TaskInbox;
+
+#[cfg(feature = "async")]
+mod tokio_impl {
+    use super::*;
+    use tokio::runtime::Handle;
+    use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+    /// Receives from tasks on a tokio runtime. Used from the render thread,
+    /// outside the runtime, so it has to be handed the runtime to wait on.
+    pub struct TaskInbox {
+        rx: UnboundedReceiver<Command>,
+        runtime: Handle,
+    }
+
+    impl TaskInbox {
+        pub fn new(rx: UnboundedReceiver<Command>, runtime: Handle) -> Self {
+            TaskInbox { rx, runtime }
+        }
+    }
+
+    impl Inbox for TaskInbox {
+        fn recv_until(&mut self, deadline: Instant) -> Result<Option<Command>> {
+            let next = tokio::time::timeout_at(deadline.into(), self.rx.recv());
+            match self.runtime.block_on(next) {
+                Ok(Some(command)) => Ok(Some(command)),
+                Ok(None) => bail!("control tasks exited"),
+                Err(_) => Ok(None),
+            }
+        }
+    }
+
+    impl Outbox for UnboundedSender<Command> {
+        fn send(&self, command: Command) -> bool {
+            UnboundedSender::send(self, command).is_ok()
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Talking to the pet over HTTP:
+//!
+//! ```text
+//! curl -d '{"text": "good morning"}' http://gotchi.local:8080/say
+//! ```
+//!
+//! What's said is matched against the words in `[text.keywords]` to tell
+//! what it is, and the pet answers with the matching `[text.replies]`
+//! line, both in the response and in its speech bubble. Praise counts as
+//! a petting and scolding as discipline, so flattery shares petting's
+//! hourly cap and a script can't talk the pet happy. A greeting only
+//! cheers it up for a moment, and anything it doesn't follow leaves it
+//! looking lost.
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::config::{KeywordsConfig, TextConfig};
+use crate::events::Action;
+use crate::pet::Pet;
+use crate::reactions::Face;
+use crate::screens;
+use crate::template::{Template, VARIABLES};
+
+/// Longest thing the pet listens to, in letters.
+pub const MAX_TEXT: usize = 280;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Intent {
+    Greeting,
+    Praise,
+    Scold,
+    Status,
+    /// None of the keywords matched.
+    Unknown,
+}
+
+impl Intent {
+    /// The kinds with keywords, in the order ties go.
+    const MATCHED: [Intent; 4] = [
+        Intent::Scold,
+        Intent::Status,
+        Intent::Praise,
+        Intent::Greeting,
+    ];
+
+    /// What `text` is, by the longest keyword phrase found in it as whole
+    /// words.
+    pub fn of(text: &str, keywords: &KeywordsConfig) -> Intent {
+        let said = words(text);
+        let mut best = (0, Intent::Unknown);
+        for intent in Intent::MATCHED {
+            for keyword in intent.keywords(keywords) {
+                let phrase = words(keyword);
+                let found =
+                    !phrase.is_empty() && said.windows(phrase.len()).any(|window| window == phrase);
+                if found && phrase.len() > best.0 {
+                    best = (phrase.len(), intent);
+                }
+            }
+        }
+        best.1
+    }
+
+    fn keywords(self, keywords: &KeywordsConfig) -> &[String] {
+        match self {
+            Intent::Greeting => &keywords.greeting,
+            Intent::Praise => &keywords.praise,
+            Intent::Scold => &keywords.scold,
+            Intent::Status => &keywords.status,
+            Intent::Unknown => &[],
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Intent::Greeting => "greeting",
+            Intent::Praise => "praise",
+            Intent::Scold => "scold",
+            Intent::Status => "status",
+            Intent::Unknown => "unknown",
+        }
+    }
+
+    /// What being told this does to the pet, on top of the reply.
+    pub fn action(self) -> Option<Action> {
+        match self {
+            Intent::Praise => Some(Action::Pet),
+            Intent::Scold => Some(Action::Discipline),
+            Intent::Greeting | Intent::Status | Intent::Unknown => None,
+        }
+    }
+
+    /// The face it pulls for a moment, if any.
+    pub fn face(self) -> Option<Face> {
+        match self {
+            Intent::Greeting => Some(Face::Happy),
+            Intent::Unknown => Some(Face::Lost),
+            Intent::Praise | Intent::Scold | Intent::Status => None,
+        }
+    }
+
+    fn reply(self, text: &TextConfig) -> &Template {
+        let replies = &text.replies;
+        match self {
+            Intent::Greeting => &replies.greeting,
+            Intent::Praise => &replies.praise,
+            Intent::Scold => &replies.scold,
+            Intent::Status => &replies.status,
+            Intent::Unknown => &replies.confused,
+        }
+    }
+}
+
+/// Something said to the pet and its answer, on its way to the loop.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Talk {
+    pub intent: Intent,
+    pub reply: String,
+}
+
+impl Talk {
+    /// Works out what `pet` makes of `said` at `now`. The reply can only
+    /// use what the pet and the time give; the host's numbers come out as
+    /// `?`.
+    pub fn new(said: &str, pet: &Pet, now: DateTime<FixedOffset>, text: &TextConfig) -> Talk {
+        let intent = Intent::of(said, &text.keywords);
+        let reply = intent.reply(text).render(|name| {
+            screens::pet_var(pet, now, name)
+                .or_else(|| VARIABLES.contains(&name).then(|| "?".into()))
+        });
+        Talk { intent, reply }
+    }
+}
+
+/// `text` lowercased and cut into words, dropping punctuation.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::pet::STAT_MAX;
+
+    fn intent(text: &str) -> Intent {
+        Intent::of(text, &KeywordsConfig::default())
+    }
+
+    #[test]
+    fn the_keywords_tell_what_was_said() {
+        let cases = [
+            ("hi", Intent::Greeting),
+            ("Good MORNING, Mara!", Intent::Greeting),
+            ("buna", Intent::Greeting),
+            ("good", Intent::Praise),
+            ("well done!!", Intent::Praise),
+            ("I love you", Intent::Praise),
+            ("bad", Intent::Scold),
+            ("No, no... stop it", Intent::Scold),
+            ("how are you?", Intent::Status),
+            ("status", Intent::Status),
+            ("pizza", Intent::Unknown),
+            ("", Intent::Unknown),
+            // Whole words only.
+            ("goodness, chill", Intent::Unknown),
+            ("badger", Intent::Unknown),
+            // The longest phrase wins: "good" is praise, but "good
+            // morning" is longer.
+            ("good morning, good", Intent::Greeting),
+            ("hey, how are you", Intent::Status),
+            // A tie goes to the scolding.
+            ("good bad", Intent::Scold),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(intent(text), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn the_keywords_can_be_in_any_language() {
+        let keywords = KeywordsConfig {
+            greeting: vec!["bună ziua".into()],
+            praise: vec!["bravo".into(), "ești cuminte".into()],
+            scold: vec!["rău".into()],
+            status: Vec::new(),
+        };
+        assert_eq!(Intent::of("BUNĂ ZIUA!", &keywords), Intent::Greeting);
+        assert_eq!(Intent::of("bravo", &keywords), Intent::Praise);
+        assert_eq!(Intent::of("Ești cuminte.", &keywords), Intent::Praise);
+        assert_eq!(Intent::of("ești rău", &keywords), Intent::Scold);
+        // The English ones are gone.
+        assert_eq!(Intent::of("hello", &keywords), Intent::Unknown);
+        assert_eq!(Intent::of("how are you", &keywords), Intent::Unknown);
+    }
+
+    #[test]
+    fn each_kind_has_its_reply_and_effect() {
+        let now = DateTime::parse_from_rfc3339("2024-05-01T12:00:00+03:00").unwrap();
+        let mut pet = Pet::new("Mara", now.to_utc());
+        (pet.hunger, pet.happiness) = (40.0, 75.4);
+        let text = TextConfig::default();
+        let talk = |said: &str| Talk::new(said, &pet, now, &text);
+        assert_eq!(talk("hello").reply, "Hi!");
+        assert_eq!(talk("clever").reply, "Hehe, thanks!");
+        assert_eq!(talk("naughty").reply, "Sorry...");
+        assert_eq!(talk("are you ok").reply, "40% full, 75% happy");
+        assert_eq!(
+            talk("what's the weather"),
+            Talk {
+                intent: Intent::Unknown,
+                reply: "Huh?".into()
+            }
+        );
+        // Numbers the pet doesn't know come out as `?`.
+        let mut text = TextConfig::default();
+        text.replies.status = Template::parse("{name} at {temp_c}°").unwrap();
+        assert_eq!(Talk::new("status", &pet, now, &text).reply, "Mara at ?°");
+
+        let effects = [
+            Intent::Greeting,
+            Intent::Praise,
+            Intent::Scold,
+            Intent::Status,
+            Intent::Unknown,
+        ]
+        .map(|intent| (intent.action(), intent.face()));
+        assert_eq!(
+            effects,
+            [
+                (None, Some(Face::Happy)),
+                (Some(Action::Pet), None),
+                (Some(Action::Discipline), None),
+                (None, None),
+                (None, Some(Face::Lost)),
+            ]
+        );
+    }
+
+    #[test]
+    fn flattery_runs_into_the_petting_cap() {
+        let mut pet = Pet::new("Mara", Utc::now());
+        pet.happiness = 0.0;
+        let action = intent("good job").action().unwrap();
+        let mut gains = Vec::new();
+        for _ in 0..20 {
+            let before = pet.happiness;
+            pet.apply(action);
+            gains.push(pet.happiness - before);
+        }
+        assert!(gains[0] > 0.0);
+        // It tails off, and stops well short of the top.
+        assert!(gains.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert_eq!(*gains.last().unwrap(), 0.0);
+        assert!(pet.happiness < STAT_MAX / 2.0, "{}", pet.happiness);
+    }
+}
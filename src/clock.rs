@@ -0,0 +1,225 @@
+//! The one place that knows what time it is.
+//!
+//! Every wall-clock decision (quiet hours, the clock face, sunrise times)
+//! goes through a [`Clock`], which pairs the configured timezone with a time
+//! source. That keeps a Pi whose system zone fell back to UTC on Bucharest
+//! time, and lets a run be pinned to a fixed moment with `--at`.
+
+use std::sync::Arc;
+
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, NaiveDateTime, NaiveTime, TimeZone, Utc,
+    Weekday,
+};
+use chrono_tz::Tz;
+
+/// Where local times are reckoned.
+#[derive(Clone, Copy, Debug)]
+pub enum Zone {
+    /// An IANA zone such as `Europe/Bucharest`.
+    Named(Tz),
+    /// Whatever the system's localtime says.
+    System,
+}
+
+impl Zone {
+    /// Parses the `timezone` config option. An unknown name falls back to
+    /// the system zone rather than refusing to start.
+    pub fn from_config(name: Option<&str>) -> Zone {
+        let Some(name) = name else {
+            return Zone::System;
+        };
+        match name.parse::<Tz>() {
+            Ok(tz) => Zone::Named(tz),
+            Err(_) => {
+                log::warn!("unknown timezone {name:?}, using the system's local time");
+                Zone::System
+            }
+        }
+    }
+
+    fn localize(self, at: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self {
+            Zone::Named(tz) => at.with_timezone(&tz).fixed_offset(),
+            Zone::System => at.with_timezone(&Local).fixed_offset(),
+        }
+    }
+
+    /// The earliest instant showing `naive` on the wall clock, if any.
+    fn resolve(self, naive: NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        match self {
+            Zone::Named(tz) => tz
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|t| t.fixed_offset()),
+            Zone::System => Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|t| t.fixed_offset()),
+        }
+    }
+}
+
+type TimeSource = Arc<dyn Fn() -> DateTime<Utc> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct Clock {
+    zone: Zone,
+    source: TimeSource,
+}
+
+impl Clock {
+    /// Real time from the system clock.
+    pub fn new(zone: Zone) -> Self {
+        Clock::with_source(zone, Utc::now)
+    }
+
+    pub fn with_source(
+        zone: Zone,
+        source: impl Fn() -> DateTime<Utc> + Send + Sync + 'static,
+    ) -> Self {
+        Clock {
+            zone,
+            source: Arc::new(source),
+        }
+    }
+
+    /// A clock stuck at `at`.
+    pub fn frozen(zone: Zone, at: DateTime<Utc>) -> Self {
+        Clock::with_source(zone, move || at)
+    }
+
+    pub fn now_utc(&self) -> DateTime<Utc> {
+        (self.source)()
+    }
+
+    /// The current time in the configured zone.
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        self.localize(self.now_utc())
+    }
+
+    pub fn localize(&self, at: DateTime<Utc>) -> DateTime<FixedOffset> {
+        self.zone.localize(at)
+    }
+
+    /// The first instant after `after` showing `time` on the wall clock.
+    /// When DST skips over `time` that day, the first instant after the gap
+    /// is used.
+    pub fn next_occurrence(
+        &self,
+        after: DateTime<FixedOffset>,
+        time: NaiveTime,
+    ) -> DateTime<FixedOffset> {
+        self.next_occurrence_on(after, time, &[])
+    }
+
+    /// Likewise, but only on `days`, or any day with none.
+    pub fn next_occurrence_on(
+        &self,
+        after: DateTime<FixedOffset>,
+        time: NaiveTime,
+        days: &[Weekday],
+    ) -> DateTime<FixedOffset> {
+        let mut date = self.localize(after.to_utc()).date_naive();
+        loop {
+            let naive = date.and_time(time);
+            let candidate = (days.is_empty() || days.contains(&date.weekday()))
+                .then(|| {
+                    self.zone
+                        .resolve(naive)
+                        .or_else(|| self.zone.resolve(naive + Duration::hours(1)))
+                })
+                .flatten();
+            if let Some(candidate) = candidate.filter(|c| *c > after) {
+                return candidate;
+            }
+            date = date.succ_opt().expect("date out of range");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucharest(at: &str) -> Clock {
+        Clock::frozen(
+            Zone::Named(chrono_tz::Europe::Bucharest),
+            at.parse().unwrap(),
+        )
+    }
+
+    fn time(text: &str) -> NaiveTime {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn frozen_clocks_stay_put() {
+        let clock = bucharest("2024-07-01T09:30:00Z");
+        assert_eq!(clock.now_utc(), clock.now_utc());
+        assert_eq!(clock.now().to_rfc3339(), "2024-07-01T12:30:00+03:00");
+        let winter = bucharest("2024-01-01T09:30:00Z");
+        assert_eq!(winter.now().to_rfc3339(), "2024-01-01T11:30:00+02:00");
+    }
+
+    #[test]
+    fn sources_are_injectable() {
+        let ticks = std::sync::atomic::AtomicI64::new(0);
+        let clock = Clock::with_source(Zone::Named(Tz::UTC), move || {
+            let n = ticks.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            DateTime::from_timestamp(n * 60, 0).unwrap()
+        });
+        assert_eq!(clock.now().to_rfc3339(), "1970-01-01T00:00:00+00:00");
+        assert_eq!(clock.now().to_rfc3339(), "1970-01-01T00:01:00+00:00");
+    }
+
+    #[test]
+    fn unknown_zones_fall_back_to_the_system() {
+        assert!(matches!(Zone::from_config(None), Zone::System));
+        assert!(matches!(
+            Zone::from_config(Some("Europe/Bucharets")),
+            Zone::System
+        ));
+        assert!(matches!(
+            Zone::from_config(Some("Europe/Bucharest")),
+            Zone::Named(chrono_tz::Europe::Bucharest)
+        ));
+    }
+
+    #[test]
+    fn next_occurrence_is_strictly_after() {
+        let clock = bucharest("2024-07-01T09:30:00Z");
+        let now = clock.now();
+        let next = clock.next_occurrence(now, time("12:30:00"));
+        assert_eq!(next.to_rfc3339(), "2024-07-02T12:30:00+03:00");
+        let next = clock.next_occurrence(now, time("12:31:00"));
+        assert_eq!(next.to_rfc3339(), "2024-07-01T12:31:00+03:00");
+    }
+
+    #[test]
+    fn next_occurrence_on_weekdays() {
+        // A Monday.
+        let clock = bucharest("2024-07-01T09:30:00Z");
+        let next = clock.next_occurrence_on(clock.now(), time("08:00:00"), &[Weekday::Mon]);
+        assert_eq!(next.to_rfc3339(), "2024-07-08T08:00:00+03:00");
+        let days = [Weekday::Fri, Weekday::Tue];
+        let next = clock.next_occurrence_on(clock.now(), time("08:00:00"), &days);
+        assert_eq!(next.to_rfc3339(), "2024-07-02T08:00:00+03:00");
+    }
+
+    #[test]
+    fn next_occurrence_across_dst() {
+        // 03:30 doesn't happen on the last Sunday of March; the same time
+        // after the gap does instead.
+        let clock = bucharest("2024-03-30T12:00:00Z");
+        let next = clock.next_occurrence(clock.now(), time("03:30:00"));
+        assert_eq!(next.to_rfc3339(), "2024-03-31T04:30:00+03:00");
+        // It happens twice on the last Sunday of October: the first time
+        // counts, and the repeat isn't a new occurrence.
+        let clock = bucharest("2024-10-26T12:00:00Z");
+        let next = clock.next_occurrence(clock.now(), time("03:30:00"));
+        assert_eq!(next.to_rfc3339(), "2024-10-27T03:30:00+03:00");
+        let after = clock.next_occurrence(next, time("03:30:00"));
+        assert_eq!(after.to_rfc3339(), "2024-10-28T03:30:00+02:00");
+    }
+}
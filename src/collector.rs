@@ -0,0 +1,364 @@
+//! Background thread gathering slow or blocking data (sensors, network
+//! lookups) so the render loop only ever reads a ready-made snapshot.
+
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::profile;
+use crate::scheduler::Power;
+use crate::system;
+
+/// Everything collected so far. Fields stay `None` until their source has
+/// run successfully at least once.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    pub cpu_temperature: Option<f32>,
+    /// Available memory in kilobytes.
+    pub free_memory: Option<u64>,
+    pub uptime: Option<Duration>,
+    pub ip_address: Option<IpAddr>,
+    /// Mains too when there's no battery to ask.
+    pub power: Power,
+    /// A newer release than the running one, when the update check found one.
+    pub available_update: Option<String>,
+    /// Package upgrades waiting, see [`crate::server`].
+    pub upgrades: Option<usize>,
+    #[cfg(feature = "github")]
+    pub github: crate::github::GithubStatus,
+    /// One per configured symbol, in config order.
+    #[cfg(feature = "ticker")]
+    pub tickers: Vec<crate::ticker::Quote>,
+    /// The hourly forecast, see [`crate::weather`].
+    #[cfg(feature = "weather")]
+    pub forecast: Vec<crate::weather::Hour>,
+}
+
+type SharedSnapshot = Arc<Mutex<Snapshot>>;
+
+/// One kind of data, refreshed on its own schedule.
+pub trait Source: Send {
+    fn name(&self) -> &'static str;
+    /// Time until the next run, asked after each collection.
+    fn interval(&self) -> Duration;
+    /// Gathers fresh data and stores it. Implementations should do any slow
+    /// work before taking the lock.
+    fn collect(&mut self, snapshot: &Mutex<Snapshot>);
+    /// Whether the source only runs while a screen that needs it is in the
+    /// carousel, see [`crate::screens::Screen::sources`]. The rest always
+    /// run, since the pet and the status file use them too.
+    fn on_demand(&self) -> bool {
+        false
+    }
+}
+
+/// Shortest gap between runs of any source while in low-power mode.
+const LOW_POWER_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// Handle to the collector thread. Dropping it stops the thread and waits
+/// for it to finish.
+pub struct Collector {
+    snapshot: SharedSnapshot,
+    control: Sender<Control>,
+    stats: Arc<Mutex<CollectorStats>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+enum Control {
+    LowPower(bool),
+    /// Names of the on-demand sources something wants.
+    Demand(BTreeSet<&'static str>),
+    Stop,
+}
+
+/// Which sources are running, and how often they've been started and
+/// stopped since startup, for the status file. `/metrics` has the starts
+/// and stops by source, see [`crate::profile`].
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CollectorStats {
+    pub running: Vec<&'static str>,
+    pub starts: u32,
+    pub stops: u32,
+}
+
+impl Collector {
+    /// Starts the collector thread running `sources`, with the on-demand
+    /// ones limited to those named in `demand`.
+    pub fn spawn(sources: Vec<Box<dyn Source>>, demand: BTreeSet<&'static str>) -> Result<Self> {
+        let snapshot = SharedSnapshot::default();
+        let stats = Arc::new(Mutex::new(CollectorStats::default()));
+        let (tx, rx) = mpsc::channel();
+        let thread = thread::Builder::new().name("collector".into()).spawn({
+            let (snapshot, stats) = (snapshot.clone(), stats.clone());
+            move || run(sources, demand, &snapshot, &stats, &rx)
+        })?;
+        Ok(Collector {
+            snapshot,
+            control: tx,
+            stats,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.snapshot.lock().unwrap().clone()
+    }
+
+    pub fn stats(&self) -> CollectorStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// In low-power mode every source runs at most every half hour. Leaving
+    /// it runs everything straight away so the display catches up.
+    pub fn set_low_power(&self, on: bool) {
+        // Only fails if the thread is gone, in which case nothing's running.
+        self.control.send(Control::LowPower(on)).ok();
+    }
+
+    /// Starts the on-demand sources named in `demand` and stops the rest.
+    pub fn set_demand(&self, demand: BTreeSet<&'static str>) {
+        self.control.send(Control::Demand(demand)).ok();
+    }
+}
+
+impl Drop for Collector {
+    fn drop(&mut self) {
+        self.control.send(Control::Stop).ok();
+        if let Some(thread) = self.thread.take() {
+            // A source in the middle of a fetch finishes it first.
+            thread.join().ok();
+        }
+    }
+}
+
+/// A source and when it's next due, or `None` while it's stopped.
+struct Scheduled {
+    source: Box<dyn Source>,
+    due: Option<Instant>,
+}
+
+fn run(
+    sources: Vec<Box<dyn Source>>,
+    demand: BTreeSet<&'static str>,
+    shared: &Mutex<Snapshot>,
+    stats: &Mutex<CollectorStats>,
+    control: &Receiver<Control>,
+) {
+    let mut scheduled: Vec<_> = sources
+        .into_iter()
+        .map(|source| Scheduled { source, due: None })
+        .collect();
+    apply_demand(&mut scheduled, &demand, stats);
+    let mut low_power = false;
+    loop {
+        let now = Instant::now();
+        for entry in scheduled.iter_mut() {
+            if entry.due.is_some_and(|due| due <= now) {
+                log::debug!("collecting {}", entry.source.name());
+                entry.source.collect(shared);
+                let mut interval = entry.source.interval();
+                if low_power {
+                    interval = interval.max(LOW_POWER_INTERVAL);
+                }
+                entry.due = Some(Instant::now() + interval);
+            }
+        }
+        let next = scheduled.iter().filter_map(|entry| entry.due).min();
+        let received = match next {
+            Some(next) => control.recv_timeout(next.saturating_duration_since(Instant::now())),
+            // Nothing running; wait to be told otherwise.
+            None => control.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(Control::LowPower(on)) => {
+                if low_power && !on {
+                    let now = Instant::now();
+                    for entry in scheduled.iter_mut().filter(|entry| entry.due.is_some()) {
+                        entry.due = Some(now);
+                    }
+                }
+                log::debug!("collector low-power mode {}", if on { "on" } else { "off" });
+                low_power = on;
+            }
+            Ok(Control::Demand(demand)) => apply_demand(&mut scheduled, &demand, stats),
+            Ok(Control::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+    }
+}
+
+/// Starts and stops sources to match `demand`. Newly started ones run
+/// straight away.
+fn apply_demand(
+    scheduled: &mut [Scheduled],
+    demand: &BTreeSet<&'static str>,
+    stats: &Mutex<CollectorStats>,
+) {
+    let mut stats = stats.lock().unwrap();
+    for entry in scheduled.iter_mut() {
+        let name = entry.source.name();
+        let wanted = !entry.source.on_demand() || demand.contains(name);
+        match (entry.due.is_some(), wanted) {
+            (false, true) => {
+                log::info!("starting the {name} source");
+                entry.due = Some(Instant::now());
+                stats.starts += 1;
+                profile::source(name, true);
+            }
+            (true, false) => {
+                log::info!("stopping the {name} source, no screen needs it");
+                entry.due = None;
+                stats.stops += 1;
+                profile::source(name, false);
+            }
+            _ => {}
+        }
+    }
+    stats.running = scheduled
+        .iter()
+        .filter(|entry| entry.due.is_some())
+        .map(|entry| entry.source.name())
+        .collect();
+}
+
+/// CPU temperature, free memory, uptime and the local address.
+pub struct SystemSource;
+
+impl Source for SystemSource {
+    fn name(&self) -> &'static str {
+        "system"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(15)
+    }
+
+    fn collect(&mut self, snapshot: &Mutex<Snapshot>) {
+        let cpu_temperature = system::cpu_temperature();
+        let free_memory = system::available_memory();
+        let uptime = system::uptime();
+        let ip_address = system::local_ip();
+        let power = match system::on_battery() {
+            Some(true) => Power::Battery,
+            _ => Power::Mains,
+        };
+        let mut snapshot = snapshot.lock().unwrap();
+        snapshot.power = power;
+        snapshot.uptime = uptime;
+        snapshot.ip_address = ip_address;
+        snapshot.cpu_temperature = cpu_temperature;
+        snapshot.free_memory = free_memory;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::screens::Carousel;
+
+    /// Stands in for the update check, which the pet and about screens
+    /// both show.
+    struct Shared;
+
+    impl Source for Shared {
+        fn name(&self) -> &'static str {
+            "update-check"
+        }
+
+        fn interval(&self) -> Duration {
+            Duration::from_secs(3600)
+        }
+
+        fn collect(&mut self, snapshot: &Mutex<Snapshot>) {
+            snapshot.lock().unwrap().available_update = Some("9.9.9".into());
+        }
+
+        fn on_demand(&self) -> bool {
+            true
+        }
+    }
+
+    fn scheduled() -> Vec<Scheduled> {
+        vec![
+            Scheduled {
+                source: Box::new(SystemSource),
+                due: None,
+            },
+            Scheduled {
+                source: Box::new(Shared),
+                due: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn a_shared_source_runs_until_no_screen_needs_it() {
+        let config = Config {
+            screens: vec!["pet".into(), "about".into(), "clock".into()],
+            ..Config::default()
+        };
+        let mut carousel = Carousel::from_config(&config).unwrap();
+        let mut scheduled = scheduled();
+        let stats = Mutex::new(CollectorStats::default());
+        let mut toggle = |name: &str, on: bool| {
+            carousel.set_enabled(name, on, &config).unwrap();
+            apply_demand(&mut scheduled, &carousel.demand(), &stats);
+            let stats = stats.lock().unwrap().clone();
+            (stats.running, stats.starts, stats.stops)
+        };
+        assert_eq!(
+            toggle("clock", true),
+            (vec!["system", "update-check"], 2, 0)
+        );
+        // The pet screen still needs it.
+        assert_eq!(
+            toggle("about", false),
+            (vec!["system", "update-check"], 2, 0)
+        );
+        assert_eq!(toggle("pet", false), (vec!["system"], 2, 1));
+        assert_eq!(
+            toggle("about", true),
+            (vec!["system", "update-check"], 3, 1)
+        );
+        assert_eq!(toggle("pet", true), (vec!["system", "update-check"], 3, 1));
+        let metrics = profile::prometheus();
+        for counter in ["starts", "stops"] {
+            let line = format!("inky_gotchi_source_{counter}_total{{source=\"update-check\"}}");
+            assert!(metrics.contains(&line), "{line} missing from {metrics}");
+        }
+    }
+
+    #[test]
+    fn the_thread_stops_sources_it_is_told_to() {
+        let sources: Vec<Box<dyn Source>> = vec![Box::new(SystemSource), Box::new(Shared)];
+        let collector = Collector::spawn(sources, BTreeSet::from(["update-check"])).unwrap();
+        let wait_for = |check: &dyn Fn(&CollectorStats) -> bool| {
+            let started = Instant::now();
+            loop {
+                let stats = collector.stats();
+                if check(&stats) {
+                    return stats;
+                }
+                assert!(started.elapsed() < Duration::from_secs(5), "{stats:?}");
+                thread::sleep(Duration::from_millis(5));
+            }
+        };
+        wait_for(&|stats| stats.starts == 2);
+        collector.set_demand(BTreeSet::new());
+        let stats = wait_for(&|stats| stats.stops == 1);
+        assert_eq!(stats.running, ["system"]);
+        assert_eq!(
+            collector.snapshot().available_update.as_deref(),
+            Some("9.9.9")
+        );
+        // Joins the thread, rather than leaving it running.
+        drop(collector);
+    }
+}
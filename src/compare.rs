@@ -0,0 +1,490 @@
+//! Catching layout changes before they reach the panel.
+//!
+//! `compare-renders render` draws a fixed set of scenes, every screen with
+//! the pet in a few states, in both text sizes, into one PNG each. The
+//! clock, seed and numbers are fixed, so the same code always draws the
+//! same pictures. Render once on each of two revisions, and
+//! `compare-renders diff` puts each pair side by side, before, after and
+//! the difference with every changed pixel in red, and lists how many
+//! pixels each scene changed by, as a table to paste into a review.
+//!
+//! The about and logs screens are left out: they show the build and the
+//! log, which change between any two revisions.
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::time::Duration as StdDuration;
+
+use anyhow::{ensure, Context as _, Result};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::clock::{Clock, Zone};
+use crate::collector::Snapshot;
+use crate::config::Config;
+use crate::display::{self, Color, Frame, PanelInfo, HEIGHT, WIDTH};
+use crate::events::Reading;
+use crate::pet::{Egg, Form, Pet};
+use crate::quiet;
+use crate::runtime::Runtime;
+use crate::screens::sleep::SleepScreen;
+use crate::screens::{self, Context, Screen};
+use crate::simulate;
+use crate::summary::Tally;
+
+/// The seed every render uses, so the pet's wanderings come out the same.
+const SEED: u64 = 0;
+/// Written next to the pictures by `diff`.
+pub const SUMMARY: &str = "summary.md";
+/// Space between the three pictures of a comparison.
+const GAP: u32 = 6;
+/// How much comparisons are blown up by, to be looked at on a monitor.
+const SCALE: u32 = 2;
+
+/// One of the pictures: a screen, and what's done to the pet first.
+struct Scene {
+    name: &'static str,
+    screen: &'static str,
+    setup: fn(&mut Pet, DateTime<Utc>),
+}
+
+const SCENES: &[Scene] = &[
+    Scene {
+        name: "pet",
+        screen: "pet",
+        setup: |_, _| {},
+    },
+    Scene {
+        name: "pet-hungry",
+        screen: "pet",
+        setup: |pet, _| {
+            pet.hunger = 8.0;
+            pet.happiness = 20.0;
+        },
+    },
+    Scene {
+        name: "pet-messes",
+        screen: "pet",
+        setup: |pet, _| pet.messes = 3,
+    },
+    Scene {
+        name: "pet-misbehaving",
+        screen: "pet",
+        setup: |pet, now| {
+            pet.misbehave(now);
+        },
+    },
+    Scene {
+        name: "pet-noble",
+        screen: "pet",
+        setup: |pet, _| pet.form = Form::Noble,
+    },
+    Scene {
+        name: "pet-ember",
+        screen: "pet",
+        setup: |pet, _| {
+            pet.form = Form::Wild;
+            pet.egg = Some(Egg::Ember);
+        },
+    },
+    Scene {
+        name: "pet-dead",
+        screen: "pet",
+        setup: |pet, now| pet.died = Some(now),
+    },
+    Scene {
+        name: "clock",
+        screen: "clock",
+        setup: |_, _| {},
+    },
+    Scene {
+        name: "stats",
+        screen: "stats",
+        setup: |_, _| {},
+    },
+    Scene {
+        name: "health",
+        screen: "health",
+        setup: |pet, now| {
+            let readings = [("steps", 6200.0, Some(10000.0)), ("weight", 71.4, None)];
+            for (metric, value, goal) in readings {
+                let reading = Reading {
+                    metric: metric.to_string(),
+                    value,
+                    goal,
+                };
+                pet.record(reading, now, now.date_naive());
+            }
+        },
+    },
+    Scene {
+        name: "heatmap",
+        screen: "heatmap",
+        setup: |_, _| {},
+    },
+    Scene {
+        name: "sleep",
+        screen: "sleep",
+        setup: |_, _| {},
+    },
+];
+
+/// Draws every scene into `dir` as `<scene>.png`, returning how many
+/// pictures that was. Each config layout is drawn as a scene of its own.
+pub fn render(config: &Config, zone: Zone, dir: &Path) -> Result<usize> {
+    std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    let mut layouts: Vec<&str> = config.layouts.keys().map(String::as_str).collect();
+    layouts.sort_unstable();
+    let custom: Vec<Scene> = layouts
+        .into_iter()
+        .map(|name| {
+            let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+            Scene {
+                name,
+                screen: name,
+                setup: |_, _| {},
+            }
+        })
+        .collect();
+
+    let mut written = 0;
+    for large in [false, true] {
+        let mut config = config.clone();
+        config.accessibility.large_text = large;
+        for scene in SCENES.iter().chain(&custom) {
+            let frames = draw(&config, zone, scene)?;
+            let count = frames.len();
+            for (index, frame) in frames.iter().enumerate() {
+                let mut name = scene.name.to_string();
+                // Large stats take several screens.
+                if count > 1 {
+                    write!(name, "-{}", index + 1)?;
+                }
+                if large {
+                    name.push_str("-large");
+                }
+                display::write_png(frame, &dir.join(format!("{name}.png")))?;
+                written += 1;
+            }
+        }
+    }
+    Ok(written)
+}
+
+/// Draws `scene` on a pet two days old, as of the fixed start.
+fn draw(config: &Config, zone: Zone, scene: &Scene) -> Result<Vec<Frame>> {
+    let start = simulate::default_start();
+    let clock = Clock::frozen(zone, start);
+    let now = clock.now();
+    let mut pet = Pet::new(&config.pet.name, start - Duration::days(2));
+    pet.egg = Some(Egg::Plain);
+    pet.hunger = 70.0;
+    pet.happiness = 80.0;
+    pet.energy = 60.0;
+    let runtime = Runtime::new(SEED, &mut pet);
+    (scene.setup)(&mut pet, start);
+
+    let built: Vec<Box<dyn Screen>> = match scene.screen {
+        "sleep" => vec![Box::new(SleepScreen)],
+        name => screens::build(name, config)?,
+    };
+    let panel = PanelInfo::simulator();
+    let snapshot = Snapshot {
+        cpu_temperature: Some(48.5),
+        free_memory: Some(212_000),
+        uptime: Some(StdDuration::from_secs(3 * 24 * 60 * 60 + 5 * 60 * 60)),
+        ip_address: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 42))),
+        ..Snapshot::default()
+    };
+    let tally = Tally::new(start.date_naive());
+    let mut planner = quiet::Planner::default();
+    let ctx = Context {
+        now,
+        clock: &clock,
+        config,
+        pet: &pet,
+        runtime: &runtime,
+        panel: &panel,
+        snapshot: &snapshot,
+        tally: &tally,
+        quiet: planner.get(config, &clock, now),
+    };
+    Ok(built
+        .iter()
+        .map(|screen| {
+            let mut frame = Frame::new(WIDTH, HEIGHT);
+            screen.draw(&ctx, &mut frame);
+            frame
+        })
+        .collect())
+}
+
+/// How one scene came out between two renders.
+enum Outcome {
+    Changed(u32),
+    Added,
+    Removed,
+}
+
+/// Compares the renders in `before` and `after`, writing a comparison of
+/// each scene in both into `out`, and returns the summary table, which is
+/// also written to `out`.
+pub fn diff(before: &Path, after: &Path, out: &Path) -> Result<String> {
+    let old = scenes_in(before)?;
+    let new = scenes_in(after)?;
+    ensure!(
+        !old.is_empty() || !new.is_empty(),
+        "no renders in {} or {}",
+        before.display(),
+        after.display()
+    );
+    std::fs::create_dir_all(out).with_context(|| format!("creating {}", out.display()))?;
+
+    let mut rows = Vec::new();
+    for name in old.union(&new) {
+        let file = format!("{name}.png");
+        let outcome = match (old.contains(name), new.contains(name)) {
+            (true, true) => {
+                let old = read_frame(&before.join(&file))?;
+                let new = read_frame(&after.join(&file))?;
+                let (comparison, changed) = side_by_side(&old, &new);
+                display::write_png(&comparison, &out.join(&file))?;
+                Outcome::Changed(changed)
+            }
+            (false, _) => Outcome::Added,
+            (_, false) => Outcome::Removed,
+        };
+        rows.push((name.as_str(), outcome));
+    }
+
+    let mut table = String::from("| scenario | changed pixels |\n|---|---:|\n");
+    for (name, outcome) in &rows {
+        let cell = match outcome {
+            Outcome::Changed(count) => count.to_string(),
+            Outcome::Added => "added".to_string(),
+            Outcome::Removed => "removed".to_string(),
+        };
+        writeln!(table, "| {name} | {cell} |")?;
+    }
+    let changed = rows
+        .iter()
+        .filter(|(_, outcome)| !matches!(outcome, Outcome::Changed(0)))
+        .count();
+    writeln!(table, "\n{changed} of {} scenarios changed.", rows.len())?;
+    let path = out.join(SUMMARY);
+    std::fs::write(&path, &table).with_context(|| format!("writing {}", path.display()))?;
+    Ok(table)
+}
+
+/// The scenes rendered into `dir`, by file name.
+fn scenes_in(dir: &Path) -> Result<BTreeSet<String>> {
+    let entries = std::fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))?;
+    let mut names = BTreeSet::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "png") {
+            if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                names.insert(stem.to_string());
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Reads back a picture written by `render`.
+fn read_frame(path: &Path) -> Result<Frame> {
+    let file = std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut reader = png::Decoder::new(std::io::BufReader::new(file))
+        .read_info()
+        .with_context(|| format!("reading {}", path.display()))?;
+    let mut data = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let info = reader.next_frame(&mut data)?;
+    ensure!(
+        info.width == WIDTH && info.height == HEIGHT && info.color_type == png::ColorType::Rgb,
+        "{} isn't a render",
+        path.display()
+    );
+    let mut frame = Frame::new(WIDTH, HEIGHT);
+    for (index, rgb) in data[..info.buffer_size()].chunks_exact(3).enumerate() {
+        let (x, y) = ((index as u32 % WIDTH) as i32, (index as u32 / WIDTH) as i32);
+        let (color, shaded) = match [rgb[0], rgb[1], rgb[2]] {
+            display::WHITE => (Color::White, false),
+            display::LIGHT_GREY => (Color::White, true),
+            display::BLACK => (Color::Black, false),
+            display::DARK_GREY => (Color::Black, true),
+            display::RED => (Color::Red, false),
+            _ => anyhow::bail!("{} has colours the panel can't show", path.display()),
+        };
+        frame.set_pixel(x, y, color);
+        if shaded {
+            frame.shade(x, y);
+        }
+    }
+    Ok(frame)
+}
+
+/// `old`, `new` and where they differ, left to right, and how many pixels
+/// that was. In the difference, unchanged ink is grey and changes are red.
+fn side_by_side(old: &Frame, new: &Frame) -> (Frame, u32) {
+    let width = 3 * WIDTH + 2 * GAP;
+    let mut out = Frame::new(width * SCALE, HEIGHT * SCALE);
+    let mut put = |x: u32, y: u32, color: Color, shaded: bool| {
+        for dy in 0..SCALE {
+            for dx in 0..SCALE {
+                let (x, y) = ((x * SCALE + dx) as i32, (y * SCALE + dy) as i32);
+                out.set_pixel(x, y, color);
+                if shaded {
+                    out.shade(x, y);
+                }
+            }
+        }
+    };
+    // The gaps are grey, so the panels' white edges show.
+    for y in 0..HEIGHT {
+        for gap in [WIDTH, 2 * WIDTH + GAP] {
+            for x in gap..gap + GAP {
+                put(x, y, Color::White, true);
+            }
+        }
+    }
+
+    let mut changed = 0;
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let (px, py) = (x as i32, y as i32);
+            let before = (old.pixel(px, py), old.is_shaded(px, py));
+            let after = (new.pixel(px, py), new.is_shaded(px, py));
+            put(x, y, before.0, before.1);
+            put(WIDTH + GAP + x, y, after.0, after.1);
+            let diff = if before != after {
+                changed += 1;
+                (Color::Red, false)
+            } else {
+                (Color::White, after.0 != Color::White)
+            };
+            put(2 * (WIDTH + GAP) + x, y, diff.0, diff.1);
+        }
+    }
+    (out, changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone() -> Zone {
+        Zone::Named(chrono_tz::Europe::Bucharest)
+    }
+
+    fn files(dir: &Path) -> Vec<(String, Vec<u8>)> {
+        let mut files: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, std::fs::read(&path).unwrap())
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn renders_come_out_the_same_every_time() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (one, two) = (tmp.path().join("one"), tmp.path().join("two"));
+        let config = Config::default();
+        let count = render(&config, zone(), &one).unwrap();
+        assert_eq!(render(&config, zone(), &two).unwrap(), count);
+        // Every scene, in both sizes, and the large stats take more
+        // than one screen.
+        assert!(count > 2 * SCENES.len(), "{count}");
+        let rendered = files(&one);
+        assert_eq!(rendered.len(), count);
+        assert!(rendered.iter().any(|(name, _)| name == "pet-large.png"));
+        assert!(rendered == files(&two));
+
+        let table = diff(&one, &two, &tmp.path().join("out")).unwrap();
+        assert!(table.ends_with(&format!("\n0 of {count} scenarios changed.\n")));
+        assert!(table.contains("| pet-dead | 0 |"));
+    }
+
+    #[test]
+    fn changes_are_counted_and_shown_in_red() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = |name: &str| {
+            let dir = tmp.path().join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            dir
+        };
+        let (before, after, out) = (dir("before"), dir("after"), tmp.path().join("out"));
+        let mut old = Frame::new(WIDTH, HEIGHT);
+        old.set_pixel(10, 10, Color::Black);
+        old.set_pixel(11, 10, Color::Black);
+        let mut new = old.clone();
+        new.set_pixel(10, 10, Color::Red);
+        new.set_pixel(50, 20, Color::Black);
+        new.shade(11, 10);
+        let write = |dir: &Path, name: &str, frame: &Frame| {
+            display::write_png(frame, &dir.join(format!("{name}.png"))).unwrap()
+        };
+        write(&before, "clock", &old);
+        write(&after, "clock", &new);
+        write(&before, "gone", &old);
+        write(&after, "new", &new);
+
+        let table = diff(&before, &after, &out).unwrap();
+        assert_eq!(
+            table,
+            "| scenario | changed pixels |\n|---|---:|\n\
+             | clock | 3 |\n| gone | removed |\n| new | added |\n\
+             \n3 of 3 scenarios changed.\n"
+        );
+        assert_eq!(std::fs::read_to_string(out.join(SUMMARY)).unwrap(), table);
+        assert!(!out.join("gone.png").exists());
+
+        // Before, after and the difference, blown up.
+        let mut png = png::Decoder::new(std::io::BufReader::new(
+            std::fs::File::open(out.join("clock.png")).unwrap(),
+        ))
+        .read_info()
+        .unwrap();
+        let mut data = vec![0; png.output_buffer_size().unwrap()];
+        let info = png.next_frame(&mut data).unwrap();
+        assert_eq!(
+            (info.width, info.height),
+            ((3 * WIDTH + 2 * GAP) * SCALE, HEIGHT * SCALE)
+        );
+        let at = |x: u32, y: u32| {
+            let i = ((y * SCALE * info.width + x * SCALE) * 3) as usize;
+            [data[i], data[i + 1], data[i + 2]]
+        };
+        let diff_x = 2 * (WIDTH + GAP);
+        assert_eq!(at(10, 10), display::BLACK);
+        assert_eq!(at(WIDTH + GAP + 10, 10), display::RED);
+        assert_eq!(at(diff_x + 10, 10), display::RED);
+        assert_eq!(at(diff_x + 11, 10), display::RED);
+        assert_eq!(at(diff_x + 50, 20), display::RED);
+        assert_eq!(at(diff_x + 5, 5), display::WHITE);
+        assert_eq!(at(WIDTH, 0), display::LIGHT_GREY);
+    }
+
+    #[test]
+    fn renders_read_back_as_they_were() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("frame.png");
+        let mut frame = Frame::new(WIDTH, HEIGHT);
+        frame.set_pixel(0, 0, Color::Black);
+        frame.set_pixel(1, 0, Color::Red);
+        frame.set_pixel(2, 0, Color::Black);
+        frame.shade(2, 0);
+        frame.shade(3, 0);
+        display::write_png(&frame, &path).unwrap();
+        assert!(read_frame(&path).unwrap() == frame);
+
+        display::write_png(&Frame::new(10, 10), &path).unwrap();
+        assert!(read_frame(&path).is_err());
+        assert!(diff(tmp.path(), &tmp.path().join("missing"), tmp.path()).is_err());
+    }
+}
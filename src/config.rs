@@ -0,0 +1,1059 @@
+//! User configuration, read from a TOML file.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use chrono::{NaiveTime, Weekday};
+use serde::{Deserialize, Deserializer};
+
+use crate::display::Mirror;
+use crate::events::PetEvent;
+use crate::icons::Icon;
+use crate::input::InputMode;
+use crate::message;
+use crate::quiet::{self, QuietConfig};
+use crate::reminder;
+use crate::screens::{self, declarative::DeclarativeScreen};
+use crate::summary::Window;
+use crate::template::Template;
+use crate::transition::Transition;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Seconds between display refreshes, when nothing special is going on.
+    pub refresh_interval: u64,
+    pub cadence: CadenceConfig,
+    /// Nightly window in which the pet sleeps and the display is parked.
+    pub quiet_hours: Option<QuietConfig>,
+    /// IANA timezone, e.g. `Europe/Bucharest`. Defaults to the system's.
+    pub timezone: Option<String>,
+    /// Observer latitude in degrees, positive north. Used for sunrise/sunset.
+    pub latitude: f64,
+    /// Observer longitude in degrees, positive east.
+    pub longitude: f64,
+    /// Smooth the edges of large text by rendering it supersampled.
+    pub supersample_text: bool,
+    pub accessibility: AccessibilityConfig,
+    /// Only push the changed region to the panel instead of the whole frame.
+    pub partial_refresh: bool,
+    /// Flip the image left to right, for a panel viewed in a mirror or
+    /// through a periscope-style case.
+    pub mirror_x: bool,
+    /// Flip the image top to bottom.
+    pub mirror_y: bool,
+    /// Degrees clockwise the panel is mounted at. Screens are laid out
+    /// for a landscape panel, so only 0 and 180 work.
+    pub rotation: u16,
+    /// Move the whole picture a pixel or two every hour, so the parts that
+    /// never change don't burn into the panel.
+    pub anti_burn_in: bool,
+    /// Clearing away the old image before a big change.
+    pub pre_clear: PreClearConfig,
+    /// How the carousel moves from one screen to the next: `none`, `wipe`
+    /// or `dissolve`.
+    pub transition: Transition,
+    /// Screens shown by the carousel, in order.
+    pub screens: Vec<String>,
+    /// Screens described in the config rather than in code, by name. These
+    /// come on top of [`EXAMPLE_LAYOUTS`], and replace any of the same name.
+    #[serde(deserialize_with = "layouts_over_examples")]
+    pub layouts: BTreeMap<String, LayoutConfig>,
+    /// Artwork pack to draw the pet from instead of the built-in sprites,
+    /// see [`crate::artwork`].
+    pub artwork: Option<PathBuf>,
+    /// Refreshes each screen stays up before the carousel moves on.
+    pub screen_dwell: u32,
+    /// Minutes a pinned screen stays pinned; 0 keeps it until unpinned.
+    pub pin_minutes: u32,
+    /// Minutes guest mode stays on; 0 keeps it until turned off.
+    pub guest_minutes: u32,
+    /// Recurring reminders, see [`crate::reminder`].
+    pub reminders: Vec<ReminderConfig>,
+    /// Minutes a reminder stays up each time; 0 keeps it up until it's
+    /// acknowledged.
+    pub reminder_minutes: u32,
+    /// Minutes until a reminder nobody acknowledged comes back.
+    pub reminder_repeat_minutes: u32,
+    /// Hours a reminder that fell due while the daemon wasn't running is
+    /// still worth bringing up at startup.
+    pub reminder_missed_hours: u32,
+    /// CPU temperature in °C above which the pet starts sweating.
+    pub hot_temperature: f32,
+    /// Where button presses come from besides the control socket: `auto`,
+    /// `keyboard`, `evdev` or `none`; see [`crate::input`].
+    pub input: InputMode,
+    /// The keyboard to read with `input = "evdev"`, e.g.
+    /// `/dev/input/event3`; otherwise the first one found.
+    pub input_device: Option<PathBuf>,
+    /// Unix socket accepting commands like `feed`.
+    pub control_socket: PathBuf,
+    /// JSON snapshot of the daemon's state, rewritten every cycle.
+    pub status_file: PathBuf,
+    /// Where the pet is saved between runs.
+    pub save_file: PathBuf,
+    /// Holds the note put up with `say`, so it survives a restart.
+    pub message_file: PathBuf,
+    /// Holds the pinned screen, likewise.
+    pub pin_file: PathBuf,
+    /// When each reminder last went off and was acknowledged.
+    pub reminders_file: PathBuf,
+    /// Settings saved from the on-panel menu, laid over this config.
+    pub overrides_file: PathBuf,
+    /// Today's and yesterday's tallies for the daily summary.
+    pub summary_file: PathBuf,
+    /// The pet's memory of the last couple of weeks.
+    pub journal_file: PathBuf,
+    /// Held while the daemon runs, so a second instance can't fight it
+    /// over the panel.
+    pub lock_file: PathBuf,
+    /// HTTP endpoint taking pushed metrics such as step counts.
+    pub push: PushConfig,
+    pub watchdog: WatchdogConfig,
+    pub summary: SummaryConfig,
+    pub selfie: SelfieConfig,
+    pub hooks: HooksConfig,
+    pub low_memory: LowMemoryConfig,
+    pub server: ServerConfig,
+    pub pet: PetConfig,
+    pub text: TextConfig,
+    pub simulator: SimulatorConfig,
+    #[cfg(feature = "update-check")]
+    pub update_check: UpdateCheckConfig,
+    #[cfg(feature = "rtc")]
+    pub rtc: RtcConfig,
+    #[cfg(feature = "github")]
+    pub github: GithubConfig,
+    #[cfg(feature = "ticker")]
+    pub ticker: TickerConfig,
+    #[cfg(feature = "weather")]
+    pub weather: WeatherConfig,
+    #[cfg(feature = "webhook")]
+    pub webhook: WebhookConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            refresh_interval: 60,
+            cadence: CadenceConfig::default(),
+            quiet_hours: None,
+            timezone: None,
+            // Bucharest.
+            latitude: 44.43,
+            longitude: 26.10,
+            supersample_text: true,
+            accessibility: AccessibilityConfig::default(),
+            partial_refresh: false,
+            mirror_x: false,
+            mirror_y: false,
+            rotation: 0,
+            anti_burn_in: false,
+            pre_clear: PreClearConfig::default(),
+            transition: Transition::None,
+            screens: vec!["pet".into(), "clock".into()],
+            layouts: example_layouts(),
+            artwork: None,
+            screen_dwell: 5,
+            pin_minutes: 0,
+            guest_minutes: 180,
+            reminders: Vec::new(),
+            reminder_minutes: 10,
+            reminder_repeat_minutes: 30,
+            reminder_missed_hours: 12,
+            hot_temperature: 65.0,
+            input: InputMode::Auto,
+            input_device: None,
+            control_socket: PathBuf::from("/tmp/inky-gotchi.sock"),
+            status_file: PathBuf::from("/tmp/inky-gotchi-status.json"),
+            save_file: PathBuf::from("pet.json"),
+            message_file: PathBuf::from("message.json"),
+            pin_file: PathBuf::from("pin.json"),
+            reminders_file: PathBuf::from("reminders.json"),
+            overrides_file: PathBuf::from("overrides.toml"),
+            summary_file: PathBuf::from("summary.json"),
+            journal_file: PathBuf::from("journal.json"),
+            lock_file: PathBuf::from("/run/inky-gotchi/inky-gotchi.lock"),
+            push: PushConfig::default(),
+            watchdog: WatchdogConfig::default(),
+            summary: SummaryConfig::default(),
+            selfie: SelfieConfig::default(),
+            hooks: HooksConfig::default(),
+            low_memory: LowMemoryConfig::default(),
+            server: ServerConfig::default(),
+            pet: PetConfig::default(),
+            text: TextConfig::default(),
+            simulator: SimulatorConfig::default(),
+            #[cfg(feature = "update-check")]
+            update_check: UpdateCheckConfig::default(),
+            #[cfg(feature = "rtc")]
+            rtc: RtcConfig::default(),
+            #[cfg(feature = "github")]
+            github: GithubConfig::default(),
+            #[cfg(feature = "ticker")]
+            ticker: TickerConfig::default(),
+            #[cfg(feature = "weather")]
+            weather: WeatherConfig::default(),
+            #[cfg(feature = "webhook")]
+            webhook: WebhookConfig::default(),
+        }
+    }
+}
+
+/// Layouts shipped with the daemon, ready to add to `screens`.
+pub const EXAMPLE_LAYOUTS: &str = r#"
+[care]
+elements = [
+    { kind = "text", text = "How's {name}?", size = "large", align = "center" },
+    { kind = "hline" },
+    { kind = "bar", value = "hunger", label = "Food" },
+    { kind = "bar", value = "happiness", label = "Joy" },
+    { kind = "bar", value = "energy", label = "Rest" },
+    { kind = "bar", value = "training", label = "Manners" },
+    { kind = "spacer", height = 4 },
+    { kind = "text", text = "day {day}, feeling {mood}", align = "center" },
+]
+
+[host]
+elements = [
+    { kind = "icon", icon = "wifi_strong", align = "center" },
+    { kind = "text", text = "{ip}", size = "large", align = "center" },
+    { kind = "hline" },
+    { kind = "text", text = "up {uptime}" },
+    { kind = "bar", value = "temp_c", label = "CPU", max = 85 },
+]
+"#;
+
+fn example_layouts() -> BTreeMap<String, LayoutConfig> {
+    toml::from_str(EXAMPLE_LAYOUTS).expect("example layouts parse")
+}
+
+fn layouts_over_examples<'de, D>(
+    deserializer: D,
+) -> Result<BTreeMap<String, LayoutConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut layouts = example_layouts();
+    layouts.extend(BTreeMap::deserialize(deserializer)?);
+    Ok(layouts)
+}
+
+/// A screen made of elements stacked from the top; see
+/// `screens::declarative` for what each kind draws.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutConfig {
+    pub elements: Vec<ElementConfig>,
+}
+
+/// One element of a layout. Which of the fields apply depends on `kind`;
+/// they're checked when the screen is built.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ElementConfig {
+    /// `text`, `bar`, `icon`, `spacer`, `hline` or `forecast`.
+    pub kind: String,
+    /// Template for a text element.
+    pub text: Option<String>,
+    /// Variable a bar shows, e.g. `hunger`.
+    pub value: Option<String>,
+    /// Range a bar spans; 0 to 100 unless set.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Shown to the left of a bar.
+    pub label: Option<String>,
+    pub icon: Option<String>,
+    /// Height of a spacer or forecast, in pixels.
+    pub height: Option<u32>,
+    /// `left`, `center` or `right`.
+    pub align: Option<String>,
+    /// `small` or `large`, for text and icons.
+    pub size: Option<String>,
+}
+
+/// How the refresh interval adapts to activity. All values in seconds.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CadenceConfig {
+    /// Shortest gap between refreshes, used right after an interaction.
+    pub min_spacing: u64,
+    /// How long to keep refreshing quickly after an interaction.
+    pub activity_window: u64,
+    /// Gap used once the frame has stopped changing.
+    pub idle_interval: u64,
+    /// Identical frames in a row before switching to the idle interval.
+    pub idle_after: u32,
+    /// Shortest gap between refreshes on battery, bar urgent ones; 0
+    /// refreshes as on mains.
+    pub battery_interval: u64,
+}
+
+impl Default for CadenceConfig {
+    fn default() -> Self {
+        CadenceConfig {
+            min_spacing: 15,
+            activity_window: 120,
+            idle_interval: 600,
+            idle_after: 5,
+            battery_interval: 600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PetConfig {
+    /// Name given to a newly hatched pet. Existing saves keep their name.
+    pub name: String,
+    /// Longest stretch, in hours, the pet ages across in one go. Guards
+    /// against a wildly wrong clock after a power cut.
+    pub max_offline_hours: u64,
+    /// Local time of the daily check that keeps the care streak.
+    #[serde(deserialize_with = "quiet::deserialize_time")]
+    pub daily_check: NaiveTime,
+}
+
+impl Default for PetConfig {
+    fn default() -> Self {
+        PetConfig {
+            name: "Mara".into(),
+            max_offline_hours: 48,
+            daily_check: NaiveTime::from_hms_opt(20, 0, 0).unwrap(),
+        }
+    }
+}
+
+/// Templated text shown on the screens; see `template` for the syntax.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TextConfig {
+    /// Left side of the pet screen's header.
+    pub header: Template,
+    /// Extra line in the clock screen's footer, empty by default.
+    pub footer: Template,
+    pub speech: SpeechConfig,
+    /// What the pet answers when talked to, see [`crate::chat`].
+    pub replies: RepliesConfig,
+    /// The words it picks out of what it's told.
+    pub keywords: KeywordsConfig,
+}
+
+impl Default for TextConfig {
+    fn default() -> Self {
+        TextConfig {
+            header: template("Buna {name}!"),
+            footer: template(""),
+            speech: SpeechConfig::default(),
+            replies: RepliesConfig::default(),
+            keywords: KeywordsConfig::default(),
+        }
+    }
+}
+
+/// What the pet says in its speech bubble for each mood.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SpeechConfig {
+    pub hungry: Template,
+    pub dirty: Template,
+    pub sleepy: Template,
+    pub bored: Template,
+}
+
+impl Default for SpeechConfig {
+    fn default() -> Self {
+        SpeechConfig {
+            hungry: template("Hungry!"),
+            dirty: template("Eww..."),
+            sleepy: template("Sleepy"),
+            bored: template("Bored!"),
+        }
+    }
+}
+
+/// The pet's answer to each kind of thing it's told.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RepliesConfig {
+    pub greeting: Template,
+    pub praise: Template,
+    pub scold: Template,
+    pub status: Template,
+    /// For anything it didn't understand.
+    pub confused: Template,
+}
+
+impl Default for RepliesConfig {
+    fn default() -> Self {
+        RepliesConfig {
+            greeting: template("Hi!"),
+            praise: template("Hehe, thanks!"),
+            scold: template("Sorry..."),
+            status: template("{hunger}% full, {happiness}% happy"),
+            confused: template("Huh?"),
+        }
+    }
+}
+
+/// Words and phrases that give away what the pet is being told, in any
+/// case. Where several match, the longest phrase wins.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct KeywordsConfig {
+    pub greeting: Vec<String>,
+    pub praise: Vec<String>,
+    pub scold: Vec<String>,
+    pub status: Vec<String>,
+}
+
+impl Default for KeywordsConfig {
+    fn default() -> Self {
+        let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect();
+        KeywordsConfig {
+            greeting: words(&[
+                "hi",
+                "hello",
+                "hey",
+                "buna",
+                "good morning",
+                "good afternoon",
+                "good evening",
+                "good night",
+            ]),
+            praise: words(&[
+                "good",
+                "good job",
+                "well done",
+                "clever",
+                "cute",
+                "love you",
+                "best",
+                "thanks",
+            ]),
+            scold: words(&["bad", "naughty", "stop it", "shame on you", "no no"]),
+            status: words(&[
+                "how are you",
+                "how do you feel",
+                "are you ok",
+                "are you hungry",
+                "status",
+            ]),
+        }
+    }
+}
+
+fn template(text: &str) -> Template {
+    Template::parse(text).expect("default template is valid")
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SimulatorConfig {
+    /// Where the simulator writes the rendered frame.
+    pub output: PathBuf,
+    /// Shade an artwork pack's greys in, in the simulator's frames, in
+    /// recordings and on the card. The panel can't show them either way.
+    pub greyscale: bool,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        SimulatorConfig {
+            output: PathBuf::from("frame.png"),
+            greyscale: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PushConfig {
+    /// Address to listen on, e.g. `0.0.0.0:8080`. Off unless set.
+    pub listen: Option<SocketAddr>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    pub enabled: bool,
+    /// Cycles the render loop may fall behind by before it counts as
+    /// stuck.
+    pub missed_cycles: u32,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        WatchdogConfig {
+            enabled: true,
+            missed_cycles: 3,
+        }
+    }
+}
+
+/// Before a refresh that changes most of the panel, its negative is shown
+/// to even out the pixels, so the old image doesn't ghost through. Costs
+/// an extra refresh.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PreClearConfig {
+    pub enabled: bool,
+    /// Share of what's drawn, from 0 to 1, that has to change.
+    pub threshold: f32,
+}
+
+impl Default for PreClearConfig {
+    fn default() -> Self {
+        PreClearConfig {
+            enabled: true,
+            threshold: 0.5,
+        }
+    }
+}
+
+impl PreClearConfig {
+    /// The threshold, if pre-clearing is on.
+    pub fn threshold(&self) -> Option<f32> {
+        self.enabled.then_some(self.threshold)
+    }
+}
+
+/// For people who find the panel hard to read.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AccessibilityConfig {
+    /// Draw all text in the large font and bars twice as tall, trading
+    /// the denser screens for simpler ones.
+    pub large_text: bool,
+}
+
+/// The look back at yesterday, put up once a day.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SummaryConfig {
+    pub enabled: bool,
+    /// Local time it goes up.
+    #[serde(deserialize_with = "quiet::deserialize_time")]
+    pub at: NaiveTime,
+    /// How long it stays up.
+    pub minutes: u32,
+}
+
+impl Default for SummaryConfig {
+    fn default() -> Self {
+        SummaryConfig {
+            enabled: true,
+            at: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            minutes: 5,
+        }
+    }
+}
+
+impl SummaryConfig {
+    /// When the summary is shown, if it's on at all.
+    pub fn window(&self) -> Option<Window> {
+        (self.enabled && self.minutes > 0).then(|| Window {
+            at: self.at,
+            length: chrono::Duration::minutes(self.minutes.min(24 * 60 - 1) as i64),
+        })
+    }
+}
+
+/// One of `[[reminders]]`, e.g. watering the plants on Tuesdays at 18:00.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "ReminderToml")]
+pub struct ReminderConfig {
+    pub text: String,
+    /// Local time it goes off.
+    pub at: NaiveTime,
+    /// Days it goes off, e.g. `["tue"]`; every day, without any.
+    pub days: Vec<Weekday>,
+    /// An [`Icon`] name, e.g. `leaf`; a bell, without one.
+    pub icon: Option<String>,
+}
+
+/// A reminder as written: `at` and maybe `days`, or `cron` instead.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReminderToml {
+    text: String,
+    at: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_days")]
+    days: Vec<Weekday>,
+    /// E.g. `0 18 * * tue`, see [`reminder::parse_cron`].
+    cron: Option<String>,
+    icon: Option<String>,
+}
+
+impl TryFrom<ReminderToml> for ReminderConfig {
+    type Error = String;
+
+    fn try_from(toml: ReminderToml) -> Result<Self, String> {
+        let (at, days) = match (toml.at, toml.cron) {
+            (Some(at), None) => (quiet::parse_time(&at)?, toml.days),
+            (None, Some(cron)) if toml.days.is_empty() => reminder::parse_cron(&cron)?,
+            (None, Some(_)) => return Err("days can't go with cron, which has its own".into()),
+            (Some(_), Some(_)) => return Err("either at or cron, not both".into()),
+            (None, None) => {
+                return Err("needs at, e.g. \"18:00\", or cron, e.g. \"0 18 * * tue\"".into())
+            }
+        };
+        Ok(ReminderConfig {
+            text: toml.text,
+            at,
+            days,
+            icon: toml.icon,
+        })
+    }
+}
+
+fn deserialize_days<'de, D>(deserializer: D) -> Result<Vec<Weekday>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|day| {
+            day.parse::<Weekday>()
+                .map_err(|_| serde::de::Error::custom(format!("{day:?} isn't a day, e.g. \"tue\"")))
+        })
+        .collect()
+}
+
+/// Photos from a Pi camera, see [`crate::camera`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SelfieConfig {
+    /// Program that takes the still; `rpicam-still` on newer Raspberry Pi
+    /// OS releases.
+    pub command: String,
+    /// Seconds the camera gets before it's given up on.
+    pub timeout: u64,
+    /// How long the photo stays up.
+    pub minutes: u32,
+    /// Where photos are saved.
+    pub dir: PathBuf,
+    /// Put the red parts of a photo on the red plane.
+    pub tricolor: bool,
+    /// How far from pure red, in degrees of hue, still counts as red.
+    pub red_hue: f32,
+}
+
+impl Default for SelfieConfig {
+    fn default() -> Self {
+        SelfieConfig {
+            command: "libcamera-still".into(),
+            timeout: 10,
+            minutes: 3,
+            dir: PathBuf::from("selfies"),
+            tricolor: false,
+            red_hue: 20.0,
+        }
+    }
+}
+
+/// Cutting rendering back when memory runs short, see [`crate::lowmem`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LowMemoryConfig {
+    /// Available memory in MB below which the costlier extras are skipped.
+    pub reduced_mb: u64,
+    /// Below this, everything that can go does.
+    pub minimal_mb: u64,
+}
+
+impl Default for LowMemoryConfig {
+    fn default() -> Self {
+        LowMemoryConfig {
+            reduced_mb: 64,
+            minimal_mb: 32,
+        }
+    }
+}
+
+/// Keeping an eye on the host as a server, see [`crate::server`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Greet people logging in over SSH.
+    pub logins: bool,
+    /// Check once a day for package upgrades waiting.
+    pub upgrades: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            logins: true,
+            upgrades: true,
+        }
+    }
+}
+
+/// Commands run when something happens to the pet, see [`crate::hooks`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// Seconds a hook may run before it's killed.
+    pub timeout: u64,
+    /// Shell command for each event, by the event's name.
+    #[serde(flatten)]
+    pub commands: BTreeMap<String, String>,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        HooksConfig {
+            timeout: 30,
+            commands: BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(feature = "update-check")]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UpdateCheckConfig {
+    pub enabled: bool,
+    /// GitHub "latest release" API endpoint.
+    pub url: String,
+    /// Remembers the last answer so restarts don't re-query.
+    pub cache_file: PathBuf,
+}
+
+#[cfg(feature = "update-check")]
+impl Default for UpdateCheckConfig {
+    fn default() -> Self {
+        UpdateCheckConfig {
+            enabled: true,
+            url: "https://api.github.com/repos/BanuCalin/inky-gotchi/releases/latest".into(),
+            cache_file: PathBuf::from("update-check.json"),
+        }
+    }
+}
+
+#[cfg(feature = "webhook")]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Where the weekly report is POSTed; no report while unset.
+    pub url: Option<String>,
+    pub format: crate::webhook::Format,
+    /// Local time on Sundays the report goes out.
+    #[serde(deserialize_with = "quiet::deserialize_time")]
+    pub at: NaiveTime,
+    /// The last week sent, and any report still waiting to go.
+    pub state_file: PathBuf,
+}
+
+#[cfg(feature = "webhook")]
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            url: None,
+            format: crate::webhook::Format::default(),
+            at: NaiveTime::from_hms_opt(19, 0, 0).unwrap(),
+            state_file: PathBuf::from("webhook.json"),
+        }
+    }
+}
+
+#[cfg(feature = "rtc")]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RtcConfig {
+    pub enabled: bool,
+    /// I2C bus the DS3231 is on.
+    pub bus: PathBuf,
+    /// Seconds the system clock may be off from the RTC before the RTC is
+    /// trusted instead, while NTP isn't synchronised.
+    pub max_drift: u64,
+}
+
+#[cfg(feature = "rtc")]
+impl Default for RtcConfig {
+    fn default() -> Self {
+        RtcConfig {
+            enabled: true,
+            bus: PathBuf::from("/dev/i2c-1"),
+            max_drift: 120,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config at `path`, or the defaults when no path is given.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let Some(path) = path else {
+            return Ok(Config::default());
+        };
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading config {}", path.display()))?;
+        let config: Config =
+            toml::from_str(&text).with_context(|| format!("parsing config {}", path.display()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let Some(feature) = self.input.missing_feature() {
+            anyhow::bail!(
+                "input = {:?} needs the {feature} feature, which this build doesn't have",
+                self.input.name()
+            );
+        }
+        anyhow::ensure!(
+            matches!(self.rotation, 0 | 180),
+            "rotation must be 0 or 180, got {}",
+            self.rotation
+        );
+        anyhow::ensure!(
+            (-90.0..=90.0).contains(&self.latitude),
+            "latitude must be between -90 and 90, got {}",
+            self.latitude
+        );
+        anyhow::ensure!(
+            (-180.0..=180.0).contains(&self.longitude),
+            "longitude must be between -180 and 180, got {}",
+            self.longitude
+        );
+        anyhow::ensure!(
+            self.refresh_interval > 0,
+            "refresh_interval must be positive"
+        );
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&self.pre_clear.threshold),
+            "pre_clear.threshold must be between 0 and 1, got {}",
+            self.pre_clear.threshold
+        );
+        anyhow::ensure!(
+            self.watchdog.missed_cycles > 0,
+            "watchdog.missed_cycles must be positive"
+        );
+        anyhow::ensure!(
+            (0.0..180.0).contains(&self.selfie.red_hue),
+            "selfie.red_hue must be from 0 up to 180 degrees"
+        );
+        anyhow::ensure!(
+            self.low_memory.minimal_mb <= self.low_memory.reduced_mb,
+            "low_memory.minimal_mb can't be above low_memory.reduced_mb"
+        );
+        anyhow::ensure!(
+            self.reminder_minutes == 0 || self.reminder_repeat_minutes > 0,
+            "reminder_repeat_minutes must be positive"
+        );
+        for (index, reminder) in self.reminders.iter().enumerate() {
+            let text = reminder.text.trim();
+            anyhow::ensure!(!text.is_empty(), "reminders[{index}]: text is empty");
+            anyhow::ensure!(
+                text.chars().count() <= message::MAX_LENGTH,
+                "reminders[{index}]: text is over {} characters",
+                message::MAX_LENGTH
+            );
+            // The text is what the saved state knows a reminder by.
+            anyhow::ensure!(
+                !self.reminders[..index]
+                    .iter()
+                    .any(|r| r.text.trim() == text),
+                "reminders[{index}]: {text:?} is there twice"
+            );
+            if let Some(name) = reminder
+                .icon
+                .as_deref()
+                .filter(|n| Icon::parse(n).is_none())
+            {
+                anyhow::bail!("reminders[{index}]: unknown icon {name:?}");
+            }
+        }
+        if let Some(name) = self
+            .hooks
+            .commands
+            .keys()
+            .find(|name| PetEvent::parse(name).is_none())
+        {
+            anyhow::bail!("hooks.{name}: no such event, see --list-events");
+        }
+        let (speech, replies) = (&self.text.speech, &self.text.replies);
+        let templates = [
+            ("text.header", &self.text.header),
+            ("text.footer", &self.text.footer),
+            ("text.speech.hungry", &speech.hungry),
+            ("text.speech.dirty", &speech.dirty),
+            ("text.speech.sleepy", &speech.sleepy),
+            ("text.speech.bored", &speech.bored),
+            ("text.replies.greeting", &replies.greeting),
+            ("text.replies.praise", &replies.praise),
+            ("text.replies.scold", &replies.scold),
+            ("text.replies.status", &replies.status),
+            ("text.replies.confused", &replies.confused),
+        ];
+        for (key, template) in templates {
+            for name in template.unknown_variables() {
+                log::warn!("{key}: unknown variable {{{name}}} will be shown as is");
+            }
+        }
+        for (name, layout) in &self.layouts {
+            anyhow::ensure!(
+                screens::by_name(name).is_none(),
+                "layouts.{name}: clashes with the built-in screen of that name"
+            );
+            DeclarativeScreen::check(name, layout)?;
+        }
+        #[cfg(feature = "ticker")]
+        self.ticker.validate()?;
+        #[cfg(feature = "weather")]
+        self.weather.validate()?;
+        #[cfg(feature = "webhook")]
+        if let Some(url) = &self.webhook.url {
+            anyhow::ensure!(
+                url.starts_with("http://") || url.starts_with("https://"),
+                "webhook.url must be an http:// or https:// URL"
+            );
+        }
+        Ok(())
+    }
+
+    /// The flips for `mirror_x` and `mirror_y` on a panel mounted at
+    /// `rotation`. Half a turn is the same as flipping both ways, so it
+    /// comes out as flips too.
+    pub fn mirror(&self) -> Mirror {
+        let turned = self.rotation == 180;
+        Mirror {
+            x: self.mirror_x != turned,
+            y: self.mirror_y != turned,
+        }
+    }
+}
+
+#[cfg(feature = "github")]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GithubConfig {
+    /// Personal access token; needed for notifications. Falls back to the
+    /// `INKY_GOTCHI_GITHUB_TOKEN` environment variable.
+    pub token: Option<Secret>,
+    /// `owner/name` of the repository whose CI to follow.
+    pub repo: Option<String>,
+}
+
+/// A string that stays out of logs: `Debug` only says whether it's set.
+#[cfg(feature = "github")]
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+#[cfg(feature = "github")]
+impl Secret {
+    pub fn new(value: String) -> Self {
+        Secret(value)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(feature = "github")]
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+#[cfg(feature = "ticker")]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TickerConfig {
+    /// Seconds between fetches.
+    pub interval: u64,
+    /// Shown on the `ticker` screen, at most two.
+    pub symbols: Vec<TickerSymbol>,
+}
+
+#[cfg(feature = "ticker")]
+impl Default for TickerConfig {
+    fn default() -> Self {
+        TickerConfig {
+            interval: 300,
+            symbols: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "ticker")]
+impl TickerConfig {
+    /// Symbols the screen has room for.
+    pub const MAX_SYMBOLS: usize = 2;
+
+    fn validate(&self) -> Result<()> {
+        anyhow::ensure!(self.interval > 0, "ticker.interval must be positive");
+        anyhow::ensure!(
+            self.symbols.len() <= Self::MAX_SYMBOLS,
+            "ticker.symbols has room for {} entries, got {}",
+            Self::MAX_SYMBOLS,
+            self.symbols.len()
+        );
+        for (index, symbol) in self.symbols.iter().enumerate() {
+            for (field, pointer) in [
+                ("price", Some(&symbol.price)),
+                ("change", symbol.change.as_ref()),
+            ] {
+                if let Some(pointer) = pointer.filter(|p| !p.is_empty() && !p.starts_with('/')) {
+                    anyhow::bail!(
+                        "ticker.symbols[{index}].{field}: JSON pointer {pointer:?} must start with /"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One quote, pulled out of any JSON endpoint with JSON pointers.
+#[cfg(feature = "ticker")]
+#[derive(Debug, Clone, Deserialize)]
+pub struct TickerSymbol {
+    /// Shown next to the price, e.g. `BTC`.
+    pub label: String,
+    pub url: String,
+    /// Where the price is in the response, e.g. `/bitcoin/usd`.
+    pub price: String,
+    /// Where the 24 h change in percent is, if the endpoint has it.
+    pub change: Option<String>,
+}
+
+#[cfg(feature = "weather")]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WeatherConfig {
+    /// Open-Meteo's forecast endpoint, or a self-hosted one. The position
+    /// comes from `latitude` and `longitude`.
+    pub url: String,
+    /// Seconds between fetches.
+    pub interval: u64,
+}
+
+#[cfg(feature = "weather")]
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        WeatherConfig {
+            url: "https://api.open-meteo.com/v1/forecast".into(),
+            interval: 30 * 60,
+        }
+    }
+}
+
+#[cfg(feature = "weather")]
+impl WeatherConfig {
+    fn validate(&self) -> Result<()> {
+        anyhow::ensure!(self.interval > 0, "weather.interval must be positive");
+        anyhow::ensure!(
+            self.url.starts_with("http://") || self.url.starts_with("https://"),
+            "weather.url must be an http:// or https:// URL, got {:?}",
+            self.url
+        );
+        Ok(())
+    }
+}
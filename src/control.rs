@@ -0,0 +1,170 @@
+//! Unix socket accepting one command per line, e.g.
+//! `echo feed | socat - UNIX-CONNECT:/tmp/inky-gotchi.sock`.
+
+use std::io::{BufRead, BufReader, Write};
+#[cfg(not(feature = "async"))]
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+#[cfg(not(feature = "async"))]
+use std::sync::mpsc::Sender;
+#[cfg(not(feature = "async"))]
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{bail, Context as _, Result};
+
+use crate::buttons::{Button, Press};
+use crate::events::Command;
+use crate::message::Message;
+use crate::screens;
+use crate::transfer::Transfer;
+
+/// Binds the socket and forwards parsed commands to `tx` from a background
+/// thread. `press <button>` and `hold <button>` stand in for physical
+/// button presses and go to `presses` instead. Commands naming a screen
+/// have to name one of `screens`.
+#[cfg(not(feature = "async"))]
+pub fn spawn(
+    path: &Path,
+    tx: Sender<Command>,
+    presses: Sender<Press>,
+    screens: Vec<String>,
+) -> Result<()> {
+    remove_stale(path)?;
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("binding {}", path.display()))?;
+    log::info!("listening for commands on {}", path.display());
+    thread::Builder::new()
+        .name("control".into())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(err) = serve(stream, &tx, &presses, &screens) {
+                            log::warn!("control connection failed: {err}");
+                        }
+                    }
+                    Err(err) => log::warn!("control accept failed: {err}"),
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Sends `line` to the daemon on `socket` and waits for it to be taken.
+/// Returns `false` if no daemon is listening.
+pub fn send(socket: &Path, line: &str) -> Result<bool> {
+    let stream = match UnixStream::connect(socket) {
+        Ok(stream) => stream,
+        Err(err)
+            if matches!(
+                err.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(false)
+        }
+        Err(err) => return Err(err).with_context(|| format!("connecting to {}", socket.display())),
+    };
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    writeln!(&stream, "{line}")?;
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    match reply.trim() {
+        "ok" => Ok(true),
+        reply => bail!("the daemon said: {reply}"),
+    }
+}
+
+/// Clears away the socket left behind by a previous run.
+pub fn remove_stale(path: &Path) -> Result<()> {
+    if path.exists() {
+        std::fs::remove_file(path)
+            .with_context(|| format!("removing stale socket {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "async"))]
+fn serve(
+    stream: UnixStream,
+    tx: &Sender<Command>,
+    presses: &Sender<Press>,
+    screens: &[String],
+) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let sent = match Request::parse(&line, screens) {
+            Ok(Request::Press(press)) => presses.send(press).is_ok(),
+            Ok(Request::Command(command)) => tx.send(command).is_ok(),
+            Err(reply) => {
+                writeln!(writer, "{reply}")?;
+                continue;
+            }
+        };
+        if !sent {
+            // The main loop is gone; we're shutting down.
+            return Ok(());
+        }
+        writeln!(writer, "ok")?;
+    }
+    Ok(())
+}
+
+/// One line from the socket, understood.
+#[derive(Clone, Debug)]
+pub enum Request {
+    Command(Command),
+    Press(Press),
+}
+
+impl Request {
+    /// Parses a non-blank line, logging it. The error is the reply to send
+    /// back.
+    pub fn parse(line: &str, screens: &[String]) -> Result<Request, String> {
+        let press = match line.trim().split_once(' ') {
+            Some(("press", name)) => Some((name, false)),
+            Some(("hold", name)) => Some((name, true)),
+            _ => None,
+        };
+        let request = if let Some((name, long)) = press {
+            match Button::parse(name) {
+                Some(button) => Request::Press(Press { button, long }),
+                None => return Err(format!("error: unknown button {:?}", name.trim())),
+            }
+        } else if let Some(code) = line.trim_start().strip_prefix("import ") {
+            // Not trimmed at the end: a code can end in a space.
+            let code = code.trim_end_matches(['\r', '\n']);
+            let pet = Transfer::decode(code).map_err(|err| format!("error: bad code: {err:#}"))?;
+            Request::Command(Command::Import(pet))
+        } else if let Some(json) = line.trim().strip_prefix("say ") {
+            let message: Message =
+                serde_json::from_str(json).map_err(|err| format!("error: bad message: {err}"))?;
+            message.validate().map_err(|err| format!("error: {err}"))?;
+            Request::Command(Command::Say(message))
+        } else {
+            let command = Command::parse(line)
+                .ok_or_else(|| format!("error: unknown command {:?}", line.trim()))?;
+            if let Command::PinScreen(name) | Command::SetScreen(name, true) = &command {
+                screens::check_name(name, screens).map_err(|err| format!("error: {err}"))?;
+            }
+            Request::Command(command)
+        };
+        match &request {
+            Request::Press(Press {
+                button,
+                long: false,
+            }) => log::info!("control: press {button:?}"),
+            Request::Press(Press { button, long: true }) => {
+                log::info!("control: hold {button:?}")
+            }
+            Request::Command(command) => log::info!("control: {command:?}"),
+        }
+        Ok(request)
+    }
+}
@@ -0,0 +1,1165 @@
+//! Tri-colour framebuffer matching the Inky pHAT's black and red planes.
+
+use std::convert::Infallible;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Utc};
+use embedded_graphics::pixelcolor::PixelColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use serde::Serialize;
+
+use crate::rle;
+use crate::transition::{self, Transition};
+
+/// Visible width of the Inky pHAT in landscape orientation.
+pub const WIDTH: u32 = 212;
+/// Visible height of the Inky pHAT in landscape orientation.
+pub const HEIGHT: u32 = 104;
+/// How often a panel that stopped answering is tried again.
+const PROBE_INTERVAL: Duration = Duration::from_secs(180);
+/// Shortest time from one update of the panel to a full refresh, however
+/// hard the rest of the daemon asks: refreshing in a tight loop wears the
+/// panel out. See [`Guard`].
+pub const MIN_FULL_SPACING: Duration = Duration::from_secs(10);
+/// Shortest time from one update of the panel to a partial refresh.
+pub const MIN_PARTIAL_SPACING: Duration = Duration::from_secs(1);
+
+/// The three colours the panel can show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    White,
+    Black,
+    Red,
+}
+
+impl PixelColor for Color {
+    type Raw = ();
+}
+
+/// One bit per pixel for each plane, rows padded to whole bytes.
+///
+/// A set bit means "ink": black in the black plane, red in the red plane.
+/// Red wins when both are set, which is also what the controller does.
+///
+/// A third plane, of shades, is never sent to the panel: it lightens
+/// black to dark grey and darkens white to light grey in previews, for
+/// artwork drawn in grey, see [`crate::artwork`]. Drawing over a pixel
+/// clears it.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Frame {
+    width: u32,
+    height: u32,
+    black: Vec<u8>,
+    red: Vec<u8>,
+    shade: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = Self::stride_for(width) * height as usize;
+        Frame {
+            width,
+            height,
+            black: vec![0; len],
+            red: vec![0; len],
+            shade: vec![0; len],
+        }
+    }
+
+    fn stride_for(width: u32) -> usize {
+        width.div_ceil(8) as usize
+    }
+
+    pub fn stride(&self) -> usize {
+        Self::stride_for(self.width)
+    }
+
+    /// Bytes taken by the planes.
+    pub fn footprint(&self) -> usize {
+        self.black.len() + self.red.len() + self.shade.len()
+    }
+
+    fn planes(&self) -> [&[u8]; 3] {
+        [&self.black, &self.red, &self.shade]
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: Color) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+        let idx = y as usize * self.stride() + x as usize / 8;
+        let bit = 0x80 >> (x % 8);
+        let (black, red) = match color {
+            Color::White => (false, false),
+            Color::Black => (true, false),
+            Color::Red => (false, true),
+        };
+        set_bit(&mut self.black[idx], bit, black);
+        set_bit(&mut self.red[idx], bit, red);
+        set_bit(&mut self.shade[idx], bit, false);
+    }
+
+    /// Greys the pixel at `x`, `y` in previews, unless it's red.
+    pub fn shade(&mut self, x: i32, y: i32) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+        if self.pixel(x, y) == Color::Red {
+            return;
+        }
+        let idx = y as usize * self.stride() + x as usize / 8;
+        set_bit(&mut self.shade[idx], 0x80 >> (x % 8), true);
+    }
+
+    pub fn is_shaded(&self, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return false;
+        }
+        let idx = y as usize * self.stride() + x as usize / 8;
+        self.shade[idx] & (0x80 >> (x % 8)) != 0
+    }
+
+    /// Copies the pixel at `from` in `source` to `x`, `y`, shade and all.
+    fn copy_pixel(&mut self, x: i32, y: i32, source: &Frame, from: Point) {
+        self.set_pixel(x, y, source.pixel(from.x, from.y));
+        if source.is_shaded(from.x, from.y) {
+            self.shade(x, y);
+        }
+    }
+
+    pub fn pixel(&self, x: i32, y: i32) -> Color {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return Color::White;
+        }
+        let idx = y as usize * self.stride() + x as usize / 8;
+        let bit = 0x80 >> (x % 8);
+        if self.red[idx] & bit != 0 {
+            Color::Red
+        } else if self.black[idx] & bit != 0 {
+            Color::Black
+        } else {
+            Color::White
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.black.fill(0);
+        self.red.fill(0);
+        self.shade.fill(0);
+    }
+
+    /// This frame flipped as `mirror` says.
+    pub fn mirrored(&self, mirror: Mirror) -> Frame {
+        let mut out = Frame::new(self.width, self.height);
+        let (width, height) = (self.width as i32, self.height as i32);
+        for y in 0..height {
+            let from_y = if mirror.y { height - 1 - y } else { y };
+            for x in 0..width {
+                let from_x = if mirror.x { width - 1 - x } else { x };
+                out.copy_pixel(x, y, self, Point::new(from_x, from_y));
+            }
+        }
+        out
+    }
+
+    /// This frame moved by `offset`, leaving white behind. Whatever moves
+    /// past an edge is lost.
+    pub fn shifted(&self, offset: Point) -> Frame {
+        let mut out = Frame::new(self.width, self.height);
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                let (from_x, from_y) = (x - offset.x, y - offset.y);
+                if (0..self.width as i32).contains(&from_x)
+                    && (0..self.height as i32).contains(&from_y)
+                {
+                    out.copy_pixel(x, y, self, Point::new(from_x, from_y));
+                }
+            }
+        }
+        out
+    }
+
+    /// The negative of this frame, for wiping out ghosting: black and red
+    /// turn white, white turns black. Red can't be the inverse of anything,
+    /// so it's never produced.
+    pub fn inverted(&self) -> Frame {
+        let mut out = Frame::new(self.width, self.height);
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                let color = match self.pixel(x, y) {
+                    Color::White => Color::Black,
+                    Color::Black | Color::Red => Color::White,
+                };
+                out.set_pixel(x, y, color);
+            }
+        }
+        out
+    }
+}
+
+/// How much of the content differs between `old` and `new`: of the pixels
+/// inked in either, the share that changed, from 0 to 1. Measured against
+/// the whole panel, even a complete change of screen would barely count,
+/// since most of it is white paper.
+pub fn changed_fraction(old: &Frame, new: &Frame) -> f32 {
+    let (mut inked, mut changed) = (0, 0);
+    for y in 0..new.height as i32 {
+        for x in 0..new.width as i32 {
+            let (a, b) = (old.pixel(x, y), new.pixel(x, y));
+            if a != Color::White || b != Color::White {
+                inked += 1;
+                changed += (a != b) as u32;
+            }
+        }
+    }
+    match inked {
+        0 => 0.0,
+        _ => changed as f32 / inked as f32,
+    }
+}
+
+/// Flips applied to the finished image, after everything is drawn, so
+/// screens don't need to know about them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Mirror {
+    /// Left to right.
+    pub x: bool,
+    /// Top to bottom.
+    pub y: bool,
+}
+
+impl Mirror {
+    pub fn is_none(self) -> bool {
+        !self.x && !self.y
+    }
+}
+
+/// A [`Frame`] kept run-length encoded, for remembering what the panel
+/// shows between updates, see [`crate::rle`]. A picture is mostly white
+/// paper, so the copy the display keeps takes a fraction of the memory.
+#[derive(Clone)]
+pub struct PackedFrame {
+    width: u32,
+    height: u32,
+    /// The black, red and shade planes.
+    planes: rle::Packed,
+}
+
+impl PackedFrame {
+    pub fn new(frame: &Frame) -> Self {
+        PackedFrame {
+            width: frame.width,
+            height: frame.height,
+            planes: rle::Packed::new(&frame.planes(), frame.stride()),
+        }
+    }
+
+    /// The frame in full again.
+    pub fn unpack(&self) -> Frame {
+        let mut frame = Frame::new(self.width, self.height);
+        let [black, red, shade]: [Vec<u8>; 3] = self
+            .planes
+            .unpack()
+            .try_into()
+            .expect("packed from three planes");
+        (frame.black, frame.red, frame.shade) = (black, red, shade);
+        frame
+    }
+
+    /// Whether `frame` is the frame packed.
+    pub fn matches(&self, frame: &Frame) -> bool {
+        self.width == frame.width
+            && self.height == frame.height
+            && self.planes.matches(&frame.planes())
+    }
+
+    /// Smallest rectangle covering every pixel that differs between this
+    /// frame and `new`, widened to whole bytes horizontally since that's
+    /// the granularity the controller's RAM window uses. `None` when the
+    /// frames are identical.
+    pub fn dirty_bounds(&self, new: &Frame) -> Option<Rectangle> {
+        self.planes.dirty(&new.planes()).map(|(c0, r0, c1, r1)| {
+            let x0 = c0 as u32 * 8;
+            let x1 = ((c1 as u32 + 1) * 8).min(new.width);
+            Rectangle::new(
+                Point::new(x0 as i32, r0 as i32),
+                Size::new(x1 - x0, (r1 - r0 + 1) as u32),
+            )
+        })
+    }
+
+    /// Bytes taken by the packed planes.
+    pub fn footprint(&self) -> usize {
+        self.planes.footprint()
+    }
+}
+
+fn set_bit(byte: &mut u8, bit: u8, on: bool) {
+    if on {
+        *byte |= bit;
+    } else {
+        *byte &= !bit;
+    }
+}
+
+impl OriginDimensions for Frame {
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
+
+impl DrawTarget for Frame {
+    type Color = Color;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            self.set_pixel(point.x, point.y, color);
+        }
+        Ok(())
+    }
+}
+
+/// What we're drawing to, for the about screen and status file.
+#[derive(Clone, Debug, Serialize)]
+pub struct PanelInfo {
+    pub name: &'static str,
+    pub width: u32,
+    pub height: u32,
+    /// Degrees clockwise.
+    pub rotation: u16,
+    /// Waveform profile in use, if the panel has one.
+    pub lut: &'static str,
+}
+
+impl PanelInfo {
+    pub fn simulator() -> Self {
+        PanelInfo {
+            name: "simulator",
+            width: WIDTH,
+            height: HEIGHT,
+            rotation: 0,
+            lut: "none",
+        }
+    }
+}
+
+/// Furthest the picture is moved against burn-in, in each direction.
+pub const JITTER_MAX: i32 = 2;
+
+/// Where the picture goes against burn-in at `now`: a step to the next
+/// spot on a 5×5 grid every hour, snaking across it so it never jumps far.
+pub fn jitter_offset(now: DateTime<Utc>) -> Point {
+    let side = 2 * JITTER_MAX + 1;
+    let step = (now.timestamp().div_euclid(3600) % (side * side) as i64) as i32;
+    let (row, column) = (step / side, step % side);
+    let column = if row % 2 == 0 {
+        column
+    } else {
+        side - 1 - column
+    };
+    Point::new(column - JITTER_MAX, row - JITTER_MAX)
+}
+
+/// Keeps updates of the panel at least [`MIN_FULL_SPACING`] apart, or
+/// [`MIN_PARTIAL_SPACING`] for a partial refresh. An update asked for too
+/// soon is held back and counted; whatever is drawn by the time the
+/// window opens goes out instead, so a burst of them comes to one.
+///
+/// It sits below the cadence, which already spaces refreshes out, to stop
+/// a bug somewhere above from hammering the panel.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Guard {
+    /// When the panel last took an update.
+    last: Option<Instant>,
+    /// When the update held back can go, while there is one.
+    held: Option<Instant>,
+    /// How many updates were held back and folded into a later one.
+    coalesced: u64,
+}
+
+impl Guard {
+    /// Whether an update, `partial` or not, can go out at `now`. One that
+    /// can't is counted as held back.
+    pub fn admit(&mut self, now: Instant, partial: bool) -> bool {
+        let spacing = match partial {
+            true => MIN_PARTIAL_SPACING,
+            false => MIN_FULL_SPACING,
+        };
+        match self.last.map(|last| last + spacing) {
+            Some(opens) if now < opens => {
+                self.held = Some(opens);
+                self.coalesced += 1;
+                false
+            }
+            _ => {
+                self.held = None;
+                true
+            }
+        }
+    }
+
+    /// Notes that the panel took an update at `now`.
+    pub fn sent(&mut self, now: Instant) {
+        self.last = Some(now);
+        self.held = None;
+    }
+
+    pub fn held(&self) -> Option<Instant> {
+        self.held
+    }
+
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced
+    }
+}
+
+/// Stand-in for the panel that writes every new frame to a PNG.
+pub struct Simulator {
+    output: PathBuf,
+    partial_refresh: bool,
+    mirror: Mirror,
+    /// Last frame as drawn by the screens, before any decorations.
+    last: Option<PackedFrame>,
+    /// Last frame actually shown, decorations included.
+    shown: Option<PackedFrame>,
+    asleep: bool,
+    /// Set while the panel isn't answering.
+    degraded: Option<Degraded>,
+    /// For the next update.
+    transition: Option<Transition>,
+    /// Share of the panel that has to change for the old image to be
+    /// cleared first; never, without one.
+    pre_clear: Option<f32>,
+    /// Whether the last update did that.
+    pre_cleared: bool,
+    /// How far the picture is moved against burn-in.
+    jitter: Point,
+    /// Set when the jitter moved since the last update, which then has to
+    /// redraw the whole panel.
+    jitter_moved: bool,
+    guard: Guard,
+}
+
+struct Degraded {
+    since: DateTime<Utc>,
+    next_probe: Instant,
+}
+
+/// Whether the panel is taking updates, for the status file.
+#[derive(Clone, Debug, Serialize)]
+pub struct LinkStatus {
+    pub degraded: bool,
+    /// When it stopped answering.
+    pub degraded_since: Option<DateTime<Utc>>,
+}
+
+impl Simulator {
+    pub fn new(
+        output: PathBuf,
+        partial_refresh: bool,
+        mirror: Mirror,
+        pre_clear: Option<f32>,
+    ) -> Self {
+        Simulator {
+            output,
+            partial_refresh,
+            mirror,
+            last: None,
+            shown: None,
+            asleep: false,
+            degraded: None,
+            transition: None,
+            pre_clear,
+            pre_cleared: false,
+            jitter: Point::zero(),
+            jitter_moved: false,
+            guard: Guard::default(),
+        }
+    }
+
+    /// Starts over as if just opened, e.g. after a stall. The refresh
+    /// guard carries on, so this is no way around it.
+    pub fn reopen(&mut self) {
+        let guard = self.guard;
+        *self = Simulator::new(
+            self.output.clone(),
+            self.partial_refresh,
+            self.mirror,
+            self.pre_clear,
+        );
+        self.guard = guard;
+    }
+
+    /// Changes the share of the panel that has to change for a pre-clear,
+    /// or turns pre-clearing off with `None`.
+    pub fn set_pre_clear(&mut self, threshold: Option<f32>) {
+        self.pre_clear = threshold;
+    }
+
+    /// When the update the refresh guard held back in the last
+    /// [`diff`](Simulator::diff) can go, if it did.
+    pub fn held(&self) -> Option<Instant> {
+        self.guard.held()
+    }
+
+    /// How many updates the refresh guard has held back since start.
+    pub fn coalesced(&self) -> u64 {
+        self.guard.coalesced()
+    }
+
+    /// Puts the panel into deep sleep. The image stays on screen; the next
+    /// update wakes it again.
+    pub fn sleep(&mut self) {
+        if !self.asleep {
+            log::info!("panel entering deep sleep");
+            self.asleep = true;
+        }
+    }
+
+    pub fn info(&self) -> PanelInfo {
+        PanelInfo::simulator()
+    }
+
+    pub fn link_status(&self) -> LinkStatus {
+        LinkStatus {
+            degraded: self.degraded.is_some(),
+            degraded_since: self.degraded.as_ref().map(|d| d.since),
+        }
+    }
+
+    /// Has the next update go through `effect`, if it refreshes at all.
+    pub fn transition(&mut self, effect: Transition) {
+        self.transition = Some(effect).filter(|&effect| effect != Transition::None);
+    }
+
+    /// Whether the last update cleared the panel before drawing, an extra
+    /// refresh on top of the one it reported.
+    pub fn pre_cleared(&self) -> bool {
+        self.pre_cleared
+    }
+
+    /// Moves the whole picture by `offset` from the next update on, after
+    /// everything is drawn, so screens don't know about it. Returns whether
+    /// it moved, in which case the next update redraws the whole panel.
+    pub fn jitter(&mut self, offset: Point) -> bool {
+        if offset == self.jitter {
+            return false;
+        }
+        log::info!(
+            "moving the picture to ({}, {}) against burn-in",
+            offset.x,
+            offset.y
+        );
+        self.jitter = offset;
+        self.jitter_moved = true;
+        self.invalidate();
+        true
+    }
+
+    /// Forces the next update to refresh even if the frame is unchanged.
+    pub fn invalidate(&mut self) {
+        self.last = None;
+    }
+
+    /// Whether `frame` is what the panel already shows, before decoration.
+    pub fn is_showing(&self, frame: &Frame) -> bool {
+        self.last.as_ref().is_some_and(|last| last.matches(frame))
+    }
+
+    /// `frame` as the panel would show it: see [`compose`].
+    pub fn compose(&self, frame: &Frame, decorate: impl FnOnce(&mut Frame)) -> Frame {
+        compose(frame, decorate, self.jitter, self.mirror)
+    }
+
+    /// Shows `frame` with `decorate` drawn on top, and then mirrored,
+    /// returning whether the panel was refreshed. The same as
+    /// [`compose`](Simulator::compose), [`diff`](Simulator::diff) and
+    /// [`transfer`](Simulator::transfer) one after the other.
+    pub fn update(&mut self, frame: &Frame, decorate: impl FnOnce(&mut Frame)) -> Result<bool> {
+        let output = self.compose(frame, decorate);
+        match self.diff(frame, output) {
+            Some(update) => self.transfer(update),
+            None => Ok(false),
+        }
+    }
+
+    /// Works out what it takes to get from what the panel shows to
+    /// `output`, composed from `frame`, without touching the panel. `None`
+    /// means there's nothing to send.
+    ///
+    /// Whether anything changed is judged on `frame` alone, so decorations
+    /// that differ every cycle (like the debug overlay's counters) ride
+    /// along with real changes instead of forcing refreshes by themselves.
+    ///
+    /// An update the refresh guard holds back is `None` too, and leaves the
+    /// transition and a jitter move for the one that goes out.
+    pub fn diff(&mut self, frame: &Frame, output: Frame) -> Option<Update> {
+        self.pre_cleared = false;
+        self.guard.held = None;
+        if self.is_showing(frame) {
+            self.transition = None;
+            log::debug!("frame unchanged, skipping update");
+            return None;
+        }
+        if let Some(degraded) = &self.degraded {
+            if Instant::now() < degraded.next_probe {
+                self.transition = None;
+                log::debug!("panel not answering, skipping update");
+                return None;
+            }
+        }
+        // Everything moved, so nothing partial about it.
+        let dirty = match &self.shown {
+            Some(shown) if self.partial_refresh && !self.jitter_moved => {
+                shown.dirty_bounds(&output)
+            }
+            _ => None,
+        };
+        let partial = dirty.is_some() && self.transition.is_none();
+        if !self.guard.admit(Instant::now(), partial) {
+            log::debug!("too soon after the last update, holding this one back");
+            return None;
+        }
+        let transition = self.transition.take();
+        self.jitter_moved = false;
+        if let Some(dirty) = dirty {
+            log::debug!(
+                "partial refresh of {}x{} at ({}, {})",
+                dirty.size.width,
+                dirty.size.height,
+                dirty.top_left.x,
+                dirty.top_left.y
+            );
+        }
+        // The old image in full, only for what needs it.
+        let shown = match &self.shown {
+            Some(shown) if transition.is_some() || self.pre_clear.is_some() => Some(shown.unpack()),
+            _ => None,
+        };
+        // Steps on the way there; they'd use the fast waveform, if the
+        // panel had one, since nobody looks closely at them.
+        let mut between = match (transition, &shown) {
+            (Some(effect), Some(shown)) => (1..transition::STEPS)
+                .map(|step| effect.blend(shown, &output, step))
+                .collect(),
+            _ => Vec::new(),
+        };
+        // A big change leaves a ghost of the old image behind. Showing its
+        // negative first, fast, evens the pixels out; a transition already
+        // goes through other images, so it doesn't need this.
+        let pre_clear = match (&shown, self.pre_clear) {
+            (Some(shown), Some(threshold)) if between.is_empty() => {
+                let changed = changed_fraction(shown, &output);
+                (changed >= threshold).then(|| {
+                    log::debug!(
+                        "{:.0}% of the image changed, clearing the panel first",
+                        changed * 100.0
+                    );
+                    shown.inverted()
+                })
+            }
+            _ => None,
+        };
+        let pre_cleared = pre_clear.is_some();
+        between.extend(pre_clear);
+        Some(Update {
+            frame: frame.clone(),
+            output,
+            between,
+            pre_cleared,
+        })
+    }
+
+    /// Sends `update` to the panel, returning whether it was refreshed.
+    ///
+    /// A panel that fails to take the frame, e.g. because the HAT was
+    /// pulled off, isn't an error: updates are skipped until it answers
+    /// again, tried every [`PROBE_INTERVAL`], and then it gets a full redraw.
+    pub fn transfer(&mut self, update: Update) -> Result<bool> {
+        if self.asleep {
+            log::info!("waking panel");
+            self.asleep = false;
+        }
+        self.guard.sent(Instant::now());
+        let written = update
+            .between
+            .iter()
+            .chain([&update.output])
+            .try_for_each(|frame| write_png(frame, &self.output))
+            .with_context(|| format!("writing {}", self.output.display()));
+        if let Err(err) = written {
+            if self.degraded.is_none() {
+                log::warn!(
+                    "panel not answering ({err:#}); skipping updates, trying again every {}s",
+                    PROBE_INTERVAL.as_secs()
+                );
+            }
+            self.degraded = Some(Degraded {
+                since: self.degraded.as_ref().map_or_else(Utc::now, |d| d.since),
+                next_probe: Instant::now() + PROBE_INTERVAL,
+            });
+            // Whatever made it onto the panel before is no longer known,
+            // so the frame it comes back to has to go out in full even if
+            // the screens haven't changed.
+            self.last = None;
+            self.shown = None;
+            return Ok(false);
+        }
+        if self.degraded.take().is_some() {
+            log::info!("panel is back, redrawing in full");
+        }
+        log::info!("wrote frame to {}", self.output.display());
+        self.pre_cleared = update.pre_cleared;
+        let (last, shown) = (
+            PackedFrame::new(&update.frame),
+            PackedFrame::new(&update.output),
+        );
+        log::trace!(
+            "keeping the frame in {} bytes rather than {}",
+            last.footprint() + shown.footprint(),
+            update.frame.footprint() + update.output.footprint()
+        );
+        (self.last, self.shown) = (Some(last), Some(shown));
+        Ok(true)
+    }
+}
+
+/// What [`Simulator::transfer`] sends to the panel, from
+/// [`Simulator::diff`].
+pub struct Update {
+    /// As drawn by the screens, for telling whether the next one changed.
+    frame: Frame,
+    /// As shown.
+    output: Frame,
+    /// Shown first, on the way there.
+    between: Vec<Frame>,
+    pre_cleared: bool,
+}
+
+/// `frame` with `decorate` drawn on top, moved by `offset` against
+/// burn-in and then mirrored: what ends up on the panel, or in a PNG.
+pub fn compose(
+    frame: &Frame,
+    decorate: impl FnOnce(&mut Frame),
+    offset: Point,
+    mirror: Mirror,
+) -> Frame {
+    let mut output = frame.clone();
+    decorate(&mut output);
+    if offset != Point::zero() {
+        output = output.shifted(offset);
+    }
+    if !mirror.is_none() {
+        output = output.mirrored(mirror);
+    }
+    output
+}
+
+pub const WHITE: [u8; 3] = [0xff, 0xff, 0xff];
+pub const LIGHT_GREY: [u8; 3] = [0xaa, 0xaa, 0xaa];
+pub const DARK_GREY: [u8; 3] = [0x55, 0x55, 0x55];
+pub const BLACK: [u8; 3] = [0x00, 0x00, 0x00];
+pub const RED: [u8; 3] = [0xc0, 0x20, 0x20];
+
+pub fn write_png(frame: &Frame, path: &Path) -> Result<()> {
+    encode_png(frame, BufWriter::new(File::create(path)?))
+}
+
+/// Encodes `frame` as an RGB PNG, in the panel's own colours and the two
+/// greys of any shading.
+pub fn encode_png(frame: &Frame, out: impl Write) -> Result<()> {
+    let mut encoder = png::Encoder::new(out, frame.width(), frame.height());
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    let mut data = Vec::with_capacity((frame.width() * frame.height() * 3) as usize);
+    for y in 0..frame.height() as i32 {
+        for x in 0..frame.width() as i32 {
+            let rgb = match (frame.pixel(x, y), frame.is_shaded(x, y)) {
+                (Color::White, false) => WHITE,
+                (Color::White, true) => LIGHT_GREY,
+                (Color::Black, false) => BLACK,
+                (Color::Black, true) => DARK_GREY,
+                (Color::Red, _) => RED,
+            };
+            data.extend_from_slice(&rgb);
+        }
+    }
+    writer.write_image_data(&data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::screens::{self, clock::ClockScreen, Fixture};
+    use proptest::prelude::*;
+
+    /// The clock, as the panel shows it with these options.
+    fn shown(mirror_x: bool, mirror_y: bool, rotation: u16) -> Frame {
+        let config = Config {
+            mirror_x,
+            mirror_y,
+            rotation,
+            ..Config::default()
+        };
+        compose(&clock(), |_| {}, Point::zero(), config.mirror())
+    }
+
+    fn clock() -> Frame {
+        Fixture::at("2024-05-01T12:04:30+03:00").draw(&ClockScreen)
+    }
+
+    /// Whether every pixel of `flipped` is `frame`'s at `from(x, y)`.
+    fn flipped_by(flipped: &Frame, frame: &Frame, from: impl Fn(i32, i32) -> (i32, i32)) -> bool {
+        let (width, height) = (frame.width() as i32, frame.height() as i32);
+        (0..height).all(|y| {
+            (0..width).all(|x| {
+                let (fx, fy) = from(x, y);
+                flipped.pixel(x, y) == frame.pixel(fx, fy)
+            })
+        })
+    }
+
+    #[test]
+    fn mirror_x_flips_left_to_right() {
+        let (w, frame) = (WIDTH as i32, shown(true, false, 0));
+        assert!(flipped_by(&frame, &clock(), |x, y| (w - 1 - x, y)));
+        screens::assert_golden(&frame, "mirror-x");
+    }
+
+    #[test]
+    fn mirror_y_flips_top_to_bottom() {
+        let (h, frame) = (HEIGHT as i32, shown(false, true, 0));
+        assert!(flipped_by(&frame, &clock(), |x, y| (x, h - 1 - y)));
+        screens::assert_golden(&frame, "mirror-y");
+    }
+
+    #[test]
+    fn half_a_turn_goes_before_the_flips() {
+        let (w, h) = (WIDTH as i32, HEIGHT as i32);
+        let turned = shown(false, false, 180);
+        assert!(flipped_by(&turned, &clock(), |x, y| (w - 1 - x, h - 1 - y)));
+        screens::assert_golden(&turned, "rotation-180");
+        // Turned and then flipped both ways, it's back where it started.
+        let both = shown(true, true, 180);
+        assert!(both == clock());
+        screens::assert_golden(&both, "mirror-both-rotation-180");
+        assert!(shown(true, false, 180) == shown(false, true, 0));
+        assert!(shown(false, true, 180) == shown(true, false, 0));
+    }
+
+    /// The colours `frame` comes out in as a PNG, row by row.
+    fn encoded(frame: &Frame) -> Vec<[u8; 3]> {
+        let mut png = Vec::new();
+        encode_png(frame, &mut png).unwrap();
+        let mut reader = png::Decoder::new(std::io::Cursor::new(png))
+            .read_info()
+            .unwrap();
+        let mut data = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut data).unwrap();
+        data.chunks(3).map(|rgb| [rgb[0], rgb[1], rgb[2]]).collect()
+    }
+
+    #[test]
+    fn shades_only_show_in_the_png() {
+        let mut frame = Frame::new(4, 1);
+        frame.set_pixel(1, 0, Color::Black);
+        frame.set_pixel(2, 0, Color::Black);
+        frame.set_pixel(3, 0, Color::Red);
+        let plain = frame.clone();
+        assert_eq!(encoded(&plain), [WHITE, BLACK, BLACK, RED]);
+        for x in [0, 2, 3] {
+            frame.shade(x, 0);
+        }
+        // Red can't be shaded.
+        assert!(!frame.is_shaded(3, 0));
+        assert_eq!(encoded(&frame), [LIGHT_GREY, BLACK, DARK_GREY, RED]);
+        // The panel's planes are as they were, so only the shade changed.
+        assert_eq!(frame.black, plain.black);
+        assert_eq!(frame.red, plain.red);
+        // Drawing over a pixel takes its shade off.
+        frame.set_pixel(2, 0, Color::Black);
+        assert_eq!(encoded(&frame), [LIGHT_GREY, BLACK, BLACK, RED]);
+    }
+
+    #[test]
+    fn rotations_other_than_half_a_turn_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        for (rotation, ok) in [(0, true), (90, false), (180, true), (270, false)] {
+            std::fs::write(&path, format!("rotation = {rotation}")).unwrap();
+            let loaded = Config::load(Some(&path));
+            assert_eq!(loaded.is_ok(), ok, "rotation {rotation}");
+        }
+    }
+
+    /// Lets `display` try the panel again straight away.
+    fn probe_now(display: &mut Simulator) {
+        display.degraded.as_mut().expect("degraded").next_probe = Instant::now();
+        display.guard = Guard::default();
+    }
+
+    #[test]
+    fn a_panel_that_comes_back_is_redrawn_in_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("panel.png");
+        let mut display = Simulator::new(output.clone(), true, Mirror::default(), None);
+        let frame = clock();
+        assert!(display.update(&frame, |_| {}).unwrap());
+
+        // Pulled off: writing fails, and nothing is known to be shown.
+        display.output = dir.path().join("gone").join("panel.png");
+        let mut other = frame.clone();
+        other.set_pixel(0, 0, Color::Black);
+        display.guard = Guard::default();
+        assert!(!display.update(&other, |_| {}).unwrap());
+        assert!(display.link_status().degraded);
+        assert!(!display.is_showing(&frame));
+
+        // Back with the same screen as before it went: still redrawn.
+        display.output = output;
+        probe_now(&mut display);
+        let update = display.diff(&frame, display.compose(&frame, |_| {}));
+        let update = update.expect("the frame goes out");
+        assert!(display.transfer(update).unwrap());
+        assert!(!display.link_status().degraded);
+        assert!(display.is_showing(&frame));
+    }
+
+    #[test]
+    fn a_degraded_panel_is_only_probed_now_and_then() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("gone").join("panel.png");
+        let mut display = Simulator::new(output, false, Mirror::default(), None);
+        assert!(!display.update(&clock(), |_| {}).unwrap());
+        assert!(display.degraded.is_some());
+        display.guard = Guard::default();
+        assert!(display.diff(&clock(), clock()).is_none());
+        probe_now(&mut display);
+        assert!(display.diff(&clock(), clock()).is_some());
+    }
+
+    /// Feeds `guard` an update every `every` from `start` for `over`, each
+    /// sent as soon as it's let through, and returns when they went.
+    fn hammer(
+        guard: &mut Guard,
+        start: Instant,
+        every: Duration,
+        over: Duration,
+        partial: bool,
+    ) -> Vec<Duration> {
+        let mut sent = Vec::new();
+        let mut at = Duration::ZERO;
+        while at < over {
+            if guard.admit(start + at, partial) {
+                guard.sent(start + at);
+                sent.push(at);
+            }
+            at += every;
+        }
+        sent
+    }
+
+    #[test]
+    fn the_guard_spaces_out_a_tight_loop() {
+        let start = Instant::now();
+        let mut guard = Guard::default();
+        let every = Duration::from_millis(100);
+        let sent = hammer(&mut guard, start, every, Duration::from_secs(60), false);
+        let secs: Vec<u64> = sent.iter().map(|at| at.as_secs()).collect();
+        assert_eq!(secs, [0, 10, 20, 30, 40, 50]);
+        assert_eq!(guard.coalesced(), 600 - 6);
+
+        // Partial refreshes only need a second.
+        let mut guard = Guard::default();
+        let sent = hammer(&mut guard, start, every, Duration::from_secs(5), true);
+        let secs: Vec<u64> = sent.iter().map(|at| at.as_secs()).collect();
+        assert_eq!(secs, [0, 1, 2, 3, 4]);
+        assert_eq!(guard.coalesced(), 50 - 5);
+    }
+
+    #[test]
+    fn the_guard_says_when_a_held_update_can_go() {
+        let start = Instant::now();
+        let mut guard = Guard::default();
+        assert!(guard.admit(start, false));
+        guard.sent(start);
+        assert_eq!(guard.held(), None);
+
+        let soon = start + Duration::from_secs(3);
+        assert!(!guard.admit(soon, false));
+        assert_eq!(guard.held(), Some(start + MIN_FULL_SPACING));
+        // A partial one could go sooner.
+        assert!(!guard.admit(start + Duration::from_millis(500), true));
+        assert_eq!(guard.held(), Some(start + MIN_PARTIAL_SPACING));
+        assert!(guard.admit(soon, true));
+        assert_eq!(guard.held(), None);
+        assert_eq!(guard.coalesced(), 2);
+
+        // Right on the spacing is soon enough.
+        guard.sent(soon);
+        assert!(guard.admit(soon + MIN_FULL_SPACING, false));
+    }
+
+    #[test]
+    fn a_burst_of_frames_comes_out_as_the_last_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut display =
+            Simulator::new(dir.path().join("panel.png"), false, Mirror::default(), None);
+        let first = clock();
+        assert!(display.update(&first, |_| {}).unwrap());
+        let mut latest = first.clone();
+        for x in 0..20 {
+            latest.set_pixel(x, 0, Color::Black);
+            assert!(!display.update(&latest, |_| {}).unwrap());
+            assert!(display.held().is_some());
+        }
+        assert_eq!(display.coalesced(), 20);
+        assert!(display.is_showing(&first));
+
+        // Once the window opens, what's drawn by then goes out.
+        display.guard.last = Some(Instant::now() - MIN_FULL_SPACING);
+        assert!(display.update(&latest, |_| {}).unwrap());
+        assert!(display.is_showing(&latest));
+        assert_eq!(display.held(), None);
+        // Starting the panel over doesn't let the next one through early.
+        display.reopen();
+        let mut next = latest.clone();
+        next.set_pixel(0, 1, Color::Black);
+        assert!(!display.update(&next, |_| {}).unwrap());
+        assert_eq!(display.coalesced(), 21);
+    }
+
+    /// Smallest rectangle covering every byte that differs between `old`
+    /// and `new`, going through them one at a time.
+    fn naive_bounds(old: &Frame, new: &Frame) -> Option<Rectangle> {
+        let stride = new.stride();
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        let planes = [
+            (&old.black, &new.black),
+            (&old.red, &new.red),
+            (&old.shade, &new.shade),
+        ];
+        for (a, b) in planes {
+            for (i, _) in a.iter().zip(b).enumerate().filter(|(_, (a, b))| a != b) {
+                let (col, row) = (i % stride, i / stride);
+                bounds = Some(match bounds {
+                    None => (col, row, col, row),
+                    Some((c0, r0, c1, r1)) => (c0.min(col), r0.min(row), c1.max(col), r1.max(row)),
+                });
+            }
+        }
+        bounds.map(|(c0, r0, c1, r1)| {
+            let x0 = c0 as u32 * 8;
+            let x1 = ((c1 as u32 + 1) * 8).min(new.width);
+            Rectangle::new(
+                Point::new(x0 as i32, r0 as i32),
+                Size::new(x1 - x0, (r1 - r0 + 1) as u32),
+            )
+        })
+    }
+
+    /// Where a mark goes and in what, as `(x, y, width, height, colour)`,
+    /// with 3 for a shade.
+    type Mark = (u32, u32, u32, u32, u8);
+
+    fn mark(frame: &mut Frame, (x, y, width, height, colour): Mark) {
+        for y in y..(y + height).min(frame.height) {
+            for x in x..(x + width).min(frame.width) {
+                match colour {
+                    0 => frame.set_pixel(x as i32, y as i32, Color::White),
+                    1 => frame.set_pixel(x as i32, y as i32, Color::Black),
+                    2 => frame.set_pixel(x as i32, y as i32, Color::Red),
+                    _ => frame.shade(x as i32, y as i32),
+                }
+            }
+        }
+    }
+
+    /// A frame like the screens draw: paper with blocks of ink on it and
+    /// stray pixels, as its size and marks, and then marks for changes to
+    /// it. Most are small, some as big as the panel, so rows don't always
+    /// end on a whole byte and runs go past 255 bytes.
+    fn scenes() -> impl Strategy<Value = ((u32, u32), Vec<Mark>, Vec<Mark>)> {
+        let size = (
+            prop_oneof![1..=40u32, Just(WIDTH)],
+            prop_oneof![1..=20u32, Just(HEIGHT)],
+        );
+        size.prop_flat_map(|(width, height)| {
+            let blocks = |most| {
+                prop::collection::vec(
+                    (0..width, 0..height, 1..=width, 1..=height, 0..4u8),
+                    0..most,
+                )
+            };
+            let pixel = (0..width, 0..height, Just(1), Just(1), 0..4u8);
+            let drawn = (blocks(6), prop::collection::vec(pixel.clone(), 0..8));
+            let changes = (blocks(3), prop::collection::vec(pixel, 0..4));
+            (Just((width, height)), drawn, changes)
+        })
+        .prop_map(|(size, (mut drawn, pixels), (mut changes, moved))| {
+            drawn.extend(pixels);
+            changes.extend(moved);
+            (size, drawn, changes)
+        })
+    }
+
+    fn drawn((width, height): (u32, u32), marks: &[Mark]) -> Frame {
+        let mut frame = Frame::new(width, height);
+        marks.iter().for_each(|&m| mark(&mut frame, m));
+        frame
+    }
+
+    proptest! {
+        #[test]
+        fn packed_frames_unpack_as_they_were((size, marks, _) in scenes()) {
+            let frame = drawn(size, &marks);
+            let packed = PackedFrame::new(&frame);
+            prop_assert!(packed.unpack() == frame);
+            prop_assert!(packed.matches(&frame));
+            prop_assert_eq!(packed.dirty_bounds(&frame), None);
+        }
+
+        #[test]
+        fn packed_bounds_are_the_naive_ones((size, marks, changes) in scenes()) {
+            let old = drawn(size, &marks);
+            let mut new = old.clone();
+            changes.into_iter().for_each(|m| mark(&mut new, m));
+            let packed = PackedFrame::new(&old);
+            prop_assert_eq!(packed.dirty_bounds(&new), naive_bounds(&old, &new));
+            prop_assert_eq!(packed.matches(&new), old == new);
+        }
+    }
+
+    #[test]
+    fn packed_frames_take_a_fraction_of_the_memory() {
+        let fixture = Fixture::at("2024-05-01T12:04:30+03:00");
+        for name in ["pet", "clock", "stats", "heatmap"] {
+            for screen in screens::build(name, &fixture.config).unwrap() {
+                let frame = fixture.draw(screen.as_ref());
+                let packed = PackedFrame::new(&frame);
+                let saved = 1.0 - packed.footprint() as f32 / frame.footprint() as f32;
+                assert!(saved > 0.7, "{name} only saves {:.0}%", saved * 100.0);
+            }
+        }
+    }
+}
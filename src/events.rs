@@ -0,0 +1,217 @@
+//! Messages flowing into the main loop, and what they cause.
+
+use serde::{Deserialize, Serialize};
+
+use crate::buttons::ButtonEvent;
+use crate::chat::Talk;
+use crate::message::Message;
+use crate::transfer::Transfer;
+
+/// Something the daemon has been asked to do, e.g. over the control socket,
+/// or something it noticed in the background.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Act(Action),
+    /// Show or hide the debug overlay.
+    ToggleDebug,
+    /// Redraw the whole panel, e.g. to clear ghosting.
+    Refresh,
+    System(SystemEvent),
+    Record(Reading),
+    /// A gesture on the buttons; see [`crate::buttons`].
+    Button(ButtonEvent),
+    /// Put a note up, see [`crate::message`].
+    Say(Message),
+    /// Something said to the pet, see [`crate::chat`].
+    Talk(Talk),
+    /// Someone logged in over SSH, see [`crate::server`].
+    Login(String),
+    /// Take a photo with the camera and put it up, see [`crate::camera`].
+    Selfie,
+    /// Put the pet's transfer code up, see [`crate::transfer`].
+    Export,
+    /// Offer to swap the pet for one from another device.
+    Import(Transfer),
+    /// Switch a screen in the carousel on or off.
+    SetScreen(String, bool),
+    /// Hold the carousel on a screen until it's unpinned.
+    PinScreen(String),
+    UnpinScreen,
+    /// Show the next screen now, or the one before with `false`.
+    TurnScreen(bool),
+    /// Turn guest mode on or off, or the other way with `None`; see
+    /// [`crate::guest`].
+    Guest(Option<bool>),
+    /// Acknowledge every reminder waiting, see [`crate::reminder`].
+    Acknowledge,
+    /// Stop cleanly; sent on SIGTERM or SIGINT.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    Shutdown,
+}
+
+impl Command {
+    /// Parses the commands accepted on the control socket: one word,
+    /// `screen <name> on|off`, `screen pin <name>`, `screen next`, `prev`
+    /// or `unpin`, or `guest on|off`.
+    pub fn parse(text: &str) -> Option<Command> {
+        if let Some(rest) = text.trim().strip_prefix("screen ") {
+            let rest = rest.trim();
+            match rest {
+                "next" => return Some(Command::TurnScreen(true)),
+                "prev" => return Some(Command::TurnScreen(false)),
+                "unpin" => return Some(Command::UnpinScreen),
+                _ => {}
+            }
+            if let Some(name) = rest.strip_prefix("pin ") {
+                return Some(Command::PinScreen(name.trim().to_string()));
+            }
+            let (name, state) = rest.rsplit_once(' ')?;
+            let on = match state {
+                "on" => true,
+                "off" => false,
+                _ => return None,
+            };
+            return Some(Command::SetScreen(name.trim().to_string(), on));
+        }
+        let action = match text.trim() {
+            "debug" => return Some(Command::ToggleDebug),
+            "refresh" => return Some(Command::Refresh),
+            "selfie" => return Some(Command::Selfie),
+            "export" => return Some(Command::Export),
+            "guest" => return Some(Command::Guest(None)),
+            "guest on" => return Some(Command::Guest(Some(true))),
+            "guest off" => return Some(Command::Guest(Some(false))),
+            "ack" => return Some(Command::Acknowledge),
+            "feed" => Action::Feed,
+            "play" => Action::Play,
+            "pet" => Action::Pet,
+            "clean" => Action::Clean,
+            "discipline" => Action::Discipline,
+            _ => return None,
+        };
+        Some(Command::Act(action))
+    }
+}
+
+/// A number pushed from elsewhere, e.g. the day's step count.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reading {
+    /// As in `/push/<metric>`.
+    pub metric: String,
+    pub value: f64,
+    /// What the value is working towards, if anything.
+    pub goal: Option<f64>,
+}
+
+/// Things the owner can do to the pet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Feed,
+    Play,
+    Pet,
+    Clean,
+    /// Tell the pet off; right after misbehaviour this trains it.
+    Discipline,
+}
+
+/// Something that happened to the pet, for the display to react to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PetEvent {
+    Fed,
+    Played,
+    Petted,
+    Cleaned,
+    /// Started misbehaving, waiting to see if anyone reacts.
+    Misbehaved,
+    Disciplined,
+    /// Told off without having done anything.
+    Scolded,
+    /// The pet is feeling the heat; raised every cycle while it lasts.
+    Hot,
+    /// A pushed metric reached its goal for the first time today.
+    GoalReached,
+    /// Fell ill, from messes left lying about.
+    Sick,
+    /// Starved to death.
+    Died,
+    /// Grew into its adult form.
+    Evolved,
+    /// Hunger or energy dropped low enough to put the alert border up.
+    StatCritical,
+    /// Quiet hours are over.
+    WokeUp,
+    /// Someone logged in to the host.
+    Visited,
+    /// Package upgrades are waiting, or more of them than before.
+    UpgradesPending,
+}
+
+impl PetEvent {
+    pub const ALL: [PetEvent; 16] = [
+        PetEvent::Fed,
+        PetEvent::Played,
+        PetEvent::Petted,
+        PetEvent::Cleaned,
+        PetEvent::Misbehaved,
+        PetEvent::Disciplined,
+        PetEvent::Scolded,
+        PetEvent::Hot,
+        PetEvent::GoalReached,
+        PetEvent::Sick,
+        PetEvent::Died,
+        PetEvent::Evolved,
+        PetEvent::StatCritical,
+        PetEvent::WokeUp,
+        PetEvent::Visited,
+        PetEvent::UpgradesPending,
+    ];
+
+    /// The event's name in the journal and in `[hooks]`.
+    pub fn name(self) -> &'static str {
+        match self {
+            PetEvent::Fed => "fed",
+            PetEvent::Played => "played",
+            PetEvent::Petted => "petted",
+            PetEvent::Cleaned => "cleaned",
+            PetEvent::Misbehaved => "misbehaved",
+            PetEvent::Disciplined => "disciplined",
+            PetEvent::Scolded => "scolded",
+            PetEvent::Hot => "hot",
+            PetEvent::GoalReached => "goal_reached",
+            PetEvent::Sick => "sick",
+            PetEvent::Died => "died",
+            PetEvent::Evolved => "evolved",
+            PetEvent::StatCritical => "stat_critical",
+            PetEvent::WokeUp => "woke_up",
+            PetEvent::Visited => "visited",
+            PetEvent::UpgradesPending => "upgrades_pending",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<PetEvent> {
+        PetEvent::ALL.into_iter().find(|event| event.name() == name)
+    }
+}
+
+/// A change in the host's health or an outside service, published once it
+/// has settled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SystemEvent {
+    /// The primary address went away, i.e. there's no route out.
+    NetworkDown,
+    NetworkUp,
+    /// Still online, but under a different address.
+    AddressChanged,
+    DiskAlmostFull,
+    DiskOk,
+    /// The supply is sagging, per the firmware's throttling flags.
+    Undervoltage,
+    PowerOk,
+    /// The followed repository's latest CI run failed.
+    #[cfg(feature = "github")]
+    CiFailed,
+    /// CI is green again after failing.
+    #[cfg(feature = "github")]
+    CiPassed,
+}
@@ -0,0 +1,470 @@
+//! Unread GitHub notifications and the latest CI run of one repository,
+//! for the `github` screen.
+//!
+//! Requests are conditional, so an unchanged answer costs a 304 rather than
+//! a full body, and the source slows down whenever GitHub asks it to: the
+//! `X-Poll-Interval` header sets the pace and rate limiting backs it off.
+//! The token is only ever sent in the `Authorization` header.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use chrono::Utc;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+
+use crate::channel::Outbox;
+use crate::collector::{Snapshot, Source};
+use crate::config::{GithubConfig, Secret};
+use crate::events::{Command, SystemEvent};
+
+/// Token used when the config doesn't have one.
+pub const TOKEN_ENV: &str = "INKY_GOTCHI_GITHUB_TOKEN";
+
+const API: &str = "https://api.github.com";
+/// Gap between polls unless GitHub asks for longer.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// Longest wait after being rate limited.
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Notifications fetched at once; more than this shows as `50+`.
+pub const NOTIFICATION_PAGE: usize = 50;
+
+/// How the latest workflow run went.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CiState {
+    Passing,
+    Failing,
+    Running,
+    /// Cancelled, skipped and the like.
+    Other,
+}
+
+/// What the screen shows. Fields stay `None` until their request succeeds.
+#[derive(Clone, Debug, Default)]
+pub struct GithubStatus {
+    pub unread: Option<usize>,
+    pub ci: Option<CiState>,
+    pub repo: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Runs {
+    workflow_runs: Vec<Run>,
+}
+
+#[derive(Deserialize)]
+struct Run {
+    status: String,
+    conclusion: Option<String>,
+}
+
+impl Run {
+    fn state(&self) -> CiState {
+        if self.status != "completed" {
+            return CiState::Running;
+        }
+        match self.conclusion.as_deref() {
+            Some("success") => CiState::Passing,
+            Some("failure" | "timed_out" | "startup_failure") => CiState::Failing,
+            _ => CiState::Other,
+        }
+    }
+}
+
+/// One endpoint polled with conditional requests.
+struct Endpoint<T> {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    value: Option<T>,
+}
+
+/// The response headers [`Endpoint::handle`] looks at.
+const HEADERS: [&str; 6] = [
+    "etag",
+    "last-modified",
+    "x-poll-interval",
+    "retry-after",
+    "x-ratelimit-reset",
+    "x-ratelimit-remaining",
+];
+
+/// What a poll came back with, besides the value itself.
+#[derive(Debug, PartialEq, Eq)]
+enum Polled {
+    /// New or unchanged; either way GitHub may ask for a longer interval.
+    Ok(Option<Duration>),
+    /// Rate limited, with how long GitHub said to wait if it did.
+    Limited(Option<Duration>),
+}
+
+impl<T> Endpoint<T> {
+    fn new(url: String) -> Self {
+        Endpoint {
+            url,
+            etag: None,
+            last_modified: None,
+            value: None,
+        }
+    }
+
+    fn poll(
+        &mut self,
+        agent: &ureq::Agent,
+        token: Option<&Secret>,
+        parse: impl FnOnce(&mut ureq::Body) -> Result<T>,
+    ) -> Result<Polled> {
+        let mut request = agent
+            .get(&self.url)
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28");
+        if let Some(token) = token {
+            request = request.header("Authorization", format!("Bearer {}", token.expose()));
+        }
+        if let Some(etag) = &self.etag {
+            request = request.header("If-None-Match", etag);
+        }
+        if let Some(modified) = &self.last_modified {
+            request = request.header("If-Modified-Since", modified);
+        }
+        let mut response = request.call()?;
+        let headers: HashMap<&str, String> = HEADERS
+            .into_iter()
+            .filter_map(|name| {
+                let value = response.headers().get(name)?.to_str().ok()?;
+                Some((name, value.to_owned()))
+            })
+            .collect();
+        let status = response.status().as_u16();
+        self.handle(status, &headers, Utc::now().timestamp(), || {
+            parse(response.body_mut())
+        })
+    }
+
+    /// Takes in a response with `status` and `headers`, got at the Unix
+    /// time `now`. `body` is only read for a 200.
+    fn handle(
+        &mut self,
+        status: u16,
+        headers: &HashMap<&str, String>,
+        now: i64,
+        body: impl FnOnce() -> Result<T>,
+    ) -> Result<Polled> {
+        let header = |name: &str| headers.get(name).cloned();
+        let seconds = |name: &str| header(name)?.parse().ok().map(Duration::from_secs);
+        let poll_interval = seconds("x-poll-interval");
+        match status {
+            304 => Ok(Polled::Ok(poll_interval)),
+            200 => {
+                self.value = Some(body()?);
+                self.etag = header("etag");
+                self.last_modified = header("last-modified");
+                Ok(Polled::Ok(poll_interval))
+            }
+            403 | 429 => {
+                let reset = header("x-ratelimit-reset")
+                    .and_then(|at| at.parse::<i64>().ok())
+                    .filter(|_| header("x-ratelimit-remaining").as_deref() == Some("0"))
+                    .map(|at| Duration::from_secs((at - now).max(0) as u64));
+                Ok(Polled::Limited(seconds("retry-after").or(reset)))
+            }
+            status => bail!("HTTP {status}"),
+        }
+    }
+}
+
+pub struct GithubSource<O> {
+    agent: ureq::Agent,
+    token: Option<Secret>,
+    notifications: Option<Endpoint<usize>>,
+    runs: Option<Endpoint<CiState>>,
+    repo: Option<String>,
+    next_poll: Duration,
+    backoff: Duration,
+    events: O,
+    /// CI state last reported as an event.
+    reported: Option<CiState>,
+}
+
+impl<O: Outbox> GithubSource<O> {
+    /// Returns `None` when there's nothing to follow: no token means no
+    /// notifications, and no repository means no CI.
+    pub fn new(config: &GithubConfig, events: O) -> Option<Self> {
+        let token = config
+            .token
+            .clone()
+            .or_else(|| std::env::var(TOKEN_ENV).ok().map(Secret::new));
+        let notifications = token
+            .as_ref()
+            .map(|_| Endpoint::new(format!("{API}/notifications?per_page={NOTIFICATION_PAGE}")));
+        let runs = config
+            .repo
+            .as_ref()
+            .map(|repo| Endpoint::new(format!("{API}/repos/{repo}/actions/runs?per_page=1")));
+        if notifications.is_none() && runs.is_none() {
+            log::info!("no GitHub token or repository configured, not polling");
+            return None;
+        }
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(HTTP_TIMEOUT))
+            .user_agent(concat!("inky-gotchi/", env!("CARGO_PKG_VERSION")))
+            // 304s and rate limits are answers, not failures.
+            .http_status_as_error(false)
+            .build()
+            .into();
+        Some(GithubSource {
+            agent,
+            token,
+            notifications,
+            runs,
+            repo: config.repo.clone(),
+            next_poll: Duration::ZERO,
+            backoff: Duration::ZERO,
+            events,
+            reported: None,
+        })
+    }
+
+    /// How long GitHub wants us to wait after `polled`, if it said. Rate
+    /// limits also grow the backoff and set `limited`; the wait after one
+    /// is kept between [`POLL_INTERVAL`] and [`MAX_BACKOFF`].
+    fn settle(
+        &mut self,
+        polled: Result<Polled>,
+        what: &str,
+        limited: &mut bool,
+    ) -> Option<Duration> {
+        match polled {
+            Ok(Polled::Ok(interval)) => interval,
+            Ok(Polled::Limited(wait)) => {
+                *limited = true;
+                self.backoff = (self.backoff * 2).clamp(POLL_INTERVAL, MAX_BACKOFF);
+                let wait = wait.unwrap_or_default().clamp(self.backoff, MAX_BACKOFF);
+                log::warn!("rate limited on {what}, waiting {}s", wait.as_secs());
+                Some(wait)
+            }
+            Err(err) => {
+                log::warn!("fetching {what} from GitHub failed: {err:#}");
+                None
+            }
+        }
+    }
+
+    fn report(&mut self, ci: Option<CiState>) {
+        let event = match (self.reported, ci) {
+            (Some(CiState::Failing), Some(CiState::Passing)) => SystemEvent::CiPassed,
+            (previous, Some(CiState::Failing)) if previous != Some(CiState::Failing) => {
+                SystemEvent::CiFailed
+            }
+            _ => return,
+        };
+        self.reported = ci;
+        self.events.send(Command::System(event));
+    }
+}
+
+impl<O: Outbox> Source for GithubSource<O> {
+    fn name(&self) -> &'static str {
+        "github"
+    }
+
+    fn on_demand(&self) -> bool {
+        true
+    }
+
+    fn interval(&self) -> Duration {
+        self.next_poll
+    }
+
+    fn collect(&mut self, snapshot: &Mutex<Snapshot>) {
+        let mut limited = false;
+        let mut wait = POLL_INTERVAL;
+        if let Some(mut endpoint) = self.notifications.take() {
+            let polled = endpoint.poll(&self.agent, self.token.as_ref(), |body| {
+                Ok(body.read_json::<Vec<IgnoredAny>>()?.len())
+            });
+            wait = wait.max(
+                self.settle(polled, "notifications", &mut limited)
+                    .unwrap_or_default(),
+            );
+            self.notifications = Some(endpoint);
+        }
+        if let Some(mut endpoint) = self.runs.take() {
+            let polled = endpoint.poll(&self.agent, self.token.as_ref(), |body| {
+                let runs: Runs = body.read_json()?;
+                Ok(runs
+                    .workflow_runs
+                    .first()
+                    .map_or(CiState::Other, Run::state))
+            });
+            wait = wait.max(
+                self.settle(polled, "workflow runs", &mut limited)
+                    .unwrap_or_default(),
+            );
+            self.runs = Some(endpoint);
+        }
+        if !limited {
+            self.backoff = Duration::ZERO;
+        }
+        self.next_poll = wait;
+
+        let status = GithubStatus {
+            unread: self.notifications.as_ref().and_then(|e| e.value),
+            ci: self.runs.as_ref().and_then(|e| e.value),
+            repo: self.repo.clone(),
+        };
+        self.report(status.ci);
+        snapshot.lock().unwrap().github = status;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{self, Receiver, Sender};
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn headers<'a>(pairs: &[(&'a str, &str)]) -> HashMap<&'a str, String> {
+        pairs
+            .iter()
+            .map(|&(name, value)| (name, value.to_owned()))
+            .collect()
+    }
+
+    /// An endpoint that already has `5` from a response tagged `"v1"`.
+    fn endpoint() -> Endpoint<usize> {
+        let mut endpoint = Endpoint::new("https://example.invalid/notifications".into());
+        let first = endpoint.handle(200, &headers(&[("etag", "\"v1\"")]), NOW, || Ok(5));
+        assert_eq!(first.unwrap(), Polled::Ok(None));
+        endpoint
+    }
+
+    fn unread() -> Result<usize> {
+        panic!("nothing to read")
+    }
+
+    #[test]
+    fn not_modified_keeps_what_was_there() {
+        let mut endpoint = endpoint();
+        let polled = endpoint.handle(304, &headers(&[("x-poll-interval", "120")]), NOW, unread);
+        assert_eq!(polled.unwrap(), Polled::Ok(Some(Duration::from_secs(120))));
+        assert_eq!(endpoint.value, Some(5));
+        assert_eq!(endpoint.etag.as_deref(), Some("\"v1\""));
+    }
+
+    #[test]
+    fn a_new_answer_replaces_the_old_one() {
+        let mut endpoint = endpoint();
+        let tags = headers(&[
+            ("etag", "\"v2\""),
+            ("last-modified", "Tue, 14 Nov 2023 22:13:20 GMT"),
+        ]);
+        assert_eq!(
+            endpoint.handle(200, &tags, NOW, || Ok(7)).unwrap(),
+            Polled::Ok(None)
+        );
+        assert_eq!(endpoint.value, Some(7));
+        assert_eq!(endpoint.etag.as_deref(), Some("\"v2\""));
+        assert!(endpoint.last_modified.is_some());
+        // A body that can't be read leaves the last good value.
+        assert!(endpoint
+            .handle(200, &headers(&[]), NOW, || bail!("bad json"))
+            .is_err());
+        assert_eq!(endpoint.value, Some(7));
+        assert_eq!(endpoint.etag.as_deref(), Some("\"v2\""));
+    }
+
+    #[test]
+    fn rate_limits_say_how_long_to_wait() {
+        let reset = (NOW + 90).to_string();
+        for (status, pairs, wait) in [
+            (403, vec![("retry-after", "30")], Some(30)),
+            (429, vec![("retry-after", "30")], Some(30)),
+            (
+                403,
+                vec![
+                    ("x-ratelimit-remaining", "0"),
+                    ("x-ratelimit-reset", &reset),
+                ],
+                Some(90),
+            ),
+            // Not out of requests, so the reset isn't what's in the way.
+            (
+                403,
+                vec![
+                    ("x-ratelimit-remaining", "12"),
+                    ("x-ratelimit-reset", &reset),
+                ],
+                None,
+            ),
+            (403, vec![], None),
+        ] {
+            let polled = endpoint().handle(status, &headers(&pairs), NOW, unread);
+            assert_eq!(
+                polled.unwrap(),
+                Polled::Limited(wait.map(Duration::from_secs)),
+                "{status} {pairs:?}"
+            );
+        }
+        assert!(endpoint().handle(500, &headers(&[]), NOW, unread).is_err());
+    }
+
+    fn github() -> (GithubSource<Sender<Command>>, Receiver<Command>) {
+        let (tx, rx) = mpsc::channel();
+        let config = GithubConfig {
+            token: None,
+            repo: Some("BanuCalin/inky-gotchi".into()),
+        };
+        (GithubSource::new(&config, tx).unwrap(), rx)
+    }
+
+    #[test]
+    fn the_backoff_doubles_up_to_an_hour() {
+        let (mut source, _rx) = github();
+        let mut limited = false;
+        let mut waits = Vec::new();
+        for _ in 0..8 {
+            let wait = source.settle(Ok(Polled::Limited(None)), "runs", &mut limited);
+            waits.push(wait.unwrap().as_secs());
+        }
+        assert!(limited);
+        assert_eq!(waits, [60, 120, 240, 480, 960, 1920, 3600, 3600]);
+        // GitHub's own wait counts when it's longer, up to the same hour.
+        let mut limited = false;
+        let long = Some(Duration::from_secs(5 * 3600));
+        let wait = source.settle(Ok(Polled::Limited(long)), "runs", &mut limited);
+        assert_eq!(wait, Some(MAX_BACKOFF));
+
+        let (mut source, _rx) = github();
+        let mut limited = false;
+        let short = Some(Duration::from_secs(5));
+        let wait = source.settle(Ok(Polled::Limited(short)), "runs", &mut limited);
+        assert_eq!(wait, Some(POLL_INTERVAL));
+        limited = false;
+        let wait = source.settle(Ok(Polled::Ok(None)), "runs", &mut limited);
+        assert_eq!((wait, limited), (None, false));
+    }
+
+    #[test]
+    fn ci_events_come_on_transitions_only() {
+        let (mut source, rx) = github();
+        let events = || rx.try_iter().collect::<Vec<_>>();
+        source.report(Some(CiState::Passing));
+        assert_eq!(events(), []);
+        source.report(Some(CiState::Failing));
+        assert_eq!(events(), [Command::System(SystemEvent::CiFailed)]);
+        source.report(Some(CiState::Failing));
+        source.report(Some(CiState::Running));
+        source.report(Some(CiState::Failing));
+        assert_eq!(events(), []);
+        source.report(Some(CiState::Passing));
+        assert_eq!(events(), [Command::System(SystemEvent::CiPassed)]);
+        source.report(Some(CiState::Passing));
+        source.report(Some(CiState::Failing));
+        assert_eq!(events(), [Command::System(SystemEvent::CiFailed)]);
+    }
+}
@@ -0,0 +1,55 @@
+//! Guest mode: while visitors are round, what's personal stays off the
+//! panel. It's toggled with a long press of A or `guest` on the control
+//! socket, and turns itself off after `guest_minutes`. It's never saved,
+//! so a restart always comes back without it.
+//!
+//! Whatever puts a value on the panel says how private it is, with
+//! [`Privacy`], and [`crate::screens::Context::shown`] does the rest, so
+//! nothing here needs to know which values there are.
+
+use chrono::{DateTime, Utc};
+
+/// What masked values show as. The panel's fonts have no bullet.
+pub const MASK: &str = "···";
+
+/// How a value may be shown with guests around.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Privacy {
+    Public,
+    /// Shown as [`MASK`], so it's clear something's there, e.g. the IP
+    /// address.
+    Masked,
+    /// Left out altogether, e.g. who logged in.
+    Hidden,
+}
+
+impl Privacy {
+    /// `value`, as far as it can be shown while `guests` are around.
+    pub fn apply(self, value: &str, guests: bool) -> Option<&str> {
+        match self {
+            _ if !guests => Some(value),
+            Privacy::Public => Some(value),
+            Privacy::Masked => Some(MASK),
+            Privacy::Hidden => None,
+        }
+    }
+}
+
+/// Guest mode, while it's on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Guest {
+    /// When it turns itself off; never, without one.
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Guest {
+    /// Guest mode from `now`, lasting `minutes`, or until turned off with 0.
+    pub fn start(now: DateTime<Utc>, minutes: u32) -> Guest {
+        let until = (minutes > 0).then(|| now + chrono::Duration::minutes(minutes as i64));
+        Guest { until }
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_some_and(|until| now >= until)
+    }
+}
@@ -0,0 +1,114 @@
+//! The choice of egg on a brand-new install. Until one is picked the
+//! panel shows nothing else: tap to move to the next egg, hold to pick it.
+//! Left alone for [`TIMEOUT`], as on a headless install, the plain egg is
+//! picked.
+
+use std::time::{Duration, Instant};
+
+use crate::buttons::ButtonEvent;
+use crate::pet::Egg;
+
+pub const TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+pub struct Picker {
+    pub selected: usize,
+    opened: Instant,
+}
+
+impl Picker {
+    pub fn new(now: Instant) -> Self {
+        Picker {
+            selected: 0,
+            opened: now,
+        }
+    }
+
+    /// The egg picked with this press, if it was picked.
+    pub fn handle(&mut self, event: ButtonEvent) -> Option<Egg> {
+        match event {
+            ButtonEvent::Short(_) | ButtonEvent::Tripletap(_) => {
+                self.selected = (self.selected + 1) % Egg::ALL.len();
+                None
+            }
+            ButtonEvent::Long(_) => Some(Egg::ALL[self.selected]),
+        }
+    }
+
+    /// When the default gets picked.
+    pub fn deadline(&self) -> Instant {
+        self.opened + TIMEOUT
+    }
+
+    pub fn timed_out(&self, now: Instant) -> bool {
+        now >= self.deadline()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buttons::{Button, Press, TapDetector};
+
+    /// Feeds `presses` through the tap detector into a new picker, a
+    /// second apart, and returns the egg picked, if any, and where the
+    /// picker ended up.
+    fn pick(presses: &[(Button, bool)]) -> (Option<Egg>, usize) {
+        let start = Instant::now();
+        let mut picker = Picker::new(start);
+        let mut taps = TapDetector::default();
+        let mut picked = None;
+        for (i, &(button, long)) in presses.iter().enumerate() {
+            let at = start + Duration::from_secs(i as u64);
+            let mut events = taps.expire(at);
+            events.extend(taps.press(Press { button, long }, at));
+            for event in events {
+                picked = picked.or(picker.handle(event));
+            }
+        }
+        // Whatever taps are still pending go through once they're stale.
+        for event in taps.expire(start + TIMEOUT) {
+            picked = picked.or(picker.handle(event));
+        }
+        (picked, picker.selected)
+    }
+
+    #[test]
+    fn taps_step_through_the_eggs_and_a_hold_picks() {
+        use Button::{A, B, C};
+        assert_eq!(pick(&[(A, true)]), (Some(Egg::Plain), 0));
+        assert_eq!(pick(&[(B, false), (A, true)]), (Some(Egg::Speckled), 1));
+        assert_eq!(
+            pick(&[(B, false), (C, false), (C, true)]),
+            (Some(Egg::Ember), 2)
+        );
+        // Round past the last egg, and nothing picked without a hold.
+        assert_eq!(pick(&[(A, false), (B, false), (C, false)]), (None, 0));
+        assert_eq!(pick(&[(A, false), (B, false)]), (None, 2));
+    }
+
+    #[test]
+    fn three_quick_taps_still_only_step_once() {
+        let (mut picker, now) = (Picker::new(Instant::now()), Instant::now());
+        let mut taps = TapDetector::default();
+        let tap = Press {
+            button: Button::A,
+            long: false,
+        };
+        let events: Vec<_> = (0..3).flat_map(|_| taps.press(tap, now)).collect();
+        assert_eq!(events, [ButtonEvent::Tripletap(Button::A)]);
+        for event in events {
+            assert_eq!(picker.handle(event), None);
+        }
+        assert_eq!(picker.selected, 1);
+    }
+
+    #[test]
+    fn left_alone_it_times_out() {
+        let opened = Instant::now();
+        let mut picker = Picker::new(opened);
+        picker.handle(ButtonEvent::Short(Button::A));
+        assert!(!picker.timed_out(opened + TIMEOUT - Duration::from_secs(1)));
+        assert!(picker.timed_out(opened + TIMEOUT));
+        assert_eq!(picker.deadline(), opened + Duration::from_secs(300));
+    }
+}
@@ -0,0 +1,193 @@
+//! Watches the host for changes worth telling the pet about (the network
+//! going away, the disk filling up, the supply sagging) and publishes them
+//! as [`SystemEvent`]s on the main channel.
+//!
+//! Readings are debounced: a new state has to hold for several readings in a
+//! row before it counts, so a flapping WiFi link doesn't make the display
+//! churn.
+
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::channel::Outbox;
+use crate::collector::{Snapshot, Source};
+use crate::events::{Command, SystemEvent};
+use crate::system;
+
+const INTERVAL: Duration = Duration::from_secs(15);
+/// Identical readings in a row before a new state is believed.
+const SETTLE_READINGS: u32 = 3;
+/// Disk use, as a fraction, above which the disk counts as almost full.
+const DISK_FULL: f32 = 0.95;
+
+/// A value that only changes once a new reading has repeated enough times.
+struct Debounce<T> {
+    settled: Option<T>,
+    candidate: Option<(T, u32)>,
+}
+
+impl<T: Clone + PartialEq> Debounce<T> {
+    fn new() -> Self {
+        Debounce {
+            settled: None,
+            candidate: None,
+        }
+    }
+
+    /// Feeds in a reading. Returns the previous settled value (`None` on the
+    /// first) and the new one whenever the settled value changes.
+    fn update(&mut self, reading: T) -> Option<(Option<T>, T)> {
+        if self.settled.as_ref() == Some(&reading) {
+            self.candidate = None;
+            return None;
+        }
+        let seen = match &self.candidate {
+            Some((value, seen)) if *value == reading => seen + 1,
+            _ => 1,
+        };
+        if seen < SETTLE_READINGS {
+            self.candidate = Some((reading, seen));
+            return None;
+        }
+        self.candidate = None;
+        let previous = self.settled.replace(reading.clone());
+        Some((previous, reading))
+    }
+}
+
+pub struct HealthSource<O> {
+    events: O,
+    disk: PathBuf,
+    address: Debounce<Option<IpAddr>>,
+    disk_full: Debounce<bool>,
+    undervoltage: Debounce<bool>,
+}
+
+impl<O: Outbox> HealthSource<O> {
+    /// Watches the filesystem holding `disk` among other things.
+    pub fn new(events: O, disk: PathBuf) -> Self {
+        HealthSource {
+            events,
+            disk,
+            address: Debounce::new(),
+            disk_full: Debounce::new(),
+            undervoltage: Debounce::new(),
+        }
+    }
+
+    fn publish(&self, event: SystemEvent) {
+        log::info!("system event: {event:?}");
+        // Only fails while shutting down.
+        self.events.send(Command::System(event));
+    }
+
+    /// Turns a settled change in a yes/no condition into an event. Starting
+    /// out healthy isn't news; starting out unhealthy is.
+    fn flag(
+        &self,
+        change: Option<(Option<bool>, bool)>,
+        raised: SystemEvent,
+        cleared: SystemEvent,
+    ) {
+        match change {
+            Some((_, true)) => self.publish(raised),
+            Some((Some(true), false)) => self.publish(cleared),
+            _ => {}
+        }
+    }
+}
+
+impl<O: Outbox> Source for HealthSource<O> {
+    fn name(&self) -> &'static str {
+        "health"
+    }
+
+    fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    fn collect(&mut self, _snapshot: &Mutex<Snapshot>) {
+        match self.address.update(system::local_ip()) {
+            Some((Some(Some(_)) | None, None)) => self.publish(SystemEvent::NetworkDown),
+            Some((Some(None), Some(_))) => self.publish(SystemEvent::NetworkUp),
+            Some((Some(Some(old)), Some(new))) => {
+                log::info!("address changed from {old} to {new}");
+                self.publish(SystemEvent::AddressChanged);
+            }
+            _ => {}
+        }
+
+        if let Some(usage) = system::disk_usage(&self.disk) {
+            let change = self.disk_full.update(usage > DISK_FULL);
+            self.flag(change, SystemEvent::DiskAlmostFull, SystemEvent::DiskOk);
+        }
+        if let Some(low) = system::undervoltage() {
+            let change = self.undervoltage.update(low);
+            self.flag(change, SystemEvent::Undervoltage, SystemEvent::PowerOk);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What each of `readings` settled into, in order.
+    fn changes<T: Clone + PartialEq>(
+        debounce: &mut Debounce<T>,
+        readings: &[T],
+    ) -> Vec<(Option<T>, T)> {
+        readings
+            .iter()
+            .filter_map(|reading| debounce.update(reading.clone()))
+            .collect()
+    }
+
+    fn settled() -> Debounce<bool> {
+        let mut debounce = Debounce::new();
+        changes(&mut debounce, &[true; SETTLE_READINGS as usize]);
+        debounce
+    }
+
+    #[test]
+    fn the_first_state_settles_from_nothing() {
+        let mut debounce = Debounce::new();
+        for _ in 1..SETTLE_READINGS {
+            assert_eq!(debounce.update(true), None);
+        }
+        assert_eq!(debounce.update(true), Some((None, true)));
+        assert_eq!(debounce.update(true), None);
+    }
+
+    #[test]
+    fn a_short_flip_changes_nothing() {
+        let mut debounce = settled();
+        let mut flapping = vec![false; SETTLE_READINGS as usize - 1];
+        flapping.push(true);
+        let flapping = flapping.repeat(5);
+        assert_eq!(changes(&mut debounce, &flapping), []);
+    }
+
+    #[test]
+    fn a_held_state_changes_once() {
+        let mut debounce = settled();
+        let held = [false; 2 * SETTLE_READINGS as usize];
+        assert_eq!(changes(&mut debounce, &held), [(Some(true), false)]);
+    }
+
+    #[test]
+    fn only_readings_in_a_row_count() {
+        let mut debounce: Debounce<Option<IpAddr>> = Debounce::new();
+        let home: IpAddr = "192.168.1.20".parse().unwrap();
+        let away: IpAddr = "10.0.0.7".parse().unwrap();
+        changes(&mut debounce, &[Some(home); SETTLE_READINGS as usize]);
+        // Each new address starts the count over.
+        let readings = [None, None, Some(away), Some(away), None, None, None];
+        assert_eq!(
+            changes(&mut debounce, &readings),
+            [(Some(Some(home)), None)]
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! Shell commands run when something happens to the pet, e.g. a message
+//! sent when it's hungry or a smart plug switched on when it wakes.
+//!
+//! `[hooks]` maps event names to commands. Each runs with `sh -c` on a
+//! thread of its own, with what happened in `INKY_*` environment
+//! variables, and is killed once it runs over the timeout. A hook that
+//! fails is logged and counted, and that's all: the pet and the panel
+//! carry on regardless.
+
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::collector::Snapshot;
+use crate::config::HooksConfig;
+use crate::events::PetEvent;
+use crate::journal::HOT_SPELL;
+use crate::pet::Pet;
+use crate::runtime::Runtime;
+use crate::subprocess;
+
+/// An environment variable a hook gets, for `--list-events`, and how to
+/// work it out.
+struct Var {
+    name: &'static str,
+    about: &'static str,
+    value: fn(&Payload) -> String,
+}
+
+/// What a hook is told about.
+struct Payload<'a> {
+    event: PetEvent,
+    pet: &'a Pet,
+    runtime: &'a Runtime,
+    snapshot: &'a Snapshot,
+    now: DateTime<Utc>,
+}
+
+/// What every hook gets.
+const COMMON: [Var; 9] = [
+    Var {
+        name: "INKY_EVENT",
+        about: "the event's name, as below",
+        value: |payload| payload.event.name().into(),
+    },
+    Var {
+        name: "INKY_TIME",
+        about: "when it happened, RFC 3339 in UTC",
+        value: |payload| payload.now.to_rfc3339(),
+    },
+    Var {
+        name: "INKY_PET_NAME",
+        about: "the pet's name",
+        value: |Payload { pet, .. }| pet.name.clone(),
+    },
+    Var {
+        name: "INKY_HUNGER",
+        about: "fullness, 0 to 100",
+        value: |Payload { pet, .. }| format!("{:.0}", pet.hunger),
+    },
+    Var {
+        name: "INKY_HAPPINESS",
+        about: "0 to 100",
+        value: |Payload { pet, .. }| format!("{:.0}", pet.happiness),
+    },
+    Var {
+        name: "INKY_ENERGY",
+        about: "0 to 100",
+        value: |Payload { pet, .. }| format!("{:.0}", pet.energy),
+    },
+    Var {
+        name: "INKY_TRAINING",
+        about: "0 to 100",
+        value: |Payload { pet, .. }| format!("{:.0}", pet.training),
+    },
+    Var {
+        name: "INKY_MESSES",
+        about: "messes waiting to be cleaned",
+        value: |Payload { pet, .. }| pet.messes.to_string(),
+    },
+    Var {
+        name: "INKY_FORM",
+        about: "baby, noble or wild",
+        value: |Payload { pet, .. }| format!("{:?}", pet.form).to_lowercase(),
+    },
+];
+
+const CRITICAL: [Var; 1] = [Var {
+    name: "INKY_STAT",
+    about: "the stat that's low, hunger or energy",
+    value: |Payload { pet, .. }| match pet.hunger <= pet.energy {
+        true => "hunger".into(),
+        false => "energy".into(),
+    },
+}];
+
+const SICK: [Var; 1] = [Var {
+    name: "INKY_SICKNESS",
+    about: "how sick, 0 to 100",
+    value: |Payload { pet, .. }| format!("{:.0}", pet.sickness),
+}];
+
+const DIED: [Var; 1] = [Var {
+    name: "INKY_AGE_DAYS",
+    about: "whole days the pet lived",
+    value: |Payload { pet, .. }| {
+        let died = pet.died.unwrap_or(pet.last_tick);
+        (died - pet.born).num_days().to_string()
+    },
+}];
+
+const VISITED: [Var; 1] = [Var {
+    name: "INKY_USER",
+    about: "who logged in",
+    value: |Payload { runtime, .. }| runtime.visitor.clone().unwrap_or_default(),
+}];
+
+const UPGRADES: [Var; 1] = [Var {
+    name: "INKY_UPGRADES",
+    about: "how many packages can be upgraded",
+    value: |Payload { snapshot, .. }| snapshot.upgrades.unwrap_or(0).to_string(),
+}];
+
+/// Variables only some events come with.
+fn extra(event: PetEvent) -> &'static [Var] {
+    match event {
+        PetEvent::StatCritical => &CRITICAL,
+        PetEvent::Sick => &SICK,
+        PetEvent::Died => &DIED,
+        PetEvent::Visited => &VISITED,
+        PetEvent::UpgradesPending => &UPGRADES,
+        _ => &[],
+    }
+}
+
+/// Every event a hook can be set for and the variables it gets, for
+/// `--list-events`.
+pub fn describe() -> String {
+    let mut out = String::from("Every hook gets:\n");
+    for var in &COMMON {
+        out += &format!("  {:<16}{}\n", var.name, var.about);
+    }
+    out += "\nEvents, with anything extra they get:\n";
+    for event in PetEvent::ALL {
+        out += event.name();
+        out += "\n";
+        for var in extra(event) {
+            out += &format!("  {:<16}{}\n", var.name, var.about);
+        }
+    }
+    out
+}
+
+/// Hook runs since startup, for the status file.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct HookStats {
+    pub runs: u64,
+    /// Exited with an error or couldn't be started.
+    pub failures: u64,
+    /// Killed for taking too long.
+    pub timeouts: u64,
+}
+
+pub struct Hooks {
+    /// By event name.
+    commands: BTreeMap<&'static str, String>,
+    timeout: Duration,
+    stats: Arc<Mutex<HookStats>>,
+    /// `hot` comes every cycle while it lasts; only the start of a spell
+    /// runs its hook.
+    last_hot: Option<DateTime<Utc>>,
+}
+
+impl Hooks {
+    pub fn new(config: &HooksConfig) -> Self {
+        Hooks {
+            commands: config
+                .commands
+                .iter()
+                .filter_map(|(name, command)| {
+                    Some((PetEvent::parse(name)?.name(), command.clone()))
+                })
+                .collect(),
+            timeout: Duration::from_secs(config.timeout),
+            stats: Arc::default(),
+            last_hot: None,
+        }
+    }
+
+    pub fn stats(&self) -> HookStats {
+        lock(&self.stats).clone()
+    }
+
+    /// Starts the hook for `event`, if there is one, and leaves it to run.
+    pub fn fire(
+        &mut self,
+        event: PetEvent,
+        pet: &Pet,
+        runtime: &Runtime,
+        snapshot: &Snapshot,
+        now: DateTime<Utc>,
+    ) {
+        if event == PetEvent::Hot {
+            let spell = self.last_hot.is_some_and(|at| now - at < HOT_SPELL);
+            self.last_hot = Some(now);
+            if spell {
+                return;
+            }
+        }
+        let Some(script) = self.commands.get(event.name()) else {
+            return;
+        };
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        let payload = Payload {
+            event,
+            pet,
+            runtime,
+            snapshot,
+            now,
+        };
+        for var in COMMON.iter().chain(extra(event)) {
+            command.env(var.name, (var.value)(&payload));
+        }
+        let (name, timeout, stats) = (event.name(), self.timeout, self.stats.clone());
+        log::debug!("running the {name} hook");
+        thread::spawn(move || {
+            let finished = subprocess::run(&mut command, timeout);
+            let mut stats = lock(&stats);
+            stats.runs += 1;
+            match finished {
+                Ok(finished) => match finished.status {
+                    Some(status) if status.success() => {}
+                    Some(status) => {
+                        stats.failures += 1;
+                        log::warn!("the {name} hook {status}: {}", finished.stderr);
+                    }
+                    None => {
+                        stats.timeouts += 1;
+                        log::warn!(
+                            "the {name} hook took longer than {}s, stopped it",
+                            timeout.as_secs()
+                        );
+                    }
+                },
+                Err(err) => {
+                    stats.failures += 1;
+                    log::warn!("couldn't run the {name} hook: {err}");
+                }
+            }
+        });
+    }
+}
+
+fn lock(stats: &Mutex<HookStats>) -> std::sync::MutexGuard<'_, HookStats> {
+    stats
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn hooks(commands: &[(&str, String)], timeout: u64) -> Hooks {
+        Hooks::new(&HooksConfig {
+            timeout,
+            commands: commands
+                .iter()
+                .map(|(name, command)| (name.to_string(), command.clone()))
+                .collect(),
+        })
+    }
+
+    /// Fires `event` at the pet, sick and a bit hungry.
+    fn fire(hooks: &mut Hooks, event: PetEvent, now: DateTime<Utc>) {
+        let mut pet = Pet::new("Mara", now);
+        (pet.hunger, pet.sickness) = (42.4, 63.0);
+        let runtime = Runtime::new(1, &mut pet);
+        hooks.fire(event, &pet, &runtime, &Snapshot::default(), now);
+    }
+
+    /// Waits for the hooks started so far to have finished `runs`.
+    fn wait_for_runs(hooks: &Hooks, runs: u64) -> HookStats {
+        let start = Instant::now();
+        while hooks.stats().runs < runs {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "hooks never finished"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+        hooks.stats()
+    }
+
+    #[test]
+    fn a_hook_gets_what_happened_in_its_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("env");
+        let command = format!("env | grep ^INKY_ | sort > {:?}", out.display().to_string());
+        let mut hooks = hooks(&[("sick", command)], 5);
+        let now: DateTime<Utc> = "2024-05-01T09:00:00Z".parse().unwrap();
+        fire(&mut hooks, PetEvent::Sick, now);
+        assert_eq!(wait_for_runs(&hooks, 1).failures, 0);
+        let env = std::fs::read_to_string(&out).unwrap();
+        for line in [
+            "INKY_EVENT=sick",
+            "INKY_PET_NAME=Mara",
+            "INKY_HUNGER=42",
+            "INKY_SICKNESS=63",
+            "INKY_TIME=2024-05-01T09:00:00+00:00",
+        ] {
+            assert!(env.lines().any(|l| l == line), "no {line} in:\n{env}");
+        }
+    }
+
+    #[test]
+    fn failures_and_timeouts_are_counted() {
+        let mut hooks = hooks(&[("fed", "exit 3".into()), ("died", "sleep 5".into())], 1);
+        let now = Utc::now();
+        fire(&mut hooks, PetEvent::Fed, now);
+        fire(&mut hooks, PetEvent::Died, now);
+        // Events without a hook run nothing.
+        fire(&mut hooks, PetEvent::Played, now);
+        let stats = wait_for_runs(&hooks, 2);
+        assert_eq!((stats.runs, stats.failures, stats.timeouts), (2, 1, 1));
+    }
+
+    #[test]
+    fn a_hot_spell_runs_its_hook_once() {
+        let mut hooks = hooks(&[("hot", "true".into())], 5);
+        let now = Utc::now();
+        for minutes in 0..10 {
+            fire(
+                &mut hooks,
+                PetEvent::Hot,
+                now + chrono::Duration::minutes(minutes),
+            );
+        }
+        fire(
+            &mut hooks,
+            PetEvent::Hot,
+            now + chrono::Duration::minutes(9) + HOT_SPELL,
+        );
+        wait_for_runs(&hooks, 2);
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(hooks.stats().runs, 2);
+    }
+
+    #[test]
+    fn every_event_is_listed_with_its_variables() {
+        let listed = describe();
+        for event in PetEvent::ALL {
+            assert!(
+                listed.lines().any(|line| line == event.name()),
+                "{}",
+                event.name()
+            );
+        }
+        let sick = listed.split("\nsick\n").nth(1).unwrap();
+        assert!(sick.starts_with("  INKY_SICKNESS"));
+        // Names in the config map to the events they're for, and others
+        // are dropped.
+        let hooks = hooks(&[("sick", "true".into()), ("dance", "true".into())], 5);
+        assert_eq!(hooks.commands.keys().copied().collect::<Vec<_>>(), ["sick"]);
+    }
+}
@@ -0,0 +1,949 @@
+//! The shared icon set, all on a 12×12 grid so they line up in headers and
+//! footers. Every icon can also be drawn at 24×24 for larger layouts.
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+
+use crate::display::{Color, Frame};
+use crate::sprite::{Flip, Sprite};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Icon {
+    Sunrise,
+    Sunset,
+    Sun,
+    Cloud,
+    Rain,
+    Snow,
+    Storm,
+    Fog,
+    BatteryFull,
+    BatteryHalf,
+    BatteryLow,
+    BatteryCharging,
+    WifiOff,
+    WifiWeak,
+    WifiMedium,
+    WifiStrong,
+    Heart,
+    Skull,
+    RedCross,
+    /// A newer release is out.
+    UpdateAvailable,
+    /// An apple.
+    Food,
+    /// A ball, for games played.
+    Game,
+    Broom,
+    /// A streak that's still going.
+    Flame,
+    /// A birthday cake, for the pet's age.
+    Cake,
+    Trophy,
+    /// A chain link snapped in two, for being offline.
+    BrokenLink,
+    /// A disk with its gauge pinned at full.
+    DiskFull,
+    /// Notifications.
+    Bell,
+    /// A tick, e.g. for passing CI.
+    Check,
+    /// An X, e.g. for failing CI.
+    Cross,
+    /// A shopping cart, for errands.
+    Cart,
+    /// Saving the battery.
+    Leaf,
+    /// Package upgrades waiting.
+    Package,
+    /// A drawing pin, for a screen pinned in place. Short enough for a
+    /// title bar.
+    Pin,
+    /// Guest mode is on, see [`crate::guest`]. As short as the pin.
+    Mask,
+}
+
+impl Icon {
+    /// Edge length of the regular size, in pixels.
+    pub const SIZE: u32 = 12;
+
+    /// Every icon, for rendering the whole set at once.
+    pub const ALL: [Icon; 36] = [
+        Icon::Sunrise,
+        Icon::Sunset,
+        Icon::Sun,
+        Icon::Cloud,
+        Icon::Rain,
+        Icon::Snow,
+        Icon::Storm,
+        Icon::Fog,
+        Icon::BatteryFull,
+        Icon::BatteryHalf,
+        Icon::BatteryLow,
+        Icon::BatteryCharging,
+        Icon::WifiOff,
+        Icon::WifiWeak,
+        Icon::WifiMedium,
+        Icon::WifiStrong,
+        Icon::Heart,
+        Icon::Skull,
+        Icon::RedCross,
+        Icon::UpdateAvailable,
+        Icon::Food,
+        Icon::Game,
+        Icon::Broom,
+        Icon::Flame,
+        Icon::Cake,
+        Icon::Trophy,
+        Icon::BrokenLink,
+        Icon::DiskFull,
+        Icon::Bell,
+        Icon::Check,
+        Icon::Cross,
+        Icon::Cart,
+        Icon::Leaf,
+        Icon::Package,
+        Icon::Pin,
+        Icon::Mask,
+    ];
+
+    /// Looks up an icon by its name in snake case, e.g. `battery_full`.
+    pub fn parse(name: &str) -> Option<Icon> {
+        let name = name.replace('_', "");
+        Icon::ALL
+            .into_iter()
+            .find(|icon| format!("{icon:?}").eq_ignore_ascii_case(&name))
+    }
+
+    fn sprite(self) -> &'static Sprite {
+        match self {
+            Icon::Sunrise => &SUNRISE,
+            Icon::Sunset => &SUNSET,
+            Icon::Sun => &SUN,
+            Icon::Cloud => &CLOUD,
+            Icon::Rain => &RAIN,
+            Icon::Snow => &SNOW,
+            Icon::Storm => &STORM,
+            Icon::Fog => &FOG,
+            Icon::BatteryFull => &BATTERY_FULL,
+            Icon::BatteryHalf => &BATTERY_HALF,
+            Icon::BatteryLow => &BATTERY_LOW,
+            Icon::BatteryCharging => &BATTERY_CHARGING,
+            Icon::WifiOff => &WIFI_OFF,
+            Icon::WifiWeak => &WIFI_WEAK,
+            Icon::WifiMedium => &WIFI_MEDIUM,
+            Icon::WifiStrong => &WIFI_STRONG,
+            Icon::Heart => &HEART,
+            Icon::Skull => &SKULL,
+            Icon::RedCross => &RED_CROSS,
+            Icon::UpdateAvailable => &UPDATE_AVAILABLE,
+            Icon::Food => &FOOD,
+            Icon::Game => &GAME,
+            Icon::Broom => &BROOM,
+            Icon::Flame => &FLAME,
+            Icon::Cake => &CAKE,
+            Icon::Trophy => &TROPHY,
+            Icon::BrokenLink => &BROKEN_LINK,
+            Icon::DiskFull => &DISK_FULL,
+            Icon::Bell => &BELL,
+            Icon::Check => &CHECK,
+            Icon::Cross => &CROSS,
+            Icon::Cart => &CART,
+            Icon::Leaf => &LEAF,
+            Icon::Package => &PACKAGE,
+            Icon::Pin => &PIN,
+            Icon::Mask => &MASK,
+        }
+    }
+
+    /// Draws the icon's set pixels in `color`, leaving the rest untouched.
+    pub fn draw<D>(self, target: &mut D, origin: Point, color: Color)
+    where
+        D: DrawTarget<Color = Color>,
+    {
+        self.sprite().draw(target, origin, color, Flip::None);
+    }
+
+    /// Draws the icon at 24×24.
+    pub fn draw_large<D>(self, target: &mut D, origin: Point, color: Color)
+    where
+        D: DrawTarget<Color = Color>,
+    {
+        self.sprite().draw_scaled(target, origin, color, 2);
+    }
+
+    /// Draws the icon over a filled `background` square, for placing it on
+    /// inverted or red areas where transparent pixels would disappear.
+    pub fn draw_opaque<D>(self, target: &mut D, origin: Point, color: Color, background: Color)
+    where
+        D: DrawTarget<Color = Color>,
+    {
+        Rectangle::new(origin, Size::new(Self::SIZE, Self::SIZE))
+            .into_styled(PrimitiveStyle::with_fill(background))
+            .draw(target)
+            .ok();
+        self.draw(target, origin, color);
+    }
+}
+
+/// Renders every icon in each way it can be drawn, one row per style:
+/// black, red, inverted, and large. Handy for eyeballing the set.
+pub fn sheet() -> Frame {
+    let pitch = Icon::SIZE as i32 + 2;
+    let large_pitch = 2 * Icon::SIZE as i32 + 2;
+    let width = 2 + large_pitch * Icon::ALL.len() as i32;
+    let mut frame = Frame::new(width as u32, (3 * pitch + large_pitch + 2) as u32);
+    for (i, icon) in Icon::ALL.into_iter().enumerate() {
+        let x = 2 + i as i32 * pitch;
+        icon.draw(&mut frame, Point::new(x, 2), Color::Black);
+        icon.draw(&mut frame, Point::new(x, 2 + pitch), Color::Red);
+        icon.draw_opaque(
+            &mut frame,
+            Point::new(x, 2 + 2 * pitch),
+            Color::White,
+            Color::Black,
+        );
+        let x = 2 + i as i32 * large_pitch;
+        icon.draw_large(&mut frame, Point::new(x, 2 + 3 * pitch), Color::Black);
+    }
+    frame
+}
+
+const SUNRISE: Sprite = Sprite::new(
+    12,
+    &[
+        0b000001100000,
+        0b000011110000,
+        0b000111111000,
+        0b000000000000,
+        0b001000000100,
+        0b000101101000,
+        0b000011110000,
+        0b000111111000,
+        0b001111111100,
+        0b111111111111,
+        0b000000000000,
+        0b000000000000,
+    ],
+);
+
+const SUNSET: Sprite = Sprite::new(
+    12,
+    &[
+        0b000111111000,
+        0b000011110000,
+        0b000001100000,
+        0b000000000000,
+        0b001000000100,
+        0b000101101000,
+        0b000011110000,
+        0b000111111000,
+        0b001111111100,
+        0b111111111111,
+        0b000000000000,
+        0b000000000000,
+    ],
+);
+
+const SUN: Sprite = Sprite::new(
+    12,
+    &[
+        0b000001100000,
+        0b010001100010,
+        0b001000000100,
+        0b000011110000,
+        0b000111111000,
+        0b110111111011,
+        0b110111111011,
+        0b000111111000,
+        0b000011110000,
+        0b001000000100,
+        0b010001100010,
+        0b000001100000,
+    ],
+);
+
+const CLOUD: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000000,
+        0b000000000000,
+        0b000011100000,
+        0b000100010000,
+        0b011100001100,
+        0b100000000110,
+        0b100000000010,
+        0b100000000010,
+        0b011111111100,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+    ],
+);
+
+const RAIN: Sprite = Sprite::new(
+    12,
+    &[
+        0b000011100000,
+        0b000100010000,
+        0b011100001100,
+        0b100000000110,
+        0b100000000010,
+        0b011111111100,
+        0b000000000000,
+        0b001001001000,
+        0b010010010000,
+        0b000000000000,
+        0b010010010000,
+        0b100100100000,
+    ],
+);
+
+const SNOW: Sprite = Sprite::new(
+    12,
+    &[
+        0b000011100000,
+        0b000100010000,
+        0b011100001100,
+        0b100000000110,
+        0b100000000010,
+        0b011111111100,
+        0b000000000000,
+        0b010001000100,
+        0b111011101110,
+        0b010001000100,
+        0b000000000000,
+        0b000000000000,
+    ],
+);
+
+const STORM: Sprite = Sprite::new(
+    12,
+    &[
+        0b000011100000,
+        0b000100010000,
+        0b011100001100,
+        0b100000000110,
+        0b100000000010,
+        0b011110011100,
+        0b000001100000,
+        0b000011000000,
+        0b000111111000,
+        0b000000110000,
+        0b000001100000,
+        0b000010000000,
+    ],
+);
+
+const FOG: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000000,
+        0b001111111100,
+        0b000000000000,
+        0b011111111110,
+        0b000000000000,
+        0b111111110000,
+        0b000000000000,
+        0b000111111111,
+        0b000000000000,
+        0b011111111110,
+        0b000000000000,
+        0b000000000000,
+    ],
+);
+
+const BATTERY_FULL: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b011111111100,
+        0b010111111100,
+        0b010111111101,
+        0b010111111101,
+        0b010111111100,
+        0b011111111100,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+    ],
+);
+
+const BATTERY_HALF: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b011111111100,
+        0b010111100100,
+        0b010111100101,
+        0b010111100101,
+        0b010111100100,
+        0b011111111100,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+    ],
+);
+
+const BATTERY_LOW: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b011111111100,
+        0b010100000100,
+        0b010100000101,
+        0b010100000101,
+        0b010100000100,
+        0b011111111100,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+    ],
+);
+
+const BATTERY_CHARGING: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b011111111100,
+        0b010001000100,
+        0b010011000101,
+        0b010111111101,
+        0b010001100100,
+        0b010001000100,
+        0b011111111100,
+        0b000000000000,
+        0b000000000000,
+    ],
+);
+
+const WIFI_OFF: Sprite = Sprite::new(
+    12,
+    &[
+        0b100000000000,
+        0b010000000000,
+        0b001000000000,
+        0b000100000000,
+        0b000010000000,
+        0b000001000000,
+        0b000000100000,
+        0b000000010000,
+        0b000000001000,
+        0b000000000100,
+        0b000001100010,
+        0b000001100001,
+    ],
+);
+
+const WIFI_WEAK: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b000001100000,
+        0b000001100000,
+    ],
+);
+
+const WIFI_MEDIUM: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+        0b000011110000,
+        0b000100001000,
+        0b001001100100,
+        0b000001100000,
+        0b000000000000,
+        0b000001100000,
+        0b000001100000,
+    ],
+);
+
+const WIFI_STRONG: Sprite = Sprite::new(
+    12,
+    &[
+        0b000111111000,
+        0b011000000110,
+        0b100001100001,
+        0b000111111000,
+        0b001000000100,
+        0b000011110000,
+        0b000100001000,
+        0b000001100000,
+        0b000000000000,
+        0b000000000000,
+        0b000001100000,
+        0b000001100000,
+    ],
+);
+
+const HEART: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000000,
+        0b011100011100,
+        0b111110111110,
+        0b111111111110,
+        0b111111111110,
+        0b111111111110,
+        0b011111111100,
+        0b001111111000,
+        0b000111110000,
+        0b000011100000,
+        0b000001000000,
+        0b000000000000,
+    ],
+);
+
+const SKULL: Sprite = Sprite::new(
+    12,
+    &[
+        0b000111111000,
+        0b001111111100,
+        0b011111111110,
+        0b011001100110,
+        0b010001100010,
+        0b011001100110,
+        0b011110011110,
+        0b001110011100,
+        0b000111111000,
+        0b000101101000,
+        0b000111111000,
+        0b000000000000,
+    ],
+);
+
+const RED_CROSS: Sprite = Sprite::new(
+    12,
+    &[
+        0b000011110000,
+        0b000011110000,
+        0b000011110000,
+        0b000011110000,
+        0b111111111111,
+        0b111111111111,
+        0b111111111111,
+        0b111111111111,
+        0b000011110000,
+        0b000011110000,
+        0b000011110000,
+        0b000011110000,
+    ],
+);
+
+const UPDATE_AVAILABLE: Sprite = Sprite::new(
+    12,
+    &[
+        0b000001100000,
+        0b000011110000,
+        0b000111111000,
+        0b001110011100,
+        0b011100001110,
+        0b000001100000,
+        0b000001100000,
+        0b000001100000,
+        0b000001100000,
+        0b000001100000,
+        0b001111111100,
+        0b000000000000,
+    ],
+);
+
+const FOOD: Sprite = Sprite::new(
+    12,
+    &[
+        0b000001100000,
+        0b000011000000,
+        0b001101011000,
+        0b011111111110,
+        0b111111111111,
+        0b111111111101,
+        0b111111111101,
+        0b111111111110,
+        0b111111111111,
+        0b011111111110,
+        0b001111111100,
+        0b000110011000,
+    ],
+);
+
+const GAME: Sprite = Sprite::new(
+    12,
+    &[
+        0b000011110000,
+        0b001100001100,
+        0b010010010010,
+        0b010001100010,
+        0b100001100001,
+        0b100110011001,
+        0b100110011001,
+        0b100001100001,
+        0b010001100010,
+        0b010010010010,
+        0b001100001100,
+        0b000011110000,
+    ],
+);
+
+const BROOM: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000110,
+        0b000000001100,
+        0b000000011000,
+        0b000000110000,
+        0b000001100000,
+        0b000111100000,
+        0b001111110000,
+        0b010101011000,
+        0b010101010100,
+        0b101010101000,
+        0b101010100000,
+        0b010101000000,
+    ],
+);
+
+const FLAME: Sprite = Sprite::new(
+    12,
+    &[
+        0b000001000000,
+        0b000001100000,
+        0b000011100000,
+        0b000011110000,
+        0b000111110100,
+        0b001111111010,
+        0b011110011110,
+        0b011100001110,
+        0b111100001111,
+        0b011101101110,
+        0b001110011100,
+        0b000011110000,
+    ],
+);
+
+const CAKE: Sprite = Sprite::new(
+    12,
+    &[
+        0b001001001000,
+        0b001001001000,
+        0b011101011100,
+        0b011101011100,
+        0b111111111111,
+        0b100000000001,
+        0b101010101011,
+        0b111111111111,
+        0b100000000001,
+        0b100000000001,
+        0b100000000001,
+        0b111111111111,
+    ],
+);
+
+const TROPHY: Sprite = Sprite::new(
+    12,
+    &[
+        0b111111111111,
+        0b111111111111,
+        0b011111111110,
+        0b110111111101,
+        0b100111111100,
+        0b010111111101,
+        0b001111111100,
+        0b000111111000,
+        0b000001100000,
+        0b000001100000,
+        0b000111111000,
+        0b001111111100,
+    ],
+);
+
+const BROKEN_LINK: Sprite = Sprite::new(
+    12,
+    &[
+        0b010000000010,
+        0b001000000100,
+        0b000000000000,
+        0b111110011111,
+        0b100010010001,
+        0b101110011101,
+        0b101000000101,
+        0b101110011101,
+        0b100010010001,
+        0b111110011111,
+        0b001000000100,
+        0b010000000010,
+    ],
+);
+
+const DISK_FULL: Sprite = Sprite::new(
+    12,
+    &[
+        0b011111111110,
+        0b100000000001,
+        0b101111111101,
+        0b101111111101,
+        0b100000000001,
+        0b111111111111,
+        0b100000000001,
+        0b101100000001,
+        0b100000000001,
+        0b111111111111,
+        0b000000000000,
+        0b000000000000,
+    ],
+);
+
+const BELL: Sprite = Sprite::new(
+    12,
+    &[
+        0b000001100000,
+        0b000011110000,
+        0b000100001000,
+        0b001000000100,
+        0b001000000100,
+        0b001000000100,
+        0b001000000100,
+        0b010000000010,
+        0b100000000001,
+        0b111111111111,
+        0b000010010000,
+        0b000001100000,
+    ],
+);
+
+const CHECK: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000000,
+        0b000000000001,
+        0b000000000011,
+        0b000000000110,
+        0b000000001100,
+        0b100000011000,
+        0b110000110000,
+        0b011001100000,
+        0b001111000000,
+        0b000110000000,
+        0b000000000000,
+        0b000000000000,
+    ],
+);
+
+const CROSS: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000000,
+        0b011000000110,
+        0b011100001110,
+        0b001110011100,
+        0b000111111000,
+        0b000011110000,
+        0b000011110000,
+        0b000111111000,
+        0b001110011100,
+        0b011100001110,
+        0b011000000110,
+        0b000000000000,
+    ],
+);
+
+const CART: Sprite = Sprite::new(
+    12,
+    &[
+        0b110000000000,
+        0b010000000000,
+        0b011111111110,
+        0b010000000010,
+        0b001000000100,
+        0b001000000100,
+        0b000111111100,
+        0b000100000000,
+        0b000111111110,
+        0b000010000100,
+        0b000111001110,
+        0b000010000100,
+    ],
+);
+
+const LEAF: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000111,
+        0b000000111101,
+        0b000011110011,
+        0b000111100111,
+        0b001111001111,
+        0b011110011110,
+        0b011100111110,
+        0b011001111100,
+        0b010011111000,
+        0b000111100000,
+        0b001000000000,
+        0b010000000000,
+    ],
+);
+
+const PACKAGE: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000000,
+        0b000111111000,
+        0b001000110100,
+        0b010000110010,
+        0b111111111111,
+        0b100000110001,
+        0b100000110001,
+        0b100000000001,
+        0b100000000001,
+        0b100000000001,
+        0b111111111111,
+        0b000000000000,
+    ],
+);
+
+const PIN: Sprite = Sprite::new(
+    12,
+    &[
+        0b000111111000,
+        0b000011110000,
+        0b000011110000,
+        0b000011110000,
+        0b000111111000,
+        0b001111111100,
+        0b000001100000,
+        0b000001100000,
+        0b000001100000,
+        0b000001000000,
+        0b000000000000,
+        0b000000000000,
+    ],
+);
+
+const MASK: Sprite = Sprite::new(
+    12,
+    &[
+        0b000000000000,
+        0b000000000000,
+        0b011000000110,
+        0b111111111111,
+        0b110011110011,
+        0b100001100001,
+        0b110011110011,
+        0b011111111110,
+        0b001110011100,
+        0b000000000000,
+        0b000000000000,
+        0b000000000000,
+    ],
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points of a `size`-square frame in `color`.
+    fn inked(frame: &Frame, size: u32, color: Color) -> Vec<Point> {
+        (0..size as i32)
+            .flat_map(|y| (0..size as i32).map(move |x| Point::new(x, y)))
+            .filter(|p| frame.pixel(p.x, p.y) == color)
+            .collect()
+    }
+
+    fn drawn(draw: impl FnOnce(&mut Frame)) -> Frame {
+        let mut frame = Frame::new(2 * Icon::SIZE, 2 * Icon::SIZE);
+        draw(&mut frame);
+        frame
+    }
+
+    #[test]
+    fn every_icon_is_listed_once() {
+        for (i, icon) in Icon::ALL.iter().enumerate() {
+            assert!(!Icon::ALL[..i].contains(icon), "{icon:?} listed twice");
+        }
+    }
+
+    #[test]
+    fn every_icon_draws_the_same_in_either_plane() {
+        for icon in Icon::ALL {
+            let black = drawn(|f| icon.draw(f, Point::zero(), Color::Black));
+            let red = drawn(|f| icon.draw(f, Point::zero(), Color::Red));
+            let shape = inked(&black, 2 * Icon::SIZE, Color::Black);
+            assert!(!shape.is_empty(), "{icon:?} is blank");
+            assert!(
+                shape
+                    .iter()
+                    .all(|p| p.x < Icon::SIZE as i32 && p.y < Icon::SIZE as i32),
+                "{icon:?} spills out of its square"
+            );
+            assert_eq!(inked(&red, 2 * Icon::SIZE, Color::Red), shape, "{icon:?}");
+            assert!(inked(&red, 2 * Icon::SIZE, Color::Black).is_empty());
+        }
+    }
+
+    #[test]
+    fn every_icon_inverts_cleanly() {
+        for icon in Icon::ALL {
+            let plain = drawn(|f| icon.draw(f, Point::zero(), Color::Black));
+            let shape = inked(&plain, Icon::SIZE, Color::Black);
+            for background in [Color::Black, Color::Red] {
+                let opaque =
+                    drawn(|f| icon.draw_opaque(f, Point::zero(), Color::White, background));
+                assert_eq!(inked(&opaque, Icon::SIZE, Color::White), shape, "{icon:?}");
+                let filled = inked(&opaque, Icon::SIZE, background).len();
+                assert_eq!(filled + shape.len(), (Icon::SIZE * Icon::SIZE) as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn large_icons_are_doubled_exactly() {
+        for icon in Icon::ALL {
+            let small = drawn(|f| icon.draw(f, Point::zero(), Color::Black));
+            let large = drawn(|f| icon.draw_large(f, Point::zero(), Color::Black));
+            for y in 0..2 * Icon::SIZE as i32 {
+                for x in 0..2 * Icon::SIZE as i32 {
+                    assert_eq!(
+                        large.pixel(x, y),
+                        small.pixel(x / 2, y / 2),
+                        "{icon:?} at {x},{y}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn icons_parse_by_snake_case_name() {
+        for icon in Icon::ALL {
+            let mut name = String::new();
+            for c in format!("{icon:?}").chars() {
+                if c.is_uppercase() && !name.is_empty() {
+                    name.push('_');
+                }
+                name.push(c.to_ascii_lowercase());
+            }
+            assert_eq!(Icon::parse(&name), Some(icon), "{name}");
+        }
+        assert_eq!(Icon::parse("umbrella"), None);
+    }
+}
@@ -0,0 +1,341 @@
+//! `init`: a handful of questions on the terminal, and a config file with
+//! the answers, commented, to start from. Every question has a default in
+//! brackets, so pressing Enter all the way through gives a config that
+//! works, and the file is read back through [`Config::load`] before `init`
+//! says it's done.
+//!
+//! What it can find out by itself, it does: the system's timezone, and
+//! with the `rtc` feature whether a DS3231 answers on the I2C bus.
+
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use chrono_tz::Tz;
+
+use crate::config::Config;
+use crate::input::InputMode;
+use crate::persist;
+use crate::screens;
+
+/// What `init` found out.
+struct Answers {
+    name: String,
+    /// `None` for the system's.
+    timezone: Option<String>,
+    latitude: f64,
+    longitude: f64,
+    /// Mounted the other way up, so the picture has to be turned round.
+    upside_down: bool,
+    input: InputMode,
+    screens: Vec<String>,
+    partial_refresh: bool,
+    #[cfg(feature = "rtc")]
+    rtc: bool,
+}
+
+/// Asks its questions on `input` and `output` and writes the config to
+/// `path`. Returns whether to run the self-test with it.
+pub fn run(path: &Path, input: &mut impl BufRead, output: &mut impl Write) -> Result<bool> {
+    let mut prompt = Prompt { input, output };
+    if path.exists() && !prompt.yes_no(&format!("{} exists. Replace it?", path.display()), false)? {
+        bail!("kept {}", path.display());
+    }
+    let answers = ask(&mut prompt)?;
+    persist::write_atomic(path, render(&answers).as_bytes())?;
+    Config::load(Some(path))?;
+    writeln!(prompt.output, "Wrote {}.", path.display())?;
+    prompt.yes_no("Run the self-test now?", true)
+}
+
+fn ask(prompt: &mut Prompt<'_, impl BufRead, impl Write>) -> Result<Answers> {
+    let defaults = Config::default();
+    let name = prompt.ask("Name for a new pet", &defaults.pet.name, |name| {
+        Ok(name.to_string())
+    })?;
+
+    let system = system_timezone();
+    let timezone = prompt.ask(
+        "Timezone (\"system\" to follow the system)",
+        system.as_deref().unwrap_or("system"),
+        |zone| match zone {
+            "system" => Ok(None),
+            zone => zone
+                .parse::<Tz>()
+                .map(|_| Some(zone.to_string()))
+                .map_err(|_| format!("unknown timezone {zone:?}, e.g. Europe/Bucharest")),
+        },
+    )?;
+    let degrees = |limit: f64| {
+        move |text: &str| match text.parse::<f64>() {
+            Ok(value) if value.abs() <= limit => Ok(value),
+            _ => Err(format!("a number between -{limit} and {limit}")),
+        }
+    };
+    let latitude = prompt.ask(
+        "Latitude, for sunrise and sunset (north is positive)",
+        &defaults.latitude.to_string(),
+        degrees(90.0),
+    )?;
+    let longitude = prompt.ask(
+        "Longitude (east is positive)",
+        &defaults.longitude.to_string(),
+        degrees(180.0),
+    )?;
+
+    let upside_down = prompt.yes_no("Is the panel mounted upside down?", false)?;
+    let input = prompt.ask(
+        "Buttons from a keyboard, in the terminal or from /dev/input: auto, keyboard, evdev or none",
+        "auto",
+        |mode| {
+            let mode = InputMode::ALL
+                .into_iter()
+                .find(|m| m.name() == mode)
+                .ok_or("auto, keyboard, evdev or none")?;
+            match mode.missing_feature() {
+                Some(feature) => Err(format!("this build doesn't have the {feature} feature")),
+                None => Ok(mode),
+            }
+        },
+    )?;
+
+    let available = screens::available(&defaults);
+    let screens = prompt.ask(
+        &format!("Screens, in order ({})", available.join(", ")),
+        &defaults.screens.join(", "),
+        |list| {
+            let names: Vec<String> = list
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect();
+            if names.is_empty() {
+                return Err("at least one".into());
+            }
+            for name in &names {
+                screens::check_name(name, &available)?;
+            }
+            Ok(names)
+        },
+    )?;
+    let partial_refresh = prompt.yes_no(
+        "Refresh only what changed? Quicker, but ghosts on some panels",
+        defaults.partial_refresh,
+    )?;
+
+    #[cfg(feature = "rtc")]
+    let rtc = {
+        let bus = crate::config::RtcConfig::default().bus;
+        let found = crate::rtc::Ds3231::open(&bus).and_then(|mut rtc| rtc.read());
+        if let Err(err) = &found {
+            writeln!(prompt.output, "No DS3231 found: {err:#}")?;
+        }
+        prompt.yes_no("Keep time with the DS3231 RTC?", found.is_ok())?
+    };
+
+    Ok(Answers {
+        name,
+        timezone,
+        latitude,
+        longitude,
+        upside_down,
+        input,
+        screens,
+        partial_refresh,
+        #[cfg(feature = "rtc")]
+        rtc,
+    })
+}
+
+/// The system's timezone by name, from `/etc/timezone` or where
+/// `/etc/localtime` points.
+fn system_timezone() -> Option<String> {
+    let named = std::fs::read_to_string("/etc/timezone")
+        .ok()
+        .map(|text| text.trim().to_string())
+        .or_else(|| {
+            let target = std::fs::read_link("/etc/localtime").ok()?;
+            let target = target.to_str()?;
+            Some(target.split_once("zoneinfo/")?.1.to_string())
+        })?;
+    named.parse::<Tz>().is_ok().then_some(named)
+}
+
+/// The config file for `answers`, with a line on each setting.
+fn render(answers: &Answers) -> String {
+    let quote = |text: &str| toml::Value::String(text.to_string()).to_string();
+    let timezone = match &answers.timezone {
+        Some(zone) => format!("timezone = {}", quote(zone)),
+        None => "# timezone = \"Europe/Bucharest\"".to_string(),
+    };
+    let screens: Vec<String> = answers.screens.iter().map(|name| quote(name)).collect();
+    let input = answers.input.name();
+    #[cfg(feature = "rtc")]
+    let rtc = match answers.rtc {
+        true => "\n[rtc]\n# A DS3231 on I2C, for keeping time without a network.\nenabled = true\n",
+        false => "",
+    };
+    #[cfg(not(feature = "rtc"))]
+    let rtc = "";
+    format!(
+        "\
+# Written by `inky-gotchi init`. Everything is explained in the README;
+# whatever is left out takes its default.
+
+# Local time, for the clock and quiet hours. Without it, the system's.
+{timezone}
+# Where the panel is, for sunrise and sunset, in degrees.
+latitude = {}
+longitude = {}
+
+# Degrees the panel is turned, 180 for one mounted the other way up.
+rotation = {rotation}
+# Only sends what changed to the panel.
+partial_refresh = {}
+
+# Screens the carousel goes through, in order.
+screens = [{}]
+# Button presses from a keyboard: auto, keyboard, evdev or none.
+input = \"{input}\"
+
+[pet]
+# Given to a newly hatched pet. A pet already saved keeps its name.
+name = {}
+{rtc}",
+        answers.latitude,
+        answers.longitude,
+        answers.partial_refresh,
+        screens.join(", "),
+        quote(&answers.name),
+        rotation = if answers.upside_down { 180 } else { 0 },
+    )
+}
+
+/// Questions on a terminal, or anything else.
+struct Prompt<'a, R, W> {
+    input: &'a mut R,
+    output: &'a mut W,
+}
+
+impl<R: BufRead, W: Write> Prompt<'_, R, W> {
+    /// Asks `question` until `parse` takes the answer, or the default on
+    /// an empty one. The end of the input counts as empty.
+    fn ask<T>(
+        &mut self,
+        question: &str,
+        default: &str,
+        parse: impl Fn(&str) -> Result<T, String>,
+    ) -> Result<T> {
+        loop {
+            write!(self.output, "{question} [{default}]: ")?;
+            self.output.flush()?;
+            let mut line = String::new();
+            let read = self.input.read_line(&mut line)?;
+            if read == 0 {
+                writeln!(self.output)?;
+            }
+            let answer = match line.trim() {
+                "" => default,
+                answer => answer,
+            };
+            match parse(answer) {
+                Ok(value) => return Ok(value),
+                // Nothing more is coming, and the default doesn't work.
+                Err(err) if read == 0 => bail!("{question}: {err}"),
+                Err(err) => writeln!(self.output, "  {err}")?,
+            }
+        }
+    }
+
+    fn yes_no(&mut self, question: &str, default: bool) -> Result<bool> {
+        let hint = if default { "Y/n" } else { "y/N" };
+        self.ask(question, hint, |answer| {
+            match answer.to_ascii_lowercase().as_str() {
+                "y/n" => Ok(default),
+                "y" | "yes" => Ok(true),
+                "n" | "no" => Ok(false),
+                _ => Err("y or n".into()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `init` with `answers`, a line each, into a fresh directory and
+    /// reads the config back as the daemon would.
+    fn init(answers: &str) -> (Config, bool, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let mut output = Vec::new();
+        let self_test = run(&path, &mut answers.as_bytes(), &mut output).unwrap();
+        let config = Config::load(Some(&path)).unwrap();
+        (config, self_test, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn pressing_enter_throughout_gives_the_defaults() {
+        let (config, self_test, _) = init("");
+        let defaults = Config::default();
+        assert_eq!(config.timezone, system_timezone());
+        assert_eq!(config.pet.name, defaults.pet.name);
+        assert_eq!(
+            (config.latitude, config.longitude),
+            (defaults.latitude, defaults.longitude)
+        );
+        assert_eq!(config.rotation, 0);
+        assert_eq!(config.input, InputMode::Auto);
+        assert_eq!(config.screens, defaults.screens);
+        assert_eq!(config.partial_refresh, defaults.partial_refresh);
+        assert!(self_test);
+    }
+
+    #[test]
+    fn answers_end_up_in_the_config() {
+        let answers = "Mochi \"the\" cat\nEurope/Bucharest\n44.43\n26.1\ny\nnone\nclock, pet\nn\n";
+        let (config, _, _) = init(&format!("{answers}{}", "n\n".repeat(2)));
+        assert_eq!(config.pet.name, "Mochi \"the\" cat");
+        assert_eq!(config.timezone.as_deref(), Some("Europe/Bucharest"));
+        assert_eq!((config.latitude, config.longitude), (44.43, 26.1));
+        assert_eq!(config.rotation, 180);
+        assert_eq!(config.input, InputMode::None);
+        assert_eq!(config.screens, ["clock", "pet"]);
+        assert!(!config.partial_refresh);
+
+        let (config, _, _) = init("\nsystem\n");
+        assert_eq!(config.timezone, None);
+    }
+
+    #[test]
+    fn wrong_answers_are_asked_again() {
+        let (config, _, output) = init("\nMars/Olympus\nEurope/Bucharest\n91\n-45.5\n\nmaybe\n");
+        assert!(
+            output.contains("unknown timezone \"Mars/Olympus\""),
+            "{output}"
+        );
+        assert!(output.contains("a number between -90 and 90"), "{output}");
+        assert!(output.contains("y or n"), "{output}");
+        assert_eq!(config.timezone.as_deref(), Some("Europe/Bucharest"));
+        assert_eq!(config.latitude, -45.5);
+
+        let (_, _, output) = init(&format!("{}kennel, clock\n", "\n".repeat(6)));
+        assert!(output.contains("kennel"), "{output}");
+    }
+
+    #[test]
+    fn an_existing_config_is_kept_unless_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "latitude = 1.0\n").unwrap();
+        let err = run(&path, &mut "\n".as_bytes(), &mut Vec::new()).unwrap_err();
+        assert_eq!(err.to_string(), format!("kept {}", path.display()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "latitude = 1.0\n");
+
+        run(&path, &mut "y\n".as_bytes(), &mut Vec::new()).unwrap();
+        let config = Config::load(Some(&path)).unwrap();
+        assert_eq!(config.latitude, Config::default().latitude);
+    }
+}
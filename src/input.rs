@@ -0,0 +1,552 @@
+//! Where button presses come from, besides `press` and `hold` on the
+//! control socket. Each [`InputSource`] feeds the same press channel the
+//! socket does, so gestures, the settings menu and everything else behave
+//! the same whichever one is in use.
+//!
+//! Both sources are for trying things out without buttons, and each is a
+//! feature of its own:
+//!
+//! - `keyboard` reads the terminal the daemon runs in: `f`, `p` and `c`
+//!   press A, B and C (feed, play, clean), and the same keys with Shift
+//!   hold them down. A terminal doesn't report keys being released, hence
+//!   Shift for holding. The arrow keys turn the carousel, and Ctrl-C or
+//!   Ctrl-D stops the daemon.
+//! - `evdev` reads a keyboard's events from `/dev/input`, which do say when
+//!   a key comes up, so holding `f`, `p` or `c` for [`HOLD`] is a long
+//!   press, as it would be on a button. The arrow keys turn the carousel
+//!   here too. It needs read access to the device, usually by being in the
+//!   `input` group.
+
+use std::path::Path;
+#[cfg(feature = "evdev")]
+use std::path::PathBuf;
+#[cfg(any(feature = "keyboard", feature = "evdev"))]
+use std::thread;
+#[cfg(feature = "evdev")]
+use std::time::Duration;
+
+#[cfg(feature = "evdev")]
+use anyhow::Context as _;
+use anyhow::Result;
+use serde::Deserialize;
+
+#[cfg(any(feature = "keyboard", feature = "evdev"))]
+use crate::buttons::Button;
+use crate::buttons::Press;
+use crate::channel::Outbox;
+#[cfg(any(feature = "keyboard", feature = "evdev"))]
+use crate::events::Command;
+
+/// How long a key has to be held down on an evdev keyboard for a long
+/// press.
+#[cfg(feature = "evdev")]
+pub const HOLD: Duration = Duration::from_millis(600);
+/// Where keyboards turn up, for `input = "evdev"` without `input_device`.
+#[cfg(feature = "evdev")]
+const KEYBOARDS: &str = "/dev/input/by-path";
+
+/// Which input source to run, set as `input` in the config.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputMode {
+    /// The terminal when running in one, otherwise an evdev keyboard if
+    /// there is one; whichever of them is built in.
+    #[default]
+    Auto,
+    Keyboard,
+    Evdev,
+    /// Only the control socket.
+    None,
+}
+
+impl InputMode {
+    pub const ALL: [InputMode; 4] = [
+        InputMode::Auto,
+        InputMode::Keyboard,
+        InputMode::Evdev,
+        InputMode::None,
+    ];
+
+    /// As it's written in the config.
+    pub fn name(self) -> &'static str {
+        match self {
+            InputMode::Auto => "auto",
+            InputMode::Keyboard => "keyboard",
+            InputMode::Evdev => "evdev",
+            InputMode::None => "none",
+        }
+    }
+
+    /// The feature this mode needs, if it isn't built in.
+    pub fn missing_feature(self) -> Option<&'static str> {
+        match self {
+            InputMode::Keyboard if !cfg!(feature = "keyboard") => Some("keyboard"),
+            InputMode::Evdev if !cfg!(feature = "evdev") => Some("evdev"),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(any(feature = "keyboard", feature = "evdev"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    Press(Press),
+    /// Turn the carousel forwards, or back with `false`.
+    Turn(bool),
+    /// Asked for the daemon to stop.
+    #[cfg(feature = "keyboard")]
+    Quit,
+}
+
+#[cfg(any(feature = "keyboard", feature = "evdev"))]
+pub trait InputSource: Send + 'static {
+    fn name(&self) -> &'static str;
+    /// Blocks until the next input; `None` once the source has gone away.
+    fn next(&mut self) -> Option<Input>;
+}
+
+/// The far end of the press channel, from a thread or the tokio runtime.
+pub trait PressSink: Send + 'static {
+    /// Returns false once nobody's listening.
+    #[cfg_attr(not(any(feature = "keyboard", feature = "evdev")), allow(dead_code))]
+    fn press(&self, press: Press) -> bool;
+}
+
+impl PressSink for std::sync::mpsc::Sender<Press> {
+    fn press(&self, press: Press) -> bool {
+        self.send(press).is_ok()
+    }
+}
+
+#[cfg(feature = "async")]
+impl PressSink for tokio::sync::mpsc::UnboundedSender<Press> {
+    fn press(&self, press: Press) -> bool {
+        self.send(press).is_ok()
+    }
+}
+
+/// Key presses read from a terminal, byte by byte.
+#[cfg(feature = "keyboard")]
+pub struct Keyboard<R> {
+    input: R,
+}
+
+#[cfg(feature = "keyboard")]
+impl<R: std::io::Read + Send + 'static> Keyboard<R> {
+    pub fn new(input: R) -> Self {
+        Keyboard { input }
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        let mut byte = [0];
+        match self.input.read(&mut byte) {
+            Ok(1) => Some(byte[0]),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "keyboard")]
+impl<R: std::io::Read + Send + 'static> InputSource for Keyboard<R> {
+    fn name(&self) -> &'static str {
+        "keyboard"
+    }
+
+    fn next(&mut self) -> Option<Input> {
+        loop {
+            let byte = self.byte()?;
+            let button = match byte.to_ascii_lowercase() {
+                b'f' => Button::A,
+                b'p' => Button::B,
+                b'c' => Button::C,
+                // Ctrl-C, Ctrl-D.
+                0x03 | 0x04 => return Some(Input::Quit),
+                // Escape sequences are skipped whole, apart from the right
+                // and left arrows.
+                0x1b => {
+                    if self.byte()? != b'[' {
+                        continue;
+                    }
+                    let mut last = self.byte()?;
+                    while !(0x40..=0x7e).contains(&last) {
+                        last = self.byte()?;
+                    }
+                    match last {
+                        b'C' => return Some(Input::Turn(true)),
+                        b'D' => return Some(Input::Turn(false)),
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            };
+            return Some(Input::Press(Press {
+                button,
+                long: byte.is_ascii_uppercase(),
+            }));
+        }
+    }
+}
+
+/// Puts the terminal back the way it was when dropped.
+#[cfg(feature = "keyboard")]
+pub struct Terminal(libc::termios);
+
+#[cfg(feature = "keyboard")]
+impl Terminal {
+    /// Has stdin hand over each key as it's typed, without echoing it, and
+    /// Ctrl-C arrive as a key rather than a signal.
+    fn raw() -> std::io::Result<Terminal> {
+        // SAFETY: termios is plain data, and is only used once tcgetattr
+        // has filled it in.
+        let mut attrs: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut attrs) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let saved = Terminal(attrs);
+        attrs.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        attrs.c_cc[libc::VMIN] = 1;
+        attrs.c_cc[libc::VTIME] = 0;
+        // SAFETY: `attrs` is a valid termios from tcgetattr.
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &attrs) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(saved)
+    }
+}
+
+#[cfg(feature = "keyboard")]
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        // SAFETY: restores attributes tcgetattr gave us.
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0) };
+    }
+}
+
+/// Linux key codes, from `input-event-codes.h`.
+#[cfg(feature = "evdev")]
+mod key {
+    pub const EV_KEY: u16 = 0x01;
+    pub const F: u16 = 33;
+    pub const P: u16 = 25;
+    pub const C: u16 = 46;
+    pub const LEFT: u16 = 105;
+    pub const RIGHT: u16 = 106;
+    /// What an `EV_KEY` event's value means.
+    pub const UP: i32 = 0;
+    pub const DOWN: i32 = 1;
+    pub const REPEAT: i32 = 2;
+}
+
+/// Key presses read from an evdev device, with real holds: a key held for
+/// [`HOLD`] is a long press as soon as the keyboard repeats it past then,
+/// or when it comes up, whichever is first.
+#[cfg(feature = "evdev")]
+pub struct Evdev<R> {
+    events: R,
+    /// The button down, when it went down, and whether it's already been
+    /// sent as a long press.
+    down: Option<(Button, Duration, bool)>,
+}
+
+#[cfg(feature = "evdev")]
+impl<R: std::io::Read + Send + 'static> Evdev<R> {
+    pub fn new(events: R) -> Self {
+        Evdev { events, down: None }
+    }
+
+    /// The next key event's code, value and time.
+    fn event(&mut self) -> Option<(u16, i32, Duration)> {
+        let mut bytes = [0; std::mem::size_of::<libc::input_event>()];
+        loop {
+            self.events.read_exact(&mut bytes).ok()?;
+            // SAFETY: input_event is plain data, and the kernel hands them
+            // over whole; `bytes` is exactly one.
+            let event: libc::input_event =
+                unsafe { std::ptr::read_unaligned(bytes.as_ptr().cast()) };
+            if event.type_ == key::EV_KEY {
+                let at = Duration::new(event.time.tv_sec as u64, event.time.tv_usec as u32 * 1000);
+                return Some((event.code, event.value, at));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "evdev")]
+impl<R: std::io::Read + Send + 'static> InputSource for Evdev<R> {
+    fn name(&self) -> &'static str {
+        "evdev"
+    }
+
+    fn next(&mut self) -> Option<Input> {
+        loop {
+            let (code, value, at) = self.event()?;
+            let button = match code {
+                key::F => Button::A,
+                key::P => Button::B,
+                key::C => Button::C,
+                key::RIGHT if value == key::DOWN => return Some(Input::Turn(true)),
+                key::LEFT if value == key::DOWN => return Some(Input::Turn(false)),
+                _ => continue,
+            };
+            match (value, self.down) {
+                // A second key while one is down starts over from it.
+                (key::DOWN, _) => self.down = Some((button, at, false)),
+                (key::REPEAT, Some((held, since, false)))
+                    if held == button && at.saturating_sub(since) >= HOLD =>
+                {
+                    self.down = Some((held, since, true));
+                    return Some(Input::Press(Press { button, long: true }));
+                }
+                (key::UP, Some((held, since, sent))) if held == button => {
+                    self.down = None;
+                    if !sent {
+                        let long = at.saturating_sub(since) >= HOLD;
+                        return Some(Input::Press(Press { button, long }));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The evdev device to read: `configured`, or else the first keyboard
+/// under [`KEYBOARDS`].
+#[cfg(feature = "evdev")]
+pub(crate) fn evdev_device(configured: Option<&Path>) -> Option<PathBuf> {
+    if let Some(path) = configured {
+        return Some(path.to_path_buf());
+    }
+    let mut keyboards: Vec<PathBuf> = std::fs::read_dir(KEYBOARDS)
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.to_string_lossy().ends_with("-event-kbd"))
+        .collect();
+    keyboards.sort();
+    keyboards.into_iter().next()
+}
+
+/// Whatever has to stay alive while the source runs; dropping it puts
+/// the terminal back the way it was.
+pub struct Session {
+    #[cfg(feature = "keyboard")]
+    _terminal: Option<Terminal>,
+}
+
+/// Starts the source `mode` picks, if any, passing its presses to
+/// `presses` and anything else to `tx`. `device` is the configured evdev
+/// device, if any. Keep the returned session alive for as long as the
+/// daemon runs.
+pub fn spawn(
+    mode: InputMode,
+    device: Option<&Path>,
+    presses: impl PressSink,
+    tx: impl Outbox,
+) -> Result<Session> {
+    let session = Session {
+        #[cfg(feature = "keyboard")]
+        _terminal: None,
+    };
+    #[cfg(feature = "keyboard")]
+    if mode == InputMode::Keyboard
+        || mode == InputMode::Auto && std::io::IsTerminal::is_terminal(&std::io::stdin())
+    {
+        let terminal = match Terminal::raw() {
+            Ok(terminal) => Some(terminal),
+            // Still fine for piped input, a line at a time.
+            Err(err) => {
+                log::debug!("stdin isn't a terminal: {err}");
+                None
+            }
+        };
+        log::info!("keyboard: f/p/c press A/B/C, with Shift to hold, Ctrl-C quits");
+        run(Keyboard::new(std::io::stdin()), presses, tx)?;
+        return Ok(Session {
+            _terminal: terminal,
+        });
+    }
+    #[cfg(feature = "evdev")]
+    if matches!(mode, InputMode::Evdev | InputMode::Auto) {
+        let Some(path) = evdev_device(device) else {
+            anyhow::ensure!(
+                mode == InputMode::Auto,
+                "no keyboard found under {KEYBOARDS}"
+            );
+            return Ok(session);
+        };
+        match std::fs::File::open(&path) {
+            Ok(file) => {
+                log::info!(
+                    "evdev: f/p/c on {} press A/B/C, held to hold",
+                    path.display()
+                );
+                run(Evdev::new(file), presses, tx)?;
+            }
+            Err(err) if mode == InputMode::Auto => {
+                log::info!("not reading {}: {err}", path.display());
+            }
+            Err(err) => return Err(err).with_context(|| format!("opening {}", path.display())),
+        }
+        return Ok(session);
+    }
+    let _ = (mode, device, presses, tx);
+    Ok(session)
+}
+
+/// Runs `source` on a thread of its own.
+#[cfg(any(feature = "keyboard", feature = "evdev"))]
+pub fn run(mut source: impl InputSource, presses: impl PressSink, tx: impl Outbox) -> Result<()> {
+    thread::Builder::new()
+        .name(source.name().into())
+        .spawn(move || loop {
+            match source.next() {
+                Some(Input::Press(press)) => {
+                    if !presses.press(press) {
+                        return;
+                    }
+                }
+                Some(Input::Turn(forwards)) => {
+                    if !tx.send(Command::TurnScreen(forwards)) {
+                        return;
+                    }
+                }
+                #[cfg(feature = "keyboard")]
+                Some(Input::Quit) => {
+                    log::info!("asked to quit from the {}", source.name());
+                    tx.send(Command::Shutdown);
+                    return;
+                }
+                None => {
+                    log::info!("{} input closed", source.name());
+                    return;
+                }
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(feature = "keyboard", feature = "evdev"))]
+    fn inputs(mut source: impl InputSource) -> Vec<Input> {
+        std::iter::from_fn(|| source.next()).collect()
+    }
+
+    #[cfg(any(feature = "keyboard", feature = "evdev"))]
+    fn press(button: Button, long: bool) -> Input {
+        Input::Press(Press { button, long })
+    }
+
+    #[cfg(feature = "keyboard")]
+    #[test]
+    fn terminal_keys() {
+        let typed = b"fPc\x1b[C\x1b[D\x1b[1;5Ax\x1bOq\x03f".to_vec();
+        assert_eq!(
+            inputs(Keyboard::new(std::io::Cursor::new(typed))),
+            [
+                press(Button::A, false),
+                press(Button::B, true),
+                press(Button::C, false),
+                Input::Turn(true),
+                Input::Turn(false),
+                // Other sequences and keys are skipped.
+                Input::Quit,
+                press(Button::A, false),
+            ]
+        );
+    }
+
+    /// The bytes of an evdev key event `ms` milliseconds in.
+    #[cfg(feature = "evdev")]
+    fn event(code: u16, value: i32, ms: u64) -> Vec<u8> {
+        // SAFETY: input_event is plain data, all zeroes is valid.
+        let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+        event.time.tv_sec = (ms / 1000) as _;
+        event.time.tv_usec = (ms % 1000 * 1000) as _;
+        (event.type_, event.code, event.value) = (key::EV_KEY, code, value);
+        // SAFETY: reading the bytes of a plain struct.
+        unsafe {
+            std::slice::from_raw_parts(
+                (&event as *const libc::input_event).cast::<u8>(),
+                std::mem::size_of::<libc::input_event>(),
+            )
+        }
+        .to_vec()
+    }
+
+    #[cfg(feature = "evdev")]
+    fn evdev(events: &[(u16, i32, u64)]) -> Vec<Input> {
+        let bytes: Vec<u8> = events
+            .iter()
+            .flat_map(|&(code, value, ms)| event(code, value, ms))
+            .collect();
+        inputs(Evdev::new(std::io::Cursor::new(bytes)))
+    }
+
+    #[cfg(feature = "evdev")]
+    #[test]
+    fn evdev_holds_are_timed() {
+        use key::{C, DOWN, F, LEFT, P, REPEAT, RIGHT, UP};
+        let hold = HOLD.as_millis() as u64;
+        assert_eq!(
+            evdev(&[
+                // Tapped, and held to the release.
+                (F, DOWN, 0),
+                (F, UP, 100),
+                (P, DOWN, 1000),
+                (P, UP, 1000 + hold),
+                // Held with the keyboard repeating: long as soon as it's
+                // repeated past HOLD, and only once.
+                (C, DOWN, 3000),
+                (C, REPEAT, 3000 + hold / 2),
+                (C, REPEAT, 3000 + hold),
+                (C, REPEAT, 3000 + hold + 30),
+                (C, UP, 3000 + 2 * hold),
+                // Arrows, and keys that mean nothing.
+                (RIGHT, DOWN, 5000),
+                (RIGHT, UP, 5050),
+                (LEFT, DOWN, 5100),
+                (30, DOWN, 5200),
+                (30, UP, 6200),
+            ]),
+            [
+                press(Button::A, false),
+                press(Button::B, true),
+                press(Button::C, true),
+                Input::Turn(true),
+                Input::Turn(false),
+            ]
+        );
+    }
+
+    #[cfg(feature = "evdev")]
+    #[test]
+    fn evdev_only_counts_the_last_key_down() {
+        use key::{DOWN, F, P, UP};
+        // P goes down while F is held, so F's release is nothing.
+        assert_eq!(
+            evdev(&[(F, DOWN, 0), (P, DOWN, 50), (F, UP, 100), (P, UP, 150)]),
+            [press(Button::B, false)]
+        );
+    }
+
+    #[test]
+    fn modes_say_what_they_need() {
+        for mode in InputMode::ALL {
+            let parsed: InputMode =
+                toml::from_str::<toml::Value>(&format!("m = {:?}", mode.name())).unwrap()["m"]
+                    .clone()
+                    .try_into()
+                    .unwrap();
+            assert_eq!(parsed, mode);
+        }
+        assert_eq!(InputMode::Auto.missing_feature(), None);
+        assert_eq!(InputMode::None.missing_feature(), None);
+        assert_eq!(
+            InputMode::Evdev.missing_feature().is_some(),
+            !cfg!(feature = "evdev")
+        );
+    }
+}
@@ -0,0 +1,395 @@
+//! What the pet remembers: the last couple of weeks of things that happened
+//! to it, saved between runs, and the odd line it comes out with about
+//! them, like "You fed me 3 times today!".
+//!
+//! The journal keeps at most [`CAPACITY`] entries. Anything older than
+//! [`MAX_AGE`] is forgotten when it's loaded.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use rand::{Rng, RngExt};
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Clock;
+use crate::events::PetEvent;
+use crate::persist;
+
+pub const CAPACITY: usize = 200;
+pub const MAX_AGE: Duration = Duration::days(14);
+/// Hot is raised every cycle while it lasts, so it's only written down
+/// again once this long has passed.
+pub const HOT_SPELL: Duration = Duration::hours(6);
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    pub event: PetEvent,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Journal {
+    entries: VecDeque<Entry>,
+}
+
+impl Journal {
+    /// The journal saved at `path`, minus what's been forgotten by `now`.
+    /// An unreadable file starts over rather than stopping the daemon.
+    pub fn load(path: &Path, now: DateTime<Utc>) -> Journal {
+        let mut journal: Journal = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|err| {
+                log::warn!("ignoring unreadable {}: {err}", path.display());
+                Journal::default()
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Journal::default(),
+            Err(err) => {
+                log::warn!("couldn't read {}: {err}", path.display());
+                Journal::default()
+            }
+        };
+        journal.entries.retain(|entry| now - entry.at <= MAX_AGE);
+        journal.trim();
+        journal
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        persist::write_atomic(path, serde_json::to_string(self)?.as_bytes())
+    }
+
+    /// Writes `event` down, returning whether it was, i.e. the journal is
+    /// due to be saved.
+    pub fn record(&mut self, at: DateTime<Utc>, event: PetEvent) -> bool {
+        if event == PetEvent::Hot && self.last(PetEvent::Hot).is_some_and(|e| at - e < HOT_SPELL) {
+            return false;
+        }
+        self.entries.push_back(Entry { at, event });
+        self.trim();
+        true
+    }
+
+    fn trim(&mut self) {
+        while self.entries.len() > CAPACITY {
+            self.entries.pop_front();
+        }
+    }
+
+    /// When `event` last happened.
+    fn last(&self, event: PetEvent) -> Option<DateTime<Utc>> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.event == event)
+            .map(|entry| entry.at)
+    }
+
+    /// When the journal starts, i.e. how far back it can tell that
+    /// something didn't happen.
+    fn since(&self) -> Option<DateTime<Utc>> {
+        self.entries.front().map(|entry| entry.at)
+    }
+}
+
+/// Something the pet could say, and how much it's on its mind.
+struct Thought {
+    text: String,
+    weight: f64,
+}
+
+/// How much something `age` ago is on the pet's mind: full weight now,
+/// half a day later half as much, and so on.
+fn recency(age: Duration) -> f64 {
+    1.0 / (1.0 + age.num_minutes().max(0) as f64 / (12.0 * 60.0))
+}
+
+/// Everything the journal reminds the pet of at `now`.
+fn thoughts(journal: &Journal, clock: &Clock, now: DateTime<FixedOffset>) -> Vec<Thought> {
+    let utc = now.to_utc();
+    let mut thoughts = Vec::new();
+    let today = |at: DateTime<Utc>| clock.localize(at).date_naive() == now.date_naive();
+    let fed_today = journal
+        .entries
+        .iter()
+        .filter(|e| e.event == PetEvent::Fed && today(e.at))
+        .count();
+    if fed_today >= 2 {
+        thoughts.push(Thought {
+            text: format!("You fed me {fed_today} times today!"),
+            weight: 2.0,
+        });
+    }
+    // Only lines about things that didn't happen need the journal to go
+    // back far enough to know.
+    let known_for = journal
+        .since()
+        .map_or(Duration::zero(), |since| utc - since);
+    for (event, text) in [
+        (PetEvent::Played, "We haven't played in"),
+        (PetEvent::Petted, "Nobody's petted me in"),
+    ] {
+        let idle = journal.last(event).map_or(known_for, |at| utc - at);
+        let days = idle.num_days().min(known_for.num_days());
+        if days >= 2 {
+            thoughts.push(Thought {
+                text: format!("{text} {days} days"),
+                weight: 1.0 + days as f64 / 2.0,
+            });
+        }
+    }
+    if let Some(at) = journal.last(PetEvent::Cleaned) {
+        let age = utc - at;
+        if age < Duration::hours(3) {
+            thoughts.push(Thought {
+                text: "Thanks for cleaning up!".into(),
+                weight: 1.5 * recency(age),
+            });
+        }
+    }
+    let yesterday =
+        |at: DateTime<Utc>| now.date_naive().pred_opt() == Some(clock.localize(at).date_naive());
+    if let Some(at) = journal
+        .last(PetEvent::Disciplined)
+        .filter(|&at| yesterday(at))
+    {
+        thoughts.push(Thought {
+            text: "Sorry about yesterday...".into(),
+            weight: recency(utc - at),
+        });
+    }
+    if let Some(at) = journal.last(PetEvent::GoalReached) {
+        let age = utc - at;
+        if age < Duration::days(2) {
+            thoughts.push(Thought {
+                text: "We smashed that goal!".into(),
+                weight: 1.5 * recency(age),
+            });
+        }
+    }
+    let last_week = |e: &&Entry| (Duration::days(5)..Duration::days(9)).contains(&(utc - e.at));
+    let sick = journal
+        .entries
+        .iter()
+        .rev()
+        .filter(last_week)
+        .any(|e| e.event == PetEvent::Sick);
+    if sick {
+        thoughts.push(Thought {
+            text: "Last week I was sick".into(),
+            weight: 0.5,
+        });
+    }
+    thoughts
+}
+
+/// A line about the pet's recent past, picked at random with the weight
+/// of each, or `None` when nothing comes to mind. The same journal, time
+/// and RNG state always give the same line.
+pub fn recall(
+    journal: &Journal,
+    clock: &Clock,
+    now: DateTime<FixedOffset>,
+    rng: &mut impl Rng,
+) -> Option<String> {
+    let thoughts = thoughts(journal, clock, now);
+    let total: f64 = thoughts.iter().map(|t| t.weight).sum();
+    if thoughts.is_empty() || total <= 0.0 {
+        return None;
+    }
+    let mut pick = rng.random_range(0.0..total);
+    for thought in &thoughts {
+        if pick < thought.weight {
+            return Some(thought.text.clone());
+        }
+        pick -= thought.weight;
+    }
+    thoughts.last().map(|t| t.text.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Zone;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// A frozen Bucharest clock, with the time it's at.
+    fn at(when: &str) -> (Clock, DateTime<FixedOffset>) {
+        let utc: DateTime<Utc> = DateTime::parse_from_rfc3339(when).unwrap().to_utc();
+        let clock = Clock::frozen(Zone::Named(chrono_tz::Europe::Bucharest), utc);
+        let now = clock.now();
+        (clock, now)
+    }
+
+    /// How many hours ago, and what.
+    type Happened = (i64, PetEvent);
+
+    /// A journal of `events`, each so many hours before `now`.
+    fn journal(now: DateTime<FixedOffset>, events: &[Happened]) -> Journal {
+        let mut journal = Journal::default();
+        let mut events = events.to_vec();
+        events.sort_by_key(|&(hours, _)| -hours);
+        for (hours, event) in events {
+            journal.record(now.to_utc() - Duration::hours(hours), event);
+        }
+        journal
+    }
+
+    fn texts(journal: &Journal, clock: &Clock, now: DateTime<FixedOffset>) -> Vec<String> {
+        thoughts(journal, clock, now)
+            .into_iter()
+            .map(|t| t.text)
+            .collect()
+    }
+
+    #[test]
+    fn each_line_comes_from_its_own_events() {
+        let (clock, now) = at("2024-05-10T18:00:00+03:00");
+        let cases: [(&[Happened], &[&str]); 6] = [
+            (&[], &[]),
+            (
+                &[(1, PetEvent::Fed), (5, PetEvent::Fed), (9, PetEvent::Fed)],
+                &["You fed me 3 times today!"],
+            ),
+            (
+                &[(80, PetEvent::Fed), (1, PetEvent::Petted)],
+                &["We haven't played in 3 days"],
+            ),
+            (
+                &[
+                    (1, PetEvent::Cleaned),
+                    (2, PetEvent::Played),
+                    (3, PetEvent::Petted),
+                ],
+                &["Thanks for cleaning up!"],
+            ),
+            (
+                &[
+                    (20, PetEvent::Disciplined),
+                    (1, PetEvent::Played),
+                    (1, PetEvent::Petted),
+                ],
+                &["Sorry about yesterday..."],
+            ),
+            (
+                &[
+                    (24 * 6, PetEvent::Sick),
+                    (1, PetEvent::Played),
+                    (1, PetEvent::Petted),
+                ],
+                &["Last week I was sick"],
+            ),
+        ];
+        for (events, expected) in cases {
+            assert_eq!(
+                texts(&journal(now, events), &clock, now),
+                expected,
+                "{events:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn being_sick_is_only_remembered_about_a_week_on() {
+        let (clock, now) = at("2024-05-10T18:00:00+03:00");
+        for (days, remembered) in [(2, false), (5, true), (8, true), (10, false)] {
+            let journal = journal(
+                now,
+                &[
+                    (days * 24, PetEvent::Sick),
+                    (1, PetEvent::Played),
+                    (1, PetEvent::Petted),
+                ],
+            );
+            let lines = texts(&journal, &clock, now);
+            assert_eq!(
+                lines.iter().any(|line| line == "Last week I was sick"),
+                remembered,
+                "{days} days on: {lines:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn the_same_seed_says_the_same_things() {
+        let (clock, now) = at("2024-05-10T18:00:00+03:00");
+        let journal = journal(
+            now,
+            &[
+                (1, PetEvent::Fed),
+                (2, PetEvent::Fed),
+                (1, PetEvent::Cleaned),
+                (24 * 6, PetEvent::Sick),
+                (24 * 7, PetEvent::Played),
+            ],
+        );
+        let said = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..20)
+                .map(|_| recall(&journal, &clock, now, &mut rng).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(said(7), said(7));
+        // Over enough tries every line comes up, the fresh ones most.
+        let lines = said(7).into_iter().chain(said(8)).chain(said(9));
+        let mut counts = std::collections::BTreeMap::<String, usize>::new();
+        for line in lines {
+            *counts.entry(line).or_default() += 1;
+        }
+        assert_eq!(counts.len(), 5, "{counts:?}");
+        assert!(counts["You fed me 2 times today!"] > counts["Last week I was sick"]);
+    }
+
+    #[test]
+    fn nothing_to_say_about_an_empty_journal() {
+        let (clock, now) = at("2024-05-10T18:00:00+03:00");
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(recall(&Journal::default(), &clock, now, &mut rng), None);
+    }
+
+    #[test]
+    fn hot_spells_are_written_down_once() {
+        let (_, now) = at("2024-05-10T18:00:00+03:00");
+        let now = now.to_utc();
+        let mut journal = Journal::default();
+        assert!(journal.record(now, PetEvent::Hot));
+        assert!(!journal.record(now + Duration::hours(5), PetEvent::Hot));
+        assert!(journal.record(now + HOT_SPELL, PetEvent::Hot));
+        assert!(journal.record(now, PetEvent::Fed));
+        assert!(journal.record(now, PetEvent::Fed));
+    }
+
+    #[test]
+    fn loading_forgets_the_old_and_keeps_the_last_few_hundred() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.json");
+        let (_, now) = at("2024-05-10T18:00:00+03:00");
+        let now = now.to_utc();
+        let mut journal = Journal::default();
+        for minutes in (0..CAPACITY as i64 + 50).rev() {
+            journal.record(now - Duration::minutes(minutes), PetEvent::Fed);
+        }
+        assert_eq!(journal.entries.len(), CAPACITY);
+        // Saved before it was forgotten, say by an older build.
+        journal.entries.push_front(Entry {
+            at: now - MAX_AGE - Duration::hours(1),
+            event: PetEvent::Sick,
+        });
+        journal.save(&path).unwrap();
+
+        let loaded = Journal::load(&path, now);
+        assert_eq!(loaded.entries.len(), CAPACITY);
+        assert!(loaded.entries.iter().all(|e| e.event == PetEvent::Fed));
+        assert_eq!(
+            loaded.since(),
+            Some(now - Duration::minutes(CAPACITY as i64 - 1))
+        );
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(Journal::load(&path, now).entries.is_empty());
+        assert!(Journal::load(&dir.path().join("missing.json"), now)
+            .entries
+            .is_empty());
+    }
+}
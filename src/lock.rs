@@ -0,0 +1,188 @@
+//! A lock file so that only one instance drives the panel at a time.
+//!
+//! The lock is an advisory `flock` on a file holding the owner's PID. The
+//! kernel drops it when the owner exits, however that happens, so a stale
+//! file never blocks anyone.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context as _, Result};
+
+/// Exit status when another instance holds the lock.
+pub const EXIT_LOCKED: i32 = 3;
+
+/// How long `--takeover` waits for the old instance to let go.
+const TAKEOVER_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL: Duration = Duration::from_millis(100);
+
+/// Held for as long as this is alive.
+pub struct Lock {
+    _file: File,
+}
+
+pub enum Acquired {
+    Locked(Lock),
+    /// Someone else has it; their PID, if they wrote one.
+    Busy(Option<i32>),
+    /// The file couldn't be opened at all, e.g. `/run` isn't writable for
+    /// a development run.
+    Unavailable,
+}
+
+/// Tries once, without waiting.
+pub fn acquire(path: &Path) -> Result<Acquired> {
+    let Some(mut file) = open(path) else {
+        return Ok(Acquired::Unavailable);
+    };
+    // SAFETY: the descriptor belongs to `file`, which outlives the call.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() != ErrorKind::WouldBlock {
+            return Err(err).with_context(|| format!("locking {}", path.display()));
+        }
+        return Ok(Acquired::Busy(read_pid(&mut file)));
+    }
+    // Opened read-only when the file isn't ours to write; the lock still
+    // works, only the PID goes unrecorded.
+    let pid = std::process::id().to_string();
+    if file.set_len(0).is_ok() {
+        file.write_all(pid.as_bytes()).ok();
+    }
+    Ok(Acquired::Locked(Lock { _file: file }))
+}
+
+/// Asks the holder to stop with SIGTERM, then waits for its lock.
+pub fn take_over(path: &Path, holder: Option<i32>) -> Result<Lock> {
+    let Some(pid) = holder else {
+        bail!("{} is locked but holds no PID to signal", path.display());
+    };
+    // 0 and -1 would signal a whole group of processes, so only ever
+    // another single process gets signalled.
+    if !is_other_process(pid) {
+        bail!(
+            "{} names PID {pid}, which isn't another process",
+            path.display()
+        );
+    }
+    log::info!("asking PID {pid} to stop");
+    // SAFETY: plain syscall, no memory involved.
+    if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+        let err = std::io::Error::last_os_error();
+        return Err(err).with_context(|| format!("signalling PID {pid}"));
+    }
+    let started = Instant::now();
+    while started.elapsed() < TAKEOVER_TIMEOUT {
+        match acquire(path)? {
+            Acquired::Locked(lock) => return Ok(lock),
+            Acquired::Busy(_) => thread::sleep(POLL),
+            Acquired::Unavailable => bail!("lost access to {}", path.display()),
+        }
+    }
+    bail!(
+        "PID {pid} still holds {} after {}s",
+        path.display(),
+        TAKEOVER_TIMEOUT.as_secs()
+    )
+}
+
+fn open(path: &Path) -> Option<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).ok();
+    }
+    let writable = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path);
+    match writable.or_else(|_| File::open(path)) {
+        Ok(file) => Some(file),
+        Err(err) => {
+            log::warn!(
+                "couldn't open lock file {}: {err}; not guarding against a second instance",
+                path.display()
+            );
+            None
+        }
+    }
+}
+
+fn read_pid(file: &mut File) -> Option<i32> {
+    let mut text = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut text).ok()?;
+    text.trim()
+        .parse()
+        .ok()
+        .filter(|&pid| is_other_process(pid))
+}
+
+fn is_other_process(pid: i32) -> bool {
+    pid > 0 && pid as u32 != std::process::id()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn busy(path: &Path) -> Option<i32> {
+        match acquire(path).unwrap() {
+            Acquired::Busy(holder) => holder,
+            _ => panic!("{} should be busy", path.display()),
+        }
+    }
+
+    #[test]
+    fn the_lock_is_held_until_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run/inky-gotchi.lock");
+        let Acquired::Locked(lock) = acquire(&path).unwrap() else {
+            panic!("nobody holds it yet");
+        };
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+        // Our own PID is no use to anyone trying to take over.
+        assert_eq!(busy(&path), None);
+        drop(lock);
+        assert!(matches!(acquire(&path).unwrap(), Acquired::Locked(_)));
+    }
+
+    #[test]
+    fn the_holders_pid_is_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("inky-gotchi.lock");
+        let Acquired::Locked(lock) = acquire(&path).unwrap() else {
+            panic!("nobody holds it yet");
+        };
+        for (written, read) in [
+            ("4242\n", Some(4242)),
+            ("0", None),
+            ("-1", None),
+            ("-4242", None),
+            (&std::process::id().to_string(), None),
+            ("nobody", None),
+            ("", None),
+        ] {
+            fs::write(&path, written).unwrap();
+            assert_eq!(busy(&path), read, "{written:?}");
+        }
+        drop(lock);
+    }
+
+    #[test]
+    fn taking_over_refuses_to_signal_groups_or_itself() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("inky-gotchi.lock");
+        let _lock = acquire(&path).unwrap();
+        for pid in [None, Some(0), Some(-1), Some(std::process::id() as i32)] {
+            assert!(take_over(&path, pid).is_err(), "{pid:?}");
+        }
+    }
+}
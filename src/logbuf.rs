@@ -0,0 +1,135 @@
+//! The last few hundred log lines, kept in memory so they can be read off
+//! the panel or over HTTP when there's no shell on the device.
+//!
+//! Everything still goes to env_logger as before; [`init`] puts a logger in
+//! front of it that also copies each line that gets through the filter into
+//! a ring buffer. Lines are formatted and cut down to [`MAX_LINE`] before
+//! the lock is taken, so logging only ever holds it for a push. The lock is
+//! parking_lot's, which takes a single atomic operation when nobody else
+//! holds it, and can't be poisoned by a panic elsewhere.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// Lines kept; the oldest go first.
+pub const CAPACITY: usize = 200;
+/// Characters kept of each message.
+pub const MAX_LINE: usize = 160;
+
+static BUFFER: Mutex<VecDeque<Line>> = Mutex::new(VecDeque::new());
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Line {
+    pub at: DateTime<Utc>,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl Line {
+    /// A line logged now, with `message` cut down to [`MAX_LINE`].
+    fn new(level: Level, target: &str, mut message: String) -> Line {
+        if let Some((cut, _)) = message.char_indices().nth(MAX_LINE) {
+            message.truncate(cut);
+        }
+        Line {
+            at: Utc::now(),
+            level,
+            target: target.to_string(),
+            message,
+        }
+    }
+}
+
+/// Adds `line` to the buffer, pushing the oldest out once it's full.
+fn keep(line: Line) {
+    let mut buffer = BUFFER.lock();
+    if buffer.len() == CAPACITY {
+        buffer.pop_front();
+    }
+    buffer.push_back(line);
+}
+
+struct Tee(env_logger::Logger);
+
+impl Log for Tee {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.0.matches(record) {
+            return;
+        }
+        self.0.log(record);
+        keep(Line::new(
+            record.level(),
+            record.target(),
+            record.args().to_string(),
+        ));
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+/// Sets up logging as `env_logger` would with `RUST_LOG`, defaulting to
+/// `info`, and starts keeping lines.
+pub fn init() {
+    let logger =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).build();
+    let max = logger.filter();
+    if log::set_boxed_logger(Box::new(Tee(logger))).is_ok() {
+        log::set_max_level(max);
+    }
+}
+
+/// Up to `n` of the newest lines at `level` or more severe, newest first.
+pub fn recent(level: LevelFilter, n: usize) -> Vec<Line> {
+    BUFFER
+        .lock()
+        .iter()
+        .rev()
+        .filter(|line| line.level <= level)
+        .take(n)
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The buffer is shared by the whole process, so everything that
+    /// fills it is in this one test.
+    #[test]
+    fn keeps_the_newest_lines_cut_short() {
+        keep(Line::new(Level::Warn, "test", "ă".repeat(MAX_LINE + 10)));
+        let newest = recent(LevelFilter::Trace, 1);
+        assert_eq!(newest[0].message.chars().count(), MAX_LINE);
+
+        for i in 0..CAPACITY + 5 {
+            let level = if i % 2 == 0 {
+                Level::Info
+            } else {
+                Level::Error
+            };
+            keep(Line::new(level, "test", format!("line {i}")));
+        }
+        assert_eq!(BUFFER.lock().len(), CAPACITY);
+        let all = recent(LevelFilter::Trace, usize::MAX);
+        assert_eq!(all.len(), CAPACITY);
+        assert_eq!(all[0].message, format!("line {}", CAPACITY + 4));
+        assert_eq!(all[CAPACITY - 1].message, "line 5");
+
+        let errors = recent(LevelFilter::Warn, 3);
+        let messages: Vec<_> = errors.iter().map(|line| line.message.as_str()).collect();
+        let n = CAPACITY + 4;
+        assert_eq!(messages, [n - 1, n - 3, n - 5].map(|i| format!("line {i}")));
+    }
+}
@@ -0,0 +1,136 @@
+//! Backing off when memory runs short. On a 512 MB Pi Zero shared with
+//! other services, the extras that make the picture nicer can push the
+//! daemon into swap and a refresh out to half a minute, so available
+//! memory is checked before the costly steps and they're skipped while
+//! it's low:
+//!
+//! - reduced: large text isn't supersampled and photos are read in bands
+//!   rather than whole;
+//! - minimal: on top of that, big changes aren't pre-cleared, which saves
+//!   a second frame and its refresh.
+//!
+//! The limits are `[low_memory] reduced_mb` and `minimal_mb`.
+
+use serde::Serialize;
+
+use crate::config::LowMemoryConfig;
+use crate::system;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quality {
+    #[default]
+    Full,
+    Reduced,
+    Minimal,
+}
+
+impl Quality {
+    /// What `available` kilobytes of memory leave room for. Without a
+    /// reading there's nothing to go on, so nothing is cut.
+    pub fn for_memory(available: Option<u64>, config: &LowMemoryConfig) -> Quality {
+        match available.map(|kb| kb / 1024) {
+            Some(mb) if mb < config.minimal_mb => Quality::Minimal,
+            Some(mb) if mb < config.reduced_mb => Quality::Reduced,
+            _ => Quality::Full,
+        }
+    }
+
+    /// What the memory available right now leaves room for.
+    pub fn measure(config: &LowMemoryConfig) -> Quality {
+        Quality::for_memory(system::available_memory(), config)
+    }
+
+    pub fn supersample(self) -> bool {
+        self == Quality::Full
+    }
+
+    pub fn banded(self) -> bool {
+        self != Quality::Full
+    }
+
+    pub fn pre_clear(self) -> bool {
+        self != Quality::Minimal
+    }
+
+    /// What's skipped at this level, for the log.
+    pub fn cutbacks(self) -> Vec<&'static str> {
+        let mut cutbacks = Vec::new();
+        if !self.supersample() {
+            cutbacks.push("no supersampling");
+        }
+        if self.banded() {
+            cutbacks.push("photos in bands");
+        }
+        if !self.pre_clear() {
+            cutbacks.push("no pre-clearing");
+        }
+        cutbacks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quality(mb: u64) -> Quality {
+        Quality::for_memory(Some(mb * 1024), &LowMemoryConfig::default())
+    }
+
+    #[test]
+    fn levels_follow_the_limits() {
+        // The defaults are 64 and 32 MB, and each limit is the first
+        // amount that's enough.
+        assert_eq!(quality(512), Quality::Full);
+        assert_eq!(quality(64), Quality::Full);
+        assert_eq!(quality(63), Quality::Reduced);
+        assert_eq!(quality(32), Quality::Reduced);
+        assert_eq!(quality(31), Quality::Minimal);
+        assert_eq!(quality(0), Quality::Minimal);
+        // Kilobytes short of a whole MB round down.
+        let config = LowMemoryConfig::default();
+        assert_eq!(
+            Quality::for_memory(Some(64 * 1024 - 1), &config),
+            Quality::Reduced
+        );
+    }
+
+    #[test]
+    fn no_reading_cuts_nothing() {
+        let config = LowMemoryConfig {
+            reduced_mb: u64::MAX,
+            minimal_mb: u64::MAX,
+        };
+        assert_eq!(Quality::for_memory(None, &config), Quality::Full);
+        assert_eq!(Quality::for_memory(Some(0), &config), Quality::Minimal);
+    }
+
+    #[test]
+    fn zero_limits_never_cut_back() {
+        let config = LowMemoryConfig {
+            reduced_mb: 0,
+            minimal_mb: 0,
+        };
+        assert_eq!(Quality::for_memory(Some(0), &config), Quality::Full);
+    }
+
+    #[test]
+    fn each_level_skips_more() {
+        assert!(Quality::Full.cutbacks().is_empty());
+        assert_eq!(
+            Quality::Reduced.cutbacks(),
+            ["no supersampling", "photos in bands"]
+        );
+        assert_eq!(
+            Quality::Minimal.cutbacks(),
+            ["no supersampling", "photos in bands", "no pre-clearing"]
+        );
+    }
+
+    #[test]
+    fn the_status_file_names_the_level() {
+        let names = [Quality::Full, Quality::Reduced, Quality::Minimal]
+            .map(|quality| serde_json::to_value(quality).unwrap());
+        assert_eq!(names, ["full", "reduced", "minimal"]);
+    }
+}
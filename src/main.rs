@@ -1,3 +1,1634 @@
-fn main() {
-    println!("Hello, world!");
+use std::path::{Path, PathBuf};
+#[cfg(not(feature = "async"))]
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context as _, Result};
+use chrono::{DateTime, NaiveTime, Utc};
+use clap::{Parser, Subcommand};
+
+mod artwork;
+mod astro;
+mod build_info;
+mod buttons;
+mod camera;
+mod card;
+mod channel;
+mod chat;
+mod clock;
+mod collector;
+mod compare;
+mod config;
+mod control;
+mod display;
+mod events;
+#[cfg(feature = "github")]
+mod github;
+mod guest;
+mod hatch;
+mod health;
+mod hooks;
+mod icons;
+mod init;
+mod input;
+mod journal;
+mod lock;
+mod logbuf;
+mod lowmem;
+mod message;
+mod overlay;
+mod paths;
+mod persist;
+mod pet;
+mod pin;
+mod profile;
+mod push;
+mod qr;
+mod queue;
+mod quiet;
+mod reactions;
+mod record;
+mod reminder;
+mod rle;
+#[cfg(feature = "rtc")]
+mod rtc;
+mod runtime;
+mod scheduler;
+mod screens;
+mod selftest;
+mod server;
+mod settings;
+mod shade;
+#[cfg(not(feature = "async"))]
+mod signals;
+mod simulate;
+mod sprite;
+mod status;
+mod subprocess;
+mod summary;
+mod system;
+#[cfg(feature = "async")]
+mod tasks;
+mod template;
+mod text;
+#[cfg(feature = "ticker")]
+mod ticker;
+mod transfer;
+mod transition;
+#[cfg(feature = "update-check")]
+mod update;
+mod watchdog;
+#[cfg(feature = "weather")]
+mod weather;
+#[cfg(feature = "webhook")]
+mod webhook;
+
+use build_info::BuildInfo;
+use buttons::{Button, ButtonEvent};
+use channel::{Inbox, Outbox};
+use clock::{Clock, Zone};
+use config::Config;
+use display::{Color, Frame, PanelInfo, Simulator, HEIGHT, WIDTH};
+use events::{Command, PetEvent};
+use guest::{Guest, Privacy};
+use hatch::Picker;
+use journal::Journal;
+use message::Message;
+use overlay::{Compositor, DebugInfo, Scene};
+use pet::{Egg, Pet};
+use pin::Pin;
+use profile::{Stage, Trace};
+use reminder::Reminders;
+use runtime::Runtime;
+use scheduler::Cadence;
+use screens::hatch::HatchScreen;
+use screens::message::MessageScreen;
+use screens::reminder::ReminderScreen;
+use screens::selfie::SelfieScreen;
+use screens::settings::SettingsScreen;
+use screens::sleep::SleepScreen;
+use screens::summary::SummaryScreen;
+use screens::transfer::{ExportScreen, OfferScreen};
+use screens::{Carousel, Context, Screen};
+use settings::{Menu, Outcome, Overrides, Settings};
+use status::{CadenceStatus, Status};
+use summary::Tally;
+
+#[derive(Parser)]
+#[command(
+    about = "Tamagotchi-style pet for the Inky pHAT",
+    disable_version_flag = true
+)]
+struct Args {
+    /// Print the version and exit; add --verbose for build details.
+    #[arg(short = 'V', long)]
+    version: bool,
+    /// Show more detail, e.g. with --version.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Print every event a hook can be set for, with the variables it
+    /// gets, and exit.
+    #[arg(long)]
+    list_events: bool,
+    /// Path to the TOML config file.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// Where state files go, instead of $XDG_DATA_HOME/inky-gotchi or
+    /// ~/.local/share/inky-gotchi.
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+    /// Render a single frame and exit.
+    #[arg(long)]
+    once: bool,
+    /// Run this many cycles back to back, refreshing the panel on every
+    /// one, then print how long each stage took and exit.
+    #[arg(long, value_name = "N", conflicts_with = "once")]
+    profile_cycles: Option<usize>,
+    /// Seed for the random number generator, for reproducible runs.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Pretend it's always this moment (RFC 3339), e.g. for screenshots.
+    #[arg(long)]
+    at: Option<DateTime<Utc>>,
+    /// Stop an instance that's already running and take over the display.
+    #[arg(long)]
+    takeover: bool,
+    /// If the system is going down, stop the daemon, put the powered-off
+    /// screen up and exit; for an `ExecStop=` drop-in. On a restart of
+    /// just the service it leaves the panel as it is.
+    #[arg(long, conflicts_with_all = ["once", "profile_cycles"])]
+    shutdown_screen: bool,
+    /// Flip the image left to right, on top of `mirror_x` in the config.
+    #[arg(long)]
+    mirror_x: bool,
+    /// Flip the image top to bottom, on top of `mirror_y` in the config.
+    #[arg(long)]
+    mirror_y: bool,
+    #[command(subcommand)]
+    command: Option<Cmd>,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Render the whole icon set to a PNG.
+    Icons {
+        #[arg(default_value = "icons.png")]
+        output: PathBuf,
+    },
+    /// Fast-forward the pet through a scenario of timed actions, printing
+    /// what happened and the stats hour by hour.
+    Simulate {
+        scenario: PathBuf,
+        /// Virtual days to run for.
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+        /// Write the hourly stats here instead of printing them.
+        #[arg(long)]
+        csv: Option<PathBuf>,
+    },
+    /// Step the pet through virtual time like simulate, writing what the
+    /// panel shows at every step as numbered PNGs plus a manifest.
+    Record {
+        /// Actions to inject, in the simulate format.
+        scenario: Option<PathBuf>,
+        /// Where the frames go.
+        #[arg(long, default_value = "frames")]
+        out: PathBuf,
+        /// Virtual time to record, e.g. `1d` or `6h`.
+        #[arg(long, default_value = "1d", value_parser = simulate::parse_duration)]
+        length: chrono::Duration,
+        /// Virtual time between frames.
+        #[arg(long, default_value = "10m", value_parser = simulate::parse_duration)]
+        step: chrono::Duration,
+    },
+    /// Turn a recording into an animated GIF.
+    #[cfg(feature = "render-gif")]
+    RenderGif {
+        #[arg(default_value = "frames")]
+        dir: PathBuf,
+        #[arg(long, default_value = "demo.gif")]
+        output: PathBuf,
+        /// Virtual seconds per second of animation.
+        #[arg(long, default_value_t = 3600.0)]
+        speedup: f64,
+    },
+    /// Print where every file the daemon uses is.
+    Paths,
+    /// Check an artwork pack and say what it would draw. Exits non-zero
+    /// with everything wrong with it.
+    PackValidate { dir: PathBuf },
+    /// Draw the pet, its stats and age as a PNG for sharing.
+    Card {
+        #[arg(default_value = "card.png")]
+        output: PathBuf,
+    },
+    /// Check the panel, storage and any peripherals, and report on each.
+    /// Exits non-zero if anything failed.
+    SelfTest {
+        /// Print the report as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Ask a few questions and write a config to start from, then offer
+    /// to run the self-test with it.
+    Init {
+        #[arg(default_value = "gotchi.toml")]
+        path: PathBuf,
+    },
+    /// Leave a note on the panel, e.g. `say "Buy milk" --icon cart`. It
+    /// stays up until --until or the next button press.
+    Say {
+        text: String,
+        /// An icon to show next to it, by name.
+        #[arg(long)]
+        icon: Option<String>,
+        /// Take it down at this time of day (HH:MM).
+        #[arg(long, value_parser = quiet::parse_time)]
+        until: Option<NaiveTime>,
+        /// Who it's from; defaults to $USER.
+        #[arg(long)]
+        from: Option<String>,
+    },
+    /// Move the pet to another device, by QR code or by typing its code in.
+    Transfer {
+        #[command(subcommand)]
+        action: TransferCmd,
+    },
+    /// Draw every screen in set states, to spot layout changes between
+    /// revisions.
+    CompareRenders {
+        #[command(subcommand)]
+        action: CompareCmd,
+    },
+}
+
+#[derive(Subcommand)]
+enum TransferCmd {
+    /// Print the pet's code and put it up on the panel as a QR code.
+    Export,
+    /// Install the pet from a code made by `transfer export`. With the
+    /// daemon running, it asks on the panel.
+    Import {
+        code: String,
+        /// Don't ask first.
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CompareCmd {
+    /// Draw each scene into a PNG, on a fixed clock and seed.
+    Render {
+        #[arg(default_value = "renders")]
+        dir: PathBuf,
+    },
+    /// Put two renders side by side with their differences and list the
+    /// pixels changed per scene.
+    Diff {
+        before: PathBuf,
+        after: PathBuf,
+        /// Where the comparisons and summary go.
+        #[arg(long, default_value = "compare")]
+        out: PathBuf,
+    },
+}
+
+fn main() -> Result<()> {
+    logbuf::init();
+    let args = Args::parse();
+    if args.version {
+        print_version(args.verbose);
+        return Ok(());
+    }
+    if args.list_events {
+        print!("{}", hooks::describe());
+        return Ok(());
+    }
+    if let Some(Cmd::Icons { output }) = &args.command {
+        display::write_png(&icons::sheet(), output)?;
+        log::info!("wrote icon sheet to {}", output.display());
+        return Ok(());
+    }
+    if let Some(Cmd::PackValidate { dir }) = &args.command {
+        let pack = artwork::Pack::load(dir)?;
+        println!(
+            "{}: {} of {} sprites drawn from the pack, the rest built in",
+            pack.name,
+            pack.drawn,
+            artwork::SpriteId::ALL.len()
+        );
+        if pack.grey > 0 {
+            println!("{} of them have grey in them, for previews", pack.grey);
+        }
+        return Ok(());
+    }
+    #[cfg(feature = "render-gif")]
+    if let Some(Cmd::RenderGif {
+        dir,
+        output,
+        speedup,
+    }) = &args.command
+    {
+        let frames = record::render_gif(dir, output, *speedup)?;
+        log::info!("wrote {frames} frames to {}", output.display());
+        return Ok(());
+    }
+    if let Some(Cmd::Init { path }) = &args.command {
+        if !init::run(path, &mut std::io::stdin().lock(), &mut std::io::stdout())? {
+            return Ok(());
+        }
+        let mut config = Config::load(Some(path))?;
+        paths::resolve(&mut config, &paths::data_dir(args.data_dir.as_deref())?)?;
+        let report = selftest::run(&config);
+        report.print();
+        std::process::exit(report.exit_code());
+    }
+    let mut config = Config::load(args.config.as_deref())?;
+    config.mirror_x |= args.mirror_x;
+    config.mirror_y |= args.mirror_y;
+    let data_dir = paths::data_dir(args.data_dir.as_deref())?;
+    paths::resolve(&mut config, &data_dir)?;
+    if let Some(dir) = &config.artwork {
+        let pack = artwork::Pack::load(dir)?;
+        log::info!(
+            "drawing the pet from {} in {}, {} sprites of its own",
+            pack.name,
+            dir.display(),
+            pack.drawn
+        );
+        artwork::install(pack);
+    }
+    if let Some(Cmd::Paths) = &args.command {
+        println!("{:<24}{}", "data_dir", data_dir.display());
+        for (key, path) in paths::list(&config) {
+            println!("{key:<24}{}", path.display());
+        }
+        return Ok(());
+    }
+    if let Some(Cmd::Card { output }) = &args.command {
+        let now = args.at.unwrap_or_else(Utc::now);
+        let supersample = lowmem::Quality::measure(&config.low_memory).supersample();
+        let png = card::png(
+            &config.save_file,
+            now,
+            config.supersample_text && supersample,
+            config.simulator.greyscale,
+        )?;
+        persist::write_atomic(output, &png)?;
+        log::info!("wrote the card to {}", output.display());
+        return Ok(());
+    }
+    let zone = Zone::from_config(config.timezone.as_deref());
+    if let Some(Cmd::SelfTest { json }) = &args.command {
+        let report = selftest::run(&config);
+        if *json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            report.print();
+        }
+        std::process::exit(report.exit_code());
+    }
+    if let Some(Cmd::Simulate {
+        scenario,
+        days,
+        csv,
+    }) = &args.command
+    {
+        return run_simulation(&args, &config, zone, scenario, *days, csv.as_deref());
+    }
+    if let Some(Cmd::Record {
+        scenario,
+        out,
+        length,
+        step,
+    }) = &args.command
+    {
+        let scenario = match scenario {
+            Some(path) => simulate::Scenario::load(path)?,
+            None => simulate::Scenario::default(),
+        };
+        let span = record::Span {
+            start: args.at.unwrap_or_else(simulate::default_start),
+            length: *length,
+            step: *step,
+        };
+        let frames = record::record(&config, zone, &scenario, &span, args.seed.unwrap_or(0), out)?;
+        log::info!("recorded {frames} frames to {}", out.display());
+        return Ok(());
+    }
+    if let Some(Cmd::CompareRenders { action }) = &args.command {
+        match action {
+            CompareCmd::Render { dir } => {
+                let count = compare::render(&config, zone, dir)?;
+                log::info!("rendered {count} scenes to {}", dir.display());
+            }
+            CompareCmd::Diff { before, after, out } => {
+                print!("{}", compare::diff(before, after, out)?);
+                log::info!("wrote comparisons to {}", out.display());
+            }
+        }
+        return Ok(());
+    }
+    if let Some(Cmd::Say {
+        text,
+        icon,
+        until,
+        from,
+    }) = &args.command
+    {
+        let clock = match args.at {
+            Some(at) => Clock::frozen(zone, at),
+            None => Clock::new(zone),
+        };
+        let message = Message {
+            text: text.clone(),
+            icon: icon.clone(),
+            author: from.clone().or_else(|| std::env::var("USER").ok()),
+            until: until.map(|time| clock.next_occurrence(clock.now(), time).to_utc()),
+        };
+        return say(&config, &clock, &message);
+    }
+    if let Some(Cmd::Transfer { action }) = &args.command {
+        let now = args.at.unwrap_or_else(Utc::now);
+        return match action {
+            TransferCmd::Export => export(&config, now),
+            TransferCmd::Import { code, yes } => import(&config, code, *yes, now),
+        };
+    }
+
+    if args.shutdown_screen {
+        return shutdown_screen(&args, &config, zone);
+    }
+
+    // Everything from here on drives the panel, so one instance at a time.
+    let _lock = match lock::acquire(&config.lock_file)? {
+        lock::Acquired::Locked(lock) => Some(lock),
+        lock::Acquired::Busy(holder) if args.takeover => {
+            Some(lock::take_over(&config.lock_file, holder)?)
+        }
+        lock::Acquired::Busy(holder) => {
+            let holder = holder.map_or("another process".into(), |pid| format!("PID {pid}"));
+            eprintln!(
+                "inky-gotchi is already running as {holder} (lock on {}); pass --takeover to replace it",
+                config.lock_file.display()
+            );
+            std::process::exit(lock::EXIT_LOCKED);
+        }
+        lock::Acquired::Unavailable => None,
+    };
+
+    // A single render or a profiling run doesn't need anyone at the
+    // keyboard.
+    let input_mode = match args.once || args.profile_cycles.is_some() {
+        true => input::InputMode::None,
+        false => config.input,
+    };
+    #[cfg(not(feature = "async"))]
+    {
+        let (tx, rx) = mpsc::channel();
+        let (press_tx, press_rx) = mpsc::channel();
+        signals::spawn(tx.clone())?;
+        buttons::spawn(press_rx, tx.clone())?;
+        control::spawn(
+            &config.control_socket,
+            tx.clone(),
+            press_tx.clone(),
+            screens::available(&config),
+        )?;
+        let _input = input::spawn(
+            input_mode,
+            config.input_device.as_deref(),
+            press_tx,
+            tx.clone(),
+        )?;
+        run(&args, &config, zone, tx, rx)
+    }
+    #[cfg(feature = "async")]
+    {
+        // One thread drives every task; rendering gets its own, so a slow
+        // panel refresh never holds up the socket.
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            let press_tx = tasks::spawn(
+                &config.control_socket,
+                tx.clone(),
+                screens::available(&config),
+            )?;
+            let _input = input::spawn(
+                input_mode,
+                config.input_device.as_deref(),
+                press_tx,
+                tx.clone(),
+            )?;
+            let inbox = channel::TaskInbox::new(rx, tokio::runtime::Handle::current());
+            tokio::task::spawn_blocking(move || run(&args, &config, zone, tx, inbox)).await?
+        })
+    }
+}
+
+/// The daemon proper: ticks the pet and keeps the display up to date,
+/// taking commands from `inbox`. `outbox` is for background sources that
+/// want to raise commands of their own.
+fn run(
+    args: &Args,
+    config: &Config,
+    zone: Zone,
+    outbox: impl Outbox,
+    mut inbox: impl Inbox,
+) -> Result<()> {
+    #[cfg(feature = "rtc")]
+    let ntp_synced = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let clock = match args.at {
+        Some(at) => Clock::frozen(zone, at),
+        #[cfg(feature = "rtc")]
+        None => rtc::startup_clock(&config.rtc, zone, ntp_synced.clone()),
+        #[cfg(not(feature = "rtc"))]
+        None => Clock::new(zone),
+    };
+
+    let mut pet = Pet::load_or_new(&config.save_file, &config.pet.name, clock.now_utc())?;
+    let seed = args.seed.unwrap_or_else(rand::random);
+    log::info!("rng seed {seed}");
+    let mut runtime = Runtime::new(seed, &mut pet);
+
+    // Shared with the watchdog, which reinitialises it if the loop gets
+    // stuck anywhere else.
+    let shared_display = Arc::new(Mutex::new(Simulator::new(
+        config.simulator.output.clone(),
+        config.partial_refresh,
+        config.mirror(),
+        config.pre_clear.threshold(),
+    )));
+    let panel = PanelInfo {
+        rotation: config.rotation,
+        ..shared_display.lock().unwrap().info()
+    };
+    let mut frame = Frame::new(WIDTH, HEIGHT);
+    let mut carousel = Carousel::from_config(config)?;
+    let mut pin = Pin::load(&config.pin_file);
+    if let Some(saved) = &pin {
+        match carousel.pin(&saved.screen, config) {
+            Ok(_) => log::info!("still pinned to {}", saved.screen),
+            Err(err) => {
+                log::warn!("can't pin {} again: {err:#}", saved.screen);
+                pin = None;
+                Pin::remove(&config.pin_file);
+            }
+        }
+    }
+    // Never saved: guests are over for the evening, not for good.
+    let mut guest: Option<Guest> = None;
+    let mut message = Message::load(&config.message_file);
+    let mut reminders = Reminders::load(config, clock.now_utc());
+    let mut selfie: Option<camera::Selfie> = None;
+    let mut export: Option<transfer::Showing> = None;
+    let mut offer: Option<transfer::Offer> = None;
+    let mut tally = Tally::load(&config.summary_file, clock.now().date_naive());
+    let mut journal = Journal::load(&config.journal_file, clock.now_utc());
+    #[cfg(feature = "webhook")]
+    let mut webhook = webhook::Webhook::new(&config.webhook);
+    let compositor = Compositor::new();
+    let mut hooks = hooks::Hooks::new(&config.hooks);
+
+    if let Some(addr) = config.push.listen {
+        let card = push::CardSource {
+            save_file: config.save_file.clone(),
+            supersample: config.supersample_text,
+            greyscale: config.simulator.greyscale,
+            low_memory: config.low_memory.clone(),
+        };
+        let chat = push::ChatSource {
+            save_file: config.save_file.clone(),
+            clock: clock.clone(),
+            text: config.text.clone(),
+        };
+        push::spawn(addr, outbox.clone(), card, chat, screens::available(config))?;
+    }
+    let mut sources: Vec<Box<dyn collector::Source>> = vec![
+        Box::new(collector::SystemSource),
+        Box::new(health::HealthSource::new(outbox.clone(), "/".into())),
+    ];
+    if config.server.logins {
+        sources.push(Box::new(server::LoginSource::new(outbox.clone())));
+    }
+    if config.server.upgrades {
+        sources.push(Box::new(server::UpgradeSource::default()));
+    }
+    #[cfg(feature = "update-check")]
+    if let Some(source) = update::UpdateSource::new(&config.update_check, clock.clone()) {
+        sources.push(Box::new(source));
+    }
+    #[cfg(feature = "rtc")]
+    if let Some(source) = rtc::RtcSource::new(&config.rtc, ntp_synced) {
+        sources.push(Box::new(source));
+    }
+    #[cfg(feature = "github")]
+    if let Some(source) = github::GithubSource::new(&config.github, outbox) {
+        sources.push(Box::new(source));
+    }
+    #[cfg(feature = "ticker")]
+    if let Some(source) = ticker::TickerSource::new(&config.ticker) {
+        sources.push(Box::new(source));
+    }
+    #[cfg(feature = "weather")]
+    sources.push(Box::new(weather::WeatherSource::new(config)));
+    let collector = collector::Collector::spawn(sources, carousel.demand())?;
+    let heartbeat = watchdog::Heartbeat::new();
+    if config.watchdog.enabled && !args.once {
+        let display = shared_display.clone();
+        watchdog::spawn(
+            heartbeat.clone(),
+            config.watchdog.missed_cycles,
+            move || match display.try_lock() {
+                Ok(mut display) => {
+                    display.reopen();
+                    true
+                }
+                Err(_) => false,
+            },
+        )?;
+    }
+
+    // What the pet was up to last cycle, to tell when it changes. Nothing
+    // counts as waking up or running low on the first one.
+    let mut asleep = None;
+    let mut critical = overlay::is_critical(&pet);
+    // Package upgrades waiting as of the last check, so only more of them
+    // are news.
+    let mut upgrades = 0;
+    let mut queue = queue::Queue::default();
+    let mut traces = Vec::new();
+    let mut refreshes = 0u64;
+    let mut pre_clears = 0u64;
+    let mut last_refresh: Option<Duration> = None;
+    let mut settings = Settings::new(config, &Overrides::load(&config.overrides_file)?);
+    let mut menu: Option<Menu> = None;
+    // A brand-new pet waits for its egg to be picked.
+    let mut picker = pet.egg.is_none().then(|| Picker::new(Instant::now()));
+    let mut cadence = Cadence::from_config(settings.refresh_interval(), &config.cadence);
+    let mut schedule = scheduler::State {
+        last_cycle: Instant::now(),
+        last_interaction: None,
+        unchanged_frames: 0,
+        parked_until: None,
+        shown: None,
+        pinned: None,
+    };
+    let mut planner = quiet::Planner::default();
+
+    loop {
+        let mut trace = Trace::start();
+        heartbeat.phase("reading the snapshot");
+        let snapshot = collector.snapshot();
+        trace.lap(Stage::Collect);
+        heartbeat.phase("ticking the pet");
+        let now = clock.now();
+        // Everything that came in since the last cycle, taken in one go so
+        // it all shows in this frame.
+        let queue_depth = queue.len();
+        let pending = queue.drain();
+        let quiet_hours = settings.quiet_hours(planner.get(config, &clock, now));
+        let quiet_now = quiet_hours.is_some_and(|quiet| quiet.contains(now.time()));
+        let bedtime = quiet::is_bedtime(quiet_hours, now.time());
+        let (was_dead, was_form, was_sick) = (pet.is_dead(), pet.form, pet.is_sick());
+        pet.tick(now.to_utc(), &clock, &config.pet, bedtime);
+        let mut events = Vec::new();
+        if pet.is_dead() && !was_dead {
+            events.push(PetEvent::Died);
+        }
+        if pet.form != was_form {
+            events.push(PetEvent::Evolved);
+        }
+        if pet.is_sick() && !was_sick && !pet.is_dead() {
+            events.push(PetEvent::Sick);
+        }
+        if asleep.replace(bedtime) == Some(true) && !bedtime && !pet.is_dead() {
+            events.push(PetEvent::WokeUp);
+        }
+        runtime.advance(&pet);
+        if !pet.is_dead() && tally.sample(now, pet.stats(), snapshot.cpu_temperature) {
+            if let Err(err) = tally.save(&config.summary_file) {
+                log::warn!("couldn't save the day's tally: {err:#}");
+            }
+        }
+        #[cfg(feature = "webhook")]
+        if let Some(webhook) = &mut webhook {
+            webhook.poll(now, &tally, &pet.name, snapshot.uptime);
+        }
+
+        // Set when something outside the screen changes what's on the panel.
+        let mut forced = false;
+        for queued in pending {
+            log::debug!(
+                "{:?}, waited {} ms",
+                queued.command,
+                queued.at.elapsed().as_millis()
+            );
+            let command = queued.command;
+            // Buttons pick the egg until there is one, work the settings
+            // menu while it's open, and the pet otherwise.
+            let command = match command {
+                Command::Button(event) if picker.is_some() => {
+                    let open = picker.as_mut().expect("picker is open");
+                    if let Some(egg) = open.handle(event) {
+                        log::info!("{} hatches from the {} egg", pet.name, egg.label());
+                        pet.egg = Some(egg);
+                        picker = None;
+                    }
+                    forced = true;
+                    continue;
+                }
+                Command::Button(event) if menu.is_some() => {
+                    let open = menu.as_mut().expect("menu is open");
+                    if let Outcome::Saved(saved) = open.handle(event, Instant::now()) {
+                        log::info!("saving settings: {saved:?}");
+                        settings = saved;
+                        cadence =
+                            Cadence::from_config(settings.refresh_interval(), &config.cadence);
+                        if let Err(err) = settings.overrides(config).save(&config.overrides_file) {
+                            log::warn!("couldn't save settings: {err:#}");
+                        }
+                        menu = None;
+                    }
+                    forced = true;
+                    continue;
+                }
+                // A pet from elsewhere comes in on a long press of A and
+                // is sent off by anything else.
+                Command::Button(event) if offer.is_some() => {
+                    let open = offer.take().expect("offer is open");
+                    if event == ButtonEvent::Long(Button::A) {
+                        log::info!("{} moves in, {} moves out", open.pet.name, pet.name);
+                        pet = open.pet.into_pet(now.to_utc());
+                        runtime = Runtime::new(seed, &mut pet);
+                        journal = Journal::default();
+                        if let Err(err) = journal.save(&config.journal_file) {
+                            log::warn!("couldn't save the journal: {err:#}");
+                        }
+                        pet.save(&config.save_file)?;
+                    } else {
+                        log::info!("turned {} away", open.pet.name);
+                    }
+                    forced = true;
+                    continue;
+                }
+                Command::Button(ButtonEvent::Long(Button::C)) => {
+                    log::info!("opening the settings menu");
+                    menu = Some(Menu::open(settings, config, Instant::now()));
+                    forced = true;
+                    continue;
+                }
+                Command::Button(ButtonEvent::Long(Button::B)) => Command::Selfie,
+                // Three taps of B turn the carousel, and of C pin the
+                // screen showing or let it go.
+                Command::Button(ButtonEvent::Tripletap(Button::B)) => Command::TurnScreen(true),
+                Command::Button(ButtonEvent::Tripletap(Button::C)) => match carousel.pinned() {
+                    Some(_) => Command::UnpinScreen,
+                    None => Command::PinScreen(carousel.current().name().to_string()),
+                },
+                Command::Button(ButtonEvent::Long(Button::A))
+                    if carousel.current().name() == "logs" =>
+                {
+                    runtime.log_page += 1;
+                    forced = true;
+                    continue;
+                }
+                Command::Button(ButtonEvent::Long(Button::A)) => Command::Guest(None),
+                Command::Button(event) => match event.action() {
+                    Some(action) => Command::Act(action),
+                    None => continue,
+                },
+                command => command,
+            };
+            match command {
+                // The first press while a code is up only takes it down.
+                Command::Act(action) if export.is_some() => {
+                    log::info!("{action:?} took the code down");
+                    export = None;
+                    forced = true;
+                }
+                // Likewise for a photo.
+                Command::Act(action) if selfie.is_some() => {
+                    log::info!("{action:?} dismissed the selfie");
+                    selfie = None;
+                    forced = true;
+                }
+                // Likewise for a reminder, which it acknowledges.
+                Command::Act(action) if reminders.up().is_some() => {
+                    log::info!("{action:?} acknowledged the reminder");
+                    reminders.acknowledge(now.to_utc(), false);
+                    forced = true;
+                }
+                // Likewise after a note is left.
+                Command::Act(action) if message.is_some() => {
+                    log::info!("{action:?} dismissed the message");
+                    message = None;
+                    Message::remove(&config.message_file);
+                    forced = true;
+                }
+                Command::Act(action) if pet.is_dead() => {
+                    log::info!("ignoring {action:?}, {} is gone", pet.name);
+                }
+                Command::Act(action) => {
+                    let reaction = pet.apply(action);
+                    events.push(runtime.react(&pet.name, reaction));
+                }
+                Command::System(event) => runtime.on_system_event(event),
+                Command::SetScreen(name, on) => match carousel.set_enabled(&name, on, config) {
+                    Ok(true) => {
+                        log::info!("screen {name} {}", if on { "on" } else { "off" });
+                        collector.set_demand(carousel.demand());
+                        // Switching the pinned screen off took the pin.
+                        if pin.is_some() && carousel.pinned().is_none() {
+                            pin = None;
+                            Pin::remove(&config.pin_file);
+                            forced = true;
+                        }
+                    }
+                    Ok(false) => {}
+                    Err(err) => log::warn!("can't switch screen {name}: {err:#}"),
+                },
+                Command::PinScreen(name) => match carousel.pin(&name, config) {
+                    Ok(added) => {
+                        log::info!("pinned {name}");
+                        if added {
+                            collector.set_demand(carousel.demand());
+                        }
+                        let minutes = chrono::Duration::minutes(config.pin_minutes as i64);
+                        let until = (config.pin_minutes > 0).then(|| now.to_utc() + minutes);
+                        let saved = Pin {
+                            screen: name,
+                            until,
+                        };
+                        if let Err(err) = saved.save(&config.pin_file) {
+                            log::warn!("couldn't save the pin: {err:#}");
+                        }
+                        pin = Some(saved);
+                        forced = true;
+                    }
+                    Err(err) => log::warn!("can't pin {name}: {err:#}"),
+                },
+                Command::UnpinScreen => {
+                    if let Some(name) = carousel.unpin() {
+                        log::info!("unpinned {name}");
+                        forced = true;
+                    }
+                    pin = None;
+                    Pin::remove(&config.pin_file);
+                }
+                Command::TurnScreen(forward) => {
+                    carousel.step(forward);
+                    log::info!("turned to {}", carousel.current().name());
+                    // The pin came along; it keeps its time.
+                    if let (Some(name), Some(saved)) = (carousel.pinned(), &mut pin) {
+                        saved.screen = name.to_string();
+                        if let Err(err) = saved.save(&config.pin_file) {
+                            log::warn!("couldn't save the pin: {err:#}");
+                        }
+                    }
+                    forced = true;
+                }
+                // Asking again while it's on starts the time over.
+                Command::Guest(on) => {
+                    if on.unwrap_or(guest.is_none()) {
+                        if guest.is_none() {
+                            log::info!("guest mode on");
+                        }
+                        guest = Some(Guest::start(now.to_utc(), config.guest_minutes));
+                        forced = true;
+                    } else if guest.take().is_some() {
+                        log::info!("guest mode off");
+                        forced = true;
+                    }
+                }
+                Command::Acknowledge => {
+                    if reminders.acknowledge(now.to_utc(), true) {
+                        forced = true;
+                    } else {
+                        log::info!("no reminders waiting to acknowledge");
+                    }
+                }
+                Command::Selfie => {
+                    log::info!("taking a selfie");
+                    heartbeat.phase("taking a selfie");
+                    let quality = lowmem::Quality::measure(&config.low_memory);
+                    let shot = camera::capture(&config.selfie, quality.banded());
+                    if let Ok(photo) = &shot {
+                        match camera::save(photo, &config.selfie.dir, now) {
+                            Ok(path) => log::info!("saved the selfie to {}", path.display()),
+                            Err(err) => log::warn!("couldn't save the selfie: {err:#}"),
+                        }
+                    }
+                    let shown = chrono::Duration::minutes(config.selfie.minutes as i64);
+                    selfie = Some(camera::Selfie {
+                        shot,
+                        until: now.to_utc() + shown,
+                    });
+                    forced = true;
+                }
+                Command::Export => match transfer::Showing::new(&pet, now.to_utc()) {
+                    Ok(showing) => {
+                        log::info!("showing {}'s code", pet.name);
+                        export = Some(showing);
+                        forced = true;
+                    }
+                    Err(err) => log::warn!("can't show a code: {err:#}"),
+                },
+                Command::Import(incoming) => {
+                    log::info!("{} is asking to move in", incoming.name);
+                    offer = Some(transfer::Offer {
+                        pet: incoming,
+                        until: now.to_utc() + transfer::OFFERED_FOR,
+                    });
+                    forced = true;
+                }
+                Command::Say(note) => {
+                    log::info!("showing a message: {:?}", note.text);
+                    if let Err(err) = note.save(&config.message_file) {
+                        log::warn!("couldn't save the message: {err:#}");
+                    }
+                    message = Some(note);
+                    forced = true;
+                }
+                Command::Talk(talk) if pet.is_dead() => {
+                    log::info!("ignoring {:?}, {} is gone", talk.intent, pet.name);
+                }
+                Command::Talk(talk) => {
+                    log::info!(
+                        "told something ({}), answering {:?}",
+                        talk.intent.label(),
+                        talk.reply
+                    );
+                    if let Some(action) = talk.intent.action() {
+                        let reaction = pet.apply(action);
+                        events.push(runtime.react(&pet.name, reaction));
+                    }
+                    runtime.hear(talk, Privacy::Public, now.to_utc());
+                    forced = true;
+                }
+                Command::Login(user) => {
+                    let reply = format!("hello {user}!");
+                    // Who's logged in is nobody else's business.
+                    runtime.hear(
+                        chat::Talk {
+                            intent: chat::Intent::Greeting,
+                            reply,
+                        },
+                        Privacy::Hidden,
+                        now.to_utc(),
+                    );
+                    runtime.visitor = Some(user);
+                    events.push(PetEvent::Visited);
+                    forced = true;
+                }
+                Command::Record(reading) => {
+                    events.extend(pet.record(reading, now.to_utc(), now.date_naive()));
+                }
+                // Acted on as soon as it arrives.
+                Command::Shutdown => {}
+                // Turned into actions above.
+                Command::Button(_) => {}
+                Command::Refresh => {
+                    log::info!("redrawing the panel");
+                    shared_display.lock().unwrap().invalidate();
+                    forced = true;
+                }
+                Command::ToggleDebug => {
+                    runtime.debug_overlay = !runtime.debug_overlay;
+                    // The overlay isn't part of the change detection, so
+                    // make sure toggling it shows up.
+                    shared_display.lock().unwrap().invalidate();
+                    forced = true;
+                }
+            }
+        }
+        if picker.as_ref().is_some_and(|p| p.timed_out(Instant::now())) {
+            log::info!(
+                "nobody picked an egg, {} hatches from the plain one",
+                pet.name
+            );
+            pet.egg = Some(Egg::Plain);
+            picker = None;
+            forced = true;
+        }
+        if menu.as_ref().is_some_and(|m| m.timed_out(Instant::now())) {
+            log::info!("settings menu timed out, discarding changes");
+            menu = None;
+            forced = true;
+        }
+        if export.as_ref().is_some_and(|e| now.to_utc() >= e.until) {
+            log::info!("code's time is up");
+            export = None;
+            forced = true;
+        }
+        if offer.as_ref().is_some_and(|o| now.to_utc() >= o.until) {
+            log::info!(
+                "nobody took {} in",
+                offer.as_ref().expect("offer is open").pet.name
+            );
+            offer = None;
+            forced = true;
+        }
+        if selfie.as_ref().is_some_and(|s| s.is_expired(now.to_utc())) {
+            log::info!("selfie's time is up");
+            selfie = None;
+            forced = true;
+        }
+        if pin.as_ref().is_some_and(|p| p.is_expired(now.to_utc())) {
+            if let Some(name) = carousel.unpin() {
+                log::info!("unpinned {name}, its time is up");
+            }
+            pin = None;
+            Pin::remove(&config.pin_file);
+            forced = true;
+        }
+        if guest.is_some_and(|g| g.is_expired(now.to_utc())) {
+            log::info!("guest mode's time is up");
+            guest = None;
+            forced = true;
+        }
+        if message.as_ref().is_some_and(|m| m.is_expired(now.to_utc())) {
+            log::info!("message expired");
+            message = None;
+            Message::remove(&config.message_file);
+        }
+        if reminders.tick(&clock, now) {
+            forced = true;
+        }
+        if !quiet_now && runtime.misbehavior_due(&pet, now.to_utc()) {
+            events.push(pet.misbehave(now.to_utc()));
+        }
+        if snapshot
+            .cpu_temperature
+            .is_some_and(|t| t >= config.hot_temperature)
+        {
+            events.push(PetEvent::Hot);
+        }
+        let is_critical = !pet.is_dead() && overlay::is_critical(&pet);
+        if is_critical && !critical {
+            events.push(PetEvent::StatCritical);
+        }
+        critical = is_critical;
+        if let Some(count) = snapshot.upgrades {
+            if count > upgrades {
+                events.push(PetEvent::UpgradesPending);
+            }
+            upgrades = count;
+        }
+        let mut remembered = false;
+        for event in events {
+            tally.count(event);
+            remembered |= journal.record(now.to_utc(), event);
+            runtime.on_event(event);
+            hooks.fire(event, &pet, &runtime, &snapshot, now.to_utc());
+        }
+        if remembered {
+            if let Err(err) = journal.save(&config.journal_file) {
+                log::warn!("couldn't save the journal: {err:#}");
+            }
+        }
+        if !pet.is_dead() {
+            runtime.reminisce(&journal, &clock, now);
+        }
+        trace.lap(Stage::Tick);
+
+        schedule.last_cycle = Instant::now();
+        let mut deadline = scheduler::next_deadline(&cadence, &schedule, schedule.last_cycle);
+        // Once the pet is asleep and nothing is going on, show the sleep face
+        // and park until the end of quiet hours.
+        let quiet = quiet_hours.filter(|_| {
+            quiet_now
+                && queue_depth == 0
+                && runtime.particles.is_empty()
+                && !pet.is_misbehaving()
+                && !overlay::is_critical(&pet)
+                && !deadline.mode.is_recently_active()
+                && message.is_none()
+                && reminders.up().is_none()
+                && selfie.is_none()
+                && export.is_none()
+                && offer.is_none()
+                && menu.is_none()
+                && picker.is_none()
+        });
+        // Looked at just before drawing, the costliest part of the cycle.
+        let available = system::available_memory();
+        let quality = lowmem::Quality::for_memory(available, &config.low_memory);
+        if quality != runtime.quality {
+            let mb = available.unwrap_or_default() / 1024;
+            match quality {
+                lowmem::Quality::Full => log::info!("{mb} MB available, back to full quality"),
+                _ => log::warn!(
+                    "only {mb} MB available, cutting back: {}",
+                    quality.cutbacks().join(", ")
+                ),
+            }
+            runtime.quality = quality;
+            shared_display
+                .lock()
+                .unwrap()
+                .set_pre_clear(config.pre_clear.threshold().filter(|_| quality.pre_clear()));
+        }
+        if snapshot.power != runtime.power {
+            match snapshot.power {
+                scheduler::Power::Battery => log::info!("on battery"),
+                // What was held back goes out straight away.
+                scheduler::Power::Mains => {
+                    log::info!("back on mains");
+                    forced = true;
+                }
+            }
+            runtime.power = snapshot.power;
+        }
+        runtime.batching = scheduler::is_batching(&cadence, runtime.power);
+        runtime.pinned = carousel.pinned().is_some();
+        runtime.guest = guest.is_some();
+        let ctx = Context {
+            now,
+            clock: &clock,
+            config,
+            pet: &pet,
+            runtime: &runtime,
+            panel: &panel,
+            snapshot: &snapshot,
+            tally: &tally,
+            quiet: quiet_hours,
+        };
+        let note = message.as_ref().map(MessageScreen);
+        let photo = selfie.as_ref().map(SelfieScreen);
+        let reminder = reminders
+            .up()
+            .map(|(reminder, due)| ReminderScreen { reminder, due });
+        let code = export.as_ref().map(ExportScreen);
+        let offer_screen = offer.as_ref().map(|offer| OfferScreen {
+            offer,
+            current: &pet.name,
+        });
+        // There's nothing to look back on during the pet's first day.
+        let summary_window = config.summary.window();
+        let summary = (summary_window.is_some_and(|w| w.contains(now.time()))
+            && clock.localize(pet.born).date_naive() < now.date_naive())
+        .then_some(SummaryScreen(&tally));
+        let picker_screen = picker.as_ref().map(HatchScreen);
+        let menu_screen = menu.as_ref().map(SettingsScreen);
+        let screen: &dyn Screen = if let Some(picker) = &picker_screen {
+            picker
+        } else if let Some(menu) = &menu_screen {
+            menu
+        } else if let Some(offer) = &offer_screen {
+            offer
+        } else if quiet.is_some() {
+            &SleepScreen
+        } else if let Some(code) = &code {
+            code
+        } else if let Some(photo) = &photo {
+            photo
+        } else if let Some(reminder) = &reminder {
+            reminder
+        } else if let Some(note) = &note {
+            note
+        } else if let Some(summary) = &summary {
+            summary
+        } else {
+            carousel.current()
+        };
+        heartbeat.phase("drawing");
+        frame.clear();
+        screen.draw(&ctx, &mut frame);
+        log::debug!("drew {}", screen.name());
+        let own_interval = screen.desired_interval(&ctx);
+        // The pinned screen, while it's up rather than something in front
+        // of it, refreshes at its own interval instead of the cadence's.
+        schedule.pinned = carousel
+            .pinned()
+            .filter(|name| *name == screen.name() && own_interval != Duration::MAX)
+            .map(|_| own_interval);
+        let mut stays_accurate = own_interval;
+        if let Some(window) = summary_window.filter(|_| summary.is_none()) {
+            let starts = clock.next_occurrence(now, window.at);
+            stays_accurate = stays_accurate.min((starts - now).to_std().unwrap_or_default());
+        }
+        let reminder_change = reminders.next_change(&clock, now);
+        if let Some(at) = reminder_change {
+            stays_accurate = stays_accurate.min((at - now.to_utc()).to_std().unwrap_or_default());
+        }
+        let dirty = screen.is_dirty(&ctx);
+        // The alert border is a decoration, so it doesn't count as a change
+        // by itself. While it's up, and once more to clear it, the panel has
+        // to be refreshed regardless.
+        let alternate = match runtime.alert_border {
+            Some(Color::Red) => Color::Black,
+            _ => Color::Red,
+        };
+        let border = overlay::is_critical(&pet).then_some(alternate);
+        // Held until the panel is done with, so the watchdog can't
+        // reinitialise it halfway through an update.
+        let mut display = shared_display.lock().unwrap();
+        if border.is_some() || runtime.alert_border.is_some() {
+            display.invalidate();
+            forced = true;
+        }
+        runtime.alert_border = border;
+        // Profiling times every stage on every cycle.
+        if args.profile_cycles.is_some() {
+            display.invalidate();
+            forced = true;
+        }
+        if config.anti_burn_in && display.jitter(display::jitter_offset(now.to_utc())) {
+            forced = true;
+        }
+        heartbeat.phase("refreshing the panel");
+        let started = Instant::now();
+        let pending_frame = scheduler::Pending {
+            screen: screen.name(),
+            dirty,
+            forced,
+        };
+        // A change of screen goes through the configured effect, except
+        // on the way into the night's sleep or when rendering just once.
+        let switched = schedule
+            .shown
+            .as_ref()
+            .is_some_and(|shown| shown.screen != screen.name());
+        if switched && quiet.is_none() && !args.once {
+            display.transition(config.transition);
+        }
+        let unchanged = display.is_showing(&frame);
+        let urgent = forced
+            || overlay::is_critical(&pet)
+            || schedule
+                .last_interaction
+                .is_some_and(|at| schedule.shown.is_none_or(|shown| at > shown.at));
+        let since = schedule
+            .shown
+            .map(|shown| started.saturating_duration_since(shown.at));
+        let batched = !scheduler::batch_allows(runtime.power, urgent, since, cadence.battery);
+        let push = unchanged
+            || (!batched && scheduler::should_refresh(&schedule, &pending_frame, started));
+        if !push && batched {
+            log::debug!("on battery, saving changes to {} for later", screen.name());
+        } else if !push {
+            log::debug!("holding back changes to {} for now", screen.name());
+        }
+        let scene = Scene {
+            debug: runtime.debug_overlay,
+            info: DebugInfo {
+                refreshes,
+                last_refresh,
+                free_memory: snapshot.free_memory,
+                screen: screen.name(),
+                queue_depth,
+                cadence: deadline,
+            },
+            border,
+        };
+        let output =
+            push.then(|| display.compose(&frame, |output| compositor.apply(&scene, output)));
+        trace.lap(Stage::Compose);
+        let update = output.and_then(|output| display.diff(&frame, output));
+        if let Some(opens) = display.held() {
+            log::info!(
+                "held back a refresh of {} for {:.1}s, too soon after the last one",
+                screen.name(),
+                opens
+                    .saturating_duration_since(Instant::now())
+                    .as_secs_f32()
+            );
+            profile::coalesced(screen.name());
+        }
+        trace.lap(Stage::Diff);
+        let refreshed = match update {
+            Some(update) => display.transfer(update)?,
+            None => false,
+        };
+        trace.lap(Stage::Transfer);
+        // The simulator has no waveform to wait out.
+        trace.lap(Stage::WaveformWait);
+        let stale_at = started.checked_add(stays_accurate);
+        if refreshed {
+            schedule.shown = Some(scheduler::Shown {
+                screen: screen.name(),
+                at: started,
+                stale_at,
+            });
+        } else if let Some(shown) = schedule.shown.as_mut().filter(|_| unchanged) {
+            // The panel is still accurate as of now.
+            shown.stale_at = stale_at;
+        }
+        if refreshed {
+            refreshes += 1;
+            pre_clears += display.pre_cleared() as u64;
+            last_refresh = Some(started.elapsed());
+            schedule.unchanged_frames = 0;
+        } else if unchanged || !batched {
+            // Changes saved up for the battery aren't the frame settling
+            // down, so they don't count towards idling.
+            schedule.unchanged_frames += 1;
+        }
+        if let Some(quiet) = quiet {
+            let wake = quiet.next_end(&clock, now);
+            log::info!("sleeping until {}", wake.format("%H:%M"));
+            display.sleep();
+            collector.set_low_power(true);
+            // A reminder wakes the panel, pet or no pet.
+            let wake = reminder_change.map_or(wake, |at| wake.min(clock.localize(at)));
+            let until = (wake - now).to_std().unwrap_or_default();
+            schedule.parked_until = Some(schedule.last_cycle + until);
+        }
+        let (link, coalesced) = (display.link_status(), display.coalesced());
+        drop(display);
+        deadline = scheduler::next_deadline(&cadence, &schedule, schedule.last_cycle);
+        heartbeat.phase("saving");
+        pet.save(&config.save_file)?;
+        let status = Status {
+            updated: clock.now_utc(),
+            build: BuildInfo::get(),
+            panel: panel.clone(),
+            link,
+            screen: screen.name(),
+            refreshes,
+            pre_clears,
+            coalesced,
+            last_refresh_ms: last_refresh.map(|d| d.as_millis()),
+            available_update: snapshot.available_update.as_deref(),
+            cadence: CadenceStatus::new(deadline, runtime.power, runtime.batching),
+            watchdog: heartbeat.status(),
+            collector: collector.stats(),
+            hooks: hooks.stats(),
+            quality: runtime.quality,
+            pet: &pet,
+        };
+        if let Err(err) = status.write(&config.status_file) {
+            log::warn!("couldn't write status file: {err:#}");
+        }
+        trace.lap(Stage::Persist);
+        log::debug!("cycle took {trace}");
+        profile::observe(&trace);
+        let shown = screen.name();
+        if quiet.is_none()
+            && message.is_none()
+            && reminder.is_none()
+            && selfie.is_none()
+            && export.is_none()
+            && offer.is_none()
+            && menu.is_none()
+            && summary.is_none()
+            && picker.is_none()
+        {
+            carousel.advance();
+        }
+        if refreshed {
+            tally.count_refresh();
+        }
+
+        if args.once {
+            return Ok(());
+        }
+        if let Some(cycles) = args.profile_cycles {
+            // A cycle the refresh guard held back never got to the panel,
+            // so it waits for the guard and doesn't count.
+            let held = shared_display.lock().unwrap().held();
+            if let Some(opens) = held {
+                let wait = opens.saturating_duration_since(Instant::now());
+                heartbeat.beat(shown, wait);
+                std::thread::sleep(wait);
+                continue;
+            }
+            traces.push(trace);
+            if traces.len() >= cycles {
+                println!("{}", profile::table(&traces));
+                return Ok(());
+            }
+            heartbeat.beat(shown, Duration::ZERO);
+            continue;
+        }
+        heartbeat.beat(shown, deadline.at.saturating_duration_since(Instant::now()));
+        heartbeat.phase("waiting");
+        // Sleep until the next refresh. Commands count as interaction, which
+        // pulls the deadline in to the minimum spacing, so their effect
+        // shows up soon without hammering the panel.
+        // A refresh held back by the guard goes out as soon as it allows.
+        let held = shared_display.lock().unwrap().held();
+        loop {
+            let deadline = scheduler::next_deadline(&cadence, &schedule, Instant::now());
+            let until = held.map_or(deadline.at, |held| held.min(deadline.at));
+            match inbox.recv_until(until)? {
+                Some(Command::Shutdown) => return Ok(()),
+                // Host changes show up on the next refresh; they aren't
+                // worth waking the panel or speeding it up for.
+                Some(command @ Command::System(_)) => queue.push(command, Instant::now()),
+                Some(command) => {
+                    queue.push(command, Instant::now());
+                    schedule.last_interaction = Some(Instant::now());
+                    // Coming out of parking redraws straight away.
+                    if wake(&mut schedule, &collector) {
+                        break;
+                    }
+                }
+                None => {
+                    wake(&mut schedule, &collector);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Hands `message` to the running daemon, or with none running, puts it on
+/// the panel directly and leaves it for the daemon to pick up on start.
+fn say(config: &Config, clock: &Clock, message: &Message) -> Result<()> {
+    message.validate().map_err(anyhow::Error::msg)?;
+    if message::post(&config.control_socket, message)? {
+        return Ok(());
+    }
+    let _lock = match lock::acquire(&config.lock_file)? {
+        lock::Acquired::Locked(lock) => Some(lock),
+        lock::Acquired::Busy(_) => bail!(
+            "inky-gotchi holds {} but isn't listening on {}",
+            config.lock_file.display(),
+            config.control_socket.display()
+        ),
+        lock::Acquired::Unavailable => None,
+    };
+    message.save(&config.message_file)?;
+    let mut frame = Frame::new(WIDTH, HEIGHT);
+    screens::message::draw(
+        &mut frame,
+        message,
+        clock,
+        screens::Profile::from_config(config),
+        config.supersample_text,
+    );
+    let mut display = Simulator::new(
+        config.simulator.output.clone(),
+        config.partial_refresh,
+        config.mirror(),
+        config.pre_clear.threshold(),
+    );
+    if display.update(&frame, |_| {})? {
+        log::info!("no daemon running, drew the message directly");
+    } else {
+        log::warn!(
+            "no daemon running and the panel isn't answering; the message is saved for later"
+        );
+    }
+    Ok(())
+}
+
+/// Takes the panel from the daemon and leaves it saying the host is off,
+/// with the pet as of its last save. Pre-clearing is skipped, so it's done
+/// in one refresh, well inside systemd's stop timeout.
+fn shutdown_screen(args: &Args, config: &Config, zone: Zone) -> Result<()> {
+    if !system::shutting_down() {
+        log::info!("the system isn't shutting down, leaving the panel as it is");
+        return Ok(());
+    }
+    let _lock = match lock::acquire(&config.lock_file)? {
+        lock::Acquired::Locked(lock) => Some(lock),
+        lock::Acquired::Busy(holder) => Some(lock::take_over(&config.lock_file, holder)?),
+        lock::Acquired::Unavailable => None,
+    };
+    let clock = match args.at {
+        Some(at) => Clock::frozen(zone, at),
+        None => Clock::new(zone),
+    };
+    let pet = Pet::load(&config.save_file)?;
+    let mut frame = Frame::new(WIDTH, HEIGHT);
+    screens::poweroff::draw(
+        &mut frame,
+        &pet,
+        clock.now(),
+        screens::Profile::from_config(config),
+    );
+    let mut display = Simulator::new(
+        config.simulator.output.clone(),
+        config.partial_refresh,
+        config.mirror(),
+        None,
+    );
+    if display.update(&frame, |_| {})? {
+        log::info!("shutting down, drew the powered-off screen");
+    } else {
+        log::warn!("shutting down, but the panel isn't answering");
+    }
+    display.sleep();
+    Ok(())
+}
+
+fn export(config: &Config, now: DateTime<Utc>) -> Result<()> {
+    let pet = Pet::load(&config.save_file)?;
+    let showing = transfer::Showing::new(&pet, now)?;
+    println!("{}", transfer::readable(&showing.code));
+    if control::send(&config.control_socket, "export")? {
+        return Ok(());
+    }
+    let _lock = match lock::acquire(&config.lock_file)? {
+        lock::Acquired::Locked(lock) => Some(lock),
+        // The code's printed, which is what matters.
+        lock::Acquired::Busy(_) => return Ok(()),
+        lock::Acquired::Unavailable => None,
+    };
+    let mut frame = Frame::new(WIDTH, HEIGHT);
+    screens::transfer::draw(&mut frame, &showing, screens::Profile::from_config(config));
+    let mut display = Simulator::new(
+        config.simulator.output.clone(),
+        config.partial_refresh,
+        config.mirror(),
+        config.pre_clear.threshold(),
+    );
+    if display.update(&frame, |_| {})? {
+        log::info!("no daemon running, drew the code directly");
+    }
+    Ok(())
+}
+
+fn import(config: &Config, code: &str, yes: bool, now: DateTime<Utc>) -> Result<()> {
+    let incoming = transfer::Transfer::decode(code).context("bad code")?;
+    println!("{}", incoming.summary());
+    if control::send(
+        &config.control_socket,
+        &format!("import {}", transfer::readable(code)),
+    )? {
+        println!("waiting on the panel: hold A there to swap");
+        return Ok(());
+    }
+    let _lock = match lock::acquire(&config.lock_file)? {
+        lock::Acquired::Locked(lock) => Some(lock),
+        lock::Acquired::Busy(_) => bail!(
+            "inky-gotchi holds {} but isn't listening on {}",
+            config.lock_file.display(),
+            config.control_socket.display()
+        ),
+        lock::Acquired::Unavailable => None,
+    };
+    if !yes {
+        match Pet::load(&config.save_file).ok() {
+            Some(current) => print!("{} would go. Swap? [y/N] ", current.name),
+            None => print!("Install? [y/N] "),
+        }
+        std::io::Write::flush(&mut std::io::stdout())?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("kept the pet");
+            return Ok(());
+        }
+    }
+    let name = incoming.name.clone();
+    incoming.into_pet(now).save(&config.save_file)?;
+    Journal::default().save(&config.journal_file)?;
+    println!("{name} moved in");
+    Ok(())
+}
+
+/// Ends parking, returning whether the display was parked.
+fn wake(schedule: &mut scheduler::State, collector: &collector::Collector) -> bool {
+    let parked = schedule.parked_until.take().is_some();
+    if parked {
+        log::info!("waking up");
+        collector.set_low_power(false);
+    }
+    parked
+}
+
+fn run_simulation(
+    args: &Args,
+    config: &Config,
+    zone: Zone,
+    scenario: &Path,
+    days: u32,
+    csv: Option<&Path>,
+) -> Result<()> {
+    let scenario = simulate::Scenario::load(scenario)?;
+    // A fixed start and seed by default, so runs are repeatable.
+    let start = args.at.unwrap_or_else(simulate::default_start);
+    let seed = args.seed.unwrap_or(0);
+    let clock = Clock::frozen(zone, start);
+    let report = simulate::run(config, &clock, &scenario, days, seed, start);
+    for event in &report.events {
+        println!("{event}");
+    }
+    println!("{}", if report.survived { "survived" } else { "died" });
+    match csv {
+        Some(path) => std::fs::write(path, &report.csv)
+            .with_context(|| format!("writing {}", path.display()))?,
+        None => print!("\n{}", report.csv),
+    }
+    Ok(())
+}
+
+fn print_version(verbose: bool) {
+    let build = BuildInfo::get();
+    println!("inky-gotchi {}", build.version);
+    if verbose {
+        println!("commit:   {}", build.commit_label());
+        println!("built:    {}", build.build_date);
+        println!("rustc:    {}", build.rustc);
+        println!("features: {}", build.features_label());
+    }
 }
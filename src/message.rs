@@ -0,0 +1,91 @@
+//! Notes left on the panel with `inky-gotchi say`. A note replaces the
+//! carousel until it expires or someone presses a button, and is kept in a
+//! small file so it's still up after a restart.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::control;
+use crate::icons::Icon;
+use crate::persist;
+
+/// Longest note accepted; more than this wouldn't fit on the panel anyway.
+pub const MAX_LENGTH: usize = 200;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub text: String,
+    /// An [`Icon`] name, e.g. `cart`.
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Who left it, for the footer.
+    #[serde(default)]
+    pub author: Option<String>,
+    /// When it comes down by itself; never, without one.
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Message {
+    /// Checks what came over the socket or the command line. The error is
+    /// meant for whoever sent it.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.text.trim().is_empty() {
+            return Err("the message is empty".into());
+        }
+        if self.text.chars().count() > MAX_LENGTH {
+            return Err(format!("messages are at most {MAX_LENGTH} characters"));
+        }
+        if let Some(name) = self.icon.as_deref().filter(|n| Icon::parse(n).is_none()) {
+            return Err(format!("unknown icon {name:?}"));
+        }
+        Ok(())
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_some_and(|until| now >= until)
+    }
+
+    /// The note left at `path`, if there is one. An unreadable file is
+    /// treated as no note.
+    pub fn load(path: &Path) -> Option<Message> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(err) => {
+                log::warn!("reading {}: {err}", path.display());
+                return None;
+            }
+        };
+        match serde_json::from_str(&text) {
+            Ok(message) => Some(message),
+            Err(err) => {
+                log::warn!("ignoring unreadable message file {}: {err}", path.display());
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        persist::write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    /// Takes the note at `path` down.
+    pub fn remove(path: &Path) {
+        if let Err(err) = fs::remove_file(path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("removing {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+/// Hands `message` to the daemon listening on `socket`. Returns false when
+/// nothing is listening there.
+pub fn post(socket: &Path, message: &Message) -> Result<bool> {
+    control::send(socket, &format!("say {}", serde_json::to_string(message)?))
+}
@@ -0,0 +1,305 @@
+//! Things drawn over the finished screen. Each is an [`Overlay`] layer,
+//! registered with the [`Compositor`] at startup and drawn in priority
+//! order, lowest first, once the screen itself is done.
+
+use std::time::Duration;
+
+use embedded_graphics::mono_font::iso_8859_15::FONT_6X10;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+
+use crate::display::{Color, Frame};
+use crate::pet::Pet;
+use crate::scheduler::Deadline;
+use crate::shade::{self, Bayer};
+use crate::text::{self, Align};
+
+/// Width of the alert border around the edge of the screen.
+const BORDER_WIDTH: u32 = 4;
+/// Hunger or energy below this sets off the border alert.
+const CRITICAL_STAT: f32 = 10.0;
+
+/// Height of the debug strip along the bottom edge.
+const DEBUG_HEIGHT: u32 = 11;
+
+/// Internals shown by the debug overlay.
+pub struct DebugInfo<'a> {
+    pub refreshes: u64,
+    pub last_refresh: Option<Duration>,
+    /// Available memory in kilobytes.
+    pub free_memory: Option<u64>,
+    pub screen: &'a str,
+    pub queue_depth: usize,
+    pub cadence: Deadline,
+}
+
+/// What the layers get to look at, beyond the screen they're drawn over.
+pub struct Scene<'a> {
+    /// Whether the debug overlay is toggled on.
+    pub debug: bool,
+    pub info: DebugInfo<'a>,
+    /// The alert border's colour this cycle, if it's up.
+    pub border: Option<Color>,
+}
+
+/// A layer drawn over the screen.
+pub trait Overlay {
+    /// Where it goes in the stack; higher priorities are drawn on top.
+    fn priority(&self) -> i32;
+
+    fn active(&self, scene: &Scene) -> bool;
+
+    fn draw(&self, scene: &Scene, target: &mut Frame);
+}
+
+/// The overlay layers, in the order they're drawn.
+pub struct Compositor {
+    layers: Vec<Box<dyn Overlay>>,
+}
+
+impl Compositor {
+    /// A compositor with the built-in layers registered.
+    pub fn new() -> Self {
+        let mut compositor = Compositor { layers: Vec::new() };
+        compositor.register(Box::new(DebugStrip));
+        compositor.register(Box::new(AlertBorder));
+        compositor
+    }
+
+    /// Adds `layer` to the stack. Layers with the same priority are drawn in
+    /// the order they were registered.
+    pub fn register(&mut self, layer: Box<dyn Overlay>) {
+        let at = self
+            .layers
+            .partition_point(|l| l.priority() <= layer.priority());
+        self.layers.insert(at, layer);
+    }
+
+    /// Draws every active layer onto `target`, which already holds the
+    /// screen.
+    pub fn apply(&self, scene: &Scene, target: &mut Frame) {
+        for layer in self.layers.iter().filter(|l| l.active(scene)) {
+            layer.draw(scene, target);
+        }
+    }
+}
+
+/// Internals along the bottom edge, see [`draw_debug`].
+struct DebugStrip;
+
+impl Overlay for DebugStrip {
+    fn priority(&self) -> i32 {
+        10
+    }
+
+    fn active(&self, scene: &Scene) -> bool {
+        scene.debug
+    }
+
+    fn draw(&self, scene: &Scene, target: &mut Frame) {
+        draw_debug(target, &scene.info);
+    }
+}
+
+/// The flashing border while the pet is in a bad way, over everything else.
+struct AlertBorder;
+
+impl Overlay for AlertBorder {
+    fn priority(&self) -> i32 {
+        20
+    }
+
+    fn active(&self, scene: &Scene) -> bool {
+        scene.border.is_some()
+    }
+
+    fn draw(&self, scene: &Scene, target: &mut Frame) {
+        if let Some(color) = scene.border {
+            draw_border(target, color);
+        }
+    }
+}
+
+/// Draws a strip of internals along the bottom. The strip is dithered so the
+/// screen underneath still shows through around the text.
+pub fn draw_debug(frame: &mut Frame, info: &DebugInfo) {
+    let strip = Rectangle::new(
+        Point::new(0, (frame.height() - DEBUG_HEIGHT) as i32),
+        Size::new(frame.width(), DEBUG_HEIGHT),
+    );
+    shade::fill(frame, strip, 0.5, Bayer::Two, Color::Black);
+
+    let duration = match info.last_refresh {
+        Some(d) => format!("{:.1}s", d.as_secs_f32()),
+        None => "-".into(),
+    };
+    let memory = match info.free_memory {
+        Some(kb) => format!("{}M", kb / 1024),
+        None => "?M".into(),
+    };
+    let line = format!(
+        "#{} {} {} {} q{} {}s {}",
+        info.refreshes,
+        duration,
+        memory,
+        info.screen,
+        info.queue_depth,
+        info.cadence.interval.as_secs(),
+        info.cadence.mode.label()
+    );
+    let area = strip.offset(-1);
+    let placement = text::place(&line, &FONT_6X10, area, Align::LEFT);
+    placement
+        .bounds
+        .offset(1)
+        .into_styled(PrimitiveStyle::with_fill(Color::White))
+        .draw(frame)
+        .ok();
+    text::draw(frame, &line, &FONT_6X10, Color::Black, area, Align::LEFT);
+}
+
+/// Whether the pet is in a bad enough way for the border alert.
+pub fn is_critical(pet: &Pet) -> bool {
+    pet.hunger < CRITICAL_STAT || pet.energy < CRITICAL_STAT
+}
+
+/// Draws a solid band around the outer edge, over whatever is there.
+pub fn draw_border(frame: &mut Frame, color: Color) {
+    let style = PrimitiveStyle::with_stroke(color, BORDER_WIDTH);
+    // Strokes are centred on the outline, so inset it by half the width to
+    // keep the whole band on screen.
+    let inset = (BORDER_WIDTH / 2) as i32;
+    Rectangle::new(
+        Point::new(inset, inset),
+        Size::new(frame.width() - BORDER_WIDTH, frame.height() - BORDER_WIDTH),
+    )
+    .into_styled(style)
+    .draw(frame)
+    .ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::Mode;
+    use crate::screens::{pet::PetScreen, Fixture};
+    use std::time::Instant;
+
+    fn scene(debug: bool, border: Option<Color>) -> Scene<'static> {
+        Scene {
+            debug,
+            info: DebugInfo {
+                refreshes: 42,
+                last_refresh: Some(Duration::from_millis(1500)),
+                free_memory: Some(180 * 1024),
+                screen: "pet",
+                queue_depth: 2,
+                cadence: Deadline {
+                    at: Instant::now(),
+                    interval: Duration::from_secs(60),
+                    mode: Mode::Normal,
+                },
+            },
+            border,
+        }
+    }
+
+    /// The pet screen, which the layers are drawn over.
+    fn screen() -> Frame {
+        Fixture::at("2024-05-01T12:04:30+03:00").draw(&PetScreen)
+    }
+
+    /// What the daemon drew before the compositor: the debug strip, then
+    /// the border.
+    fn hand_wired(scene: &Scene) -> Frame {
+        let mut frame = screen();
+        if scene.debug {
+            draw_debug(&mut frame, &scene.info);
+        }
+        if let Some(color) = scene.border {
+            draw_border(&mut frame, color);
+        }
+        frame
+    }
+
+    fn composited(scene: &Scene) -> Frame {
+        let mut frame = screen();
+        Compositor::new().apply(scene, &mut frame);
+        frame
+    }
+
+    #[test]
+    fn output_matches_the_hand_wired_order() {
+        for debug in [false, true] {
+            for border in [None, Some(Color::Black), Some(Color::Red)] {
+                let scene = scene(debug, border);
+                assert!(
+                    composited(&scene) == hand_wired(&scene),
+                    "debug {debug}, border {border:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inactive_layers_leave_the_screen_alone() {
+        assert!(composited(&scene(false, None)) == screen());
+        assert!(composited(&scene(true, None)) != screen());
+        assert!(composited(&scene(false, Some(Color::Red))) != screen());
+    }
+
+    #[test]
+    fn the_border_goes_over_the_debug_strip() {
+        let frame = composited(&scene(true, Some(Color::Red)));
+        let bottom = frame.height() as i32 - 1;
+        for x in [0, frame.width() as i32 / 2, frame.width() as i32 - 1] {
+            assert_eq!(frame.pixel(x, bottom), Color::Red);
+        }
+    }
+
+    /// Fills the whole frame, to show which layer went last.
+    struct Fill(i32, Color);
+
+    impl Overlay for Fill {
+        fn priority(&self) -> i32 {
+            self.0
+        }
+
+        fn active(&self, _: &Scene) -> bool {
+            true
+        }
+
+        fn draw(&self, _: &Scene, target: &mut Frame) {
+            target
+                .bounding_box()
+                .into_styled(PrimitiveStyle::with_fill(self.1))
+                .draw(target)
+                .ok();
+        }
+    }
+
+    #[test]
+    fn layers_are_drawn_by_priority_then_registration() {
+        let on_top = |layers: Vec<Fill>| {
+            let mut compositor = Compositor { layers: Vec::new() };
+            for layer in layers {
+                compositor.register(Box::new(layer));
+            }
+            let mut frame = Frame::new(4, 4);
+            compositor.apply(&scene(false, None), &mut frame);
+            frame.pixel(0, 0)
+        };
+        assert_eq!(
+            on_top(vec![Fill(2, Color::Red), Fill(1, Color::Black)]),
+            Color::Red
+        );
+        assert_eq!(
+            on_top(vec![Fill(1, Color::Black), Fill(2, Color::Red)]),
+            Color::Red
+        );
+        assert_eq!(
+            on_top(vec![Fill(1, Color::Red), Fill(1, Color::Black)]),
+            Color::Black
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! Where state files live: one data directory, from `--data-dir`, else
+//! `$XDG_DATA_HOME/inky-gotchi`, else `~/.local/share/inky-gotchi`.
+//!
+//! State file paths in the config that are relative are taken relative to
+//! that directory, so the rest of the daemon just uses the config's paths.
+//! Those used to be relative to wherever the daemon was started; a file
+//! still found there is moved into the data directory the first time.
+
+use std::fs::{self, DirBuilder};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context as _, Result};
+
+use crate::config::Config;
+
+const APP: &str = "inky-gotchi";
+
+/// The data directory to use, created readable by the owner only if it
+/// doesn't exist yet.
+pub fn data_dir(flag: Option<&Path>) -> Result<PathBuf> {
+    let env = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+    let dir = match (flag, env("XDG_DATA_HOME"), env("HOME")) {
+        (Some(dir), _, _) => dir.to_path_buf(),
+        (None, Some(xdg), _) => Path::new(&xdg).join(APP),
+        (None, None, Some(home)) => Path::new(&home).join(".local/share").join(APP),
+        (None, None, None) => bail!("no data directory: pass --data-dir or set $HOME"),
+    };
+    if !dir.is_dir() {
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)
+            .with_context(|| format!("creating data directory {}", dir.display()))?;
+        log::info!("created data directory {}", dir.display());
+    }
+    Ok(dir)
+}
+
+/// The state files in `config`, by their config key.
+fn state_files(config: &mut Config) -> Vec<(&'static str, &mut PathBuf)> {
+    #[cfg_attr(
+        not(any(feature = "update-check", feature = "webhook")),
+        allow(unused_mut)
+    )]
+    let mut files = vec![
+        ("save_file", &mut config.save_file),
+        ("message_file", &mut config.message_file),
+        ("pin_file", &mut config.pin_file),
+        ("reminders_file", &mut config.reminders_file),
+        ("overrides_file", &mut config.overrides_file),
+        ("summary_file", &mut config.summary_file),
+        ("journal_file", &mut config.journal_file),
+        ("selfie.dir", &mut config.selfie.dir),
+    ];
+    #[cfg(feature = "update-check")]
+    files.push((
+        "update_check.cache_file",
+        &mut config.update_check.cache_file,
+    ));
+    #[cfg(feature = "webhook")]
+    files.push(("webhook.state_file", &mut config.webhook.state_file));
+    files
+}
+
+/// Points the relative state paths in `config` into `dir`, moving over
+/// any file still at its old place.
+pub fn resolve(config: &mut Config, dir: &Path) -> Result<()> {
+    for (key, path) in state_files(config) {
+        if path.is_absolute() {
+            continue;
+        }
+        let legacy = path.clone();
+        *path = dir.join(&legacy);
+        if !legacy.is_file() {
+            continue;
+        }
+        if path.exists() {
+            log::warn!(
+                "{key}: using {} and ignoring the old {}",
+                path.display(),
+                legacy.display()
+            );
+            continue;
+        }
+        migrate(&legacy, path)
+            .with_context(|| format!("moving {} to {}", legacy.display(), path.display()))?;
+        log::info!("moved {} to {}", legacy.display(), path.display());
+    }
+    Ok(())
+}
+
+fn migrate(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // The data directory is often on another filesystem than wherever the
+    // daemon used to run, and rename can't cross those.
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// Every file and socket the daemon uses, for `paths`.
+pub fn list(config: &Config) -> Vec<(&'static str, &Path)> {
+    #[cfg_attr(
+        not(any(feature = "update-check", feature = "webhook")),
+        allow(unused_mut)
+    )]
+    let mut paths = vec![
+        ("save_file", config.save_file.as_path()),
+        ("message_file", &config.message_file),
+        ("pin_file", &config.pin_file),
+        ("reminders_file", &config.reminders_file),
+        ("overrides_file", &config.overrides_file),
+        ("summary_file", &config.summary_file),
+        ("journal_file", &config.journal_file),
+        ("selfie.dir", &config.selfie.dir),
+        ("status_file", &config.status_file),
+        ("lock_file", &config.lock_file),
+        ("control_socket", &config.control_socket),
+        ("simulator.output", &config.simulator.output),
+    ];
+    #[cfg(feature = "update-check")]
+    paths.push(("update_check.cache_file", &config.update_check.cache_file));
+    #[cfg(feature = "webhook")]
+    paths.push(("webhook.state_file", &config.webhook.state_file));
+    paths
+}
@@ -0,0 +1,21 @@
+//! Helpers for files that must survive power cuts.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context as _, Result};
+
+/// Replaces `path` with `contents` atomically: the data goes to a temporary
+/// sibling first and is renamed over the target once synced, so a crash
+/// leaves either the old file or the new one, never a truncated mix.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = Path::new(&tmp);
+    let mut file = fs::File::create(tmp).with_context(|| format!("creating {}", tmp.display()))?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(tmp, path).with_context(|| format!("replacing {}", path.display()))?;
+    Ok(())
+}
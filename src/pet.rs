@@ -0,0 +1,1210 @@
+//! The pet itself: its stats, how they decay, and its save file.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Clock;
+use crate::config::PetConfig;
+use crate::events::{Action, PetEvent, Reading};
+use crate::persist;
+use crate::runtime::ParticleKind;
+
+/// Stats range from 0 (desperate) to this value (perfectly content).
+pub const STAT_MAX: f32 = 100.0;
+
+/// Points lost per hour for each stat.
+const HUNGER_DECAY: f32 = 4.0;
+const HAPPINESS_DECAY: f32 = 3.0;
+const ENERGY_DECAY: f32 = 2.0;
+/// Points of energy gained per hour asleep, instead of losing them, by a
+/// pet that isn't the least bit sick.
+const ENERGY_REGEN: f32 = 10.0;
+/// Hours between the pet making a new mess.
+const HOURS_PER_MESS: f32 = 6.0;
+/// Below this much energy the pet is too tired to eat properly, and food
+/// does it only this share of its usual good.
+const TIRED_ENERGY: f32 = 15.0;
+const TIRED_FEED_SHARE: f32 = 0.5;
+const PET_AMOUNT: f32 = 5.0;
+/// Each petting within the hour is worth half the one before, and all of
+/// them together at most this much: the third already runs into it.
+const PETTING_HOURLY_CAP: f32 = 8.0;
+/// Messes stop accumulating at this point; there's no room for more.
+pub const MAX_MESSES: u8 = 4;
+/// Sickness gained per hour for each mess left lying around, and lost per
+/// hour once they're all cleaned up.
+const MESS_SICKNESS: f32 = 2.0;
+const SICKNESS_RECOVERY: f32 = 4.0;
+/// Sickness from which the pet counts as sick.
+const SICK_THRESHOLD: f32 = 50.0;
+/// Every stat has to be above this at the daily check, and the pet not
+/// sick, for the day to count towards a streak.
+const STREAK_THRESHOLD: f32 = 50.0;
+/// How long the owner has to respond to misbehaviour.
+const MISBEHAVIOR_WINDOW: Duration = Duration::minutes(15);
+/// Training gained for disciplining in time, and lost for letting it slide.
+const TRAINING_GAIN: f32 = 15.0;
+const TRAINING_LOSS: f32 = 10.0;
+/// Happiness gained the first time each day a pushed metric reaches its
+/// goal.
+const GOAL_AMOUNT: f32 = 15.0;
+/// Happiness lost when told off for nothing.
+const UNFAIR_DISCIPLINE_COST: f32 = 10.0;
+/// Hours the pet can go with an empty stomach before it dies.
+const STARVATION_HOURS: f32 = 24.0;
+/// Age at which the pet grows into its adult form.
+const ADULT_AGE: Duration = Duration::days(3);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pet {
+    pub name: String,
+    pub born: DateTime<Utc>,
+    pub last_tick: DateTime<Utc>,
+    /// Fullness; low means hungry.
+    pub hunger: f32,
+    pub happiness: f32,
+    pub energy: f32,
+    pub messes: u8,
+    /// How ill the pet is from living in its mess, from 0 (healthy) to
+    /// [`STAT_MAX`]; see [`Pet::is_sick`].
+    #[serde(default)]
+    pub sickness: f32,
+    /// How well-behaved the pet has been brought up, from 0 to [`STAT_MAX`].
+    #[serde(default = "default_training")]
+    pub training: f32,
+    /// Set while the pet is misbehaving, until the window to respond ends.
+    #[serde(default)]
+    pub misbehaving_until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub form: Form,
+    /// The egg picked on the first run; `None` until then.
+    #[serde(default = "legacy_egg")]
+    pub egg: Option<Egg>,
+    #[serde(default)]
+    petting: PettingHour,
+    /// Hours in a row spent with hunger at zero.
+    #[serde(default)]
+    starving_hours: f32,
+    #[serde(default)]
+    pub died: Option<DateTime<Utc>>,
+    /// Hours accumulated towards the next mess.
+    #[serde(default)]
+    mess_progress: f32,
+    /// Horizontal position the pet likes to stop at while wandering.
+    #[serde(default)]
+    pub favorite_spot: Option<i32>,
+    #[serde(default)]
+    pub lifetime: Lifetime,
+    /// When the next daily check is due.
+    #[serde(default)]
+    next_check: Option<DateTime<Utc>>,
+    /// The latest value pushed for each metric, by name.
+    #[serde(default)]
+    pub metrics: BTreeMap<String, Metric>,
+}
+
+fn default_training() -> f32 {
+    STAT_MAX / 2.0
+}
+
+/// Pets from before there was a choice came from a plain egg.
+fn legacy_egg() -> Option<Egg> {
+    Some(Egg::Plain)
+}
+
+/// What the pet hatched from, which decides what it can grow into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Egg {
+    #[default]
+    Plain,
+    /// Gentle: grows up noble with less training.
+    Speckled,
+    /// Fiery: hard to bring up noble, and red as an adult.
+    Ember,
+}
+
+impl Egg {
+    pub const ALL: [Egg; 3] = [Egg::Plain, Egg::Speckled, Egg::Ember];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Egg::Plain => "Plain",
+            Egg::Speckled => "Speckled",
+            Egg::Ember => "Ember",
+        }
+    }
+
+    /// Training needed to grow into the noble form.
+    fn noble_training(self) -> f32 {
+        match self {
+            Egg::Plain => 60.0,
+            Egg::Speckled => 45.0,
+            Egg::Ember => 75.0,
+        }
+    }
+}
+
+/// What the pet has grown into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Form {
+    #[default]
+    Baby,
+    /// Well trained and well looked after.
+    Noble,
+    /// Everything else: left to run wild.
+    Wild,
+}
+
+/// The adult form earned by the pet's upbringing, given the egg it came
+/// from and its training and lifetime care score when it comes of age.
+pub fn adult_form(egg: Egg, training: f32, care_score: Option<u32>) -> Form {
+    if training >= egg.noble_training() && care_score.unwrap_or(0) >= 50 {
+        Form::Noble
+    } else {
+        Form::Wild
+    }
+}
+
+/// Happiness gained from petting in the current hour, which starts with
+/// the first petting in it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PettingHour {
+    started: Option<DateTime<Utc>>,
+    count: u32,
+    gained: f32,
+}
+
+impl PettingHour {
+    /// Whether the hour is over at `now`, or never started.
+    fn is_over(&self, now: DateTime<Utc>) -> bool {
+        self.started
+            .is_none_or(|started| now - started >= Duration::hours(1))
+    }
+
+    /// The share of a full petting one more at `now` is worth.
+    fn share(&self, now: DateTime<Utc>) -> f32 {
+        let (count, gained) = match self.is_over(now) {
+            true => (0, 0.0),
+            false => (self.count, self.gained),
+        };
+        0.5f32
+            .powi(count as i32)
+            .min((PETTING_HOURLY_CAP - gained) / PET_AMOUNT)
+    }
+
+    /// Notes a petting at `now` that was worth `share` of a full one.
+    fn record(&mut self, now: DateTime<Utc>, share: f32) {
+        if self.is_over(now) {
+            *self = PettingHour {
+                started: Some(now),
+                ..PettingHour::default()
+            };
+        }
+        self.count += 1;
+        self.gained += share * PET_AMOUNT;
+    }
+}
+
+/// The latest reading of a pushed metric.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Metric {
+    pub value: f64,
+    pub goal: Option<f64>,
+    pub at: DateTime<Utc>,
+    /// Local day the goal was last reached on, so it only pays out once a
+    /// day.
+    #[serde(default)]
+    rewarded: Option<NaiveDate>,
+}
+
+/// Running totals over the pet's whole life, kept up as things happen
+/// rather than recomputed from history.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lifetime {
+    pub feedings: u32,
+    pub games: u32,
+    pub pettings: u32,
+    pub messes_cleaned: u32,
+    /// Times the pet fell sick and got better.
+    pub sicknesses: u32,
+    /// Daily checks so far, and how many of those found the pet well.
+    pub checks: u32,
+    pub good_checks: u32,
+    /// Consecutive good daily checks up to the latest one.
+    pub streak: u32,
+    pub longest_streak: u32,
+}
+
+impl Lifetime {
+    /// Share of daily checks that found the pet well, as a percentage.
+    pub fn care_score(&self) -> Option<u32> {
+        (self.checks > 0).then(|| self.good_checks * 100 / self.checks)
+    }
+}
+
+impl Pet {
+    pub fn new(name: &str, now: DateTime<Utc>) -> Self {
+        Pet {
+            name: name.to_string(),
+            born: now,
+            last_tick: now,
+            hunger: STAT_MAX,
+            happiness: STAT_MAX,
+            energy: STAT_MAX,
+            messes: 0,
+            sickness: 0.0,
+            training: default_training(),
+            misbehaving_until: None,
+            form: Form::Baby,
+            egg: None,
+            petting: PettingHour::default(),
+            starving_hours: 0.0,
+            died: None,
+            mess_progress: 0.0,
+            favorite_spot: None,
+            lifetime: Lifetime::default(),
+            next_check: None,
+            metrics: BTreeMap::new(),
+        }
+    }
+
+    /// Loads the pet saved at `path`, which has to be there.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("parsing save file {}", path.display()))
+    }
+
+    /// Loads the pet from `path`, hatching a new one if there is no save yet.
+    pub fn load_or_new(path: &Path, name: &str, now: DateTime<Utc>) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("parsing save file {}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                log::info!("no save at {}, hatching {name}", path.display());
+                Ok(Pet::new(name, now))
+            }
+            Err(err) => Err(err).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        persist::write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    /// Advances the stats to `now`, running any daily checks passed on
+    /// the way. Time running backwards counts as no time at all, and a gap
+    /// is never counted as more than the configured maximum.
+    ///
+    /// While `asleep`, energy comes back rather than running down.
+    pub fn tick(&mut self, now: DateTime<Utc>, clock: &Clock, config: &PetConfig, asleep: bool) {
+        if self.is_dead() {
+            return;
+        }
+        let elapsed = now - self.last_tick;
+        let max_gap = Duration::hours(config.max_offline_hours as i64);
+        if elapsed < Duration::zero() {
+            log::warn!(
+                "clock went back {}s, not aging the pet",
+                -elapsed.num_seconds()
+            );
+            self.last_tick = now;
+        } else if elapsed > max_gap {
+            log::warn!(
+                "offline for {}h, aging the pet by {}h only",
+                elapsed.num_hours(),
+                max_gap.num_hours()
+            );
+            self.last_tick = now - max_gap;
+        }
+        let next_check = |after: DateTime<Utc>| {
+            clock
+                .next_occurrence(clock.localize(after), config.daily_check)
+                .to_utc()
+        };
+        // Checks skipped over above, or pushed far ahead by a clock that
+        // ran backwards, are rescheduled from where aging resumes. One a
+        // day ahead stays, so a clock set back a few hours doesn't run the
+        // day's check again.
+        let mut check = match self.next_check {
+            Some(at) if at > self.last_tick && at <= next_check(next_check(self.last_tick)) => at,
+            _ => next_check(self.last_tick),
+        };
+        // Decay is linear, so aging up to each check in turn gives the same
+        // result as one step while letting the check see the stats as they
+        // were at the time.
+        while check <= now {
+            self.decay(check, asleep);
+            if self.is_dead() {
+                break;
+            }
+            self.daily_check();
+            check = next_check(check);
+        }
+        self.next_check = Some(check);
+        if self.is_dead() {
+            return;
+        }
+        self.decay(now, asleep);
+
+        if self.misbehaving_until.is_some_and(|until| until <= now) {
+            log::info!("{}'s misbehaviour went unchecked", self.name);
+            self.misbehaving_until = None;
+            self.training = clamp_stat(self.training - TRAINING_LOSS);
+        }
+        if self.form == Form::Baby && now - self.born >= ADULT_AGE {
+            let egg = self.egg.unwrap_or_default();
+            self.form = adult_form(egg, self.training, self.lifetime.care_score());
+            log::info!("{} grew up into its {:?} form", self.name, self.form);
+        }
+    }
+
+    /// Stores a pushed reading taken at `now`. Reaching the goal makes the
+    /// pet happier, once per metric on any local day `today`.
+    pub fn record(
+        &mut self,
+        reading: Reading,
+        now: DateTime<Utc>,
+        today: NaiveDate,
+    ) -> Option<PetEvent> {
+        let dead = self.is_dead();
+        let metric = self
+            .metrics
+            .entry(reading.metric)
+            .or_insert_with(|| Metric {
+                value: reading.value,
+                goal: reading.goal,
+                at: now,
+                rewarded: None,
+            });
+        metric.value = reading.value;
+        metric.goal = reading.goal;
+        metric.at = now;
+        let reached = metric.goal.is_some_and(|goal| metric.value >= goal);
+        if !reached || metric.rewarded == Some(today) || dead {
+            return None;
+        }
+        metric.rewarded = Some(today);
+        self.happiness = clamp_stat(self.happiness + GOAL_AMOUNT);
+        Some(PetEvent::GoalReached)
+    }
+
+    /// Starts a bout of misbehaviour, which the owner has a short window to
+    /// respond to with [`Action::Discipline`].
+    pub fn misbehave(&mut self, now: DateTime<Utc>) -> PetEvent {
+        self.misbehaving_until = Some(now + MISBEHAVIOR_WINDOW);
+        PetEvent::Misbehaved
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.died.is_some()
+    }
+
+    pub fn is_misbehaving(&self) -> bool {
+        self.misbehaving_until.is_some()
+    }
+
+    pub fn is_sick(&self) -> bool {
+        self.sickness >= SICK_THRESHOLD
+    }
+
+    fn decay(&mut self, to: DateTime<Utc>, asleep: bool) {
+        let hours = (to - self.last_tick).num_seconds() as f32 / 3600.0;
+        self.last_tick = to;
+        if self.hunger > HUNGER_DECAY * hours {
+            self.starving_hours = 0.0;
+        } else {
+            self.starving_hours += hours - self.hunger / HUNGER_DECAY;
+        }
+        if self.starving_hours >= STARVATION_HOURS && self.died.is_none() {
+            log::info!("{} starved to death", self.name);
+            self.died = Some(to);
+        }
+        self.hunger = clamp_stat(self.hunger - HUNGER_DECAY * hours);
+        self.happiness = clamp_stat(self.happiness - HAPPINESS_DECAY * hours);
+        let energy_rate = match asleep {
+            true => sleep_regen(self.sickness),
+            false => -ENERGY_DECAY,
+        };
+        self.energy = clamp_stat(self.energy + energy_rate * hours);
+
+        let was_sick = self.is_sick();
+        let sickness_rate = match self.messes {
+            0 => -SICKNESS_RECOVERY,
+            messes => MESS_SICKNESS * messes as f32,
+        };
+        self.sickness = clamp_stat(self.sickness + sickness_rate * hours);
+        if was_sick && !self.is_sick() && self.died.is_none() {
+            log::info!("{} got better", self.name);
+            self.lifetime.sicknesses += 1;
+        } else if !was_sick && self.is_sick() {
+            log::info!("{} fell sick", self.name);
+        }
+
+        self.mess_progress += hours;
+        while self.mess_progress >= HOURS_PER_MESS {
+            self.mess_progress -= HOURS_PER_MESS;
+            self.messes = (self.messes + 1).min(MAX_MESSES);
+        }
+    }
+
+    fn daily_check(&mut self) {
+        let well = [self.hunger, self.happiness, self.energy]
+            .iter()
+            .all(|&stat| stat > STREAK_THRESHOLD)
+            && !self.is_sick();
+        let life = &mut self.lifetime;
+        life.checks += 1;
+        if well {
+            life.good_checks += 1;
+            life.streak += 1;
+            life.longest_streak = life.longest_streak.max(life.streak);
+        } else {
+            life.streak = 0;
+        }
+    }
+}
+
+/// The stats an action moves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub hunger: f32,
+    pub happiness: f32,
+    pub energy: f32,
+}
+
+/// What an action works on: the stats it moves, and what else decides
+/// how they move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Care {
+    pub stats: Stats,
+    pub training: f32,
+    pub messes: u8,
+    /// Whether the pet is misbehaving, which decides what discipline does.
+    pub misbehaving: bool,
+    /// Share of a full petting's happiness still to be had this hour.
+    pub petting: f32,
+}
+
+/// What an action came to, for the rest of the daemon to act on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reaction {
+    pub event: PetEvent,
+    pub effects: Vec<SideEffect>,
+}
+
+/// Something an action sets off besides changing the pet.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SideEffect {
+    /// How many of a kind of particle float up from the pet.
+    Particles(ParticleKind, u32),
+    /// A line for the log, about the pet.
+    Log(String),
+}
+
+/// What an action does, for each way it can turn out: discipline goes
+/// one way while the pet is misbehaving and another when it isn't.
+struct Effect {
+    event: PetEvent,
+    hunger: f32,
+    happiness: f32,
+    energy: f32,
+    training: f32,
+    /// Whether the messes are cleared away.
+    cleans: bool,
+    particles: Option<(ParticleKind, u32)>,
+}
+
+/// Every action's effect in one place. On top of this, food does less
+/// for a pet that's worn out, and petting goes down through the hour; see
+/// [`apply_action`].
+const EFFECTS: [Effect; 6] = [
+    Effect {
+        event: PetEvent::Fed,
+        hunger: 25.0,
+        happiness: 0.0,
+        energy: 0.0,
+        training: 0.0,
+        cleans: false,
+        particles: Some((ParticleKind::Heart, 3)),
+    },
+    // A game is fun but tiring, and works up an appetite.
+    Effect {
+        event: PetEvent::Played,
+        hunger: -5.0,
+        happiness: 20.0,
+        energy: -10.0,
+        training: 0.0,
+        cleans: false,
+        particles: Some((ParticleKind::Note, 2)),
+    },
+    Effect {
+        event: PetEvent::Petted,
+        hunger: 0.0,
+        happiness: PET_AMOUNT,
+        energy: 0.0,
+        training: 0.0,
+        cleans: false,
+        particles: Some((ParticleKind::Heart, 3)),
+    },
+    Effect {
+        event: PetEvent::Disciplined,
+        hunger: 0.0,
+        happiness: 0.0,
+        energy: 0.0,
+        training: TRAINING_GAIN,
+        cleans: false,
+        particles: Some((ParticleKind::Note, 1)),
+    },
+    Effect {
+        event: PetEvent::Scolded,
+        hunger: 0.0,
+        happiness: -UNFAIR_DISCIPLINE_COST,
+        energy: 0.0,
+        training: 0.0,
+        cleans: false,
+        particles: Some((ParticleKind::Sweat, 2)),
+    },
+    Effect {
+        event: PetEvent::Cleaned,
+        hunger: 0.0,
+        happiness: 0.0,
+        energy: 0.0,
+        training: 0.0,
+        cleans: true,
+        particles: None,
+    },
+];
+
+/// `care` after `action`, with the stats kept within 0 and [`STAT_MAX`],
+/// and what else it sets off.
+pub fn apply_action(action: Action, care: Care) -> (Care, Reaction) {
+    let event = match action {
+        Action::Feed => PetEvent::Fed,
+        Action::Play => PetEvent::Played,
+        Action::Pet => PetEvent::Petted,
+        Action::Discipline if care.misbehaving => PetEvent::Disciplined,
+        Action::Discipline => PetEvent::Scolded,
+        Action::Clean => PetEvent::Cleaned,
+    };
+    let effect = EFFECTS
+        .iter()
+        .find(|effect| effect.event == event)
+        .expect("every event an action comes to has an effect");
+    let mut effects: Vec<_> = effect
+        .particles
+        .map(|(kind, count)| SideEffect::Particles(kind, count))
+        .into_iter()
+        .collect();
+    let mut log = |line: String| effects.push(SideEffect::Log(line));
+
+    let mut hunger = effect.hunger;
+    if hunger > 0.0 && care.stats.energy < TIRED_ENERGY {
+        hunger *= TIRED_FEED_SHARE;
+        log("too tired to eat properly".into());
+    }
+    let mut happiness = effect.happiness;
+    if event == PetEvent::Petted {
+        happiness *= care.petting;
+        if care.petting == 0.0 {
+            log("has had all the petting it wants this hour".into());
+        }
+    }
+    match event {
+        PetEvent::Disciplined => log("was told off in time and learnt from it".into()),
+        PetEvent::Scolded => log("was told off for nothing".into()),
+        _ => {}
+    }
+    if effect.cleans && care.messes > 0 {
+        log(match care.messes {
+            1 => "had a mess cleaned up".into(),
+            messes => format!("had {messes} messes cleaned up"),
+        });
+    }
+
+    let after = Care {
+        stats: Stats {
+            hunger: clamp_stat(care.stats.hunger + hunger),
+            happiness: clamp_stat(care.stats.happiness + happiness),
+            energy: clamp_stat(care.stats.energy + effect.energy),
+        },
+        training: clamp_stat(care.training + effect.training),
+        messes: if effect.cleans { 0 } else { care.messes },
+        misbehaving: care.misbehaving && event != PetEvent::Disciplined,
+        petting: care.petting,
+    };
+    (after, Reaction { event, effects })
+}
+
+impl Pet {
+    pub fn stats(&self) -> Stats {
+        Stats {
+            hunger: self.hunger,
+            happiness: self.happiness,
+            energy: self.energy,
+        }
+    }
+
+    /// What an action at `now` would work on.
+    pub fn care(&self, now: DateTime<Utc>) -> Care {
+        Care {
+            stats: self.stats(),
+            training: self.training,
+            messes: self.messes,
+            misbehaving: self.is_misbehaving(),
+            petting: self.petting.share(now),
+        }
+    }
+
+    /// Applies something the owner did and reports what it meant to the
+    /// pet.
+    pub fn apply(&mut self, action: Action) -> Reaction {
+        // Only ever applied right after a tick, so `last_tick` is now and
+        // an expired misbehaviour window has already been cleared.
+        let now = self.last_tick;
+        let before = self.care(now);
+        let (after, reaction) = apply_action(action, before);
+        let Stats {
+            hunger,
+            happiness,
+            energy,
+        } = after.stats;
+        (self.hunger, self.happiness, self.energy) = (hunger, happiness, energy);
+        (self.training, self.messes) = (after.training, after.messes);
+        if !after.misbehaving {
+            self.misbehaving_until = None;
+        }
+        let life = &mut self.lifetime;
+        match reaction.event {
+            PetEvent::Fed => life.feedings += 1,
+            PetEvent::Played => life.games += 1,
+            PetEvent::Petted => {
+                life.pettings += 1;
+                self.petting.record(now, before.petting);
+            }
+            PetEvent::Cleaned => life.messes_cleaned += before.messes as u32,
+            _ => {}
+        }
+        reaction
+    }
+}
+
+/// The pet's most pressing need, if it has one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mood {
+    Hungry,
+    Dirty,
+    Sleepy,
+    Bored,
+    Content,
+}
+
+impl Mood {
+    pub fn label(self) -> &'static str {
+        match self {
+            Mood::Hungry => "hungry",
+            Mood::Dirty => "dirty",
+            Mood::Sleepy => "sleepy",
+            Mood::Bored => "bored",
+            Mood::Content => "content",
+        }
+    }
+}
+
+impl Pet {
+    pub fn mood(&self) -> Mood {
+        if self.hunger < 30.0 {
+            Mood::Hungry
+        } else if self.messes >= 2 {
+            Mood::Dirty
+        } else if self.energy < 30.0 {
+            Mood::Sleepy
+        } else if self.happiness < 30.0 {
+            Mood::Bored
+        } else {
+            Mood::Content
+        }
+    }
+}
+
+/// Energy gained per hour asleep at `sickness`: a sick pet sleeps badly,
+/// and one as ill as can be doesn't get its energy back at all.
+fn sleep_regen(sickness: f32) -> f32 {
+    ENERGY_REGEN * (1.0 - sickness / STAT_MAX)
+}
+
+fn clamp_stat(value: f32) -> f32 {
+    value.clamp(0.0, STAT_MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Zone;
+
+    fn at(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    /// Bucharest, where the default 20:00 check is 17:00 UTC in summer.
+    fn clock() -> Clock {
+        Clock::frozen(
+            Zone::Named(chrono_tz::Europe::Bucharest),
+            at("2024-05-01T00:00:00Z"),
+        )
+    }
+
+    fn tick(pet: &mut Pet, now: &str) {
+        pet.tick(at(now), &clock(), &PetConfig::default(), false);
+    }
+
+    /// Keeps everything topped up, hour by hour, from the last tick to `to`.
+    fn look_after(pet: &mut Pet, to: &str) {
+        let to = at(to);
+        while pet.last_tick < to {
+            let next = (pet.last_tick + Duration::hours(1)).min(to);
+            pet.tick(next, &clock(), &PetConfig::default(), false);
+            (pet.hunger, pet.happiness, pet.energy) = (STAT_MAX, STAT_MAX, STAT_MAX);
+            pet.apply(Action::Clean);
+        }
+    }
+
+    #[test]
+    fn streak_counts_each_check_once() {
+        let mut pet = Pet::new("Tester", at("2024-05-01T06:00:00Z"));
+        look_after(&mut pet, "2024-05-01T16:59:00Z");
+        assert_eq!(pet.lifetime.checks, 0);
+        look_after(&mut pet, "2024-05-01T17:00:00Z");
+        assert_eq!((pet.lifetime.checks, pet.lifetime.streak), (1, 1));
+        look_after(&mut pet, "2024-05-03T18:00:00Z");
+        assert_eq!((pet.lifetime.checks, pet.lifetime.streak), (3, 3));
+        assert_eq!(pet.lifetime.care_score(), Some(100));
+        // Going over the same time again doesn't count it twice.
+        tick(&mut pet, "2024-05-03T18:00:00Z");
+        assert_eq!(pet.lifetime.checks, 3);
+    }
+
+    #[test]
+    fn a_bad_day_ends_the_streak_but_not_the_record() {
+        let mut pet = Pet::new("Tester", at("2024-05-01T06:00:00Z"));
+        look_after(&mut pet, "2024-05-02T16:00:00Z");
+        pet.happiness = 20.0;
+        tick(&mut pet, "2024-05-02T17:00:00Z");
+        assert_eq!(pet.lifetime.streak, 0);
+        assert_eq!(pet.lifetime.longest_streak, 1);
+        look_after(&mut pet, "2024-05-04T17:00:00Z");
+        assert_eq!(pet.lifetime.streak, 2);
+        assert_eq!(pet.lifetime.longest_streak, 2);
+        assert_eq!(pet.lifetime.care_score(), Some(75));
+    }
+
+    #[test]
+    fn a_gap_runs_each_check_on_the_stats_of_the_time() {
+        // Full at the first check, starving by the second, both passed in
+        // one step.
+        let mut pet = Pet::new("Tester", at("2024-05-01T16:00:00Z"));
+        tick(&mut pet, "2024-05-03T12:00:00Z");
+        assert_eq!(pet.lifetime.checks, 2);
+        assert_eq!(pet.lifetime.good_checks, 1);
+        assert_eq!(pet.lifetime.longest_streak, 1);
+        assert_eq!(pet.lifetime.streak, 0);
+    }
+
+    #[test]
+    fn checks_beyond_the_offline_cap_are_skipped() {
+        let mut pet = Pet::new("Tester", at("2024-05-01T18:00:00Z"));
+        tick(&mut pet, "2024-05-06T18:00:00Z");
+        // Only the last 48 hours are aged through, with a check on each
+        // of the two days.
+        assert_eq!(pet.lifetime.checks, 2);
+        assert!(!pet.is_dead());
+        assert_eq!(pet.hunger, 0.0);
+    }
+
+    #[test]
+    fn a_clock_running_back_does_not_repeat_checks() {
+        let mut pet = Pet::new("Tester", at("2024-05-01T06:00:00Z"));
+        look_after(&mut pet, "2024-05-01T18:00:00Z");
+        tick(&mut pet, "2024-05-01T10:00:00Z");
+        assert_eq!(pet.lifetime.checks, 1);
+        look_after(&mut pet, "2024-05-01T18:00:00Z");
+        assert_eq!(pet.lifetime.checks, 1);
+        look_after(&mut pet, "2024-05-02T17:00:00Z");
+        assert_eq!(pet.lifetime.checks, 2);
+    }
+
+    #[test]
+    fn mess_makes_the_pet_sick_and_breaks_the_streak() {
+        let mut pet = Pet::new("Tester", at("2024-05-01T06:00:00Z"));
+        look_after(&mut pet, "2024-05-01T17:00:00Z");
+        assert_eq!(pet.lifetime.streak, 1);
+        // Fed and happy but never cleaned up after.
+        let mut now = pet.last_tick;
+        while !pet.is_sick() {
+            now += Duration::hours(1);
+            pet.tick(now, &clock(), &PetConfig::default(), false);
+            (pet.hunger, pet.happiness, pet.energy) = (STAT_MAX, STAT_MAX, STAT_MAX);
+        }
+        assert!(now < at("2024-05-02T17:00:00Z"), "sick by {now}");
+        tick(&mut pet, "2024-05-02T17:00:00Z");
+        assert_eq!(pet.lifetime.streak, 0);
+        assert_eq!(pet.lifetime.sicknesses, 0);
+        // Cleaned up after, it gets better.
+        look_after(&mut pet, "2024-05-03T17:00:00Z");
+        assert!(!pet.is_sick());
+        assert_eq!(pet.lifetime.sicknesses, 1);
+        assert_eq!(pet.lifetime.streak, 1);
+    }
+
+    #[test]
+    fn adult_form_depends_on_egg_training_and_care() {
+        for (egg, training, care, form) in [
+            (Egg::Plain, 60.0, Some(50), Form::Noble),
+            (Egg::Plain, 59.0, Some(100), Form::Wild),
+            (Egg::Plain, 100.0, Some(49), Form::Wild),
+            (Egg::Plain, 100.0, None, Form::Wild),
+            (Egg::Speckled, 45.0, Some(50), Form::Noble),
+            (Egg::Speckled, 44.0, Some(50), Form::Wild),
+            (Egg::Ember, 74.0, Some(100), Form::Wild),
+            (Egg::Ember, 75.0, Some(100), Form::Noble),
+        ] {
+            assert_eq!(
+                adult_form(egg, training, care),
+                form,
+                "{egg:?} {training} {care:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn disciplining_in_time_trains_the_pet() {
+        let mut pet = Pet::new("Tester", at("2024-05-01T06:00:00Z"));
+        assert_eq!(pet.misbehave(pet.last_tick), PetEvent::Misbehaved);
+        tick(&mut pet, "2024-05-01T06:10:00Z");
+        assert!(pet.is_misbehaving());
+        assert_eq!(pet.apply(Action::Discipline).event, PetEvent::Disciplined);
+        assert!(!pet.is_misbehaving());
+        assert_eq!(pet.training, 50.0 + TRAINING_GAIN);
+    }
+
+    #[test]
+    fn misbehaviour_let_slide_costs_training() {
+        let mut pet = Pet::new("Tester", at("2024-05-01T06:00:00Z"));
+        pet.misbehave(pet.last_tick);
+        tick(&mut pet, "2024-05-01T06:15:00Z");
+        assert!(!pet.is_misbehaving());
+        assert_eq!(pet.training, 50.0 - TRAINING_LOSS);
+        // Too late now: it's told off for nothing.
+        let happiness = pet.happiness;
+        assert_eq!(pet.apply(Action::Discipline).event, PetEvent::Scolded);
+        assert_eq!(pet.happiness, happiness - UNFAIR_DISCIPLINE_COST);
+        assert_eq!(pet.training, 50.0 - TRAINING_LOSS);
+    }
+
+    #[test]
+    fn grows_up_once_and_keeps_its_form() {
+        let mut pet = Pet::new("Tester", at("2024-05-01T06:00:00Z"));
+        pet.egg = Some(Egg::Speckled);
+        pet.training = 80.0;
+        look_after(&mut pet, "2024-05-04T05:00:00Z");
+        assert_eq!(pet.form, Form::Baby);
+        look_after(&mut pet, "2024-05-04T06:00:00Z");
+        assert_eq!(pet.form, Form::Noble);
+        // Later upbringing doesn't change it, and it's saved.
+        pet.training = 0.0;
+        look_after(&mut pet, "2024-05-06T06:00:00Z");
+        let saved: Pet = serde_json::from_str(&serde_json::to_string(&pet).unwrap()).unwrap();
+        assert_eq!(saved.form, Form::Noble);
+    }
+
+    #[test]
+    fn neglect_grows_up_wild() {
+        let mut pet = Pet::new("Tester", at("2024-05-01T06:00:00Z"));
+        pet.egg = Some(Egg::Speckled);
+        pet.training = 80.0;
+        pet.hunger = 10.0;
+        // Topped up, but only ever straight after the daily check.
+        let mut day = at("2024-05-01T17:30:00Z");
+        while day < at("2024-05-04T06:00:00Z") {
+            pet.tick(day, &clock(), &PetConfig::default(), false);
+            (pet.hunger, pet.happiness, pet.energy) = (STAT_MAX, STAT_MAX, STAT_MAX);
+            day += Duration::hours(24);
+        }
+        tick(&mut pet, "2024-05-04T06:00:00Z");
+        assert_eq!(pet.lifetime.care_score(), Some(0));
+        assert_eq!(pet.form, Form::Wild);
+    }
+
+    /// The happiness a petting at `now` is worth, noting it down.
+    fn boost(hour: &mut PettingHour, now: DateTime<Utc>) -> f32 {
+        let share = hour.share(now);
+        hour.record(now, share);
+        share * PET_AMOUNT
+    }
+
+    #[test]
+    fn petting_is_capped_per_hour() {
+        let mut hour = PettingHour::default();
+        let start = at("2024-05-01T06:00:00Z");
+        let boosts: Vec<f32> = (0..6)
+            .map(|i| boost(&mut hour, start + Duration::minutes(i)))
+            .collect();
+        assert_eq!(boosts, [5.0, 2.5, 0.5, 0.0, 0.0, 0.0]);
+        assert_eq!(boosts.iter().sum::<f32>(), PETTING_HOURLY_CAP);
+        // The hour runs from the first petting in it.
+        assert_eq!(boost(&mut hour, start + Duration::minutes(59)), 0.0);
+        assert_eq!(boost(&mut hour, start + Duration::minutes(60)), PET_AMOUNT);
+        assert_eq!(boost(&mut hour, start + Duration::minutes(61)), 2.5);
+    }
+
+    #[test]
+    fn petting_raises_happiness_up_to_the_cap() {
+        let mut pet = Pet::new("Tester", at("2024-05-01T06:00:00Z"));
+        pet.happiness = 50.0;
+        for _ in 0..10 {
+            assert_eq!(pet.apply(Action::Pet).event, PetEvent::Petted);
+        }
+        assert_eq!(pet.happiness, 50.0 + PETTING_HOURLY_CAP);
+        assert_eq!(pet.lifetime.pettings, 10);
+    }
+
+    /// Care with these stats and everything else at its plainest.
+    fn care(hunger: f32, happiness: f32, energy: f32) -> Care {
+        Care {
+            stats: Stats {
+                hunger,
+                happiness,
+                energy,
+            },
+            training: 50.0,
+            messes: 0,
+            misbehaving: false,
+            petting: 1.0,
+        }
+    }
+
+    fn log(line: &str) -> SideEffect {
+        SideEffect::Log(line.into())
+    }
+
+    #[test]
+    fn actions_follow_the_effect_matrix() {
+        use ParticleKind::{Heart, Note, Sweat};
+        use SideEffect::Particles;
+        let misbehaving = Care {
+            misbehaving: true,
+            ..care(50.0, 50.0, 50.0)
+        };
+        let messy = Care {
+            messes: 3,
+            ..care(50.0, 50.0, 50.0)
+        };
+        let petted_out = Care {
+            petting: 0.0,
+            ..care(50.0, 50.0, 50.0)
+        };
+        let half_petted = Care {
+            petting: 0.5,
+            ..care(50.0, 50.0, 50.0)
+        };
+        #[rustfmt::skip]
+        let cases = [
+            // Action, before, after, event, side effects.
+            (Action::Feed, care(50.0, 50.0, 50.0), care(75.0, 50.0, 50.0),
+                PetEvent::Fed, vec![Particles(Heart, 3)]),
+            (Action::Feed, care(50.0, 50.0, 10.0), care(62.5, 50.0, 10.0),
+                PetEvent::Fed, vec![Particles(Heart, 3), log("too tired to eat properly")]),
+            (Action::Play, care(50.0, 50.0, 50.0), care(45.0, 70.0, 40.0),
+                PetEvent::Played, vec![Particles(Note, 2)]),
+            (Action::Pet, care(50.0, 50.0, 50.0), care(50.0, 55.0, 50.0),
+                PetEvent::Petted, vec![Particles(Heart, 3)]),
+            (Action::Pet, half_petted, Care { stats: care(50.0, 52.5, 50.0).stats, ..half_petted },
+                PetEvent::Petted, vec![Particles(Heart, 3)]),
+            (Action::Pet, petted_out, petted_out, PetEvent::Petted,
+                vec![Particles(Heart, 3), log("has had all the petting it wants this hour")]),
+            (Action::Discipline, misbehaving, Care { training: 65.0, ..care(50.0, 50.0, 50.0) },
+                PetEvent::Disciplined,
+                vec![Particles(Note, 1), log("was told off in time and learnt from it")]),
+            (Action::Discipline, care(50.0, 50.0, 50.0), care(50.0, 40.0, 50.0),
+                PetEvent::Scolded, vec![Particles(Sweat, 2), log("was told off for nothing")]),
+            (Action::Clean, messy, care(50.0, 50.0, 50.0),
+                PetEvent::Cleaned, vec![log("had 3 messes cleaned up")]),
+            (Action::Clean, care(50.0, 50.0, 50.0), care(50.0, 50.0, 50.0),
+                PetEvent::Cleaned, vec![]),
+        ];
+        for (action, before, after, event, effects) in cases {
+            let (got, reaction) = apply_action(action, before);
+            assert_eq!(got, after, "{action:?} on {before:?}");
+            assert_eq!(
+                reaction,
+                Reaction { event, effects },
+                "{action:?} on {before:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn actions_keep_the_stats_within_0_and_100() {
+        let high = Care {
+            training: 95.0,
+            misbehaving: true,
+            ..care(90.0, 95.0, 100.0)
+        };
+        let low = care(2.0, 5.0, 4.0);
+        #[rustfmt::skip]
+        let cases = [
+            (Action::Feed, high, (100.0, 95.0, 100.0, 95.0)),
+            (Action::Play, high, (85.0, 100.0, 90.0, 95.0)),
+            (Action::Pet, high, (90.0, 100.0, 100.0, 95.0)),
+            (Action::Discipline, high, (90.0, 95.0, 100.0, 100.0)),
+            (Action::Play, low, (0.0, 25.0, 0.0, 50.0)),
+            (Action::Discipline, low, (2.0, 0.0, 4.0, 50.0)),
+        ];
+        for (action, before, (hunger, happiness, energy, training)) in cases {
+            let (after, _) = apply_action(action, before);
+            let Stats {
+                hunger: h,
+                happiness: p,
+                energy: e,
+            } = after.stats;
+            assert_eq!(
+                (h, p, e, after.training),
+                (hunger, happiness, energy, training),
+                "{action:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn every_action_has_a_row() {
+        for action in [Action::Feed, Action::Play, Action::Pet, Action::Clean] {
+            apply_action(action, care(50.0, 50.0, 50.0));
+        }
+        for misbehaving in [false, true] {
+            apply_action(
+                Action::Discipline,
+                Care {
+                    misbehaving,
+                    ..care(50.0, 50.0, 50.0)
+                },
+            );
+        }
+        // And no event has two.
+        for (i, effect) in EFFECTS.iter().enumerate() {
+            assert!(
+                EFFECTS[i + 1..]
+                    .iter()
+                    .all(|other| other.event != effect.event),
+                "{:?}",
+                effect.event
+            );
+        }
+    }
+
+    #[test]
+    fn sickness_slows_sleep_down() {
+        assert_eq!(sleep_regen(0.0), ENERGY_REGEN);
+        assert_eq!(sleep_regen(SICK_THRESHOLD), ENERGY_REGEN / 2.0);
+        assert_eq!(sleep_regen(STAT_MAX), 0.0);
+
+        let night = |sickness: f32| {
+            let mut pet = Pet::new("Tester", at("2024-05-01T20:00:00Z"));
+            (pet.energy, pet.sickness) = (20.0, sickness);
+            pet.tick(
+                at("2024-05-01T22:00:00Z"),
+                &clock(),
+                &PetConfig::default(),
+                true,
+            );
+            pet.energy
+        };
+        assert_eq!(night(0.0), 40.0);
+        assert_eq!(night(60.0), 28.0);
+        assert_eq!(night(STAT_MAX), 20.0);
+    }
+
+    #[test]
+    fn applying_an_action_keeps_the_books() {
+        let mut pet = Pet::new("Tester", at("2024-05-01T06:00:00Z"));
+        pet.messes = 2;
+        pet.apply(Action::Clean);
+        assert_eq!((pet.messes, pet.lifetime.messes_cleaned), (0, 2));
+        pet.apply(Action::Feed);
+        pet.apply(Action::Play);
+        assert_eq!((pet.lifetime.feedings, pet.lifetime.games), (1, 1));
+    }
+
+    fn steps(value: f64, goal: Option<f64>) -> Reading {
+        Reading {
+            metric: "steps".into(),
+            value,
+            goal,
+        }
+    }
+
+    #[test]
+    fn reaching_a_goal_cheers_the_pet_once_a_day() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pet.json");
+        let mut pet = Pet::new("Tester", at("2024-05-01T06:00:00Z"));
+        pet.happiness = 50.0;
+        let record = |pet: &mut Pet, reading, now: &str| {
+            let now = at(now);
+            pet.record(reading, now, now.date_naive())
+        };
+        let goal = Some(10_000.0);
+        assert_eq!(
+            record(&mut pet, steps(8421.0, goal), "2024-05-01T10:00:00Z"),
+            None
+        );
+        assert_eq!(pet.happiness, 50.0);
+        assert_eq!(
+            record(&mut pet, steps(10_000.0, goal), "2024-05-01T12:00:00Z"),
+            Some(PetEvent::GoalReached)
+        );
+        assert_eq!(pet.happiness, 50.0 + GOAL_AMOUNT);
+        assert_eq!(
+            record(&mut pet, steps(12_000.0, goal), "2024-05-01T15:00:00Z"),
+            None
+        );
+        // Without a goal there's nothing to reach.
+        assert_eq!(
+            record(&mut pet, steps(20_000.0, None), "2024-05-01T16:00:00Z"),
+            None
+        );
+
+        // Still rewarded for today after a restart.
+        pet.save(&path).unwrap();
+        let mut pet = Pet::load(&path).unwrap();
+        assert_eq!(pet.metrics["steps"].value, 20_000.0);
+        assert_eq!(
+            record(&mut pet, steps(13_000.0, goal), "2024-05-01T20:00:00Z"),
+            None
+        );
+        assert_eq!(pet.happiness, 50.0 + GOAL_AMOUNT);
+
+        // Other metrics have goals of their own.
+        let water = Reading {
+            metric: "water".into(),
+            value: 2.0,
+            goal: Some(2.0),
+        };
+        assert_eq!(
+            record(&mut pet, water, "2024-05-01T21:00:00Z"),
+            Some(PetEvent::GoalReached)
+        );
+        assert_eq!(
+            record(&mut pet, steps(10_500.0, goal), "2024-05-02T09:00:00Z"),
+            Some(PetEvent::GoalReached)
+        );
+        assert_eq!(pet.happiness, 50.0 + 3.0 * GOAL_AMOUNT);
+    }
+}
@@ -0,0 +1,57 @@
+//! The screen the carousel is pinned to, kept in a small file so the pin
+//! holds across a restart.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::persist;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pin {
+    pub screen: String,
+    /// When the carousel moves on by itself; never, without one.
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Pin {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_some_and(|until| now >= until)
+    }
+
+    /// The pin saved at `path`, if there is one. An unreadable file is
+    /// treated as no pin.
+    pub fn load(path: &Path) -> Option<Pin> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(err) => {
+                log::warn!("reading {}: {err}", path.display());
+                return None;
+            }
+        };
+        match serde_json::from_str(&text) {
+            Ok(pin) => Some(pin),
+            Err(err) => {
+                log::warn!("ignoring unreadable pin file {}: {err}", path.display());
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        persist::write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    pub fn remove(path: &Path) {
+        if let Err(err) = fs::remove_file(path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("removing {}: {err}", path.display());
+            }
+        }
+    }
+}
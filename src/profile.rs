@@ -0,0 +1,302 @@
+//! Where the time in a cycle goes. The loop runs in fixed stages, each
+//! timed into a [`Trace`]: logged at debug every cycle, added to a
+//! histogram per stage that `GET /metrics` serves in Prometheus' text
+//! format, and printed as a table by `--profile-cycles`.
+//!
+//! The histograms live in a static, like [`crate::logbuf`]'s lines, so the
+//! HTTP listener can read them without the loop handing anything over.
+//! Next to them is a count, by screen, of refreshes the panel's refresh
+//! guard held back, to show which one keeps asking too often, the
+//! starts and stops of each collector source, and the render loop's
+//! heartbeat as [`crate::watchdog`] sees it.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Reading the collector's snapshot.
+    Collect,
+    /// Ticking the pet and handling commands and events.
+    Tick,
+    /// Drawing the screen and its decorations.
+    Compose,
+    /// Working out what has to change on the panel.
+    Diff,
+    /// Sending the image to the panel.
+    Transfer,
+    /// Waiting for the panel to finish refreshing. The simulator is done
+    /// as soon as the PNG is written, so this is always zero for now.
+    WaveformWait,
+    /// Saving the pet and writing the status file.
+    Persist,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 7] = [
+        Stage::Collect,
+        Stage::Tick,
+        Stage::Compose,
+        Stage::Diff,
+        Stage::Transfer,
+        Stage::WaveformWait,
+        Stage::Persist,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Collect => "collect_snapshot",
+            Stage::Tick => "tick",
+            Stage::Compose => "compose",
+            Stage::Diff => "diff",
+            Stage::Transfer => "transfer",
+            Stage::WaveformWait => "waveform_wait",
+            Stage::Persist => "persist",
+        }
+    }
+}
+
+/// How long each stage of one cycle took. A stage that didn't run, like
+/// the transfer when nothing changed, took no time.
+#[derive(Clone, Copy, Debug)]
+pub struct Trace {
+    times: [Duration; Stage::ALL.len()],
+    /// When the stage being timed started.
+    mark: Instant,
+}
+
+impl Trace {
+    pub fn start() -> Self {
+        Trace {
+            times: [Duration::ZERO; Stage::ALL.len()],
+            mark: Instant::now(),
+        }
+    }
+
+    /// Puts the time since the last lap down to `stage`.
+    pub fn lap(&mut self, stage: Stage) {
+        let now = Instant::now();
+        self.times[stage as usize] += now - self.mark;
+        self.mark = now;
+    }
+
+    pub fn get(&self, stage: Stage) -> Duration {
+        self.times[stage as usize]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.times.iter().sum()
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for stage in Stage::ALL {
+            write!(f, "{} {:.1}ms, ", stage.name(), millis(self.get(stage)))?;
+        }
+        write!(f, "total {:.1}ms", millis(self.total()))
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Upper bounds of the histogram buckets, in seconds: from a quick draw
+/// to a full refresh of a tri-colour panel, which takes about 15s.
+const BUCKETS: [f64; 10] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.25, 1.0, 5.0, 15.0, 30.0];
+
+#[derive(Clone, Copy)]
+struct Histogram {
+    /// Observations at or under each bound, not cumulative yet.
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+const EMPTY: Histogram = Histogram {
+    buckets: [0; BUCKETS.len()],
+    count: 0,
+    sum: 0.0,
+};
+
+static HISTOGRAMS: Mutex<[Histogram; Stage::ALL.len()]> = Mutex::new([EMPTY; Stage::ALL.len()]);
+static COALESCED: Mutex<BTreeMap<&'static str, u64>> = Mutex::new(BTreeMap::new());
+/// Starts and stops by source.
+static SOURCES: Mutex<BTreeMap<&'static str, (u64, u64)>> = Mutex::new(BTreeMap::new());
+static HEARTBEAT: Mutex<Option<Heartbeat>> = Mutex::new(None);
+
+#[derive(Clone, Copy)]
+struct Heartbeat {
+    at: Instant,
+    cycles: u64,
+    trips: u32,
+}
+
+/// Counts a refresh of `screen` held back by the refresh guard, see
+/// [`crate::display::Guard`].
+pub fn coalesced(screen: &'static str) {
+    let mut coalesced = COALESCED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *coalesced.entry(screen).or_default() += 1;
+}
+
+/// Counts a start of the collector source `name`, or a stop.
+pub fn source(name: &'static str, started: bool) {
+    let mut sources = SOURCES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let (starts, stops) = sources.entry(name).or_default();
+    *if started { starts } else { stops } += 1;
+}
+
+/// Records the render loop's last beat at `at`, after `cycles` cycles and
+/// `trips` stalls.
+pub fn heartbeat(at: Instant, cycles: u64, trips: u32) {
+    *HEARTBEAT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Heartbeat { at, cycles, trips });
+}
+
+/// Adds a finished cycle to the histograms.
+pub fn observe(trace: &Trace) {
+    let mut histograms = HISTOGRAMS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    for stage in Stage::ALL {
+        let seconds = trace.get(stage).as_secs_f64();
+        let histogram = &mut histograms[stage as usize];
+        if let Some(bucket) = BUCKETS.iter().position(|&bound| seconds <= bound) {
+            histogram.buckets[bucket] += 1;
+        }
+        histogram.count += 1;
+        histogram.sum += seconds;
+    }
+}
+
+/// The histograms, the held back refreshes, the source starts and stops
+/// and the heartbeat in Prometheus' text exposition format.
+pub fn prometheus() -> String {
+    let histograms = *HISTOGRAMS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let name = "inky_gotchi_cycle_stage_seconds";
+    let mut out = format!(
+        "# HELP {name} Time spent in each stage of a display cycle.\n# TYPE {name} histogram\n"
+    );
+    for stage in Stage::ALL {
+        let histogram = &histograms[stage as usize];
+        let label = stage.name();
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(histogram.buckets) {
+            cumulative += count;
+            writeln!(
+                out,
+                "{name}_bucket{{stage=\"{label}\",le=\"{bound}\"}} {cumulative}"
+            )
+            .ok();
+        }
+        writeln!(
+            out,
+            "{name}_bucket{{stage=\"{label}\",le=\"+Inf\"}} {}",
+            histogram.count
+        )
+        .ok();
+        writeln!(out, "{name}_sum{{stage=\"{label}\"}} {}", histogram.sum).ok();
+        writeln!(out, "{name}_count{{stage=\"{label}\"}} {}", histogram.count).ok();
+    }
+    let coalesced = COALESCED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let name = "inky_gotchi_refreshes_coalesced_total";
+    write!(
+        out,
+        "# HELP {name} Refreshes held back for coming too soon after the last.\n# TYPE {name} counter\n"
+    )
+    .ok();
+    for (screen, count) in coalesced.iter() {
+        writeln!(out, "{name}{{screen=\"{screen}\"}} {count}").ok();
+    }
+    let sources = SOURCES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    for (name, help, started) in [
+        (
+            "inky_gotchi_source_starts_total",
+            "Collector source starts.",
+            true,
+        ),
+        (
+            "inky_gotchi_source_stops_total",
+            "Collector source stops.",
+            false,
+        ),
+    ] {
+        write!(out, "# HELP {name} {help}\n# TYPE {name} counter\n").ok();
+        for (source, &(starts, stops)) in sources.iter() {
+            let count = if started { starts } else { stops };
+            writeln!(out, "{name}{{source=\"{source}\"}} {count}").ok();
+        }
+    }
+    // Nothing until the first beat.
+    let heartbeat = *HEARTBEAT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(heartbeat) = heartbeat {
+        let name = "inky_gotchi_render_cycles_total";
+        write!(
+            out,
+            "# HELP {name} Display cycles completed.\n# TYPE {name} counter\n{name} {}\n",
+            heartbeat.cycles
+        )
+        .ok();
+        let name = "inky_gotchi_watchdog_trips_total";
+        write!(
+            out,
+            "# HELP {name} Times the render loop was found stuck.\n# TYPE {name} counter\n{name} {}\n",
+            heartbeat.trips
+        )
+        .ok();
+        let name = "inky_gotchi_heartbeat_age_seconds";
+        write!(
+            out,
+            "# HELP {name} Time since the render loop last completed a cycle.\n# TYPE {name} gauge\n{name} {}\n",
+            heartbeat.at.elapsed().as_secs_f64()
+        )
+        .ok();
+    }
+    out
+}
+
+/// A table of `traces`, for `--profile-cycles`: the median, 95th
+/// percentile and worst time of each stage, in milliseconds.
+pub fn table(traces: &[Trace]) -> String {
+    let mut out = format!(
+        "{:<18}{:>10}{:>10}{:>10}\n",
+        "stage", "median", "p95", "max"
+    );
+    let rows = Stage::ALL
+        .iter()
+        .map(|&stage| (stage.name(), traces.iter().map(|t| t.get(stage)).collect()))
+        .chain([("total", traces.iter().map(Trace::total).collect())]);
+    for (name, mut times) in rows.collect::<Vec<(_, Vec<Duration>)>>() {
+        times.sort();
+        let at = |share: f64| {
+            let index = ((times.len() as f64 * share).ceil() as usize).saturating_sub(1);
+            times.get(index).map_or(0.0, |&d| millis(d))
+        };
+        writeln!(
+            out,
+            "{name:<18}{:>10.2}{:>10.2}{:>10.2}",
+            at(0.5),
+            at(0.95),
+            at(1.0)
+        )
+        .ok();
+    }
+    write!(out, "{} cycles", traces.len()).ok();
+    out
+}
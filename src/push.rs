@@ -0,0 +1,620 @@
+//! A small HTTP endpoint for numbers pushed from elsewhere, e.g. a phone
+//! posting the day's steps:
+//!
+//! ```text
+//! curl -d '{"steps": 8421, "goal": 10000}' http://gotchi.local:8080/push/steps
+//! ```
+//!
+//! Any metric name works. The value goes under the metric's own name and
+//! the goal is optional. Known metrics get tighter bounds in [`LIMITS`].
+//!
+//! The same listener serves the newest log lines as JSON, e.g. the last 50
+//! warnings and errors:
+//!
+//! ```text
+//! curl 'http://gotchi.local:8080/logs?level=warn&n=50'
+//! ```
+//!
+//! a picture of the pet for sharing, see [`crate::card`]:
+//!
+//! ```text
+//! curl -o card.png http://gotchi.local:8080/card.png
+//! ```
+//!
+//! how long each stage of the display cycle takes, for Prometheus to
+//! scrape from `/metrics`, see [`crate::profile`], and takes in a pet from
+//! another device, see [`crate::transfer`]:
+//!
+//! ```text
+//! curl -d 'NC0B1MK...' http://gotchi.local:8080/import
+//! ```
+//!
+//! passes on what's said to the pet, see [`crate::chat`], and moves the
+//! carousel, taking what the control socket's `screen` commands take:
+//!
+//! ```text
+//! curl -d 'pin weather' http://gotchi.local:8080/screen
+//! ```
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context as _, Result};
+use serde_json::{Map, Value};
+
+use crate::card;
+use crate::channel::Outbox;
+use crate::chat::{self, Talk};
+use crate::clock::Clock;
+use crate::config::{LowMemoryConfig, TextConfig};
+use crate::events::{Command, Reading};
+use crate::logbuf;
+use crate::lowmem::Quality;
+use crate::pet::Pet;
+use crate::profile;
+use crate::screens;
+use crate::transfer::Transfer;
+
+/// Believable values for the metrics we know about.
+const LIMITS: &[(&str, RangeInclusive<f64>)] = &[("steps", 0.0..=200_000.0)];
+/// Everything else just has to be sane.
+const DEFAULT_LIMIT: RangeInclusive<f64> = -1e9..=1e9;
+/// Longest metric name, so nobody fills the save file with junk.
+const MAX_NAME: usize = 24;
+/// Requests are a line of JSON; anything bigger isn't one of ours.
+const MAX_BODY: usize = 1024;
+const MAX_HEADERS: usize = 8 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Log lines sent when the request doesn't say.
+const DEFAULT_LOG_LINES: usize = 50;
+
+enum Request {
+    Push(Reading),
+    /// The newest `n` log lines at `level` or worse.
+    Logs(log::LevelFilter, usize),
+    Card,
+    Metrics,
+    Import(Transfer),
+    /// Something to say to the pet.
+    Say(String),
+    /// One of the `screen` commands.
+    Screen(Command),
+}
+
+/// What the card is drawn from.
+pub struct CardSource {
+    pub save_file: PathBuf,
+    pub supersample: bool,
+    pub greyscale: bool,
+    pub low_memory: LowMemoryConfig,
+}
+
+/// What the pet's answers are worked out from.
+pub struct ChatSource {
+    pub save_file: PathBuf,
+    pub clock: Clock,
+    pub text: TextConfig,
+}
+
+/// An HTTP status and a line saying what was wrong.
+#[derive(Debug)]
+struct Reply(u16, String);
+
+impl Reply {
+    fn reason(&self) -> &'static str {
+        match self.0 {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            413 => "Payload Too Large",
+            422 => "Unprocessable Entity",
+            _ => "Service Unavailable",
+        }
+    }
+}
+
+/// Listens on `addr` and forwards each valid push to `tx` from a
+/// background thread.
+pub fn spawn(
+    addr: SocketAddr,
+    tx: impl Outbox,
+    card: CardSource,
+    chat: ChatSource,
+    screens: Vec<String>,
+) -> Result<()> {
+    let listener = TcpListener::bind(addr).with_context(|| format!("binding {addr}"))?;
+    log::info!(
+        "accepting pushed metrics on http://{addr}/push/<metric>, logs on /logs, the card on /card.png, timings on /metrics, pets on /import, talk on /say, screen commands on /screen"
+    );
+    thread::Builder::new().name("push".into()).spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(err) = serve(stream, &tx, &card, &chat, &screens) {
+                        log::warn!("push connection failed: {err}");
+                    }
+                }
+                Err(err) => log::warn!("push accept failed: {err}"),
+            }
+        }
+    })?;
+    Ok(())
+}
+
+/// Handles one request and closes the connection.
+fn serve(
+    stream: TcpStream,
+    tx: &impl Outbox,
+    card: &CardSource,
+    chat: &ChatSource,
+    screens: &[String],
+) -> Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let reply = match read_request(&stream, screens) {
+        Ok(Request::Push(reading)) => {
+            log::info!("push: {} = {}", reading.metric, reading.value);
+            if tx.send(Command::Record(reading)) {
+                Reply(200, "ok".into())
+            } else {
+                Reply(503, "shutting down".into())
+            }
+        }
+        Ok(Request::Logs(level, n)) => {
+            // Not logged itself, so reading the logs doesn't push lines out.
+            let body = serde_json::to_string(&logbuf::recent(level, n))?;
+            return respond(&stream, &Reply(200, body), "application/json");
+        }
+        // Drawn from the last save, which is at most a cycle old.
+        Ok(Request::Card) => match card::png(
+            &card.save_file,
+            chrono::Utc::now(),
+            card.supersample && Quality::measure(&card.low_memory).supersample(),
+            card.greyscale,
+        ) {
+            Ok(png) => return send(&stream, &Reply(200, String::new()), "image/png", &png),
+            Err(err) => {
+                log::warn!("couldn't draw the card: {err:#}");
+                Reply(503, "no pet to draw yet".into())
+            }
+        },
+        Ok(Request::Import(pet)) => {
+            log::info!("push: {} offered", pet.name);
+            let line = format!("{} is on the panel, hold A there to swap", pet.name);
+            if tx.send(Command::Import(pet)) {
+                Reply(200, line)
+            } else {
+                Reply(503, "shutting down".into())
+            }
+        }
+        // Answered from the last save too; the loop shows the same reply.
+        Ok(Request::Say(said)) => match Pet::load(&chat.save_file) {
+            Ok(pet) if pet.is_dead() => Reply(409, format!("{} is gone", pet.name)),
+            Ok(pet) => {
+                let talk = Talk::new(&said, &pet, chat.clock.now(), &chat.text);
+                log::info!("push: told {:?} ({})", said, talk.intent.label());
+                let line = talk.reply.clone();
+                if tx.send(Command::Talk(talk)) {
+                    Reply(200, line)
+                } else {
+                    Reply(503, "shutting down".into())
+                }
+            }
+            Err(err) => {
+                log::warn!("couldn't load the pet to talk to: {err:#}");
+                Reply(503, "no pet to talk to yet".into())
+            }
+        },
+        Ok(Request::Screen(command)) => {
+            log::info!("push: {command:?}");
+            if tx.send(command) {
+                Reply(200, "ok".into())
+            } else {
+                Reply(503, "shutting down".into())
+            }
+        }
+        Ok(Request::Metrics) => {
+            let reply = Reply(200, profile::prometheus());
+            return send(
+                &stream,
+                &reply,
+                "text/plain; version=0.0.4",
+                reply.1.as_bytes(),
+            );
+        }
+        Err(reply) => {
+            log::warn!("request rejected: {} {}", reply.0, reply.1);
+            reply
+        }
+    };
+    respond(&stream, &reply, "text/plain")
+}
+
+fn respond(stream: &TcpStream, reply: &Reply, content_type: &str) -> Result<()> {
+    send(
+        stream,
+        reply,
+        content_type,
+        format!("{}\n", reply.1).as_bytes(),
+    )
+}
+
+/// Sends `reply`'s status with `body` in place of its line.
+fn send(mut stream: &TcpStream, reply: &Reply, content_type: &str, body: &[u8]) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        reply.0,
+        reply.reason(),
+        body.len(),
+    )?;
+    stream.write_all(body)?;
+    Ok(())
+}
+
+fn read_request(stream: impl Read, screens: &[String]) -> Result<Request, Reply> {
+    let bad = |message: &str| Reply(400, message.into());
+    let mut reader = BufReader::new(stream.take((MAX_HEADERS + MAX_BODY) as u64));
+    let mut request = String::new();
+    reader
+        .read_line(&mut request)
+        .map_err(|_| bad("unreadable request"))?;
+    let mut parts = request.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let mut line = String::new();
+    let mut length = 0;
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => return Err(bad("truncated headers")),
+            Ok(_) if line.trim().is_empty() => break,
+            Ok(_) => {}
+            Err(_) => return Err(bad("unreadable headers")),
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| bad("bad Content-Length"))?;
+            }
+        }
+    }
+
+    let (route, query) = path.split_once('?').unwrap_or((path, ""));
+    if route == "/logs" {
+        if method != "GET" {
+            return Err(Reply(405, "only GET is accepted".into()));
+        }
+        return parse_logs_query(query);
+    }
+    if route == "/card.png" {
+        if method != "GET" {
+            return Err(Reply(405, "only GET is accepted".into()));
+        }
+        return Ok(Request::Card);
+    }
+    if route == "/metrics" {
+        if method != "GET" {
+            return Err(Reply(405, "only GET is accepted".into()));
+        }
+        return Ok(Request::Metrics);
+    }
+    let import = route == "/import";
+    let say = route == "/say";
+    let screen = route == "/screen";
+    let metric = route
+        .strip_prefix("/push/")
+        .or((import || say || screen).then_some(""))
+        .ok_or_else(|| {
+            Reply(
+                404,
+                "try POST /push/<metric>, GET /logs, GET /card.png, GET /metrics, POST /import, POST /say or POST /screen"
+                    .into(),
+            )
+        })?;
+    if method != "POST" {
+        return Err(Reply(405, "only POST is accepted".into()));
+    }
+    if length > MAX_BODY {
+        return Err(Reply(413, format!("body over {MAX_BODY} bytes")));
+    }
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|_| bad("truncated body"))?;
+    if import {
+        let code = String::from_utf8_lossy(&body);
+        return Transfer::decode(code.trim_end_matches(['\r', '\n']))
+            .map(Request::Import)
+            .map_err(|err| Reply(422, format!("bad code: {err:#}")));
+    }
+    if say {
+        return parse_say(&body).map(Request::Say);
+    }
+    if screen {
+        return parse_screen(&String::from_utf8_lossy(&body), screens).map(Request::Screen);
+    }
+    parse(metric, &body).map(Request::Push)
+}
+
+/// Reads `{"text": "good morning"}`.
+fn parse_say(body: &[u8]) -> Result<String, Reply> {
+    let body: Map<String, Value> = serde_json::from_slice(body)
+        .map_err(|_| Reply(400, "body must be a JSON object".into()))?;
+    let text = match body.get("text") {
+        Some(Value::String(text)) => text.trim(),
+        Some(_) => return Err(Reply(422, "text must be a string".into())),
+        None => return Err(Reply(422, "missing \"text\"".into())),
+    };
+    if text.is_empty() {
+        return Err(Reply(422, "text is empty".into()));
+    }
+    if text.chars().count() > chat::MAX_TEXT {
+        return Err(Reply(422, format!("text over {} letters", chat::MAX_TEXT)));
+    }
+    Ok(text.to_string())
+}
+
+/// Reads what follows `screen` on the control socket, e.g. `pin clock` or
+/// `next`.
+fn parse_screen(body: &str, screens: &[String]) -> Result<Command, Reply> {
+    let command = match Command::parse(&format!("screen {}", body.trim())) {
+        Some(command) => command,
+        None => {
+            return Err(Reply(
+                422,
+                format!("unknown screen command {:?}", body.trim()),
+            ))
+        }
+    };
+    if let Command::PinScreen(name) | Command::SetScreen(name, true) = &command {
+        screens::check_name(name, screens).map_err(|err| Reply(422, err))?;
+    }
+    Ok(command)
+}
+
+/// Reads `level=warn&n=50`, both optional: all kept lines at any level,
+/// [`DEFAULT_LOG_LINES`] of them.
+fn parse_logs_query(query: &str) -> Result<Request, Reply> {
+    let mut level = log::LevelFilter::Trace;
+    let mut n = DEFAULT_LOG_LINES;
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        match pair.split_once('=').unwrap_or((pair, "")) {
+            ("level", value) => {
+                level = value
+                    .parse()
+                    .map_err(|_| Reply(400, format!("unknown level {value:?}")))?;
+            }
+            ("n", value) => {
+                n = value
+                    .parse()
+                    .map_err(|_| Reply(400, format!("n must be a count, not {value:?}")))?;
+            }
+            (name, _) => return Err(Reply(400, format!("unknown parameter {name:?}"))),
+        }
+    }
+    Ok(Request::Logs(level, n.min(logbuf::CAPACITY)))
+}
+
+/// Checks a push of `metric` with `body`, which has to look like
+/// `{"<metric>": 8421, "goal": 10000}`.
+fn parse(metric: &str, body: &[u8]) -> Result<Reading, Reply> {
+    let invalid = |message: String| Reply(422, message);
+    let valid_name = !metric.is_empty()
+        && metric.len() <= MAX_NAME
+        && metric
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_');
+    if !valid_name {
+        return Err(Reply(
+            404,
+            format!("metric names are up to {MAX_NAME} of a-z, 0-9 and _"),
+        ));
+    }
+    let body: Map<String, Value> = serde_json::from_slice(body)
+        .map_err(|_| Reply(400, "body must be a JSON object".into()))?;
+    let limit = LIMITS
+        .iter()
+        .find(|(name, _)| *name == metric)
+        .map_or(&DEFAULT_LIMIT, |(_, limit)| limit);
+    let number = |field: &str| -> Result<Option<f64>, Reply> {
+        match body.get(field) {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => match value.as_f64() {
+                Some(n) if limit.contains(&n) => Ok(Some(n)),
+                Some(n) => Err(invalid(format!(
+                    "{field} {n} is outside {}..={}",
+                    limit.start(),
+                    limit.end()
+                ))),
+                None => Err(invalid(format!("{field} must be a number"))),
+            },
+        }
+    };
+    let value = number(metric)?.ok_or_else(|| invalid(format!("missing {metric:?}")))?;
+    let goal = number("goal")?;
+    if goal.is_some_and(|goal| goal <= 0.0) {
+        return Err(invalid("goal must be positive".into()));
+    }
+    Ok(Reading {
+        metric: metric.to_string(),
+        value,
+        goal,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(path: &str, body: &str) -> String {
+        format!(
+            "POST {path} HTTP/1.1\r\nHost: gotchi.local\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    /// The reading `raw` pushes, or the status it's turned away with.
+    fn push(raw: &str) -> Result<Reading, u16> {
+        match read_request(raw.as_bytes(), &[]) {
+            Ok(Request::Push(reading)) => Ok(reading),
+            Ok(_) => panic!("{raw:?} isn't a push"),
+            Err(Reply(code, _)) => Err(code),
+        }
+    }
+
+    fn reading(metric: &str, value: f64, goal: Option<f64>) -> Result<Reading, u16> {
+        Ok(Reading {
+            metric: metric.into(),
+            value,
+            goal,
+        })
+    }
+
+    #[test]
+    fn metric_names_are_short_and_plain() {
+        let longest = "a".repeat(MAX_NAME);
+        for (name, accepted) in [
+            ("steps", true),
+            ("heart_rate2", true),
+            (longest.as_str(), true),
+            (&format!("{longest}a"), false),
+            ("", false),
+            ("Steps", false),
+            ("steps-today", false),
+            ("steps.json", false),
+            ("../steps", false),
+            ("пасы", false),
+        ] {
+            let body = format!(r#"{{"{name}": 1}}"#);
+            let pushed = push(&post(&format!("/push/{name}"), &body));
+            match accepted {
+                true => assert_eq!(pushed, reading(name, 1.0, None), "{name:?}"),
+                false => assert_eq!(pushed, Err(404), "{name:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn absurd_values_are_rejected() {
+        for (metric, body, pushed) in [
+            (
+                "steps",
+                r#"{"steps": 8421, "goal": 10000}"#,
+                reading("steps", 8421.0, Some(10_000.0)),
+            ),
+            ("steps", r#"{"steps": 0}"#, reading("steps", 0.0, None)),
+            (
+                "steps",
+                r#"{"steps": 200000, "goal": null}"#,
+                reading("steps", 200_000.0, None),
+            ),
+            ("steps", r#"{"steps": 200001}"#, Err(422)),
+            ("steps", r#"{"steps": -1}"#, Err(422)),
+            ("steps", r#"{"steps": "8421"}"#, Err(422)),
+            ("steps", r#"{"goal": 10000}"#, Err(422)),
+            ("steps", r#"{"steps": 10, "goal": 0}"#, Err(422)),
+            ("steps", r#"{"steps": 10, "goal": -10000}"#, Err(422)),
+            ("steps", r#"{"steps": 10, "goal": 1000000}"#, Err(422)),
+            ("steps", r#"{"steps": 10, "goal": "lots"}"#, Err(422)),
+            // Anything else only has to be sane.
+            (
+                "water",
+                r#"{"water": -2.5, "goal": 2}"#,
+                reading("water", -2.5, Some(2.0)),
+            ),
+            ("water", r#"{"water": 1e9}"#, reading("water", 1e9, None)),
+            ("water", r#"{"water": 1.5e9}"#, Err(422)),
+            ("water", r#"{"water": 1, "goal": 1e12}"#, Err(422)),
+            // JSON has no NaN or infinity, and numbers too big for an f64
+            // don't parse.
+            ("water", r#"{"water": NaN}"#, Err(400)),
+            ("water", r#"{"water": Infinity}"#, Err(400)),
+            ("water", r#"{"water": -Infinity}"#, Err(400)),
+            ("water", r#"{"water": 1e400}"#, Err(400)),
+            ("water", r#"{"water": 1, "goal": 1e400}"#, Err(400)),
+            ("water", "8421", Err(400)),
+            ("water", "", Err(400)),
+        ] {
+            assert_eq!(
+                push(&post(&format!("/push/{metric}"), body)),
+                pushed,
+                "{body}"
+            );
+        }
+    }
+
+    #[test]
+    fn bodies_over_the_limit_are_turned_away() {
+        let fits = format!(r#"{{"steps": 1{}}}"#, " ".repeat(MAX_BODY - 12));
+        assert_eq!(fits.len(), MAX_BODY);
+        assert_eq!(
+            push(&post("/push/steps", &fits)),
+            reading("steps", 1.0, None)
+        );
+        let over = format!("{fits} ");
+        assert_eq!(push(&post("/push/steps", &over)), Err(413));
+        // Going by what the headers say, before reading any of it.
+        let claimed = "POST /push/steps HTTP/1.1\r\nContent-Length: 1000000\r\n\r\n";
+        assert_eq!(push(claimed), Err(413));
+        let truncated = "POST /push/steps HTTP/1.1\r\nContent-Length: 40\r\n\r\n{}";
+        assert_eq!(push(truncated), Err(400));
+    }
+
+    #[test]
+    fn routes_and_methods_are_checked() {
+        for (raw, code) in [
+            ("GET /push/steps HTTP/1.1\r\n\r\n", 405),
+            (
+                "PUT /push/steps HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}",
+                405,
+            ),
+            ("POST /logs HTTP/1.1\r\nContent-Length: 0\r\n\r\n", 405),
+            ("POST /metrics HTTP/1.1\r\nContent-Length: 0\r\n\r\n", 405),
+            ("DELETE /card.png HTTP/1.1\r\n\r\n", 405),
+            ("GET /say HTTP/1.1\r\n\r\n", 405),
+            ("POST /nowhere HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}", 404),
+            ("GET / HTTP/1.1\r\n\r\n", 404),
+            ("POST /push HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}", 404),
+            ("GET /push/steps HTTP/1.1\r\n", 400),
+            (
+                "POST /push/steps HTTP/1.1\r\nContent-Length: many\r\n\r\n",
+                400,
+            ),
+        ] {
+            let code_ = match read_request(raw.as_bytes(), &[]) {
+                Ok(_) => panic!("{raw:?} was accepted"),
+                Err(Reply(code, _)) => code,
+            };
+            assert_eq!(code_, code, "{raw:?}");
+        }
+        assert!(matches!(
+            read_request("GET /metrics HTTP/1.1\r\n\r\n".as_bytes(), &[]),
+            Ok(Request::Metrics)
+        ));
+    }
+
+    #[test]
+    fn what_is_said_has_to_be_text() {
+        let say = |body: &str| parse_say(body.as_bytes()).map_err(|Reply(code, _)| code);
+        assert_eq!(
+            say(r#"{"text": "  good morning \n"}"#),
+            Ok("good morning".into())
+        );
+        assert_eq!(say("good morning"), Err(400));
+        assert_eq!(say(r#"["good morning"]"#), Err(400));
+        assert_eq!(say(r#"{"said": "hi"}"#), Err(422));
+        assert_eq!(say(r#"{"text": 3}"#), Err(422));
+        assert_eq!(say(r#"{"text": "   "}"#), Err(422));
+        let long = "ă".repeat(chat::MAX_TEXT);
+        assert_eq!(say(&format!(r#"{{"text": "{long}"}}"#)), Ok(long.clone()));
+        assert_eq!(say(&format!(r#"{{"text": "{long}!"}}"#)), Err(422));
+    }
+}
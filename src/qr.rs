@@ -0,0 +1,481 @@
+//! QR codes, just as much of them as [`crate::transfer`] needs:
+//! alphanumeric mode at error correction level M, versions 1 to 10. The
+//! smallest version the text fits is used, with the mask that scores best
+//! by the standard's penalty rules.
+
+/// What alphanumeric mode can hold, in the order of its values.
+pub const ALPHANUMERIC: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+pub const MAX_VERSION: usize = 10;
+
+/// How a version's codewords are split up at level M: error correction
+/// codewords per block, then (blocks, data codewords each) for the short
+/// blocks and the long ones.
+struct Blocks {
+    ec: usize,
+    groups: [(usize, usize); 2],
+}
+
+const BLOCKS: [Blocks; MAX_VERSION] = [
+    Blocks {
+        ec: 10,
+        groups: [(1, 16), (0, 0)],
+    },
+    Blocks {
+        ec: 16,
+        groups: [(1, 28), (0, 0)],
+    },
+    Blocks {
+        ec: 26,
+        groups: [(1, 44), (0, 0)],
+    },
+    Blocks {
+        ec: 18,
+        groups: [(2, 32), (0, 0)],
+    },
+    Blocks {
+        ec: 24,
+        groups: [(2, 43), (0, 0)],
+    },
+    Blocks {
+        ec: 16,
+        groups: [(4, 27), (0, 0)],
+    },
+    Blocks {
+        ec: 18,
+        groups: [(4, 31), (0, 0)],
+    },
+    Blocks {
+        ec: 22,
+        groups: [(2, 38), (2, 39)],
+    },
+    Blocks {
+        ec: 22,
+        groups: [(3, 36), (2, 37)],
+    },
+    Blocks {
+        ec: 26,
+        groups: [(4, 43), (1, 44)],
+    },
+];
+
+/// Centres of the alignment patterns along each axis.
+const ALIGNMENT: [&[usize]; MAX_VERSION] = [
+    &[],
+    &[6, 18],
+    &[6, 22],
+    &[6, 26],
+    &[6, 30],
+    &[6, 34],
+    &[6, 22, 38],
+    &[6, 24, 42],
+    &[6, 26, 46],
+    &[6, 28, 50],
+];
+
+const fn data_codewords(version: usize) -> usize {
+    let [(short, short_len), (long, long_len)] = BLOCKS[version - 1].groups;
+    short * short_len + long * long_len
+}
+
+const fn count_bits(version: usize) -> usize {
+    if version <= 9 {
+        9
+    } else {
+        11
+    }
+}
+
+/// The most characters a code of `version` holds: two to every 11 bits,
+/// and one more in 6 bits if there's room.
+pub const fn capacity(version: usize) -> usize {
+    let bits = data_codewords(version) * 8 - 4 - count_bits(version);
+    bits / 11 * 2 + if bits % 11 >= 6 { 1 } else { 0 }
+}
+
+pub struct Qr {
+    size: usize,
+    dark: Vec<bool>,
+}
+
+impl Qr {
+    /// The code for `text`, or `None` if it has anything alphanumeric mode
+    /// can't hold or is too long for version 10.
+    pub fn encode(text: &str) -> Option<Qr> {
+        let values: Vec<u16> = text
+            .bytes()
+            .map(|b| ALPHANUMERIC.iter().position(|&c| c == b).map(|v| v as u16))
+            .collect::<Option<_>>()?;
+        let version = (1..=MAX_VERSION).find(|&v| capacity(v) >= values.len())?;
+
+        let capacity_bits = data_codewords(version) * 8;
+        let mut bits = Bits::default();
+        bits.push(0b0010, 4);
+        bits.push(values.len() as u32, count_bits(version));
+        for pair in values.chunks(2) {
+            match *pair {
+                [a, b] => bits.push(a as u32 * 45 + b as u32, 11),
+                [a] => bits.push(a as u32, 6),
+                _ => unreachable!(),
+            }
+        }
+        bits.push(0, (capacity_bits - bits.len()).min(4));
+        bits.push(0, (8 - bits.len() % 8) % 8);
+        let mut data = bits.bytes();
+        for pad in [0xec, 0x11].into_iter().cycle() {
+            if data.len() == data_codewords(version) {
+                break;
+            }
+            data.push(pad);
+        }
+
+        let mut qr = Matrix::new(version);
+        qr.draw_function_patterns(version);
+        qr.draw_codewords(&interleave(version, &data));
+        let mask = (0..8)
+            .min_by_key(|&mask| {
+                let mut trial = qr.clone();
+                trial.apply_mask(mask);
+                trial.draw_format(mask);
+                trial.penalty()
+            })
+            .expect("there are masks");
+        qr.apply_mask(mask);
+        qr.draw_format(mask);
+        Some(Qr {
+            size: qr.size,
+            dark: qr.dark,
+        })
+    }
+
+    /// Modules along each side, without the quiet zone.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.dark[y * self.size + x]
+    }
+}
+
+#[derive(Default)]
+struct Bits(Vec<bool>);
+
+impl Bits {
+    fn push(&mut self, value: u32, count: usize) {
+        self.0.extend((0..count).rev().map(|i| value >> i & 1 != 0));
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        self.0
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit as u8))
+            .collect()
+    }
+}
+
+/// Splits `data` into blocks, works out each one's error correction, and
+/// interleaves the lot the way the codewords go into the symbol.
+fn interleave(version: usize, data: &[u8]) -> Vec<u8> {
+    let layout = &BLOCKS[version - 1];
+    let divisor = rs_divisor(layout.ec);
+    let mut blocks = Vec::new();
+    let mut rest = data;
+    for (count, length) in layout.groups {
+        for _ in 0..count {
+            let (block, after) = rest.split_at(length);
+            blocks.push((block, rs_remainder(block, &divisor)));
+            rest = after;
+        }
+    }
+    let longest = blocks.iter().map(|(data, _)| data.len()).max().unwrap_or(0);
+    let mut out = Vec::new();
+    for i in 0..longest {
+        out.extend(blocks.iter().filter_map(|(data, _)| data.get(i)));
+    }
+    for i in 0..layout.ec {
+        out.extend(blocks.iter().map(|(_, ec)| ec[i]));
+    }
+    out
+}
+
+/// Multiplication in GF(2⁸) modulo x⁸ + x⁴ + x³ + x² + 1.
+fn gf_mul(x: u8, y: u8) -> u8 {
+    let mut z: u8 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x1d);
+        z ^= ((y >> i) & 1) * x;
+    }
+    z
+}
+
+/// The generator polynomial for `degree` error correction codewords,
+/// highest power first, leading 1 left out.
+fn rs_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0; degree];
+    result[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_mul(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_mul(root, 0x02);
+    }
+    result
+}
+
+fn rs_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ result.remove(0);
+        result.push(0);
+        for (x, &y) in result.iter_mut().zip(divisor) {
+            *x ^= gf_mul(y, factor);
+        }
+    }
+    result
+}
+
+#[derive(Clone)]
+struct Matrix {
+    size: usize,
+    dark: Vec<bool>,
+    /// Modules taken by the finders, timing and the like, which data
+    /// goes around and masks leave alone.
+    function: Vec<bool>,
+}
+
+impl Matrix {
+    fn new(version: usize) -> Self {
+        let size = version * 4 + 17;
+        Matrix {
+            size,
+            dark: vec![false; size * size],
+            function: vec![false; size * size],
+        }
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.dark[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    fn draw_function_patterns(&mut self, version: usize) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+        for (x, y) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            self.draw_finder(x, y);
+        }
+        let centres = ALIGNMENT[version - 1];
+        let last = centres.len().saturating_sub(1);
+        for (i, &x) in centres.iter().enumerate() {
+            for (j, &y) in centres.iter().enumerate() {
+                // Where a finder already is.
+                if [(0, 0), (0, last), (last, 0)].contains(&(i, j)) {
+                    continue;
+                }
+                for dy in -2..=2i32 {
+                    for dx in -2..=2i32 {
+                        let ring = dx.abs().max(dy.abs());
+                        let (x, y) = ((x as i32 + dx) as usize, (y as i32 + dy) as usize);
+                        self.set_function(x, y, ring != 1);
+                    }
+                }
+            }
+        }
+        // Reserved for now and filled in once the mask is picked.
+        self.draw_format(0);
+        if version >= 7 {
+            let mut rem = version as u32;
+            for _ in 0..12 {
+                rem = (rem << 1) ^ ((rem >> 11) * 0x1f25);
+            }
+            let bits = (version as u32) << 12 | rem;
+            for i in 0..18 {
+                let dark = bits >> i & 1 != 0;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                self.set_function(a, b, dark);
+                self.set_function(b, a, dark);
+            }
+        }
+    }
+
+    /// A finder centred on `(cx, cy)`, with its light separator.
+    fn draw_finder(&mut self, cx: usize, cy: usize) {
+        for dy in -4..=4i32 {
+            for dx in -4..=4i32 {
+                let ring = dx.abs().max(dy.abs());
+                let (x, y) = (cx as i32 + dx, cy as i32 + dy);
+                if (0..self.size as i32).contains(&x) && (0..self.size as i32).contains(&y) {
+                    self.set_function(x as usize, y as usize, ring != 2 && ring != 4);
+                }
+            }
+        }
+    }
+
+    /// Both copies of the format information for level M and `mask`, and
+    /// the module that's always dark.
+    fn draw_format(&mut self, mask: u32) {
+        // Level M is 00, so the mask is all there is to it.
+        let data = mask;
+        let mut rem = data;
+        for _ in 0..10 {
+            rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+        }
+        let bits = (data << 10 | rem) ^ 0x5412;
+        let bit = |i: usize| bits >> i & 1 != 0;
+        let size = self.size;
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Lays the codewords out in two-module columns, zigzagging up and
+    /// down from the bottom right and stepping around the timing column.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let size = self.size;
+        let mut i = 0;
+        let mut right = size as i32 - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = right as usize - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward {
+                        size - 1 - vertical
+                    } else {
+                        vertical
+                    };
+                    if !self.function[y * size + x] && i < codewords.len() * 8 {
+                        self.dark[y * size + x] = codewords[i / 8] >> (7 - i % 8) & 1 != 0;
+                        i += 1;
+                    }
+                }
+            }
+            right -= 2;
+        }
+    }
+
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let flip = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                if flip && !self.function[y * self.size + x] {
+                    self.dark[y * self.size + x] ^= true;
+                }
+            }
+        }
+    }
+
+    /// How hard the symbol is to read, by the standard's four rules: long
+    /// runs, 2×2 blocks, finder lookalikes and an uneven share of dark.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let at = |x: usize, y: usize| self.dark[y * size + x];
+        let lines = (0..size).flat_map(|i| {
+            [
+                (0..size).map(|x| at(x, i)).collect::<Vec<_>>(),
+                (0..size).map(|y| at(i, y)).collect::<Vec<_>>(),
+            ]
+        });
+        let finder = [true, false, true, true, true, false, true];
+        let mut score = 0;
+        for line in lines {
+            for run in line.chunk_by(|a, b| a == b) {
+                if run.len() >= 5 {
+                    score += run.len() - 2;
+                }
+            }
+            // Beyond the edge counts as light.
+            let mut padded = vec![false; 4];
+            padded.extend(&line);
+            padded.extend([false; 4]);
+            for window in padded.windows(11) {
+                let light = |range: &[bool]| range.iter().all(|&dark| !dark);
+                if (window[..7] == finder && light(&window[7..]))
+                    || (light(&window[..4]) && window[4..] == finder)
+                {
+                    score += 40;
+                }
+            }
+        }
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let colour = at(x, y);
+                if at(x + 1, y) == colour && at(x, y + 1) == colour && at(x + 1, y + 1) == colour {
+                    score += 3;
+                }
+            }
+        }
+        let total = size * size;
+        let dark = self.dark.iter().filter(|&&dark| dark).count();
+        let k = ((dark * 20).abs_diff(total * 10))
+            .div_ceil(total)
+            .saturating_sub(1);
+        score + k * 10
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hello_world_gets_the_published_error_correction() {
+        // "HELLO WORLD" at 1-M, from the worked example in the standard.
+        let data = [
+            32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17,
+        ];
+        let ec = [196, 35, 39, 119, 235, 215, 231, 226, 93, 23];
+        assert_eq!(interleave(1, &data), [&data[..], &ec[..]].concat());
+    }
+
+    #[test]
+    fn codes_grow_with_the_text_and_stop_at_version_10() {
+        let qr = Qr::encode("HELLO WORLD").unwrap();
+        assert_eq!(qr.size(), 21);
+        // The finder's dark ring and light gap in the top left corner.
+        assert!((0..7).all(|i| qr.is_dark(i, 0) && qr.is_dark(0, i)));
+        assert!(!qr.is_dark(1, 1) && qr.is_dark(2, 2));
+
+        let longest = "A".repeat(capacity(MAX_VERSION));
+        assert_eq!(Qr::encode(&longest).unwrap().size(), 17 + 4 * MAX_VERSION);
+        assert!(Qr::encode(&format!("{longest}A")).is_none());
+        // Lower case isn't in alphanumeric mode.
+        assert!(Qr::encode("hello").is_none());
+    }
+}
@@ -0,0 +1,210 @@
+//! Commands waiting on the next cycle. They pile up while a frame is drawn
+//! and the panel takes its seconds to refresh, and are all taken at once
+//! at the start of the next cycle, before the pet is ticked, so what they
+//! do shows in the frame after.
+//!
+//! They're kept in the order they came in, except for repeats that would
+//! only do the same thing over: another `refresh`, a newer reading of the
+//! same metric or the same screen switched again replaces the one waiting,
+//! at the back. The queue is bounded; when it's full the oldest of those
+//! goes first, and only then the oldest of anything.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::events::Command;
+
+/// Commands kept at most. Far more than a cycle's worth from people; a
+/// runaway script on the socket is stopped here.
+pub const CAPACITY: usize = 64;
+
+/// A command and when the loop picked it up.
+#[derive(Debug)]
+pub struct Queued {
+    pub command: Command,
+    pub at: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct Queue {
+    entries: VecDeque<Queued>,
+}
+
+impl Queue {
+    pub fn push(&mut self, command: Command, at: Instant) {
+        if let Some(repeat) = key(&command) {
+            self.entries
+                .retain(|queued| key(&queued.command) != Some(repeat));
+        }
+        if self.entries.len() >= CAPACITY {
+            let oldest = self
+                .entries
+                .iter()
+                .position(|queued| key(&queued.command).is_some())
+                .unwrap_or(0);
+            if let Some(dropped) = self.entries.remove(oldest) {
+                log::warn!(
+                    "command queue full, dropping {:?} from {:.1}s ago",
+                    dropped.command,
+                    at.saturating_duration_since(dropped.at).as_secs_f32()
+                );
+            }
+        }
+        self.entries.push_back(Queued { command, at });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Everything waiting, oldest first, leaving the queue empty.
+    pub fn drain(&mut self) -> Vec<Queued> {
+        self.entries.drain(..).collect()
+    }
+}
+
+/// What makes two commands the same, when only the later is worth doing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Key<'a> {
+    Refresh,
+    Reading(&'a str),
+    Screen(&'a str),
+}
+
+fn key(command: &Command) -> Option<Key<'_>> {
+    match command {
+        Command::Refresh => Some(Key::Refresh),
+        Command::Record(reading) => Some(Key::Reading(&reading.metric)),
+        Command::SetScreen(name, _) => Some(Key::Screen(name)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::events::{Action, Reading};
+
+    fn reading(metric: &str, value: f64) -> Command {
+        Command::Record(Reading {
+            metric: metric.into(),
+            value,
+            goal: None,
+        })
+    }
+
+    /// `commands` pushed a second apart, as they come out.
+    fn through(commands: impl IntoIterator<Item = Command>) -> Vec<Command> {
+        let start = Instant::now();
+        let mut queue = Queue::default();
+        for (i, command) in commands.into_iter().enumerate() {
+            queue.push(command, start + Duration::from_secs(i as u64));
+        }
+        queue.drain().into_iter().map(|q| q.command).collect()
+    }
+
+    #[test]
+    fn actions_keep_their_order() {
+        let actions = [
+            Action::Feed,
+            Action::Clean,
+            Action::Feed,
+            Action::Play,
+            Action::Feed,
+        ]
+        .map(Command::Act);
+        assert_eq!(through(actions.clone()), actions);
+    }
+
+    #[test]
+    fn repeats_replace_the_one_waiting_at_the_back() {
+        let out = through([
+            Command::Refresh,
+            Command::Act(Action::Feed),
+            reading("steps", 100.0),
+            Command::Refresh,
+            reading("water", 1.0),
+            Command::Act(Action::Clean),
+            reading("steps", 200.0),
+            Command::SetScreen("moon".into(), false),
+            Command::SetScreen("moon".into(), true),
+        ]);
+        assert_eq!(
+            out,
+            [
+                Command::Act(Action::Feed),
+                Command::Refresh,
+                reading("water", 1.0),
+                Command::Act(Action::Clean),
+                reading("steps", 200.0),
+                Command::SetScreen("moon".into(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn different_screens_and_metrics_are_kept_apart() {
+        let commands = [
+            reading("steps", 1.0),
+            Command::SetScreen("moon".into(), true),
+            reading("water", 2.0),
+            Command::SetScreen("clock".into(), false),
+        ];
+        assert_eq!(through(commands.clone()), commands);
+    }
+
+    #[test]
+    fn draining_empties_the_queue_with_arrival_times() {
+        let start = Instant::now();
+        let mut queue = Queue::default();
+        queue.push(Command::Act(Action::Feed), start);
+        queue.push(Command::Refresh, start + Duration::from_secs(1));
+        queue.push(Command::Refresh, start + Duration::from_secs(2));
+        assert_eq!(queue.len(), 2);
+        let drained = queue.drain();
+        let times: Vec<_> = drained.iter().map(|q| q.at - start).collect();
+        assert_eq!(times, [Duration::ZERO, Duration::from_secs(2)]);
+        assert_eq!(queue.len(), 0);
+        assert!(queue.drain().is_empty());
+    }
+
+    #[test]
+    fn a_full_queue_drops_the_oldest_repeatable_first() {
+        let mut commands = vec![reading("steps", 1.0), Command::Act(Action::Feed)];
+        commands.push(reading("water", 1.0));
+        commands.extend((3..CAPACITY).map(|_| Command::Act(Action::Pet)));
+        commands.push(Command::Act(Action::Clean));
+        let out = through(commands.clone());
+        assert_eq!(out.len(), CAPACITY);
+        // The steps went, the water's still there.
+        assert_eq!(out[..], commands[1..]);
+
+        // Then the water, and the feed stays until there's nothing else.
+        commands.push(Command::Act(Action::Play));
+        let out = through(commands.clone());
+        assert_eq!(out.len(), CAPACITY);
+        assert_eq!(out[0], Command::Act(Action::Feed));
+        assert_eq!(out[1..], commands[3..]);
+    }
+
+    #[test]
+    fn a_full_queue_of_actions_drops_the_oldest() {
+        let mut commands: Vec<_> = (0..CAPACITY).map(|_| Command::Act(Action::Pet)).collect();
+        commands[0] = Command::Act(Action::Feed);
+        commands.push(Command::Act(Action::Clean));
+        let out = through(commands.clone());
+        assert_eq!(out[..], commands[1..]);
+        // A repeat that replaces one waiting makes room for itself.
+        let mut commands: Vec<_> = (0..CAPACITY - 1)
+            .map(|_| Command::Act(Action::Pet))
+            .collect();
+        commands.insert(0, Command::Refresh);
+        commands.push(Command::Refresh);
+        let out = through(commands.clone());
+        assert_eq!(out.len(), CAPACITY);
+        assert_eq!(out[0], Command::Act(Action::Pet));
+        assert_eq!(out[CAPACITY - 1], Command::Refresh);
+    }
+}
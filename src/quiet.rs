@@ -0,0 +1,512 @@
+//! Quiet hours: the nightly window during which the pet sleeps and the
+//! display leaves the panel alone. It's either fixed or follows the sun,
+//! from civil dusk to civil dawn, and either way can take extra windows,
+//! say for an afternoon nap.
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveTime, Timelike};
+use serde::Deserialize;
+
+use crate::astro;
+use crate::clock::Clock;
+use crate::config::Config;
+
+/// Configured as `{ start = "22:00", end = "07:00" }`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawQuietHours")]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+#[derive(Deserialize)]
+struct RawQuietHours {
+    start: String,
+    end: String,
+}
+
+impl TryFrom<RawQuietHours> for QuietHours {
+    type Error = String;
+
+    fn try_from(raw: RawQuietHours) -> Result<Self, Self::Error> {
+        Ok(QuietHours {
+            start: parse_time(&raw.start)?,
+            end: parse_time(&raw.end)?,
+        })
+    }
+}
+
+/// Configured as fixed hours, as `"sun"`, or as a table with the sun,
+/// fixed hours or both, and extra windows:
+/// `{ sun = true, start = "22:00", end = "07:00", extra = [...] }`.
+/// With the sun, the fixed hours are for nights it doesn't get dark
+/// enough, or light, near the poles; they default to 22:00 to 07:00.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawQuietConfig")]
+pub struct QuietConfig {
+    pub sun: bool,
+    pub hours: QuietHours,
+    pub extra: Vec<QuietHours>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawQuietConfig {
+    Keyword(String),
+    Table {
+        #[serde(default)]
+        sun: bool,
+        start: Option<String>,
+        end: Option<String>,
+        #[serde(default)]
+        extra: Vec<RawQuietHours>,
+    },
+}
+
+impl TryFrom<RawQuietConfig> for QuietConfig {
+    type Error = String;
+
+    fn try_from(raw: RawQuietConfig) -> Result<Self, Self::Error> {
+        match raw {
+            RawQuietConfig::Keyword(word) if word == "sun" => Ok(QuietConfig {
+                sun: true,
+                hours: NIGHT,
+                extra: Vec::new(),
+            }),
+            RawQuietConfig::Keyword(word) => Err(format!(
+                "expected \"sun\" or a table with start and end, got {word:?}"
+            )),
+            RawQuietConfig::Table {
+                sun,
+                start,
+                end,
+                extra,
+            } => {
+                let hours = match (start, end) {
+                    (Some(start), Some(end)) => QuietHours::try_from(RawQuietHours { start, end })?,
+                    (None, None) if sun => NIGHT,
+                    _ => return Err("quiet hours need both a start and an end".into()),
+                };
+                let extra = extra
+                    .into_iter()
+                    .map(QuietHours::try_from)
+                    .collect::<Result<_, _>>()?;
+                Ok(QuietConfig { sun, hours, extra })
+            }
+        }
+    }
+}
+
+impl QuietConfig {
+    /// The quiet hours for `date`, at the given position if they follow
+    /// the sun: from that evening's dusk to the next morning's dawn, and
+    /// the extra windows.
+    pub fn on(&self, date: NaiveDate, clock: &Clock, latitude: f64, longitude: f64) -> Quiet {
+        let night = if self.sun {
+            let dusk = astro::daylight(date, latitude, longitude, astro::CIVIL_ZENITH).set();
+            let dawn = astro::daylight(
+                date + Duration::days(1),
+                latitude,
+                longitude,
+                astro::CIVIL_ZENITH,
+            )
+            .rise();
+            match (dusk, dawn) {
+                (Some(dusk), Some(dawn)) => QuietHours {
+                    start: clock
+                        .localize(dusk)
+                        .time()
+                        .with_nanosecond(0)
+                        .unwrap_or_default(),
+                    end: clock
+                        .localize(dawn)
+                        .time()
+                        .with_nanosecond(0)
+                        .unwrap_or_default(),
+                },
+                _ => {
+                    log::warn!(
+                        "the sun doesn't reach civil dusk and dawn around {date}, \
+                         quiet from {} to {} instead",
+                        self.hours.start.format("%H:%M"),
+                        self.hours.end.format("%H:%M")
+                    );
+                    self.hours
+                }
+            }
+        } else {
+            self.hours
+        };
+        Quiet::merged(std::iter::once(night).chain(self.extra.iter().copied()))
+    }
+}
+
+/// A day's quiet hours: every window that applies, merged where they
+/// overlap or touch, in order of their starts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Quiet {
+    windows: Vec<QuietHours>,
+}
+
+const DAY: u32 = 24 * 60 * 60;
+
+impl Quiet {
+    pub fn merged(windows: impl IntoIterator<Item = QuietHours>) -> Quiet {
+        // As spans of seconds into the day, cut in two at midnight.
+        let mut spans = Vec::new();
+        for window in windows {
+            let start = window.start.num_seconds_from_midnight();
+            let end = window.end.num_seconds_from_midnight();
+            if start < end {
+                spans.push((start, end));
+            } else if start > end {
+                spans.push((start, DAY));
+                spans.push((0, end));
+            }
+        }
+        spans.sort_unstable();
+        let mut merged: Vec<(u32, u32)> = Vec::new();
+        for (start, end) in spans {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        // Join back up across midnight.
+        if merged.len() > 1 && merged[0].0 == 0 && merged[merged.len() - 1].1 == DAY {
+            let (_, end) = merged.remove(0);
+            merged.last_mut().expect("more than one span").1 = end;
+        }
+        let time = |seconds: u32| {
+            NaiveTime::from_num_seconds_from_midnight_opt(seconds % DAY, 0).unwrap_or_default()
+        };
+        Quiet {
+            windows: merged
+                .into_iter()
+                .map(|(start, end)| QuietHours {
+                    start: time(start),
+                    end: time(end),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        self.windows.iter().any(|window| window.contains(time))
+    }
+
+    /// When the window `now` is in ends, or else the next one to end.
+    pub fn next_end(&self, clock: &Clock, now: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        let time = now.time();
+        self.windows
+            .iter()
+            .find(|window| window.contains(time))
+            .into_iter()
+            .chain(&self.windows)
+            .map(|window| window.next_end(clock, now))
+            .next()
+            .unwrap_or(now)
+    }
+
+    /// E.g. `19:12-06:55, 13:00-14:00`, for the log.
+    pub fn describe(&self) -> String {
+        self.windows
+            .iter()
+            .map(|window| {
+                format!(
+                    "{}-{}",
+                    window.start.format("%H:%M"),
+                    window.end.format("%H:%M")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Works the quiet hours out once a day; the sun's times only move a
+/// minute or two from one day to the next.
+#[derive(Default)]
+pub struct Planner {
+    day: Option<(NaiveDate, Quiet)>,
+}
+
+impl Planner {
+    /// The configured quiet hours for `now`'s day, if any.
+    pub fn get(
+        &mut self,
+        config: &Config,
+        clock: &Clock,
+        now: DateTime<FixedOffset>,
+    ) -> Option<&Quiet> {
+        let quiet = config.quiet_hours.as_ref()?;
+        let date = now.date_naive();
+        if self.day.as_ref().is_none_or(|(day, _)| *day != date) {
+            let today = quiet.on(date, clock, config.latitude, config.longitude);
+            log::debug!("quiet hours on {date}: {}", today.describe());
+            self.day = Some((date, today));
+        }
+        self.day.as_ref().map(|(_, today)| today)
+    }
+}
+
+/// When the pet sleeps if no quiet hours are set. The panel isn't parked
+/// then, but the pet still needs its rest.
+const NIGHT: QuietHours = QuietHours {
+    start: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+    end: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+};
+
+/// Whether the pet is asleep at `time`: during `quiet` hours, or at night
+/// without any.
+pub fn is_bedtime(quiet: Option<&Quiet>, time: NaiveTime) -> bool {
+    match quiet {
+        Some(quiet) => quiet.contains(time),
+        None => NIGHT.contains(time),
+    }
+}
+
+/// Parses a wall-clock time written as `HH:MM`.
+pub fn parse_time(text: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(text, "%H:%M").map_err(|_| format!("expected HH:MM, got {text:?}"))
+}
+
+/// For `HH:MM` config fields.
+pub fn deserialize_time<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    parse_time(&text).map_err(serde::de::Error::custom)
+}
+
+impl QuietHours {
+    /// Whether `time` falls inside the window, which may wrap past midnight.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// The first end of the window strictly after `now`.
+    pub fn next_end(&self, clock: &Clock, now: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        clock.next_occurrence(now, self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Zone;
+
+    fn hours(start: &str, end: &str) -> QuietHours {
+        QuietHours {
+            start: parse_time(start).unwrap(),
+            end: parse_time(end).unwrap(),
+        }
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn clock(zone: &str) -> Clock {
+        Clock::new(Zone::from_config(Some(zone)))
+    }
+
+    fn sun(extra: Vec<QuietHours>) -> QuietConfig {
+        QuietConfig {
+            sun: true,
+            hours: NIGHT,
+            extra,
+        }
+    }
+
+    /// How many minutes of the day are quiet.
+    fn minutes(quiet: &Quiet) -> i64 {
+        quiet
+            .windows
+            .iter()
+            .map(|w| (w.end - w.start).num_minutes().rem_euclid(24 * 60))
+            .sum()
+    }
+
+    #[test]
+    fn windows_merge_where_they_overlap_or_touch() {
+        let merged = Quiet::merged([
+            hours("13:00", "14:00"),
+            hours("09:00", "10:00"),
+            hours("13:30", "15:00"),
+            hours("15:00", "15:30"),
+            hours("09:15", "09:45"),
+        ]);
+        assert_eq!(merged.describe(), "09:00-10:00, 13:00-15:30");
+        // One that starts and ends together is no window at all.
+        assert_eq!(Quiet::merged([hours("12:00", "12:00")]).describe(), "");
+    }
+
+    #[test]
+    fn windows_merge_across_midnight() {
+        let merged = Quiet::merged([
+            hours("22:00", "07:00"),
+            hours("06:00", "08:00"),
+            hours("21:00", "22:30"),
+        ]);
+        assert_eq!(merged.describe(), "21:00-08:00");
+        assert!(merged.contains(parse_time("00:00").unwrap()));
+        assert!(merged.contains(parse_time("07:59").unwrap()));
+        assert!(!merged.contains(parse_time("08:00").unwrap()));
+        // Ending at midnight and starting there join up.
+        let merged = Quiet::merged([hours("23:00", "00:00"), hours("00:00", "01:00")]);
+        assert_eq!(merged.describe(), "23:00-01:00");
+        // One inside the night is swallowed by it.
+        let merged = Quiet::merged([hours("22:00", "07:00"), hours("02:00", "03:00")]);
+        assert_eq!(merged.describe(), "22:00-07:00");
+    }
+
+    #[test]
+    fn nights_follow_the_seasons() {
+        let clock = clock("UTC");
+        // Bucharest, the Equator, and Hobart on the other side.
+        type Place = (f64, f64, bool);
+        let places: [Place; 3] = [
+            (44.43, 26.10, true),
+            (0.0, 0.0, false),
+            (-42.88, 147.33, false),
+        ];
+        for (latitude, longitude, north) in places {
+            let june = minutes(&sun(Vec::new()).on(date(2024, 6, 21), &clock, latitude, longitude));
+            let december =
+                minutes(&sun(Vec::new()).on(date(2024, 12, 21), &clock, latitude, longitude));
+            let (short, long) = match north {
+                true => (june, december),
+                false => (december, june),
+            };
+            if latitude == 0.0 {
+                // A day's about as long all year round.
+                assert!((june - december).abs() < 10, "{june} and {december}");
+                assert!((9 * 60..12 * 60).contains(&june), "{june}");
+            } else {
+                assert!(long - short > 4 * 60, "{short} and {long} at {latitude}");
+            }
+        }
+    }
+
+    #[test]
+    fn sun_times_are_local() {
+        let clock = clock("Europe/Bucharest");
+        let winter = sun(Vec::new()).on(date(2024, 12, 21), &clock, 44.43, 26.10);
+        let summer = sun(Vec::new()).on(date(2024, 6, 21), &clock, 44.43, 26.10);
+        let [winter] = winter.windows[..] else {
+            panic!("{}", winter.describe());
+        };
+        let [summer] = summer.windows[..] else {
+            panic!("{}", summer.describe());
+        };
+        // Civil dusk is about 17:10 in December and 21:40 in June, dawn
+        // about 07:20 and 05:00.
+        assert!(hours("16:50", "17:30").contains(winter.start), "{winter:?}");
+        assert!(hours("07:00", "07:40").contains(winter.end), "{winter:?}");
+        assert!(hours("21:20", "22:00").contains(summer.start), "{summer:?}");
+        assert!(hours("04:40", "05:20").contains(summer.end), "{summer:?}");
+    }
+
+    #[test]
+    fn nights_without_dusk_or_dawn_use_the_fixed_hours() {
+        let clock = clock("UTC");
+        let fixed = QuietConfig {
+            sun: true,
+            hours: hours("23:00", "06:00"),
+            extra: vec![hours("13:00", "14:00")],
+        };
+        // Tromsø under the midnight sun, and Svalbard in the polar night,
+        // too far north even for civil twilight.
+        for (day, latitude, longitude) in [
+            (date(2024, 6, 21), 69.65, 18.96),
+            (date(2024, 12, 21), 78.22, 15.65),
+        ] {
+            let quiet = fixed.on(day, &clock, latitude, longitude);
+            assert_eq!(quiet.describe(), "13:00-14:00, 23:00-06:00");
+        }
+        // Tromsø in midwinter still gets civil dusk and dawn.
+        let quiet = fixed.on(date(2024, 12, 21), &clock, 69.65, 18.96);
+        assert_ne!(quiet.describe(), "13:00-14:00, 23:00-06:00");
+    }
+
+    #[test]
+    fn extra_windows_join_the_night() {
+        let clock = clock("Europe/Bucharest");
+        let plain = sun(Vec::new()).on(date(2024, 12, 21), &clock, 44.43, 26.10);
+        let night = plain.windows[0];
+        // A nap apart from the night, and an evening that runs into dusk.
+        let config = sun(vec![hours("13:00", "14:00"), hours("16:00", "17:30")]);
+        let quiet = config.on(date(2024, 12, 21), &clock, 44.43, 26.10);
+        assert_eq!(
+            quiet.windows,
+            [
+                hours("13:00", "14:00"),
+                QuietHours {
+                    start: parse_time("16:00").unwrap(),
+                    end: night.end,
+                },
+            ]
+        );
+        // In June dusk is later, so the evening stays apart.
+        let quiet = config.on(date(2024, 6, 21), &clock, 44.43, 26.10);
+        assert_eq!(quiet.windows.len(), 3, "{}", quiet.describe());
+        assert_eq!(
+            minutes(&quiet),
+            60 + 90 + minutes(&sun(Vec::new()).on(date(2024, 6, 21), &clock, 44.43, 26.10))
+        );
+    }
+
+    #[test]
+    fn the_planner_works_a_day_out_once() {
+        let clock = clock("Europe/Bucharest");
+        let config = Config {
+            quiet_hours: Some(sun(Vec::new())),
+            latitude: 44.43,
+            longitude: 26.10,
+            ..Config::default()
+        };
+        let at = |text: &str| DateTime::parse_from_rfc3339(text).unwrap();
+        let mut planner = Planner::default();
+        let june = planner
+            .get(&config, &clock, at("2024-06-21T12:00:00+03:00"))
+            .cloned();
+        assert_eq!(
+            june,
+            Some(sun(Vec::new()).on(date(2024, 6, 21), &clock, 44.43, 26.10))
+        );
+        // Later the same day it's the same night, even with the position
+        // moved.
+        let moved = Config {
+            latitude: 0.0,
+            ..config.clone()
+        };
+        let evening = planner
+            .get(&moved, &clock, at("2024-06-21T23:00:00+03:00"))
+            .cloned();
+        assert_eq!(evening, june);
+        // Past midnight it's the next day's.
+        let next = planner
+            .get(&config, &clock, at("2024-06-22T01:00:00+03:00"))
+            .cloned();
+        assert_eq!(
+            next,
+            Some(sun(Vec::new()).on(date(2024, 6, 22), &clock, 44.43, 26.10))
+        );
+        assert!(next.unwrap().contains(parse_time("01:00").unwrap()));
+        // Six months on, the night is hours longer.
+        let december = planner
+            .get(&config, &clock, at("2024-12-21T12:00:00+02:00"))
+            .unwrap();
+        assert!(minutes(december) > minutes(&june.unwrap()) + 4 * 60);
+        // Without quiet hours there's nothing to plan.
+        assert_eq!(
+            planner.get(&Config::default(), &clock, at("2024-12-22T12:00:00+02:00")),
+            None
+        );
+    }
+}
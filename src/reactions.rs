@@ -0,0 +1,157 @@
+//! How the pet takes changes in the host's health.
+//!
+//! [`REACTIONS`] is the one place that decides what a [`SystemEvent`] does
+//! on screen; a new integration adds its events there rather than teaching
+//! the screens about it.
+
+use crate::display::Color;
+use crate::events::SystemEvent;
+use crate::icons::Icon;
+
+/// Refreshes a passing expression stays on the pet's face.
+pub const FACE_REFRESHES: u32 = 2;
+
+/// A condition that lasts until a later event clears it, shown as an icon in
+/// the header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flag {
+    Offline,
+    DiskFull,
+    Undervoltage,
+    #[cfg(feature = "github")]
+    CiFailing,
+}
+
+impl Flag {
+    pub fn icon(self) -> Icon {
+        match self {
+            Flag::Offline => Icon::BrokenLink,
+            Flag::DiskFull => Icon::DiskFull,
+            Flag::Undervoltage => Icon::BatteryLow,
+            #[cfg(feature = "github")]
+            Flag::CiFailing => Icon::Cross,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            #[cfg(feature = "github")]
+            Flag::CiFailing => Color::Red,
+            _ => Color::Black,
+        }
+    }
+
+    /// The expression the pet wears for as long as the flag is up.
+    pub fn face(self) -> Option<Face> {
+        match self {
+            Flag::Offline => Some(Face::Lost),
+            #[cfg(feature = "github")]
+            Flag::CiFailing => Some(Face::Worried),
+            Flag::DiskFull | Flag::Undervoltage => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Face {
+    Lost,
+    Happy,
+    #[cfg_attr(not(feature = "github"), allow(dead_code))]
+    Worried,
+}
+
+/// What one event does. Everything is optional, so an event can, say, just
+/// pull a face.
+#[derive(Clone, Copy, Debug)]
+pub struct Reaction {
+    pub raise: Option<Flag>,
+    pub clear: Option<Flag>,
+    /// An expression shown for [`FACE_REFRESHES`], whatever the flags say.
+    pub face: Option<Face>,
+}
+
+impl Reaction {
+    const NONE: Reaction = Reaction {
+        raise: None,
+        clear: None,
+        face: None,
+    };
+}
+
+pub const REACTIONS: &[(SystemEvent, Reaction)] = &[
+    (
+        SystemEvent::NetworkDown,
+        Reaction {
+            raise: Some(Flag::Offline),
+            ..Reaction::NONE
+        },
+    ),
+    (
+        SystemEvent::NetworkUp,
+        Reaction {
+            clear: Some(Flag::Offline),
+            face: Some(Face::Happy),
+            ..Reaction::NONE
+        },
+    ),
+    (
+        SystemEvent::AddressChanged,
+        Reaction {
+            face: Some(Face::Lost),
+            ..Reaction::NONE
+        },
+    ),
+    (
+        SystemEvent::DiskAlmostFull,
+        Reaction {
+            raise: Some(Flag::DiskFull),
+            ..Reaction::NONE
+        },
+    ),
+    (
+        SystemEvent::DiskOk,
+        Reaction {
+            clear: Some(Flag::DiskFull),
+            ..Reaction::NONE
+        },
+    ),
+    (
+        SystemEvent::Undervoltage,
+        Reaction {
+            raise: Some(Flag::Undervoltage),
+            ..Reaction::NONE
+        },
+    ),
+    (
+        SystemEvent::PowerOk,
+        Reaction {
+            clear: Some(Flag::Undervoltage),
+            ..Reaction::NONE
+        },
+    ),
+    #[cfg(feature = "github")]
+    (
+        SystemEvent::CiFailed,
+        Reaction {
+            raise: Some(Flag::CiFailing),
+            ..Reaction::NONE
+        },
+    ),
+    #[cfg(feature = "github")]
+    (
+        SystemEvent::CiPassed,
+        Reaction {
+            clear: Some(Flag::CiFailing),
+            face: Some(Face::Happy),
+            ..Reaction::NONE
+        },
+    ),
+];
+
+/// The entry for `event`; events without one are ignored.
+pub fn lookup(event: SystemEvent) -> Reaction {
+    REACTIONS
+        .iter()
+        .find(|(e, _)| *e == event)
+        .map_or(Reaction::NONE, |(_, reaction)| *reaction)
+}
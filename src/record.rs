@@ -0,0 +1,240 @@
+//! Recording a stretch of virtual time as frames, for demo animations.
+//!
+//! `record` steps the pet through a scenario the way `simulate` does, and
+//! at every step draws what the panel would show into a numbered PNG.
+//! `manifest.json` next to them lists each frame with its virtual time.
+//! `render-gif` (with the `render-gif` feature) turns a recording into an
+//! animated GIF, speeding virtual time up by a given factor.
+
+use std::path::Path;
+
+use anyhow::{ensure, Context as _, Result};
+use chrono::{DateTime, Duration, Utc};
+use embedded_graphics::prelude::Point;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::{Clock, Zone};
+use crate::collector::Snapshot;
+use crate::config::Config;
+use crate::display::{self, Color, Frame, PanelInfo, HEIGHT, WIDTH};
+use crate::overlay;
+use crate::pet::Pet;
+use crate::quiet;
+use crate::runtime::Runtime;
+use crate::screens::sleep::SleepScreen;
+use crate::screens::{Carousel, Context, Screen};
+use crate::simulate::Scenario;
+use crate::summary::Tally;
+
+pub const MANIFEST: &str = "manifest.json";
+
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    pub frames: Vec<RecordedFrame>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Relative to the manifest.
+    pub file: String,
+    pub at: DateTime<Utc>,
+}
+
+/// How much to record.
+pub struct Span {
+    pub start: DateTime<Utc>,
+    pub length: Duration,
+    /// Virtual time between frames.
+    pub step: Duration,
+}
+
+/// Records `span` into `dir`, returning how many frames were written. The
+/// pet starts fresh, and the seed and clock are fixed, so a recording can
+/// be made again.
+pub fn record(
+    config: &Config,
+    zone: Zone,
+    scenario: &Scenario,
+    span: &Span,
+    seed: u64,
+    dir: &Path,
+) -> Result<usize> {
+    ensure!(span.step > Duration::zero(), "the step must be positive");
+    std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    let mut pet = Pet::new(&config.pet.name, span.start);
+    let mut runtime = Runtime::new(seed, &mut pet);
+    let mut carousel = Carousel::from_config(config)?;
+    let panel = PanelInfo::simulator();
+    let snapshot = Snapshot::default();
+    // Nothing's recorded into it, so the heatmap is a week with the panel
+    // off.
+    let tally = Tally::new(span.start.date_naive());
+    let mut frame = Frame::new(WIDTH, HEIGHT);
+    let mut manifest = Manifest { frames: Vec::new() };
+    let mut elapsed = Duration::zero();
+    let mut planner = quiet::Planner::default();
+
+    while elapsed <= span.length {
+        let clock = Clock::frozen(zone, span.start + elapsed);
+        let now = clock.now();
+        let quiet_hours = planner.get(config, &clock, now);
+        let asleep = quiet_hours.is_some_and(|quiet| quiet.contains(now.time()));
+        let bedtime = quiet::is_bedtime(quiet_hours, now.time());
+        pet.tick(now.to_utc(), &clock, &config.pet, bedtime);
+        runtime.advance(&pet);
+        let misbehaved = runtime.misbehavior_due(&pet, now.to_utc());
+        if misbehaved {
+            let event = pet.misbehave(now.to_utc());
+            runtime.on_event(event);
+        }
+        let previous = elapsed - span.step;
+        for action in scenario.due(previous, elapsed, misbehaved) {
+            if !pet.is_dead() {
+                let reaction = pet.apply(action);
+                runtime.react(&pet.name, reaction);
+            }
+        }
+
+        let ctx = Context {
+            now,
+            clock: &clock,
+            config,
+            pet: &pet,
+            runtime: &runtime,
+            panel: &panel,
+            snapshot: &snapshot,
+            tally: &tally,
+            quiet: quiet_hours,
+        };
+        let screen: &dyn Screen = if asleep {
+            &SleepScreen
+        } else {
+            carousel.current()
+        };
+        frame.clear();
+        screen.draw(&ctx, &mut frame);
+        // Alternates like on the panel, one colour per frame.
+        let border = overlay::is_critical(&pet).then_some(match manifest.frames.len() % 2 {
+            0 => Color::Red,
+            _ => Color::Black,
+        });
+        let decorate = |output: &mut Frame| {
+            if let Some(color) = border {
+                overlay::draw_border(output, color);
+            }
+        };
+        let output = display::compose(&frame, decorate, Point::zero(), config.mirror());
+        let file = format!("frame-{:05}.png", manifest.frames.len());
+        display::write_png(&output, &dir.join(&file))?;
+        manifest.frames.push(RecordedFrame {
+            file,
+            at: now.to_utc(),
+        });
+        if !asleep {
+            carousel.advance();
+        }
+        elapsed += span.step;
+    }
+
+    let path = dir.join(MANIFEST);
+    std::fs::write(&path, serde_json::to_string_pretty(&manifest)?)
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(manifest.frames.len())
+}
+
+/// Assembles the recording in `dir` into an animated GIF at `output`. Each
+/// frame stays up for its share of virtual time, divided by `speedup`;
+/// runs of identical frames are merged into one.
+#[cfg(feature = "render-gif")]
+pub fn render_gif(dir: &Path, output: &Path, speedup: f64) -> Result<usize> {
+    use std::borrow::Cow;
+    use std::fs::File;
+    use std::io::BufWriter;
+
+    ensure!(speedup > 0.0, "the speedup must be positive");
+    let path = dir.join(MANIFEST);
+    let manifest: Manifest = serde_json::from_str(
+        &std::fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?,
+    )
+    .with_context(|| format!("parsing {}", path.display()))?;
+    ensure!(
+        !manifest.frames.is_empty(),
+        "{} lists no frames",
+        path.display()
+    );
+
+    // Frames as palette indices, with how long each is shown for.
+    let mut frames: Vec<(Vec<u8>, Duration)> = Vec::new();
+    for (index, recorded) in manifest.frames.iter().enumerate() {
+        let pixels = read_indexed(&dir.join(&recorded.file))?;
+        let shown = match manifest.frames.get(index + 1) {
+            Some(next) => next.at - recorded.at,
+            // The last frame gets as long as the one before it.
+            None if index > 0 => recorded.at - manifest.frames[index - 1].at,
+            None => Duration::seconds(1),
+        };
+        match frames.last_mut() {
+            Some((last, length)) if *last == pixels => *length += shown,
+            _ => frames.push((pixels, shown)),
+        }
+    }
+
+    let file = File::create(output).with_context(|| format!("creating {}", output.display()))?;
+    let mut encoder = gif::Encoder::new(
+        BufWriter::new(file),
+        WIDTH as u16,
+        HEIGHT as u16,
+        &PALETTE.concat(),
+    )?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+    for (pixels, shown) in &frames {
+        let centis = shown.num_milliseconds() as f64 / speedup / 10.0;
+        // Viewers treat anything shorter than this as a tenth of a second.
+        let delay = centis.round().clamp(2.0, u16::MAX as f64) as u16;
+        encoder.write_frame(&gif::Frame {
+            width: WIDTH as u16,
+            height: HEIGHT as u16,
+            delay,
+            buffer: Cow::Borrowed(pixels),
+            ..gif::Frame::default()
+        })?;
+    }
+    Ok(frames.len())
+}
+
+/// White, black and red, as [`display::write_png`] writes them, and the
+/// greys of shaded artwork.
+#[cfg(feature = "render-gif")]
+const PALETTE: [[u8; 3]; 5] = [
+    display::WHITE,
+    display::BLACK,
+    display::RED,
+    display::LIGHT_GREY,
+    display::DARK_GREY,
+];
+
+/// Reads back a recorded frame as indices into [`PALETTE`].
+#[cfg(feature = "render-gif")]
+fn read_indexed(path: &Path) -> Result<Vec<u8>> {
+    let file = std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut reader = png::Decoder::new(std::io::BufReader::new(file))
+        .read_info()
+        .with_context(|| format!("reading {}", path.display()))?;
+    let mut data = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let info = reader.next_frame(&mut data)?;
+    ensure!(
+        info.width == WIDTH && info.height == HEIGHT && info.color_type == png::ColorType::Rgb,
+        "{} isn't a recorded frame",
+        path.display()
+    );
+    data[..info.buffer_size()]
+        .chunks_exact(3)
+        .map(|rgb| {
+            PALETTE
+                .iter()
+                .position(|color| color == rgb)
+                .map(|index| index as u8)
+                .with_context(|| format!("{} has colours the panel can't show", path.display()))
+        })
+        .collect()
+}
@@ -0,0 +1,553 @@
+//! Recurring reminders from `[[reminders]]`, e.g. watering the plants on
+//! Tuesdays at 18:00, set with `at` and `days` or as a cron schedule, see
+//! [`parse_cron`]. One that goes off takes over the panel until someone
+//! acknowledges it, with a button or `ack` on the control socket. Left
+//! alone, it comes down after `reminder_minutes` and goes back up every
+//! `reminder_repeat_minutes` until it's acknowledged.
+//!
+//! When each reminder last went off and was acknowledged is kept in
+//! `reminders_file`, by its text. One still waiting goes back up after a
+//! restart, and one that fell due while the daemon wasn't running goes off
+//! once at startup, if it's no more than `reminder_missed_hours` late.
+//! Times come from [`Clock::next_occurrence_on`], so a reminder in the hour
+//! DST skips goes off an hour later, and one in the hour it repeats goes
+//! off the first time round.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, FixedOffset, NaiveTime, TimeDelta, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Clock;
+use crate::config::{Config, ReminderConfig};
+use crate::persist;
+
+/// What's saved for one reminder.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Record {
+    /// Times up to here have been dealt with: the reminder went off, or
+    /// was too late to bother with.
+    checked: DateTime<Utc>,
+    /// The time it last went off for.
+    #[serde(default)]
+    fired: Option<DateTime<Utc>>,
+    #[serde(default)]
+    acknowledged: Option<DateTime<Utc>>,
+}
+
+impl Record {
+    fn is_waiting(&self) -> bool {
+        self.fired
+            .is_some_and(|fired| self.acknowledged.is_none_or(|ack| ack < fired))
+    }
+}
+
+pub struct Reminders {
+    file: PathBuf,
+    reminders: Vec<ReminderConfig>,
+    /// By text, for each of `reminders`.
+    records: BTreeMap<String, Record>,
+    /// When each reminder that came down unacknowledged goes back up. Not
+    /// saved, so a restart puts them straight back up.
+    resting: BTreeMap<String, DateTime<Utc>>,
+    /// The one on the panel, as an index into `reminders`, and since when.
+    up: Option<(usize, DateTime<Utc>)>,
+    /// `None` keeps one up until it's acknowledged.
+    stays_up: Option<TimeDelta>,
+    repeat: TimeDelta,
+    missed: TimeDelta,
+}
+
+impl Reminders {
+    /// The reminders in `config`, with what's saved for them. One that's
+    /// new counts from `now`, so it doesn't go off for a time already
+    /// past.
+    pub fn load(config: &Config, now: DateTime<Utc>) -> Reminders {
+        let mut saved = load_records(&config.reminders_file);
+        let mut added = false;
+        let records = config
+            .reminders
+            .iter()
+            .map(|reminder| {
+                let text = reminder.text.trim().to_string();
+                let record = saved.remove(&text).unwrap_or_else(|| {
+                    added = true;
+                    Record {
+                        checked: now,
+                        fired: None,
+                        acknowledged: None,
+                    }
+                });
+                (text, record)
+            })
+            .collect();
+        let minutes = |minutes: u32| TimeDelta::minutes(minutes as i64);
+        let reminders = Reminders {
+            file: config.reminders_file.clone(),
+            reminders: config.reminders.clone(),
+            records,
+            resting: BTreeMap::new(),
+            up: None,
+            stays_up: (config.reminder_minutes > 0).then(|| minutes(config.reminder_minutes)),
+            repeat: minutes(config.reminder_repeat_minutes),
+            missed: TimeDelta::hours(config.reminder_missed_hours as i64),
+        };
+        // What's left in `saved` is for reminders taken out of the config.
+        if added || !saved.is_empty() {
+            reminders.save();
+        }
+        reminders
+    }
+
+    /// The reminder on the panel, and the time it went off for.
+    pub fn up(&self) -> Option<(&ReminderConfig, DateTime<Utc>)> {
+        let reminder = &self.reminders[self.up?.0];
+        let fired = self.record(reminder).fired?;
+        Some((reminder, fired))
+    }
+
+    /// Sets off whatever has fallen due by `now`, and puts up or takes
+    /// down what's on the panel. Returns whether that changed.
+    pub fn tick(&mut self, clock: &Clock, now: DateTime<FixedOffset>) -> bool {
+        let utc = now.to_utc();
+        let mut dirty = false;
+        for reminder in &self.reminders {
+            let text = reminder.text.trim();
+            let record = self
+                .records
+                .get_mut(text)
+                .expect("every reminder has a record");
+            let Some(due) = latest_due(clock, reminder, record.checked, now) else {
+                continue;
+            };
+            record.checked = due;
+            dirty = true;
+            if utc - due > self.missed {
+                log::info!(
+                    "reminder {text:?} was due at {}, too long ago to bring up",
+                    clock.localize(due).format("%a %H:%M")
+                );
+                continue;
+            }
+            log::info!("reminder {text:?} is due");
+            record.fired = Some(due);
+            self.resting.remove(text);
+        }
+        if dirty {
+            self.save();
+        }
+
+        let before = self.up.map(|(index, _)| index);
+        if let Some((index, since)) = self.up {
+            if self.stays_up.is_some_and(|stays| utc >= since + stays) {
+                let text = self.reminders[index].text.trim();
+                log::info!(
+                    "reminder {text:?} came down unacknowledged, back in {} minutes",
+                    self.repeat.num_minutes()
+                );
+                self.resting.insert(text.to_string(), utc + self.repeat);
+                self.up = None;
+            }
+        }
+        if self.up.is_none() {
+            let next = self.reminders.iter().position(|reminder| {
+                let text = reminder.text.trim();
+                self.records[text].is_waiting()
+                    && self.resting.get(text).is_none_or(|back| utc >= *back)
+            });
+            if let Some(index) = next {
+                self.resting.remove(self.reminders[index].text.trim());
+                self.up = Some((index, utc));
+            }
+        }
+        self.up.map(|(index, _)| index) != before
+    }
+
+    /// Acknowledges the reminder on the panel, or with `all` every one
+    /// waiting. Returns whether there was any.
+    pub fn acknowledge(&mut self, now: DateTime<Utc>, all: bool) -> bool {
+        let up = self.up.take().map(|(index, _)| index);
+        let mut any = false;
+        for (index, reminder) in self.reminders.iter().enumerate() {
+            let text = reminder.text.trim();
+            let record = self
+                .records
+                .get_mut(text)
+                .expect("every reminder has a record");
+            if (all || up == Some(index)) && record.is_waiting() {
+                log::info!("reminder {text:?} acknowledged");
+                record.acknowledged = Some(now);
+                self.resting.remove(text);
+                any = true;
+            }
+        }
+        if any {
+            self.save();
+        }
+        any
+    }
+
+    /// The next time [`Reminders::tick`] has something to do, if ever.
+    pub fn next_change(&self, clock: &Clock, now: DateTime<FixedOffset>) -> Option<DateTime<Utc>> {
+        let due = self
+            .reminders
+            .iter()
+            .map(|r| clock.next_occurrence_on(now, r.at, &r.days).to_utc());
+        let down = self.up.and_then(|(_, since)| Some(since + self.stays_up?));
+        let back = self
+            .resting
+            .iter()
+            .filter(|(text, _)| self.records[text.as_str()].is_waiting())
+            .map(|(_, back)| *back);
+        due.chain(down).chain(back).min()
+    }
+
+    fn record(&self, reminder: &ReminderConfig) -> &Record {
+        &self.records[reminder.text.trim()]
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string_pretty(&self.records)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| persist::write_atomic(&self.file, text.as_bytes()));
+        if let Err(err) = result {
+            log::warn!("couldn't save the reminders: {err:#}");
+        }
+    }
+}
+
+/// The last time `reminder` was due after `checked`, up to `now`.
+fn latest_due(
+    clock: &Clock,
+    reminder: &ReminderConfig,
+    checked: DateTime<Utc>,
+    now: DateTime<FixedOffset>,
+) -> Option<DateTime<Utc>> {
+    // A reminder goes off at least once a week, so the last time it was
+    // due is in the last eight days, with a day's slack for DST.
+    let mut after = clock.localize(checked.max(now.to_utc() - TimeDelta::days(8)));
+    let mut latest = None;
+    loop {
+        let due = clock.next_occurrence_on(after, reminder.at, &reminder.days);
+        if due > now {
+            return latest;
+        }
+        latest = Some(due.to_utc());
+        after = due;
+    }
+}
+
+/// The time and days of a cron schedule, `minute hour day month weekday`,
+/// e.g. `0 18 * * tue`. A reminder goes off at one time on some days of
+/// the week, so the minute and hour have to be single numbers, and the
+/// day of the month and the month `*`. The weekday is `*`, or a list of
+/// days and ranges of them, by name or number from 0 for Sunday to 7 for
+/// Sunday again, e.g. `mon-fri` or `1,3,5`.
+pub fn parse_cron(text: &str) -> Result<(NaiveTime, Vec<Weekday>), String> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    let [minute, hour, day, month, weekday] = fields[..] else {
+        return Err(format!(
+            "cron {text:?} needs five fields: minute hour day month weekday"
+        ));
+    };
+    let number = |field: &str, name: &str, max: u32| {
+        field
+            .parse::<u32>()
+            .ok()
+            .filter(|n| *n <= max)
+            .ok_or_else(|| {
+                format!("cron {name} {field:?} has to be a single number from 0 to {max}")
+            })
+    };
+    let (minute, hour) = (number(minute, "minute", 59)?, number(hour, "hour", 23)?);
+    for (field, name) in [(day, "day of the month"), (month, "month")] {
+        if field != "*" {
+            return Err(format!("cron {name} {field:?} isn't supported, only *"));
+        }
+    }
+    let mut days = Vec::new();
+    if weekday != "*" {
+        for part in weekday.split(',') {
+            let (first, last) = part.split_once('-').unwrap_or((part, part));
+            let (first, last) = (parse_weekday(first)?, parse_weekday(last)?);
+            let mut day = first;
+            loop {
+                if !days.contains(&day) {
+                    days.push(day);
+                }
+                if day == last {
+                    break;
+                }
+                day = day.succ();
+            }
+        }
+        days.sort_by_key(|day| day.num_days_from_monday());
+    }
+    let at = NaiveTime::from_hms_opt(hour, minute, 0).expect("checked above");
+    Ok((at, days))
+}
+
+/// A cron weekday: a name, or a number with both 0 and 7 for Sunday.
+fn parse_weekday(text: &str) -> Result<Weekday, String> {
+    match text.parse::<u8>() {
+        Ok(0 | 7) => Ok(Weekday::Sun),
+        Ok(number @ 1..=6) => Ok(Weekday::try_from(number - 1).expect("in range")),
+        Ok(_) => Err(format!("cron weekday {text:?} isn't from 0 to 7")),
+        Err(_) => text
+            .parse()
+            .map_err(|_| format!("cron weekday {text:?} isn't a day, e.g. \"tue\" or 2")),
+    }
+}
+
+/// The records saved at `path`. An unreadable file is treated as none, so
+/// every reminder starts afresh.
+fn load_records(path: &Path) -> BTreeMap<String, Record> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return BTreeMap::new(),
+        Err(err) => {
+            log::warn!("reading {}: {err}", path.display());
+            return BTreeMap::new();
+        }
+    };
+    match serde_json::from_str(&text) {
+        Ok(records) => records,
+        Err(err) => {
+            log::warn!(
+                "ignoring unreadable reminders file {}: {err}",
+                path.display()
+            );
+            BTreeMap::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Zone;
+
+    /// A config with `reminders` and the state in a fresh directory.
+    fn config(reminders: &str) -> (tempfile::TempDir, Config) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config: Config = toml::from_str(reminders).unwrap();
+        config.reminders_file = dir.path().join("reminders.json");
+        (dir, config)
+    }
+
+    /// A clock stopped at `time` in Bucharest.
+    fn clock(time: &str) -> Clock {
+        let at = time.parse::<DateTime<FixedOffset>>().unwrap();
+        Clock::frozen(Zone::Named(chrono_tz::Europe::Bucharest), at.to_utc())
+    }
+
+    fn utc(time: &str) -> DateTime<Utc> {
+        time.parse::<DateTime<FixedOffset>>().unwrap().to_utc()
+    }
+
+    fn tick(reminders: &mut Reminders, time: &str) -> bool {
+        let clock = clock(time);
+        reminders.tick(&clock, clock.now())
+    }
+
+    fn next(reminders: &Reminders, time: &str) -> Option<String> {
+        let clock = clock(time);
+        let next = reminders.next_change(&clock, clock.now())?;
+        Some(clock.localize(next).to_rfc3339())
+    }
+
+    fn due(reminders: &Reminders) -> Option<String> {
+        reminders.up().map(|(_, due)| due.to_rfc3339())
+    }
+
+    #[test]
+    fn cron_schedules_are_times_on_days_of_the_week() {
+        use Weekday::*;
+        for (cron, at, days) in [
+            ("0 18 * * tue", "18:00", &[Tue][..]),
+            ("30 9 * * *", "09:30", &[]),
+            (" 5  0 * *  mon-fri ", "00:05", &[Mon, Tue, Wed, Thu, Fri]),
+            ("0 8 * * 0,6", "08:00", &[Sat, Sun]),
+            ("0 8 * * 7,1,monday", "08:00", &[Mon, Sun]),
+            ("59 23 * * fri-mon", "23:59", &[Mon, Fri, Sat, Sun]),
+        ] {
+            let at = NaiveTime::parse_from_str(at, "%H:%M").unwrap();
+            assert_eq!(parse_cron(cron), Ok((at, days.to_vec())), "{cron}");
+        }
+    }
+
+    #[test]
+    fn cron_schedules_a_reminder_cannot_keep_are_errors() {
+        for (cron, error) in [
+            ("0 18 * *", "needs five fields"),
+            (
+                "*/5 * * * *",
+                "cron minute \"*/5\" has to be a single number",
+            ),
+            (
+                "0 9,21 * * *",
+                "cron hour \"9,21\" has to be a single number",
+            ),
+            ("60 9 * * *", "from 0 to 59"),
+            (
+                "0 18 1 * *",
+                "cron day of the month \"1\" isn't supported, only *",
+            ),
+            ("0 18 * 5 *", "cron month \"5\" isn't supported"),
+            ("0 18 * * 8", "cron weekday \"8\" isn't from 0 to 7"),
+            ("0 18 * * someday", "cron weekday \"someday\" isn't a day"),
+        ] {
+            let err = parse_cron(cron).unwrap_err();
+            assert!(err.contains(error), "{cron}: {err}");
+        }
+    }
+
+    #[test]
+    fn the_config_takes_at_or_cron() {
+        let (_dir, config) =
+            config("[[reminders]]\ntext = \"Water the plants\"\ncron = \"0 18 * * tue,sat\"");
+        let reminder = &config.reminders[0];
+        assert_eq!(reminder.at, NaiveTime::from_hms_opt(18, 0, 0).unwrap());
+        assert_eq!(reminder.days, [Weekday::Tue, Weekday::Sat]);
+
+        for (fields, error) in [
+            (
+                "cron = \"0 18 1 * *\"",
+                "day of the month \"1\" isn't supported",
+            ),
+            (
+                "at = \"18:00\"\ncron = \"0 18 * * *\"",
+                "either at or cron, not both",
+            ),
+            (
+                "cron = \"0 18 * * *\"\ndays = [\"tue\"]",
+                "days can't go with cron",
+            ),
+            ("icon = \"leaf\"", "needs at, e.g. \"18:00\", or cron"),
+            ("at = \"6pm\"", "expected HH:MM, got \"6pm\""),
+            (
+                "at = \"18:00\"\nschedule = \"weekly\"",
+                "unknown field `schedule`",
+            ),
+        ] {
+            let toml = format!("[[reminders]]\ntext = \"Water the plants\"\n{fields}");
+            let err = toml::from_str::<Config>(&toml).unwrap_err().to_string();
+            assert!(err.contains(error), "{fields}: {err}");
+        }
+    }
+
+    #[test]
+    fn one_in_the_hour_dst_skips_goes_off_an_hour_later() {
+        // The clocks go from 03:00 to 04:00 on Sunday 31 March 2024.
+        let (_dir, config) = config("[[reminders]]\ntext = \"Plants\"\ncron = \"30 3 * * sun\"");
+        let mut reminders = Reminders::load(&config, utc("2024-03-30T12:00:00+02:00"));
+        let expected = "2024-03-31T04:30:00+03:00";
+        assert_eq!(
+            next(&reminders, "2024-03-31T02:59:59+02:00").as_deref(),
+            Some(expected)
+        );
+        assert!(!tick(&mut reminders, "2024-03-31T04:29:59+03:00"));
+        assert!(tick(&mut reminders, expected));
+        assert_eq!(due(&reminders), Some(utc(expected).to_rfc3339()));
+    }
+
+    #[test]
+    fn one_in_the_hour_dst_repeats_goes_off_the_first_time_round() {
+        // 03:00 to 04:00 happens twice on Sunday 27 October 2024.
+        let (_dir, config) = config("[[reminders]]\ntext = \"Plants\"\ncron = \"30 3 * * sun\"");
+        let mut reminders = Reminders::load(&config, utc("2024-10-26T12:00:00+03:00"));
+        assert!(tick(&mut reminders, "2024-10-27T03:30:00+03:00"));
+        assert!(reminders.acknowledge(utc("2024-10-27T03:31:00+03:00"), false));
+        assert!(!tick(&mut reminders, "2024-10-27T03:30:00+02:00"));
+        assert_eq!(due(&reminders), None);
+        assert_eq!(
+            next(&reminders, "2024-10-27T03:30:00+02:00").as_deref(),
+            Some("2024-11-03T03:30:00+02:00")
+        );
+    }
+
+    #[test]
+    fn weekly_reminders_cross_the_week_boundary() {
+        let (_dir, config) = config(
+            "[[reminders]]\ntext = \"Bins\"\ncron = \"0 0 * * mon\"\n\
+             [[reminders]]\ntext = \"Call home\"\nat = \"23:59\"\ndays = [\"sun\"]",
+        );
+        // Saturday night.
+        let mut reminders = Reminders::load(&config, utc("2024-06-29T22:00:00+03:00"));
+        assert_eq!(
+            next(&reminders, "2024-06-29T22:00:00+03:00").as_deref(),
+            Some("2024-06-30T23:59:00+03:00")
+        );
+        assert!(tick(&mut reminders, "2024-06-30T23:59:00+03:00"));
+        assert_eq!(reminders.up().unwrap().0.text, "Call home");
+        reminders.acknowledge(utc("2024-06-30T23:59:30+03:00"), false);
+        assert_eq!(
+            next(&reminders, "2024-06-30T23:59:30+03:00").as_deref(),
+            Some("2024-07-01T00:00:00+03:00")
+        );
+        assert!(tick(&mut reminders, "2024-07-01T00:00:00+03:00"));
+        assert_eq!(reminders.up().unwrap().0.text, "Bins");
+        reminders.acknowledge(utc("2024-07-01T00:01:00+03:00"), false);
+        // Next from Monday is a week on for the bins and six days for the
+        // call home.
+        assert_eq!(
+            next(&reminders, "2024-07-01T00:01:00+03:00").as_deref(),
+            Some("2024-07-07T23:59:00+03:00")
+        );
+    }
+
+    #[test]
+    fn left_alone_it_comes_down_and_back_until_acknowledged() {
+        let (_dir, config) = config("[[reminders]]\ntext = \"Tablets\"\nat = \"09:00\"");
+        let mut reminders = Reminders::load(&config, utc("2024-06-01T08:00:00+03:00"));
+        assert!(tick(&mut reminders, "2024-06-01T09:00:00+03:00"));
+        assert_eq!(
+            next(&reminders, "2024-06-01T09:00:00+03:00").as_deref(),
+            Some("2024-06-01T09:10:00+03:00")
+        );
+        assert!(!tick(&mut reminders, "2024-06-01T09:09:59+03:00"));
+        assert!(tick(&mut reminders, "2024-06-01T09:10:00+03:00"));
+        assert_eq!(due(&reminders), None);
+        assert_eq!(
+            next(&reminders, "2024-06-01T09:10:00+03:00").as_deref(),
+            Some("2024-06-01T09:40:00+03:00")
+        );
+        assert!(tick(&mut reminders, "2024-06-01T09:40:00+03:00"));
+        assert_eq!(
+            due(&reminders),
+            Some(utc("2024-06-01T09:00:00+03:00").to_rfc3339())
+        );
+        assert!(reminders.acknowledge(utc("2024-06-01T09:45:00+03:00"), false));
+        assert!(!reminders.acknowledge(utc("2024-06-01T09:46:00+03:00"), true));
+        assert!(!tick(&mut reminders, "2024-06-01T10:20:00+03:00"));
+    }
+
+    #[test]
+    fn one_missed_while_off_goes_off_once_if_it_is_recent() {
+        let (_dir, config) = config("[[reminders]]\ntext = \"Bins\"\ncron = \"0 0 * * mon\"");
+        Reminders::load(&config, utc("2024-06-20T12:00:00+03:00"));
+
+        // Off for over a week: only the last Monday counts, 8 hours late.
+        let mut reminders = Reminders::load(&config, utc("2024-07-01T08:00:00+03:00"));
+        assert!(tick(&mut reminders, "2024-07-01T08:00:00+03:00"));
+        assert_eq!(
+            due(&reminders),
+            Some(utc("2024-07-01T00:00:00+03:00").to_rfc3339())
+        );
+
+        // Still waiting after a restart.
+        let mut reminders = Reminders::load(&config, utc("2024-07-01T08:05:00+03:00"));
+        assert!(tick(&mut reminders, "2024-07-01T08:05:00+03:00"));
+        reminders.acknowledge(utc("2024-07-01T08:06:00+03:00"), true);
+
+        // The next Monday's is too late by the afternoon, and let go.
+        let mut reminders = Reminders::load(&config, utc("2024-07-08T13:00:00+03:00"));
+        assert!(!tick(&mut reminders, "2024-07-08T13:00:00+03:00"));
+        assert_eq!(due(&reminders), None);
+        let record = &reminders.records["Bins"];
+        assert_eq!(record.checked, utc("2024-07-08T00:00:00+03:00"));
+        assert_eq!(record.fired, Some(utc("2024-07-01T00:00:00+03:00")));
+    }
+}
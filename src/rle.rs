@@ -0,0 +1,260 @@
+//! Planes of bytes kept run-length encoded, for frames that have to be
+//! remembered but are mostly paper: each plane comes down to long runs of
+//! `0x00`, with some `0xff` where there's ink. See
+//! [`crate::display::PackedFrame`].
+//!
+//! Packed planes are compared with live ones without unpacking them. Each
+//! row's bytes across the planes are also hashed, so rows that match are
+//! skipped without going through their runs. A 64-bit hash colliding
+//! would only keep a changed row off the panel until the next change to
+//! it.
+
+/// Planes of the same size, `stride` bytes to a row, as runs of one byte.
+#[derive(Clone, Debug)]
+pub struct Packed {
+    stride: usize,
+    /// Each plane's runs, by length and value. Runs go on over the ends of
+    /// rows.
+    planes: Vec<Vec<(u8, u8)>>,
+    rows: Vec<u64>,
+}
+
+impl Packed {
+    pub fn new(planes: &[&[u8]], stride: usize) -> Self {
+        let packed = planes
+            .iter()
+            .map(|plane| {
+                let mut runs: Vec<(u8, u8)> = Vec::new();
+                for &byte in plane.iter() {
+                    match runs.last_mut() {
+                        Some((length, value)) if *value == byte && *length < u8::MAX => {
+                            *length += 1
+                        }
+                        _ => runs.push((1, byte)),
+                    }
+                }
+                runs.shrink_to_fit();
+                runs
+            })
+            .collect();
+        let height = planes
+            .first()
+            .map_or(0, |plane| plane.len() / stride.max(1));
+        Packed {
+            stride,
+            planes: packed,
+            rows: (0..height)
+                .map(|row| row_hash(planes, stride, row))
+                .collect(),
+        }
+    }
+
+    /// The planes in full again.
+    pub fn unpack(&self) -> Vec<Vec<u8>> {
+        self.planes
+            .iter()
+            .map(|runs| {
+                runs.iter()
+                    .flat_map(|&(length, value)| std::iter::repeat_n(value, length as usize))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Whether `planes` are the planes packed.
+    pub fn matches(&self, planes: &[&[u8]]) -> bool {
+        planes.len() == self.planes.len()
+            && planes
+                .iter()
+                .all(|plane| plane.len() == self.rows.len() * self.stride)
+            && (0..self.rows.len()).all(|row| self.rows[row] == row_hash(planes, self.stride, row))
+    }
+
+    /// The first and last column and row of bytes that differ between the
+    /// planes packed and `planes`, as `(column, row, column, row)`. `None`
+    /// when they're the same. `planes` have to be the same size.
+    pub fn dirty(&self, planes: &[&[u8]]) -> Option<(usize, usize, usize, usize)> {
+        let stride = self.stride;
+        let mut cursors: Vec<Runs> = self.planes.iter().map(|runs| Runs::new(runs)).collect();
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        for row in 0..self.rows.len() {
+            if self.rows[row] == row_hash(planes, stride, row) {
+                cursors.iter_mut().for_each(|cursor| cursor.skip(stride));
+                continue;
+            }
+            for (cursor, plane) in cursors.iter_mut().zip(planes) {
+                for col in 0..stride {
+                    if cursor.next() == Some(plane[row * stride + col]) {
+                        continue;
+                    }
+                    bounds = Some(match bounds {
+                        None => (col, row, col, row),
+                        Some((c0, r0, c1, r1)) => {
+                            (c0.min(col), r0.min(row), c1.max(col), r1.max(row))
+                        }
+                    });
+                }
+            }
+        }
+        bounds
+    }
+
+    /// Bytes taken by the runs and row hashes.
+    pub fn footprint(&self) -> usize {
+        let runs: usize = self.planes.iter().map(Vec::len).sum();
+        runs * size_of::<(u8, u8)>() + self.rows.len() * size_of::<u64>()
+    }
+}
+
+/// FNV-1a over row `row` of each of `planes`.
+fn row_hash(planes: &[&[u8]], stride: usize, row: usize) -> u64 {
+    planes
+        .iter()
+        .flat_map(|plane| &plane[row * stride..(row + 1) * stride])
+        .fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Reads a packed plane a byte at a time, without unpacking it.
+struct Runs<'a> {
+    runs: &'a [(u8, u8)],
+    /// Bytes of the first run already read.
+    taken: u8,
+}
+
+impl<'a> Runs<'a> {
+    fn new(runs: &'a [(u8, u8)]) -> Self {
+        Runs { runs, taken: 0 }
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let &(length, value) = self.runs.first()?;
+        self.taken += 1;
+        if self.taken == length {
+            self.runs = &self.runs[1..];
+            self.taken = 0;
+        }
+        Some(value)
+    }
+
+    /// Passes over `n` bytes, a run at a time.
+    fn skip(&mut self, mut n: usize) {
+        while let Some(&(length, _)) = self.runs.first() {
+            let left = (length - self.taken) as usize;
+            if n < left {
+                self.taken += n as u8;
+                return;
+            }
+            n -= left;
+            self.runs = &self.runs[1..];
+            self.taken = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// The first and last differing bytes, a byte at a time.
+    fn naive(
+        old: &[Vec<u8>],
+        new: &[Vec<u8>],
+        stride: usize,
+    ) -> Option<(usize, usize, usize, usize)> {
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        for (a, b) in old.iter().zip(new) {
+            for (i, _) in a.iter().zip(b).enumerate().filter(|(_, (a, b))| a != b) {
+                let (col, row) = (i % stride, i / stride);
+                bounds = Some(match bounds {
+                    None => (col, row, col, row),
+                    Some((c0, r0, c1, r1)) => (c0.min(col), r0.min(row), c1.max(col), r1.max(row)),
+                });
+            }
+        }
+        bounds
+    }
+
+    fn refs(planes: &[Vec<u8>]) -> Vec<&[u8]> {
+        planes.iter().map(Vec::as_slice).collect()
+    }
+
+    /// Bytes mostly `0x00` and `0xff` in long runs, as frames are, with
+    /// anything else now and then.
+    fn byte() -> impl Strategy<Value = u8> {
+        prop_oneof![8 => Just(0x00), 3 => Just(0xff), 1 => any::<u8>()]
+    }
+
+    /// A plane of `len` bytes in runs of up to 600, past what one run
+    /// holds.
+    fn plane(len: usize) -> impl Strategy<Value = Vec<u8>> {
+        prop::collection::vec((byte(), 1..600usize), 1..12).prop_map(move |runs| {
+            let mut plane: Vec<u8> = runs
+                .into_iter()
+                .flat_map(|(value, length)| std::iter::repeat_n(value, length))
+                .take(len)
+                .collect();
+            plane.resize(len, 0);
+            plane
+        })
+    }
+
+    /// A change to make: which plane, which byte and what to.
+    type Change = (prop::sample::Index, prop::sample::Index, u8);
+
+    /// Up to three planes of the same size, with their stride, and changes
+    /// to make to them.
+    fn buffers() -> impl Strategy<Value = (usize, Vec<Vec<u8>>, Vec<Change>)> {
+        (1..=30usize, 1..=110usize, 1..=3usize).prop_flat_map(|(stride, height, count)| {
+            let change = (
+                any::<prop::sample::Index>(),
+                any::<prop::sample::Index>(),
+                byte(),
+            );
+            (
+                Just(stride),
+                prop::collection::vec(plane(stride * height), count),
+                prop::collection::vec(change, 0..6),
+            )
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn planes_round_trip((stride, planes, _) in buffers()) {
+            let packed = Packed::new(&refs(&planes), stride);
+            prop_assert_eq!(&packed.unpack(), &planes);
+            prop_assert!(packed.matches(&refs(&planes)));
+            prop_assert_eq!(packed.dirty(&refs(&planes)), None);
+        }
+
+        #[test]
+        fn dirty_is_the_naive_comparison((stride, old, changes) in buffers()) {
+            let mut new = old.clone();
+            for (plane, at, value) in changes {
+                let plane = &mut new[plane.index(old.len())];
+                let at = at.index(plane.len());
+                plane[at] = value;
+            }
+            let packed = Packed::new(&refs(&old), stride);
+            prop_assert_eq!(packed.dirty(&refs(&new)), naive(&old, &new, stride));
+            prop_assert_eq!(packed.matches(&refs(&new)), old == new);
+        }
+    }
+
+    #[test]
+    fn paper_packs_small() {
+        let paper = vec![0u8; 27 * 104];
+        let packed = Packed::new(&[&paper, &paper, &paper], 27);
+        assert_eq!(
+            packed.planes.iter().map(Vec::len).collect::<Vec<_>>(),
+            [12, 12, 12]
+        );
+        // Most of what's left is the row hashes.
+        assert_eq!(packed.footprint(), 3 * 12 * 2 + 104 * 8);
+        assert!(!packed.matches(&[&paper, &paper]));
+        assert!(!packed.matches(&[&paper, &paper, &paper[27..]]));
+    }
+}
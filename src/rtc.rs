@@ -0,0 +1,199 @@
+//! DS3231 real-time clock on I2C.
+//!
+//! The Pi has no clock of its own, so after a power cut without network the
+//! system time is whatever it last saved, and the pet would think time stood
+//! still or ran backwards. At startup the RTC is read and, when NTP hasn't
+//! synchronised yet and the two disagree, used as the time source. Once NTP
+//! is synchronised, system time takes over and is written back to the RTC.
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Context as _, Result};
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+use i2cdev::core::I2CDevice;
+use i2cdev::linux::LinuxI2CDevice;
+
+use crate::clock::{Clock, Zone};
+use crate::collector::{Snapshot, Source};
+use crate::config::RtcConfig;
+
+const ADDRESS: u16 = 0x68;
+const TIME_REGISTER: u8 = 0x00;
+const STATUS_REGISTER: u8 = 0x0f;
+/// Set when the oscillator stopped, e.g. the backup battery ran flat.
+const OSCILLATOR_STOPPED: u8 = 0x80;
+
+/// How often to look for NTP synchronisation until it happens.
+const SYNC_POLL_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// How often to write system time back once synchronised, to correct drift.
+const WRITE_BACK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub struct Ds3231 {
+    device: LinuxI2CDevice,
+}
+
+impl Ds3231 {
+    pub fn open(bus: &Path) -> Result<Self> {
+        let device = LinuxI2CDevice::new(bus, ADDRESS)
+            .with_context(|| format!("opening DS3231 on {}", bus.display()))?;
+        Ok(Ds3231 { device })
+    }
+
+    /// The time kept by the RTC, which is always set to UTC.
+    pub fn read(&mut self) -> Result<DateTime<Utc>> {
+        let status = self.device.smbus_read_byte_data(STATUS_REGISTER)?;
+        ensure!(
+            status & OSCILLATOR_STOPPED == 0,
+            "RTC oscillator stopped, time is invalid"
+        );
+        let regs = self.device.smbus_read_i2c_block_data(TIME_REGISTER, 7)?;
+        ensure!(regs.len() == 7, "short read from RTC");
+        let century = if regs[5] & 0x80 != 0 { 2100 } else { 2000 };
+        let date = NaiveDate::from_ymd_opt(
+            century + from_bcd(regs[6]) as i32,
+            from_bcd(regs[5] & 0x1f),
+            from_bcd(regs[4] & 0x3f),
+        );
+        // Hours are in 24-hour mode, which is how `write` sets them.
+        let time = date.and_then(|date| {
+            date.and_hms_opt(
+                from_bcd(regs[2] & 0x3f),
+                from_bcd(regs[1] & 0x7f),
+                from_bcd(regs[0] & 0x7f),
+            )
+        });
+        let time = time.with_context(|| format!("RTC holds an invalid time: {regs:02x?}"))?;
+        Ok(time.and_utc())
+    }
+
+    pub fn write(&mut self, at: DateTime<Utc>) -> Result<()> {
+        let century = if at.year() >= 2100 { 0x80 } else { 0 };
+        let regs = [
+            to_bcd(at.second()),
+            to_bcd(at.minute()),
+            to_bcd(at.hour()),
+            to_bcd(at.weekday().number_from_monday()),
+            to_bcd(at.day()),
+            to_bcd(at.month()) | century,
+            to_bcd(at.year().rem_euclid(100) as u32),
+        ];
+        self.device
+            .smbus_write_i2c_block_data(TIME_REGISTER, &regs)?;
+        let status = self.device.smbus_read_byte_data(STATUS_REGISTER)?;
+        self.device
+            .smbus_write_byte_data(STATUS_REGISTER, status & !OSCILLATOR_STOPPED)?;
+        Ok(())
+    }
+}
+
+fn from_bcd(byte: u8) -> u32 {
+    (byte >> 4) as u32 * 10 + (byte & 0x0f) as u32
+}
+
+fn to_bcd(value: u32) -> u8 {
+    (((value / 10) << 4) | (value % 10)) as u8
+}
+
+/// Whether systemd-timesyncd (or another NTP client) has synchronised the
+/// system clock, or `None` if `timedatectl` can't tell us.
+pub fn ntp_synchronized() -> Option<bool> {
+    let output = Command::new("timedatectl")
+        .args(["show", "--property=NTPSynchronized", "--value"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim() == "yes")
+}
+
+/// Picks the clock to run on. Returns the system clock unless the RTC can
+/// be read, NTP isn't synchronised, and the two are more than
+/// `config.max_drift` apart; then the RTC's time is used, counting on from
+/// startup, until `synced` is set.
+pub fn startup_clock(config: &RtcConfig, zone: Zone, synced: Arc<AtomicBool>) -> Clock {
+    if !config.enabled {
+        return Clock::new(zone);
+    }
+    let rtc_time = match Ds3231::open(&config.bus).and_then(|mut rtc| rtc.read()) {
+        Ok(time) => time,
+        Err(err) => {
+            log::warn!("couldn't read RTC: {err:#}");
+            return Clock::new(zone);
+        }
+    };
+    if ntp_synchronized().unwrap_or(false) {
+        return Clock::new(zone);
+    }
+    let system_time = Utc::now();
+    let drift = (system_time - rtc_time).abs().to_std().unwrap_or_default();
+    if drift <= Duration::from_secs(config.max_drift) {
+        return Clock::new(zone);
+    }
+    log::warn!(
+        "system time {system_time} disagrees with the RTC's {rtc_time} and NTP \
+         isn't synchronised; using the RTC"
+    );
+    let started = Instant::now();
+    Clock::with_source(zone, move || {
+        if synced.load(Ordering::Relaxed) {
+            Utc::now()
+        } else {
+            rtc_time + started.elapsed()
+        }
+    })
+}
+
+/// Waits for NTP synchronisation, then hands time keeping back to the system
+/// clock and writes it to the RTC, daily after that to correct drift.
+pub struct RtcSource {
+    rtc: Ds3231,
+    synced: Arc<AtomicBool>,
+}
+
+impl RtcSource {
+    pub fn new(config: &RtcConfig, synced: Arc<AtomicBool>) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        match Ds3231::open(&config.bus) {
+            Ok(rtc) => Some(RtcSource { rtc, synced }),
+            Err(err) => {
+                // Already reported by `startup_clock`.
+                log::debug!("RTC unavailable: {err:#}");
+                None
+            }
+        }
+    }
+}
+
+impl Source for RtcSource {
+    fn name(&self) -> &'static str {
+        "rtc"
+    }
+
+    fn interval(&self) -> Duration {
+        if self.synced.load(Ordering::Relaxed) {
+            WRITE_BACK_INTERVAL
+        } else {
+            SYNC_POLL_INTERVAL
+        }
+    }
+
+    fn collect(&mut self, _snapshot: &Mutex<Snapshot>) {
+        if !ntp_synchronized().unwrap_or(false) {
+            return;
+        }
+        if !self.synced.swap(true, Ordering::Relaxed) {
+            log::info!("NTP synchronised, using system time");
+        }
+        match self.rtc.write(Utc::now()) {
+            Ok(()) => log::info!("wrote system time to the RTC"),
+            Err(err) => log::warn!("couldn't set the RTC: {err:#}"),
+        }
+    }
+}
@@ -0,0 +1,496 @@
+//! State that lives only as long as the process: things that make the
+//! display feel alive but aren't worth saving.
+
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use embedded_graphics::prelude::Point;
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+
+use crate::artwork;
+use crate::chat::Talk;
+use crate::clock::Clock;
+use crate::display::Color;
+use crate::events::{PetEvent, SystemEvent};
+use crate::guest::Privacy;
+use crate::journal::{self, Journal};
+use crate::lowmem::Quality;
+use crate::pet::{Pet, Reaction, SideEffect};
+use crate::reactions::{self, Face, Flag};
+use crate::scheduler::Power;
+use crate::screens::pet as layout;
+use crate::sprite::{self, Flip, Sprite};
+
+/// Fastest the pet walks, in pixels per refresh.
+const MAX_SPEED: f32 = 6.0;
+/// Chance per refresh that the pet changes its pace.
+const TURN_CHANCE: f64 = 0.3;
+/// Chance that the pet stops when it passes its favourite spot.
+const LINGER_CHANCE: f64 = 0.5;
+
+/// Minutes between bouts of misbehaviour.
+const MISBEHAVIOR_GAP: std::ops::RangeInclusive<i64> = 3 * 60..=8 * 60;
+
+/// Minutes between the pet bringing up something from its journal.
+const MEMORY_GAP: std::ops::RangeInclusive<i64> = 60..=3 * 60;
+/// How long it keeps talking about it.
+const MEMORY_SHOWN: Duration = Duration::minutes(10);
+/// How long an answer to something said stays in the bubble.
+const REPLY_SHOWN: Duration = Duration::minutes(5);
+
+/// Refreshes a freshly spawned particle stays on screen.
+const PARTICLE_LIFETIME: u32 = 3;
+/// Pixels a particle rises per refresh.
+const PARTICLE_RISE: i32 = 5;
+
+pub struct Runtime {
+    pub rng: StdRng,
+    pub walker: Walker,
+    pub particles: Vec<Particle>,
+    /// Whether the debug overlay is showing. Deliberately not saved, so a
+    /// restart always comes back to the normal display.
+    pub debug_overlay: bool,
+    /// Colour of the alert border on the last refresh, while it's up.
+    pub alert_border: Option<Color>,
+    /// Page of the logs screen, counting back from the newest.
+    pub log_page: usize,
+    /// How much of the rendering memory leaves room for, as of the last
+    /// cycle.
+    pub quality: Quality,
+    /// What the host ran on last cycle.
+    pub power: Power,
+    /// Whether refreshes are being batched for the battery, see
+    /// [`crate::scheduler`].
+    pub batching: bool,
+    /// Whether the carousel is pinned to the screen showing.
+    pub pinned: bool,
+    /// Whether guest mode is on, see [`crate::guest`].
+    pub guest: bool,
+    /// Who last logged in to the host, see [`crate::server`].
+    pub visitor: Option<String>,
+    /// Host conditions currently worrying the pet, oldest first.
+    pub flags: Vec<Flag>,
+    /// A passing expression and the refreshes it has left.
+    face: Option<(Face, u32)>,
+    /// When the pet will next misbehave; picked afresh on every start.
+    next_misbehavior: Option<DateTime<Utc>>,
+    /// What the pet is reminiscing about, and until when.
+    memory: Option<(String, DateTime<Utc>)>,
+    next_memory: Option<DateTime<Utc>>,
+    /// The pet's answer to the last thing said to it, how private it is,
+    /// and until when.
+    reply: Option<(String, Privacy, DateTime<Utc>)>,
+}
+
+impl Runtime {
+    pub fn new(seed: u64, pet: &mut Pet) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (min, max) = layout::walk_range();
+        if pet.favorite_spot.is_none() {
+            let spot = rng.random_range(min..=max);
+            log::info!("{} picked a favourite spot at x={spot}", pet.name);
+            pet.favorite_spot = Some(spot);
+        }
+        let start = pet.favorite_spot.unwrap_or(min) as f32;
+        Runtime {
+            rng,
+            walker: Walker::new(start),
+            particles: Vec::new(),
+            debug_overlay: false,
+            alert_border: None,
+            log_page: 0,
+            quality: Quality::Full,
+            power: Power::Mains,
+            batching: false,
+            pinned: false,
+            guest: false,
+            visitor: None,
+            flags: Vec::new(),
+            face: None,
+            next_misbehavior: None,
+            memory: None,
+            next_memory: None,
+            reply: None,
+        }
+    }
+
+    /// Whether it's time for the pet to misbehave again. The gaps are
+    /// random but come from the seeded RNG, so runs replay exactly.
+    pub fn misbehavior_due(&mut self, pet: &Pet, now: DateTime<Utc>) -> bool {
+        if pet.is_misbehaving() || pet.is_dead() {
+            return false;
+        }
+        let due = self.next_misbehavior.is_some_and(|at| at <= now);
+        if due || self.next_misbehavior.is_none() {
+            let gap = self.rng.random_range(MISBEHAVIOR_GAP);
+            self.next_misbehavior = Some(now + Duration::minutes(gap));
+        }
+        due
+    }
+
+    /// Every hour or few, has the pet bring up something from `journal`
+    /// for a while. Like misbehaving, when comes from the seeded RNG.
+    pub fn reminisce(&mut self, journal: &Journal, clock: &Clock, now: DateTime<FixedOffset>) {
+        let utc = now.to_utc();
+        self.memory = self.memory.take().filter(|(_, until)| *until > utc);
+        let due = self.next_memory.is_some_and(|at| at <= utc);
+        if due {
+            self.memory = journal::recall(journal, clock, now, &mut self.rng)
+                .map(|text| (text, utc + MEMORY_SHOWN));
+        }
+        if due || self.next_memory.is_none() {
+            let gap = self.rng.random_range(MEMORY_GAP);
+            self.next_memory = Some(utc + Duration::minutes(gap));
+        }
+    }
+
+    /// What the pet is reminiscing about right now, if anything.
+    pub fn memory(&self) -> Option<&str> {
+        self.memory.as_ref().map(|(text, _)| text.as_str())
+    }
+
+    /// Puts the pet's answer to something said up for a while, with the
+    /// face it pulls.
+    pub fn hear(&mut self, talk: Talk, privacy: Privacy, now: DateTime<Utc>) {
+        if let Some(face) = talk.intent.face() {
+            self.face = Some((face, reactions::FACE_REFRESHES));
+        }
+        self.reply = Some((talk.reply, privacy, now + REPLY_SHOWN));
+    }
+
+    /// What the pet is saying back, if it was talked to lately.
+    pub fn reply(&self, now: DateTime<Utc>) -> Option<(&str, Privacy)> {
+        self.reply
+            .as_ref()
+            .filter(|(_, _, until)| *until > now)
+            .map(|(text, privacy, _)| (text.as_str(), *privacy))
+    }
+
+    /// Moves everything on by one refresh cycle.
+    pub fn advance(&mut self, pet: &Pet) {
+        if let Some((_, left)) = &mut self.face {
+            *left = left.saturating_sub(1);
+        }
+        self.face = self.face.filter(|&(_, left)| left > 0);
+        for particle in &mut self.particles {
+            particle.lifetime -= 1;
+            particle.position.y -= PARTICLE_RISE;
+            particle.position.x += self.rng.random_range(-1..=1);
+        }
+        self.particles.retain(|p| p.lifetime > 0);
+
+        let (min, max) = layout::walk_range();
+        self.walker
+            .step(&mut self.rng, min as f32, max as f32, pet.favorite_spot);
+    }
+
+    /// Applies whatever [`reactions::REACTIONS`] says `event` does.
+    pub fn on_system_event(&mut self, event: SystemEvent) {
+        let reaction = reactions::lookup(event);
+        if let Some(flag) = reaction.clear {
+            self.flags.retain(|&f| f != flag);
+        }
+        if let Some(flag) = reaction.raise.filter(|f| !self.flags.contains(f)) {
+            self.flags.push(flag);
+        }
+        if let Some(face) = reaction.face {
+            self.face = Some((face, reactions::FACE_REFRESHES));
+        }
+    }
+
+    /// The expression the pet should be wearing, if anything overrides its
+    /// usual one: a passing face first, then the oldest flag with one.
+    pub fn face(&self) -> Option<Face> {
+        self.face
+            .map(|(face, _)| face)
+            .or_else(|| self.flags.iter().find_map(|flag| flag.face()))
+    }
+
+    /// Carries out what an action set off, and hands back the event it
+    /// came to.
+    pub fn react(&mut self, name: &str, reaction: Reaction) -> PetEvent {
+        for effect in reaction.effects {
+            match effect {
+                SideEffect::Particles(kind, count) => self.spawn(kind, count),
+                SideEffect::Log(line) => log::info!("{name} {line}"),
+            }
+        }
+        reaction.event
+    }
+
+    /// Spawns the particles that go with a pet event the pet didn't get
+    /// from an action; those come with the action's [`Reaction`].
+    pub fn on_event(&mut self, event: PetEvent) {
+        let (kind, count) = match event {
+            PetEvent::GoalReached => (ParticleKind::Heart, 4),
+            // Raised every cycle while hot; keep a drop or two going rather
+            // than piling them up.
+            PetEvent::Hot if !self.particles.iter().any(|p| p.kind == ParticleKind::Sweat) => {
+                (ParticleKind::Sweat, 2)
+            }
+            PetEvent::Fed
+            | PetEvent::Petted
+            | PetEvent::Played
+            | PetEvent::Disciplined
+            | PetEvent::Scolded
+            | PetEvent::Hot
+            | PetEvent::Cleaned
+            | PetEvent::Misbehaved
+            | PetEvent::Sick
+            | PetEvent::Died
+            | PetEvent::Evolved
+            | PetEvent::StatCritical
+            | PetEvent::WokeUp
+            | PetEvent::Visited
+            | PetEvent::UpgradesPending => return,
+        };
+        self.spawn(kind, count);
+    }
+
+    fn spawn(&mut self, kind: ParticleKind, count: u32) {
+        let head = layout::pet_origin(self.walker.x());
+        for _ in 0..count {
+            let x = head.x + self.rng.random_range(0..artwork::footprint().width as i32);
+            let y = head.y - self.rng.random_range(2..10);
+            self.particles.push(Particle {
+                kind,
+                position: Point::new(x, y),
+                lifetime: PARTICLE_LIFETIME,
+            });
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParticleKind {
+    Heart,
+    Sweat,
+    Note,
+}
+
+impl ParticleKind {
+    pub fn sprite(self) -> &'static Sprite {
+        match self {
+            ParticleKind::Heart => &sprite::HEART,
+            ParticleKind::Sweat => &sprite::SWEAT_DROP,
+            ParticleKind::Note => &sprite::MUSIC_NOTE,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            ParticleKind::Heart => Color::Red,
+            ParticleKind::Sweat | ParticleKind::Note => Color::Black,
+        }
+    }
+}
+
+/// A small sprite floating up from the pet for a few refreshes.
+#[derive(Clone, Debug)]
+pub struct Particle {
+    pub kind: ParticleKind,
+    pub position: Point,
+    /// Refreshes left before it disappears.
+    pub lifetime: u32,
+}
+
+/// Horizontal position and velocity of the wandering pet.
+pub struct Walker {
+    x: f32,
+    velocity: f32,
+    /// Refreshes left to stand still for.
+    paused: u32,
+    facing: Flip,
+}
+
+impl Walker {
+    pub fn new(x: f32) -> Self {
+        Walker {
+            x,
+            velocity: 0.0,
+            paused: 0,
+            facing: Flip::None,
+        }
+    }
+
+    /// Left edge of the pet sprite.
+    pub fn x(&self) -> i32 {
+        self.x.round() as i32
+    }
+
+    /// The sprite faces right; mirror it while walking left.
+    pub fn flip(&self) -> Flip {
+        self.facing
+    }
+
+    fn step(&mut self, rng: &mut StdRng, min: f32, max: f32, favorite: Option<i32>) {
+        if self.paused > 0 {
+            self.paused -= 1;
+            return;
+        }
+        if self.velocity == 0.0 || rng.random_bool(TURN_CHANCE) {
+            let nudge = rng.random_range(-2.0..=2.0);
+            self.velocity = (self.velocity + nudge).clamp(-MAX_SPEED, MAX_SPEED);
+        }
+
+        let previous = self.x;
+        self.x += self.velocity;
+        if self.x < min {
+            self.x = min + (min - self.x);
+            self.velocity = -self.velocity;
+        } else if self.x > max {
+            self.x = max - (self.x - max);
+            self.velocity = -self.velocity;
+        }
+        self.x = self.x.clamp(min, max);
+
+        if self.velocity > 0.0 {
+            self.facing = Flip::None;
+        } else if self.velocity < 0.0 {
+            self.facing = Flip::Horizontal;
+        }
+
+        if let Some(spot) = favorite {
+            let spot = spot as f32;
+            let passed = (previous - spot).signum() != (self.x - spot).signum();
+            if passed && rng.random_bool(LINGER_CHANCE) {
+                self.x = spot;
+                self.paused = rng.random_range(2..=5);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::Action;
+
+    fn runtime(seed: u64) -> (Runtime, Pet) {
+        let mut pet = Pet::new("Tester", "2024-05-01T12:00:00Z".parse().unwrap());
+        (Runtime::new(seed, &mut pet), pet)
+    }
+
+    /// Has `pet` do `action`, with what it sets off.
+    fn act(runtime: &mut Runtime, pet: &mut Pet, action: Action) -> PetEvent {
+        let reaction = pet.apply(action);
+        runtime.react(&pet.name, reaction)
+    }
+
+    fn positions(runtime: &Runtime) -> Vec<(ParticleKind, Point, u32)> {
+        runtime
+            .particles
+            .iter()
+            .map(|p| (p.kind, p.position, p.lifetime))
+            .collect()
+    }
+
+    #[test]
+    fn particles_replay_under_a_fixed_seed() {
+        let run = |seed| {
+            let (mut runtime, mut pet) = runtime(seed);
+            let mut seen = Vec::new();
+            for action in [Action::Feed, Action::Play] {
+                act(&mut runtime, &mut pet, action);
+                runtime.advance(&pet);
+                seen.push(positions(&runtime));
+            }
+            runtime.on_event(PetEvent::Hot);
+            seen.push(positions(&runtime));
+            seen
+        };
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+    }
+
+    #[test]
+    fn particles_last_a_fixed_number_of_refreshes() {
+        let (mut runtime, mut pet) = runtime(1);
+        act(&mut runtime, &mut pet, Action::Pet);
+        assert_eq!(runtime.particles.len(), 3);
+        let start: Vec<_> = runtime.particles.iter().map(|p| p.position.y).collect();
+        for refresh in 1..PARTICLE_LIFETIME {
+            runtime.advance(&pet);
+            assert_eq!(runtime.particles.len(), 3, "gone after {refresh}");
+            for (particle, y) in runtime.particles.iter().zip(&start) {
+                assert_eq!(particle.position.y, y - PARTICLE_RISE * refresh as i32);
+            }
+        }
+        runtime.advance(&pet);
+        assert!(runtime.particles.is_empty());
+    }
+
+    #[test]
+    fn sweat_does_not_pile_up() {
+        let (mut runtime, pet) = runtime(1);
+        for _ in 0..5 {
+            runtime.on_event(PetEvent::Hot);
+        }
+        assert_eq!(runtime.particles.len(), 2);
+        for _ in 0..PARTICLE_LIFETIME {
+            runtime.advance(&pet);
+        }
+        runtime.on_event(PetEvent::Hot);
+        assert_eq!(runtime.particles.len(), 2);
+    }
+
+    #[test]
+    fn each_action_spawns_its_own_kind() {
+        for (action, kind, count) in [
+            (Action::Feed, ParticleKind::Heart, 3),
+            (Action::Pet, ParticleKind::Heart, 3),
+            (Action::Play, ParticleKind::Note, 2),
+            (Action::Discipline, ParticleKind::Sweat, 2),
+            (Action::Clean, ParticleKind::Heart, 0),
+        ] {
+            let (mut runtime, mut pet) = runtime(1);
+            act(&mut runtime, &mut pet, action);
+            assert_eq!(runtime.particles.len(), count, "{action:?}");
+            assert!(
+                runtime.particles.iter().all(|p| p.kind == kind),
+                "{action:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn events_without_an_action_spawn_their_own() {
+        for (event, kind, count) in [
+            (PetEvent::Hot, ParticleKind::Sweat, 2),
+            (PetEvent::GoalReached, ParticleKind::Heart, 4),
+            (PetEvent::Misbehaved, ParticleKind::Heart, 0),
+            // These come with the action instead.
+            (PetEvent::Fed, ParticleKind::Heart, 0),
+            (PetEvent::Scolded, ParticleKind::Heart, 0),
+        ] {
+            let (mut runtime, _) = runtime(1);
+            runtime.on_event(event);
+            assert_eq!(runtime.particles.len(), count, "{event:?}");
+            assert!(
+                runtime.particles.iter().all(|p| p.kind == kind),
+                "{event:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn misbehaviour_replays_under_a_fixed_seed() {
+        let times = |seed| {
+            let (mut runtime, pet) = runtime(seed);
+            let mut now = pet.last_tick;
+            let mut due = Vec::new();
+            while due.len() < 5 {
+                if runtime.misbehavior_due(&pet, now) {
+                    due.push(now);
+                }
+                now += Duration::minutes(1);
+            }
+            due
+        };
+        let first = times(42);
+        assert_eq!(first, times(42));
+        assert_ne!(first, times(43));
+        for gap in first.windows(2).map(|w| (w[1] - w[0]).num_minutes()) {
+            assert!(MISBEHAVIOR_GAP.contains(&gap), "{gap} minutes apart");
+        }
+    }
+}
@@ -0,0 +1,509 @@
+//! Decides when the next refresh should happen.
+//!
+//! Right after someone interacts with the pet we refresh as often as the
+//! panel allows, then ease back to the base interval, and stretch out to a
+//! long idle interval once nothing on screen has changed for a while.
+//!
+//! On top of that, the screen on the panel gets a say: it can ask for a
+//! refresh before the cadence would give one (the clock at the turn of the
+//! minute), and can hold back changes that aren't worth a refresh of their
+//! own until then. A screen pinned to the panel goes further and sets the
+//! pace itself, at its own interval.
+//!
+//! On battery, refreshes are batched: the pet and the sources carry on as
+//! usual, but only something urgent gets to the panel before the battery
+//! interval is up.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::config::CadenceConfig;
+
+/// The three intervals and what switches between them.
+#[derive(Clone, Debug)]
+pub struct Cadence {
+    /// Shortest allowed gap between refreshes, used right after interaction.
+    pub min_spacing: Duration,
+    /// Normal gap between refreshes.
+    pub base: Duration,
+    /// Gap once the frame has been static for `idle_after` cycles.
+    pub idle: Duration,
+    /// How long after an interaction to keep refreshing at `min_spacing`.
+    /// The interval then ramps linearly back to `base` over the same span.
+    pub activity_window: Duration,
+    pub idle_after: u32,
+    /// Shortest gap between refreshes on battery, bar urgent ones; zero
+    /// doesn't batch.
+    pub battery: Duration,
+}
+
+impl Cadence {
+    pub fn from_config(base: Duration, config: &CadenceConfig) -> Self {
+        Cadence {
+            min_spacing: Duration::from_secs(config.min_spacing).min(base),
+            base,
+            idle: Duration::from_secs(config.idle_interval).max(base),
+            activity_window: Duration::from_secs(config.activity_window),
+            idle_after: config.idle_after,
+            battery: Duration::from_secs(config.battery_interval),
+        }
+    }
+}
+
+/// What the scheduler knows about recent history.
+#[derive(Clone, Debug)]
+pub struct State {
+    /// When the last cycle ran.
+    pub last_cycle: Instant,
+    pub last_interaction: Option<Instant>,
+    /// Consecutive cycles whose frame didn't change.
+    pub unchanged_frames: u32,
+    /// Set while the display is parked: no cycles until this deadline
+    /// unless an event arrives first.
+    pub parked_until: Option<Instant>,
+    /// What's on the panel; `None` until the first refresh.
+    pub shown: Option<Shown>,
+    /// How often the pinned screen wants refreshing, while it's the one
+    /// drawn and has an interval of its own.
+    pub pinned: Option<Duration>,
+}
+
+/// The last frame that made it to the panel.
+#[derive(Clone, Copy, Debug)]
+pub struct Shown {
+    pub screen: &'static str,
+    pub at: Instant,
+    /// When the screen said it would go out of date; `None` if it never
+    /// does by itself.
+    pub stale_at: Option<Instant>,
+}
+
+/// A frame that differs from the panel, waiting on a decision.
+#[derive(Clone, Copy, Debug)]
+pub struct Pending {
+    pub screen: &'static str,
+    /// The screen's call on whether its changes are worth a refresh now.
+    pub dirty: bool,
+    /// Something drawn over the screen changed, e.g. the debug overlay or
+    /// the alert border.
+    pub forced: bool,
+}
+
+/// Whether a changed frame should go to the panel now rather than wait.
+/// New screens, interactions and outside changes always show straight away;
+/// otherwise it's up to the screen, until its content goes stale.
+pub fn should_refresh(state: &State, pending: &Pending, now: Instant) -> bool {
+    let Some(shown) = state.shown else {
+        return true;
+    };
+    pending.forced
+        || pending.dirty
+        || shown.screen != pending.screen
+        || state.last_interaction.is_some_and(|at| at > shown.at)
+        || shown.stale_at.is_some_and(|stale| stale <= now)
+}
+
+/// What the host runs on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Power {
+    #[default]
+    Mains,
+    Battery,
+}
+
+/// Whether refreshes are held back on `power`, given the cadence.
+pub fn is_batching(cadence: &Cadence, power: Power) -> bool {
+    power == Power::Battery && !cadence.battery.is_zero()
+}
+
+/// Whether a frame that [`should_refresh`] may go to the panel, on
+/// `power`. On battery only an `urgent` one does, like a stat running out
+/// or a button press, until `interval` has passed `since` the last
+/// refresh; on mains, or with no interval, any does.
+pub fn batch_allows(
+    power: Power,
+    urgent: bool,
+    since: Option<Duration>,
+    interval: Duration,
+) -> bool {
+    power == Power::Mains
+        || interval.is_zero()
+        || urgent
+        || since.is_none_or(|since| since >= interval)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Just interacted with: refreshing at the minimum spacing.
+    Active,
+    /// Easing back from active to the base interval.
+    Decaying,
+    Normal,
+    /// Nothing is changing: refreshing at the idle interval.
+    Idle,
+    /// Asleep until a single wakeup; only events end it early.
+    Parked,
+    /// The screen's content goes stale before the cadence's deadline.
+    Screen,
+    /// A pinned screen's own interval.
+    Pinned,
+}
+
+impl Mode {
+    /// Whether someone has interacted recently enough that the display
+    /// should stay responsive.
+    pub fn is_recently_active(self) -> bool {
+        matches!(self, Mode::Active | Mode::Decaying)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Mode::Active => "active",
+            Mode::Decaying => "decay",
+            Mode::Normal => "normal",
+            Mode::Idle => "idle",
+            Mode::Parked => "parked",
+            Mode::Screen => "screen",
+            Mode::Pinned => "pinned",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Deadline {
+    pub at: Instant,
+    pub interval: Duration,
+    pub mode: Mode,
+}
+
+/// When the cycle after `state.last_cycle` should run.
+pub fn next_deadline(cadence: &Cadence, state: &State, now: Instant) -> Deadline {
+    if let Some(until) = state.parked_until {
+        return Deadline {
+            at: until,
+            interval: until.saturating_duration_since(state.last_cycle),
+            mode: Mode::Parked,
+        };
+    }
+    let since_interaction = state
+        .last_interaction
+        .map(|at| now.saturating_duration_since(at));
+    let window = cadence.activity_window;
+
+    let (interval, mode) = match since_interaction {
+        Some(since) if since < window => (cadence.min_spacing, Mode::Active),
+        Some(since) if since < window * 2 && !window.is_zero() => {
+            let progress = (since - window).as_secs_f64() / window.as_secs_f64();
+            let span = cadence.base.saturating_sub(cadence.min_spacing);
+            let interval = cadence.min_spacing + span.mul_f64(progress);
+            (interval, Mode::Decaying)
+        }
+        // A pinned screen keeps its own time whether or not it changes,
+        // but no slower than idle, so the pet is still looked in on.
+        _ => match state.pinned {
+            Some(own) => (own.clamp(cadence.min_spacing, cadence.idle), Mode::Pinned),
+            None if state.unchanged_frames >= cadence.idle_after => (cadence.idle, Mode::Idle),
+            None => (cadence.base, Mode::Normal),
+        },
+    };
+    let cadence_at = state.last_cycle + interval;
+    // Never sooner than the panel allows, even if the screen asks.
+    let stale_at = state
+        .shown
+        .and_then(|shown| Some(shown.stale_at?.max(shown.at + cadence.min_spacing)));
+    match stale_at {
+        Some(at) if at < cadence_at => Deadline {
+            at,
+            interval: at.saturating_duration_since(state.last_cycle),
+            mode: Mode::Screen,
+        },
+        _ => Deadline {
+            at: cadence_at,
+            interval,
+            mode,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn cadence() -> Cadence {
+        Cadence {
+            min_spacing: 15 * SECOND,
+            base: 60 * SECOND,
+            idle: 600 * SECOND,
+            activity_window: 30 * SECOND,
+            idle_after: 5,
+            battery: Duration::ZERO,
+        }
+    }
+
+    fn state(start: Instant) -> State {
+        State {
+            last_cycle: start,
+            last_interaction: None,
+            unchanged_frames: 0,
+            parked_until: None,
+            shown: None,
+            pinned: None,
+        }
+    }
+
+    #[test]
+    fn settles_on_the_base_interval() {
+        let start = Instant::now();
+        let deadline = next_deadline(&cadence(), &state(start), start);
+        assert_eq!(deadline.mode, Mode::Normal);
+        assert_eq!(deadline.at, start + 60 * SECOND);
+    }
+
+    #[test]
+    fn speeds_up_after_interaction_then_eases_back() {
+        let start = Instant::now();
+        let mut state = state(start);
+        state.last_interaction = Some(start);
+        // Idle frames don't count while someone's using it.
+        state.unchanged_frames = 100;
+        for (since, interval, mode) in [
+            (0, 15, Mode::Active),
+            (29, 15, Mode::Active),
+            (30, 15, Mode::Decaying),
+            (45, 37, Mode::Decaying),
+            (59, 58, Mode::Decaying),
+        ] {
+            let now = start + since * SECOND;
+            state.last_cycle = now;
+            let deadline = next_deadline(&cadence(), &state, now);
+            assert_eq!(deadline.mode, mode, "{since}s on");
+            assert_eq!(deadline.interval.as_secs(), interval, "{since}s on");
+            assert_eq!(deadline.at, now + deadline.interval);
+        }
+        let now = start + 60 * SECOND;
+        assert_eq!(next_deadline(&cadence(), &state, now).mode, Mode::Idle);
+    }
+
+    #[test]
+    fn stretches_out_once_nothing_changes() {
+        let start = Instant::now();
+        let mut state = state(start);
+        state.unchanged_frames = 4;
+        assert_eq!(next_deadline(&cadence(), &state, start).mode, Mode::Normal);
+        state.unchanged_frames = 5;
+        let deadline = next_deadline(&cadence(), &state, start);
+        assert_eq!(deadline.mode, Mode::Idle);
+        assert_eq!(deadline.at, start + 600 * SECOND);
+    }
+
+    #[test]
+    fn parking_overrides_everything() {
+        let start = Instant::now();
+        let mut state = state(start);
+        state.last_interaction = Some(start);
+        state.parked_until = Some(start + 3600 * SECOND);
+        let deadline = next_deadline(&cadence(), &state, start);
+        assert_eq!(deadline.mode, Mode::Parked);
+        assert_eq!(deadline.at, start + 3600 * SECOND);
+    }
+
+    #[test]
+    fn screens_can_only_bring_the_deadline_forward() {
+        let start = Instant::now();
+        let mut state = state(start);
+        let shown = |stale| Shown {
+            screen: "clock",
+            at: start,
+            stale_at: Some(start + stale * SECOND),
+        };
+        state.shown = Some(shown(20));
+        let deadline = next_deadline(&cadence(), &state, start);
+        assert_eq!(
+            (deadline.mode, deadline.at),
+            (Mode::Screen, start + 20 * SECOND)
+        );
+        // Not sooner than the panel allows...
+        state.shown = Some(shown(2));
+        assert_eq!(
+            next_deadline(&cadence(), &state, start).at,
+            start + 15 * SECOND
+        );
+        // ...and no later than the cadence.
+        state.shown = Some(shown(90));
+        assert_eq!(next_deadline(&cadence(), &state, start).mode, Mode::Normal);
+    }
+
+    #[test]
+    fn a_pinned_screen_sets_its_own_pace() {
+        let start = Instant::now();
+        let mut state = state(start);
+        state.pinned = Some(300 * SECOND);
+        let deadline = next_deadline(&cadence(), &state, start);
+        assert_eq!(
+            (deadline.mode, deadline.at, deadline.interval),
+            (Mode::Pinned, start + 300 * SECOND, 300 * SECOND)
+        );
+        // Unchanged frames don't stretch it out to idle...
+        state.unchanged_frames = 100;
+        assert_eq!(next_deadline(&cadence(), &state, start), deadline);
+        // ...but it never goes slower than idle, nor faster than the panel
+        // allows.
+        state.pinned = Some(3600 * SECOND);
+        assert_eq!(
+            next_deadline(&cadence(), &state, start).at,
+            start + 600 * SECOND
+        );
+        state.pinned = Some(SECOND);
+        assert_eq!(
+            next_deadline(&cadence(), &state, start).at,
+            start + 15 * SECOND
+        );
+        // Someone pressing buttons still gets a quick panel.
+        state.pinned = Some(300 * SECOND);
+        state.last_interaction = Some(start);
+        assert_eq!(next_deadline(&cadence(), &state, start).mode, Mode::Active);
+        // And a screen going stale sooner still comes first.
+        state.last_interaction = None;
+        state.shown = Some(Shown {
+            screen: "clock",
+            at: start,
+            stale_at: Some(start + 40 * SECOND),
+        });
+        let deadline = next_deadline(&cadence(), &state, start);
+        assert_eq!(
+            (deadline.mode, deadline.at),
+            (Mode::Screen, start + 40 * SECOND)
+        );
+    }
+
+    #[test]
+    fn changed_frames_wait_for_the_screen() {
+        let start = Instant::now();
+        let mut state = state(start);
+        let pending = Pending {
+            screen: "clock",
+            dirty: false,
+            forced: false,
+        };
+        assert!(should_refresh(&state, &pending, start), "nothing shown yet");
+        state.shown = Some(Shown {
+            screen: "clock",
+            at: start,
+            stale_at: Some(start + 60 * SECOND),
+        });
+        assert!(!should_refresh(&state, &pending, start));
+        assert!(should_refresh(&state, &pending, start + 60 * SECOND));
+        assert!(should_refresh(
+            &state,
+            &Pending {
+                screen: "pet",
+                ..pending
+            },
+            start
+        ));
+        state.last_interaction = Some(start + SECOND);
+        assert!(should_refresh(&state, &pending, start + SECOND));
+    }
+
+    /// How each kind of screen behaves: the clock goes stale at the minute
+    /// and holds back changes until then, the stats every half hour, and
+    /// the pet never by itself but shows every change.
+    fn shown(screen: &'static str, at: Instant) -> Shown {
+        let stale = match screen {
+            "clock" => Some(60 * SECOND),
+            "stats" => Some(1800 * SECOND),
+            _ => None,
+        };
+        Shown {
+            screen,
+            at,
+            stale_at: stale.map(|stale| at + stale),
+        }
+    }
+
+    fn pending(screen: &'static str) -> Pending {
+        Pending {
+            screen,
+            dirty: screen == "pet",
+            forced: false,
+        }
+    }
+
+    #[test]
+    fn screen_combinations() {
+        let start = Instant::now();
+        let cadence = Cadence {
+            base: 300 * SECOND,
+            ..cadence()
+        };
+        // What's shown, what's drawn 30s later, whether that goes out
+        // then, and when the next cycle is.
+        for (on, next, refresh, deadline, mode) in [
+            ("clock", "clock", false, 60, Mode::Screen),
+            ("clock", "pet", true, 60, Mode::Screen),
+            ("clock", "stats", true, 60, Mode::Screen),
+            ("stats", "stats", false, 300, Mode::Normal),
+            ("stats", "pet", true, 300, Mode::Normal),
+            ("pet", "pet", true, 300, Mode::Normal),
+            ("pet", "clock", true, 300, Mode::Normal),
+        ] {
+            let mut state = state(start);
+            state.shown = Some(shown(on, start));
+            let now = start + 30 * SECOND;
+            assert_eq!(
+                should_refresh(&state, &pending(next), now),
+                refresh,
+                "{on} then {next}"
+            );
+            let at = next_deadline(&cadence, &state, now);
+            assert_eq!((at.at, at.mode), (start + deadline * SECOND, mode), "{on}");
+        }
+    }
+
+    #[test]
+    fn batching_lets_only_urgent_frames_through() {
+        let (minute, interval) = (60 * SECOND, 600 * SECOND);
+        type Case = (Power, bool, Option<Duration>, Duration, bool);
+        let cases: [Case; 10] = [
+            // On mains anything goes.
+            (Power::Mains, false, Some(SECOND), interval, true),
+            (Power::Mains, true, Some(SECOND), interval, true),
+            // On battery, a frame waits for the interval...
+            (Power::Battery, false, Some(SECOND), interval, false),
+            (Power::Battery, false, Some(9 * minute), interval, false),
+            (Power::Battery, false, Some(interval), interval, true),
+            (Power::Battery, false, Some(2 * interval), interval, true),
+            // ...unless it's urgent,
+            (Power::Battery, true, Some(SECOND), interval, true),
+            (Power::Battery, true, Some(Duration::ZERO), interval, true),
+            // or nothing's been shown yet,
+            (Power::Battery, false, None, interval, true),
+            // or batching's off.
+            (Power::Battery, false, Some(SECOND), Duration::ZERO, true),
+        ];
+        for (power, urgent, since, interval, allowed) in cases {
+            assert_eq!(
+                batch_allows(power, urgent, since, interval),
+                allowed,
+                "{power:?}, urgent {urgent}, {since:?} since, every {interval:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn only_batching_on_battery_with_an_interval() {
+        let mut cadence = cadence();
+        assert!(!is_batching(&cadence, Power::Battery));
+        cadence.battery = 600 * SECOND;
+        assert!(is_batching(&cadence, Power::Battery));
+        assert!(!is_batching(&cadence, Power::Mains));
+        // As the status file has it.
+        let names = [Power::Mains, Power::Battery].map(|p| serde_json::to_value(p).unwrap());
+        assert_eq!(names, ["mains", "battery"]);
+    }
+}
@@ -0,0 +1,57 @@
+//! Version and build details, for telling devices apart.
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{Context, Profile, Screen};
+use crate::build_info::BuildInfo;
+use crate::display::{Color, Frame, WIDTH};
+use crate::text::{self, Align};
+
+pub struct AboutScreen;
+
+impl Screen for AboutScreen {
+    fn name(&self) -> &'static str {
+        "about"
+    }
+
+    fn sources(&self) -> &'static [&'static str] {
+        &["update-check"]
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let profile = ctx.profile();
+        let build = BuildInfo::get();
+        let panel = ctx.panel;
+        let mut lines = vec![
+            format!("inky-gotchi {}", build.version),
+            format!("commit {}", build.commit_label()),
+            format!("built {}", build.build_date),
+            build.rustc.to_string(),
+            format!("features: {}", build.features_label()),
+            format!(
+                "{} {}x{} rot {} lut {}",
+                panel.name, panel.width, panel.height, panel.rotation, panel.lut
+            ),
+        ];
+        // Large text has room for the version and the commit, which are
+        // what tell devices apart.
+        if profile == Profile::Large {
+            lines = vec![build.version.to_string(), build.commit_label()];
+        }
+        if let Some(version) = &ctx.snapshot.available_update {
+            lines.push(format!("update available: {version}"));
+        }
+
+        let top = ctx.title(frame, "About", None) + 2;
+        let line_height = profile.line_height();
+        for (i, line) in lines.iter().enumerate() {
+            let row = Rectangle::new(
+                Point::new(2, top + (i as u32 * line_height) as i32),
+                Size::new(WIDTH - 4, line_height),
+            );
+            let line = profile.fit(line, row.size.width);
+            text::draw(frame, &line, profile.font(), Color::Black, row, Align::LEFT);
+        }
+    }
+}
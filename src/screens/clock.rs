@@ -0,0 +1,251 @@
+//! Large clock with the date and a sun/moon footer.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Timelike, Utc};
+
+use embedded_graphics::mono_font::iso_8859_15::{FONT_10X20, FONT_6X10};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle};
+
+use super::{Context, Profile, Screen};
+use crate::astro::{self, MoonPhase};
+use crate::display::{Color, Frame, WIDTH};
+use crate::icons::Icon;
+use crate::text::{self, Align, BigText, HAlign, VAlign};
+
+/// Top edge of the footer band.
+const FOOTER_Y: i32 = 91;
+
+pub struct ClockScreen;
+
+impl Screen for ClockScreen {
+    fn name(&self) -> &'static str {
+        "clock"
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let time = ctx.now.format("%H:%M").to_string();
+        let big = BigText::new(&FONT_10X20, Color::Black, ctx.supersample());
+        let area = Rectangle::new(Point::new(0, 8), Size::new(WIDTH, 40));
+        big.draw(frame, &time, area, Align::CENTER);
+
+        let profile = ctx.profile();
+        let date = ctx.now.format("%a %d %b").to_string();
+        let height = profile.font().character_size.height + 4;
+        let area = Rectangle::new(Point::new(0, 64 - height as i32), Size::new(WIDTH, height));
+        let align = Align::new(HAlign::Center, VAlign::Bottom);
+        text::draw(frame, &date, profile.font(), Color::Black, area, align);
+
+        // The footer's times and moon are too fine for large text, which
+        // makes do with the clock and the date.
+        if profile == Profile::Regular {
+            draw_footer(ctx, frame);
+        }
+    }
+
+    fn desired_interval(&self, ctx: &Context) -> Duration {
+        // Leap seconds show up as nanoseconds past a billion.
+        let into_minute = Duration::from_secs(ctx.now.second().into())
+            + Duration::from_nanos((ctx.now.nanosecond() % 1_000_000_000).into());
+        Duration::from_secs(60).saturating_sub(into_minute)
+    }
+
+    /// Only the minute really matters; the footer can wait for it.
+    fn is_dirty(&self, _ctx: &Context) -> bool {
+        false
+    }
+}
+
+fn draw_footer(ctx: &Context, frame: &mut Frame) {
+    Line::new(
+        Point::new(0, FOOTER_Y - 3),
+        Point::new(WIDTH as i32 - 1, FOOTER_Y - 3),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+    .draw(frame)
+    .ok();
+
+    let daylight = astro::sun_times(
+        ctx.now.date_naive(),
+        ctx.config.latitude,
+        ctx.config.longitude,
+    );
+    let slot = |x| Rectangle::new(Point::new(x, FOOTER_Y), Size::new(30, 11));
+    Icon::Sunrise.draw(frame, Point::new(3, FOOTER_Y), Color::Black);
+    draw_time(ctx, frame, slot(17), daylight.rise());
+    Icon::Sunset.draw(frame, Point::new(59, FOOTER_Y), Color::Black);
+    draw_time(ctx, frame, slot(73), daylight.set());
+
+    let extra = ctx.render(&ctx.config.text.footer);
+    let slot = Rectangle::new(Point::new(105, FOOTER_Y), Size::new(WIDTH - 125, 11));
+    text::draw(frame, &extra, &FONT_6X10, Color::Black, slot, Align::RIGHT);
+
+    let phase = MoonPhase::at(ctx.now.with_timezone(&Utc));
+    draw_moon(
+        frame,
+        Point::new(WIDTH as i32 - 10, FOOTER_Y + 5),
+        11,
+        phase,
+    );
+}
+
+/// Renders `HH:MM` in local time, or an em dash when the event doesn't
+/// happen today (polar day or night).
+fn draw_time(ctx: &Context, frame: &mut Frame, slot: Rectangle, at: Option<DateTime<Utc>>) {
+    match at {
+        Some(at) => {
+            let time = ctx.clock.localize(at).format("%H:%M").to_string();
+            text::draw(frame, &time, &FONT_6X10, Color::Black, slot, Align::LEFT);
+        }
+        None => {
+            // The fonts are ISO-8859-15 and have no em dash, so draw one.
+            let mid = slot.center();
+            Line::new(mid - Point::new(7, 0), mid + Point::new(7, 0))
+                .into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+                .draw(frame)
+                .ok();
+        }
+    }
+}
+
+/// Draws the moon as a disc whose dark part is bounded by the terminator,
+/// an ellipse arc whose half-width follows the phase. Works at any size.
+pub fn draw_moon<D>(target: &mut D, center: Point, diameter: u32, phase: MoonPhase)
+where
+    D: DrawTarget<Color = Color>,
+{
+    let r = diameter as f64 / 2.0;
+    let terminator = (2.0 * std::f64::consts::PI * phase.age).cos();
+    let reach = diameter as i32 / 2 + 1;
+    // Sample pixel centres so even diameters stay symmetric.
+    let offset = if diameter.is_multiple_of(2) { 0.5 } else { 0.0 };
+    let pixels = (-reach..=reach).flat_map(move |dy| {
+        (-reach..=reach).filter_map(move |dx| {
+            let x = dx as f64 + offset;
+            let y = dy as f64 + offset;
+            let dist = (x * x + y * y).sqrt();
+            if dist > r {
+                return None;
+            }
+            let half_width = (r * r - y * y).max(0.0).sqrt();
+            let lit = if phase.waxing() {
+                x > half_width * terminator
+            } else {
+                x < -half_width * terminator
+            };
+            let outline = dist > r - 1.0;
+            let color = if outline || !lit {
+                Color::Black
+            } else {
+                Color::White
+            };
+            Some(Pixel(center + Point::new(dx, dy), color))
+        })
+    });
+    target.draw_iter(pixels).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moon(age: f64, diameter: u32) -> Frame {
+        let mut frame = Frame::new(40, 40);
+        draw_moon(&mut frame, Point::new(20, 20), diameter, MoonPhase { age });
+        frame
+    }
+
+    #[test]
+    fn moon_is_lit_on_the_right_side_at_any_size() {
+        for diameter in [7, 11, 12, 24] {
+            let quarter = diameter as i32 / 4;
+            let full = moon(0.5, diameter);
+            assert_eq!(full.pixel(20, 20), Color::White, "full at {diameter}");
+            let new = moon(0.0, diameter);
+            assert_eq!(
+                new.pixel(20 - quarter, 20),
+                Color::Black,
+                "new at {diameter}"
+            );
+            assert_eq!(
+                new.pixel(20 + quarter, 20),
+                Color::Black,
+                "new at {diameter}"
+            );
+            // Waxing lights the right, waning the left.
+            let first = moon(0.25, diameter);
+            assert_eq!(first.pixel(20 + quarter, 20), Color::White);
+            assert_eq!(first.pixel(20 - quarter, 20), Color::Black);
+            let last = moon(0.75, diameter);
+            assert_eq!(last.pixel(20 - quarter, 20), Color::White);
+            assert_eq!(last.pixel(20 + quarter, 20), Color::Black);
+            // Nothing outside the disc.
+            let reach = diameter as i32 / 2 + 2;
+            assert_eq!(full.pixel(20 + reach, 20), Color::White);
+            assert_eq!(full.pixel(20, 20 - reach), Color::White);
+        }
+    }
+
+    /// The character cells of the big time, drawn at twice the font's size
+    /// where [`ClockScreen::draw`] puts it.
+    fn cells() -> Vec<Rectangle> {
+        let cell = FONT_10X20.character_size * 2;
+        let area = Rectangle::new(Point::new(0, 8), Size::new(WIDTH, 40));
+        let origin = Align::CENTER.position(Size::new(cell.width * 5, cell.height), area);
+        (0..5)
+            .map(|index| Rectangle::new(origin + Point::new(index * cell.width as i32, 0), cell))
+            .collect()
+    }
+
+    /// What a partial refresh from `before` to `after` sends.
+    fn refreshed(before: &str, after: &str) -> Rectangle {
+        let draw = |at| crate::screens::Fixture::at(at).draw(&ClockScreen);
+        crate::display::PackedFrame::new(&draw(before))
+            .dirty_bounds(&draw(after))
+            .expect("the time changed")
+    }
+
+    /// `cell` out to whole bytes across, as [`PackedFrame::dirty_bounds`]
+    /// widens it.
+    ///
+    /// [`PackedFrame::dirty_bounds`]: crate::display::PackedFrame::dirty_bounds
+    fn columns(cell: Rectangle) -> (i32, i32) {
+        let right = cell.top_left.x + cell.size.width as i32;
+        (cell.top_left.x / 8 * 8, (right + 7) / 8 * 8)
+    }
+
+    #[test]
+    fn a_new_minute_refreshes_one_glyph_cell() {
+        let cells = cells();
+        let dirty = refreshed("2024-05-01T12:04:30+03:00", "2024-05-01T12:05:00+03:00");
+        let across = (dirty.top_left.x, dirty.top_left.x + dirty.size.width as i32);
+        assert_eq!(across, columns(cells[4]));
+        // Only rows where the 4 and the 5 differ, all inside the cell.
+        let (top, bottom) = (cells[4].top_left.y, cells[4].bottom_right().unwrap().y);
+        assert!(dirty.top_left.y >= top, "{dirty:?}");
+        assert!(dirty.bottom_right().unwrap().y <= bottom, "{dirty:?}");
+        // No other cell's pixels are in it.
+        for cell in &cells[..4] {
+            assert!(
+                cell.intersection(&dirty).is_zero_sized(),
+                "{cell:?} in {dirty:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_new_hour_refreshes_the_digits_that_change() {
+        let cells = cells();
+        let dirty = refreshed("2024-05-01T12:59:30+03:00", "2024-05-01T13:00:00+03:00");
+        let across = (dirty.top_left.x, dirty.top_left.x + dirty.size.width as i32);
+        // The 1 stays, so the refresh starts at the hour's second digit.
+        assert_eq!(across, (columns(cells[1]).0, columns(cells[4]).1));
+
+        let dirty = refreshed("2024-05-01T19:59:30+03:00", "2024-05-01T20:00:00+03:00");
+        let across = (dirty.top_left.x, dirty.top_left.x + dirty.size.width as i32);
+        assert_eq!(across, (columns(cells[0]).0, columns(cells[4]).1));
+        assert!(dirty.top_left.y >= cells[0].top_left.y, "{dirty:?}");
+        assert!(dirty.bottom_right().unwrap().y <= cells[0].bottom_right().unwrap().y);
+    }
+}
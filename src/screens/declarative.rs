@@ -0,0 +1,680 @@
+//! Screens described in the config as a stack of elements, top to bottom:
+//!
+//! ```toml
+//! [layouts.care]
+//! elements = [
+//!     { kind = "text", text = "How's {name}?", size = "large", align = "center" },
+//!     { kind = "hline" },
+//!     { kind = "bar", value = "hunger", label = "Food" },
+//! ]
+//! ```
+//!
+//! Values come from the same variables as text templates. Everything is
+//! checked when the screen is built, so a typo stops startup with the
+//! element and field it's in.
+//!
+//! Sizes are classes rather than pixels: `small` and `large` text, and
+//! bars, come out bigger with `accessibility.large_text`. A stack that
+//! fits normally can then run out of room; whatever doesn't fit is left
+//! off the bottom.
+//!
+//! With the `weather` feature, a `forecast` element draws the next twelve
+//! hours as a strip, see [`draw_forecast`].
+
+use anyhow::{bail, Result};
+use embedded_graphics::mono_font::iso_8859_15::{FONT_10X20, FONT_6X10};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle};
+
+use super::{Context, Profile, Screen};
+use crate::config::{ElementConfig, LayoutConfig};
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::icons::Icon;
+use crate::template::{self, Template};
+use crate::text::{self, Align, BigText, HAlign, VAlign};
+#[cfg(feature = "weather")]
+use crate::weather;
+
+/// Space between elements, and around the stack.
+const GAP: i32 = 2;
+const MARGIN: i32 = 4;
+const BAR_HEIGHT: u32 = 8;
+/// A forecast strip's height unless the element sets one.
+#[cfg(feature = "weather")]
+const FORECAST_HEIGHT: u32 = 40;
+
+pub struct DeclarativeScreen {
+    name: &'static str,
+    elements: Vec<Element>,
+    profile: Profile,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scale {
+    Small,
+    Large,
+}
+
+enum Element {
+    Text {
+        template: Template,
+        align: HAlign,
+        size: Scale,
+    },
+    Bar {
+        variable: String,
+        min: f64,
+        max: f64,
+        label: Option<String>,
+    },
+    Icon {
+        icon: Icon,
+        align: HAlign,
+        size: Scale,
+    },
+    Spacer(u32),
+    Hline,
+    #[cfg(feature = "weather")]
+    Forecast(u32),
+}
+
+impl DeclarativeScreen {
+    pub fn new(name: &'static str, config: &LayoutConfig, profile: Profile) -> Result<Self> {
+        let mut elements = parse(name, config)?;
+        let mut bottom = GAP;
+        let fits = elements
+            .iter()
+            .take_while(|element| {
+                bottom += element.height(profile) as i32 + GAP;
+                bottom <= HEIGHT as i32
+            })
+            .count();
+        if fits < elements.len() {
+            log::warn!(
+                "layouts.{name}: the last {} elements don't fit with large text",
+                elements.len() - fits
+            );
+            elements.truncate(fits);
+        }
+        Ok(DeclarativeScreen {
+            name,
+            elements,
+            profile,
+        })
+    }
+
+    /// Checks the layout called `name` without building it, e.g. while
+    /// loading the config.
+    pub fn check(name: &str, config: &LayoutConfig) -> Result<()> {
+        parse(name, config).map(drop)
+    }
+}
+
+/// Fails on the first element that doesn't make sense.
+fn parse(name: &str, config: &LayoutConfig) -> Result<Vec<Element>> {
+    let mut elements = Vec::new();
+    let mut bottom = GAP;
+    for (index, element) in config.elements.iter().enumerate() {
+        let element = match Element::parse(element) {
+            Ok(element) => element,
+            Err((field, message)) => bail!("layouts.{name}.elements[{index}].{field}: {message}"),
+        };
+        bottom += element.height(Profile::Regular) as i32 + GAP;
+        if bottom > HEIGHT as i32 {
+            bail!("layouts.{name}.elements[{index}]: runs past the bottom of the panel");
+        }
+        elements.push(element);
+    }
+    Ok(elements)
+}
+
+/// A field that's wrong, and why.
+type Invalid = (&'static str, String);
+
+impl Element {
+    fn parse(config: &ElementConfig) -> Result<Element, Invalid> {
+        let allowed: &[&str] = match config.kind.as_str() {
+            "text" => &["text", "align", "size"],
+            "bar" => &["value", "min", "max", "label"],
+            "icon" => &["icon", "align", "size"],
+            "spacer" => &["height"],
+            "hline" => &[],
+            #[cfg(feature = "weather")]
+            "forecast" => &["height"],
+            #[cfg(not(feature = "weather"))]
+            "forecast" => {
+                return Err(("kind", "forecast elements need the weather feature".into()))
+            }
+            kind => {
+                return Err((
+                    "kind",
+                    format!(
+                        "unknown kind {kind:?}, expected text, bar, icon, spacer, hline or forecast"
+                    ),
+                ))
+            }
+        };
+        let set = [
+            ("text", config.text.is_some()),
+            ("value", config.value.is_some()),
+            ("min", config.min.is_some()),
+            ("max", config.max.is_some()),
+            ("label", config.label.is_some()),
+            ("icon", config.icon.is_some()),
+            ("height", config.height.is_some()),
+            ("align", config.align.is_some()),
+            ("size", config.size.is_some()),
+        ];
+        if let Some((field, _)) = set
+            .iter()
+            .find(|(field, set)| *set && !allowed.contains(field))
+        {
+            return Err((field, format!("not used by {} elements", config.kind)));
+        }
+        let required = |field: &'static str, value: &Option<String>| {
+            value
+                .clone()
+                .ok_or((field, format!("required for {} elements", config.kind)))
+        };
+
+        Ok(match config.kind.as_str() {
+            "text" => {
+                let text = required("text", &config.text)?;
+                let template = Template::parse(&text).map_err(|err| ("text", err))?;
+                if let Some(name) = template.unknown_variables().next() {
+                    return Err(("text", format!("unknown variable {{{name}}}")));
+                }
+                Element::Text {
+                    template,
+                    align: parse_align(config)?,
+                    size: parse_size(config)?,
+                }
+            }
+            "bar" => {
+                let variable = required("value", &config.value)?;
+                if !template::NUMBERS.contains(&variable.as_str()) {
+                    return Err((
+                        "value",
+                        format!(
+                            "{variable:?} isn't a number; try one of {}",
+                            template::NUMBERS.join(", ")
+                        ),
+                    ));
+                }
+                let (min, max) = (config.min.unwrap_or(0.0), config.max.unwrap_or(100.0));
+                if min >= max || !min.is_finite() || !max.is_finite() {
+                    return Err(("max", format!("has to be above min ({min})")));
+                }
+                Element::Bar {
+                    variable,
+                    min,
+                    max,
+                    label: config.label.clone(),
+                }
+            }
+            "icon" => {
+                let name = required("icon", &config.icon)?;
+                let icon = Icon::parse(&name).ok_or(("icon", format!("unknown icon {name:?}")))?;
+                Element::Icon {
+                    icon,
+                    align: parse_align(config)?,
+                    size: parse_size(config)?,
+                }
+            }
+            "spacer" => Element::Spacer(
+                config
+                    .height
+                    .ok_or(("height", "required for spacer elements".into()))?,
+            ),
+            #[cfg(feature = "weather")]
+            "forecast" => {
+                let height = config.height.unwrap_or(FORECAST_HEIGHT);
+                if height < 8 {
+                    return Err(("height", format!("has to be at least 8, got {height}")));
+                }
+                Element::Forecast(height)
+            }
+            _ => Element::Hline,
+        })
+    }
+
+    fn height(&self, profile: Profile) -> u32 {
+        match self {
+            Element::Text {
+                size: Scale::Small, ..
+            } => profile.font().character_size.height,
+            Element::Text {
+                size: Scale::Large, ..
+            } => 2 * profile.font().character_size.height,
+            Element::Bar { .. } => profile
+                .bar_height(BAR_HEIGHT)
+                .max(profile.line_height() - 1),
+            // Icons don't come bigger than large.
+            Element::Icon { size, .. } if *size == Scale::Large || profile == Profile::Large => {
+                2 * Icon::SIZE
+            }
+            Element::Icon { .. } => Icon::SIZE,
+            Element::Spacer(height) => *height,
+            Element::Hline => 3,
+            #[cfg(feature = "weather")]
+            Element::Forecast(height) => *height,
+        }
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame, area: Rectangle, profile: Profile) {
+        match self {
+            Element::Text {
+                template,
+                align,
+                size,
+            } => {
+                let text = ctx.render(template);
+                let align = Align::new(*align, VAlign::Middle);
+                match size {
+                    Scale::Small => {
+                        let text = profile.fit(&text, area.size.width);
+                        text::draw(frame, &text, profile.font(), Color::Black, area, align);
+                    }
+                    Scale::Large => {
+                        let font = match profile {
+                            Profile::Regular => &FONT_6X10,
+                            Profile::Large => &FONT_10X20,
+                        };
+                        BigText::new(font, Color::Black, ctx.supersample())
+                            .draw(frame, &text, area, align)
+                    }
+                }
+            }
+            Element::Bar {
+                variable,
+                min,
+                max,
+                label,
+            } => {
+                let mut bar = area;
+                let bar_height = profile.bar_height(BAR_HEIGHT);
+                if let Some(label) = label {
+                    let font = profile.font();
+                    // Room for a word or so, so bars line up under each other.
+                    let indent = 8 * font.character_size.width as i32 + 4;
+                    let label = profile.fit(label, indent as u32 - 4);
+                    text::draw(frame, &label, font, Color::Black, area, Align::LEFT);
+                    bar.top_left.x += indent;
+                    bar.size.width -= indent as u32;
+                }
+                let bar = Rectangle::new(
+                    bar.top_left + Point::new(0, (area.size.height - bar_height) as i32 / 2),
+                    Size::new(bar.size.width, bar_height),
+                );
+                bar.into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+                    .draw(frame)
+                    .ok();
+                // `?` and the like, before the value is known, leave it empty.
+                let value = ctx.var(variable).and_then(|v| v.parse::<f64>().ok());
+                let Some(value) = value else {
+                    return;
+                };
+                let share = ((value - min) / (max - min)).clamp(0.0, 1.0);
+                let filled = ((bar.size.width - 2) as f64 * share).round() as u32;
+                Rectangle::new(
+                    bar.top_left + Point::new(1, 1),
+                    Size::new(filled, bar_height - 2),
+                )
+                .into_styled(PrimitiveStyle::with_fill(Color::Black))
+                .draw(frame)
+                .ok();
+            }
+            Element::Icon { icon, align, .. } => {
+                let edge = self.height(profile);
+                let origin =
+                    Align::new(*align, VAlign::Middle).position(Size::new(edge, edge), area);
+                match edge == Icon::SIZE {
+                    true => icon.draw(frame, origin, Color::Black),
+                    false => icon.draw_large(frame, origin, Color::Black),
+                }
+            }
+            Element::Spacer(_) => {}
+            Element::Hline => {
+                let y = area.top_left.y + 1;
+                Line::new(Point::new(0, y), Point::new(WIDTH as i32 - 1, y))
+                    .into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+                    .draw(frame)
+                    .ok();
+            }
+            #[cfg(feature = "weather")]
+            Element::Forecast(_) => {
+                let forecast = weather::reduce(&ctx.snapshot.forecast, ctx.now.to_utc());
+                draw_forecast(ctx, frame, area, profile, &forecast);
+            }
+        }
+    }
+}
+
+/// Twelve columns across `area`, one an hour from the current one: a bar
+/// as tall as the chance of rain, and a red mark at the temperature,
+/// scaled to the day's lowest and highest. Hours with no forecast are
+/// hollow. Under them, a red line marks the current hour, and every third
+/// hour is labelled when there's room.
+#[cfg(feature = "weather")]
+fn draw_forecast(
+    ctx: &Context,
+    frame: &mut Frame,
+    area: Rectangle,
+    profile: Profile,
+    forecast: &weather::Forecast,
+) {
+    let font = profile.font();
+    let label = font.character_size.height;
+    let labelled = area.size.height >= 2 * label + 8;
+    // The marker under the columns, and a pixel either side of it.
+    let below = 4 + if labelled { label } else { 0 };
+    let plot = Rectangle::new(
+        area.top_left,
+        Size::new(area.size.width, area.size.height - below),
+    );
+    let column = plot.size.width / weather::HOURS as u32;
+    let width = if column > 2 { column - 2 } else { column };
+    let left = plot.top_left.x + (plot.size.width - column * weather::HOURS as u32) as i32 / 2;
+    let bottom = plot.top_left.y + plot.size.height as i32 - 1;
+    let pen = PrimitiveStyle::with_stroke(Color::Black, 1);
+    let fill = |color| PrimitiveStyle::with_fill(color);
+
+    Line::new(
+        Point::new(left, bottom),
+        Point::new(left + (column * weather::HOURS as u32) as i32 - 1, bottom),
+    )
+    .into_styled(pen)
+    .draw(frame)
+    .ok();
+    for (index, slot) in forecast.slots.iter().enumerate() {
+        let x = left + (index as u32 * column + (column - width) / 2) as i32;
+        let Some(hour) = slot else {
+            Rectangle::new(
+                Point::new(x, plot.top_left.y),
+                Size::new(width, plot.size.height),
+            )
+            .into_styled(pen)
+            .draw(frame)
+            .ok();
+            continue;
+        };
+        let rain = ((plot.size.height - 1) as f64 * hour.precipitation / 100.0).round() as u32;
+        Rectangle::new(Point::new(x, bottom - rain as i32), Size::new(width, rain))
+            .into_styled(fill(Color::Black))
+            .draw(frame)
+            .ok();
+        // An even day draws a flat line across the middle.
+        let share = match forecast.range {
+            Some((low, high)) if high > low => (high - hour.temperature) / (high - low),
+            _ => 0.5,
+        };
+        let y = plot.top_left.y + ((plot.size.height - 3) as f64 * share).round() as i32;
+        Rectangle::new(Point::new(x, y), Size::new(width, 2))
+            .into_styled(fill(Color::Red))
+            .draw(frame)
+            .ok();
+    }
+
+    Rectangle::new(Point::new(left, bottom + 2), Size::new(column, 2))
+        .into_styled(fill(Color::Red))
+        .draw(frame)
+        .ok();
+    if !labelled {
+        return;
+    }
+    for index in (0..weather::HOURS).step_by(3) {
+        let at = ctx
+            .clock
+            .localize(forecast.start + chrono::TimeDelta::hours(index as i64));
+        let x = left + (index as u32 * column) as i32;
+        let area = Rectangle::new(Point::new(x, bottom + 5), Size::new(column * 3, label));
+        text::draw(
+            frame,
+            &at.format("%H").to_string(),
+            font,
+            Color::Black,
+            area,
+            Align::LEFT,
+        );
+    }
+}
+
+fn parse_align(config: &ElementConfig) -> Result<HAlign, Invalid> {
+    match config.align.as_deref() {
+        None | Some("left") => Ok(HAlign::Left),
+        Some("center") => Ok(HAlign::Center),
+        Some("right") => Ok(HAlign::Right),
+        Some(other) => Err((
+            "align",
+            format!("unknown alignment {other:?}, expected left, center or right"),
+        )),
+    }
+}
+
+fn parse_size(config: &ElementConfig) -> Result<Scale, Invalid> {
+    match config.size.as_deref() {
+        None | Some("small") => Ok(Scale::Small),
+        Some("large") => Ok(Scale::Large),
+        Some(other) => Err((
+            "size",
+            format!("unknown size {other:?}, expected small or large"),
+        )),
+    }
+}
+
+impl Screen for DeclarativeScreen {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn sources(&self) -> &'static [&'static str] {
+        #[cfg(feature = "weather")]
+        if self
+            .elements
+            .iter()
+            .any(|element| matches!(element, Element::Forecast(_)))
+        {
+            return &["weather"];
+        }
+        &[]
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let mut top = GAP;
+        for element in &self.elements {
+            let height = element.height(self.profile);
+            let area = Rectangle::new(
+                Point::new(MARGIN, top),
+                Size::new(WIDTH - 2 * MARGIN as u32, height),
+            );
+            element.draw(ctx, frame, area, self.profile);
+            top += height as i32 + GAP;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screens::Fixture;
+
+    /// Layouts in here are drawn and compared with
+    /// `tests/golden/layout-{stem}.txt`.
+    const FIXTURES: &str = "tests/fixtures/layouts";
+
+    fn layout(toml: &str) -> LayoutConfig {
+        toml::from_str(toml).expect("layout parses as TOML")
+    }
+
+    /// A pet partway down every bar, so the fills show.
+    fn fixture() -> Fixture {
+        let mut fixture = Fixture::at("2024-05-01T12:04:30+03:00");
+        fixture.pet.hunger = 95.0;
+        fixture.pet.happiness = 50.0;
+        fixture.pet.energy = 5.0;
+        fixture.pet.training = 30.0;
+        fixture
+    }
+
+    #[test]
+    fn errors_name_the_element_and_field() {
+        for (elements, error) in [
+            (
+                r#"{ kind = "text", text = "hi" }, { kind = "dial" }"#,
+                "layouts.test.elements[1].kind: unknown kind \"dial\"",
+            ),
+            (
+                r#"{ kind = "hline", text = "hi" }"#,
+                "layouts.test.elements[0].text: not used by hline elements",
+            ),
+            (
+                r#"{ kind = "hline" }, { kind = "bar", label = "Food" }"#,
+                "layouts.test.elements[1].value: required for bar elements",
+            ),
+            (
+                r#"{ kind = "bar", value = "name" }"#,
+                "layouts.test.elements[0].value: \"name\" isn't a number",
+            ),
+            (
+                r#"{ kind = "bar", value = "hunger", min = 50, max = 50 }"#,
+                "layouts.test.elements[0].max: has to be above min (50)",
+            ),
+            (
+                r#"{ kind = "text", text = "{nmae}" }"#,
+                "layouts.test.elements[0].text: unknown variable {nmae}",
+            ),
+            (
+                r#"{ kind = "text", text = "{name" }"#,
+                "layouts.test.elements[0].text: ",
+            ),
+            (
+                r#"{ kind = "spacer" }, { kind = "hline" }"#,
+                "layouts.test.elements[0].height: required for spacer elements",
+            ),
+            (
+                r#"{ kind = "spacer", height = 4 }, { kind = "icon", icon = "kettle" }"#,
+                "layouts.test.elements[1].icon: unknown icon \"kettle\"",
+            ),
+            (
+                r#"{ kind = "icon", icon = "heart", align = "middle" }"#,
+                "layouts.test.elements[0].align: unknown alignment \"middle\"",
+            ),
+            (
+                r#"{ kind = "text", text = "hi", size = "huge" }"#,
+                "layouts.test.elements[0].size: unknown size \"huge\"",
+            ),
+            (
+                r#"{ kind = "spacer", height = 60 }, { kind = "spacer", height = 60 }"#,
+                "layouts.test.elements[1]: runs past the bottom of the panel",
+            ),
+        ] {
+            let config = layout(&format!("elements = [{elements}]"));
+            let err = DeclarativeScreen::check("test", &config)
+                .expect_err(elements)
+                .to_string();
+            assert!(err.starts_with(error), "{elements}: {err}");
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_caught_by_the_config() {
+        let err =
+            toml::from_str::<LayoutConfig>(r#"elements = [{ kind = "text", colour = "red" }]"#)
+                .unwrap_err();
+        assert!(err.to_string().contains("unknown field `colour`"), "{err}");
+    }
+
+    #[test]
+    fn example_layouts_match_the_golden_renders() {
+        let fixture = fixture();
+        for name in ["care", "host"] {
+            let config = &fixture.config.layouts[name];
+            let screen = DeclarativeScreen::new("example", config, Profile::Regular).unwrap();
+            fixture.assert_golden(&screen, &format!("layout-{name}"));
+        }
+    }
+
+    #[test]
+    fn fixture_layouts_match_the_golden_renders() {
+        let fixture = fixture();
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(FIXTURES);
+        let mut paths: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty(), "no layouts in {FIXTURES}");
+        for path in paths {
+            let stem = path.file_stem().unwrap().to_str().unwrap();
+            let config = layout(&std::fs::read_to_string(&path).unwrap());
+            let screen = DeclarativeScreen::new("fixture", &config, Profile::Regular)
+                .unwrap_or_else(|err| panic!("{}: {err:#}", path.display()));
+            fixture.assert_golden(&screen, &format!("layout-{stem}"));
+        }
+    }
+
+    /// Strips drawn from the weather module's captured payload, wide and
+    /// labelled, and narrow without labels.
+    #[cfg(feature = "weather")]
+    #[test]
+    fn forecast_strips_fit_their_rectangle() {
+        let payload = include_str!("../../tests/fixtures/weather/open-meteo.json");
+        let mut fixture = fixture();
+        fixture.snapshot.forecast =
+            weather::parse(&serde_json::from_str(payload).unwrap()).unwrap();
+        let ctx = fixture.context();
+        let forecast = weather::reduce(&ctx.snapshot.forecast, ctx.now.to_utc());
+        // 13:00 UTC, the fifth hour from 12:04 in Bucharest, has no chance
+        // of rain in the payload.
+        assert!(forecast.slots[4].is_none());
+        for (golden, area, below) in [
+            (
+                "forecast-wide",
+                Rectangle::new(Point::new(4, 20), Size::new(204, 60)),
+                14,
+            ),
+            (
+                "forecast-narrow",
+                Rectangle::new(Point::new(30, 40), Size::new(70, 20)),
+                4,
+            ),
+        ] {
+            let mut frame = Frame::new(WIDTH, HEIGHT);
+            draw_forecast(&ctx, &mut frame, area, Profile::Regular, &forecast);
+            for y in 0..HEIGHT as i32 {
+                for x in 0..WIDTH as i32 {
+                    if !area.contains(Point::new(x, y)) {
+                        assert_eq!(frame.pixel(x, y), Color::White, "{golden} at {x},{y}");
+                    }
+                }
+            }
+            // The missing hour is an outline, empty down the middle.
+            let column = area.size.width as i32 / 12;
+            let left = area.top_left.x + (area.size.width as i32 - 12 * column) / 2;
+            let middle = left + 4 * column + column / 2;
+            let (top, bottom) = (
+                area.top_left.y,
+                area.top_left.y + area.size.height as i32 - below - 1,
+            );
+            assert_eq!(frame.pixel(middle, top), Color::Black, "{golden}");
+            for y in top + 1..bottom - 1 {
+                assert_eq!(
+                    frame.pixel(middle, y),
+                    Color::White,
+                    "{golden} at {middle},{y}"
+                );
+            }
+            crate::screens::assert_golden(&frame, golden);
+        }
+    }
+
+    #[test]
+    fn large_text_leaves_off_what_does_not_fit() {
+        let config = &fixture().config.layouts["care"];
+        let regular = DeclarativeScreen::new("care", config, Profile::Regular).unwrap();
+        let large = DeclarativeScreen::new("care", config, Profile::Large).unwrap();
+        assert_eq!(regular.elements.len(), config.elements.len());
+        assert!(large.elements.len() < regular.elements.len());
+    }
+}
@@ -0,0 +1,99 @@
+//! Unread GitHub notifications and how CI is doing.
+
+use embedded_graphics::mono_font::iso_8859_15::FONT_10X20;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{Context, Profile, Screen};
+use crate::display::{Color, Frame, WIDTH};
+use crate::github::{CiState, NOTIFICATION_PAGE};
+use crate::guest::Privacy;
+use crate::icons::Icon;
+use crate::text::{self, Align};
+
+const COLUMN_WIDTH: u32 = WIDTH / 2;
+const ICON_Y: i32 = 30;
+
+pub struct GithubScreen;
+
+impl Screen for GithubScreen {
+    fn name(&self) -> &'static str {
+        "github"
+    }
+
+    fn sources(&self) -> &'static [&'static str] {
+        &["github"]
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let status = &ctx.snapshot.github;
+        let profile = ctx.profile();
+        let repo = status.repo.as_deref();
+        let repo = repo.and_then(|repo| ctx.shown(repo, Privacy::Hidden));
+        ctx.title(frame, "GitHub", repo);
+
+        let unread = match status.unread {
+            Some(count) if count >= NOTIFICATION_PAGE => format!("{count}+"),
+            Some(count) => count.to_string(),
+            None => "-".into(),
+        };
+        draw_column(
+            frame,
+            profile,
+            0,
+            Icon::Bell,
+            Color::Black,
+            &unread,
+            "unread",
+        );
+
+        let (icon, color, label) = match status.ci {
+            Some(CiState::Passing) => (Icon::Check, Color::Black, "passing"),
+            Some(CiState::Failing) => (Icon::Cross, Color::Red, "failing"),
+            Some(CiState::Running) => (Icon::Cloud, Color::Black, "running"),
+            Some(CiState::Other) => (Icon::Fog, Color::Black, "no verdict"),
+            None => (Icon::Fog, Color::Black, "unknown"),
+        };
+        draw_column(frame, profile, 1, icon, color, "CI", label);
+    }
+}
+
+/// A large icon with a big value beside it and a caption underneath.
+fn draw_column(
+    frame: &mut Frame,
+    profile: Profile,
+    column: u32,
+    icon: Icon,
+    color: Color,
+    value: &str,
+    caption: &str,
+) {
+    let left = (column * COLUMN_WIDTH) as i32;
+    let size = 2 * Icon::SIZE as i32;
+    icon.draw_large(frame, Point::new(left + 12, ICON_Y), color);
+    let value_area = Rectangle::new(
+        Point::new(left + 12 + size + 6, ICON_Y),
+        Size::new(COLUMN_WIDTH - size as u32 - 20, size as u32),
+    );
+    text::draw(
+        frame,
+        value,
+        &FONT_10X20,
+        Color::Black,
+        value_area,
+        Align::LEFT,
+    );
+    let caption_area = Rectangle::new(
+        Point::new(left, ICON_Y + size + 8),
+        Size::new(COLUMN_WIDTH, profile.line_height() + 1),
+    );
+    let caption = profile.fit(caption, COLUMN_WIDTH);
+    text::draw(
+        frame,
+        &caption,
+        profile.font(),
+        color,
+        caption_area,
+        Align::CENTER,
+    );
+}
@@ -0,0 +1,91 @@
+//! The choice of egg on the first run, see [`crate::hatch`].
+
+use std::time::{Duration, Instant};
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+
+use super::{draw_title, Context, Profile, Screen};
+use crate::artwork::{self, SpriteId};
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::hatch::Picker;
+use crate::pet::Egg;
+use crate::sprite::Flip;
+use crate::text::{self, Align};
+
+const SCALE: u32 = 2;
+
+pub struct HatchScreen<'a>(pub &'a Picker);
+
+impl Screen for HatchScreen<'_> {
+    fn name(&self) -> &'static str {
+        "hatch"
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let profile = ctx.profile();
+        let font = profile.font();
+        let egg_top = draw_title(frame, profile, "Pick an egg", None) + 5;
+
+        let shell = artwork::get(SpriteId::Egg);
+        let column = WIDTH / Egg::ALL.len() as u32;
+        for (index, &egg) in Egg::ALL.iter().enumerate() {
+            let left = index as i32 * column as i32;
+            let origin = Point::new(left + (column - shell.width * SCALE) as i32 / 2, egg_top);
+            let color = if egg == Egg::Ember {
+                Color::Red
+            } else {
+                Color::Black
+            };
+            shell.draw_scaled(frame, origin, color, SCALE);
+            if ctx.config.simulator.greyscale {
+                shell.shade(frame, origin, Flip::None, SCALE);
+            }
+            let (pattern, color) = match egg {
+                Egg::Plain => (None, Color::Black),
+                Egg::Speckled => (Some(SpriteId::EggSpots), Color::Black),
+                Egg::Ember => (Some(SpriteId::EggFlames), Color::Red),
+            };
+            if let Some(pattern) = pattern.map(artwork::get) {
+                pattern.draw_scaled(frame, origin, color, SCALE);
+                if ctx.config.simulator.greyscale {
+                    pattern.shade(frame, origin, Flip::None, SCALE);
+                }
+            }
+
+            let label = Rectangle::new(
+                Point::new(left, egg_top + (shell.height * SCALE) as i32 + 4),
+                Size::new(column, profile.line_height() + 1),
+            );
+            // The selected egg's label is picked out as white on black.
+            let color = if index == self.0.selected {
+                label
+                    .into_styled(PrimitiveStyle::with_fill(Color::Black))
+                    .draw(frame)
+                    .ok();
+                Color::White
+            } else {
+                Color::Black
+            };
+            text::draw(frame, egg.label(), font, color, label, Align::CENTER);
+        }
+
+        // The large font has no room for the hint; the README has it.
+        if profile == Profile::Regular {
+            let hint = Rectangle::new(Point::new(2, HEIGHT as i32 - 11), Size::new(WIDTH - 4, 10));
+            text::draw(
+                frame,
+                "tap next, hold choose",
+                font,
+                Color::Black,
+                hint,
+                Align::LEFT,
+            );
+        }
+    }
+
+    /// Until the plain egg is picked by default.
+    fn desired_interval(&self, _ctx: &Context) -> Duration {
+        self.0.deadline().saturating_duration_since(Instant::now())
+    }
+}
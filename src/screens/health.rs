@@ -0,0 +1,120 @@
+//! Metrics pushed over HTTP, like the day's steps, with progress towards
+//! their goals.
+
+use chrono::Duration;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+
+use super::{Context, Profile, Screen};
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::pet::Metric;
+use crate::text::{self, Align};
+
+const BAR_HEIGHT: u32 = 8;
+/// Readings older than this are drawn greyed out.
+const STALE_AFTER: Duration = Duration::hours(24);
+
+pub struct HealthScreen;
+
+impl Screen for HealthScreen {
+    fn name(&self) -> &'static str {
+        "health"
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let profile = ctx.profile();
+        let top = ctx.title(frame, "Health", None);
+
+        let metrics = &ctx.pet.metrics;
+        if metrics.is_empty() {
+            let area = Rectangle::new(Point::new(0, top), Size::new(WIDTH, HEIGHT - top as u32));
+            text::draw(
+                frame,
+                "Nothing pushed yet",
+                profile.font(),
+                Color::Black,
+                area,
+                Align::CENTER,
+            );
+            return;
+        }
+        // Rows the screen has room for; further metrics aren't shown.
+        let (rows, row_height) = match profile {
+            Profile::Regular => (3, 30),
+            Profile::Large => (2, 40),
+        };
+        for (row, (name, metric)) in metrics.iter().take(rows).enumerate() {
+            let top = top + 3 + row as i32 * row_height;
+            let stale = ctx.now.to_utc() - metric.at > STALE_AFTER;
+            draw_metric(frame, top, name, metric, stale, profile);
+        }
+    }
+}
+
+fn draw_metric(
+    frame: &mut Frame,
+    top: i32,
+    name: &str,
+    metric: &Metric,
+    stale: bool,
+    profile: Profile,
+) {
+    let font = profile.font();
+    let label = if stale {
+        format!("{name} (stale)")
+    } else {
+        name.to_string()
+    };
+    let area = Rectangle::new(
+        Point::new(4, top),
+        Size::new(WIDTH - 8, font.character_size.height),
+    );
+    let value = match metric.goal {
+        Some(goal) => format!("{} / {}", format_value(metric.value), format_value(goal)),
+        None => format_value(metric.value),
+    };
+    let value = text::draw(frame, &value, font, Color::Black, area, Align::RIGHT);
+    let room = (value.top_left.x - area.top_left.x - 4).max(0) as u32;
+    text::draw(
+        frame,
+        &profile.fit(&label, room),
+        font,
+        Color::Black,
+        area,
+        Align::LEFT,
+    );
+
+    let Some(goal) = metric.goal else {
+        return;
+    };
+    let origin = Point::new(4, top + font.character_size.height as i32 + 2);
+    let width = WIDTH - 8;
+    let bar_height = profile.bar_height(BAR_HEIGHT);
+    Rectangle::new(origin, Size::new(width, bar_height))
+        .into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+        .draw(frame)
+        .ok();
+    let progress = (metric.value / goal).clamp(0.0, 1.0);
+    let filled = Rectangle::new(
+        origin + Point::new(1, 1),
+        Size::new(
+            ((width - 2) as f64 * progress).round() as u32,
+            bar_height - 2,
+        ),
+    );
+    // No grey on the panel, so a stale bar is only half filled in.
+    let pixels = filled
+        .points()
+        .filter(|p| !stale || (p.x + p.y) % 2 == 0)
+        .map(|p| Pixel(p, Color::Black));
+    frame.draw_iter(pixels).ok();
+}
+
+/// Whole numbers as they are, anything else to one decimal.
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{value:.0}")
+    } else {
+        format!("{value:.1}")
+    }
+}
@@ -0,0 +1,207 @@
+//! The CPU temperature over the last week, an hour to a cell: a row for
+//! each day, today at the bottom. Cells are shaded by where they fall in
+//! the week's range, from blank for the coolest quarter to solid red for
+//! the hottest. An hour the panel was off for is an empty outline.
+
+use std::time::Duration;
+
+use chrono::{NaiveDate, Timelike};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+
+use super::{Context, Profile, Screen};
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::shade::{self, Bayer};
+use crate::text::{self, Align};
+
+/// The week's range is stretched to at least this many degrees, so a
+/// steady week stays cool rather than being shaded by the odd degree.
+const MIN_SPREAD: f32 = 4.0;
+/// Hours between labels along the bottom.
+const LABEL_EVERY: usize = 6;
+
+pub struct HeatmapScreen;
+
+impl Screen for HeatmapScreen {
+    fn name(&self) -> &'static str {
+        "heatmap"
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let week = ctx.tally.temperatures(ctx.now.date_naive());
+        let range = range(&week);
+        let right = range.map_or("no data".into(), |(low, high)| {
+            format!("{low:.0}-{high:.0}°C")
+        });
+        let profile = ctx.profile();
+        // The large font doesn't fit a day's row, so that goes without the
+        // days; today is still the bottom row.
+        let (title, days) = match profile {
+            Profile::Regular => ("CPU temperature", true),
+            Profile::Large => ("CPU", false),
+        };
+        let top = ctx.title(frame, title, Some(&right));
+        let font = profile.font();
+        let glyph = font.character_size;
+        let line = profile.line_height() as i32;
+        let left = if days { 2 + glyph.width as i32 + 2 } else { 2 };
+        let pitch = Size::new(
+            (WIDTH - left as u32 - 2) / 24,
+            (HEIGHT - top as u32 - line as u32 - 1) / 7,
+        );
+        let cell = pitch - Size::new(1, 1);
+
+        for (row, (date, hours)) in week.iter().enumerate() {
+            let y = top + row as i32 * pitch.height as i32;
+            if days {
+                let area = Rectangle::new(Point::new(2, y), Size::new(glyph.width, pitch.height));
+                text::draw(
+                    frame,
+                    &initial(*date),
+                    font,
+                    Color::Black,
+                    area,
+                    Align::LEFT,
+                );
+            }
+            // Today only goes as far as now.
+            let upto = match row {
+                6 => ctx.now.hour() as usize + 1,
+                _ => 24,
+            };
+            for (hour, temperature) in hours.iter().enumerate().take(upto) {
+                let at = Point::new(left + hour as i32 * pitch.width as i32, y);
+                let area = Rectangle::new(at, cell);
+                match (temperature, range) {
+                    (Some(t), Some((low, high))) => draw_cell(frame, area, level(*t, low, high)),
+                    _ => {
+                        area.into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+                            .draw(frame)
+                            .ok();
+                    }
+                }
+            }
+        }
+
+        let y = top + 7 * pitch.height as i32;
+        for hour in (0..24).step_by(LABEL_EVERY) {
+            let at = Point::new(left + hour * pitch.width as i32, y);
+            let area = Rectangle::new(at, Size::new(LABEL_EVERY as u32 * pitch.width, line as u32));
+            text::draw(
+                frame,
+                &hour.to_string(),
+                font,
+                Color::Black,
+                area,
+                Align::LEFT,
+            );
+        }
+    }
+
+    /// Until the hour's cell is filled in.
+    fn desired_interval(&self, ctx: &Context) -> Duration {
+        let into = ctx.now.minute() * 60 + ctx.now.second();
+        Duration::from_secs((3600 - into) as u64)
+    }
+
+    fn is_dirty(&self, _ctx: &Context) -> bool {
+        false
+    }
+}
+
+/// The coolest and hottest hours of the week, if there were any.
+fn range(week: &[(NaiveDate, [Option<f32>; 24])]) -> Option<(f32, f32)> {
+    week.iter()
+        .flat_map(|(_, hours)| hours.iter().flatten())
+        .fold(None, |range, &t| match range {
+            None => Some((t, t)),
+            Some((low, high)) => Some((t.min(low), t.max(high))),
+        })
+}
+
+/// Which quarter of the range `t` is in, 0 for the coolest.
+fn level(t: f32, low: f32, high: f32) -> u8 {
+    let spread = (high - low).max(MIN_SPREAD);
+    ((t - low) / spread * 4.0).clamp(0.0, 3.0) as u8
+}
+
+fn draw_cell(frame: &mut Frame, area: Rectangle, level: u8) {
+    match level {
+        0 => {}
+        1 => shade::fill(frame, area, 0.25, Bayer::Four, Color::Black),
+        2 => shade::fill(frame, area, 0.75, Bayer::Two, Color::Black),
+        _ => shade::fill(frame, area, 1.0, Bayer::Two, Color::Red),
+    }
+}
+
+/// M, T, W and so on.
+fn initial(date: NaiveDate) -> String {
+    date.format("%a").to_string()[..1].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screens::Fixture;
+    use chrono::{DateTime, FixedOffset};
+
+    /// A week up to the fixture's time: warm afternoons, a hotter day the
+    /// day before yesterday, the panel off all of Saturday and for a few
+    /// hours last night.
+    fn week() -> Fixture {
+        let mut fixture = Fixture::at("2024-05-01T12:04:30+03:00");
+        let now = fixture.clock.now();
+        let stats = fixture.pet.stats();
+        let first = now - chrono::Duration::days(7);
+        fixture.tally = crate::summary::Tally::new(first.date_naive());
+        for hours_ago in (0..7 * 24).rev() {
+            let at: DateTime<FixedOffset> = now - chrono::Duration::hours(hours_ago);
+            let (day, hour) = (at.date_naive(), at.hour());
+            if day.format("%a").to_string() == "Sat" || (day == now.date_naive() && hour < 4) {
+                continue;
+            }
+            let afternoon = 12.0 - (hour as f32 - 15.0).abs();
+            let hot_day = if hours_ago / 24 == 2 { 6.0 } else { 0.0 };
+            fixture
+                .tally
+                .sample(at, stats, Some(42.0 + afternoon + hot_day));
+        }
+        fixture
+    }
+
+    #[test]
+    fn a_synthetic_week_matches_the_golden_render() {
+        let fixture = week();
+        fixture.assert_golden(&HeatmapScreen, "heatmap");
+        let mut large = week();
+        large.config.accessibility.large_text = true;
+        large.assert_golden(&HeatmapScreen, "heatmap-large");
+    }
+
+    #[test]
+    fn no_data_is_a_grid_of_outlines() {
+        let fixture = Fixture::at("2024-05-01T12:04:30+03:00");
+        let week = fixture.tally.temperatures(fixture.clock.now().date_naive());
+        assert_eq!(range(&week), None);
+        fixture.assert_golden(&HeatmapScreen, "heatmap-empty");
+    }
+
+    #[test]
+    fn cells_go_by_quarter_of_the_range() {
+        let levels: Vec<u8> = [40.0, 44.9, 45.0, 50.0, 55.0, 59.9, 60.0]
+            .iter()
+            .map(|&t| level(t, 40.0, 60.0))
+            .collect();
+        assert_eq!(levels, [0, 0, 1, 2, 3, 3, 3]);
+        // A steady week is spread to MIN_SPREAD, so it isn't all red.
+        assert_eq!(level(41.0, 40.0, 41.0), 1);
+        assert_eq!(level(40.0, 40.0, 40.0), 0);
+    }
+
+    #[test]
+    fn days_are_labelled_by_their_initial() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        assert_eq!(initial(date), "W");
+        assert_eq!(initial(date.succ_opt().unwrap()), "T");
+    }
+}
@@ -0,0 +1,70 @@
+//! The newest warnings and errors from the log, for when something's off
+//! and there's no shell to hand. Hold A for the next page back.
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use log::{Level, LevelFilter};
+
+use super::{Context, Screen};
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::guest::Privacy;
+use crate::logbuf;
+use crate::text::{self, Align};
+
+pub struct LogsScreen;
+
+impl Screen for LogsScreen {
+    fn name(&self) -> &'static str {
+        "logs"
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let profile = ctx.profile();
+        let font = profile.font();
+        let row_height = font.character_size.height as i32;
+        // Each entry is wrapped as a whole, with its time on its first line.
+        let columns = profile.columns(WIDTH - 4);
+        let mut lines = Vec::new();
+        for entry in logbuf::recent(LevelFilter::Warn, logbuf::CAPACITY) {
+            let at = entry.at.with_timezone(&ctx.now.timezone());
+            let color = match entry.level {
+                Level::Error => Color::Red,
+                _ => Color::Black,
+            };
+            // Warnings can name people and places; guests see that there
+            // were some.
+            let message = ctx
+                .shown(&entry.message, Privacy::Hidden)
+                .unwrap_or("(hidden)");
+            let entry = format!("{} {message}", at.format("%H:%M"));
+            lines.extend(text::wrap(&entry, columns).into_iter().map(|l| (l, color)));
+        }
+        // Text lines below the title.
+        let rows = ((HEIGHT - profile.line_height() - 3) as i32 / row_height) as usize;
+        let pages = lines.len().div_ceil(rows).max(1);
+        let page = ctx.runtime.log_page % pages;
+
+        let pages_label = format!("{}/{pages}", page + 1);
+        let top = ctx.title(frame, "Logs", Some(&pages_label));
+
+        if lines.is_empty() {
+            let area = Rectangle::new(Point::new(0, top), Size::new(WIDTH, HEIGHT - top as u32));
+            text::draw(
+                frame,
+                "No warnings",
+                font,
+                Color::Black,
+                area,
+                Align::CENTER,
+            );
+            return;
+        }
+        for (row, (line, color)) in lines.iter().skip(page * rows).take(rows).enumerate() {
+            let area = Rectangle::new(
+                Point::new(2, top + 1 + row as i32 * row_height),
+                Size::new(WIDTH - 4, row_height as u32),
+            );
+            text::draw(frame, line, font, *color, area, Align::LEFT);
+        }
+    }
+}
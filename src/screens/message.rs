@@ -0,0 +1,136 @@
+//! A note left with `inky-gotchi say`, in place of the carousel.
+
+use std::time::Duration;
+
+use embedded_graphics::mono_font::iso_8859_15::{FONT_10X20, FONT_6X10};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle};
+
+use super::{Context, Profile, Screen};
+use crate::clock::Clock;
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::guest::Privacy;
+use crate::icons::Icon;
+use crate::message::Message;
+use crate::text::{self, Align, BigText, HAlign, VAlign};
+
+pub struct MessageScreen<'a>(pub &'a Message);
+
+impl Screen for MessageScreen<'_> {
+    fn name(&self) -> &'static str {
+        "message"
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        // With guests round, the note only says it's there.
+        let hidden;
+        let message = match ctx.shown(&self.0.text, Privacy::Hidden) {
+            Some(_) => self.0,
+            None => {
+                hidden = Message {
+                    text: "1 note waiting".into(),
+                    ..self.0.clone()
+                };
+                &hidden
+            }
+        };
+        draw(frame, message, ctx.clock, ctx.profile(), ctx.supersample());
+    }
+
+    /// Up until the note expires, so it comes down on time.
+    fn desired_interval(&self, ctx: &Context) -> Duration {
+        match self.0.until {
+            Some(until) => (until - ctx.now.to_utc()).to_std().unwrap_or_default(),
+            None => Duration::MAX,
+        }
+    }
+}
+
+/// Draws `message` on its own, so `say` can show it without a daemon.
+pub fn draw(
+    frame: &mut Frame,
+    message: &Message,
+    clock: &Clock,
+    profile: Profile,
+    supersample: bool,
+) {
+    let footer_top = HEIGHT as i32 - profile.line_height() as i32 - 1;
+    let mut left = 4;
+    if let Some(icon) = message.icon.as_deref().and_then(Icon::parse) {
+        let size = 2 * Icon::SIZE as i32;
+        icon.draw_large(frame, Point::new(4, (footer_top - size) / 2), Color::Black);
+        left += size + 6;
+    }
+    let body = Rectangle::new(
+        Point::new(left, 2),
+        Size::new(WIDTH - left as u32 - 4, footer_top as u32 - 4),
+    );
+    // Large if it fits, small otherwise; with large text, small is the
+    // 10x20 font, which is about as big.
+    let big_lines = text::wrap(&message.text, (body.size.width / 12) as usize);
+    if big_lines.len() as u32 * 20 <= body.size.height {
+        let big = BigText::new(&FONT_6X10, Color::Black, supersample);
+        draw_lines(&big_lines, 20, body, |line, area| {
+            big.draw(frame, line, area, Align::LEFT)
+        });
+    } else {
+        let font = match profile {
+            Profile::Regular => &FONT_6X10,
+            Profile::Large => &FONT_10X20,
+        };
+        let size = font.character_size;
+        let lines = text::wrap(&message.text, (body.size.width / size.width) as usize);
+        draw_lines(&lines, size.height, body, |line, area| {
+            text::draw(frame, line, font, Color::Black, area, Align::LEFT);
+        });
+    }
+
+    Line::new(
+        Point::new(0, footer_top),
+        Point::new(WIDTH as i32 - 1, footer_top),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+    .draw(frame)
+    .ok();
+    let footer = footer(profile);
+    let font = profile.font();
+    let until = message.until.map(|until| {
+        let until = format!("until {}", clock.localize(until).format("%H:%M"));
+        text::draw(frame, &until, font, Color::Black, footer, Align::RIGHT)
+    });
+    if let Some(author) = &message.author {
+        let from = format!("- {author}");
+        let room = until.map_or(footer.size.width, |u| (u.top_left.x - 4 - 6).max(0) as u32);
+        let from = profile.fit(&from, room);
+        text::draw(frame, &from, font, Color::Black, footer, Align::LEFT);
+    }
+}
+
+/// Where the footer's text goes, under the line across the bottom.
+pub fn footer(profile: Profile) -> Rectangle {
+    let top = HEIGHT as i32 - profile.line_height() as i32;
+    Rectangle::new(
+        Point::new(4, top),
+        Size::new(WIDTH - 8, HEIGHT - top as u32),
+    )
+}
+
+/// Stacks `lines` of `height` pixels, centred vertically in `area`, and
+/// drops whatever doesn't fit.
+fn draw_lines(
+    lines: &[String],
+    height: u32,
+    area: Rectangle,
+    mut draw: impl FnMut(&str, Rectangle),
+) {
+    let shown = lines.len().min((area.size.height / height) as usize);
+    let block = Size::new(area.size.width, shown as u32 * height);
+    let top = Align::new(HAlign::Left, VAlign::Middle).position(block, area);
+    for (row, line) in lines.iter().take(shown).enumerate() {
+        let line_area = Rectangle::new(
+            top + Point::new(0, row as i32 * height as i32),
+            Size::new(area.size.width, height),
+        );
+        draw(line, line_area);
+    }
+}
@@ -0,0 +1,683 @@
+//! Everything that can be shown on the panel.
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, FixedOffset};
+use embedded_graphics::mono_font::iso_8859_15::{FONT_10X20, FONT_6X10};
+use embedded_graphics::mono_font::MonoFont;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle};
+
+use crate::clock::Clock;
+use crate::collector::Snapshot;
+use crate::config::Config;
+use crate::display::{Color, Frame, PanelInfo, WIDTH};
+use crate::guest::Privacy;
+use crate::icons::Icon;
+use crate::pet::Pet;
+use crate::quiet::Quiet;
+use crate::runtime::Runtime;
+use crate::summary::Tally;
+use crate::template::Template;
+use crate::text::{self, Align};
+use declarative::DeclarativeScreen;
+
+pub mod about;
+pub mod clock;
+pub mod declarative;
+#[cfg(feature = "github")]
+pub mod github;
+pub mod hatch;
+pub mod health;
+pub mod heatmap;
+pub mod logs;
+pub mod message;
+pub mod pet;
+pub mod poweroff;
+pub mod reminder;
+pub mod selfie;
+pub mod settings;
+pub mod sleep;
+pub mod stats;
+pub mod summary;
+#[cfg(feature = "ticker")]
+pub mod ticker;
+pub mod transfer;
+
+/// Data available to screens while drawing one frame.
+pub struct Context<'a> {
+    /// When this frame is for, in the configured timezone.
+    pub now: DateTime<FixedOffset>,
+    pub clock: &'a Clock,
+    pub config: &'a Config,
+    pub pet: &'a Pet,
+    pub runtime: &'a Runtime,
+    pub panel: &'a PanelInfo,
+    pub snapshot: &'a Snapshot,
+    /// How the day and the week before went, see [`crate::summary`].
+    pub tally: &'a Tally,
+    /// The day's quiet hours, if any are observed.
+    pub quiet: Option<&'a Quiet>,
+}
+
+impl Context<'_> {
+    pub fn profile(&self) -> Profile {
+        Profile::from_config(self.config)
+    }
+
+    /// [`draw_title`] for a screen in the carousel, with its [`marks`].
+    ///
+    /// [`marks`]: Context::marks
+    pub fn title(&self, frame: &mut Frame, title: &str, right: Option<&str>) -> i32 {
+        title_bar(frame, self.profile(), title, right, self.marks())
+    }
+
+    /// Icons for the headers saying how the panel's being run: a pin when
+    /// the carousel's pinned, and a mask in guest mode.
+    pub fn marks(&self) -> impl Iterator<Item = Icon> {
+        [
+            (self.runtime.pinned, Icon::Pin),
+            (self.runtime.guest, Icon::Mask),
+        ]
+        .into_iter()
+        .filter_map(|(on, icon)| on.then_some(icon))
+    }
+
+    /// `value` as it may be shown on this frame: as it is, unless guest
+    /// mode is on and `privacy` says otherwise. `None` when it has to be
+    /// left out.
+    pub fn shown<'v>(&self, value: &'v str, privacy: Privacy) -> Option<&'v str> {
+        privacy.apply(value, self.runtime.guest)
+    }
+
+    /// Whether to supersample large text: if it's on, and memory isn't
+    /// short, see [`crate::lowmem`].
+    pub fn supersample(&self) -> bool {
+        self.config.supersample_text && self.runtime.quality.supersample()
+    }
+
+    /// Fills in `template` with this frame's values.
+    pub fn render(&self, template: &Template) -> String {
+        template.render(|name| self.var(name))
+    }
+
+    /// The value of a template variable as it may be shown, see
+    /// [`Context::shown`]; `None` when there's no such variable.
+    fn var(&self, name: &str) -> Option<String> {
+        let (value, privacy) = self.field(name)?;
+        Some(self.shown(&value, privacy).unwrap_or_default().to_string())
+    }
+
+    /// The value of a template variable and how private it is; `?` when
+    /// it's known but not available yet.
+    fn field(&self, name: &str) -> Option<(String, Privacy)> {
+        if let Some(value) = pet_var(self.pet, self.now, name) {
+            return Some((value, Privacy::Public));
+        }
+        let snapshot = self.snapshot;
+        let (value, privacy) = match name {
+            "temp_c" => (
+                snapshot.cpu_temperature.map(|t| format!("{t:.0}")),
+                Privacy::Public,
+            ),
+            "uptime" => (snapshot.uptime.map(format_uptime), Privacy::Public),
+            "ip" => (
+                snapshot.ip_address.map(|ip| ip.to_string()),
+                Privacy::Masked,
+            ),
+            _ => return None,
+        };
+        Some((value.unwrap_or_else(|| "?".into()), privacy))
+    }
+}
+
+/// The template variables that only take the pet and the time, for text
+/// filled in away from a frame, like the replies in [`crate::chat`].
+pub fn pet_var(pet: &Pet, now: DateTime<FixedOffset>, name: &str) -> Option<String> {
+    let value = match name {
+        "name" => pet.name.clone(),
+        "mood" => pet.mood().label().into(),
+        "day" => ((now.to_utc() - pet.born).num_days() + 1).to_string(),
+        "time" => now.format("%H:%M").to_string(),
+        "date" => now.format("%d %b").to_string(),
+        "hunger" => format!("{:.0}", pet.hunger),
+        "happiness" => format!("{:.0}", pet.happiness),
+        "energy" => format!("{:.0}", pet.energy),
+        "training" => format!("{:.0}", pet.training),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// How big things are drawn. Screens take their text, row and bar sizes
+/// from here instead of fixed pixels, so `accessibility.large_text` can
+/// scale them all up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    Regular,
+    /// All text in FONT_10X20 and bars twice as tall.
+    Large,
+}
+
+impl Profile {
+    pub fn from_config(config: &Config) -> Self {
+        match config.accessibility.large_text {
+            true => Profile::Large,
+            false => Profile::Regular,
+        }
+    }
+
+    /// The font for everything that isn't a headline.
+    pub fn font(self) -> &'static MonoFont<'static> {
+        match self {
+            Profile::Regular => &FONT_6X10,
+            Profile::Large => &FONT_10X20,
+        }
+    }
+
+    /// A line of [`Profile::font`], with room to breathe.
+    pub fn line_height(self) -> u32 {
+        self.font().character_size.height + 1
+    }
+
+    /// Characters of [`Profile::font`] across `width` pixels.
+    pub fn columns(self, width: u32) -> usize {
+        (width / self.font().character_size.width) as usize
+    }
+
+    /// A bar `height` pixels tall in the regular profile.
+    pub fn bar_height(self, height: u32) -> u32 {
+        match self {
+            Profile::Regular => height,
+            Profile::Large => 2 * height,
+        }
+    }
+
+    /// `text` made to fit `width` pixels. The regular font drops whatever
+    /// spills over; the large one fits so few letters that it stops at the
+    /// last whole word instead, see [`text::shorten`].
+    pub fn fit(self, text: &str, width: u32) -> Cow<'_, str> {
+        match self {
+            Profile::Regular => Cow::Borrowed(text),
+            Profile::Large => text::shorten(text, self.columns(width)),
+        }
+    }
+}
+
+/// Draws `title` across the top, with `right` at the other end, and a rule
+/// under both. Returns where the space below starts.
+pub fn draw_title(frame: &mut Frame, profile: Profile, title: &str, right: Option<&str>) -> i32 {
+    title_bar(frame, profile, title, right, [])
+}
+
+/// [`draw_title`], with `marks` by `right`.
+fn title_bar(
+    frame: &mut Frame,
+    profile: Profile,
+    title: &str,
+    right: Option<&str>,
+    marks: impl IntoIterator<Item = Icon>,
+) -> i32 {
+    let height = profile.line_height() + 1;
+    let bar = Rectangle::new(Point::new(2, 0), Size::new(WIDTH - 4, height));
+    let font = profile.font();
+    let right = right.map(|right| text::draw(frame, right, font, Color::Black, bar, Align::RIGHT));
+    // Where the title has to stop.
+    let mut end = right.map_or(bar.top_left.x + bar.size.width as i32, |r| r.top_left.x - 6);
+    let mut x = right.map_or(end, |r| r.top_left.x - 3);
+    for icon in marks {
+        x -= Icon::SIZE as i32;
+        let top = (height as i32 - Icon::SIZE as i32).max(0) / 2;
+        icon.draw(frame, Point::new(x, top), Color::Black);
+        end = x - 6;
+        x -= 3;
+    }
+    let room = (end - bar.top_left.x).max(0) as u32;
+    let title = profile.fit(title, room);
+    text::draw(frame, &title, font, Color::Black, bar, Align::LEFT);
+    Line::new(
+        Point::new(0, height as i32),
+        Point::new(WIDTH as i32 - 1, height as i32),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+    .draw(frame)
+    .ok();
+    height as i32 + 1
+}
+
+/// Two most significant units, e.g. `3d 4h` or `12m`.
+pub fn format_uptime(uptime: Duration) -> String {
+    let minutes = uptime.as_secs() / 60;
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+pub trait Screen {
+    fn name(&self) -> &'static str;
+    fn draw(&self, ctx: &Context, frame: &mut Frame);
+
+    /// How long a frame drawn now stays accurate, e.g. until the clock's
+    /// next minute. The scheduler refreshes no later than that, even when
+    /// the cadence would wait longer. By default screens don't go stale on
+    /// their own.
+    fn desired_interval(&self, _ctx: &Context) -> Duration {
+        Duration::MAX
+    }
+
+    /// Whether changes since the last refresh are worth showing straight
+    /// away. Screens that say no have them held back until
+    /// [`Screen::desired_interval`] runs out, or until someone interacts.
+    fn is_dirty(&self, _ctx: &Context) -> bool {
+        true
+    }
+
+    /// Names of the on-demand collector sources this screen reads from.
+    /// Those run only while some screen in the carousel asks for them.
+    fn sources(&self) -> &'static [&'static str] {
+        &[]
+    }
+}
+
+/// The screens [`by_name`] knows, for telling someone what they could
+/// have meant.
+const BUILT_IN: &[&str] = &[
+    "pet",
+    "clock",
+    "stats",
+    "about",
+    "health",
+    "logs",
+    "heatmap",
+    #[cfg(feature = "github")]
+    "github",
+    #[cfg(feature = "ticker")]
+    "ticker",
+];
+
+/// Every screen that can be shown under `config`: the built-in ones, then
+/// the config's layouts.
+pub fn available(config: &Config) -> Vec<String> {
+    BUILT_IN
+        .iter()
+        .map(|name| name.to_string())
+        .chain(config.layouts.keys().cloned())
+        .collect()
+}
+
+/// Checks `name` against the [`available`] screens, the error listing
+/// them.
+pub fn check_name(name: &str, available: &[String]) -> Result<(), String> {
+    if available.iter().any(|screen| screen == name) {
+        return Ok(());
+    }
+    Err(format!(
+        "unknown screen {name:?}, try {}",
+        available.join(", ")
+    ))
+}
+
+/// Looks up a screen by the name used in the config file.
+pub fn by_name(name: &str) -> Option<Box<dyn Screen>> {
+    match name {
+        "pet" => Some(Box::new(pet::PetScreen)),
+        "clock" => Some(Box::new(clock::ClockScreen)),
+        "stats" => Some(Box::new(stats::StatsScreen)),
+        "about" => Some(Box::new(about::AboutScreen)),
+        "health" => Some(Box::new(health::HealthScreen)),
+        "logs" => Some(Box::new(logs::LogsScreen)),
+        "heatmap" => Some(Box::new(heatmap::HeatmapScreen)),
+        #[cfg(feature = "github")]
+        "github" => Some(Box::new(github::GithubScreen)),
+        #[cfg(feature = "ticker")]
+        "ticker" => Some(Box::new(ticker::TickerScreen)),
+        _ => None,
+    }
+}
+
+/// Rotates through the configured screens, staying on each for a fixed
+/// number of refreshes. Screens can be switched off and on while running;
+/// switched-off ones are skipped.
+pub struct Carousel {
+    screens: Vec<Box<dyn Screen>>,
+    enabled: Vec<bool>,
+    current: usize,
+    shown_for: u32,
+    dwell: u32,
+    /// The screen the carousel is held on, while one is pinned.
+    pinned: Option<&'static str>,
+}
+
+/// Builds the built-in screen or config layout called `name`. That's
+/// usually one screen, but with large text the stats take several.
+pub fn build(name: &str, config: &Config) -> Result<Vec<Box<dyn Screen>>> {
+    let profile = Profile::from_config(config);
+    if name == "stats" && profile == Profile::Large {
+        return Ok(stats::Total::ALL
+            .into_iter()
+            .map(|total| Box::new(stats::TotalScreen(total)) as Box<dyn Screen>)
+            .collect());
+    }
+    if let Some(screen) = by_name(name) {
+        return Ok(vec![screen]);
+    }
+    let Some(layout) = config.layouts.get(name) else {
+        bail!("unknown screen {name:?}");
+    };
+    // Screen names are static; this one lives as long as the daemon anyway.
+    let name = Box::leak(name.to_owned().into_boxed_str());
+    Ok(vec![Box::new(DeclarativeScreen::new(
+        name, layout, profile,
+    )?)])
+}
+
+impl Carousel {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut screens = Vec::new();
+        for name in &config.screens {
+            screens.extend(build(name, config)?);
+        }
+        if screens.is_empty() {
+            bail!("no screens configured");
+        }
+        Ok(Carousel {
+            enabled: vec![true; screens.len()],
+            screens,
+            current: 0,
+            shown_for: 0,
+            dwell: config.screen_dwell.max(1),
+            pinned: None,
+        })
+    }
+
+    pub fn current(&self) -> &dyn Screen {
+        self.screens[self.current].as_ref()
+    }
+
+    /// Called once per refresh, after drawing.
+    pub fn advance(&mut self) {
+        self.shown_for += 1;
+        if self.shown_for >= self.dwell {
+            self.next();
+        }
+    }
+
+    /// Moves on to the next screen that's switched on, keeping to the
+    /// pinned one if there is one.
+    fn next(&mut self) {
+        self.turn(true, self.pinned);
+    }
+
+    /// Moves to the next screen that's on, or the one before, skipping any
+    /// not called `within` when given.
+    fn turn(&mut self, forward: bool, within: Option<&str>) {
+        self.shown_for = 0;
+        let len = self.screens.len();
+        for _ in 0..len {
+            self.current = match forward {
+                true => (self.current + 1) % len,
+                false => (self.current + len - 1) % len,
+            };
+            let name = self.screens[self.current].name();
+            if self.enabled[self.current] && within.is_none_or(|within| within == name) {
+                return;
+            }
+        }
+    }
+
+    /// Shows the next screen that's on straight away, or the one before
+    /// with `forward` false. A pin moves along with it.
+    pub fn step(&mut self, forward: bool) {
+        self.turn(forward, None);
+        if self.pinned.is_some() {
+            self.pinned = Some(self.current().name());
+        }
+    }
+
+    /// Holds the carousel on the screen called `name` until it's unpinned,
+    /// switching it on first if need be. Returns whether that switched
+    /// anything on.
+    pub fn pin(&mut self, name: &str, config: &Config) -> Result<bool> {
+        let added = self.set_enabled(name, true, config)?;
+        let index = (0..self.screens.len())
+            .find(|&index| self.screens[index].name() == name)
+            .expect("the screen was just switched on");
+        self.pinned = Some(self.screens[index].name());
+        if self.current().name() != name {
+            self.current = index;
+            self.shown_for = 0;
+        }
+        Ok(added)
+    }
+
+    /// Lets the carousel move on again, returning the screen that was
+    /// pinned.
+    pub fn unpin(&mut self) -> Option<&'static str> {
+        self.pinned.take()
+    }
+
+    pub fn pinned(&self) -> Option<&'static str> {
+        self.pinned
+    }
+
+    /// Switches the screen called `name` on or off, returning whether that
+    /// changed anything. A screen built as several, like the stats with
+    /// large text, is switched as one. A screen that isn't in the carousel yet is added at
+    /// the end. The last screen that's on can't be switched off.
+    pub fn set_enabled(&mut self, name: &str, on: bool, config: &Config) -> Result<bool> {
+        let mut indices: Vec<usize> = (0..self.screens.len())
+            .filter(|&index| self.screens[index].name() == name)
+            .collect();
+        if indices.is_empty() {
+            if !on {
+                bail!("screen {name:?} isn't in the carousel");
+            }
+            let added = build(name, config)?;
+            indices = (self.screens.len()..self.screens.len() + added.len()).collect();
+            self.enabled.extend(added.iter().map(|_| false));
+            self.screens.extend(added);
+        }
+        if indices.iter().all(|&index| self.enabled[index] == on) {
+            return Ok(false);
+        }
+        let others_on = (0..self.screens.len()).any(|i| self.enabled[i] && !indices.contains(&i));
+        if !on && !others_on {
+            bail!("{name:?} is the only screen left");
+        }
+        for &index in &indices {
+            self.enabled[index] = on;
+        }
+        if !on && self.pinned == Some(name) {
+            log::info!("unpinned {name}, it's been switched off");
+            self.pinned = None;
+        }
+        if !on && indices.contains(&self.current) {
+            self.next();
+        }
+        Ok(true)
+    }
+
+    /// The on-demand sources the screens that are on need between them.
+    pub fn demand(&self) -> BTreeSet<&'static str> {
+        self.screens
+            .iter()
+            .zip(&self.enabled)
+            .filter(|(_, &on)| on)
+            .flat_map(|(screen, _)| screen.sources().iter().copied())
+            .collect()
+    }
+}
+
+/// Everything a [`Context`] borrows, for drawing screens in tests: a
+/// fresh pet at a fixed moment in Bucharest, with the default config.
+#[cfg(test)]
+pub struct Fixture {
+    pub clock: Clock,
+    pub config: Config,
+    pub pet: Pet,
+    pub runtime: Runtime,
+    pub panel: PanelInfo,
+    pub snapshot: Snapshot,
+    pub tally: crate::summary::Tally,
+}
+
+#[cfg(test)]
+impl Fixture {
+    pub fn at(now: &str) -> Fixture {
+        let now = now.parse::<DateTime<FixedOffset>>().expect("valid time");
+        let clock = Clock::frozen(
+            crate::clock::Zone::Named(chrono_tz::Europe::Bucharest),
+            now.to_utc(),
+        );
+        let mut pet = Pet::new("Tester", now.to_utc());
+        let runtime = Runtime::new(7, &mut pet);
+        Fixture {
+            clock,
+            config: Config::default(),
+            pet,
+            runtime,
+            panel: PanelInfo::simulator(),
+            snapshot: Snapshot::default(),
+            tally: crate::summary::Tally::new(now.date_naive()),
+        }
+    }
+
+    pub fn context(&self) -> Context<'_> {
+        Context {
+            now: self.clock.now(),
+            clock: &self.clock,
+            config: &self.config,
+            pet: &self.pet,
+            runtime: &self.runtime,
+            panel: &self.panel,
+            snapshot: &self.snapshot,
+            tally: &self.tally,
+            quiet: None,
+        }
+    }
+
+    /// `screen` drawn on a blank panel-sized frame.
+    pub fn draw(&self, screen: &dyn Screen) -> Frame {
+        let mut frame = Frame::new(self.panel.width, self.panel.height);
+        screen.draw(&self.context(), &mut frame);
+        frame
+    }
+
+    /// Draws `screen` and compares it with a golden render, see
+    /// [`assert_golden`].
+    pub fn assert_golden(&self, screen: &dyn Screen, golden: &str) {
+        assert_golden(&self.draw(screen), golden);
+    }
+}
+
+/// Compares `frame` with `tests/golden/{golden}.txt`, which
+/// `UPDATE_GOLDEN=1 cargo test` rewrites.
+#[cfg(test)]
+pub fn assert_golden(frame: &Frame, golden: &str) {
+    let rendered = ascii(frame);
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{golden}.txt"));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, &rendered).unwrap();
+    }
+    let want =
+        std::fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+    for (row, (got, want)) in rendered.lines().zip(want.lines()).enumerate() {
+        assert_eq!(got, want, "row {row} differs from {}", path.display());
+    }
+    assert_eq!(rendered, want, "{} differs", path.display());
+}
+
+/// `frame` as text, a character a pixel: `.` white, `#` black, `r` red.
+#[cfg(test)]
+pub fn ascii(frame: &Frame) -> String {
+    let mut out = String::new();
+    for y in 0..frame.height() as i32 {
+        for x in 0..frame.width() as i32 {
+            out.push(match frame.pixel(x, y) {
+                Color::White => '.',
+                Color::Black => '#',
+                Color::Red => 'r',
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn screens_say_how_long_they_stay_accurate() {
+        let fixture = Fixture::at("2024-05-01T12:04:30+03:00");
+        let ctx = fixture.context();
+        for (screen, interval, dirty) in [
+            (
+                Box::new(clock::ClockScreen) as Box<dyn Screen>,
+                MINUTE / 2,
+                false,
+            ),
+            (
+                Box::new(heatmap::HeatmapScreen),
+                55 * MINUTE + MINUTE / 2,
+                false,
+            ),
+            (Box::new(stats::StatsScreen), 30 * MINUTE, false),
+            (Box::new(pet::PetScreen), Duration::MAX, true),
+        ] {
+            assert_eq!(screen.desired_interval(&ctx), interval, "{}", screen.name());
+            assert_eq!(screen.is_dirty(&ctx), dirty, "{}", screen.name());
+        }
+    }
+
+    /// Screens that draw the same wherever the tests run, compared with
+    /// `screen-{name}.txt` and, in large text, `large-{name}.txt`. The
+    /// example layouts' regular renders are `layout-{name}.txt`.
+    #[test]
+    fn screens_match_the_golden_renders_in_both_sizes() {
+        for (large, names) in [
+            (false, &["pet", "clock", "stats"][..]),
+            (true, &["pet", "clock", "stats", "care", "host"]),
+        ] {
+            let mut fixture = Fixture::at("2024-05-01T12:04:30+03:00");
+            fixture.config.accessibility.large_text = large;
+            let prefix = if large { "large" } else { "screen" };
+            for name in names {
+                let screens = build(name, &fixture.config).unwrap();
+                for (index, screen) in screens.iter().enumerate() {
+                    // Large stats are a screen a total, numbered from 1.
+                    let golden = match screens.len() {
+                        1 => format!("{prefix}-{name}"),
+                        _ => format!("{prefix}-{name}-{}", index + 1),
+                    };
+                    fixture.assert_golden(&**screen, &golden);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_clock_goes_stale_at_the_minute() {
+        for (now, left) in [
+            ("2024-05-01T12:04:00+03:00", 60),
+            ("2024-05-01T12:04:59+03:00", 1),
+        ] {
+            let fixture = Fixture::at(now);
+            let interval = clock::ClockScreen.desired_interval(&fixture.context());
+            assert_eq!(interval, Duration::from_secs(left), "{now}");
+        }
+    }
+}
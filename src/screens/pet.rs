@@ -0,0 +1,406 @@
+//! The main screen: the pet wandering around its room.
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle, RoundedRectangle, Triangle};
+
+use super::{Context, Profile, Screen};
+use crate::artwork::{self, SpriteId};
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::icons::Icon;
+use crate::pet::{Egg, Form, Mood, Pet, STAT_MAX};
+use crate::reactions::Face;
+use crate::runtime::Runtime;
+use crate::sprite::{Flip, Sprite, EXCLAMATION, MESS};
+use crate::text::{self, Align, HAlign, VAlign};
+
+/// Height of the greeting bar along the top.
+fn header_height(profile: Profile) -> u32 {
+    profile.line_height() + 1
+}
+
+/// Where the pet can roam: everything below the header.
+fn body(profile: Profile) -> Rectangle {
+    let top = header_height(profile) + 1;
+    Rectangle::new(Point::new(0, top as i32), Size::new(WIDTH, HEIGHT - top))
+}
+/// Bottom row of the pet and its messes.
+const FLOOR: i32 = HEIGHT as i32 - 3;
+/// Minimum gap kept between the pet and anything placed beside it.
+const CLEARANCE: i32 = 4;
+
+const BAR_WIDTH: u32 = 30;
+const BAR_HEIGHT: u32 = 5;
+const MESS_SLOT: i32 = MESS.width as i32 + 4;
+
+/// Range of x positions for the pet sprite's left edge. The header's
+/// height doesn't change how far it can walk.
+pub fn walk_range() -> (i32, i32) {
+    let body = body(Profile::Regular);
+    let min = body.top_left.x + 2;
+    let max = body.top_left.x + body.size.width as i32 - artwork::footprint().width as i32 - 2;
+    (min, max)
+}
+
+/// Top-left corner of the pet sprite when its left edge is at `x`.
+pub fn pet_origin(x: i32) -> Point {
+    Point::new(x, FLOOR - artwork::footprint().height as i32 + 1)
+}
+
+pub struct PetScreen;
+
+impl Screen for PetScreen {
+    fn name(&self) -> &'static str {
+        "pet"
+    }
+
+    fn sources(&self) -> &'static [&'static str] {
+        &["update-check", "github"]
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let profile = ctx.profile();
+        draw_header(ctx, frame);
+        if ctx.pet.is_dead() {
+            draw_grave(frame, profile);
+            return;
+        }
+
+        let pet_x = ctx.runtime.walker.x();
+        let pet_origin = pet_origin(pet_x);
+        let sprite = body_sprite(ctx.pet, ctx.runtime);
+        let flip = ctx.runtime.walker.flip();
+        sprite.draw(frame, pet_origin, body_color(ctx.pet), flip);
+        if ctx.config.simulator.greyscale {
+            sprite.shade(frame, pet_origin, flip, 1);
+        }
+        let mut body = frame.clipped(&body(profile));
+        for particle in &ctx.runtime.particles {
+            let kind = particle.kind;
+            kind.sprite()
+                .draw(&mut body, particle.position, kind.color(), Flip::None);
+        }
+
+        let layout = FloorLayout::around(pet_x, ctx.pet.messes, profile);
+        draw_stats(frame, layout.stats, ctx.pet, profile);
+        for origin in layout.messes {
+            MESS.draw(frame, origin, Color::Black, Flip::None);
+        }
+        if ctx.pet.is_misbehaving() {
+            let x =
+                pet_origin.x + artwork::footprint().width as i32 / 2 - EXCLAMATION.width as i32 / 2;
+            let top = pet_origin.y - EXCLAMATION.height as i32 - 3;
+            EXCLAMATION.draw(frame, Point::new(x, top), Color::Red, Flip::None);
+        } else if let Some(text) = speech(ctx) {
+            let ceiling = pet_origin.y.min(layout.stats.y);
+            let bounds = artwork::get(SpriteId::Idle).bounds(pet_origin);
+            draw_bubble(frame, bounds, ceiling, &text, profile);
+        }
+    }
+}
+
+fn draw_grave(frame: &mut Frame, profile: Profile) {
+    let size = 2 * Icon::SIZE as i32;
+    let origin = Point::new((WIDTH as i32 - size) / 2, FLOOR - size + 1);
+    Icon::Skull.draw_large(frame, origin, Color::Black);
+    let grave = Rectangle::new(origin, Size::new(size as u32, size as u32));
+    draw_bubble(frame, grave, origin.y, "R.I.P.", profile);
+}
+
+/// Pets from the ember egg grow up red.
+pub fn body_color(pet: &Pet) -> Color {
+    if pet.form != Form::Baby && pet.egg == Some(Egg::Ember) {
+        Color::Red
+    } else {
+        Color::Black
+    }
+}
+
+/// All forms and faces share the baby's footprint, so the layout doesn't
+/// change as the pet grows up or reacts.
+fn body_sprite(pet: &Pet, runtime: &Runtime) -> &'static Sprite {
+    if pet.is_misbehaving() {
+        return artwork::get(SpriteId::Mischief);
+    }
+    match runtime.face() {
+        Some(Face::Lost) => return artwork::get(SpriteId::Lost),
+        Some(Face::Happy) => return artwork::get(SpriteId::Happy),
+        Some(Face::Worried) => return artwork::get(SpriteId::Worried),
+        None => {}
+    }
+    form_sprite(pet.form)
+}
+
+/// The pet at rest in `form`.
+pub fn form_sprite(form: Form) -> &'static Sprite {
+    artwork::get(match form {
+        Form::Baby => SpriteId::Idle,
+        Form::Noble => SpriteId::Noble,
+        Form::Wild => SpriteId::Wild,
+    })
+}
+
+fn draw_header(ctx: &Context, frame: &mut Frame) {
+    let profile = ctx.profile();
+    let height = header_height(profile);
+    let bar = Rectangle::new(Point::new(2, 0), Size::new(WIDTH - 4, height));
+    let font = profile.font();
+    let age = (ctx.now.to_utc() - ctx.pet.born).num_days() + 1;
+    let age = format!("Day {age}");
+    let age = text::draw(frame, &age, font, Color::Black, bar, Align::RIGHT);
+    // Status icons line up leftwards from the age, newest furthest out.
+    let update = ctx
+        .snapshot
+        .available_update
+        .as_ref()
+        .map(|_| Icon::UpdateAvailable);
+    let update = update.map(|icon| (icon, Color::Black));
+    let eco = ctx.runtime.batching.then_some((Icon::Leaf, Color::Black));
+    let marks = ctx.marks().map(|icon| (icon, Color::Black));
+    let flags = ctx
+        .runtime
+        .flags
+        .iter()
+        .map(|flag| (flag.icon(), flag.color()));
+    let icon_top = (height - Icon::SIZE) as i32 / 2;
+    let mut x = age.top_left.x;
+    // Upgrades waiting come with how many, from the server watcher.
+    if let Some(count) = ctx.snapshot.upgrades.filter(|&count| count > 0) {
+        let room = Rectangle::new(
+            bar.top_left,
+            Size::new((x - 3 - bar.top_left.x).max(0) as u32, height),
+        );
+        let count = text::draw(
+            frame,
+            &count.to_string(),
+            font,
+            Color::Black,
+            room,
+            Align::RIGHT,
+        );
+        x = count.top_left.x - 1 - Icon::SIZE as i32;
+        Icon::Package.draw(frame, Point::new(x, icon_top), Color::Black);
+    }
+    for (icon, color) in marks.chain(eco).chain(update).chain(flags) {
+        x -= Icon::SIZE as i32 + 3;
+        icon.draw(frame, Point::new(x, icon_top), color);
+    }
+    let greeting = ctx.render(&ctx.config.text.header);
+    let room = (x - bar.top_left.x - 4).max(0) as u32;
+    let greeting = profile.fit(&greeting, room);
+    text::draw(frame, &greeting, font, Color::Black, bar, Align::LEFT);
+    Line::new(
+        Point::new(0, height as i32),
+        Point::new(WIDTH as i32 - 1, height as i32),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+    .draw(frame)
+    .ok();
+}
+
+/// Positions of the things sharing the floor with the pet, recomputed every
+/// frame so they keep out of its way wherever it has wandered.
+struct FloorLayout {
+    stats: Point,
+    messes: Vec<Point>,
+}
+
+impl FloorLayout {
+    fn around(pet_x: i32, messes: u8, profile: Profile) -> Self {
+        let body = body(profile);
+        let body_left = body.top_left.x + 2;
+        let body_right = body.top_left.x + body.size.width as i32 - 2;
+        let left = (body_left, pet_x - CLEARANCE);
+        let right = (
+            pet_x + artwork::footprint().width as i32 + CLEARANCE,
+            body_right,
+        );
+
+        // The stat block goes at the outer edge of the roomier side; messes
+        // fill in next to it, spilling over to the other side if needed.
+        let stats = stats_size(profile);
+        let stats_width = stats.width as i32;
+        let stats_top = FLOOR - stats.height as i32 + 1;
+        let (stats, mut spans) = if right.1 - right.0 >= left.1 - left.0 {
+            (
+                Point::new(right.1 - stats_width, stats_top),
+                [(right.0, right.1 - stats_width - CLEARANCE), left],
+            )
+        } else {
+            (
+                Point::new(left.0, stats_top),
+                [(left.0 + stats_width + CLEARANCE, left.1), right],
+            )
+        };
+
+        let mess_top = FLOOR - MESS.height as i32 + 1;
+        let mut placed = Vec::new();
+        for span in spans.iter_mut() {
+            while placed.len() < messes as usize && span.1 - span.0 >= MESS.width as i32 {
+                placed.push(Point::new(span.0, mess_top));
+                span.0 += MESS_SLOT;
+            }
+        }
+        FloorLayout {
+            stats,
+            messes: placed,
+        }
+    }
+}
+
+/// A label a letter wide, then the bar, for each stat. The large font's
+/// letters are taller than the bars, so its rows are spaced further apart.
+fn stats_size(profile: Profile) -> Size {
+    let label = profile.font().character_size.width;
+    let row = match profile {
+        Profile::Regular => BAR_HEIGHT + 3,
+        Profile::Large => profile.bar_height(BAR_HEIGHT) + 6,
+    };
+    Size::new(label + 2 + BAR_WIDTH, 3 * row)
+}
+
+fn draw_stats(frame: &mut Frame, origin: Point, pet: &Pet, profile: Profile) {
+    let font = profile.font();
+    let label_width = font.character_size.width;
+    let row_height = stats_size(profile).height as i32 / 3;
+    let bar_height = profile.bar_height(BAR_HEIGHT);
+    let stats = [("H", pet.hunger), ("J", pet.happiness), ("E", pet.energy)];
+    for (row, (label, value)) in stats.into_iter().enumerate() {
+        let top = origin + Point::new(0, row as i32 * row_height);
+        let cell = Rectangle::new(top, Size::new(label_width, bar_height));
+        text::draw(frame, label, font, Color::Black, cell, Align::LEFT);
+        draw_bar(
+            frame,
+            top + Point::new(label_width as i32 + 2, 0),
+            value,
+            bar_height,
+        );
+    }
+}
+
+/// An outlined bar filled in proportion to `value`; red once it's low.
+fn draw_bar(frame: &mut Frame, origin: Point, value: f32, height: u32) {
+    let outline = Rectangle::new(origin, Size::new(BAR_WIDTH, height));
+    outline
+        .into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+        .draw(frame)
+        .ok();
+    let filled = ((BAR_WIDTH - 2) as f32 * value / STAT_MAX).round() as u32;
+    let color = if value < 25.0 {
+        Color::Red
+    } else {
+        Color::Black
+    };
+    Rectangle::new(origin + Point::new(1, 1), Size::new(filled, height - 2))
+        .into_styled(PrimitiveStyle::with_fill(color))
+        .draw(frame)
+        .ok();
+}
+
+/// What the pet has to say about its situation, if anything. An answer to
+/// something just said to it comes first, and a content pet may be
+/// reminiscing instead.
+fn speech(ctx: &Context) -> Option<String> {
+    let reply = ctx.runtime.reply(ctx.now.to_utc());
+    // A reply that can't be shown leaves the bubble to the pet's mood.
+    if let Some(reply) = reply.and_then(|(reply, privacy)| ctx.shown(reply, privacy)) {
+        return (!reply.is_empty()).then(|| reply.to_string());
+    }
+    let speech = &ctx.config.text.speech;
+    let template = match ctx.pet.mood() {
+        Mood::Hungry => &speech.hungry,
+        Mood::Dirty => &speech.dirty,
+        Mood::Sleepy => &speech.sleepy,
+        Mood::Bored => &speech.bored,
+        Mood::Content => return ctx.runtime.memory().map(str::to_string),
+    };
+    let text = ctx.render(template);
+    (!text.is_empty()).then_some(text)
+}
+
+/// A speech bubble pointing at `pet` with its tail ending above `ceiling`,
+/// shifted sideways to stay inside the body.
+fn draw_bubble(frame: &mut Frame, pet: Rectangle, ceiling: i32, text: &str, profile: Profile) {
+    let body = body(profile);
+    let font = profile.font();
+    let text = profile.fit(text, body.size.width - 10);
+    let top_left = Align::new(HAlign::Left, VAlign::Top);
+    let measured = text::place(&text, font, body, top_left).bounds;
+    let width = measured.size.width as i32 + 8;
+    let height = profile.line_height() as i32 + 3;
+    let pet_center = pet.top_left.x + pet.size.width as i32 / 2;
+    let min_x = body.top_left.x + 1;
+    let max_x = body.top_left.x + body.size.width as i32 - width - 1;
+    let left = (pet_center - width / 2).clamp(min_x, max_x);
+    let top = ceiling - height - 5;
+
+    let bubble = Rectangle::new(
+        Point::new(left, top),
+        Size::new(width as u32, height as u32),
+    );
+    RoundedRectangle::with_equal_corners(bubble, Size::new(4, 4))
+        .into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+        .draw(frame)
+        .ok();
+    let tail_x = pet_center.clamp(left + 4, left + width - 5);
+    Triangle::new(
+        Point::new(tail_x - 2, top + height - 1),
+        Point::new(tail_x + 2, top + height - 1),
+        Point::new(tail_x, top + height + 3),
+    )
+    .into_styled(PrimitiveStyle::with_fill(Color::Black))
+    .draw(frame)
+    .ok();
+
+    text::draw(frame, &text, font, Color::Black, bubble, Align::CENTER);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::{Particle, ParticleKind};
+    use crate::screens::Fixture;
+
+    #[test]
+    fn particles_are_clipped_to_the_body() {
+        let mut fixture = Fixture::at("2024-05-01T12:00:00+03:00");
+        let plain = fixture.draw(&PetScreen);
+        let body = body(fixture.context().profile());
+        let heart = ParticleKind::Heart.sprite();
+        // Straddling the top of the body, so half of it would be over the
+        // header.
+        let position = Point::new(20, body.top_left.y - heart.height as i32 / 2);
+        fixture.runtime.particles.push(Particle {
+            kind: ParticleKind::Heart,
+            position,
+            lifetime: 1,
+        });
+        let with = fixture.draw(&PetScreen);
+
+        let mut changed = 0;
+        for y in 0..HEIGHT as i32 {
+            for x in 0..WIDTH as i32 {
+                if plain.pixel(x, y) != with.pixel(x, y) {
+                    assert!(body.contains(Point::new(x, y)), "drawn at {x},{y}");
+                    changed += 1;
+                }
+            }
+        }
+        assert!(changed > 0, "the visible half is drawn");
+    }
+
+    /// The pet from `tests/fixtures/pack` instead of the built-in sprites.
+    #[test]
+    fn the_fixture_pack_matches_the_golden_render() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/pack");
+        let _pack = artwork::install_for_test(artwork::Pack::load(&dir).unwrap());
+        assert_eq!(artwork::footprint(), Size::new(20, 18));
+        let mut fixture = Fixture::at("2024-05-01T12:00:00+03:00");
+        fixture.assert_golden(&PetScreen, "pack-pet");
+        // Greeted, it's happy, which the pack has as a BMP.
+        let now = fixture.clock.now();
+        let talk = crate::chat::Talk::new("hello", &fixture.pet, now, &fixture.config.text);
+        let privacy = crate::guest::Privacy::Public;
+        fixture.runtime.hear(talk, privacy, now.to_utc());
+        fixture.assert_golden(&PetScreen, "pack-pet-happy");
+    }
+}
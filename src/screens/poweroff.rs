@@ -0,0 +1,68 @@
+//! Left up when the host powers off, so the panel, which keeps its image
+//! without power, says so rather than showing a pet frozen mid-afternoon
+//! for days. Only ever drawn by `--shutdown-screen`.
+
+use chrono::{DateTime, FixedOffset};
+use embedded_graphics::mono_font::iso_8859_15::FONT_10X20;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use super::pet::{body_color, form_sprite};
+use super::Profile;
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::pet::Pet;
+use crate::text::{self, Align, HAlign, VAlign};
+
+/// How much the sleeping pet is blown up by.
+const SCALE: u32 = 2;
+
+/// Draws the powered-off screen for `pet` as of `since`.
+pub fn draw(frame: &mut Frame, pet: &Pet, since: DateTime<FixedOffset>, profile: Profile) {
+    // Large, the pet doesn't leave room for the text, so it stays out.
+    let mut left = 4;
+    if profile == Profile::Regular && !pet.is_dead() {
+        let sprite = form_sprite(pet.form);
+        let size = sprite.size() * SCALE;
+        let origin = Point::new(left, (HEIGHT - size.height) as i32 / 2 + 4);
+        sprite.draw_scaled(frame, origin, body_color(pet), SCALE);
+        let zzz = Rectangle::new(
+            Point::new(origin.x + size.width as i32 - 20, origin.y - 14),
+            Size::new(24, 12),
+        );
+        text::draw(frame, "z Z", profile.font(), Color::Black, zzz, Align::LEFT);
+        left += size.width as i32 + 8;
+    }
+
+    let width = WIDTH - left as u32 - 4;
+    let top_left = Align::new(HAlign::Left, VAlign::Top);
+    let font = profile.font();
+    let line = profile.line_height();
+    // The title and three lines, in the middle.
+    let mut top = (HEIGHT as i32 - 24 - 3 * (line as i32 + 2)) / 2;
+    let title = Rectangle::new(Point::new(left, top), Size::new(width, 20));
+    text::draw(
+        frame,
+        "Powered off",
+        &FONT_10X20,
+        Color::Black,
+        title,
+        top_left,
+    );
+    top += 24;
+
+    let day = (since.to_utc() - pet.born).num_days() + 1;
+    let lines = [
+        format!("since {}", since.format("%d %b %H:%M")),
+        format!("{}, day {day}", pet.name),
+        format!(
+            "H{:.0} J{:.0} E{:.0}",
+            pet.hunger, pet.happiness, pet.energy
+        ),
+    ];
+    for text in &lines {
+        let area = Rectangle::new(Point::new(left, top), Size::new(width, line));
+        let text = profile.fit(text, width);
+        text::draw(frame, &text, font, Color::Black, area, top_left);
+        top += line as i32 + 2;
+    }
+}
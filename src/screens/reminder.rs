@@ -0,0 +1,57 @@
+//! A reminder that's gone off, in place of the carousel until someone
+//! acknowledges it; see [`crate::reminder`].
+
+use chrono::{DateTime, Utc};
+
+use super::{message, Context, Screen};
+use crate::config::ReminderConfig;
+use crate::display::{Color, Frame};
+use crate::guest::Privacy;
+use crate::message::Message;
+use crate::text::{self, Align};
+
+pub struct ReminderScreen<'a> {
+    pub reminder: &'a ReminderConfig,
+    /// The time it went off for.
+    pub due: DateTime<Utc>,
+}
+
+impl Screen for ReminderScreen<'_> {
+    fn name(&self) -> &'static str {
+        "reminder"
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        // With guests round, it only says there's one.
+        let text = ctx
+            .shown(self.reminder.text.trim(), Privacy::Hidden)
+            .unwrap_or("Reminder");
+        let note = Message {
+            text: text.to_string(),
+            icon: Some(self.reminder.icon.clone().unwrap_or_else(|| "bell".into())),
+            author: None,
+            until: None,
+        };
+        let profile = ctx.profile();
+        message::draw(frame, &note, ctx.clock, profile, ctx.supersample());
+
+        let footer = message::footer(profile);
+        let font = profile.font();
+        let due = ctx.clock.localize(self.due);
+        let format = match due.date_naive() == ctx.now.date_naive() {
+            true => "due %H:%M",
+            false => "due %a %H:%M",
+        };
+        let due = text::draw(
+            frame,
+            &due.format(format).to_string(),
+            font,
+            Color::Black,
+            footer,
+            Align::RIGHT,
+        );
+        let room = (due.top_left.x - 4 - 6).max(0) as u32;
+        let hint = profile.fit("any button: done", room);
+        text::draw(frame, &hint, font, Color::Black, footer, Align::LEFT);
+    }
+}
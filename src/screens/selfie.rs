@@ -0,0 +1,85 @@
+//! A photo from the camera, see [`crate::camera`], with the pet
+//! photobombing it from the corner. Without a photo, what went wrong.
+
+use std::time::Duration;
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{draw_title, Context, Screen};
+use crate::artwork::{self, SpriteId};
+use crate::camera::{Selfie, Trouble};
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::sprite::Flip;
+use crate::text::{self, Align};
+
+pub struct SelfieScreen<'a>(pub &'a Selfie);
+
+impl Screen for SelfieScreen<'_> {
+    fn name(&self) -> &'static str {
+        "selfie"
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let trouble = match &self.0.shot {
+            Ok(photo) => {
+                frame.clone_from(photo);
+                let sprite = artwork::get(SpriteId::Happy);
+                let size = sprite.size();
+                let origin = Point::new(
+                    (WIDTH - size.width) as i32 - 2,
+                    (HEIGHT - size.height) as i32,
+                );
+                // A white outline keeps the pet apart from a busy photo.
+                for dx in -1..=1 {
+                    for dy in -1..=1 {
+                        let at = origin + Point::new(dx, dy);
+                        sprite.draw(frame, at, Color::White, Flip::None);
+                    }
+                }
+                sprite.draw(frame, origin, Color::Black, Flip::None);
+                return;
+            }
+            Err(trouble) => *trouble,
+        };
+        let profile = ctx.profile();
+        let top = draw_title(frame, profile, "No selfie", None);
+        let sprite = artwork::get(SpriteId::Worried);
+        let size = sprite.size();
+        let room = HEIGHT as i32 - top;
+        sprite.draw(
+            frame,
+            Point::new(4, top + (room - size.height as i32) / 2),
+            Color::Black,
+            Flip::None,
+        );
+        let left = size.width as i32 + 10;
+        let font = profile.font();
+        let line = font.character_size.height as i32;
+        let lines = text::wrap(explain(trouble), profile.columns(WIDTH - left as u32 - 2));
+        let shown = lines.len().min((room / line) as usize);
+        let first = top + (room - shown as i32 * line) / 2;
+        for (row, text) in lines.iter().take(shown).enumerate() {
+            let area = Rectangle::new(
+                Point::new(left, first + row as i32 * line),
+                Size::new(WIDTH - left as u32 - 2, line as u32),
+            );
+            text::draw(frame, text, font, Color::Black, area, Align::LEFT);
+        }
+    }
+
+    /// Until the photo comes down.
+    fn desired_interval(&self, ctx: &Context) -> Duration {
+        (self.0.until - ctx.now.to_utc())
+            .to_std()
+            .unwrap_or_default()
+    }
+}
+
+fn explain(trouble: Trouble) -> &'static str {
+    match trouble {
+        Trouble::NoCamera => "Can't find a camera. Is it plugged in?",
+        Trouble::Busy => "The camera is busy. Try again in a bit.",
+        Trouble::Failed => "The camera didn't answer. The log says why.",
+    }
+}
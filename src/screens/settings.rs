@@ -0,0 +1,68 @@
+//! The settings menu, see [`crate::settings`].
+
+use std::time::{Duration, Instant};
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+
+use super::{draw_title, Context, Profile, Screen};
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::settings::Menu;
+use crate::text::{self, Align};
+
+pub struct SettingsScreen<'a>(pub &'a Menu);
+
+impl Screen for SettingsScreen<'_> {
+    fn name(&self) -> &'static str {
+        "settings"
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let menu = self.0;
+        let profile = ctx.profile();
+        let font = profile.font();
+        let top = draw_title(frame, profile, "Settings", None) + 3;
+        let row_height = profile.line_height() + 3;
+
+        for (row, &item) in menu.items().iter().enumerate() {
+            let bar = Rectangle::new(
+                Point::new(0, top + row as i32 * row_height as i32),
+                Size::new(WIDTH, row_height),
+            );
+            // The selected item is picked out as white on black.
+            let color = if row == menu.selected {
+                bar.into_styled(PrimitiveStyle::with_fill(Color::Black))
+                    .draw(frame)
+                    .ok();
+                Color::White
+            } else {
+                Color::Black
+            };
+            let area = Rectangle::new(
+                bar.top_left + Point::new(4, 0),
+                Size::new(WIDTH - 8, row_height),
+            );
+            let value = menu.value(item);
+            text::draw(frame, item.label(), font, color, area, Align::LEFT);
+            text::draw(frame, &value, font, color, area, Align::RIGHT);
+        }
+
+        // The large font has no room for the hint; the README has it.
+        if profile == Profile::Regular {
+            let hint = Rectangle::new(Point::new(2, HEIGHT as i32 - 11), Size::new(WIDTH - 4, 10));
+            text::draw(
+                frame,
+                "tap next, hold change, 3x save",
+                font,
+                Color::Black,
+                hint,
+                Align::LEFT,
+            );
+        }
+    }
+
+    /// Until the menu times out, so it closes on time.
+    fn desired_interval(&self, _ctx: &Context) -> Duration {
+        self.0.deadline().saturating_duration_since(Instant::now())
+    }
+}
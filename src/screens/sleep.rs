@@ -0,0 +1,51 @@
+//! Shown once when the pet goes to sleep for the night, then left on the
+//! panel until morning.
+
+use chrono::Utc;
+use embedded_graphics::mono_font::iso_8859_15::FONT_10X20;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use super::clock::draw_moon;
+use super::{Context, Profile, Screen};
+use crate::astro::MoonPhase;
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::text::{self, Align, HAlign, VAlign};
+
+pub struct SleepScreen;
+
+impl Screen for SleepScreen {
+    fn name(&self) -> &'static str {
+        "sleep"
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let phase = MoonPhase::at(ctx.now.with_timezone(&Utc));
+        draw_moon(frame, Point::new(46, 44), 48, phase);
+
+        let right = Rectangle::new(Point::new(88, 16), Size::new(WIDTH - 92, 40));
+        let zzz = format!("{} zzz", ctx.pet.name);
+        text::draw(frame, &zzz, &FONT_10X20, Color::Black, right, Align::LEFT);
+
+        let wake = match ctx.quiet {
+            Some(quiet) => format!(
+                "Wakes at {}",
+                quiet.next_end(ctx.clock, ctx.now).format("%H:%M")
+            ),
+            None => "Sleeping".into(),
+        };
+        let profile = ctx.profile();
+        // Large, it's too wide to go beside the moon, so it goes under it.
+        let (bottom, align) = match profile {
+            Profile::Regular => (
+                Rectangle::new(Point::new(88, 56), Size::new(WIDTH - 92, HEIGHT - 72)),
+                Align::new(HAlign::Left, VAlign::Top),
+            ),
+            Profile::Large => (
+                Rectangle::new(Point::new(0, HEIGHT as i32 - 22), Size::new(WIDTH, 22)),
+                Align::CENTER,
+            ),
+        };
+        text::draw(frame, &wake, profile.font(), Color::Black, bottom, align);
+    }
+}
@@ -0,0 +1,165 @@
+//! Lifetime totals: what the pet has been through so far. Two columns of
+//! them normally; with large text, a screen for each of the main ones.
+
+use std::time::Duration;
+
+use embedded_graphics::mono_font::iso_8859_15::{FONT_10X20, FONT_6X10};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{title_bar, Context, Profile, Screen};
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::icons::Icon;
+use crate::text::{self, Align, BigText};
+
+const ROW_HEIGHT: i32 = 17;
+const COLUMN_WIDTH: u32 = WIDTH / 2;
+/// The totals move slowly, and straight away when someone interacts.
+const STALE_AFTER: Duration = Duration::from_secs(30 * 60);
+
+pub struct StatsScreen;
+
+impl Screen for StatsScreen {
+    fn name(&self) -> &'static str {
+        "stats"
+    }
+
+    fn desired_interval(&self, _ctx: &Context) -> Duration {
+        STALE_AFTER
+    }
+
+    fn is_dirty(&self, _ctx: &Context) -> bool {
+        false
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let heading = format!("{}'s life", ctx.pet.name);
+        title_bar(frame, Profile::Regular, &heading, None, ctx.marks());
+
+        let life = &ctx.pet.lifetime;
+        let age = (ctx.now.to_utc() - ctx.pet.born).num_days() + 1;
+        let care = match life.care_score() {
+            Some(score) => format!("care {score}%"),
+            None => "care -".into(),
+        };
+        let left = vec![
+            (Icon::Food, format!("{} fed", life.feedings)),
+            (Icon::Game, format!("{} played", life.games)),
+            (Icon::Broom, format!("{} cleaned", life.messes_cleaned)),
+            (Icon::Heart, format!("{} petted", life.pettings)),
+            (Icon::RedCross, format!("{} recovered", life.sicknesses)),
+        ];
+        let right = vec![
+            (Icon::Cake, format!("day {age}")),
+            (Icon::Flame, format!("streak {}", life.streak)),
+            (Icon::Trophy, format!("best {}", life.longest_streak)),
+            (Icon::Sun, care),
+        ];
+        for (column, rows) in [left, right].iter().enumerate() {
+            let x = 2 + column as i32 * COLUMN_WIDTH as i32;
+            for (row, (icon, label)) in rows.iter().enumerate() {
+                let y = 17 + row as i32 * ROW_HEIGHT;
+                icon.draw(frame, Point::new(x, y), Color::Black);
+                let cell = Rectangle::new(
+                    Point::new(x + Icon::SIZE as i32 + 4, y),
+                    Size::new(COLUMN_WIDTH - Icon::SIZE - 8, Icon::SIZE),
+                );
+                text::draw(frame, label, &FONT_6X10, Color::Black, cell, Align::LEFT);
+            }
+        }
+    }
+}
+
+/// One of the totals, on a screen of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Total {
+    Age,
+    Care,
+    Streak,
+    Feedings,
+}
+
+impl Total {
+    pub const ALL: [Total; 4] = [Total::Age, Total::Care, Total::Streak, Total::Feedings];
+
+    fn icon(self) -> Icon {
+        match self {
+            Total::Age => Icon::Cake,
+            Total::Care => Icon::Sun,
+            Total::Streak => Icon::Flame,
+            Total::Feedings => Icon::Food,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Total::Age => "Age",
+            Total::Care => "Care",
+            Total::Streak => "Streak",
+            Total::Feedings => "Fed",
+        }
+    }
+
+    fn value(self, ctx: &Context) -> String {
+        let life = &ctx.pet.lifetime;
+        match self {
+            Total::Age => format!("day {}", (ctx.now.to_utc() - ctx.pet.born).num_days() + 1),
+            Total::Care => life
+                .care_score()
+                .map_or("-".into(), |score| format!("{score}%")),
+            Total::Streak => format!("{} days", life.streak),
+            Total::Feedings => format!("{}x", life.feedings),
+        }
+    }
+}
+
+/// [`StatsScreen`] for large text: one total, with a big icon and twice
+/// the large font. Every one of them is called `stats`, so they're
+/// switched on and off together.
+pub struct TotalScreen(pub Total);
+
+impl Screen for TotalScreen {
+    fn name(&self) -> &'static str {
+        "stats"
+    }
+
+    fn desired_interval(&self, _ctx: &Context) -> Duration {
+        STALE_AFTER
+    }
+
+    fn is_dirty(&self, _ctx: &Context) -> bool {
+        false
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let profile = Profile::Large;
+        let top = title_bar(frame, profile, self.0.label(), None, ctx.marks());
+        let size = 2 * Icon::SIZE as i32;
+        let middle = top + (HEIGHT as i32 - top) / 2;
+        self.0
+            .icon()
+            .draw_large(frame, Point::new(8, middle - size / 2), Color::Black);
+        let left = 8 + size + 8;
+        let area = Rectangle::new(
+            Point::new(left, middle - 20),
+            Size::new(WIDTH - left as u32 - 4, 40),
+        );
+        let value = self.0.value(ctx);
+        let big = BigText::new(&FONT_10X20, Color::Black, ctx.supersample());
+        // Twice the large font fits ten letters; past that, fall back to
+        // the large font itself.
+        if value.chars().count() as u32 * 20 <= area.size.width {
+            big.draw(frame, &value, area, Align::LEFT);
+        } else {
+            let value = profile.fit(&value, area.size.width);
+            text::draw(
+                frame,
+                &value,
+                profile.font(),
+                Color::Black,
+                area,
+                Align::LEFT,
+            );
+        }
+    }
+}
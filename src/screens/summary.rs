@@ -0,0 +1,147 @@
+//! The daily look back at yesterday, see [`crate::summary`].
+
+use std::time::Duration;
+
+use embedded_graphics::mono_font::iso_8859_15::FONT_6X10;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle};
+
+use super::{draw_title, Context, Profile, Screen};
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::pet::STAT_MAX;
+use crate::summary::{self, Day, Tally};
+use crate::text::{self, Align};
+
+const ROW_TOP: i32 = 15;
+const ROW_HEIGHT: i32 = 13;
+const SPARK_LEFT: i32 = 34;
+/// Pixels per hour across the sparklines.
+const SPARK_STEP: i32 = 6;
+const SPARK_HEIGHT: i32 = 10;
+
+pub struct SummaryScreen<'a>(pub &'a Tally);
+
+impl Screen for SummaryScreen<'_> {
+    fn name(&self) -> &'static str {
+        "summary"
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let yesterday = self.0.yesterday.as_ref();
+        let profile = ctx.profile();
+        let date = ctx.now.date_naive().pred_opt().unwrap_or_default();
+        let date = date.format("%d %b").to_string();
+        let top = draw_title(frame, profile, "Yesterday", Some(&date));
+
+        let verdict = summary::verdict(&ctx.pet.name, yesterday);
+        // The sparklines are too fine to read at large text sizes, so that
+        // makes do with the verdict.
+        let day = yesterday.filter(|day| day.average().is_some());
+        let Some(day) = day.filter(|_| profile == Profile::Regular) else {
+            let area = Rectangle::new(
+                Point::new(4, top),
+                Size::new(WIDTH - 8, HEIGHT - top as u32),
+            );
+            draw_wrapped(frame, profile, &verdict, area);
+            return;
+        };
+        draw_stats(frame, day);
+        rule(frame, 66);
+        let area = Rectangle::new(Point::new(4, 67), Size::new(WIDTH - 8, HEIGHT - 67));
+        draw_wrapped(frame, profile, &verdict, area);
+    }
+
+    /// Until the summary's time is up.
+    fn desired_interval(&self, ctx: &Context) -> Duration {
+        let Some(window) = ctx.config.summary.window() else {
+            return Duration::ZERO;
+        };
+        let ends = ctx
+            .clock
+            .next_occurrence(ctx.now, window.at + window.length);
+        (ends - ctx.now).to_std().unwrap_or_default()
+    }
+}
+
+fn draw_stats(frame: &mut Frame, day: &Day) {
+    let hourly = day.hourly();
+    let average = day.average().expect("checked by the caller");
+    let rows = [
+        ("Food", average.hunger, hourly.map(|h| h.map(|s| s.hunger))),
+        (
+            "Joy",
+            average.happiness,
+            hourly.map(|h| h.map(|s| s.happiness)),
+        ),
+        ("Rest", average.energy, hourly.map(|h| h.map(|s| s.energy))),
+    ];
+    for (row, (label, average, points)) in rows.iter().enumerate() {
+        let top = ROW_TOP + row as i32 * ROW_HEIGHT;
+        let area = Rectangle::new(Point::new(4, top), Size::new(WIDTH - 8, 10));
+        text::draw(frame, label, &FONT_6X10, Color::Black, area, Align::LEFT);
+        let value = format!("{average:.0}");
+        text::draw(frame, &value, &FONT_6X10, Color::Black, area, Align::RIGHT);
+        sparkline(frame, top, points);
+    }
+
+    let top = ROW_TOP + 3 * ROW_HEIGHT + 2;
+    let area = Rectangle::new(Point::new(4, top), Size::new(WIDTH - 8, 10));
+    let care = format!("Fed {}x, cleaned {}x", day.feedings, day.cleanings);
+    text::draw(frame, &care, &FONT_6X10, Color::Black, area, Align::LEFT);
+    if let (Some(min), Some(max)) = (day.min_temperature, day.max_temperature) {
+        let range = format!("CPU {min:.0}-{max:.0}°C");
+        text::draw(frame, &range, &FONT_6X10, Color::Black, area, Align::RIGHT);
+    }
+}
+
+/// One stat through the day, an hour per step. Hours without samples
+/// leave a gap.
+fn sparkline(frame: &mut Frame, top: i32, points: &[Option<f32>; 24]) {
+    let to_point = |hour: usize, value: f32| {
+        let rise = (value / STAT_MAX).clamp(0.0, 1.0) * (SPARK_HEIGHT - 1) as f32;
+        Point::new(
+            SPARK_LEFT + hour as i32 * SPARK_STEP,
+            top + SPARK_HEIGHT - 1 - rise.round() as i32,
+        )
+    };
+    let style = PrimitiveStyle::with_stroke(Color::Black, 1);
+    for (hour, value) in points.iter().enumerate() {
+        let Some(value) = *value else {
+            continue;
+        };
+        let here = to_point(hour, value);
+        match points.get(hour + 1).copied().flatten() {
+            Some(next) => {
+                Line::new(here, to_point(hour + 1, next))
+                    .into_styled(style)
+                    .draw(frame)
+                    .ok();
+            }
+            None => {
+                Pixel(here, Color::Black).draw(frame).ok();
+            }
+        }
+    }
+}
+
+fn draw_wrapped(frame: &mut Frame, profile: Profile, line: &str, area: Rectangle) {
+    let font = profile.font();
+    let height = font.character_size.height as i32;
+    let lines = text::wrap(line, profile.columns(area.size.width));
+    let shown = lines.len().min((area.size.height as i32 / height) as usize);
+    let top = area.top_left.y + (area.size.height as i32 - shown as i32 * height) / 2;
+    for (row, line) in lines.iter().take(shown).enumerate() {
+        let area = Rectangle::new(
+            Point::new(area.top_left.x, top + row as i32 * height),
+            Size::new(area.size.width, height as u32),
+        );
+        text::draw(frame, line, font, Color::Black, area, Align::CENTER);
+    }
+}
+
+fn rule(frame: &mut Frame, y: i32) {
+    Line::new(Point::new(0, y), Point::new(WIDTH as i32 - 1, y))
+        .into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+        .draw(frame)
+        .ok();
+}
@@ -0,0 +1,114 @@
+//! Prices of one or two configured symbols and how they moved over a day.
+
+use embedded_graphics::mono_font::iso_8859_15::{FONT_10X20, FONT_6X10};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle, Triangle};
+
+use super::{Context, Profile, Screen};
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::text::{self, Align, BigText};
+use crate::ticker::{self, Quote};
+
+const ROW_HEIGHT: i32 = HEIGHT as i32 / 2;
+/// Where the change column starts.
+const CHANGE_X: i32 = 158;
+
+pub struct TickerScreen;
+
+impl Screen for TickerScreen {
+    fn name(&self) -> &'static str {
+        "ticker"
+    }
+
+    fn sources(&self) -> &'static [&'static str] {
+        &["ticker"]
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        for (row, quote) in ctx.snapshot.tickers.iter().enumerate() {
+            let top = row as i32 * ROW_HEIGHT;
+            if row > 0 {
+                Line::new(Point::new(0, top), Point::new(WIDTH as i32 - 1, top))
+                    .into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+                    .draw(frame)
+                    .ok();
+            }
+            draw_quote(ctx, frame, top, quote);
+        }
+    }
+}
+
+fn draw_quote(ctx: &Context, frame: &mut Frame, top: i32, quote: &Quote) {
+    let profile = ctx.profile();
+    let label = if quote.stale {
+        format!("{} (stale)", quote.label)
+    } else {
+        quote.label.clone()
+    };
+    let label_area = Rectangle::new(
+        Point::new(4, top + 3),
+        Size::new(WIDTH - 8, profile.font().character_size.height),
+    );
+    let change = quote.change.map(|change| {
+        let color = if change < 0.0 {
+            Color::Red
+        } else {
+            Color::Black
+        };
+        (change, color, format!("{change:+.1}%"))
+    });
+    // The change column is too narrow for large text, so it goes at the
+    // end of the label instead, its sign standing in for the arrow.
+    let mut room = label_area.size.width;
+    if let (Profile::Large, Some((_, color, percent))) = (profile, &change) {
+        let drawn = text::draw(
+            frame,
+            percent,
+            profile.font(),
+            *color,
+            label_area,
+            Align::RIGHT,
+        );
+        room = (drawn.top_left.x - 4 - 10).max(0) as u32;
+    }
+    let label = profile.fit(&label, room);
+    text::draw(
+        frame,
+        &label,
+        profile.font(),
+        Color::Black,
+        label_area,
+        Align::LEFT,
+    );
+
+    let price = quote.price.map_or("-".into(), ticker::format_price);
+    let big = BigText::new(&FONT_10X20, Color::Black, ctx.supersample());
+    let price_width = match profile {
+        Profile::Regular => CHANGE_X - 8,
+        Profile::Large => WIDTH as i32 - 8,
+    };
+    let price_area = Rectangle::new(
+        label_area.top_left + Point::new(0, label_area.size.height as i32 + 2),
+        Size::new(price_width as u32, 24),
+    );
+    big.draw(frame, &price, price_area, Align::LEFT);
+
+    let Some((change, color, percent)) = change.filter(|_| profile == Profile::Regular) else {
+        return;
+    };
+    let (tip, base) = if change < 0.0 { (8, 0) } else { (0, 8) };
+    let y = top + 22;
+    Triangle::new(
+        Point::new(CHANGE_X, y + base),
+        Point::new(CHANGE_X + 8, y + base),
+        Point::new(CHANGE_X + 4, y + tip),
+    )
+    .into_styled(PrimitiveStyle::with_fill(color))
+    .draw(frame)
+    .ok();
+    let change_area = Rectangle::new(
+        Point::new(CHANGE_X + 12, top + 15),
+        Size::new(WIDTH - CHANGE_X as u32 - 14, 24),
+    );
+    text::draw(frame, &percent, &FONT_6X10, color, change_area, Align::LEFT);
+}
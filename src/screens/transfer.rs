@@ -0,0 +1,127 @@
+//! Moving a pet between devices, see [`crate::transfer`]: its code as a QR
+//! code and as text, and the question put when one comes in.
+
+use std::time::Duration;
+
+use embedded_graphics::mono_font::iso_8859_15::FONT_6X10;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+
+use super::{draw_title, Context, Profile, Screen};
+use crate::display::{Color, Frame, HEIGHT, WIDTH};
+use crate::text::{self, Align};
+use crate::transfer::{self, Offer, Showing};
+
+/// Light modules around the QR code. The standard asks for four; two and
+/// the white around the panel are plenty, and leave room for bigger
+/// modules.
+const QUIET: usize = 2;
+
+pub struct ExportScreen<'a>(pub &'a Showing);
+
+impl Screen for ExportScreen<'_> {
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        draw(frame, self.0, ctx.profile());
+    }
+
+    /// Until the code comes down.
+    fn desired_interval(&self, ctx: &Context) -> Duration {
+        (self.0.until - ctx.now.to_utc())
+            .to_std()
+            .unwrap_or_default()
+    }
+}
+
+/// Draws `showing` on its own, so `transfer export` can put it up without
+/// a daemon.
+pub fn draw(frame: &mut Frame, showing: &Showing, profile: Profile) {
+    let qr = &showing.qr;
+    let scale = HEIGHT as usize / (qr.size() + 2 * QUIET);
+    let side = (qr.size() * scale) as i32;
+    let origin = Point::new(QUIET as i32 * scale as i32, (HEIGHT as i32 - side) / 2);
+    let module = PrimitiveStyle::with_fill(Color::Black);
+    for y in 0..qr.size() {
+        for x in 0..qr.size() {
+            if qr.is_dark(x, y) {
+                let at = origin + Point::new((x * scale) as i32, (y * scale) as i32);
+                Rectangle::new(at, Size::new(scale as u32, scale as u32))
+                    .into_styled(module)
+                    .draw(frame)
+                    .ok();
+            }
+        }
+    }
+
+    let left = origin.x + side + 2 * QUIET as i32 * scale as i32;
+    let width = WIDTH - left as u32 - 2;
+    let font = profile.font();
+    let line = profile.line_height() as i32;
+    let name = profile.fit(&showing.name, width);
+    let area = Rectangle::new(Point::new(left, 2), Size::new(width, line as u32));
+    text::draw(frame, &name, font, Color::Black, area, Align::LEFT);
+    // The code always goes in the small font; in the large one it wouldn't
+    // fit, and `transfer export` prints it anyway.
+    let rows = match profile {
+        Profile::Regular => {
+            let columns = (width / FONT_6X10.character_size.width) as usize;
+            text::wrap(&transfer::readable(&showing.code), columns)
+        }
+        Profile::Large => vec!["Scan me".to_string()],
+    };
+    let row = FONT_6X10.character_size.height as i32;
+    let mut top = 2 + line + 4;
+    for text in &rows {
+        let area = Rectangle::new(Point::new(left, top), Size::new(width, row as u32));
+        text::draw(frame, text, &FONT_6X10, Color::Black, area, Align::LEFT);
+        top += row;
+    }
+}
+
+/// A pet from elsewhere, waiting on a long press of A to come in.
+pub struct OfferScreen<'a> {
+    pub offer: &'a Offer,
+    /// Who it would replace.
+    pub current: &'a str,
+}
+
+impl Screen for OfferScreen<'_> {
+    fn name(&self) -> &'static str {
+        "offer"
+    }
+
+    fn draw(&self, ctx: &Context, frame: &mut Frame) {
+        let profile = ctx.profile();
+        let top = draw_title(frame, profile, "Swap pets?", None);
+        let font = profile.font();
+        let line = profile.line_height() as i32;
+        let footer = HEIGHT as i32 - line - 1;
+        let body = format!(
+            "{}, wants to move in. {} would go.",
+            self.offer.pet.summary(),
+            self.current
+        );
+        let lines = text::wrap(&body, profile.columns(WIDTH - 8));
+        let shown = lines.len().min(((footer - top) / line) as usize);
+        for (row, text) in lines.iter().take(shown).enumerate() {
+            let area = Rectangle::new(
+                Point::new(4, top + 2 + row as i32 * line),
+                Size::new(WIDTH - 8, line as u32),
+            );
+            text::draw(frame, text, font, Color::Black, area, Align::LEFT);
+        }
+        let hint = profile.fit("Hold A to swap, tap to keep", WIDTH - 8);
+        let area = Rectangle::new(Point::new(4, footer), Size::new(WIDTH - 8, line as u32));
+        text::draw(frame, &hint, font, Color::Black, area, Align::CENTER);
+    }
+
+    /// Until the offer lapses.
+    fn desired_interval(&self, ctx: &Context) -> Duration {
+        (self.offer.until - ctx.now.to_utc())
+            .to_std()
+            .unwrap_or_default()
+    }
+}
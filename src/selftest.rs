@@ -0,0 +1,322 @@
+//! `self-test`: checks the parts the daemon depends on, one at a time,
+//! and reports on each, for provisioning scripts to gate on.
+//!
+//! The panel check takes the daemon's lock first, so it never draws over a
+//! running instance, and lets go again before returning. It sends its test
+//! pattern the way the daemon sends a frame, through the refresh guard and
+//! the transfer, so a panel that doesn't take it fails the check. Nothing is left
+//! behind except the test pattern on the panel, which the daemon replaces
+//! on its next start.
+
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use embedded_graphics::mono_font::iso_8859_15::FONT_6X10;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::display::{Color, Frame, Simulator, HEIGHT, WIDTH};
+use crate::lock;
+use crate::persist;
+use crate::text::{self, Align};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Pass,
+    Fail,
+    /// Not set up, so there's nothing to check.
+    Skip,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    /// What was found, or what went wrong.
+    pub detail: String,
+    pub millis: u128,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub passed: bool,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn print(&self) {
+        for check in &self.checks {
+            let outcome = match check.outcome {
+                Outcome::Pass => "pass",
+                Outcome::Fail => "FAIL",
+                Outcome::Skip => "skip",
+            };
+            println!("{outcome}  {:<8} {}", check.name, check.detail);
+        }
+        println!(
+            "{}",
+            if self.passed {
+                "all checks passed"
+            } else {
+                "some checks failed"
+            }
+        );
+    }
+
+    /// For the process to exit with: 0 when everything passed.
+    pub fn exit_code(&self) -> i32 {
+        if self.passed {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+/// Runs every check, in order.
+pub fn run(config: &Config) -> Report {
+    let checks = vec![
+        timed("storage", || storage(&config.save_file)),
+        timed("panel", || panel(config)),
+        timed("rtc", || rtc(config)),
+        timed("input", || input(config)),
+    ];
+    Report {
+        passed: checks.iter().all(|check| check.outcome != Outcome::Fail),
+        checks,
+    }
+}
+
+fn timed(name: &'static str, check: impl FnOnce() -> (Outcome, String)) -> Check {
+    let started = Instant::now();
+    let (outcome, detail) = check();
+    Check {
+        name,
+        outcome,
+        detail,
+        millis: started.elapsed().as_millis(),
+    }
+}
+
+/// Whether the save file can be written, tried on a sibling so the save
+/// itself isn't touched.
+fn storage(save_file: &Path) -> (Outcome, String) {
+    let mut probe = save_file.as_os_str().to_owned();
+    probe.push(".selftest");
+    let probe = Path::new(&probe);
+    let written = persist::write_atomic(probe, b"{}");
+    fs::remove_file(probe).ok();
+    match written {
+        Ok(()) => (
+            Outcome::Pass,
+            format!("{} is writable", save_file.display()),
+        ),
+        Err(err) => (Outcome::Fail, format!("{err:#}")),
+    }
+}
+
+/// Draws a test pattern, with every colour the panel has.
+fn panel(config: &Config) -> (Outcome, String) {
+    let _lock = match lock::acquire(&config.lock_file) {
+        Ok(lock::Acquired::Locked(lock)) => Some(lock),
+        Ok(lock::Acquired::Unavailable) => None,
+        Ok(lock::Acquired::Busy(holder)) => {
+            let holder = holder.map_or("another process".into(), |pid| format!("PID {pid}"));
+            return (Outcome::Fail, format!("in use by {holder}; stop it first"));
+        }
+        Err(err) => return (Outcome::Fail, format!("{err:#}")),
+    };
+    let mut frame = Frame::new(WIDTH, HEIGHT);
+    let band = WIDTH / 3;
+    for (index, color) in [Color::White, Color::Black, Color::Red].iter().enumerate() {
+        Rectangle::new(
+            Point::new(index as i32 * band as i32, 0),
+            Size::new(band, HEIGHT),
+        )
+        .into_styled(PrimitiveStyle::with_fill(*color))
+        .draw(&mut frame)
+        .ok();
+    }
+    let label = Rectangle::new(Point::new(0, 0), Size::new(band, HEIGHT));
+    text::draw(
+        &mut frame,
+        "self-test",
+        &FONT_6X10,
+        Color::Black,
+        label,
+        Align::CENTER,
+    );
+    let mut display = Simulator::new(
+        config.simulator.output.clone(),
+        config.partial_refresh,
+        config.mirror(),
+        config.pre_clear.threshold(),
+    );
+    let started = Instant::now();
+    let output = config.simulator.output.display();
+    match display.update(&frame, |_| {}) {
+        Ok(true) => (
+            Outcome::Pass,
+            format!("test pattern shown in {}ms", started.elapsed().as_millis()),
+        ),
+        Ok(false) if display.link_status().degraded => (
+            Outcome::Fail,
+            format!("{output} didn't take the test pattern"),
+        ),
+        Ok(false) => (
+            Outcome::Fail,
+            "the refresh guard held the test pattern back".into(),
+        ),
+        Err(err) => (Outcome::Fail, format!("{err:#}")),
+    }
+}
+
+#[cfg(not(feature = "rtc"))]
+fn rtc(_config: &Config) -> (Outcome, String) {
+    (Outcome::Skip, "built without the rtc feature".into())
+}
+
+#[cfg(feature = "rtc")]
+fn rtc(config: &Config) -> (Outcome, String) {
+    let config = &config.rtc;
+    if !config.enabled {
+        return (Outcome::Skip, "turned off in the config".into());
+    }
+    match crate::rtc::Ds3231::open(&config.bus).and_then(|mut rtc| rtc.read()) {
+        Ok(time) => {
+            let drift = (chrono::Utc::now() - time).num_seconds();
+            (
+                Outcome::Pass,
+                format!("reads {time}, {drift}s behind system time"),
+            )
+        }
+        Err(err) => (Outcome::Fail, format!("{err:#}")),
+    }
+}
+
+#[cfg(not(feature = "evdev"))]
+fn input(_config: &Config) -> (Outcome, String) {
+    (Outcome::Skip, "built without the evdev feature".into())
+}
+
+/// Whether the keyboard standing in for the buttons can be read.
+#[cfg(feature = "evdev")]
+fn input(config: &Config) -> (Outcome, String) {
+    if config.input == crate::input::InputMode::None {
+        return (Outcome::Skip, "turned off in the config".into());
+    }
+    let Some(path) = crate::input::evdev_device(config.input_device.as_deref()) else {
+        return (
+            Outcome::Skip,
+            "no input_device set and no keyboard found".into(),
+        );
+    };
+    match fs::File::open(&path) {
+        Ok(_) => (Outcome::Pass, format!("{} is readable", path.display())),
+        Err(err) => (Outcome::Fail, format!("opening {}: {err}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A config with every file in `dir`.
+    fn config(dir: &Path) -> Config {
+        // Stands in for a keyboard, being a file that can be opened.
+        fs::write(dir.join("event0"), "").unwrap();
+        let mut config = Config {
+            save_file: dir.join("pet.json"),
+            lock_file: dir.join("inky-gotchi.lock"),
+            input_device: Some(dir.join("event0")),
+            ..Config::default()
+        };
+        config.simulator.output = dir.join("frame.png");
+        #[cfg(feature = "rtc")]
+        {
+            config.rtc.enabled = false;
+        }
+        config
+    }
+
+    fn outcome(report: &Report, name: &str) -> Outcome {
+        report
+            .checks
+            .iter()
+            .find(|check| check.name == name)
+            .unwrap_or_else(|| panic!("no {name} check"))
+            .outcome
+    }
+
+    #[test]
+    fn a_working_setup_passes() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = run(&config(dir.path()));
+        assert!(report.passed, "{report:?}");
+        assert_eq!(report.exit_code(), 0);
+        assert_eq!(outcome(&report, "storage"), Outcome::Pass);
+        assert_eq!(outcome(&report, "panel"), Outcome::Pass);
+        assert!(dir.path().join("frame.png").exists());
+        // Nothing left behind but the pattern.
+        assert!(!dir.path().join("pet.json.selftest").exists());
+    }
+
+    #[test]
+    fn one_failed_check_fails_the_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config(dir.path());
+        // Beneath a plain file, so not even root can write there.
+        fs::write(dir.path().join("not-a-dir"), "").unwrap();
+        config.save_file = dir.path().join("not-a-dir/pet.json");
+        let report = run(&config);
+        assert!(!report.passed);
+        assert_eq!(report.exit_code(), 1);
+        assert_eq!(outcome(&report, "storage"), Outcome::Fail);
+        assert_eq!(outcome(&report, "panel"), Outcome::Pass);
+    }
+
+    #[test]
+    fn a_panel_that_takes_nothing_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config(dir.path());
+        config.simulator.output = dir.path().join("gone/frame.png");
+        let report = run(&config);
+        assert!(!report.passed);
+        assert_eq!(outcome(&report, "panel"), Outcome::Fail);
+    }
+
+    #[test]
+    fn a_missing_input_device_is_a_failure_or_not_built() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config(dir.path());
+        config.input_device = Some(dir.path().join("event1"));
+        let report = run(&config);
+        let expected = match cfg!(feature = "evdev") {
+            true => Outcome::Fail,
+            false => Outcome::Skip,
+        };
+        assert_eq!(outcome(&report, "input"), expected);
+    }
+
+    #[test]
+    fn the_json_report_has_a_check_per_part() {
+        let dir = tempfile::tempdir().unwrap();
+        let json = serde_json::to_value(run(&config(dir.path()))).unwrap();
+        assert_eq!(json["passed"], true);
+        let checks = json["checks"].as_array().unwrap();
+        let names: Vec<_> = checks.iter().map(|check| &check["name"]).collect();
+        assert_eq!(names, ["storage", "panel", "rtc", "input"]);
+        for check in checks {
+            assert!(
+                ["pass", "fail", "skip"].contains(&check["outcome"].as_str().unwrap()),
+                "{check}"
+            );
+            assert!(check["detail"].is_string());
+            assert!(check["millis"].is_u64());
+        }
+    }
+}
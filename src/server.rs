@@ -0,0 +1,273 @@
+//! The pet as a server's mascot: it greets whoever logs in over SSH, and
+//! says when package upgrades are waiting. Both are found by asking the
+//! usual tools, `who` every half minute and `apt-get -s upgrade` once a
+//! day, and either is left alone, with a line in the log, on a host
+//! that doesn't have them. `[server] logins` and `upgrades` turn them
+//! off.
+
+use std::collections::{BTreeSet, HashMap};
+use std::process;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::channel::Outbox;
+use crate::collector::{Snapshot, Source};
+use crate::events::Command;
+use crate::subprocess;
+
+const LOGIN_INTERVAL: Duration = Duration::from_secs(30);
+/// Someone coming and going over and over, or a script logging in every
+/// minute, only gets a hello this often.
+const GREET_GAP: Duration = Duration::from_secs(30 * 60);
+const WHO_TIMEOUT: Duration = Duration::from_secs(5);
+
+const UPGRADE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How soon to try again after a failed check.
+const UPGRADE_RETRY: Duration = Duration::from_secs(60 * 60);
+/// Working out upgrades reads every package list; on a Pi Zero that can
+/// take a while.
+const APT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// An SSH session out of `who`: who it is and the line the session's on.
+/// The rest of the line tells sessions on a reused terminal apart.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Session {
+    user: String,
+    key: String,
+}
+
+/// The remote sessions in `who`'s output. Those are the ones that came in
+/// from a host, written in brackets at the end; a bracketed `:0` is a
+/// local display instead.
+fn parse_who(output: &str) -> Vec<Session> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let user = line.split_whitespace().next()?;
+            let (_, from) = line.trim_end().rsplit_once('(')?;
+            let from = from.strip_suffix(')')?;
+            (!from.is_empty() && !from.starts_with(':')).then(|| Session {
+                user: user.to_string(),
+                key: line.split_whitespace().collect::<Vec<_>>().join(" "),
+            })
+        })
+        .collect()
+}
+
+/// How many packages `apt-get -s upgrade` would upgrade: one `Inst` line
+/// each.
+fn parse_apt(output: &str) -> usize {
+    output
+        .lines()
+        .filter(|line| line.starts_with("Inst "))
+        .count()
+}
+
+/// What a failed run of `program` said, for the log.
+fn failure(program: &str, finished: subprocess::Finished) -> String {
+    match finished.status {
+        Some(status) => format!("{program} {status}: {}", finished.stderr.trim()),
+        None => format!("{program} took too long"),
+    }
+}
+
+pub struct LoginSource<O> {
+    events: O,
+    /// Sessions open as of the last look, `None` before the first, whose
+    /// sessions were already there and aren't news.
+    sessions: Option<BTreeSet<Session>>,
+    greeted: HashMap<String, Instant>,
+    /// Whether the last look failed, so a missing `who` is only logged
+    /// once.
+    failing: bool,
+}
+
+impl<O: Outbox> LoginSource<O> {
+    pub fn new(events: O) -> Self {
+        LoginSource {
+            events,
+            sessions: None,
+            greeted: HashMap::new(),
+            failing: false,
+        }
+    }
+
+    fn who(&mut self) -> Option<Vec<Session>> {
+        let result = subprocess::run(&mut process::Command::new("who"), WHO_TIMEOUT)
+            .map_err(|err| format!("couldn't run who: {err}"))
+            .and_then(|finished| match finished.status {
+                Some(status) if status.success() => {
+                    Ok(parse_who(&String::from_utf8_lossy(&finished.stdout)))
+                }
+                _ => Err(failure("who", finished)),
+            });
+        match result {
+            Ok(sessions) => {
+                self.failing = false;
+                Some(sessions)
+            }
+            Err(err) => {
+                if !self.failing {
+                    log::warn!("can't see who's logged in: {err}");
+                }
+                self.failing = true;
+                None
+            }
+        }
+    }
+
+    /// Greets whoever's in `sessions` that wasn't the last time.
+    fn seen(&mut self, sessions: Vec<Session>, now: Instant) {
+        let sessions: BTreeSet<Session> = sessions.into_iter().collect();
+        let previous = self.sessions.replace(sessions.clone());
+        let Some(previous) = previous else {
+            return;
+        };
+        let users: BTreeSet<&str> = sessions
+            .difference(&previous)
+            .map(|session| session.user.as_str())
+            .collect();
+        for user in users {
+            log::info!("{user} logged in");
+            let recent = self
+                .greeted
+                .get(user)
+                .is_some_and(|at| now.duration_since(*at) < GREET_GAP);
+            if recent {
+                continue;
+            }
+            self.greeted.insert(user.to_string(), now);
+            // Only fails while shutting down.
+            self.events.send(Command::Login(user.to_string()));
+        }
+    }
+}
+
+impl<O: Outbox> Source for LoginSource<O> {
+    fn name(&self) -> &'static str {
+        "logins"
+    }
+
+    fn interval(&self) -> Duration {
+        LOGIN_INTERVAL
+    }
+
+    fn collect(&mut self, _snapshot: &Mutex<Snapshot>) {
+        if let Some(sessions) = self.who() {
+            self.seen(sessions, Instant::now());
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct UpgradeSource {
+    next_check: Duration,
+}
+
+impl UpgradeSource {
+    fn pending() -> Result<usize, String> {
+        let mut command = process::Command::new("apt-get");
+        command.args(["-s", "upgrade"]).env("LC_ALL", "C");
+        let finished = subprocess::run(&mut command, APT_TIMEOUT)
+            .map_err(|err| format!("couldn't run apt-get: {err}"))?;
+        match finished.status {
+            Some(status) if status.success() => {
+                Ok(parse_apt(&String::from_utf8_lossy(&finished.stdout)))
+            }
+            _ => Err(failure("apt-get", finished)),
+        }
+    }
+}
+
+impl Source for UpgradeSource {
+    fn name(&self) -> &'static str {
+        "upgrades"
+    }
+
+    fn interval(&self) -> Duration {
+        self.next_check
+    }
+
+    fn collect(&mut self, snapshot: &Mutex<Snapshot>) {
+        match UpgradeSource::pending() {
+            Ok(count) => {
+                log::info!("{count} package upgrades pending");
+                snapshot.lock().unwrap().upgrades = Some(count);
+                self.next_check = UPGRADE_INTERVAL;
+            }
+            Err(err) => {
+                log::warn!("upgrade check failed: {err}");
+                self.next_check = UPGRADE_RETRY;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{self, Receiver};
+
+    use super::*;
+
+    const WHO: &str = include_str!("../tests/fixtures/server/who.txt");
+
+    fn users(sessions: &[Session]) -> Vec<&str> {
+        sessions.iter().map(|s| s.user.as_str()).collect()
+    }
+
+    /// Who's been greeted since the last look.
+    fn greeted(rx: &Receiver<Command>) -> Vec<String> {
+        rx.try_iter()
+            .map(|command| match command {
+                Command::Login(user) => user,
+                other => panic!("{other:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_remote_sessions_count() {
+        // The console and the local display are left out.
+        let sessions = parse_who(WHO);
+        assert_eq!(users(&sessions), ["pi", "ana", "pi"]);
+        assert_eq!(sessions[1].key, "ana pts/1 2024-05-01 09:30 (2001:db8::5)");
+        assert!(parse_who("").is_empty());
+        assert!(parse_who("pi pts/4 2024-05-01 11:00 ()\n").is_empty());
+        assert!(parse_who("garbled (\n\n").is_empty());
+    }
+
+    #[test]
+    fn upgrades_are_counted_by_their_inst_lines() {
+        let upgrades = include_str!("../tests/fixtures/server/apt-upgrade.txt");
+        let up_to_date = include_str!("../tests/fixtures/server/apt-up-to-date.txt");
+        // Kept back and configured packages aren't counted again.
+        assert_eq!(parse_apt(upgrades), 3);
+        assert_eq!(parse_apt(up_to_date), 0);
+        assert_eq!(parse_apt(""), 0);
+    }
+
+    #[test]
+    fn new_logins_are_greeted_once_in_a_while() {
+        let (tx, rx) = mpsc::channel();
+        let mut logins = LoginSource::new(tx);
+        let start = Instant::now();
+        let sessions = parse_who(WHO);
+        // Whoever was on already at startup isn't news.
+        logins.seen(sessions[..1].to_vec(), start);
+        assert!(greeted(&rx).is_empty());
+        // Two new sessions for pi only greet pi once.
+        logins.seen(sessions.clone(), start + LOGIN_INTERVAL);
+        assert_eq!(greeted(&rx), ["ana", "pi"]);
+        logins.seen(sessions.clone(), start + 2 * LOGIN_INTERVAL);
+        assert!(greeted(&rx).is_empty());
+
+        // Back again a minute later, too soon for another hello.
+        logins.seen(Vec::new(), start + 3 * LOGIN_INTERVAL);
+        logins.seen(sessions[1..2].to_vec(), start + 4 * LOGIN_INTERVAL);
+        assert!(greeted(&rx).is_empty());
+        // Half an hour on, it's a hello again.
+        logins.seen(Vec::new(), start + GREET_GAP);
+        logins.seen(sessions[1..2].to_vec(), start + LOGIN_INTERVAL + GREET_GAP);
+        assert_eq!(greeted(&rx), ["ana"]);
+    }
+}
@@ -0,0 +1,369 @@
+//! Settings changed on the panel itself, with the buttons: hold C to open
+//! the menu, tap to move to the next item, hold to change it and tap three
+//! times to save. Saved settings go to an overrides file that's laid over
+//! the config on the next start. A menu left alone for [`TIMEOUT`] closes
+//! without saving.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::buttons::ButtonEvent;
+use crate::config::Config;
+use crate::persist;
+use crate::quiet::Quiet;
+
+/// Refresh intervals the menu cycles through, in seconds.
+pub const REFRESH_PRESETS: [u64; 5] = [30, 60, 120, 300, 600];
+/// How long the menu waits for a press before giving up.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What's saved to the overrides file; anything left out comes from the
+/// config.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Overrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<bool>,
+}
+
+impl Overrides {
+    /// The overrides at `path`, or none if there's no file yet.
+    pub fn load(path: &Path) -> Result<Overrides> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Overrides::default())
+            }
+            Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+        };
+        let overrides: Overrides =
+            toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+        anyhow::ensure!(
+            overrides.refresh_interval != Some(0),
+            "{}: refresh_interval must be positive",
+            path.display()
+        );
+        Ok(overrides)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        persist::write_atomic(path, toml::to_string(self)?.as_bytes())
+    }
+}
+
+/// The settings in effect: the config with the overrides on top.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Settings {
+    /// Seconds, as in [`Config::refresh_interval`].
+    pub refresh_interval: u64,
+    /// Whether the configured quiet hours are observed.
+    pub quiet_hours: bool,
+}
+
+impl Settings {
+    pub fn new(config: &Config, overrides: &Overrides) -> Self {
+        Settings {
+            refresh_interval: overrides
+                .refresh_interval
+                .unwrap_or(config.refresh_interval),
+            quiet_hours: overrides.quiet_hours.unwrap_or(true),
+        }
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        Duration::from_secs(self.refresh_interval)
+    }
+
+    /// The quiet hours to observe, if any, out of those `planned` for the
+    /// day.
+    pub fn quiet_hours<'a>(&self, planned: Option<&'a Quiet>) -> Option<&'a Quiet> {
+        planned.filter(|_| self.quiet_hours)
+    }
+
+    /// What has to be saved for these settings to come back on a restart.
+    pub fn overrides(&self, config: &Config) -> Overrides {
+        Overrides {
+            refresh_interval: Some(self.refresh_interval)
+                .filter(|&interval| interval != config.refresh_interval),
+            quiet_hours: Some(self.quiet_hours).filter(|on| !on),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Item {
+    RefreshInterval,
+    QuietHours,
+}
+
+impl Item {
+    pub fn label(self) -> &'static str {
+        match self {
+            Item::RefreshInterval => "Refresh",
+            Item::QuietHours => "Quiet hours",
+        }
+    }
+}
+
+/// What a press did to the menu.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Open,
+    /// Closed, keeping these settings.
+    Saved(Settings),
+}
+
+/// The open menu, with the edits made so far.
+pub struct Menu {
+    draft: Settings,
+    items: Vec<Item>,
+    pub selected: usize,
+    last_input: Instant,
+}
+
+impl Menu {
+    /// Opens the menu on `current`. Quiet hours are only offered when the
+    /// config sets some.
+    pub fn open(current: Settings, config: &Config, now: Instant) -> Self {
+        let mut items = vec![Item::RefreshInterval];
+        if config.quiet_hours.is_some() {
+            items.push(Item::QuietHours);
+        }
+        Menu {
+            draft: current,
+            items,
+            selected: 0,
+            last_input: now,
+        }
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    /// `item`'s value in the draft, as shown.
+    pub fn value(&self, item: Item) -> String {
+        match item {
+            Item::RefreshInterval => match self.draft.refresh_interval {
+                secs if secs % 60 == 0 => format!("{}min", secs / 60),
+                secs => format!("{secs}s"),
+            },
+            Item::QuietHours if self.draft.quiet_hours => "on".into(),
+            Item::QuietHours => "off".into(),
+        }
+    }
+
+    pub fn handle(&mut self, event: ButtonEvent, now: Instant) -> Outcome {
+        self.last_input = now;
+        match event {
+            ButtonEvent::Short(_) => self.selected = (self.selected + 1) % self.items.len(),
+            ButtonEvent::Long(_) => self.change(),
+            ButtonEvent::Tripletap(_) => return Outcome::Saved(self.draft),
+        }
+        Outcome::Open
+    }
+
+    /// Steps the selected item to its next value.
+    fn change(&mut self) {
+        match self.items[self.selected] {
+            Item::RefreshInterval => {
+                // A configured value off the presets moves to the next one up.
+                let current = self.draft.refresh_interval;
+                self.draft.refresh_interval = REFRESH_PRESETS
+                    .iter()
+                    .copied()
+                    .find(|&preset| preset > current)
+                    .unwrap_or(REFRESH_PRESETS[0]);
+            }
+            Item::QuietHours => self.draft.quiet_hours = !self.draft.quiet_hours,
+        }
+    }
+
+    /// When the menu gives up waiting.
+    pub fn deadline(&self) -> Instant {
+        self.last_input + TIMEOUT
+    }
+
+    pub fn timed_out(&self, now: Instant) -> bool {
+        now >= self.deadline()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buttons::Button;
+    use crate::quiet::{QuietConfig, QuietHours};
+    use chrono::NaiveTime;
+
+    const TAP: ButtonEvent = ButtonEvent::Short(Button::A);
+    const HOLD: ButtonEvent = ButtonEvent::Long(Button::A);
+    const SAVE: ButtonEvent = ButtonEvent::Tripletap(Button::A);
+
+    fn config(quiet: bool) -> Config {
+        let quiet_hours = quiet.then(|| QuietConfig {
+            sun: false,
+            hours: QuietHours {
+                start: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+            },
+            extra: Vec::new(),
+        });
+        Config {
+            refresh_interval: 60,
+            quiet_hours,
+            ..Config::default()
+        }
+    }
+
+    /// Opens the menu on `config` and presses `events` one a second.
+    fn press(config: &Config, events: &[ButtonEvent]) -> (Menu, Vec<Outcome>) {
+        let start = Instant::now();
+        let current = Settings::new(config, &Overrides::default());
+        let mut menu = Menu::open(current, config, start);
+        let outcomes = (1..)
+            .zip(events)
+            .map(|(secs, &event)| menu.handle(event, start + Duration::from_secs(secs)))
+            .collect();
+        (menu, outcomes)
+    }
+
+    #[test]
+    fn quiet_hours_are_only_offered_when_configured() {
+        assert_eq!(
+            press(&config(false), &[]).0.items(),
+            [Item::RefreshInterval]
+        );
+        assert_eq!(
+            press(&config(true), &[]).0.items(),
+            [Item::RefreshInterval, Item::QuietHours]
+        );
+    }
+
+    #[test]
+    fn taps_move_through_the_items_and_wrap() {
+        let config = config(true);
+        let selected = |taps: usize| press(&config, &vec![TAP; taps]).0.selected;
+        assert_eq!([0, 1, 2, 3].map(selected), [0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn holding_steps_through_the_presets() {
+        let config = config(false);
+        let shown: Vec<_> = (0..6)
+            .map(|holds| {
+                press(&config, &vec![HOLD; holds])
+                    .0
+                    .value(Item::RefreshInterval)
+            })
+            .collect();
+        assert_eq!(shown, ["1min", "2min", "5min", "10min", "30s", "1min"]);
+    }
+
+    #[test]
+    fn a_value_off_the_presets_moves_to_the_next_one_up() {
+        let mut config = config(false);
+        config.refresh_interval = 90;
+        let (menu, _) = press(&config, &[]);
+        assert_eq!(menu.value(Item::RefreshInterval), "90s");
+        let (menu, _) = press(&config, &[HOLD]);
+        assert_eq!(menu.value(Item::RefreshInterval), "2min");
+    }
+
+    #[test]
+    fn saving_keeps_the_edits() {
+        let config = config(true);
+        let (_, outcomes) = press(&config, &[HOLD, TAP, HOLD, SAVE]);
+        let saved = Settings {
+            refresh_interval: 120,
+            quiet_hours: false,
+        };
+        assert_eq!(
+            outcomes,
+            [
+                Outcome::Open,
+                Outcome::Open,
+                Outcome::Open,
+                Outcome::Saved(saved)
+            ]
+        );
+        assert_eq!(
+            saved.overrides(&config),
+            Overrides {
+                refresh_interval: Some(120),
+                quiet_hours: Some(false),
+            }
+        );
+    }
+
+    #[test]
+    fn left_alone_it_times_out() {
+        let config = config(true);
+        let (menu, outcomes) = press(&config, &[HOLD, TAP, HOLD]);
+        assert!(!outcomes.iter().any(|o| matches!(o, Outcome::Saved(_))));
+        // The last press was three seconds in.
+        let last = menu.deadline() - TIMEOUT;
+        assert!(!menu.timed_out(last + TIMEOUT - Duration::from_millis(1)));
+        assert!(menu.timed_out(last + TIMEOUT));
+    }
+
+    #[test]
+    fn every_press_puts_the_timeout_back() {
+        let config = config(false);
+        let start = Instant::now();
+        let mut menu = Menu::open(
+            Settings::new(&config, &Overrides::default()),
+            &config,
+            start,
+        );
+        assert_eq!(menu.deadline(), start + TIMEOUT);
+        let later = start + TIMEOUT / 2;
+        menu.handle(TAP, later);
+        assert!(!menu.timed_out(start + TIMEOUT));
+        assert_eq!(menu.deadline(), later + TIMEOUT);
+    }
+
+    #[test]
+    fn unchanged_settings_save_nothing() {
+        let config = config(true);
+        let settings = Settings::new(&config, &Overrides::default());
+        assert_eq!(settings.overrides(&config), Overrides::default());
+        assert_eq!(toml::to_string(&Overrides::default()).unwrap(), "");
+    }
+
+    #[test]
+    fn overrides_go_over_the_config_after_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        assert_eq!(Overrides::load(&path).unwrap(), Overrides::default());
+
+        let config = config(true);
+        let (_, outcomes) = press(&config, &[HOLD, HOLD, SAVE]);
+        let Some(Outcome::Saved(saved)) = outcomes.last().copied() else {
+            panic!("not saved: {outcomes:?}");
+        };
+        saved.overrides(&config).save(&path).unwrap();
+        let reloaded = Settings::new(&config, &Overrides::load(&path).unwrap());
+        assert_eq!(reloaded, saved);
+        assert_eq!(reloaded.refresh_interval(), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn bad_overrides_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        for text in [
+            "refresh_interval = 0",
+            "theme = \"dark\"",
+            "quiet_hours = 3",
+        ] {
+            std::fs::write(&path, text).unwrap();
+            assert!(Overrides::load(&path).is_err(), "{text}");
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Grey on a panel that has none: areas filled with an ordered dither,
+//! i.e. inked wherever a fixed Bayer matrix says the level is high
+//! enough. The matrix is laid over the whole frame rather than each area,
+//! so neighbouring fills line up without seams.
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use crate::display::{Color, Frame};
+
+/// How finely the levels are told apart. Two by two gives five, in a
+/// coarse, even pattern; four by four gives seventeen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bayer {
+    Two,
+    Four,
+}
+
+const TWO: [[u8; 2]; 2] = [[0, 2], [3, 1]];
+const FOUR: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+impl Bayer {
+    /// The level at `point` has to be over to be inked, between 0 and 1.
+    fn threshold(self, point: Point) -> f32 {
+        let (x, y) = (
+            point.x.rem_euclid(4) as usize,
+            point.y.rem_euclid(4) as usize,
+        );
+        let (rank, cells) = match self {
+            Bayer::Two => (TWO[y % 2][x % 2], 4),
+            Bayer::Four => (FOUR[y][x], 16),
+        };
+        (rank as f32 + 0.5) / cells as f32
+    }
+}
+
+/// Inks `area` in `color` at `level`, from 0 for none of it to 1 for all.
+pub fn fill(frame: &mut Frame, area: Rectangle, level: f32, bayer: Bayer, color: Color) {
+    let pixels = area
+        .points()
+        .filter(|&point| level > bayer.threshold(point))
+        .map(|point| Pixel(point, color));
+    frame.draw_iter(pixels).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inked(frame: &Frame, area: Rectangle) -> usize {
+        area.points()
+            .filter(|p| frame.pixel(p.x, p.y) != Color::White)
+            .count()
+    }
+
+    #[test]
+    fn levels_ink_their_share_of_each_tile() {
+        for (bayer, tile) in [(Bayer::Two, 2), (Bayer::Four, 4)] {
+            let cells = tile * tile;
+            for inked_cells in 0..=cells {
+                let level = inked_cells as f32 / cells as f32;
+                let mut frame = Frame::new(8, 8);
+                let area = Rectangle::new(Point::zero(), Size::new(8, 8));
+                fill(&mut frame, area, level, bayer, Color::Black);
+                assert_eq!(
+                    inked(&frame, area),
+                    inked_cells as usize * 64 / cells as usize,
+                    "{bayer:?} at {level}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn neighbouring_fills_line_up() {
+        let mut apart = Frame::new(12, 4);
+        fill(
+            &mut apart,
+            Rectangle::new(Point::zero(), Size::new(5, 4)),
+            0.5,
+            Bayer::Four,
+            Color::Red,
+        );
+        fill(
+            &mut apart,
+            Rectangle::new(Point::new(5, 0), Size::new(7, 4)),
+            0.5,
+            Bayer::Four,
+            Color::Red,
+        );
+        let mut whole = Frame::new(12, 4);
+        fill(
+            &mut whole,
+            Rectangle::new(Point::zero(), Size::new(12, 4)),
+            0.5,
+            Bayer::Four,
+            Color::Red,
+        );
+        assert_eq!(crate::screens::ascii(&apart), crate::screens::ascii(&whole));
+        assert_eq!(
+            crate::screens::ascii(&whole).lines().next(),
+            Some("r.r.r.r.r.r.")
+        );
+    }
+
+    #[test]
+    fn fills_only_touch_their_area() {
+        let mut frame = Frame::new(10, 10);
+        let area = Rectangle::new(Point::new(3, 3), Size::new(4, 4));
+        fill(&mut frame, area, 1.0, Bayer::Two, Color::Black);
+        assert_eq!(
+            inked(&frame, Rectangle::new(Point::zero(), Size::new(10, 10))),
+            16
+        );
+        assert_eq!(inked(&frame, area), 16);
+    }
+}
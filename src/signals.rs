@@ -0,0 +1,48 @@
+//! SIGTERM and SIGINT for the threaded control plane: a thread of their
+//! own turns the first of either into a [`Command::Shutdown`], as tokio
+//! does for the async one in `tasks`.
+
+use std::sync::mpsc::Sender;
+use std::{io, ptr, thread};
+
+use anyhow::{Context as _, Result};
+
+use crate::events::Command;
+
+/// Starts the thread. Has to be called before any other thread starts, so
+/// that they all inherit a mask blocking the two signals and only this
+/// one takes them. Child processes start with a clear mask regardless.
+pub fn spawn(tx: Sender<Command>) -> Result<()> {
+    // SAFETY: `set` is initialised by `sigemptyset` before anything reads
+    // it, and only ever passed to the libc calls that expect it.
+    let set = unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGTERM);
+        libc::sigaddset(&mut set, libc::SIGINT);
+        set
+    };
+    // SAFETY: as above; the old mask isn't wanted.
+    let err = unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &set, ptr::null_mut()) };
+    if err != 0 {
+        return Err(io::Error::from_raw_os_error(err)).context("blocking SIGTERM and SIGINT");
+    }
+    thread::Builder::new()
+        .name("signals".into())
+        .spawn(move || {
+            let mut signal = 0;
+            // SAFETY: `set` is a valid signal set and `signal` a valid out
+            // pointer for the duration of the call.
+            if unsafe { libc::sigwait(&set, &mut signal) } != 0 {
+                log::warn!("couldn't wait for signals: {}", io::Error::last_os_error());
+                return;
+            }
+            let name = match signal {
+                libc::SIGTERM => "SIGTERM",
+                _ => "SIGINT",
+            };
+            log::info!("got {name}, shutting down");
+            tx.send(Command::Shutdown).ok();
+        })?;
+    Ok(())
+}
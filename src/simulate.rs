@@ -0,0 +1,315 @@
+//! Fast-forwarding the pet through virtual days, for balancing decay rates
+//! without waiting for real ones.
+//!
+//! A scenario is a text file with one action per line:
+//!
+//! ```text
+//! # comments and blank lines are ignored
+//! t=2h feed          # once, two hours in
+//! every=6h feed      # at 6h, 12h, 18h, ...
+//! on=misbehave discipline
+//! ```
+//!
+//! Times take `m`, `h` or `d` suffixes. Actions are the control socket's
+//! words: `feed`, `play`, `pet`, `clean` and `discipline`.
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::{bail, Context as _, Result};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::events::{Action, Command};
+use crate::pet::{Form, Pet};
+use crate::quiet;
+use crate::runtime::Runtime;
+
+/// Virtual time between ticks.
+const STEP: Duration = Duration::minutes(15);
+/// Virtual time between rows of the trajectory.
+const SAMPLE: Duration = Duration::hours(1);
+
+#[derive(Default)]
+pub struct Scenario {
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    when: When,
+    action: Action,
+}
+
+/// Times are from the start of the run.
+enum When {
+    At(Duration),
+    Every(Duration),
+    /// Straight after the pet starts misbehaving.
+    OnMisbehave,
+}
+
+impl When {
+    /// Whether this falls in the step `(from, to]`.
+    fn in_step(&self, from: Duration, to: Duration) -> bool {
+        match *self {
+            When::At(at) => from < at && at <= to,
+            When::Every(every) => {
+                let count = |t: Duration| t.num_seconds() / every.num_seconds();
+                count(to) > count(from)
+            }
+            When::OnMisbehave => false,
+        }
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading scenario {}", path.display()))?;
+        Scenario::parse(&text).with_context(|| format!("parsing scenario {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let entry = parse_entry(line).with_context(|| format!("line {}", number + 1))?;
+            entries.push(entry);
+        }
+        Ok(Scenario { entries })
+    }
+
+    /// The actions due in the step `(from, to]`, given whether the pet
+    /// started misbehaving during it.
+    pub fn due(
+        &self,
+        from: Duration,
+        to: Duration,
+        misbehaved: bool,
+    ) -> impl Iterator<Item = Action> + '_ {
+        self.entries
+            .iter()
+            .filter(move |entry| match entry.when {
+                When::OnMisbehave => misbehaved,
+                ref when => when.in_step(from, to),
+            })
+            .map(|entry| entry.action)
+    }
+}
+
+fn parse_entry(line: &str) -> Result<Entry> {
+    let Some((when, action)) = line.split_once(char::is_whitespace) else {
+        bail!("expected `<when> <action>`, got {line:?}");
+    };
+    let action = match Command::parse(action) {
+        Some(Command::Act(action)) => action,
+        _ => bail!("unknown action {:?}", action.trim()),
+    };
+    let when = if let Some(at) = when.strip_prefix("t=") {
+        When::At(parse_duration(at)?)
+    } else if let Some(every) = when.strip_prefix("every=") {
+        let every = parse_duration(every)?;
+        if every <= Duration::zero() {
+            bail!("repeat interval must be positive");
+        }
+        When::Every(every)
+    } else if when == "on=misbehave" {
+        When::OnMisbehave
+    } else {
+        bail!("expected `t=`, `every=` or `on=misbehave`, got {when:?}");
+    };
+    Ok(Entry { when, action })
+}
+
+/// Parses `90m`, `2h` or `3d`; fractions like `1.5h` are fine.
+pub fn parse_duration(text: &str) -> Result<Duration> {
+    let (number, unit) = text.split_at(text.len().saturating_sub(1));
+    let minutes_per_unit = match unit {
+        "m" => 1.0,
+        "h" => 60.0,
+        "d" => 24.0 * 60.0,
+        _ => bail!("time {text:?} needs a unit: m, h or d"),
+    };
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("bad time {text:?}"))?;
+    Ok(Duration::seconds(
+        (number * minutes_per_unit * 60.0).round() as i64,
+    ))
+}
+
+/// Where runs start unless told otherwise: a morning in early January.
+pub fn default_start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 1, 5, 8, 0, 0).unwrap()
+}
+
+/// What a run produced.
+pub struct Report {
+    /// Hourly stats, as CSV with a header row.
+    pub csv: String,
+    /// Notable moments, e.g. `t=72h grew up into Wild`.
+    pub events: Vec<String>,
+    pub survived: bool,
+}
+
+/// Runs `scenario` for `days` virtual days from `start`. Everything that
+/// would normally come from the outside world is fixed: the clock is
+/// virtual and the RNG seeded, so the same inputs always give the same
+/// report.
+pub fn run(
+    config: &Config,
+    clock: &Clock,
+    scenario: &Scenario,
+    days: u32,
+    seed: u64,
+    start: DateTime<Utc>,
+) -> Report {
+    let mut pet = Pet::new(&config.pet.name, start);
+    let mut runtime = Runtime::new(seed, &mut pet);
+    let mut csv = String::from("hours,hunger,happiness,energy,messes,training,form\n");
+    let mut events = Vec::new();
+    let end = Duration::days(days as i64);
+    let mut elapsed = Duration::zero();
+    let mut sampled = Duration::zero();
+    let mut planner = quiet::Planner::default();
+    sample(&mut csv, elapsed, &pet);
+    while elapsed < end && !pet.is_dead() {
+        let previous = elapsed;
+        elapsed += STEP;
+        let now = start + elapsed;
+        let form = pet.form;
+        let local = clock.localize(now);
+        let bedtime = quiet::is_bedtime(planner.get(config, clock, local), local.time());
+        pet.tick(now, clock, &config.pet, bedtime);
+        let stamp = format_hours(elapsed);
+        if pet.form != form {
+            events.push(format!("t={stamp}h grew up into {:?}", pet.form));
+        }
+        if pet.is_dead() {
+            events.push(format!("t={stamp}h died"));
+            break;
+        }
+        let misbehaved = runtime.misbehavior_due(&pet, now);
+        if misbehaved {
+            pet.misbehave(now);
+            events.push(format!("t={stamp}h misbehaved"));
+        }
+        for action in scenario.due(previous, elapsed, misbehaved) {
+            pet.apply(action);
+        }
+        if elapsed - sampled >= SAMPLE {
+            sampled = elapsed;
+            sample(&mut csv, elapsed, &pet);
+        }
+    }
+    if pet.form == Form::Baby && !pet.is_dead() {
+        events.push("never grew up".into());
+    }
+    Report {
+        csv,
+        events,
+        survived: !pet.is_dead(),
+    }
+}
+
+fn sample(csv: &mut String, elapsed: Duration, pet: &Pet) {
+    writeln!(
+        csv,
+        "{},{:.1},{:.1},{:.1},{},{:.1},{:?}",
+        format_hours(elapsed),
+        pet.hunger,
+        pet.happiness,
+        pet.energy,
+        pet.messes,
+        pet.training,
+        pet.form
+    )
+    .ok();
+}
+
+fn format_hours(elapsed: Duration) -> String {
+    let hours = elapsed.num_minutes() as f64 / 60.0;
+    format!("{hours}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Zone;
+
+    fn simulate(name: &str, seed: u64) -> Report {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("scenarios")
+            .join(name);
+        let scenario = Scenario::load(&path).unwrap();
+        let start = default_start();
+        let clock = Clock::frozen(Zone::Named(chrono_tz::Europe::Bucharest), start);
+        run(&Config::default(), &clock, &scenario, 7, seed, start)
+    }
+
+    #[test]
+    fn good_care_survives_the_week() {
+        let report = simulate("good-care.txt", 0);
+        assert!(report.survived, "{:?}", report.events);
+        assert!(report
+            .events
+            .iter()
+            .any(|e| e == "t=72h grew up into Noble"));
+        // A header and a row for each hour, start and end included.
+        assert_eq!(report.csv.lines().count(), 2 + 7 * 24);
+    }
+
+    #[test]
+    fn neglect_starves_on_the_third_day() {
+        let report = simulate("neglect.txt", 0);
+        assert!(!report.survived);
+        let died = report.events.iter().find_map(|e| {
+            let hours = e.strip_prefix("t=")?.strip_suffix("h died")?;
+            hours.parse::<f64>().ok()
+        });
+        let died = died.expect("a death in the events");
+        assert!((48.0..72.0).contains(&died), "died at {died}h");
+    }
+
+    #[test]
+    fn runs_replay_exactly() {
+        let first = simulate("good-care.txt", 7);
+        let again = simulate("good-care.txt", 7);
+        assert_eq!(first.csv, again.csv);
+        assert_eq!(first.events, again.events);
+    }
+
+    #[test]
+    fn scenarios_schedule_actions_by_step() {
+        let scenario =
+            Scenario::parse("t=1h feed\nevery=2h play # fun\n\non=misbehave discipline").unwrap();
+        let step = |from: i64, to: i64, misbehaved| -> Vec<Action> {
+            scenario
+                .due(Duration::minutes(from), Duration::minutes(to), misbehaved)
+                .collect()
+        };
+        assert_eq!(step(45, 60, false), [Action::Feed]);
+        assert_eq!(step(60, 75, false), []);
+        assert_eq!(step(105, 120, true), [Action::Play, Action::Discipline]);
+        assert_eq!(step(225, 240, false), [Action::Play]);
+    }
+
+    #[test]
+    fn bad_scenarios_name_the_line() {
+        for (text, wanted) in [
+            ("t=1h feed\nt=2 feed", "line 2"),
+            ("every=0h feed", "positive"),
+            ("t=1h dance", "unknown action"),
+            ("at=1h feed", "expected `t=`"),
+            ("feed", "expected `<when> <action>`"),
+        ] {
+            let err = format!("{:#}", Scenario::parse(text).err().unwrap());
+            assert!(err.contains(wanted), "{text:?}: {err}");
+        }
+        assert_eq!(parse_duration("1.5h").unwrap(), Duration::minutes(90));
+    }
+}
@@ -0,0 +1,542 @@
+//! 1-bit sprites packed one row per integer.
+//!
+//! Each row is a `u32` whose most significant used bit is the leftmost
+//! pixel, so the binary literals below read like the art they encode.
+//! Sprites loaded from an artwork pack, see [`crate::artwork`], keep a flag
+//! per pixel instead, and can have a grey level per pixel on top.
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use crate::display::{Color, Frame};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flip {
+    None,
+    /// Mirror left-to-right, e.g. so the pet faces where it's walking.
+    Horizontal,
+}
+
+pub struct Sprite {
+    pub width: u32,
+    pub height: u32,
+    pixels: Pixels,
+    /// From 0 for paper to 3 for full ink, one per pixel, for artwork
+    /// drawn in grey. The pixels are those at 2 and up.
+    levels: Option<&'static [u8]>,
+}
+
+enum Pixels {
+    /// Built in: one integer per row, as described above.
+    Rows(&'static [u32]),
+    /// Loaded from an artwork pack: one flag per pixel, row by row. A pack
+    /// is loaded once and kept until the daemon stops, so these are leaked
+    /// rather than owned, which keeps sprites usable as constants.
+    Bitmap(&'static [bool]),
+}
+
+impl Sprite {
+    pub const fn new(width: u32, rows: &'static [u32]) -> Self {
+        Sprite {
+            width,
+            height: rows.len() as u32,
+            pixels: Pixels::Rows(rows),
+            levels: None,
+        }
+    }
+
+    /// A sprite of `size` with `is_set` saying which pixels are drawn.
+    pub fn from_fn(size: Size, is_set: impl Fn(u32, u32) -> bool) -> Self {
+        let pixels: Vec<bool> = (0..size.height)
+            .flat_map(|y| (0..size.width).map(move |x| (x, y)))
+            .map(|(x, y)| is_set(x, y))
+            .collect();
+        Sprite {
+            width: size.width,
+            height: size.height,
+            pixels: Pixels::Bitmap(Vec::leak(pixels)),
+            levels: None,
+        }
+    }
+
+    /// A sprite of `size` in grey, with `level` giving each pixel's from 0
+    /// to 3. Only 2 and up are drawn on the panel.
+    pub fn from_levels(size: Size, level: impl Fn(u32, u32) -> u8) -> Self {
+        let levels: Vec<u8> = (0..size.height)
+            .flat_map(|y| (0..size.width).map(move |x| (x, y)))
+            .map(|(x, y)| level(x, y).min(3))
+            .collect();
+        let mut sprite = Sprite::from_fn(size, |x, y| levels[(y * size.width + x) as usize] >= 2);
+        sprite.levels = Some(Vec::leak(levels));
+        sprite
+    }
+
+    /// This sprite on a canvas of `size`, standing at the bottom in the
+    /// middle, and cropped if it's bigger.
+    pub fn fitted(&self, size: Size) -> Self {
+        let dx = (self.width as i32 - size.width as i32) / 2;
+        let dy = self.height as i32 - size.height as i32;
+        let from = |x: u32, y: u32| {
+            let (x, y) = (x as i32 + dx, y as i32 + dy);
+            (x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height)
+                .then_some((x as u32, y as u32))
+        };
+        match self.levels {
+            Some(_) => {
+                Sprite::from_levels(size, |x, y| from(x, y).map_or(0, |(x, y)| self.level(x, y)))
+            }
+            None => Sprite::from_fn(size, |x, y| {
+                from(x, y).is_some_and(|(x, y)| self.is_set(x, y))
+            }),
+        }
+    }
+
+    /// Whether there's grey in it.
+    pub fn is_grey(&self) -> bool {
+        self.levels.is_some()
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    pub fn bounds(&self, origin: Point) -> Rectangle {
+        Rectangle::new(origin, self.size())
+    }
+
+    fn is_set(&self, x: u32, y: u32) -> bool {
+        match &self.pixels {
+            Pixels::Rows(rows) => rows[y as usize] & (1 << (self.width - 1 - x)) != 0,
+            Pixels::Bitmap(pixels) => pixels[(y * self.width + x) as usize],
+        }
+    }
+
+    fn level(&self, x: u32, y: u32) -> u8 {
+        match self.levels {
+            Some(levels) => levels[(y * self.width + x) as usize],
+            None => 3 * self.is_set(x, y) as u8,
+        }
+    }
+
+    /// Draws the set pixels in `color`; unset pixels are left untouched.
+    pub fn draw<D>(&self, target: &mut D, origin: Point, color: Color, flip: Flip)
+    where
+        D: DrawTarget<Color = Color>,
+    {
+        let pixels = (0..self.height).flat_map(move |y| {
+            (0..self.width).filter_map(move |x| {
+                let src_x = match flip {
+                    Flip::None => x,
+                    Flip::Horizontal => self.width - 1 - x,
+                };
+                self.is_set(src_x, y)
+                    .then(|| Pixel(origin + Point::new(x as i32, y as i32), color))
+            })
+        });
+        target.draw_iter(pixels).ok();
+    }
+
+    /// Draws the sprite enlarged by an integer factor, each pixel becoming a
+    /// `scale`×`scale` block.
+    pub fn draw_scaled<D>(&self, target: &mut D, origin: Point, color: Color, scale: u32)
+    where
+        D: DrawTarget<Color = Color>,
+    {
+        let block = Size::new(scale, scale);
+        for y in 0..self.height {
+            for x in (0..self.width).filter(|&x| self.is_set(x, y)) {
+                let at = origin + Point::new((x * scale) as i32, (y * scale) as i32);
+                target.fill_solid(&Rectangle::new(at, block), color).ok();
+            }
+        }
+    }
+
+    /// Shades the greys in, after the sprite is drawn at the same place
+    /// and size: the light grey over paper, the dark over ink. Only
+    /// previews show it; the panel keeps the sprite as drawn.
+    pub fn shade(&self, frame: &mut Frame, origin: Point, flip: Flip, scale: u32) {
+        if self.levels.is_none() {
+            return;
+        }
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let src_x = match flip {
+                    Flip::None => x,
+                    Flip::Horizontal => self.width - 1 - x,
+                };
+                let level = self.level(src_x, y);
+                if level != 1 && level != 2 {
+                    continue;
+                }
+                let at = origin + Point::new((x * scale) as i32, (y * scale) as i32);
+                for dy in 0..scale as i32 {
+                    for dx in 0..scale as i32 {
+                        let (px, py) = (at.x + dx, at.y + dy);
+                        // Light grey only where nothing else is drawn.
+                        if level == 2 || frame.pixel(px, py) == Color::White {
+                            frame.shade(px, py);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The pet standing still, facing right.
+pub const PET_IDLE: Sprite = Sprite::new(
+    24,
+    &[
+        0b000000000111111000000000,
+        0b000000011000000110000000,
+        0b000001100000000001100000,
+        0b000010000000000000010000,
+        0b000100000000000000001000,
+        0b000100000000011001101000,
+        0b001000000000011001100100,
+        0b001000000000000000000100,
+        0b001000000000000010010100,
+        0b001000000000000001100100,
+        0b001000000000000000000100,
+        0b001000000000000000000100,
+        0b000100000000000000001000,
+        0b000100000000000000001000,
+        0b000011000000000000110000,
+        0b000000111111111111000000,
+        0b000000011000000110000000,
+        0b000000111000001110000000,
+    ],
+);
+
+/// The pet up to no good: scowling, tongue out.
+pub const PET_MISCHIEF: Sprite = Sprite::new(
+    24,
+    &[
+        0b000000000111111000000000,
+        0b000000011000000110000000,
+        0b000001100000000001100000,
+        0b000010000000000000010000,
+        0b000100000000011001101000,
+        0b000100000000001001001000,
+        0b001000000000011001100100,
+        0b001000000000000000000100,
+        0b001000000000000011110100,
+        0b001000000000000010010100,
+        0b001000000000000001100100,
+        0b001000000000000000000100,
+        0b000100000000000000001000,
+        0b000100000000000000001000,
+        0b000011000000000000110000,
+        0b000000111111111111000000,
+        0b000000011000000110000000,
+        0b000000111000001110000000,
+    ],
+);
+
+/// Adult form for a well-trained, well-cared-for pet: upright, with a crown.
+pub const PET_NOBLE: Sprite = Sprite::new(
+    24,
+    &[
+        0b000000000101001000000000,
+        0b000000000111111000000000,
+        0b000000011000000110000000,
+        0b000001100000000001100000,
+        0b000010000000000000010000,
+        0b000100000000001100110000,
+        0b000100000000001100110100,
+        0b001000000000000000000100,
+        0b001000000000000001000100,
+        0b001000000000000000111000,
+        0b001000000000000000000100,
+        0b000100000000000000001000,
+        0b000100000000000000001000,
+        0b000011000000000000110000,
+        0b000000111111111111000000,
+        0b000000010010010010000000,
+        0b000000011000000110000000,
+        0b000000111000001110000000,
+    ],
+);
+
+/// Adult form for a pet left to its own devices: shaggy and wide-eyed.
+pub const PET_WILD: Sprite = Sprite::new(
+    24,
+    &[
+        0b000000100101101001000000,
+        0b000000010111111010000000,
+        0b000010110000001101100000,
+        0b000001000000000001010000,
+        0b000110000000000000011000,
+        0b000100000000011101110100,
+        0b001000000000010101010100,
+        0b011000000000011101110010,
+        0b001000000000000000000110,
+        0b011000000000000010100010,
+        0b001000000000000001000100,
+        0b011000000000000000000110,
+        0b000100000000000000001000,
+        0b001100000000000000001100,
+        0b000011000000000000110000,
+        0b000000111111111111000000,
+        0b000000111000000111000000,
+        0b000001111000001111000000,
+    ],
+);
+
+/// Shown in red over a misbehaving pet.
+pub const EXCLAMATION: Sprite = Sprite::new(
+    4,
+    &[
+        0b0110, 0b1111, 0b1111, 0b1111, 0b0110, 0b0110, 0b0110, 0b0000, 0b0110, 0b0110,
+    ],
+);
+
+/// The pet with nowhere to go: eyes wide, mouth a small o.
+pub const PET_LOST: Sprite = Sprite::new(
+    24,
+    &[
+        0b000000000111111000000000,
+        0b000000011000000110000000,
+        0b000001100000000001100000,
+        0b000010000000000000010000,
+        0b000100000000111011101000,
+        0b000100000000101010101000,
+        0b001000000000111011100100,
+        0b001000000000000000000100,
+        0b001000000000000001100100,
+        0b001000000000000010010100,
+        0b001000000000000001100100,
+        0b001000000000000000000100,
+        0b000100000000000000001000,
+        0b000100000000000000001000,
+        0b000011000000000000110000,
+        0b000000111111111111000000,
+        0b000000011000000110000000,
+        0b000000111000001110000000,
+    ],
+);
+
+/// The pet beaming: eyes squeezed shut, a wide grin.
+pub const PET_HAPPY: Sprite = Sprite::new(
+    24,
+    &[
+        0b000000000111111000000000,
+        0b000000011000000110000000,
+        0b000001100000000001100000,
+        0b000010000000000000010000,
+        0b000100000000000000001000,
+        0b000100000000010001001000,
+        0b001000000000101010100100,
+        0b001000000000000000000100,
+        0b001000000000000111110100,
+        0b001000000000000100010100,
+        0b001000000000000011100100,
+        0b001000000000000000000100,
+        0b000100000000000000001000,
+        0b000100000000000000001000,
+        0b000011000000000000110000,
+        0b000000111111111111000000,
+        0b000000011000000110000000,
+        0b000000111000001110000000,
+    ],
+);
+
+/// The pet fretting: brows up, mouth wobbling.
+pub const PET_WORRIED: Sprite = Sprite::new(
+    24,
+    &[
+        0b000000000111111000000000,
+        0b000000011000000110000000,
+        0b000001100000000001100000,
+        0b000010000000001001010000,
+        0b000100000000010000101000,
+        0b000100000000000000001000,
+        0b001000000000011001100100,
+        0b001000000000011001100100,
+        0b001000000000000000000100,
+        0b001000000000000101010100,
+        0b001000000000001010100100,
+        0b001000000000000000000100,
+        0b000100000000000000001000,
+        0b000100000000000000001000,
+        0b000011000000000000110000,
+        0b000000111111111111000000,
+        0b000000011000000110000000,
+        0b000000111000001110000000,
+    ],
+);
+
+pub const MESS: Sprite = Sprite::new(
+    8,
+    &[
+        0b00010000, 0b00001000, 0b00011000, 0b00111100, 0b01111110, 0b11111111,
+    ],
+);
+
+pub const HEART: Sprite = Sprite::new(
+    7,
+    &[
+        0b0110110, 0b1111111, 0b1111111, 0b0111110, 0b0011100, 0b0001000,
+    ],
+);
+
+pub const SWEAT_DROP: Sprite =
+    Sprite::new(5, &[0b00100, 0b00100, 0b01010, 0b10001, 0b10001, 0b01110]);
+
+pub const MUSIC_NOTE: Sprite =
+    Sprite::new(5, &[0b00111, 0b00101, 0b00101, 0b00100, 0b11100, 0b11100]);
+
+/// An unhatched egg, for the first-run choice.
+pub const EGG: Sprite = Sprite::new(
+    16,
+    &[
+        0b0000011111100000,
+        0b0000110000110000,
+        0b0001100000011000,
+        0b0011000000001100,
+        0b0010000000000100,
+        0b0110000000000110,
+        0b0100000000000010,
+        0b0100000000000010,
+        0b1100000000000011,
+        0b1000000000000001,
+        0b1000000000000001,
+        0b1000000000000001,
+        0b1000000000000001,
+        0b1000000000000001,
+        0b1100000000000011,
+        0b0100000000000010,
+        0b0110000000000110,
+        0b0011000000001100,
+        0b0001110000111000,
+        0b0000011111100000,
+    ],
+);
+
+/// Drawn over [`EGG`] for the speckled one.
+pub const EGG_SPOTS: Sprite = Sprite::new(
+    16,
+    &[
+        0,
+        0,
+        0,
+        0b0000001100000000,
+        0b0000001100000000,
+        0,
+        0b0001100000110000,
+        0b0001100000110000,
+        0,
+        0b0000000110000000,
+        0b0000000110000000,
+        0,
+        0b0011000000001100,
+        0b0011000000001100,
+        0,
+        0b0000011000000000,
+        0b0000011000000000,
+        0,
+        0,
+        0,
+    ],
+);
+
+/// Drawn over [`EGG`] for the ember one: a band of flames.
+pub const EGG_FLAMES: Sprite = Sprite::new(
+    16,
+    &[
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0b0001000100010000,
+        0b0010101010101000,
+        0b0100010001000100,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    ],
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Not symmetric either way, so a flip or a transposition would show.
+    const ART: Sprite = Sprite::new(5, &[0b11000, 0b01001, 0b00111]);
+
+    #[test]
+    fn scaling_turns_each_pixel_into_a_whole_block() {
+        let origin = Point::new(3, 2);
+        for scale in 1..=4 {
+            let mut frame = Frame::new(40, 20);
+            ART.draw_scaled(&mut frame, origin, Color::Red, scale);
+            for y in 0..20 {
+                for x in 0..40 {
+                    let (sx, sy) = (x - origin.x, y - origin.y);
+                    let inside = (0..(5 * scale) as i32).contains(&sx)
+                        && (0..(3 * scale) as i32).contains(&sy);
+                    let expected = match inside && ART.is_set(sx as u32 / scale, sy as u32 / scale)
+                    {
+                        true => Color::Red,
+                        false => Color::White,
+                    };
+                    assert_eq!(frame.pixel(x, y), expected, "{scale}× at {x},{y}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scale_one_is_drawing_as_is() {
+        let (mut scaled, mut plain) = (Frame::new(8, 4), Frame::new(8, 4));
+        ART.draw_scaled(&mut scaled, Point::new(1, 1), Color::Black, 1);
+        ART.draw(&mut plain, Point::new(1, 1), Color::Black, Flip::None);
+        assert_eq!(
+            crate::screens::ascii(&scaled),
+            crate::screens::ascii(&plain)
+        );
+    }
+
+    #[test]
+    fn scaled_drawing_leaves_what_is_under_unset_pixels() {
+        let mut frame = Frame::new(10, 6);
+        // Under the first row's blank third pixel.
+        frame.set_pixel(4, 0, Color::Red);
+        ART.draw_scaled(&mut frame, Point::zero(), Color::Black, 2);
+        assert_eq!(
+            crate::screens::ascii(&frame),
+            "\
+####r.....
+####......
+..##....##
+..##....##
+....######
+....######
+"
+        );
+    }
+
+    #[test]
+    fn fitting_centres_and_crops() {
+        let fitted = ART.fitted(Size::new(7, 4));
+        let mut frame = Frame::new(7, 4);
+        fitted.draw(&mut frame, Point::zero(), Color::Black, Flip::None);
+        assert_eq!(
+            crate::screens::ascii(&frame),
+            ".......\n.##....\n..#..#.\n...###.\n"
+        );
+        let cropped = ART.fitted(Size::new(3, 2));
+        assert_eq!(cropped.size(), Size::new(3, 2));
+        assert!(cropped.is_set(0, 0) && !cropped.is_set(2, 0) && cropped.is_set(2, 1));
+    }
+}
@@ -0,0 +1,67 @@
+//! Machine-readable snapshot of the daemon, rewritten after every cycle
+//! for scripts and monitoring to pick up.
+
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::build_info::BuildInfo;
+use crate::collector::CollectorStats;
+use crate::display::{LinkStatus, PanelInfo};
+use crate::hooks::HookStats;
+use crate::lowmem::Quality;
+use crate::persist;
+use crate::pet::Pet;
+use crate::scheduler::{Deadline, Mode, Power};
+use crate::watchdog::WatchdogStatus;
+
+#[derive(Serialize)]
+pub struct Status<'a> {
+    pub updated: DateTime<Utc>,
+    pub build: BuildInfo,
+    pub panel: PanelInfo,
+    pub link: LinkStatus,
+    pub screen: &'a str,
+    pub refreshes: u64,
+    /// Extra refreshes spent clearing the panel, on top of `refreshes`.
+    pub pre_clears: u64,
+    /// Refreshes held back for coming too soon after the last one.
+    pub coalesced: u64,
+    pub last_refresh_ms: Option<u128>,
+    pub available_update: Option<&'a str>,
+    pub cadence: CadenceStatus,
+    pub watchdog: WatchdogStatus,
+    pub collector: CollectorStats,
+    pub hooks: HookStats,
+    /// How much of the rendering there's memory for, see [`crate::lowmem`].
+    pub quality: Quality,
+    pub pet: &'a Pet,
+}
+
+#[derive(Serialize)]
+pub struct CadenceStatus {
+    pub mode: Mode,
+    pub interval_secs: u64,
+    pub power: Power,
+    /// Whether refreshes are held back to save the battery.
+    pub batching: bool,
+}
+
+impl CadenceStatus {
+    pub fn new(deadline: Deadline, power: Power, batching: bool) -> Self {
+        CadenceStatus {
+            mode: deadline.mode,
+            interval_secs: deadline.interval.as_secs(),
+            power,
+            batching,
+        }
+    }
+}
+
+impl Status<'_> {
+    pub fn write(&self, path: &Path) -> Result<()> {
+        persist::write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+}
@@ -0,0 +1,65 @@
+//! Running outside programs, like the camera or a hook, without letting a
+//! hung one hold anything up for long.
+
+use std::io::{self, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often to look whether the program is done.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct Finished {
+    /// `None` if it ran out of time and was killed.
+    pub status: Option<ExitStatus>,
+    pub stdout: Vec<u8>,
+    pub stderr: String,
+}
+
+/// Runs `command` with nothing on stdin, killing it after `timeout`.
+/// Fails only if it can't be started.
+///
+/// It gets a process group of its own, so a timeout kills whatever it
+/// started too, e.g. everything in a hook's shell script, and the pipes
+/// are sure to close.
+pub fn run(command: &mut Command, timeout: Duration) -> io::Result<Finished> {
+    let mut child = command
+        .process_group(0)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Both pipes are read on the side, so a chatty program can't fill one
+    // up and stall.
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if Instant::now() < deadline => thread::sleep(POLL_INTERVAL),
+            Ok(None) | Err(_) => {
+                unsafe { libc::kill(-(child.id() as i32), libc::SIGKILL) };
+                child.wait().ok();
+                break None;
+            }
+        }
+    };
+    let stderr = stderr.join().unwrap_or_default();
+    Ok(Finished {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: String::from_utf8_lossy(&stderr).trim().to_string(),
+    })
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut out = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut out).ok();
+        }
+        out
+    })
+}
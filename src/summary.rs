@@ -0,0 +1,442 @@
+//! A look back at yesterday, shown for a few minutes each day: how the
+//! stats went hour by hour, how often the pet was fed and cleaned, the
+//! range of CPU temperatures and a line summing it all up. The hourly CPU
+//! temperatures of the last week also go on the `heatmap` screen.
+//!
+//! The day is tallied as it goes, one sample per cycle into a bucket for
+//! each local hour, and saved whenever a new hour starts. Nothing is
+//! replayed from elsewhere, so a day the panel was off simply has no
+//! tally.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::events::PetEvent;
+use crate::persist;
+use crate::pet::Stats;
+use crate::quiet::QuietHours;
+
+/// Finished days kept, for the weekly report.
+const PAST_DAYS: usize = 7;
+/// Hours a day needs samples in before it's judged on its stats.
+const PARTIAL_HOURS: usize = 6;
+/// Lowest average stat for a great day, and for a decent one.
+const GREAT_DAY: f32 = 70.0;
+const DECENT_DAY: f32 = 40.0;
+
+/// Running sums for one local hour.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+struct Hour {
+    samples: u32,
+    hunger: f32,
+    happiness: f32,
+    energy: f32,
+    /// Sum of the CPU temperatures, over the samples that had one.
+    #[serde(default)]
+    temperature: f32,
+    #[serde(default)]
+    temperatures: u32,
+}
+
+/// Everything tallied over one local day.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Day {
+    pub date: NaiveDate,
+    hours: [Hour; 24],
+    pub feedings: u32,
+    pub cleanings: u32,
+    pub min_temperature: Option<f32>,
+    pub max_temperature: Option<f32>,
+    /// Times the panel was redrawn.
+    #[serde(default)]
+    pub refreshes: u32,
+}
+
+impl Day {
+    pub fn new(date: NaiveDate) -> Self {
+        Day {
+            date,
+            hours: [Hour::default(); 24],
+            feedings: 0,
+            cleanings: 0,
+            min_temperature: None,
+            max_temperature: None,
+            refreshes: 0,
+        }
+    }
+
+    /// Hours with at least one sample in.
+    pub fn hours_seen(&self) -> usize {
+        self.hours.iter().filter(|hour| hour.samples > 0).count()
+    }
+
+    /// Each stat's average for every hour that has samples.
+    pub fn hourly(&self) -> [Option<Stats>; 24] {
+        self.hours.map(|hour| {
+            let n = hour.samples as f32;
+            (hour.samples > 0).then(|| Stats {
+                hunger: hour.hunger / n,
+                happiness: hour.happiness / n,
+                energy: hour.energy / n,
+            })
+        })
+    }
+
+    /// The average CPU temperature for every hour that has one.
+    pub fn hourly_temperature(&self) -> [Option<f32>; 24] {
+        self.hours.map(|hour| {
+            (hour.temperatures > 0).then(|| hour.temperature / hour.temperatures as f32)
+        })
+    }
+
+    /// The average of the hourly averages, so a busy hour with lots of
+    /// refreshes doesn't count for more than a quiet one.
+    pub fn average(&self) -> Option<Stats> {
+        let hours: Vec<Stats> = self.hourly().into_iter().flatten().collect();
+        let n = hours.len() as f32;
+        (!hours.is_empty()).then(|| Stats {
+            hunger: hours.iter().map(|s| s.hunger).sum::<f32>() / n,
+            happiness: hours.iter().map(|s| s.happiness).sum::<f32>() / n,
+            energy: hours.iter().map(|s| s.energy).sum::<f32>() / n,
+        })
+    }
+}
+
+/// One line on how the day went, from its tally alone; `None` is a day
+/// the panel wasn't on for at all.
+pub fn verdict(name: &str, day: Option<&Day>) -> String {
+    let Some(average) = day.and_then(Day::average) else {
+        return format!("The panel was off, no news of {name}.");
+    };
+    let day = day.expect("there's an average");
+    if day.hours_seen() < PARTIAL_HOURS {
+        return format!("Only saw a bit of {name}'s day.");
+    }
+    let lowest = average.hunger.min(average.happiness).min(average.energy);
+    if day.feedings == 0 && average.hunger < DECENT_DAY {
+        format!("Nobody fed {name} all day.")
+    } else if lowest >= GREAT_DAY {
+        format!("{name} had a great day!")
+    } else if lowest >= DECENT_DAY {
+        format!("{name} had a decent day.")
+    } else {
+        format!("{name} had a rough day.")
+    }
+}
+
+/// Today's tally so far and yesterday's, as saved.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tally {
+    pub today: Day,
+    /// `None` when the panel was off all of yesterday.
+    pub yesterday: Option<Day>,
+    /// The last few finished days with a tally, oldest first.
+    #[serde(default)]
+    pub past: Vec<Day>,
+}
+
+impl Tally {
+    pub fn new(today: NaiveDate) -> Tally {
+        Tally {
+            today: Day::new(today),
+            yesterday: None,
+            past: Vec::new(),
+        }
+    }
+
+    /// The tally saved at `path`, or a fresh one for `today`. An unreadable
+    /// file starts over rather than stopping the daemon.
+    pub fn load(path: &Path, today: NaiveDate) -> Tally {
+        let fresh = Tally::new(today);
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return fresh,
+            Err(err) => {
+                log::warn!("couldn't read {}: {err}", path.display());
+                return fresh;
+            }
+        };
+        match serde_json::from_str(&text) {
+            Ok(tally) => tally,
+            Err(err) => {
+                log::warn!("ignoring unreadable {}: {err}", path.display());
+                fresh
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        persist::write_atomic(path, serde_json::to_string(self)?.as_bytes())
+    }
+
+    /// Moves on to `date` if it's a new day. Today's tally becomes
+    /// yesterday's, unless days were skipped.
+    fn roll(&mut self, date: NaiveDate) {
+        if date <= self.today.date {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.today, Day::new(date));
+        self.past.push(previous.clone());
+        if self.past.len() > PAST_DAYS {
+            self.past.remove(0);
+        }
+        self.yesterday = (previous.date.succ_opt() == Some(date)).then_some(previous);
+    }
+
+    /// Adds a sample of the pet at `now`, returning whether it's the first
+    /// of its hour, i.e. the tally is due to be saved.
+    pub fn sample(
+        &mut self,
+        now: DateTime<FixedOffset>,
+        stats: Stats,
+        temperature: Option<f32>,
+    ) -> bool {
+        self.roll(now.date_naive());
+        let today = &mut self.today;
+        let hour = &mut today.hours[now.hour() as usize];
+        if let Some(t) = temperature {
+            today.min_temperature = Some(today.min_temperature.map_or(t, |min| min.min(t)));
+            today.max_temperature = Some(today.max_temperature.map_or(t, |max| max.max(t)));
+            hour.temperature += t;
+            hour.temperatures += 1;
+        }
+        hour.samples += 1;
+        hour.hunger += stats.hunger;
+        hour.happiness += stats.happiness;
+        hour.energy += stats.energy;
+        hour.samples == 1
+    }
+
+    pub fn count_refresh(&mut self) {
+        self.today.refreshes += 1;
+    }
+
+    /// The hourly CPU temperatures of the seven days up to `today`, oldest
+    /// first. A day with no tally has none.
+    pub fn temperatures(&self, today: NaiveDate) -> [(NaiveDate, [Option<f32>; 24]); 7] {
+        std::array::from_fn(|row| {
+            let date = today - Duration::days(6 - row as i64);
+            let day = self
+                .past
+                .iter()
+                .chain(std::iter::once(&self.today))
+                .find(|day| day.date == date);
+            (date, day.map_or([None; 24], Day::hourly_temperature))
+        })
+    }
+
+    /// The days tallied in the ISO week `week`, today included, oldest
+    /// first.
+    #[cfg(feature = "webhook")]
+    pub fn week(&self, week: chrono::IsoWeek) -> Vec<&Day> {
+        use chrono::Datelike;
+        self.past
+            .iter()
+            .chain(std::iter::once(&self.today))
+            .filter(|day| day.date.iso_week() == week)
+            .collect()
+    }
+
+    /// Counts the feedings and cleanings among the pet's events.
+    pub fn count(&mut self, event: PetEvent) {
+        match event {
+            PetEvent::Fed => self.today.feedings += 1,
+            PetEvent::Cleaned => self.today.cleanings += 1,
+            _ => {}
+        }
+    }
+}
+
+/// When the summary goes up, and for how long.
+#[derive(Clone, Copy, Debug)]
+pub struct Window {
+    pub at: NaiveTime,
+    pub length: Duration,
+}
+
+impl Window {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        QuietHours {
+            start: self.at,
+            end: self.at + self.length,
+        }
+        .contains(time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(level: f32) -> Stats {
+        Stats {
+            hunger: level,
+            happiness: level,
+            energy: level,
+        }
+    }
+
+    fn at(text: &str) -> DateTime<FixedOffset> {
+        text.parse().unwrap()
+    }
+
+    /// 1 May with a sample at `level` in each of the first `hours` hours,
+    /// and `feedings` feedings.
+    fn day(hours: u32, level: f32, feedings: u32) -> Day {
+        let mut tally = Tally::new(at("2024-05-01T00:00:00+03:00").date_naive());
+        for hour in 0..hours {
+            let now = at("2024-05-01T00:30:00+03:00") + Duration::hours(hour as i64);
+            tally.sample(now, stats(level), None);
+        }
+        tally.today.feedings = feedings;
+        tally.today
+    }
+
+    #[test]
+    fn verdicts() {
+        for (day, verdict_) in [
+            (None, "The panel was off, no news of Mara."),
+            (Some(day(0, 90.0, 3)), "The panel was off, no news of Mara."),
+            (Some(day(5, 90.0, 3)), "Only saw a bit of Mara's day."),
+            (Some(day(6, 90.0, 3)), "Mara had a great day!"),
+            (Some(day(24, 70.0, 3)), "Mara had a great day!"),
+            (Some(day(24, 69.0, 3)), "Mara had a decent day."),
+            (Some(day(24, 40.0, 0)), "Mara had a decent day."),
+            (Some(day(24, 39.0, 3)), "Mara had a rough day."),
+            (Some(day(24, 39.0, 0)), "Nobody fed Mara all day."),
+        ] {
+            assert_eq!(verdict("Mara", day.as_ref()), verdict_, "{day:?}");
+        }
+    }
+
+    #[test]
+    fn the_lowest_stat_decides() {
+        let mut tally = Tally::new(at("2024-05-01T00:00:00+03:00").date_naive());
+        for hour in 0..24 {
+            let now = at("2024-05-01T00:30:00+03:00") + Duration::hours(hour);
+            let mut sample = stats(95.0);
+            sample.energy = 30.0;
+            tally.sample(now, sample, None);
+        }
+        assert_eq!(verdict("Mara", Some(&tally.today)), "Mara had a rough day.");
+    }
+
+    #[test]
+    fn busy_hours_count_the_same_as_quiet_ones() {
+        let mut tally = Tally::new(at("2024-05-01T00:00:00+03:00").date_naive());
+        for minute in 0..59 {
+            tally.sample(
+                at("2024-05-01T10:00:00+03:00") + Duration::minutes(minute),
+                stats(100.0),
+                None,
+            );
+        }
+        tally.sample(at("2024-05-01T11:00:00+03:00"), stats(0.0), None);
+        assert_eq!(tally.today.average(), Some(stats(50.0)));
+        assert_eq!(tally.today.hours_seen(), 2);
+    }
+
+    #[test]
+    fn samples_keep_running_sums() {
+        let mut tally = Tally::new(at("2024-05-01T00:00:00+03:00").date_naive());
+        let first = tally.sample(at("2024-05-01T10:05:00+03:00"), stats(60.0), Some(48.0));
+        let second = tally.sample(at("2024-05-01T10:35:00+03:00"), stats(80.0), None);
+        let next_hour = tally.sample(at("2024-05-01T11:00:00+03:00"), stats(80.0), Some(52.5));
+        assert_eq!((first, second, next_hour), (true, false, true));
+        let today = &tally.today;
+        assert_eq!(today.hourly()[10], Some(stats(70.0)));
+        assert_eq!(today.hourly()[9], None);
+        assert_eq!(today.hourly_temperature()[10], Some(48.0));
+        assert_eq!(
+            (today.min_temperature, today.max_temperature),
+            (Some(48.0), Some(52.5))
+        );
+        tally.count(PetEvent::Fed);
+        tally.count(PetEvent::Cleaned);
+        tally.count(PetEvent::Played);
+        assert_eq!((tally.today.feedings, tally.today.cleanings), (1, 1));
+    }
+
+    #[test]
+    fn the_first_day_has_no_yesterday() {
+        let mut tally = Tally::new(at("2024-05-01T00:00:00+03:00").date_naive());
+        tally.sample(at("2024-05-01T21:00:00+03:00"), stats(90.0), None);
+        assert!(tally.yesterday.is_none());
+        // Into the next day, what there was of the first is yesterday.
+        tally.sample(at("2024-05-02T08:00:00+03:00"), stats(90.0), None);
+        let yesterday = tally.yesterday.as_ref().expect("yesterday tallied");
+        assert_eq!(yesterday.hours_seen(), 1);
+        assert_eq!(
+            verdict("Mara", Some(yesterday)),
+            "Only saw a bit of Mara's day."
+        );
+    }
+
+    #[test]
+    fn a_day_with_the_panel_off_leaves_no_yesterday() {
+        let mut tally = Tally::new(at("2024-05-01T00:00:00+03:00").date_naive());
+        tally.sample(at("2024-05-01T21:00:00+03:00"), stats(90.0), None);
+        tally.sample(at("2024-05-03T08:00:00+03:00"), stats(90.0), None);
+        assert!(tally.yesterday.is_none());
+        assert_eq!(
+            verdict("Mara", tally.yesterday.as_ref()),
+            "The panel was off, no news of Mara."
+        );
+        // It's still in the week, and the missing day has no temperatures.
+        assert_eq!(tally.past.len(), 1);
+        let week = tally.temperatures(tally.today.date);
+        assert_eq!(week[5].0, at("2024-05-02T00:00:00+03:00").date_naive());
+        assert_eq!(week[5].1, [None; 24]);
+    }
+
+    #[test]
+    fn only_a_week_is_kept() {
+        let mut tally = Tally::new(at("2024-05-01T00:00:00+03:00").date_naive());
+        for day in 0..10 {
+            tally.sample(
+                at("2024-05-01T12:00:00+03:00") + Duration::days(day),
+                stats(90.0),
+                None,
+            );
+        }
+        assert_eq!(tally.past.len(), PAST_DAYS);
+        assert_eq!(
+            tally.past[0].date,
+            at("2024-05-03T00:00:00+03:00").date_naive()
+        );
+    }
+
+    #[test]
+    fn the_tally_survives_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tally.json");
+        let today = at("2024-05-01T00:00:00+03:00").date_naive();
+        let mut tally = Tally::new(today);
+        tally.sample(at("2024-05-01T10:00:00+03:00"), stats(70.0), Some(50.0));
+        tally.count(PetEvent::Fed);
+        tally.save(&path).unwrap();
+        let loaded = Tally::load(&path, today);
+        assert_eq!(loaded.today.hourly(), tally.today.hourly());
+        assert_eq!(loaded.today.feedings, 1);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert_eq!(Tally::load(&path, today).today.hours_seen(), 0);
+    }
+
+    #[test]
+    fn the_window_can_run_past_midnight() {
+        let time = |text: &str| NaiveTime::parse_from_str(text, "%H:%M").unwrap();
+        let window = Window {
+            at: time("23:58"),
+            length: Duration::minutes(5),
+        };
+        assert!(window.contains(time("23:59")));
+        assert!(window.contains(time("00:02")));
+        assert!(!window.contains(time("00:03")));
+        assert!(!window.contains(time("23:57")));
+    }
+}
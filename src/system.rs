@@ -0,0 +1,148 @@
+//! Readings from the host system.
+
+use std::ffi::CString;
+use std::fs;
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, UdpSocket};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::subprocess;
+
+/// SoC temperature in degrees Celsius, if the kernel exposes it.
+pub fn cpu_temperature() -> Option<f32> {
+    let raw = fs::read_to_string("/sys/class/thermal/thermal_zone0/temp").ok()?;
+    let millidegrees: f32 = raw.trim().parse().ok()?;
+    Some(millidegrees / 1000.0)
+}
+
+/// Memory available to new allocations, in kilobytes, from `/proc/meminfo`.
+pub fn available_memory() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemAvailable:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Time since boot, from `/proc/uptime`.
+pub fn uptime() -> Option<Duration> {
+    let raw = fs::read_to_string("/proc/uptime").ok()?;
+    let seconds: f64 = raw.split_whitespace().next()?.parse().ok()?;
+    Some(Duration::from_secs_f64(seconds))
+}
+
+/// The address used for outgoing traffic. Connecting a UDP socket only
+/// picks a route; nothing is sent.
+pub fn local_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("192.0.2.1:9").ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+/// Fraction of the filesystem holding `path` that's in use, from 0 to 1.
+pub fn disk_usage(path: &Path) -> Option<f32> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stats = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stats` is only read after the
+    // call reports success, which means it was filled in.
+    let stats = unsafe {
+        if libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stats.assume_init()
+    };
+    if stats.f_blocks == 0 {
+        return None;
+    }
+    Some(1.0 - stats.f_bavail as f32 / stats.f_blocks as f32)
+}
+
+/// Whether the host is running off a battery right now, per the kernel's
+/// power supplies: a battery discharging with no mains or USB supply
+/// online. `None` without a battery to ask.
+pub fn on_battery() -> Option<bool> {
+    on_battery_in(Path::new("/sys/class/power_supply"))
+}
+
+/// Likewise, for the power supplies listed in `dir`.
+fn on_battery_in(dir: &Path) -> Option<bool> {
+    let (mut battery, mut discharging, mut plugged_in) = (false, false, false);
+    for entry in fs::read_dir(dir).ok()?.flatten() {
+        let read = |name: &str| {
+            fs::read_to_string(entry.path().join(name))
+                .map(|text| text.trim().to_string())
+                .unwrap_or_default()
+        };
+        match read("type").as_str() {
+            "Battery" => {
+                battery = true;
+                discharging |= read("status") == "Discharging";
+            }
+            "Mains" | "USB" => plugged_in |= read("online") == "1",
+            _ => {}
+        }
+    }
+    battery.then_some(discharging && !plugged_in)
+}
+
+/// Whether systemd is taking the system down, as opposed to just the
+/// service being stopped or restarted. False without systemd.
+pub fn shutting_down() -> bool {
+    let mut command = Command::new("systemctl");
+    command.arg("is-system-running");
+    match subprocess::run(&mut command, Duration::from_secs(2)) {
+        Ok(finished) => String::from_utf8_lossy(&finished.stdout).trim() == "stopping",
+        Err(err) => {
+            log::debug!("couldn't ask systemd whether it's shutting down: {err}");
+            false
+        }
+    }
+}
+
+/// Whether the Pi's firmware reports the supply voltage as too low right
+/// now. `None` on other hardware.
+pub fn undervoltage() -> Option<bool> {
+    let raw = fs::read_to_string("/sys/devices/platform/soc/soc:firmware/get_throttled").ok()?;
+    let flags = u32::from_str_radix(raw.trim().trim_start_matches("0x"), 16).ok()?;
+    Some(flags & 1 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A power supply called `name` in `dir`, with its sysfs attributes.
+    fn supply(dir: &Path, name: &str, attributes: &[(&str, &str)]) {
+        let path = dir.join(name);
+        fs::create_dir_all(&path).unwrap();
+        for (attribute, value) in attributes {
+            fs::write(path.join(attribute), format!("{value}\n")).unwrap();
+        }
+    }
+
+    #[test]
+    fn on_battery_when_discharging_and_unplugged() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        assert_eq!(on_battery_in(&dir.join("missing")), None);
+        // No battery to ask.
+        supply(dir, "AC", &[("type", "Mains"), ("online", "0")]);
+        assert_eq!(on_battery_in(dir), None);
+
+        supply(
+            dir,
+            "BAT0",
+            &[("type", "Battery"), ("status", "Discharging")],
+        );
+        assert_eq!(on_battery_in(dir), Some(true));
+        supply(dir, "usb", &[("type", "USB"), ("online", "1")]);
+        assert_eq!(on_battery_in(dir), Some(false));
+        supply(dir, "usb", &[("online", "0")]);
+        assert_eq!(on_battery_in(dir), Some(true));
+        supply(dir, "BAT0", &[("status", "Charging")]);
+        assert_eq!(on_battery_in(dir), Some(false));
+        supply(dir, "BAT0", &[("status", "Full")]);
+        assert_eq!(on_battery_in(dir), Some(false));
+    }
+}
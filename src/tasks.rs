@@ -0,0 +1,128 @@
+//! The control plane as tasks on a single-threaded tokio runtime: the
+//! control socket, button gestures and signal handling. Rendering and the
+//! display stay on their own thread, talking to these through the command
+//! channel.
+
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::{Context as _, Result};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+use crate::buttons::{self, Press, TapDetector};
+use crate::channel::Outbox;
+use crate::control::{self, Request};
+use crate::events::Command;
+
+/// Starts every task, returning where other input sources can send their
+/// presses. Has to be called from inside the runtime. Commands naming a
+/// screen have to name one of `screens`.
+pub fn spawn(
+    socket: &Path,
+    tx: UnboundedSender<Command>,
+    screens: Vec<String>,
+) -> Result<UnboundedSender<Press>> {
+    let (press_tx, press_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(watch_buttons(press_rx, tx.clone()));
+    listen(socket, tx.clone(), press_tx.clone(), screens)?;
+    watch_signal(SignalKind::terminate(), "SIGTERM", tx.clone())?;
+    watch_signal(SignalKind::interrupt(), "SIGINT", tx)?;
+    Ok(press_tx)
+}
+
+fn listen(
+    path: &Path,
+    tx: UnboundedSender<Command>,
+    presses: UnboundedSender<Press>,
+    screens: Vec<String>,
+) -> Result<()> {
+    control::remove_stale(path)?;
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("binding {}", path.display()))?;
+    log::info!("listening for commands on {}", path.display());
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let (tx, presses) = (tx.clone(), presses.clone());
+                    let screens = screens.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = serve(stream, &tx, &presses, &screens).await {
+                            log::warn!("control connection failed: {err}");
+                        }
+                    });
+                }
+                Err(err) => log::warn!("control accept failed: {err}"),
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn serve(
+    stream: UnixStream,
+    tx: &UnboundedSender<Command>,
+    presses: &UnboundedSender<Press>,
+    screens: &[String],
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match Request::parse(&line, screens) {
+            Ok(request) => {
+                let sent = match request {
+                    Request::Press(press) => presses.send(press).is_ok(),
+                    Request::Command(command) => tx.send(command).is_ok(),
+                };
+                if !sent {
+                    return Ok(());
+                }
+                "ok".to_string()
+            }
+            Err(reply) => reply,
+        };
+        writer.write_all(format!("{reply}\n").as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// The async twin of [`buttons::spawn`].
+async fn watch_buttons(mut presses: UnboundedReceiver<Press>, tx: UnboundedSender<Command>) {
+    let mut detector = TapDetector::default();
+    loop {
+        let events = match detector.deadline() {
+            Some(deadline) => {
+                match tokio::time::timeout_at(deadline.into(), presses.recv()).await {
+                    Ok(Some(press)) => detector.press(press, Instant::now()),
+                    Ok(None) => return,
+                    Err(_) => detector.expire(Instant::now()),
+                }
+            }
+            None => match presses.recv().await {
+                Some(press) => detector.press(press, Instant::now()),
+                None => return,
+            },
+        };
+        if !buttons::forward(events, &tx) {
+            return;
+        }
+    }
+}
+
+/// Turns the first `kind` signal into a [`Command::Shutdown`].
+fn watch_signal(kind: SignalKind, name: &'static str, tx: UnboundedSender<Command>) -> Result<()> {
+    let mut signals = signal(kind).with_context(|| format!("listening for {name}"))?;
+    tokio::spawn(async move {
+        if signals.recv().await.is_some() {
+            log::info!("got {name}, shutting down");
+            Outbox::send(&tx, Command::Shutdown);
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,180 @@
+//! Display text with `{variable}` placeholders, e.g.
+//! `"Buna {name}! {temp_c}°C, up {uptime}"`.
+//!
+//! Templates are parsed when the config is loaded, so a stray brace stops
+//! the daemon at startup rather than garbling the panel later. `{{` and `}}`
+//! stand for literal braces.
+
+use serde::Deserialize;
+
+/// Every variable a template may use. See `screens::Context::var` for what
+/// each one expands to.
+pub const VARIABLES: &[&str] = &[
+    "name",
+    "mood",
+    "day",
+    "time",
+    "date",
+    "temp_c",
+    "uptime",
+    "ip",
+    "hunger",
+    "happiness",
+    "energy",
+    "training",
+];
+
+/// The variables that expand to a plain number, so can drive a bar.
+pub const NUMBERS: &[&str] = &["day", "temp_c", "hunger", "happiness", "energy", "training"];
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Var(String),
+}
+
+impl TryFrom<String> for Template {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        Template::parse(&text)
+    }
+}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => {
+                                return Err(format!("unclosed {{ in template {text:?}"))
+                            }
+                            Some(c) => name.push(c),
+                        }
+                    }
+                    let name = name.trim();
+                    if name.is_empty() {
+                        return Err(format!("empty {{}} in template {text:?}"));
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Var(name.into()));
+                }
+                '}' => return Err(format!("unmatched }} in template {text:?} (use }}}})")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template { parts })
+    }
+
+    /// Variables used that aren't in [`VARIABLES`]. They render literally.
+    pub fn unknown_variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Var(name) if !VARIABLES.contains(&name.as_str()) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Fills in the placeholders. `lookup` returns `None` for unknown
+    /// variables, which are kept as `{name}`.
+    pub fn render(&self, lookup: impl Fn(&str) -> Option<String>) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => out.push_str(text),
+                Part::Var(name) => match lookup(name) {
+                    Some(value) => out.push_str(&value),
+                    None => {
+                        out.push('{');
+                        out.push_str(name);
+                        out.push('}');
+                    }
+                },
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(text: &str) -> String {
+        Template::parse(text).unwrap().render(|name| match name {
+            "name" => Some("Mara".into()),
+            "temp_c" => Some("48".into()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn substitutes_variables() {
+        assert_eq!(render("Buna {name}! {temp_c}°C"), "Buna Mara! 48°C");
+        assert_eq!(render("{ name }"), "Mara");
+        assert_eq!(render("{name}{name}"), "MaraMara");
+        assert_eq!(render("no variables"), "no variables");
+        assert_eq!(render(""), "");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(render("{{name}}"), "{name}");
+        assert_eq!(render("{{{name}}}"), "{Mara}");
+        assert_eq!(render("}}{{"), "}{");
+        let template = Template::parse("{{name}}").unwrap();
+        assert_eq!(template.unknown_variables().count(), 0);
+    }
+
+    #[test]
+    fn unknown_variables_render_as_written() {
+        assert_eq!(render("{nmae} is {mood}"), "{nmae} is {mood}");
+        let template = Template::parse("{nmae} is {mood} at {tmep}").unwrap();
+        let unknown: Vec<_> = template.unknown_variables().collect();
+        assert_eq!(unknown, ["nmae", "tmep"]);
+    }
+
+    #[test]
+    fn stray_braces_are_errors() {
+        for text in ["Buna {name", "{", "{}", "{ }", "a } b", "{na{me}}"] {
+            assert!(Template::parse(text).is_err(), "{text:?} parsed");
+        }
+    }
+
+    #[test]
+    fn bad_templates_stop_the_config_loading() {
+        #[derive(Debug, Deserialize)]
+        struct Text {
+            #[allow(dead_code)]
+            header: Template,
+        }
+        assert!(toml::from_str::<Text>(r#"header = "Buna {name}!""#).is_ok());
+        let err = toml::from_str::<Text>(r#"header = "Buna {name!""#).unwrap_err();
+        assert!(err.to_string().contains("unclosed {"), "{err}");
+    }
+}
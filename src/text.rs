@@ -0,0 +1,707 @@
+//! Text rendering helpers on top of embedded-graphics' mono fonts.
+//!
+//! The fonts used are ISO 8859-15, which has no ă, ș or ț. Every string
+//! drawn here is shaped a letter at a time: a letter the font lacks comes
+//! from the same size of ISO 8859-16, which covers Romanian and most of
+//! Central Europe, and failing that is written the nearest way the font
+//! can, per [`TRANSLITERATIONS`].
+
+use std::borrow::Cow;
+use std::ptr;
+
+use embedded_graphics::mono_font::{iso_8859_16, MonoFont, MonoTextStyle};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::{Baseline, Text};
+
+use crate::display::Color;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VAlign {
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// Where to put something inside a rectangle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Align {
+    pub h: HAlign,
+    pub v: VAlign,
+}
+
+impl Align {
+    pub const CENTER: Align = Align::new(HAlign::Center, VAlign::Middle);
+    pub const LEFT: Align = Align::new(HAlign::Left, VAlign::Middle);
+    pub const RIGHT: Align = Align::new(HAlign::Right, VAlign::Middle);
+
+    pub const fn new(h: HAlign, v: VAlign) -> Self {
+        Align { h, v }
+    }
+
+    /// Top-left corner for a box of `size` aligned inside `rect`. Boxes
+    /// larger than `rect` overhang it evenly when centred.
+    pub fn position(self, size: Size, rect: Rectangle) -> Point {
+        let spare_w = rect.size.width as i32 - size.width as i32;
+        let spare_h = rect.size.height as i32 - size.height as i32;
+        let x = match self.h {
+            HAlign::Left => 0,
+            HAlign::Center => spare_w / 2,
+            HAlign::Right => spare_w,
+        };
+        let y = match self.v {
+            VAlign::Top => 0,
+            VAlign::Middle => spare_h / 2,
+            VAlign::Bottom => spare_h,
+        };
+        rect.top_left + Point::new(x, y)
+    }
+}
+
+/// Result of fitting a string into a rectangle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Placement<'a> {
+    /// The part of the string that fits.
+    pub text: &'a str,
+    /// Characters dropped from the end because they didn't fit.
+    pub truncated: usize,
+    /// Pen position on the font's alphabetic baseline, as expected by
+    /// [`Text::new`].
+    pub origin: Point,
+    /// Pixels actually covered by the character cells, for stacking further
+    /// elements below or beside it.
+    pub bounds: Rectangle,
+}
+
+/// Works out where `text` goes when drawn in `font` inside `rect`.
+///
+/// Characters that would spill past the right edge are dropped rather than
+/// clipped mid-glyph.
+pub fn place<'a>(text: &'a str, font: &MonoFont, rect: Rectangle, align: Align) -> Placement<'a> {
+    let advance = font.character_size.width + font.character_spacing;
+    let total = text.chars().count();
+    let fits = if rect.size.width < font.character_size.width {
+        0
+    } else {
+        ((rect.size.width - font.character_size.width) / advance + 1) as usize
+    };
+    let kept = total.min(fits);
+    let visible = match text.char_indices().nth(kept) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    };
+
+    let size = native_size(font, visible);
+    let top_left = align.position(Size::new(size.width, font.character_size.height), rect);
+    Placement {
+        text: visible,
+        truncated: total - kept,
+        origin: top_left + Point::new(0, font.baseline as i32),
+        bounds: Rectangle::new(top_left, size),
+    }
+}
+
+/// Places and draws `text`, returning the area it covers.
+pub fn draw<D>(
+    target: &mut D,
+    text: &str,
+    font: &MonoFont,
+    color: Color,
+    rect: Rectangle,
+    align: Align,
+) -> Rectangle
+where
+    D: DrawTarget<Color = Color>,
+{
+    let placement = place(text, font, rect, align);
+    draw_shaped(
+        target,
+        placement.text,
+        font,
+        placement.origin,
+        Baseline::Alphabetic,
+        color,
+    );
+    placement.bounds
+}
+
+/// Stand-ins for letters no font has, tried in order: the form used in
+/// Romanian first, then the bare letter.
+pub const TRANSLITERATIONS: &[(char, &[char])] = &[
+    ('ş', &['ș', 's']),
+    ('Ş', &['Ș', 'S']),
+    ('ţ', &['ț', 't']),
+    ('Ţ', &['Ț', 'T']),
+    ('ș', &['s']),
+    ('Ș', &['S']),
+    ('ț', &['t']),
+    ('Ț', &['T']),
+    ('ă', &['a']),
+    ('Ă', &['A']),
+    ('â', &['a']),
+    ('Â', &['A']),
+    ('î', &['i']),
+    ('Î', &['I']),
+];
+
+/// The font with letters `font` lacks, at the same size.
+fn supplement(font: &MonoFont) -> Option<&'static MonoFont<'static>> {
+    match (font.character_size.width, font.character_size.height) {
+        (6, 10) => Some(&iso_8859_16::FONT_6X10),
+        (10, 20) => Some(&iso_8859_16::FONT_10X20),
+        _ => None,
+    }
+}
+
+fn has_glyph(font: &MonoFont, c: char) -> bool {
+    // Missing letters map to the font's replacement glyph, as does any
+    // character no font has.
+    c == '?' || font.glyph_mapping.index(c) != font.glyph_mapping.index(char::MAX)
+}
+
+/// Which font to draw `c` from, and the letter that font draws for it.
+fn shape<'a>(
+    font: &'a MonoFont<'a>,
+    extra: Option<&'a MonoFont<'a>>,
+    c: char,
+) -> (&'a MonoFont<'a>, char) {
+    let stand_ins = TRANSLITERATIONS
+        .iter()
+        .find(|(from, _)| *from == c)
+        .map_or(&[][..], |(_, to)| to);
+    for &c in std::iter::once(&c).chain(stand_ins) {
+        if has_glyph(font, c) {
+            return (font, c);
+        }
+        if let Some(extra) = extra.filter(|extra| has_glyph(extra, c)) {
+            return (extra, c);
+        }
+    }
+    (font, c)
+}
+
+/// Draws `text` at `position` in `font`, each letter from whichever font
+/// has it. Both fonts share a cell size, so letters stay on the grid
+/// [`place`] laid out.
+fn draw_shaped<D, C>(
+    target: &mut D,
+    text: &str,
+    font: &MonoFont,
+    position: Point,
+    baseline: Baseline,
+    color: C,
+) where
+    D: DrawTarget<Color = C>,
+    C: PixelColor,
+{
+    let advance = (font.character_size.width + font.character_spacing) as i32;
+    let extra = supplement(font);
+    let mut run = String::new();
+    let mut run_font = font;
+    let mut run_start = 0;
+    let mut flush = |run: &mut String, run_font: &MonoFont, start: usize| {
+        if run.is_empty() {
+            return;
+        }
+        let at = position + Point::new(start as i32 * advance, 0);
+        Text::with_baseline(run, at, MonoTextStyle::new(run_font, color), baseline)
+            .draw(target)
+            .ok();
+        run.clear();
+    };
+    for (index, c) in text.chars().enumerate() {
+        let (glyph_font, c) = shape(font, extra, c);
+        if !ptr::eq(glyph_font, run_font) {
+            flush(&mut run, run_font, run_start);
+            run_font = glyph_font;
+            run_start = index;
+        }
+        run.push(c);
+    }
+    flush(&mut run, run_font, run_start);
+}
+
+/// Largest supersampling buffer we're willing to allocate, in bytes. A full
+/// width line of FONT_10X20 at 2× needs well under this; the cap only
+/// matters for unexpectedly long strings on the Pi Zero.
+const SUPERSAMPLE_BUDGET: usize = 32 * 1024;
+/// Output pixels are drawn when at least this many of the four supersamples
+/// behind them are set. With any more, the 4× buffer's only extra detail,
+/// the filled-in inner corners of each step, is lost again and the result
+/// is plain doubling; with just one, no stroke can come out thinner than
+/// doubling would draw it.
+const COVERAGE_THRESHOLD: u32 = 1;
+
+/// Text drawn at twice the font's size.
+///
+/// Plain upscaling turns every diagonal into a staircase of 2×2 blocks. With
+/// supersampling on, glyphs are instead interpolated into a 4× buffer,
+/// thresholded, and box-filtered back down to 2×, which fills in the inner
+/// corner of each step while keeping straight strokes their original width.
+pub struct BigText {
+    font: &'static MonoFont<'static>,
+    color: Color,
+    supersample: bool,
+}
+
+impl BigText {
+    pub fn new(font: &'static MonoFont<'static>, color: Color, supersample: bool) -> Self {
+        BigText {
+            font,
+            color,
+            supersample,
+        }
+    }
+
+    /// Draws `text` aligned inside `rect`.
+    pub fn draw<D>(&self, target: &mut D, text: &str, rect: Rectangle, align: Align)
+    where
+        D: DrawTarget<Color = Color>,
+    {
+        let native = native_size(self.font, text);
+        if native.width == 0 {
+            return;
+        }
+        let origin = align.position(native * 2, rect);
+
+        let mut glyphs = Mask::new(native);
+        draw_shaped(
+            &mut glyphs,
+            text,
+            self.font,
+            Point::zero(),
+            Baseline::Top,
+            BinaryColor::On,
+        );
+
+        let scaled = if self.supersample && Mask::bytes_for(native * 4) <= SUPERSAMPLE_BUDGET {
+            glyphs.supersampled()
+        } else {
+            glyphs.doubled()
+        };
+        let color = self.color;
+        let pixels = scaled.set_points().map(move |p| Pixel(origin + p, color));
+        target.draw_iter(pixels).ok();
+    }
+}
+
+/// Breaks `text` into lines of at most `width` characters, between words
+/// where it can. Words longer than a line are split.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        loop {
+            let used = line.chars().count();
+            let room = if used == 0 {
+                width
+            } else {
+                width.saturating_sub(used + 1)
+            };
+            if word.len() <= room {
+                if used > 0 {
+                    line.push(' ');
+                }
+                line.extend(&word);
+                break;
+            }
+            if used == 0 {
+                line.extend(word.drain(..width));
+            }
+            lines.push(std::mem::take(&mut line));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// `text` cut down to at most `width` characters, ending at a word with
+/// `..` after it where there's room for one, rather than mid-word.
+pub fn shorten(text: &str, width: usize) -> Cow<'_, str> {
+    if text.chars().count() <= width {
+        return Cow::Borrowed(text);
+    }
+    let room = width.saturating_sub(2);
+    let mut kept = String::new();
+    for word in text.split_whitespace() {
+        let needed = kept.chars().count() + usize::from(!kept.is_empty()) + word.chars().count();
+        if needed > room {
+            break;
+        }
+        if !kept.is_empty() {
+            kept.push(' ');
+        }
+        kept.push_str(word);
+    }
+    if kept.is_empty() {
+        // One long word: as much of it as fits.
+        kept = text.chars().take(room).collect();
+    }
+    kept.push_str("..");
+    Cow::Owned(kept)
+}
+
+fn native_size(font: &MonoFont, text: &str) -> Size {
+    let chars = text.chars().count() as u32;
+    if chars == 0 {
+        return Size::zero();
+    }
+    let width = chars * font.character_size.width + (chars - 1) * font.character_spacing;
+    Size::new(width, font.character_size.height)
+}
+
+/// Packed 1-bit scratch buffer.
+struct Mask {
+    size: Size,
+    bits: Vec<u8>,
+}
+
+impl Mask {
+    fn new(size: Size) -> Self {
+        Mask {
+            size,
+            bits: vec![0; Self::bytes_for(size)],
+        }
+    }
+
+    fn bytes_for(size: Size) -> usize {
+        (size.width as usize * size.height as usize).div_ceil(8)
+    }
+
+    fn get(&self, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || x >= self.size.width as i32 || y >= self.size.height as i32 {
+            return false;
+        }
+        let i = y as usize * self.size.width as usize + x as usize;
+        self.bits[i / 8] & (1 << (i % 8)) != 0
+    }
+
+    fn set(&mut self, x: u32, y: u32) {
+        let i = y as usize * self.size.width as usize + x as usize;
+        self.bits[i / 8] |= 1 << (i % 8);
+    }
+
+    fn set_points(&self) -> impl Iterator<Item = Point> + '_ {
+        let (w, h) = (self.size.width as i32, self.size.height as i32);
+        (0..h)
+            .flat_map(move |y| (0..w).map(move |x| Point::new(x, y)))
+            .filter(|p| self.get(p.x, p.y))
+    }
+
+    /// Nearest-neighbour 2× upscale.
+    fn doubled(&self) -> Mask {
+        let mut out = Mask::new(self.size * 2);
+        for p in self.set_points() {
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                out.set(p.x as u32 * 2 + dx, p.y as u32 * 2 + dy);
+            }
+        }
+        out
+    }
+
+    /// 2× upscale via a bilinearly interpolated 4× intermediate.
+    fn supersampled(&self) -> Mask {
+        let hi_size = self.size * 4;
+        let mut hi = Mask::new(hi_size);
+        for y in 0..hi_size.height {
+            for x in 0..hi_size.width {
+                if self.coverage(x, y) >= 0.5 {
+                    hi.set(x, y);
+                }
+            }
+        }
+
+        let mut out = Mask::new(self.size * 2);
+        for y in 0..out.size.height {
+            for x in 0..out.size.width {
+                let (hx, hy) = (x as i32 * 2, y as i32 * 2);
+                let count = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .iter()
+                    .filter(|(dx, dy)| hi.get(hx + dx, hy + dy))
+                    .count() as u32;
+                if count >= COVERAGE_THRESHOLD {
+                    out.set(x, y);
+                }
+            }
+        }
+        out
+    }
+
+    /// Bilinear interpolation of the glyph at the centre of 4× sample
+    /// `(x, y)`, treating each source pixel as a sample at its own centre.
+    fn coverage(&self, x: u32, y: u32) -> f32 {
+        let u = (x as f32 + 0.5) / 4.0 - 0.5;
+        let v = (y as f32 + 0.5) / 4.0 - 0.5;
+        let (x0, y0) = (u.floor() as i32, v.floor() as i32);
+        let (tx, ty) = (u - x0 as f32, v - y0 as f32);
+        let at = |x, y| if self.get(x, y) { 1.0 } else { 0.0 };
+        let top = at(x0, y0) * (1.0 - tx) + at(x0 + 1, y0) * tx;
+        let bottom = at(x0, y0 + 1) * (1.0 - tx) + at(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+impl OriginDimensions for Mask {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl DrawTarget for Mask {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(p, color) in pixels {
+            let inside = p.x >= 0
+                && p.y >= 0
+                && (p.x as u32) < self.size.width
+                && (p.y as u32) < self.size.height;
+            if inside && color.is_on() {
+                self.set(p.x as u32, p.y as u32);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Frame;
+    use embedded_graphics::mono_font::iso_8859_15::{FONT_10X20, FONT_6X10};
+
+    /// Checked-in supersampled renders of the clock's digits, regenerated
+    /// with `UPDATE_GOLDEN=1 cargo test`.
+    const DIGITS_GOLDEN: &str = "tests/golden/bigtext-digits.txt";
+
+    fn big(text: &str, supersample: bool) -> Frame {
+        let size = native_size(&FONT_10X20, text) * 2;
+        let mut frame = Frame::new(size.width, size.height);
+        let rect = Rectangle::new(Point::zero(), size);
+        BigText::new(&FONT_10X20, Color::Black, supersample).draw(
+            &mut frame,
+            text,
+            rect,
+            Align::CENTER,
+        );
+        frame
+    }
+
+    fn ascii(frame: &Frame) -> String {
+        let mut out = String::new();
+        for y in 0..frame.height() as i32 {
+            for x in 0..frame.width() as i32 {
+                out.push(match frame.pixel(x, y) {
+                    Color::White => '.',
+                    _ => '#',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    #[test]
+    fn digits_match_the_golden_renders() {
+        let rendered: String = ('0'..='9')
+            .map(|digit| format!("{digit}\n{}", ascii(&big(&digit.to_string(), true))))
+            .collect();
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(DIGITS_GOLDEN);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, &rendered).unwrap();
+        }
+        let golden = std::fs::read_to_string(&path).unwrap();
+        for (digit, (got, want)) in rendered.split("\n\n").zip(golden.split("\n\n")).enumerate() {
+            assert_eq!(got, want, "digit {digit} differs from {DIGITS_GOLDEN}");
+        }
+        assert_eq!(rendered, golden);
+    }
+
+    #[test]
+    fn supersampling_keeps_every_stroke() {
+        for digit in '0'..='9' {
+            let text = digit.to_string();
+            let mut native = Mask::new(native_size(&FONT_10X20, &text));
+            draw_shaped(
+                &mut native,
+                &text,
+                &FONT_10X20,
+                Point::zero(),
+                Baseline::Top,
+                BinaryColor::On,
+            );
+            let smooth = big(&text, true);
+            for p in native.set_points() {
+                let covered = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .iter()
+                    .any(|(dx, dy)| smooth.pixel(p.x * 2 + dx, p.y * 2 + dy) != Color::White);
+                assert!(covered, "{digit} lost the pixel at {p}");
+            }
+        }
+    }
+
+    #[test]
+    fn supersampling_changes_the_doubled_glyphs() {
+        assert_ne!(ascii(&big("2", true)), ascii(&big("2", false)));
+    }
+
+    #[test]
+    fn long_text_falls_back_to_doubling() {
+        let long = "0123456789".repeat(10);
+        let native = native_size(&FONT_10X20, &long);
+        assert!(Mask::bytes_for(native * 4) > SUPERSAMPLE_BUDGET);
+        assert!(big(&long, true) == big(&long, false));
+        let short = "0123456789";
+        assert!(Mask::bytes_for(native_size(&FONT_10X20, short) * 4) <= SUPERSAMPLE_BUDGET);
+        assert!(big(short, true) != big(short, false));
+    }
+
+    #[test]
+    fn placing_uses_the_font_metrics() {
+        let rect = Rectangle::new(Point::new(10, 20), Size::new(100, 30));
+        for font in [&FONT_6X10, &FONT_10X20] {
+            let (w, h) = (font.character_size.width, font.character_size.height);
+            let top_left = place("abc", font, rect, Align::new(HAlign::Left, VAlign::Top));
+            assert_eq!(top_left.text, "abc");
+            assert_eq!(top_left.truncated, 0);
+            assert_eq!(top_left.bounds.top_left, Point::new(10, 20));
+            assert_eq!(top_left.bounds.size, Size::new(3 * w, h));
+            assert_eq!(top_left.origin, Point::new(10, 20 + font.baseline as i32));
+
+            let centred = place("abc", font, rect, Align::CENTER);
+            let x = 10 + (100 - 3 * w as i32) / 2;
+            let y = 20 + (30 - h as i32) / 2;
+            assert_eq!(centred.bounds.top_left, Point::new(x, y));
+
+            let corner = place("abc", font, rect, Align::new(HAlign::Right, VAlign::Bottom));
+            assert_eq!(
+                corner.bounds.bottom_right(),
+                rect.bottom_right(),
+                "{w}x{h} flush with the corner"
+            );
+        }
+    }
+
+    #[test]
+    fn placing_empty_text_covers_nothing() {
+        let rect = Rectangle::new(Point::new(5, 5), Size::new(50, 20));
+        for font in [&FONT_6X10, &FONT_10X20] {
+            let placement = place("", font, rect, Align::CENTER);
+            assert_eq!(placement.text, "");
+            assert_eq!(placement.truncated, 0);
+            assert_eq!(placement.bounds.size, Size::zero());
+        }
+    }
+
+    #[test]
+    fn placing_drops_what_does_not_fit() {
+        // 6x10 advances 6 pixels a letter: 40 pixels hold six, the last
+        // one reaching x = 36.
+        let rect = Rectangle::new(Point::zero(), Size::new(40, 10));
+        let placement = place("ăbcdefghij", &FONT_6X10, rect, Align::LEFT);
+        assert_eq!(placement.text, "ăbcdef");
+        assert_eq!(placement.truncated, 4);
+        assert_eq!(placement.bounds.size, Size::new(36, 10));
+
+        let big = place("abcdef", &FONT_10X20, rect, Align::LEFT);
+        assert_eq!((big.text, big.truncated), ("abcd", 2));
+
+        let narrow = Rectangle::new(Point::zero(), Size::new(5, 10));
+        let none = place("abc", &FONT_6X10, narrow, Align::LEFT);
+        assert_eq!((none.text, none.truncated), ("", 3));
+    }
+
+    /// Every letter Romanian has, in both forms of ș and ț.
+    const ROMANIAN: &str = "ăâîșțĂÂÎȘȚşţŞŢ";
+
+    /// Each character cell of `text` drawn in `font`, as ASCII art.
+    fn cells(text: &str, font: &MonoFont) -> Vec<String> {
+        let cell = font.character_size;
+        let count = text.chars().count() as u32;
+        let mut frame = Frame::new(cell.width * count, cell.height);
+        let rect = Rectangle::new(Point::zero(), frame.size());
+        draw(&mut frame, text, font, Color::Black, rect, Align::LEFT);
+        (0..count as i32)
+            .map(|i| {
+                let mut art = String::new();
+                for y in 0..cell.height as i32 {
+                    for x in 0..cell.width as i32 {
+                        let at = Point::new(i * cell.width as i32 + x, y);
+                        art.push(match frame.pixel(at.x, at.y) {
+                            Color::White => '.',
+                            _ => '#',
+                        });
+                    }
+                }
+                art
+            })
+            .collect()
+    }
+
+    #[test]
+    fn romanian_letters_draw_without_replacement_boxes() {
+        for font in [&FONT_6X10, &FONT_10X20] {
+            let replacement = &cells("?", font)[0];
+            let drawn = cells(ROMANIAN, font);
+            let bare = cells("aaistAAISTstST", font);
+            for ((letter, cell), bare) in ROMANIAN.chars().zip(&drawn).zip(&bare) {
+                assert_ne!(cell, replacement, "{letter} in {:?}", font.character_size);
+                assert!(cell.contains('#'), "{letter} is blank");
+                // Drawn with its mark, not as the bare letter.
+                assert_ne!(cell, bare, "{letter} lost its mark");
+            }
+            // Cedilla forms come out as the comma-below ones.
+            assert_eq!(drawn[3..5], drawn[10..12]);
+            assert_eq!(drawn[8..10], drawn[12..14]);
+        }
+    }
+
+    #[test]
+    fn names_mixing_fonts_stay_on_the_grid() {
+        let drawn = cells("Ștefan", &FONT_10X20);
+        assert_eq!(drawn[1..], cells("tefan", &FONT_10X20)[..]);
+        assert_eq!(drawn[0], cells("Ș", &FONT_10X20)[0]);
+    }
+
+    #[test]
+    fn big_text_draws_romanian_letters_too() {
+        let replacement = big("?", false);
+        for letter in ROMANIAN.chars() {
+            let drawn = big(&letter.to_string(), false);
+            assert!(ascii(&drawn).contains('#'), "{letter} is blank");
+            assert_ne!(ascii(&drawn), ascii(&replacement), "{letter}");
+        }
+    }
+
+    #[test]
+    fn fonts_without_a_supplement_transliterate() {
+        use embedded_graphics::mono_font::iso_8859_15::FONT_5X8;
+        assert_eq!(supplement(&FONT_5X8), None);
+        let shaped: String = ROMANIAN
+            .chars()
+            .map(|c| shape(&FONT_5X8, None, c).1)
+            .collect();
+        // â and î are in ISO 8859-15 already.
+        assert_eq!(shaped, "aâîstAÂÎSTstST");
+        for c in ROMANIAN.chars() {
+            let (font, c) = shape(&FONT_6X10, supplement(&FONT_6X10), c);
+            assert!(has_glyph(font, c));
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Prices for the `ticker` screen, fetched from whatever JSON endpoint the
+//! config points at.
+//!
+//! A failed or malformed fetch keeps the last good quote and marks it
+//! stale, so a flaky API doesn't blank the screen.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Context as _, Result};
+use serde_json::Value;
+
+use crate::collector::{Snapshot, Source};
+use crate::config::{TickerConfig, TickerSymbol};
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The latest good numbers for one symbol.
+#[derive(Clone, Debug)]
+pub struct Quote {
+    pub label: String,
+    /// `None` until the first successful fetch.
+    pub price: Option<f64>,
+    /// Change over 24 h, in percent.
+    pub change: Option<f64>,
+    /// The last fetch failed, so these numbers are older than they look.
+    pub stale: bool,
+}
+
+impl Quote {
+    /// Takes in a fetch, or keeps the last good numbers and marks them
+    /// stale when it failed.
+    fn update(&mut self, fetched: Result<(f64, Option<f64>)>) {
+        match fetched {
+            Ok((price, change)) => {
+                self.price = Some(price);
+                self.change = change;
+                self.stale = false;
+            }
+            Err(err) => {
+                log::warn!("fetching {} failed: {err:#}", self.label);
+                self.stale = self.price.is_some();
+            }
+        }
+    }
+}
+
+/// Two decimals for anything from 1 up, six below, so both a stock and a
+/// fraction-of-a-cent token read sensibly.
+pub fn format_price(price: f64) -> String {
+    if price.abs() >= 1.0 {
+        format!("{price:.2}")
+    } else {
+        format!("{price:.6}")
+    }
+}
+
+pub struct TickerSource {
+    agent: ureq::Agent,
+    symbols: Vec<TickerSymbol>,
+    quotes: Vec<Quote>,
+    interval: Duration,
+}
+
+impl TickerSource {
+    /// Returns `None` when no symbols are configured.
+    pub fn new(config: &TickerConfig) -> Option<Self> {
+        if config.symbols.is_empty() {
+            return None;
+        }
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(HTTP_TIMEOUT))
+            .user_agent(concat!("inky-gotchi/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .into();
+        let quotes = config
+            .symbols
+            .iter()
+            .map(|symbol| Quote {
+                label: symbol.label.clone(),
+                price: None,
+                change: None,
+                stale: false,
+            })
+            .collect();
+        Some(TickerSource {
+            agent,
+            symbols: config.symbols.clone(),
+            quotes,
+            interval: Duration::from_secs(config.interval),
+        })
+    }
+}
+
+/// The price and change, if the symbol has one.
+fn fetch(agent: &ureq::Agent, symbol: &TickerSymbol) -> Result<(f64, Option<f64>)> {
+    let body = agent.get(&symbol.url).call()?.body_mut().read_to_string()?;
+    extract(&body, symbol)
+}
+
+/// Pulls the price and change out of `body` with the symbol's pointers.
+fn extract(body: &str, symbol: &TickerSymbol) -> Result<(f64, Option<f64>)> {
+    let body: Value = serde_json::from_str(body).context("response isn't JSON")?;
+    let at = |pointer: &str| {
+        body.pointer(pointer)
+            .and_then(number)
+            .with_context(|| format!("no number at {pointer}"))
+    };
+    let price = at(&symbol.price)?;
+    let change = symbol.change.as_deref().map(at).transpose()?;
+    Ok((price, change))
+}
+
+/// Accepts numbers and numeric strings, which some APIs send prices as.
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+    .filter(|n: &f64| n.is_finite())
+}
+
+impl Source for TickerSource {
+    fn name(&self) -> &'static str {
+        "ticker"
+    }
+
+    fn on_demand(&self) -> bool {
+        true
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn collect(&mut self, snapshot: &Mutex<Snapshot>) {
+        for (symbol, quote) in self.symbols.iter().zip(&mut self.quotes) {
+            quote.update(fetch(&self.agent, symbol));
+        }
+        snapshot.lock().unwrap().tickers = self.quotes.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prices_get_two_decimals_from_one_up() {
+        for (price, text) in [
+            (1.0, "1.00"),
+            (0.999999, "0.999999"),
+            (0.00001234, "0.000012"),
+            (0.0, "0.000000"),
+            (-0.5, "-0.500000"),
+            (-1.0, "-1.00"),
+            (-1234.567, "-1234.57"),
+            (64_231.456, "64231.46"),
+            (1e12, "1000000000000.00"),
+        ] {
+            assert_eq!(format_price(price), text, "{price}");
+        }
+    }
+
+    fn bitcoin() -> TickerSymbol {
+        TickerSymbol {
+            label: "BTC".into(),
+            url: "https://api.coingecko.com/api/v3/simple/price".into(),
+            price: "/bitcoin/usd".into(),
+            change: Some("/bitcoin/usd_24h_change".into()),
+        }
+    }
+
+    #[test]
+    fn numbers_are_found_by_pointer() {
+        let body = r#"{"bitcoin": {"usd": 64231.5, "usd_24h_change": -2.25}}"#;
+        assert_eq!(extract(body, &bitcoin()).unwrap(), (64231.5, Some(-2.25)));
+        // Some APIs send numbers as strings.
+        let body = r#"{"bitcoin": {"usd": " 0.5 ", "usd_24h_change": "1"}}"#;
+        assert_eq!(extract(body, &bitcoin()).unwrap(), (0.5, Some(1.0)));
+        let symbol = TickerSymbol {
+            change: None,
+            ..bitcoin()
+        };
+        assert_eq!(
+            extract(r#"{"bitcoin": {"usd": 3}}"#, &symbol).unwrap(),
+            (3.0, None)
+        );
+    }
+
+    #[test]
+    fn a_malformed_response_keeps_the_last_quote_as_stale() {
+        let mut quote = Quote {
+            label: "BTC".into(),
+            price: None,
+            change: None,
+            stale: false,
+        };
+        // Nothing to keep yet, so nothing is stale either.
+        quote.update(extract("<html>502</html>", &bitcoin()));
+        assert_eq!((quote.price, quote.stale), (None, false));
+
+        let good = r#"{"bitcoin": {"usd": 64231.5, "usd_24h_change": -2.25}}"#;
+        quote.update(extract(good, &bitcoin()));
+        assert_eq!(
+            (quote.price, quote.change, quote.stale),
+            (Some(64231.5), Some(-2.25), false)
+        );
+        for bad in [
+            "<html>502</html>",
+            "",
+            r#"{"bitcoin": {"usd": "n/a", "usd_24h_change": 1}}"#,
+            r#"{"bitcoin": {"usd": 64000}}"#,
+            r#"{"ethereum": {"usd": 3000, "usd_24h_change": 1}}"#,
+            r#"{"bitcoin": {"usd": null, "usd_24h_change": 1}}"#,
+        ] {
+            let fetched = extract(bad, &bitcoin());
+            assert!(fetched.is_err(), "{bad:?}");
+            quote.update(fetched);
+            assert_eq!(
+                (quote.price, quote.change, quote.stale),
+                (Some(64231.5), Some(-2.25), true),
+                "{bad:?}"
+            );
+        }
+        quote.update(extract(good, &bitcoin()));
+        assert!(!quote.stale);
+    }
+}
@@ -0,0 +1,398 @@
+//! Moving a pet to another device without a network: the essentials of it
+//! packed into a few dozen bytes, written out in base45 (RFC 9285) so they
+//! fit a QR code's alphanumeric mode, and shown on the panel as one. The
+//! other device takes the text, typed or scanned in elsewhere, with
+//! `transfer import`.
+//!
+//! The bytes are a version, the egg and form, the stats rounded to whole
+//! points, the age in hours and the name, then a CRC-32 of all that, so a
+//! mistyped or doctored code is turned away rather than installed.
+//! The pet has no achievements yet, so there's no bitmap of them; when it
+//! does, they go in behind a new [`VERSION`], which older builds refuse.
+
+use anyhow::{bail, ensure, Context as _, Result};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::pet::{Egg, Form, Pet, MAX_MESSES, STAT_MAX};
+use crate::qr::{self, Qr};
+
+const VERSION: u8 = 1;
+/// Longest name carried, in bytes; longer ones are cut at a letter.
+const MAX_NAME: usize = 24;
+/// Version, egg, form, four stats, messes, age and the name's length.
+const HEADER: usize = 1 + 1 + 1 + 4 + 1 + 2 + 1;
+const CRC: usize = 4;
+const MAX_BYTES: usize = HEADER + MAX_NAME + CRC;
+/// Base45 turns every two bytes into three letters, and a last odd byte
+/// into two.
+pub const MAX_CODE: usize = MAX_BYTES / 2 * 3 + MAX_BYTES % 2 * 2;
+const _: () = assert!(MAX_CODE <= qr::capacity(qr::MAX_VERSION));
+
+/// How long the code stays up, and how long an incoming pet waits to be
+/// taken in.
+pub const SHOWN_FOR: Duration = Duration::minutes(5);
+pub const OFFERED_FOR: Duration = Duration::minutes(2);
+
+/// What moves with the pet. Everything else, like its history and
+/// lifetime totals, starts over on the new device.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transfer {
+    pub name: String,
+    pub egg: Egg,
+    pub form: Form,
+    pub hunger: u8,
+    pub happiness: u8,
+    pub energy: u8,
+    pub training: u8,
+    pub messes: u8,
+    pub age_hours: u16,
+}
+
+impl Transfer {
+    pub fn of(pet: &Pet, now: DateTime<Utc>) -> Result<Transfer> {
+        ensure!(!pet.is_dead(), "{} is gone and can't be moved", pet.name);
+        let egg = pet.egg.context("the egg hasn't been picked yet")?;
+        let mut name = pet.name.clone();
+        while name.len() > MAX_NAME {
+            name.pop();
+        }
+        ensure!(!name.is_empty(), "a pet without a name can't be moved");
+        let stat = |value: f32| value.round().clamp(0.0, STAT_MAX) as u8;
+        Ok(Transfer {
+            name,
+            egg,
+            form: pet.form,
+            hunger: stat(pet.hunger),
+            happiness: stat(pet.happiness),
+            energy: stat(pet.energy),
+            training: stat(pet.training),
+            messes: pet.messes,
+            age_hours: (now - pet.born).num_hours().clamp(0, u16::MAX as i64) as u16,
+        })
+    }
+
+    /// A brand new pet that carries on where this one left off, as of
+    /// `now`.
+    pub fn into_pet(self, now: DateTime<Utc>) -> Pet {
+        let born = now - Duration::hours(self.age_hours as i64);
+        let mut pet = Pet::new(&self.name, born);
+        pet.last_tick = now;
+        pet.egg = Some(self.egg);
+        pet.form = self.form;
+        pet.hunger = self.hunger as f32;
+        pet.happiness = self.happiness as f32;
+        pet.energy = self.energy as f32;
+        pet.training = self.training as f32;
+        pet.messes = self.messes;
+        pet
+    }
+
+    /// E.g. `Mara, a noble speckled, day 12`.
+    pub fn summary(&self) -> String {
+        let form = match self.form {
+            Form::Baby => "baby",
+            Form::Noble => "noble",
+            Form::Wild => "wild",
+        };
+        format!(
+            "{}, a {form} {}, day {}",
+            self.name,
+            self.egg.label().to_lowercase(),
+            self.age_hours / 24 + 1
+        )
+    }
+
+    pub fn encode(&self) -> String {
+        let mut bytes = vec![
+            VERSION,
+            Egg::ALL.iter().position(|&e| e == self.egg).unwrap_or(0) as u8,
+            FORMS.iter().position(|&f| f == self.form).unwrap_or(0) as u8,
+            self.hunger,
+            self.happiness,
+            self.energy,
+            self.training,
+            self.messes,
+        ];
+        bytes.extend(self.age_hours.to_be_bytes());
+        bytes.push(self.name.len() as u8);
+        bytes.extend(self.name.as_bytes());
+        bytes.extend(crc32(&bytes).to_be_bytes());
+        base45_encode(&bytes)
+    }
+
+    /// Reads a code back, in either form, see [`readable`].
+    pub fn decode(code: &str) -> Result<Transfer> {
+        let code = code.replace('_', " ").to_ascii_uppercase();
+        ensure!(
+            code.len() <= MAX_CODE,
+            "too long for a pet: {} letters, at most {MAX_CODE}",
+            code.len()
+        );
+        let bytes = base45_decode(&code)?;
+        ensure!(bytes.len() >= HEADER + CRC, "too short for a pet");
+        let (body, crc) = bytes.split_at(bytes.len() - CRC);
+        ensure!(
+            crc32(body).to_be_bytes() == crc,
+            "the check doesn't match; a letter is wrong or missing"
+        );
+        ensure!(
+            body[0] == VERSION,
+            "made by another version of inky-gotchi (format {}, this one reads {VERSION})",
+            body[0]
+        );
+        let egg = *Egg::ALL.get(body[1] as usize).context("no such egg")?;
+        let form = *FORMS.get(body[2] as usize).context("no such form")?;
+        let [hunger, happiness, energy, training] = [body[3], body[4], body[5], body[6]];
+        ensure!(
+            [hunger, happiness, energy, training]
+                .iter()
+                .all(|&stat| stat as f32 <= STAT_MAX),
+            "stats out of range"
+        );
+        let messes = body[7];
+        ensure!(messes <= MAX_MESSES, "more messes than there's room for");
+        let age_hours = u16::from_be_bytes([body[8], body[9]]);
+        let length = body[10] as usize;
+        ensure!(
+            (1..=MAX_NAME).contains(&length) && body.len() == HEADER + length,
+            "the name doesn't fit"
+        );
+        let name = std::str::from_utf8(&body[HEADER..]).context("the name isn't text")?;
+        Ok(Transfer {
+            name: name.to_string(),
+            egg,
+            form,
+            hunger,
+            happiness,
+            energy,
+            training,
+            messes,
+            age_hours,
+        })
+    }
+}
+
+/// `code` for people to read and type: base45 has spaces in it, which
+/// are easy to miss on the panel and get lost at either end, so they're
+/// written as `_`.
+pub fn readable(code: &str) -> String {
+    code.replace(' ', "_")
+}
+
+/// The forms by their number in a code.
+const FORMS: [Form; 3] = [Form::Baby, Form::Noble, Form::Wild];
+
+/// A pet's code on the panel.
+pub struct Showing {
+    pub name: String,
+    pub code: String,
+    pub qr: Qr,
+    pub until: DateTime<Utc>,
+}
+
+impl Showing {
+    pub fn new(pet: &Pet, now: DateTime<Utc>) -> Result<Showing> {
+        let code = Transfer::of(pet, now)?.encode();
+        let qr = Qr::encode(&code).expect("codes fit a QR code");
+        Ok(Showing {
+            name: pet.name.clone(),
+            code,
+            qr,
+            until: now + SHOWN_FOR,
+        })
+    }
+}
+
+/// A pet come in over the control socket, waiting for a yes on the
+/// buttons before it replaces the current one.
+pub struct Offer {
+    pub pet: Transfer,
+    pub until: DateTime<Utc>,
+}
+
+fn base45_encode(bytes: &[u8]) -> String {
+    let letter = |value: u32| qr::ALPHANUMERIC[value as usize % 45] as char;
+    let mut out = String::new();
+    for chunk in bytes.chunks(2) {
+        let (value, letters) = match *chunk {
+            [a, b] => (a as u32 * 256 + b as u32, 3),
+            [a] => (a as u32, 2),
+            _ => unreachable!(),
+        };
+        for i in 0..letters {
+            out.push(letter(value / 45u32.pow(i)));
+        }
+    }
+    out
+}
+
+fn base45_decode(code: &str) -> Result<Vec<u8>> {
+    let values: Vec<u32> = code
+        .chars()
+        .map(|c| {
+            qr::ALPHANUMERIC
+                .iter()
+                .position(|&a| a as char == c)
+                .map(|v| v as u32)
+                .with_context(|| format!("{c:?} can't be in a code"))
+        })
+        .collect::<Result<_>>()?;
+    let mut out = Vec::new();
+    for chunk in values.chunks(3) {
+        match *chunk {
+            [c, d, e] => {
+                let value = c + d * 45 + e * 45 * 45;
+                ensure!(value <= 0xffff, "not a valid code");
+                out.extend((value as u16).to_be_bytes());
+            }
+            [c, d] => {
+                let value = c + d * 45;
+                ensure!(value <= 0xff, "not a valid code");
+                out.push(value as u8);
+            }
+            _ => bail!("not a valid code, a letter is missing"),
+        }
+    }
+    Ok(out)
+}
+
+/// CRC-32 as in zip and PNG.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn transfers() -> impl Strategy<Value = Transfer> {
+        (
+            // Up to two bytes a letter, so never past MAX_NAME.
+            "[a-zA-Z0-9ăâîșțĂÂÎȘȚ .-]{1,12}",
+            0..Egg::ALL.len(),
+            0..FORMS.len(),
+            proptest::array::uniform4(0..=STAT_MAX as u8),
+            0..=MAX_MESSES,
+            any::<u16>(),
+        )
+            .prop_map(|(name, egg, form, stats, messes, age_hours)| Transfer {
+                name,
+                egg: Egg::ALL[egg],
+                form: FORMS[form],
+                hunger: stats[0],
+                happiness: stats[1],
+                energy: stats[2],
+                training: stats[3],
+                messes,
+                age_hours,
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn codes_round_trip_and_fit_a_qr_code(transfer in transfers()) {
+            let code = transfer.encode();
+            prop_assert!(code.len() <= MAX_CODE);
+            prop_assert!(Qr::encode(&code).is_some());
+            prop_assert_eq!(&Transfer::decode(&code).unwrap(), &transfer);
+            // As typed from the panel, in any case.
+            let typed = readable(&code).to_ascii_lowercase();
+            prop_assert_eq!(&Transfer::decode(&typed).unwrap(), &transfer);
+        }
+
+        #[test]
+        fn a_changed_byte_is_turned_away(
+            transfer in transfers(),
+            at in any::<prop::sample::Index>(),
+            flip in 1..=255u8,
+        ) {
+            let mut bytes = base45_decode(&transfer.encode()).unwrap();
+            let at = at.index(bytes.len());
+            bytes[at] ^= flip;
+            prop_assert!(Transfer::decode(&base45_encode(&bytes)).is_err());
+        }
+
+        #[test]
+        fn a_shortened_code_is_turned_away(transfer in transfers(), cut in 1..6usize) {
+            let code = transfer.encode();
+            prop_assert!(Transfer::decode(&code[..code.len() - cut]).is_err());
+        }
+    }
+
+    /// A change to a code's bytes.
+    type Forgery = fn(&mut Vec<u8>);
+
+    /// `transfer`'s code with its bytes put through `change` and a fresh
+    /// checksum, as a deliberate forgery would have.
+    fn forged(transfer: &Transfer, change: impl Fn(&mut Vec<u8>)) -> String {
+        let mut bytes = base45_decode(&transfer.encode()).unwrap();
+        bytes.truncate(bytes.len() - CRC);
+        change(&mut bytes);
+        bytes.extend(crc32(&bytes).to_be_bytes());
+        base45_encode(&bytes)
+    }
+
+    #[test]
+    fn forgeries_with_a_good_checksum_are_still_checked() {
+        let transfer = Transfer {
+            name: "Mara".into(),
+            egg: Egg::Speckled,
+            form: Form::Noble,
+            hunger: 80,
+            happiness: 60,
+            energy: 40,
+            training: 20,
+            messes: 1,
+            age_hours: 300,
+        };
+        let cases: [(&str, Forgery); 6] = [
+            ("made by another version", |b| b[0] = VERSION + 1),
+            ("no such egg", |b| b[1] = 9),
+            ("no such form", |b| b[2] = 9),
+            ("stats out of range", |b| b[3] = 101),
+            ("more messes", |b| b[7] = MAX_MESSES + 1),
+            ("the name doesn't fit", |b| b[10] = 5),
+        ];
+        for (expected, change) in cases {
+            let err = Transfer::decode(&forged(&transfer, change)).unwrap_err();
+            assert!(err.to_string().starts_with(expected), "{err}");
+        }
+        let not_text = forged(&transfer, |b| b[HEADER] = 0xff);
+        assert!(Transfer::decode(&not_text).is_err());
+        assert_eq!(
+            Transfer::decode(&forged(&transfer, |_| {})).unwrap(),
+            transfer
+        );
+    }
+
+    #[test]
+    fn a_pet_comes_out_as_it_went_in() {
+        let born: DateTime<Utc> = "2024-05-01T09:00:00Z".parse().unwrap();
+        let now = born + Duration::hours(30);
+        let mut pet = Pet::new("Ștefan cel Mare și Sfânt din Moldova", born);
+        pet.egg = Some(Egg::Ember);
+        (pet.hunger, pet.training, pet.messes) = (55.4, 12.6, 2);
+        let transfer = Transfer::of(&pet, now).unwrap();
+        assert!(transfer.name.len() <= MAX_NAME);
+        assert!(pet.name.starts_with(&transfer.name));
+        let moved = Transfer::decode(&transfer.encode()).unwrap().into_pet(now);
+        assert_eq!(moved.name, transfer.name);
+        assert_eq!(
+            (moved.hunger, moved.training, moved.messes),
+            (55.0, 13.0, 2)
+        );
+        assert_eq!(moved.egg, Some(Egg::Ember));
+        assert_eq!(moved.born, born);
+
+        pet.egg = None;
+        assert!(Transfer::of(&pet, now).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+//! Effects for moving from one screen to the next over a few refreshes,
+//! instead of replacing the whole panel at once.
+
+use serde::Deserialize;
+
+use crate::display::Frame;
+
+/// Refreshes a transition takes, the last showing the new screen in full.
+pub const STEPS: u32 = 3;
+/// Side of the squares the dissolve works in.
+const CELL: i32 = 4;
+/// Order the dissolve fills each 2x2 block of squares in.
+const DISSOLVE_ORDER: [[u32; 2]; 2] = [[0, 2], [3, 1]];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transition {
+    #[default]
+    None,
+    /// The new screen comes in from the left, a band at a time.
+    Wipe,
+    /// The new screen comes in as a checkerboard that fills up.
+    Dissolve,
+}
+
+impl Transition {
+    /// The in-between frame for `step`, from 1 up to but not including
+    /// [`STEPS`].
+    pub fn blend(self, from: &Frame, to: &Frame, step: u32) -> Frame {
+        let mut out = from.clone();
+        let width = to.width() as i32;
+        for y in 0..to.height() as i32 {
+            for x in 0..width {
+                let incoming = match self {
+                    Transition::None => true,
+                    Transition::Wipe => x < width * step as i32 / STEPS as i32,
+                    Transition::Dissolve => {
+                        let (cx, cy) = ((x / CELL) as usize, (y / CELL) as usize);
+                        DISSOLVE_ORDER[cy % 2][cx % 2] * STEPS < 4 * step
+                    }
+                };
+                if incoming {
+                    out.set_pixel(x, y, to.pixel(x, y));
+                }
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,147 @@
+//! Daily check for a newer release. Only looks: nothing is ever downloaded
+//! or installed.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Utc};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Clock;
+use crate::collector::{Snapshot, Source};
+use crate::config::UpdateCheckConfig;
+use crate::persist;
+
+/// Setting this environment variable (to anything) disables the check.
+pub const DISABLE_ENV: &str = "INKY_GOTCHI_NO_UPDATE_CHECK";
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How soon to try again after a failed check.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Result of the last check, kept on disk so restarts don't re-query.
+#[derive(Serialize, Deserialize)]
+struct Cache {
+    checked: DateTime<Utc>,
+    latest: String,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+}
+
+pub struct UpdateSource {
+    url: String,
+    cache_file: PathBuf,
+    cache: Option<Cache>,
+    next_check: Duration,
+    clock: Clock,
+}
+
+impl UpdateSource {
+    /// Returns `None` when the check is turned off in the config or via
+    /// [`DISABLE_ENV`].
+    pub fn new(config: &UpdateCheckConfig, clock: Clock) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        if std::env::var_os(DISABLE_ENV).is_some() {
+            log::info!("update check disabled by {DISABLE_ENV}");
+            return None;
+        }
+        let cache = std::fs::read_to_string(&config.cache_file)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok());
+        Some(UpdateSource {
+            url: config.url.clone(),
+            cache_file: config.cache_file.clone(),
+            cache,
+            next_check: Duration::ZERO,
+            clock,
+        })
+    }
+
+    fn fetch_latest(&self) -> Result<String> {
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .timeout_global(Some(HTTP_TIMEOUT))
+            .user_agent(concat!("inky-gotchi/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .into();
+        let release: Release = agent
+            .get(&self.url)
+            .header("Accept", "application/vnd.github+json")
+            .call()?
+            .body_mut()
+            .read_json()?;
+        Ok(release.tag_name)
+    }
+
+    fn publish(&self, snapshot: &Mutex<Snapshot>) {
+        let newer = self
+            .cache
+            .as_ref()
+            .and_then(|cache| newer_than_running(&cache.latest));
+        snapshot.lock().unwrap().available_update = newer;
+    }
+}
+
+/// The release's version if it is newer than this build.
+fn newer_than_running(tag: &str) -> Option<String> {
+    let latest = Version::parse(tag.trim_start_matches('v')).ok()?;
+    let running = Version::parse(env!("CARGO_PKG_VERSION")).ok()?;
+    (latest > running).then(|| latest.to_string())
+}
+
+impl Source for UpdateSource {
+    fn name(&self) -> &'static str {
+        "update-check"
+    }
+
+    fn on_demand(&self) -> bool {
+        true
+    }
+
+    fn interval(&self) -> Duration {
+        self.next_check
+    }
+
+    fn collect(&mut self, snapshot: &Mutex<Snapshot>) {
+        // Show the cached answer straight away, and only query once it's
+        // due, so restarts don't each cost a request.
+        self.publish(snapshot);
+        if let Some(cache) = &self.cache {
+            let age = (self.clock.now_utc() - cache.checked)
+                .to_std()
+                .unwrap_or_default();
+            if age < CHECK_INTERVAL {
+                self.next_check = CHECK_INTERVAL - age;
+                return;
+            }
+        }
+        self.next_check = RETRY_INTERVAL;
+        match self.fetch_latest() {
+            Ok(latest) => {
+                log::info!("latest release is {latest}");
+                let cache = Cache {
+                    checked: self.clock.now_utc(),
+                    latest,
+                };
+                let saved = serde_json::to_vec(&cache)
+                    .context("encoding update cache")
+                    .and_then(|json| persist::write_atomic(&self.cache_file, &json));
+                if let Err(err) = saved {
+                    log::warn!("couldn't save update cache: {err:#}");
+                }
+                self.cache = Some(cache);
+                self.next_check = CHECK_INTERVAL;
+                self.publish(snapshot);
+            }
+            Err(err) => log::warn!("update check failed: {err:#}"),
+        }
+    }
+}
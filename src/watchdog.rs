@@ -0,0 +1,249 @@
+//! Catches a render loop that's alive but wedged, e.g. blocked on a lock
+//! some slow network call is holding, with the panel frozen meanwhile.
+//!
+//! The loop beats a [`Heartbeat`] after every cycle and marks the phase it
+//! is in along the way. Once a beat is overdue by `missed_cycles` times the
+//! wait the loop expected, the watchdog logs where the loop got to and
+//! reinitialises the display itself, unless the loop is stuck inside the
+//! display and holding it. If the loop still hasn't come back one more
+//! interval later, the process aborts so systemd can restart it.
+//!
+//! Beats and stalls also go to [`crate::profile`] for `GET /metrics`.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::profile;
+
+/// How often the watchdog looks at the heartbeat.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Expected gaps shorter than this are rounded up, since a panel refresh
+/// and a save take a while even when nothing is wrong.
+const MIN_EXPECTED: Duration = Duration::from_secs(60);
+
+/// Shared between the render loop and the watchdog.
+#[derive(Clone)]
+pub struct Heartbeat(Arc<Mutex<Pulse>>);
+
+struct Pulse {
+    /// When the last cycle completed.
+    at: Instant,
+    /// How long the loop meant to wait before its next cycle.
+    expected: Duration,
+    phase: &'static str,
+    screen: &'static str,
+    cycles: u64,
+    trips: u32,
+    last_stall: Option<String>,
+}
+
+/// The watchdog's view, for the status file.
+#[derive(Clone, Debug, Serialize)]
+pub struct WatchdogStatus {
+    pub cycles: u64,
+    /// Times the loop has been found stuck since startup.
+    pub trips: u32,
+    /// Where it was stuck the last time, e.g. `drawing, cycle 12`.
+    pub last_stall: Option<String>,
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Heartbeat(Arc::new(Mutex::new(Pulse {
+            at: Instant::now(),
+            expected: MIN_EXPECTED,
+            phase: "starting",
+            screen: "none",
+            cycles: 0,
+            trips: 0,
+            last_stall: None,
+        })))
+    }
+
+    /// Marks what the loop is about to do, so a stall can be pinned on it.
+    pub fn phase(&self, phase: &'static str) {
+        self.0.lock().unwrap().phase = phase;
+    }
+
+    /// Records a completed cycle that showed `screen`; the next one is due
+    /// in `expected`.
+    pub fn beat(&self, screen: &'static str, expected: Duration) {
+        let mut pulse = self.0.lock().unwrap();
+        pulse.at = Instant::now();
+        pulse.expected = expected;
+        pulse.screen = screen;
+        pulse.cycles += 1;
+        profile::heartbeat(pulse.at, pulse.cycles, pulse.trips);
+    }
+
+    pub fn status(&self) -> WatchdogStatus {
+        let pulse = self.0.lock().unwrap();
+        WatchdogStatus {
+            cycles: pulse.cycles,
+            trips: pulse.trips,
+            last_stall: pulse.last_stall.clone(),
+        }
+    }
+}
+
+/// Starts the watchdog thread. `reinit` reinitialises the display when
+/// the loop is found stuck, returning false if it couldn't.
+pub fn spawn(
+    heartbeat: Heartbeat,
+    missed_cycles: u32,
+    reinit: impl FnMut() -> bool + Send + 'static,
+) -> Result<()> {
+    thread::Builder::new()
+        .name("watchdog".into())
+        .spawn(move || {
+            watch(
+                &heartbeat,
+                missed_cycles.max(1),
+                CHECK_INTERVAL,
+                MIN_EXPECTED,
+                reinit,
+            );
+            std::process::abort();
+        })?;
+    Ok(())
+}
+
+/// Looks at `heartbeat` every `check` until the loop has stayed stuck
+/// past a reinit, then returns so the caller can abort. Expected gaps are
+/// rounded up to `floor`.
+fn watch(
+    heartbeat: &Heartbeat,
+    missed_cycles: u32,
+    check: Duration,
+    floor: Duration,
+    mut reinit: impl FnMut() -> bool,
+) {
+    // The beat a trip was raised for, and when to give up on it.
+    let mut tripped: Option<(u64, Instant)> = None;
+    loop {
+        thread::sleep(check);
+        let mut pulse = heartbeat.0.lock().unwrap();
+        let interval = pulse.expected.max(floor);
+        let late = pulse.at.elapsed();
+        match tripped {
+            Some((cycles, _)) if cycles != pulse.cycles => {
+                log::info!("render loop is moving again");
+                tripped = None;
+            }
+            Some((_, give_up)) if Instant::now() >= give_up => {
+                log::error!(
+                    "render loop still stuck in {} after {}s, aborting",
+                    pulse.phase,
+                    late.as_secs()
+                );
+                return;
+            }
+            Some(_) => {}
+            None if late > interval * missed_cycles => {
+                log::error!(
+                    "render loop stuck for {}s: cycle {} in {}, last showed {}, expected a beat every {}s",
+                    late.as_secs(),
+                    pulse.cycles + 1,
+                    pulse.phase,
+                    pulse.screen,
+                    interval.as_secs()
+                );
+                pulse.trips += 1;
+                pulse.last_stall = Some(format!("{}, cycle {}", pulse.phase, pulse.cycles + 1));
+                profile::heartbeat(pulse.at, pulse.cycles, pulse.trips);
+                tripped = Some((pulse.cycles, Instant::now() + interval));
+                // The loop may be after the heartbeat too, so it can't be
+                // held while waiting on the display.
+                drop(pulse);
+                if reinit() {
+                    log::warn!("reinitialised the display after a stall");
+                } else {
+                    log::warn!("the display is busy, it can't be reinitialised");
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    const CHECK: Duration = Duration::from_millis(5);
+    const EXPECTED: Duration = Duration::from_millis(20);
+
+    #[test]
+    fn a_stalled_loop_is_reinitialised_then_given_up_on() {
+        let heartbeat = Heartbeat::new();
+        heartbeat.beat("pet", EXPECTED);
+        heartbeat.phase("drawing");
+        let reinits = AtomicU32::new(0);
+        let started = Instant::now();
+        watch(&heartbeat, 3, CHECK, Duration::ZERO, || {
+            reinits.fetch_add(1, Ordering::Relaxed);
+            true
+        });
+        // Three missed beats to trip, one more interval to give up.
+        assert!(started.elapsed() >= EXPECTED * 4);
+        assert_eq!(reinits.load(Ordering::Relaxed), 1);
+        let status = heartbeat.status();
+        assert_eq!(status.cycles, 1);
+        assert_eq!(status.trips, 1);
+        assert_eq!(status.last_stall.as_deref(), Some("drawing, cycle 2"));
+    }
+
+    #[test]
+    fn a_loop_that_comes_back_is_not_given_up_on() {
+        // Long enough for the loop to come back before it's given up on.
+        let expected = EXPECTED * 5;
+        let heartbeat = Heartbeat::new();
+        heartbeat.beat("pet", expected);
+        heartbeat.phase("saving");
+        let beating = heartbeat.clone();
+        let revive = thread::spawn(move || {
+            while beating.status().trips == 0 {
+                thread::sleep(CHECK);
+            }
+            // Comes back after the reinit and keeps going for a while,
+            // then stalls for good.
+            for _ in 0..20 {
+                beating.beat("clock", expected);
+                thread::sleep(CHECK);
+            }
+        });
+        let reinits = AtomicU32::new(0);
+        watch(&heartbeat, 2, CHECK, Duration::ZERO, || {
+            reinits.fetch_add(1, Ordering::Relaxed);
+            false
+        });
+        revive.join().unwrap();
+        let status = heartbeat.status();
+        assert_eq!(status.trips, 2);
+        assert_eq!(status.cycles, 21);
+        assert_eq!(reinits.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn beats_and_trips_are_in_the_metrics() {
+        let heartbeat = Heartbeat::new();
+        heartbeat.beat("pet", EXPECTED);
+        watch(&heartbeat, 1, CHECK, Duration::ZERO, || true);
+        let metrics = profile::prometheus();
+        for name in [
+            "inky_gotchi_render_cycles_total",
+            "inky_gotchi_watchdog_trips_total",
+            "inky_gotchi_heartbeat_age_seconds",
+        ] {
+            assert!(
+                metrics.contains(&format!("# TYPE {name} ")),
+                "{name} missing from {metrics}"
+            );
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! The hours ahead, for a layout's `forecast` element: temperature and
+//! chance of rain from Open-Meteo, or anything answering in its format.
+//!
+//! The collector keeps the hourly series as fetched, and [`reduce`] cuts
+//! it down to the next twelve hours every time the strip is drawn, so the
+//! strip moves on with the clock between fetches. A failed fetch keeps the
+//! last series; its hours run out one at a time and show as missing.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{ensure, Context as _, Result};
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use serde_json::Value;
+
+use crate::collector::{Snapshot, Source};
+use crate::config::{Config, WeatherConfig};
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Hours in the strip, the current one first.
+pub const HOURS: usize = 12;
+
+/// One hour of the series, as fetched.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hour {
+    /// When the hour starts.
+    pub at: DateTime<Utc>,
+    /// Degrees Celsius.
+    pub temperature: f64,
+    /// Chance of rain, in percent.
+    pub precipitation: f64,
+}
+
+/// What the strip shows.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Forecast {
+    /// The hour `now` is in, which the first slot is for.
+    pub start: DateTime<Utc>,
+    /// `None` for hours the series doesn't have.
+    pub slots: [Option<Hour>; HOURS],
+    /// Lowest and highest temperature over the day from `start`, which the
+    /// strip scales temperatures to; `None` when there's nothing in it.
+    pub range: Option<(f64, f64)>,
+}
+
+/// The hours in an Open-Meteo response asked for with
+/// `timeformat=unixtime`. Hours with a value missing are left out.
+pub fn parse(body: &Value) -> Result<Vec<Hour>> {
+    let hourly = body.get("hourly").context("no hourly forecast")?;
+    let column = |name: &str| {
+        hourly
+            .get(name)
+            .and_then(Value::as_array)
+            .with_context(|| format!("no hourly.{name}"))
+    };
+    let (times, temperatures, precipitation) = (
+        column("time")?,
+        column("temperature_2m")?,
+        column("precipitation_probability")?,
+    );
+    ensure!(
+        times.len() == temperatures.len() && times.len() == precipitation.len(),
+        "hourly columns differ in length"
+    );
+    let number = |value: &Value| value.as_f64().filter(|n| n.is_finite());
+    Ok(times
+        .iter()
+        .zip(temperatures)
+        .zip(precipitation)
+        .filter_map(|((at, temperature), precipitation)| {
+            Some(Hour {
+                at: DateTime::from_timestamp(at.as_i64()?, 0)?,
+                temperature: number(temperature)?,
+                precipitation: number(precipitation)?.clamp(0.0, 100.0),
+            })
+        })
+        .collect())
+}
+
+/// The twelve hours from the one `now` is in, out of `series`.
+pub fn reduce(series: &[Hour], now: DateTime<Utc>) -> Forecast {
+    let start = now.duration_trunc(TimeDelta::hours(1)).unwrap_or(now);
+    let hour_of = |hour: &Hour| hour.at.duration_trunc(TimeDelta::hours(1)).ok();
+    let slots = std::array::from_fn(|index| {
+        let at = start + TimeDelta::hours(index as i64);
+        series
+            .iter()
+            .find(|hour| hour_of(hour) == Some(at))
+            .cloned()
+    });
+    let day = start..start + TimeDelta::days(1);
+    let range = series
+        .iter()
+        .filter(|hour| hour_of(hour).is_some_and(|at| day.contains(&at)))
+        .fold(None, |range: Option<(f64, f64)>, hour| {
+            let t = hour.temperature;
+            Some(range.map_or((t, t), |(low, high)| (low.min(t), high.max(t))))
+        });
+    Forecast {
+        start,
+        slots,
+        range,
+    }
+}
+
+pub struct WeatherSource {
+    agent: ureq::Agent,
+    url: String,
+    interval: Duration,
+}
+
+impl WeatherSource {
+    /// Asks for the forecast where `config` puts the observer.
+    pub fn new(config: &Config) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(HTTP_TIMEOUT))
+            .user_agent(concat!("inky-gotchi/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .into();
+        let WeatherConfig { url, interval } = &config.weather;
+        WeatherSource {
+            agent,
+            url: format!(
+                "{url}?latitude={}&longitude={}&hourly=temperature_2m,precipitation_probability\
+                 &forecast_days=2&timezone=GMT&timeformat=unixtime",
+                config.latitude, config.longitude
+            ),
+            interval: Duration::from_secs(*interval),
+        }
+    }
+
+    fn fetch(&self) -> Result<Vec<Hour>> {
+        let body: Value = self.agent.get(&self.url).call()?.body_mut().read_json()?;
+        parse(&body)
+    }
+}
+
+impl Source for WeatherSource {
+    fn name(&self) -> &'static str {
+        "weather"
+    }
+
+    fn on_demand(&self) -> bool {
+        true
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn collect(&mut self, snapshot: &Mutex<Snapshot>) {
+        match self.fetch() {
+            Ok(series) => snapshot.lock().unwrap().forecast = series,
+            Err(err) => log::warn!("fetching the forecast failed: {err:#}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Open-Meteo's answer for Bucharest on 2024-05-01, two days from
+    /// midnight UTC, with the chance of rain at 13:00 UTC missing.
+    const PAYLOAD: &str = include_str!("../tests/fixtures/weather/open-meteo.json");
+
+    fn series() -> Vec<Hour> {
+        parse(&serde_json::from_str(PAYLOAD).unwrap()).unwrap()
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn hours_with_a_value_missing_are_left_out() {
+        let series = series();
+        assert_eq!(series.len(), 47);
+        assert_eq!(series[0].at, at("2024-05-01T00:00:00Z"));
+        assert!(series
+            .iter()
+            .all(|hour| hour.at != at("2024-05-01T13:00:00Z")));
+        assert_eq!(
+            series[13],
+            Hour {
+                at: at("2024-05-01T14:00:00Z"),
+                temperature: 20.8,
+                precipitation: 38.0,
+            }
+        );
+    }
+
+    #[test]
+    fn responses_without_the_columns_are_errors() {
+        for (body, error) in [
+            (r#"{"latitude": 44.4}"#, "no hourly forecast"),
+            (
+                r#"{"hourly": {"time": [], "temperature_2m": []}}"#,
+                "no hourly.precipitation_probability",
+            ),
+            (
+                r#"{"hourly": {"time": [0], "temperature_2m": [], "precipitation_probability": []}}"#,
+                "hourly columns differ in length",
+            ),
+        ] {
+            let err = parse(&serde_json::from_str(body).unwrap()).unwrap_err();
+            assert_eq!(err.to_string(), error, "{body}");
+        }
+    }
+
+    #[test]
+    fn the_strip_starts_at_the_current_hour() {
+        let forecast = reduce(&series(), at("2024-05-01T09:04:30Z"));
+        assert_eq!(forecast.start, at("2024-05-01T09:00:00Z"));
+        let hours: Vec<_> = forecast
+            .slots
+            .iter()
+            .map(|slot| slot.as_ref().map(|hour| hour.at))
+            .collect();
+        let expected: Vec<_> = (0..HOURS as i64)
+            .map(|index| forecast.start + TimeDelta::hours(index))
+            .map(|hour| (hour != at("2024-05-01T13:00:00Z")).then_some(hour))
+            .collect();
+        assert_eq!(hours, expected);
+        assert_eq!(forecast.slots[0].as_ref().unwrap().temperature, 14.0);
+        assert_eq!(forecast.slots[7].as_ref().unwrap().precipitation, 60.0);
+        // Scaled to the day from 09:00, which takes in the next night.
+        assert_eq!(forecast.range, Some((7.8, 21.0)));
+    }
+
+    #[test]
+    fn hours_past_the_end_of_the_series_are_missing() {
+        let forecast = reduce(&series(), at("2024-05-02T20:59:59Z"));
+        let known = forecast.slots.iter().filter(|slot| slot.is_some()).count();
+        assert_eq!(known, 4);
+        assert!(forecast.slots[4..].iter().all(Option::is_none));
+        assert_eq!(forecast.range, Some((11.3, 16.6)));
+
+        let forecast = reduce(&[], at("2024-05-02T20:59:59Z"));
+        assert!(forecast.slots.iter().all(Option::is_none));
+        assert_eq!(forecast.range, None);
+    }
+}
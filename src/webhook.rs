@@ -0,0 +1,538 @@
+//! The weekly care report: every Sunday evening, the week so far from the
+//! daily summary's tallies, POSTed to a webhook. Plain JSON by default, or
+//! a message Discord or Slack shows as is.
+//!
+//! The last week sent is saved, so a restart never sends one twice, and a
+//! week whose Sunday evening the daemon missed is sent when it's next up.
+//! A report is saved before it's sent, and one that couldn't be sent is
+//! tried again on a later cycle until it goes through.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context as _, Result};
+use chrono::{
+    DateTime, Datelike, FixedOffset, IsoWeek, NaiveDate, NaiveDateTime, NaiveTime, Weekday,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::config::WebhookConfig;
+use crate::persist;
+use crate::screens::format_uptime;
+use crate::summary::{Day, Tally};
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait after a failed send before trying again.
+const RETRY_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// What the webhook expects to be sent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// The [`Report`] itself.
+    #[default]
+    Json,
+    /// `{"content": ...}`, for a Discord webhook.
+    Discord,
+    /// `{"text": ...}`, for a Slack incoming webhook.
+    Slack,
+}
+
+/// One day of the week, from its tally.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DayReport {
+    pub date: NaiveDate,
+    /// Average stats, `None` when the panel was off all day.
+    pub hunger: Option<f32>,
+    pub happiness: Option<f32>,
+    pub energy: Option<f32>,
+    pub feedings: u32,
+    pub cleanings: u32,
+    pub refreshes: u32,
+    pub hours_seen: usize,
+}
+
+impl From<&Day> for DayReport {
+    fn from(day: &Day) -> Self {
+        let average = day.average();
+        DayReport {
+            date: day.date,
+            hunger: average.map(|a| a.hunger.round()),
+            happiness: average.map(|a| a.happiness.round()),
+            energy: average.map(|a| a.energy.round()),
+            feedings: day.feedings,
+            cleanings: day.cleanings,
+            refreshes: day.refreshes,
+            hours_seen: day.hours_seen(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Report {
+    /// ISO week, e.g. `2026-W41`.
+    pub week: String,
+    pub pet: String,
+    pub days: Vec<DayReport>,
+    pub feedings: u32,
+    pub cleanings: u32,
+    pub refreshes: u32,
+    /// Host uptime when the report was put together.
+    pub uptime_secs: Option<u64>,
+    /// All of the above as a few lines of text.
+    pub text: String,
+}
+
+fn week_id(week: IsoWeek) -> String {
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+/// The week whose report is due at `now`: this one from its Sunday at
+/// `at` on, and the one before until then.
+fn due_week(now: NaiveDateTime, at: NaiveTime) -> IsoWeek {
+    let week = now.iso_week();
+    let deadline = NaiveDate::from_isoywd_opt(week.year(), week.week(), Weekday::Sun)
+        .expect("every week has a Sunday")
+        .and_time(at);
+    if now >= deadline {
+        week
+    } else {
+        (now.date() - chrono::Duration::days(7)).iso_week()
+    }
+}
+
+/// Puts together the report for `week`.
+pub fn compile(name: &str, tally: &Tally, week: IsoWeek, uptime: Option<Duration>) -> Report {
+    let days: Vec<DayReport> = tally.week(week).into_iter().map(DayReport::from).collect();
+    let mut report = Report {
+        week: week_id(week),
+        pet: name.into(),
+        feedings: days.iter().map(|d| d.feedings).sum(),
+        cleanings: days.iter().map(|d| d.cleanings).sum(),
+        refreshes: days.iter().map(|d| d.refreshes).sum(),
+        days,
+        uptime_secs: uptime.map(|u| u.as_secs()),
+        text: String::new(),
+    };
+    report.text = text(&report, uptime);
+    report
+}
+
+fn text(report: &Report, uptime: Option<Duration>) -> String {
+    let mut lines = vec![format!("{}'s week, {}", report.pet, report.week)];
+    for day in &report.days {
+        let date = day.date.format("%a %d %b");
+        let line = match (day.hunger, day.happiness, day.energy) {
+            (Some(hunger), Some(happiness), Some(energy)) => format!(
+                "{date}  food {hunger:>3}  joy {happiness:>3}  rest {energy:>3}  fed {}x  cleaned {}x",
+                day.feedings, day.cleanings
+            ),
+            _ => format!("{date}  panel off"),
+        };
+        lines.push(line);
+    }
+    let mut totals = format!(
+        "Fed {}x, cleaned {}x, {} refreshes",
+        report.feedings, report.cleanings, report.refreshes
+    );
+    if let Some(uptime) = uptime {
+        totals.push_str(&format!(", up {}", format_uptime(uptime)));
+    }
+    lines.push(totals);
+    lines.join("\n")
+}
+
+fn payload(report: &Report, format: Format) -> serde_json::Value {
+    let block = format!("```\n{}\n```", report.text);
+    match format {
+        Format::Json => json!(report),
+        Format::Discord => json!({ "content": block }),
+        Format::Slack => json!({ "text": block }),
+    }
+}
+
+/// What's kept between runs.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    last_sent: Option<String>,
+    /// Put together but not sent yet.
+    pending: Option<Report>,
+}
+
+impl State {
+    /// Whether the report for `week` is still to be put together. Week IDs
+    /// sort by date, so an earlier week never comes round again after the
+    /// clock is set back.
+    fn due(&self, week: &str) -> bool {
+        self.last_sent.as_deref() < Some(week)
+            && self.pending.as_ref().map(|p| p.week.as_str()) < Some(week)
+    }
+}
+
+pub struct Webhook {
+    url: String,
+    format: Format,
+    at: NaiveTime,
+    state_file: PathBuf,
+    state: State,
+    sending: Option<JoinHandle<Result<()>>>,
+    next_try: Instant,
+}
+
+impl Webhook {
+    /// Returns `None` while no URL is configured.
+    pub fn new(config: &WebhookConfig) -> Option<Self> {
+        let url = config.url.clone()?;
+        let state = match fs::read_to_string(&config.state_file) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|err| {
+                log::warn!("ignoring unreadable {}: {err}", config.state_file.display());
+                State::default()
+            }),
+            Err(_) => State::default(),
+        };
+        Some(Webhook {
+            url,
+            format: config.format,
+            at: config.at,
+            state_file: config.state_file.clone(),
+            state,
+            sending: None,
+            next_try: Instant::now(),
+        })
+    }
+
+    /// Called every cycle: puts the report together once it's due, and
+    /// sends whatever is waiting to go, a send at a time.
+    pub fn poll(
+        &mut self,
+        now: DateTime<FixedOffset>,
+        tally: &Tally,
+        name: &str,
+        uptime: Option<Duration>,
+    ) {
+        if self.sending.as_ref().is_some_and(JoinHandle::is_finished) {
+            let sent = self.sending.take().expect("checked above").join();
+            let report = self.state.pending.as_ref().expect("only sent when pending");
+            match sent {
+                Ok(Ok(())) => {
+                    log::info!("sent the care report for {}", report.week);
+                    self.state.last_sent = Some(report.week.clone());
+                    self.state.pending = None;
+                }
+                Ok(Err(err)) => {
+                    log::warn!("couldn't send the care report, trying again later: {err:#}");
+                    self.next_try = Instant::now() + RETRY_AFTER;
+                }
+                Err(_) => {
+                    log::warn!("the care report sender panicked, trying again later");
+                    self.next_try = Instant::now() + RETRY_AFTER;
+                }
+            }
+        }
+        if self.sending.is_some() {
+            return;
+        }
+
+        self.schedule(now, tally, name, uptime);
+
+        if let Some(report) = &self.state.pending {
+            if Instant::now() < self.next_try {
+                return;
+            }
+            let url = self.url.clone();
+            let body = payload(report, self.format);
+            // Saved as sent by the sender itself, so stopping before the
+            // next cycle doesn't send it again on the next start.
+            let state_file = self.state_file.clone();
+            let sent = State {
+                last_sent: Some(report.week.clone()),
+                pending: None,
+            };
+            self.sending = thread::Builder::new()
+                .name("webhook".into())
+                .spawn(move || {
+                    post(&url, &body)?;
+                    save(&state_file, &sent);
+                    Ok(())
+                })
+                .map_err(|err| log::warn!("couldn't start sending the care report: {err}"))
+                .ok();
+        }
+    }
+
+    /// Puts the report together once it's due, and saves it before
+    /// anything is sent, so it survives a restart.
+    fn schedule(
+        &mut self,
+        now: DateTime<FixedOffset>,
+        tally: &Tally,
+        name: &str,
+        uptime: Option<Duration>,
+    ) {
+        let week = due_week(now.naive_local(), self.at);
+        if self.state.last_sent.is_none() && self.state.pending.is_none() {
+            // Nothing sent yet: the first report is this week's, rather
+            // than one for a week from before the webhook was set up.
+            let before = (now.date_naive() - chrono::Duration::days(7)).iso_week();
+            self.state.last_sent = Some(week_id(before));
+            save(&self.state_file, &self.state);
+        }
+        if !self.state.due(&week_id(week)) {
+            return;
+        }
+        if let Some(stale) = &self.state.pending {
+            log::warn!("giving up on the care report for {}", stale.week);
+        }
+        self.state.pending = Some(compile(name, tally, week, uptime));
+        save(&self.state_file, &self.state);
+    }
+}
+
+fn save(path: &Path, state: &State) {
+    let written = serde_json::to_string(state)
+        .map_err(anyhow::Error::from)
+        .and_then(|text| persist::write_atomic(path, text.as_bytes()));
+    if let Err(err) = written {
+        log::warn!("couldn't save {}: {err:#}", path.display());
+    }
+}
+
+/// POSTs `body` to `url`, trying a second time if the first one fails.
+fn post(url: &str, body: &serde_json::Value) -> Result<()> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(HTTP_TIMEOUT))
+        .user_agent(concat!("inky-gotchi/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .into();
+    let send = || agent.post(url).send_json(body).map(drop);
+    send()
+        .or_else(|err| {
+            log::debug!("webhook send failed, retrying once: {err}");
+            send()
+        })
+        // The URL is left out: webhook URLs carry their own token.
+        .context("posting the report")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::PetEvent;
+    use crate::pet::Stats;
+    use crate::summary::Day;
+
+    fn at(text: &str) -> DateTime<FixedOffset> {
+        text.parse().unwrap()
+    }
+
+    fn stats(level: f32) -> Stats {
+        Stats {
+            hunger: level,
+            happiness: level,
+            energy: level,
+        }
+    }
+
+    /// Monday and Wednesday of 2024-W18 seen, Tuesday not.
+    fn tally() -> Tally {
+        let mut tally = Tally::new(at("2024-04-29T00:00:00+03:00").date_naive());
+        tally.sample(at("2024-04-29T10:00:00+03:00"), stats(80.0), None);
+        tally.count(PetEvent::Fed);
+        tally.count(PetEvent::Fed);
+        tally.count_refresh();
+        tally.sample(at("2024-05-01T10:00:00+03:00"), stats(59.6), None);
+        tally.count(PetEvent::Fed);
+        tally.count(PetEvent::Cleaned);
+        tally.count_refresh();
+        tally.count_refresh();
+        tally
+            .past
+            .push(Day::new(at("2024-04-30T00:00:00+03:00").date_naive()));
+        tally.past.sort_by_key(|day| day.date);
+        tally
+    }
+
+    fn week(text: &str) -> IsoWeek {
+        at(text).iso_week()
+    }
+
+    fn report() -> Report {
+        let uptime = Duration::from_secs(26 * 3600 + 5 * 60);
+        compile(
+            "Mara",
+            &tally(),
+            week("2024-05-01T00:00:00+03:00"),
+            Some(uptime),
+        )
+    }
+
+    #[test]
+    fn the_report_totals_the_week() {
+        let report = report();
+        assert_eq!(report.week, "2024-W18");
+        assert_eq!(report.pet, "Mara");
+        let dates: Vec<_> = report.days.iter().map(|d| d.date.to_string()).collect();
+        assert_eq!(dates, ["2024-04-29", "2024-04-30", "2024-05-01"]);
+        assert_eq!(report.days[1].hunger, None);
+        assert_eq!(report.days[2].hunger, Some(60.0));
+        assert_eq!(
+            (report.feedings, report.cleanings, report.refreshes),
+            (3, 1, 3)
+        );
+        assert_eq!(report.uptime_secs, Some(26 * 3600 + 5 * 60));
+        // Only the week asked for.
+        let next = compile("Mara", &tally(), week("2024-05-06T00:00:00+03:00"), None);
+        assert!(next.days.is_empty());
+    }
+
+    #[test]
+    fn the_text_has_a_line_a_day() {
+        assert_eq!(
+            report().text,
+            "Mara's week, 2024-W18\n\
+             Mon 29 Apr  food  80  joy  80  rest  80  fed 2x  cleaned 0x\n\
+             Tue 30 Apr  panel off\n\
+             Wed 01 May  food  60  joy  60  rest  60  fed 1x  cleaned 1x\n\
+             Fed 3x, cleaned 1x, 3 refreshes, up 1d 2h"
+        );
+    }
+
+    #[test]
+    fn payloads_suit_each_format() {
+        let report = report();
+        let json = payload(&report, Format::Json);
+        assert_eq!(json["week"], "2024-W18");
+        assert_eq!(json["days"].as_array().unwrap().len(), 3);
+        assert_eq!(json["text"], report.text);
+        let block = format!("```\n{}\n```", report.text);
+        assert_eq!(
+            payload(&report, Format::Discord),
+            json!({ "content": block })
+        );
+        assert_eq!(payload(&report, Format::Slack), json!({ "text": block }));
+    }
+
+    #[test]
+    fn the_due_week_turns_over_on_sunday_evening() {
+        let seven = NaiveTime::from_hms_opt(19, 0, 0).unwrap();
+        for (now, due) in [
+            ("2024-05-05T18:59:00+03:00", "2024-W17"),
+            ("2024-05-05T19:00:00+03:00", "2024-W18"),
+            ("2024-05-06T08:00:00+03:00", "2024-W18"),
+            ("2024-05-11T23:59:00+03:00", "2024-W18"),
+            ("2024-05-12T19:00:00+03:00", "2024-W19"),
+            // Into a new year, with ISO weeks of its own.
+            ("2024-12-29T20:00:00+02:00", "2024-W52"),
+            ("2024-12-30T08:00:00+02:00", "2024-W52"),
+            ("2025-01-05T19:30:00+02:00", "2025-W01"),
+        ] {
+            assert_eq!(
+                week_id(due_week(at(now).naive_local(), seven)),
+                due,
+                "{now}"
+            );
+        }
+    }
+
+    fn webhook(dir: &Path, url: &str) -> Webhook {
+        Webhook::new(&WebhookConfig {
+            url: Some(url.into()),
+            state_file: dir.join("webhook.json"),
+            ..WebhookConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn saved(dir: &Path) -> State {
+        serde_json::from_str(&fs::read_to_string(dir.join("webhook.json")).unwrap()).unwrap()
+    }
+
+    fn pending(webhook: &Webhook) -> Option<&str> {
+        webhook.state.pending.as_ref().map(|p| p.week.as_str())
+    }
+
+    #[test]
+    fn each_week_is_sent_once_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let tally = tally();
+        let mut first = webhook(dir.path(), "http://localhost/hook");
+        let schedule = |webhook: &mut Webhook, now: &str| {
+            webhook.schedule(at(now), &tally, "Mara", None);
+        };
+        // Set up midweek, so the week before isn't owed a report.
+        schedule(&mut first, "2024-05-01T12:00:00+03:00");
+        schedule(&mut first, "2024-05-05T18:59:00+03:00");
+        assert_eq!(pending(&first), None);
+        schedule(&mut first, "2024-05-05T19:00:00+03:00");
+        assert_eq!(pending(&first), Some("2024-W18"));
+        // Saved before anything is sent.
+        assert_eq!(
+            saved(dir.path()).pending.map(|p| p.week).as_deref(),
+            Some("2024-W18")
+        );
+
+        // Restarted before it went out: it's still waiting, and only once.
+        let mut second = webhook(dir.path(), "http://localhost/hook");
+        assert_eq!(pending(&second), Some("2024-W18"));
+        second.state.pending.as_mut().unwrap().text = "unchanged".into();
+        schedule(&mut second, "2024-05-05T19:05:00+03:00");
+        assert_eq!(second.state.pending.as_ref().unwrap().text, "unchanged");
+
+        // The sender saves it as sent.
+        let sent = State {
+            last_sent: Some("2024-W18".into()),
+            pending: None,
+        };
+        save(&dir.path().join("webhook.json"), &sent);
+        let mut third = webhook(dir.path(), "http://localhost/hook");
+        for now in [
+            "2024-05-05T19:10:00+03:00",
+            "2024-05-06T09:00:00+03:00",
+            "2024-05-12T18:00:00+03:00",
+        ] {
+            schedule(&mut third, now);
+            assert_eq!(pending(&third), None, "{now}");
+        }
+        // Down all Sunday evening; it goes out on Monday morning instead.
+        schedule(&mut third, "2024-05-13T08:00:00+03:00");
+        assert_eq!(pending(&third), Some("2024-W19"));
+    }
+
+    #[test]
+    fn a_failed_send_is_kept_for_later() {
+        let dir = tempfile::tempdir().unwrap();
+        // A port nobody's listening on.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut webhook = webhook(dir.path(), &format!("http://127.0.0.1:{port}/hook"));
+        let tally = tally();
+        webhook.poll(at("2024-05-01T12:00:00+03:00"), &tally, "Mara", None);
+        webhook.poll(at("2024-05-05T19:00:00+03:00"), &tally, "Mara", None);
+        let started = Instant::now();
+        while !webhook
+            .sending
+            .as_ref()
+            .is_some_and(JoinHandle::is_finished)
+        {
+            assert!(
+                started.elapsed() < HTTP_TIMEOUT * 3,
+                "the send never finished"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+        webhook.poll(at("2024-05-05T19:01:00+03:00"), &tally, "Mara", None);
+        // Not tried again straight away, and not dropped.
+        assert!(webhook.sending.is_none());
+        assert!(webhook.next_try > Instant::now());
+        assert_eq!(pending(&webhook), Some("2024-W18"));
+        let state = saved(dir.path());
+        assert_eq!(state.pending.map(|p| p.week).as_deref(), Some("2024-W18"));
+        assert_eq!(state.last_sent.as_deref(), Some("2024-W17"));
+    }
+}
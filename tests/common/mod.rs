@@ -0,0 +1,122 @@
+//! Running the daemon for the integration tests.
+
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+pub const BINARY: &str = env!("CARGO_BIN_EXE_inky-gotchi");
+/// Longest any step is waited for.
+const PATIENCE: Duration = Duration::from_secs(20);
+
+/// A daemon with all of its files in a directory of its own.
+pub struct Daemon {
+    dir: TempDir,
+    child: Child,
+}
+
+impl Daemon {
+    pub fn start() -> Daemon {
+        Daemon::start_with("", Stdio::null())
+    }
+
+    /// A daemon with `extra` added to the top of its config, and `stdin`
+    /// as its standard input.
+    pub fn start_with(extra: &str, stdin: Stdio) -> Daemon {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).display().to_string();
+        // Every file it writes goes in `dir`, and commands show up within
+        // a second rather than the usual fifteen.
+        let config = format!(
+            "{extra}\ncontrol_socket = {:?}\nstatus_file = {:?}\nlock_file = {:?}\n\
+             [cadence]\nmin_spacing = 1\n[simulator]\noutput = {:?}\n",
+            path("control.sock"),
+            path("status.json"),
+            path("inky-gotchi.lock"),
+            path("frame.png"),
+        );
+        std::fs::write(dir.path().join("config.toml"), config).unwrap();
+        let child = Command::new(BINARY)
+            .arg("--config")
+            .arg(dir.path().join("config.toml"))
+            .arg("--data-dir")
+            .arg(dir.path().join("data"))
+            .args(["--seed", "1"])
+            .stdin(stdin)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let daemon = Daemon { dir, child };
+        wait_for("the control socket", || {
+            UnixStream::connect(daemon.socket()).is_ok().then_some(())
+        });
+        daemon
+    }
+
+    /// The daemon's standard input, when started with it piped.
+    pub fn stdin(&mut self) -> ChildStdin {
+        self.child.stdin.take().expect("stdin is piped")
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    pub fn socket(&self) -> PathBuf {
+        self.path("control.sock")
+    }
+
+    /// Sends one command and returns the reply.
+    pub fn send(&self, line: &str) -> String {
+        let stream = UnixStream::connect(self.socket()).unwrap();
+        stream.set_read_timeout(Some(PATIENCE)).unwrap();
+        writeln!(&stream, "{line}").unwrap();
+        let mut reply = String::new();
+        BufReader::new(&stream).read_line(&mut reply).unwrap();
+        reply.trim().to_string()
+    }
+
+    /// Waits for the status file to show the pet satisfying `check`.
+    pub fn wait_for_pet(&self, what: &str, check: impl Fn(&Value) -> bool) -> Value {
+        wait_for(what, || {
+            let status = read_json(&self.path("status.json"))?;
+            check(&status["pet"]).then(|| status["pet"].clone())
+        })
+    }
+
+    pub fn terminate(&mut self) -> ExitStatus {
+        // SAFETY: signalling our own child, which hasn't been reaped.
+        unsafe { libc::kill(self.child.id() as libc::pid_t, libc::SIGTERM) };
+        wait_for("the daemon to exit", || self.child.try_wait().unwrap())
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+pub fn read_json(path: &Path) -> Option<Value> {
+    serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()
+}
+
+pub fn wait_for<T>(what: &str, mut poll: impl FnMut() -> Option<T>) -> T {
+    let start = Instant::now();
+    loop {
+        if let Some(value) = poll() {
+            return value;
+        }
+        assert!(start.elapsed() < PATIENCE, "gave up waiting for {what}");
+        thread::sleep(Duration::from_millis(50));
+    }
+}
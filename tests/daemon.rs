@@ -0,0 +1,77 @@
+//! Runs the daemon against the simulator and drives it over the control
+//! socket. The same tests run with and without `--features async`, which
+//! swaps the control plane underneath.
+
+use std::os::unix::net::UnixStream;
+use std::process::{Command, Stdio};
+
+mod common;
+
+use common::{read_json, Daemon, BINARY};
+
+#[test]
+fn commands_reach_the_pet() {
+    let mut daemon = Daemon::start();
+    // A new pet waits for its egg; holding a button picks the first one.
+    assert_eq!(daemon.send("hold a"), "ok");
+    daemon.wait_for_pet("the egg", |pet| pet["egg"] == "plain");
+    assert_eq!(daemon.send("feed"), "ok");
+    assert_eq!(daemon.send("play"), "ok");
+    let pet = daemon.wait_for_pet("the feeding", |pet| {
+        pet["lifetime"]["feedings"] == 1 && pet["lifetime"]["games"] == 1
+    });
+    assert_eq!(pet["name"], "Mara");
+
+    assert!(daemon.terminate().success());
+    let saved = read_json(&daemon.path("data/pet.json")).expect("the pet was saved");
+    assert_eq!(saved["lifetime"]["feedings"], 1);
+    assert!(daemon.path("frame.png").exists());
+}
+
+#[test]
+fn bad_commands_are_answered_and_ignored() {
+    let mut daemon = Daemon::start();
+    assert!(daemon.send("dance").starts_with("error"));
+    assert!(daemon.send("press z").starts_with("error"));
+    assert!(daemon.send("show nowhere").starts_with("error"));
+    // Still listening after all that.
+    assert_eq!(daemon.send("hold a"), "ok");
+    daemon.wait_for_pet("the egg", |pet| pet["egg"] == "plain");
+    assert!(daemon.terminate().success());
+}
+
+#[test]
+fn a_second_instance_stays_off_the_panel() {
+    let daemon = Daemon::start();
+    let second = Command::new(BINARY)
+        .arg("--config")
+        .arg(daemon.path("config.toml"))
+        .arg("--data-dir")
+        .arg(daemon.path("data"))
+        .output()
+        .unwrap();
+    // `lock::EXIT_LOCKED`.
+    assert_eq!(second.status.code(), Some(3));
+    let stderr = String::from_utf8_lossy(&second.stderr);
+    assert!(stderr.contains("already running as PID"), "{stderr}");
+    assert!(UnixStream::connect(daemon.socket()).is_ok());
+}
+
+#[test]
+fn once_renders_a_single_frame() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("frame.png");
+    let config = format!("[simulator]\noutput = {:?}\n", output.display().to_string());
+    std::fs::write(dir.path().join("config.toml"), config).unwrap();
+    let status = Command::new(BINARY)
+        .arg("--config")
+        .arg(dir.path().join("config.toml"))
+        .arg("--data-dir")
+        .arg(dir.path().join("data"))
+        .args(["--once", "--at", "2024-05-01T12:00:00Z"])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(std::fs::read(&output).unwrap().starts_with(b"\x89PNG"));
+}
@@ -0,0 +1,114 @@
+//! Drives the settings menu from the input sources, and checks it ends up
+//! saving what the same presses over the control socket do.
+
+#![cfg(any(feature = "keyboard", feature = "evdev"))]
+
+use std::process::Stdio;
+
+mod common;
+
+use common::{wait_for, Daemon};
+
+/// Picks the egg, opens the menu, changes the first item and saves.
+const SEQUENCE: [&str; 6] = [
+    "hold a", "hold c", "hold a", "press a", "press a", "press a",
+];
+
+/// What the menu saves after [`SEQUENCE`] is sent by `send`.
+fn overrides(daemon: &Daemon, mut send: impl FnMut(&Daemon, &str)) -> String {
+    send(daemon, SEQUENCE[0]);
+    daemon.wait_for_pet("the egg", |pet| pet["egg"] == "plain");
+    for command in &SEQUENCE[1..] {
+        send(daemon, command);
+    }
+    wait_for("the overrides", || {
+        std::fs::read_to_string(daemon.path("data/overrides.toml")).ok()
+    })
+}
+
+fn over_the_socket() -> String {
+    let daemon = Daemon::start();
+    overrides(&daemon, |daemon, command| {
+        assert_eq!(daemon.send(command), "ok");
+    })
+}
+
+#[cfg(feature = "keyboard")]
+#[test]
+fn terminal_keys_work_the_menu() {
+    use std::io::Write;
+
+    let mut daemon = Daemon::start_with("input = \"keyboard\"", Stdio::piped());
+    let mut stdin = daemon.stdin();
+    let saved = overrides(&daemon, |_, command| {
+        let key = match command {
+            "hold a" => "F",
+            "hold c" => "C",
+            _ => "f",
+        };
+        stdin.write_all(key.as_bytes()).unwrap();
+    });
+    assert!(!saved.is_empty());
+    assert_eq!(saved, over_the_socket());
+    assert!(daemon.terminate().success());
+}
+
+/// The bytes of an evdev key event `ms` milliseconds in.
+#[cfg(feature = "evdev")]
+fn event(code: u16, value: i32, ms: u64) -> Vec<u8> {
+    // SAFETY: input_event is plain data, all zeroes is valid.
+    let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+    event.time.tv_sec = (ms / 1000) as _;
+    event.time.tv_usec = (ms % 1000 * 1000) as _;
+    (event.type_, event.code, event.value) = (0x01, code, value);
+    // SAFETY: reading the bytes of a plain struct.
+    unsafe {
+        std::slice::from_raw_parts(
+            (&event as *const libc::input_event).cast::<u8>(),
+            std::mem::size_of::<libc::input_event>(),
+        )
+    }
+    .to_vec()
+}
+
+#[cfg(feature = "evdev")]
+#[test]
+fn evdev_keys_work_the_menu() {
+    use std::io::Write;
+
+    const F: u16 = 33;
+    const C: u16 = 46;
+    let dir = tempfile::tempdir().unwrap();
+    let device = dir.path().join("event-kbd");
+    let path = std::ffi::CString::new(device.display().to_string()).unwrap();
+    // SAFETY: `path` is a valid C string.
+    assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o600) }, 0);
+    // Opened both ways, so the daemon's open doesn't wait for a writer
+    // and reads don't see the end while the test is between keys.
+    let mut fifo = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&device)
+        .unwrap();
+    let extra = format!(
+        "input = \"evdev\"\ninput_device = {:?}",
+        device.display().to_string()
+    );
+    let mut daemon = Daemon::start_with(&extra, Stdio::null());
+    // Times a second apart, so holds last long enough.
+    let mut ms = 0;
+    let saved = overrides(&daemon, |_, command| {
+        let (code, held) = match command {
+            "hold a" => (F, 1000),
+            "hold c" => (C, 1000),
+            _ => (F, 50),
+        };
+        let mut bytes = event(code, 1, ms);
+        bytes.extend(event(code, 0, ms + held));
+        ms += 2000;
+        fifo.write_all(&bytes).unwrap();
+    });
+    assert!(!saved.is_empty());
+    assert_eq!(saved, over_the_socket());
+    assert!(daemon.terminate().success());
+}
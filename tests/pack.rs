@@ -0,0 +1,48 @@
+//! `inky-gotchi pack-validate` on the fixture pack and on a broken one.
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+mod common;
+
+use common::BINARY;
+
+fn validate(dir: &Path) -> Output {
+    Command::new(BINARY)
+        .arg("pack-validate")
+        .arg(dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn the_fixture_pack_is_valid() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/pack");
+    let output = validate(&dir);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "blob: 3 of 10 sprites drawn from the pack, the rest built in\n"
+    );
+}
+
+#[test]
+fn a_broken_pack_fails_with_its_problems() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("pack.toml"),
+        "name = \"broken\"\nwidth = 0\nheight = 16\n[sprites]\nidle = \"idle.png\"\n",
+    )
+    .unwrap();
+    let output = validate(dir.path());
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("has 2 problem(s)"), "{stderr}");
+    assert!(stderr.contains("the pet is 0x16"), "{stderr}");
+    assert!(stderr.contains("idle: idle.png: "), "{stderr}");
+}
@@ -0,0 +1,103 @@
+//! Where the data directory ends up, and state files moving into it from
+//! where the daemon used to run, through `inky-gotchi paths`.
+
+use std::collections::BTreeMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+mod common;
+
+use common::BINARY;
+
+/// Runs `paths` in `cwd` with the default config and `$XDG_DATA_HOME` and
+/// `$HOME` set as given, and returns what it lists.
+fn paths(cwd: &Path, xdg: &str, home: &Path, args: &[&str]) -> BTreeMap<String, PathBuf> {
+    let output = Command::new(BINARY)
+        .args(args)
+        .arg("paths")
+        .current_dir(cwd)
+        .env("XDG_DATA_HOME", xdg)
+        .env("HOME", home)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let (key, path) = line.split_once(' ').unwrap();
+            (key.to_string(), PathBuf::from(path.trim()))
+        })
+        .collect()
+}
+
+#[test]
+fn the_data_directory_follows_the_flag_then_xdg_then_home() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (xdg, home) = (tmp.path().join("xdg"), tmp.path().join("home"));
+    let xdg_str = xdg.display().to_string();
+
+    let listed = paths(tmp.path(), &xdg_str, &home, &[]);
+    let dir = xdg.join("inky-gotchi");
+    assert_eq!(listed["data_dir"], dir);
+    assert_eq!(listed["save_file"], dir.join("pet.json"));
+    // Absolute paths stay where they are.
+    assert_eq!(listed["control_socket"], Path::new("/tmp/inky-gotchi.sock"));
+    let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+
+    // An empty $XDG_DATA_HOME counts as unset.
+    let listed = paths(tmp.path(), "", &home, &[]);
+    assert_eq!(listed["data_dir"], home.join(".local/share/inky-gotchi"));
+
+    let flag = tmp.path().join("flag");
+    let listed = paths(
+        tmp.path(),
+        &xdg_str,
+        &home,
+        &["--data-dir", &flag.display().to_string()],
+    );
+    assert_eq!(listed["data_dir"], flag);
+    assert_eq!(listed["journal_file"], flag.join("journal.json"));
+}
+
+#[test]
+fn old_state_files_move_into_the_data_directory() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (old, xdg) = (tmp.path().join("old"), tmp.path().join("xdg"));
+    let dir = xdg.join("inky-gotchi");
+    std::fs::create_dir_all(&old).unwrap();
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(old.join("pet.json"), "old pet").unwrap();
+    std::fs::write(old.join("journal.json"), "old journal").unwrap();
+    // Already in the data directory, so the old one is left alone.
+    std::fs::write(old.join("summary.json"), "old summary").unwrap();
+    std::fs::write(dir.join("summary.json"), "new summary").unwrap();
+
+    paths(&old, &xdg.display().to_string(), tmp.path(), &[]);
+    let read = |path: PathBuf| std::fs::read_to_string(path).ok();
+    assert_eq!(read(dir.join("pet.json")).as_deref(), Some("old pet"));
+    assert_eq!(
+        read(dir.join("journal.json")).as_deref(),
+        Some("old journal")
+    );
+    assert_eq!(read(old.join("pet.json")), None);
+    assert_eq!(read(old.join("journal.json")), None);
+    assert_eq!(
+        read(dir.join("summary.json")).as_deref(),
+        Some("new summary")
+    );
+    assert_eq!(
+        read(old.join("summary.json")).as_deref(),
+        Some("old summary")
+    );
+
+    // Nothing left to move the second time.
+    paths(&old, &xdg.display().to_string(), tmp.path(), &[]);
+    assert_eq!(read(dir.join("pet.json")).as_deref(), Some("old pet"));
+}